@@ -0,0 +1,37 @@
+package com.textextractor
+
+/**
+ * Stable codes identifying why a native call failed, mirroring
+ * `ExtractError::code` in the native library. Codes are never reused.
+ */
+object NativeErrorCode {
+    const val NO_SUCH_PROCESS = 1
+    const val PERMISSION_DENIED = 2
+    const val INVALID_ARGUMENT = 3
+    const val IO_ERROR = 4
+    const val CANCELLED = 5
+    const val TIMEOUT = 6
+    const val UNSUPPORTED_KERNEL = 7
+}
+
+/**
+ * Thrown by the native extractor when a call fails
+ *
+ * [code] is one of the [NativeErrorCode] constants. Subclasses identify
+ * failures callers may want to handle on their own; everything else is
+ * thrown as this class.
+ */
+open class NativeExtractionException(message: String, val code: Int) : RuntimeException(message)
+
+/**
+ * The target process doesn't exist, or exited during the call
+ */
+class ProcessNotFoundException(message: String) :
+    NativeExtractionException(message, NativeErrorCode.NO_SUCH_PROCESS)
+
+/**
+ * Access to the target process was denied: missing root, SELinux or
+ * ptrace restrictions
+ */
+class PermissionDeniedException(message: String) :
+    NativeExtractionException(message, NativeErrorCode.PERMISSION_DENIED)
@@ -0,0 +1,47 @@
+package com.textextractor
+
+/**
+ * Receives the NDJSON lines of [NativeMemoryExtractor.extractStringsToCallback]
+ */
+fun interface ChunkListener {
+    /** Called on the scanning thread with roughly 64 KiB of whole lines */
+    fun onChunk(chunk: String)
+}
+
+/**
+ * Receives the progress of [NativeMemoryExtractor.extractStringsWithProgress]
+ */
+fun interface ProgressListener {
+    /**
+     * Called on the scanning thread at most every 100 ms, and once more when the scan
+     * completes with [bytesScanned] equal to [totalBytes]
+     */
+    fun onProgress(bytesScanned: Long, totalBytes: Long, regionName: String)
+}
+
+/**
+ * Receives the changes [NativeMemoryExtractor.watchAddresses] sees
+ */
+fun interface ChangeListener {
+    /**
+     * Called on the watch's own thread; values are the little-endian bytes read, and
+     * [timeNanos] is when the read was made, in nanoseconds since the Unix epoch
+     */
+    fun onChange(address: Long, oldValue: Long, newValue: Long, timeNanos: Long)
+}
+
+/**
+ * Receives the deltas of [NativeMemoryExtractor.scheduleScan]
+ */
+fun interface DeltaListener {
+    /** Called on the schedule's own thread with the strings new and gone since the run before */
+    fun onDelta(json: String)
+}
+
+/**
+ * Receives the reports of [NativeMemoryExtractor.watchLaunches]
+ */
+fun interface LaunchListener {
+    /** Called with each scan of a launched process, from a thread per launch */
+    fun onScan(pid: Int, json: String)
+}
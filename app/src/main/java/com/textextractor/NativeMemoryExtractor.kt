@@ -1,16 +1,26 @@
 package com.textextractor
 
 import timber.log.Timber
+import java.nio.ByteBuffer
 
 /**
  * JNI wrapper for native memory extraction functionality
  *
  * This class provides access to Rust-based native code for advanced
  * memory extraction on rooted devices. Falls back gracefully if native
- * library is not available or device is not rooted.
+ * library is not available. Failures of the native calls themselves are
+ * thrown as [NativeExtractionException], whose [NativeExtractionException.code]
+ * says why, so callers can tell a failed call from its result.
  */
 object NativeMemoryExtractor {
 
+    /** Region presets accepted by [extractStrings] */
+    const val REGIONS_ALL = 0
+    const val REGIONS_HEAP = 1
+    const val REGIONS_STACK = 2
+    const val REGIONS_ANONYMOUS = 3
+    const val REGIONS_JIT_CACHE = 4
+
     private var nativeLibraryLoaded = false
 
     init {
@@ -36,12 +46,7 @@ object NativeMemoryExtractor {
      */
     fun checkRootAccess(): String {
         return if (nativeLibraryLoaded) {
-            try {
-                nativeCheckRootAccess()
-            } catch (e: Exception) {
-                Timber.e(e, "Error checking root access")
-                "Error checking root access: ${e.message}"
-            }
+            nativeCheckRootAccess()
         } else {
             "Native library not loaded"
         }
@@ -52,15 +57,11 @@ object NativeMemoryExtractor {
      *
      * @param pid Process ID to read memory from
      * @return String containing memory information
+     * @throws NativeExtractionException if the process can't be read
      */
     fun readProcessMemory(pid: Int): String {
         return if (nativeLibraryLoaded) {
-            try {
-                nativeReadProcessMemory(pid)
-            } catch (e: Exception) {
-                Timber.e(e, "Error reading process memory")
-                "Error: ${e.message}"
-            }
+            nativeReadProcessMemory(pid)
         } else {
             "Native library not loaded - use Accessibility Service instead"
         }
@@ -71,26 +72,117 @@ object NativeMemoryExtractor {
      *
      * @param pid Process ID to extract strings from
      * @param minLength Minimum string length to extract
+     * @param regionPreset One of the REGIONS_* constants to limit which regions are scanned
      * @return String containing extracted strings
+     * @throws NativeExtractionException if the process can't be scanned
      */
-    fun extractStrings(pid: Int, minLength: Int = 4): String {
+    fun extractStrings(pid: Int, minLength: Int = 4, regionPreset: Int = REGIONS_ALL): String {
         return if (nativeLibraryLoaded) {
-            try {
-                nativeExtractStrings(pid, minLength)
-            } catch (e: Exception) {
-                Timber.e(e, "Error extracting strings")
-                "Error: ${e.message}"
-            }
+            nativeExtractStrings(pid, minLength, regionPreset)
         } else {
             "Native library not loaded - use Accessibility Service instead"
         }
     }
 
+    /**
+     * Extract printable strings from process memory as individual elements (requires root)
+     *
+     * @param pid Process ID to extract strings from
+     * @param minLength Minimum string length to extract
+     * @param regionPreset One of the REGIONS_* constants to limit which regions are scanned
+     * @return The extracted strings, or an empty list if the native library isn't loaded
+     * @throws NativeExtractionException if the process can't be scanned
+     */
+    fun extractStringArray(pid: Int, minLength: Int = 4, regionPreset: Int = REGIONS_ALL): List<String> {
+        return if (nativeLibraryLoaded) {
+            nativeExtractStringArray(pid, minLength, regionPreset).asList()
+        } else {
+            Timber.w("Native library not loaded - use Accessibility Service instead")
+            emptyList()
+        }
+    }
+
+    /**
+     * Read raw bytes from process memory (requires root)
+     *
+     * @param pid Process ID to read from
+     * @param address Virtual address of the first byte
+     * @param length Number of bytes to read
+     * @return The bytes read, shorter than [length] if the range runs into unreadable memory,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if nothing could be read
+     */
+    fun readMemoryRange(pid: Int, address: Long, length: Int): ByteArray? {
+        return if (nativeLibraryLoaded) {
+            nativeReadMemoryRange(pid, address, length)
+        } else {
+            Timber.w("Native library not loaded - use Accessibility Service instead")
+            null
+        }
+    }
+
+    /**
+     * Read process memory into a direct buffer without an intermediate copy (requires root)
+     *
+     * Intended for polling the same range repeatedly with one reused buffer.
+     *
+     * @param pid Process ID to read from
+     * @param address Virtual address of the first byte
+     * @param buffer Direct buffer filled from index 0 up to its capacity
+     * @return Number of bytes read, or -1 if the native library isn't loaded
+     * @throws NativeExtractionException if nothing could be read
+     */
+    fun readMemoryInto(pid: Int, address: Long, buffer: ByteBuffer): Int {
+        return if (nativeLibraryLoaded) {
+            nativeReadMemoryInto(pid, address, buffer)
+        } else {
+            Timber.w("Native library not loaded - use Accessibility Service instead")
+            -1
+        }
+    }
+
+    /**
+     * Find the processes of an app, including `<package>:<name>` secondary processes
+     *
+     * @param packageName Package name of the app
+     * @return Matching PIDs with the main process first, or an empty array if none are
+     * running or the native library isn't loaded
+     * @throws NativeExtractionException if the processes can't be listed
+     */
+    fun findPidByPackage(packageName: String): IntArray {
+        return if (nativeLibraryLoaded) {
+            nativeFindPidByPackage(packageName)
+        } else {
+            Timber.w("Native library not loaded - use getProcessIdByPackage instead")
+            IntArray(0)
+        }
+    }
+
+    /**
+     * List running app processes with their pid, uid, name, RSS and start time
+     *
+     * @return JSON array of processes, or null if the native library isn't loaded
+     * @throws NativeExtractionException if the processes can't be listed
+     */
+    fun listAppProcesses(): String? {
+        return if (nativeLibraryLoaded) {
+            nativeListAppProcesses()
+        } else {
+            Timber.w("Native library not loaded - cannot list processes")
+            null
+        }
+    }
+
     /**
      * Get process ID by package name
-     * Note: This is a helper function that uses standard Android APIs
+     * Note: Uses the native lookup when available, otherwise shells out to `pidof`
+     *
+     * @throws NativeExtractionException if the native lookup fails
      */
     fun getProcessIdByPackage(packageName: String): Int? {
+        if (nativeLibraryLoaded) {
+            return findPidByPackage(packageName).firstOrNull()
+        }
         return try {
             val process = Runtime.getRuntime().exec("pidof $packageName")
             val reader = process.inputStream.bufferedReader()
@@ -103,13 +195,2244 @@ object NativeMemoryExtractor {
         }
     }
 
-    // Native methods (implemented in Rust)
-    @JvmStatic
-    private external fun nativeCheckRootAccess(): String
+    /**
+     * Open a session as [openSession] does, with the reader backend and default output
+     * format the options give (requires root)
+     *
+     * @param pid Process ID to open
+     * @param optionsJson Scan options as JSON; only `readerStrategy` and `outputFormat` are used
+     * @return The session's handle, never 0, or 0 if the native library isn't loaded
+     * @throws NativeExtractionException if the process can't be opened or the options are invalid
+     */
+    fun openSessionWithOptions(pid: Int, optionsJson: String): Long {
+        return if (nativeLibraryLoaded) {
+            nativeOpenSessionWithOptions(pid, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot open a session")
+            0L
+        }
+    }
 
-    @JvmStatic
-    private external fun nativeReadProcessMemory(pid: Int): String
+    /**
+     * List the memory regions of a process from its maps
+     *
+     * @param pid Process ID whose maps are read
+     * @return JSON array of regions, or null if the native library isn't loaded
+     * @throws NativeExtractionException if the maps can't be read
+     */
+    fun getMemoryRegions(pid: Int): String? {
+        return if (nativeLibraryLoaded) {
+            nativeGetMemoryRegions(pid)
+        } else {
+            Timber.w("Native library not loaded - cannot read memory regions")
+            null
+        }
+    }
+
+    /**
+     * Extract strings from process memory as the options ask: encodings, lengths, regions,
+     * filters and output format (requires root)
+     *
+     * @param pid Process ID to extract strings from
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @return The report, as text unless the options ask for JSON,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if the process can't be scanned
+     */
+    fun extractStringsWithOptions(pid: Int, optionsJson: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeExtractStringsWithOptions(pid, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot extract strings")
+            null
+        }
+    }
+
+    /**
+     * Search every readable region of a process for a regex (requires root)
+     *
+     * @param pid Process ID to search
+     * @param pattern Regular expression, in the syntax of the Rust regex crate
+     * @param flags `java.util.regex.Pattern` flags; CASE_INSENSITIVE, COMMENTS, MULTILINE and DOTALL are honored
+     * @param contextBytes Bytes either side of each match to hexdump, or 0 for none
+     * @return The report as text, or null if the native library isn't loaded
+     * @throws NativeExtractionException if the pattern is invalid or the process can't be searched
+     */
+    fun searchRegex(pid: Int, pattern: String, flags: Int, contextBytes: Int): String? {
+        return if (nativeLibraryLoaded) {
+            nativeSearchRegex(pid, pattern, flags, contextBytes)
+        } else {
+            Timber.w("Native library not loaded - cannot search memory")
+            null
+        }
+    }
+
+    /**
+     * Search every readable region of a process for any of several keywords at
+     * once (requires root)
+     *
+     * @param pid Process ID to search
+     * @param keywords Literal strings to look for
+     * @param caseInsensitive Match ASCII letters regardless of case
+     * @param contextBytes Bytes either side of each match to hexdump, or 0 for none
+     * @return The report as text, or null if the native library isn't loaded
+     * @throws NativeExtractionException if the process can't be searched
+     */
+    fun searchKeywords(pid: Int, keywords: Array<String>, caseInsensitive: Boolean, contextBytes: Int): String? {
+        return if (nativeLibraryLoaded) {
+            nativeSearchKeywords(pid, keywords, caseInsensitive, contextBytes)
+        } else {
+            Timber.w("Native library not loaded - cannot search memory")
+            null
+        }
+    }
+
+    /**
+     * Extract strings from process memory, writing each to [fd] as a line of NDJSON
+     * as soon as it is found (requires root)
+     *
+     * @param pid Process ID to extract strings from
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @param fd Descriptor the lines are written to; it is not closed
+     * @return False if the descriptor is invalid, the scan or a write failed, or the native
+     * library isn't loaded
+     * @throws NativeExtractionException if the scan stopped early, after writing the failure as an `error` line
+     */
+    fun extractStringsToFd(pid: Int, optionsJson: String, fd: Int): Boolean {
+        return if (nativeLibraryLoaded) {
+            nativeExtractStringsToFd(pid, optionsJson, fd)
+        } else {
+            Timber.w("Native library not loaded - cannot extract strings")
+            false
+        }
+    }
+
+    /**
+     * Extract strings from process memory, passing the NDJSON lines to
+     * [callback] about 64 KiB at a time (requires root)
+     *
+     * @param pid Process ID to extract strings from
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @param callback Receives whole lines on the scanning thread
+     * @return False on failure, as for [extractStringsToFd], or if the native library isn't loaded
+     * @throws NativeExtractionException if the scan stopped early, or rethrown from [callback] if it threw
+     */
+    fun extractStringsToCallback(pid: Int, optionsJson: String, callback: ChunkListener): Boolean {
+        return if (nativeLibraryLoaded) {
+            nativeExtractStringsToCallback(pid, optionsJson, callback)
+        } else {
+            Timber.w("Native library not loaded - cannot extract strings")
+            false
+        }
+    }
+
+    /**
+     * Open a session on a process, keeping its reader and maps open for the
+     * `session*` calls until [closeSession] (requires root)
+     *
+     * @param pid Process ID to open
+     * @return The session's handle, never 0, or 0 if the native library isn't loaded
+     * @throws NativeExtractionException if the process can't be opened
+     */
+    fun openSession(pid: Int): Long {
+        return if (nativeLibraryLoaded) {
+            nativeOpenSession(pid)
+        } else {
+            Timber.w("Native library not loaded - cannot open a session")
+            0L
+        }
+    }
+
+    /**
+     * Close a session, releasing its reader and maps
+     *
+     * @param session Handle returned by [openSession]
+     * @return False if the handle is unknown or the native library isn't loaded
+     */
+    fun closeSession(session: Long): Boolean {
+        return if (nativeLibraryLoaded) {
+            nativeCloseSession(session)
+        } else {
+            Timber.w("Native library not loaded - cannot close a session")
+            false
+        }
+    }
+
+    /**
+     * Re-read a session's maps, to pick up mappings made since it was opened
+     *
+     * @param session Handle returned by [openSession]
+     * @return False if the handle is unknown or the native library isn't loaded
+     * @throws NativeExtractionException if the maps can't be read
+     */
+    fun sessionRefreshRegions(session: Long): Boolean {
+        return if (nativeLibraryLoaded) {
+            nativeSessionRefreshRegions(session)
+        } else {
+            Timber.w("Native library not loaded - cannot refresh a session")
+            false
+        }
+    }
+
+    /**
+     * List the memory regions a session holds
+     *
+     * @param session Handle returned by [openSession]
+     * @return JSON array of regions, or null if the native library isn't loaded
+     * @throws NativeExtractionException if the session is unknown
+     */
+    fun sessionGetMemoryRegions(session: Long): String? {
+        return if (nativeLibraryLoaded) {
+            nativeSessionGetMemoryRegions(session)
+        } else {
+            Timber.w("Native library not loaded - cannot read memory regions")
+            null
+        }
+    }
+
+    /**
+     * Read every readable region of a session's process, as [readProcessMemory] does
+     *
+     * @param session Handle returned by [openSession]
+     * @return The report in the session's output format, or null if the native library isn't loaded
+     * @throws NativeExtractionException if the session is unknown or can't be scanned
+     */
+    fun sessionReadProcessMemory(session: Long): String? {
+        return if (nativeLibraryLoaded) {
+            nativeSessionReadProcessMemory(session)
+        } else {
+            Timber.w("Native library not loaded - cannot read process memory")
+            null
+        }
+    }
+
+    /**
+     * Extract strings on a session, as [extractStringsWithOptions] does
+     *
+     * @param session Handle returned by [openSession]
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @return The report, in the options' output format or else the session's,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if the session is unknown or can't be scanned
+     */
+    fun sessionExtractStrings(session: Long, optionsJson: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeSessionExtractStrings(session, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot extract strings")
+            null
+        }
+    }
+
+    /**
+     * Search a session's regions for a regex, as [searchRegex] does
+     *
+     * @param session Handle returned by [openSession]
+     * @param pattern Regular expression, in the syntax of the Rust regex crate
+     * @param flags `java.util.regex.Pattern` flags; CASE_INSENSITIVE, COMMENTS, MULTILINE and DOTALL are honored
+     * @param contextBytes Bytes either side of each match to hexdump, or 0 for none
+     * @return The report in the session's output format, or null if the native library isn't loaded
+     * @throws NativeExtractionException if the session is unknown or the pattern is invalid
+     */
+    fun sessionSearchRegex(session: Long, pattern: String, flags: Int, contextBytes: Int): String? {
+        return if (nativeLibraryLoaded) {
+            nativeSessionSearchRegex(session, pattern, flags, contextBytes)
+        } else {
+            Timber.w("Native library not loaded - cannot search memory")
+            null
+        }
+    }
+
+    /**
+     * Search a session's regions for keywords, as [searchKeywords] does
+     *
+     * @param session Handle returned by [openSession]
+     * @param keywords Literal strings to look for
+     * @param caseInsensitive Match ASCII letters regardless of case
+     * @param contextBytes Bytes either side of each match to hexdump, or 0 for none
+     * @return The report in the session's output format, or null if the native library isn't loaded
+     * @throws NativeExtractionException if the session is unknown or can't be scanned
+     */
+    fun sessionSearchKeywords(
+        session: Long,
+        keywords: Array<String>,
+        caseInsensitive: Boolean,
+        contextBytes: Int,
+    ): String? {
+        return if (nativeLibraryLoaded) {
+            nativeSessionSearchKeywords(session, keywords, caseInsensitive, contextBytes)
+        } else {
+            Timber.w("Native library not loaded - cannot search memory")
+            null
+        }
+    }
+
+    /**
+     * Read raw bytes through a session, as [readMemoryRange] does
+     *
+     * @param session Handle returned by [openSession]
+     * @param address Virtual address of the first byte
+     * @param length Number of bytes to read
+     * @return The bytes read, shorter than [length] if the range runs into unreadable memory,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if the session is unknown or nothing could be read
+     */
+    fun sessionReadMemoryRange(session: Long, address: Long, length: Int): ByteArray? {
+        return if (nativeLibraryLoaded) {
+            nativeSessionReadMemoryRange(session, address, length)
+        } else {
+            Timber.w("Native library not loaded - cannot read memory")
+            null
+        }
+    }
+
+    /**
+     * Read through a session into a direct buffer, as [readMemoryInto] does
+     *
+     * @param session Handle returned by [openSession]
+     * @param address Virtual address of the first byte
+     * @param buffer Direct buffer filled from index 0 up to its capacity
+     * @return Number of bytes read, or -1 if the native library isn't loaded
+     * @throws NativeExtractionException if the session is unknown or nothing could be read
+     */
+    fun sessionReadMemoryInto(session: Long, address: Long, buffer: ByteBuffer): Int {
+        return if (nativeLibraryLoaded) {
+            nativeSessionReadMemoryInto(session, address, buffer)
+        } else {
+            Timber.w("Native library not loaded - cannot read memory")
+            -1
+        }
+    }
+
+    /**
+     * Extract strings as [extractStringsWithOptions] does, reporting progress to
+     * [listener] on the scanning thread (requires root)
+     *
+     * @param pid Process ID to extract strings from
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @param listener Called at most every 100 ms, and once the scan completes
+     * @return The report, as text unless the options ask for JSON,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if the process can't be scanned or [listener] threw
+     */
+    fun extractStringsWithProgress(pid: Int, optionsJson: String, listener: ProgressListener): String? {
+        return if (nativeLibraryLoaded) {
+            nativeExtractStringsWithProgress(pid, optionsJson, listener)
+        } else {
+            Timber.w("Native library not loaded - cannot extract strings")
+            null
+        }
+    }
+
+    /**
+     * Extract strings on a session as [extractStringsWithProgress] does
+     *
+     * @param session Handle returned by [openSession]
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @param listener Called at most every 100 ms, and once the scan completes
+     * @return The report, in the options' output format or else the session's,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if the session is unknown, can't be scanned or [listener] threw
+     */
+    fun sessionExtractStringsWithProgress(session: Long, optionsJson: String, listener: ProgressListener): String? {
+        return if (nativeLibraryLoaded) {
+            nativeSessionExtractStringsWithProgress(session, optionsJson, listener)
+        } else {
+            Timber.w("Native library not loaded - cannot extract strings")
+            null
+        }
+    }
+
+    /**
+     * Stop the scan running on a session, from another thread; it returns the
+     * results found so far, marked as truncated
+     *
+     * @param session Handle returned by [openSession]
+     * @return False if the handle is unknown or the native library isn't loaded
+     */
+    fun cancelScan(session: Long): Boolean {
+        return if (nativeLibraryLoaded) {
+            nativeCancelScan(session)
+        } else {
+            Timber.w("Native library not loaded - cannot cancel a scan")
+            false
+        }
+    }
+
+    /**
+     * Extract strings from every process of an app into one report (requires root)
+     *
+     * @param packageName Package name of the app
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @return The report, as text unless the options ask for JSON,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if no process of the app is running or none could be scanned
+     */
+    fun extractStringsByPackage(packageName: String, optionsJson: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeExtractStringsByPackage(packageName, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot extract strings")
+            null
+        }
+    }
+
+    /**
+     * Extract strings from every process running as a UID, isolated and
+     * sandboxed ones included, into one report (requires root)
+     *
+     * @param uid UID whose processes are scanned
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @return The report, as text unless the options ask for JSON,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if no process runs as [uid] or none could be scanned
+     */
+    fun extractStringsByUid(uid: Int, optionsJson: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeExtractStringsByUid(uid, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot extract strings")
+            null
+        }
+    }
+
+    /**
+     * Extract strings from several processes in one call, one report per process
+     * (requires root)
+     *
+     * @param pids Process IDs to scan
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @return The report of each process, as text unless the options ask for JSON,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException only for invalid arguments, such as an empty PID array
+     */
+    fun extractStringsBatch(pids: IntArray, optionsJson: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeExtractStringsBatch(pids, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot extract strings")
+            null
+        }
+    }
+
+    /**
+     * Search several processes for a regex in one call (requires root)
+     *
+     * @param pids Process IDs to search
+     * @param pattern Regular expression, in the syntax of the Rust regex crate
+     * @param flags `java.util.regex.Pattern` flags; CASE_INSENSITIVE, COMMENTS, MULTILINE and DOTALL are honored
+     * @param contextBytes Bytes either side of each match to hexdump, or 0 for none
+     * @return The matches of each process as text, or null if the native library isn't loaded
+     * @throws NativeExtractionException only for invalid arguments, such as an empty PID array or pattern
+     */
+    fun searchRegexBatch(pids: IntArray, pattern: String, flags: Int, contextBytes: Int): String? {
+        return if (nativeLibraryLoaded) {
+            nativeSearchRegexBatch(pids, pattern, flags, contextBytes)
+        } else {
+            Timber.w("Native library not loaded - cannot search memory")
+            null
+        }
+    }
+
+    /**
+     * Search several processes for keywords in one call (requires root)
+     *
+     * @param pids Process IDs to search
+     * @param keywords Literal strings to look for
+     * @param caseInsensitive Match ASCII letters regardless of case
+     * @param contextBytes Bytes either side of each match to hexdump, or 0 for none
+     * @return The hits of each process as text, or null if the native library isn't loaded
+     * @throws NativeExtractionException only for invalid arguments, such as an empty PID array
+     */
+    fun searchKeywordsBatch(
+        pids: IntArray,
+        keywords: Array<String>,
+        caseInsensitive: Boolean,
+        contextBytes: Int,
+    ): String? {
+        return if (nativeLibraryLoaded) {
+            nativeSearchKeywordsBatch(pids, keywords, caseInsensitive, contextBytes)
+        } else {
+            Timber.w("Native library not loaded - cannot search memory")
+            null
+        }
+    }
+
+    /**
+     * Extract strings from the stacks of a process's threads, each listed with
+     * its stack (requires root)
+     *
+     * @param pid Process ID whose stacks are scanned
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @return The report, as text unless the options ask for JSON,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if the process can't be scanned
+     */
+    fun extractThreadStacks(pid: Int, optionsJson: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeExtractThreadStacks(pid, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot extract strings")
+            null
+        }
+    }
+
+    /**
+     * Write every readable region of a process to its own file, with a
+     * `manifest.json` of the regions and files (requires root)
+     *
+     * @param pid Process ID to dump
+     * @param outputDir Directory the files are written to, created if missing
+     * @return The report as text, or null if the native library isn't loaded
+     * @throws NativeExtractionException if the process can't be read or [outputDir] can't be written
+     */
+    fun dumpProcess(pid: Int, outputDir: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeDumpProcess(pid, outputDir)
+        } else {
+            Timber.w("Native library not loaded - cannot dump memory")
+            null
+        }
+    }
+
+    /**
+     * Write an ELF core file of a process, loadable in gdb or lldb, with every
+     * thread stopped while it is written (requires root)
+     *
+     * @param pid Process ID to dump
+     * @param outputPath File the core is written to
+     * @return The report as text, or null if the native library isn't loaded
+     * @throws NativeExtractionException if the process can't be stopped or read, or [outputPath] can't be written
+     */
+    fun dumpCore(pid: Int, outputPath: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeDumpCore(pid, outputPath)
+        } else {
+            Timber.w("Native library not loaded - cannot dump memory")
+            null
+        }
+    }
+
+    /**
+     * Write a minidump of a process, with every thread stopped while it is taken
+     * (requires root)
+     *
+     * @param pid Process ID to dump
+     * @param outputPath File the minidump is written to
+     * @param optionsJson Minidump options as JSON, which may select regions to save in full
+     * @return The report as text, or null if the native library isn't loaded
+     * @throws NativeExtractionException if the process can't be stopped or read, or [outputPath] can't be written
+     */
+    fun dumpMinidump(pid: Int, outputPath: String, optionsJson: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeDumpMinidump(pid, outputPath, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot dump memory")
+            null
+        }
+    }
+
+    /**
+     * Open a session on a saved dump instead of a live process, for the
+     * `session*` calls to analyze offline
+     *
+     * @param path A [dumpProcess] directory, a directory of region files, an ELF core or an HPROF heap dump
+     * @return The session's handle, never 0, or 0 if the native library isn't loaded
+     * @throws NativeExtractionException if the dump can't be loaded
+     */
+    fun openDumpSession(path: String): Long {
+        return if (nativeLibraryLoaded) {
+            nativeOpenDumpSession(path)
+        } else {
+            Timber.w("Native library not loaded - cannot open a dump")
+            0L
+        }
+    }
+
+    /**
+     * Write the ART heap of an app to an HPROF file, for Android Studio or MAT
+     * (requires root)
+     *
+     * @param pid Process ID to dump
+     * @param outputPath File the heap dump is written to
+     * @return The report as text, or null if the native library isn't loaded
+     * @throws NativeExtractionException if the process has no readable ART heap or [outputPath] can't be written
+     */
+    fun dumpJavaHeap(pid: Int, outputPath: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeDumpJavaHeap(pid, outputPath)
+        } else {
+            Timber.w("Native library not loaded - cannot dump the heap")
+            null
+        }
+    }
+
+    /**
+     * Extract the `java.lang.String` objects of an app's ART heap (requires root)
+     *
+     * @param pid Process ID to scan
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @return The report, as text unless the options ask for JSON,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if the process has no ART heap among the regions or can't be scanned
+     */
+    fun extractJavaStrings(pid: Int, optionsJson: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeExtractJavaStrings(pid, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot extract strings")
+            null
+        }
+    }
+
+    /**
+     * Extract Java strings on a session, as [extractJavaStrings] does
+     *
+     * @param session Handle returned by [openSession]
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @return The report, in the options' output format or else the session's,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if the session is unknown, has no ART heap or can't be scanned
+     */
+    fun sessionExtractJavaStrings(session: Long, optionsJson: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeSessionExtractJavaStrings(session, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot extract strings")
+            null
+        }
+    }
+
+    /**
+     * List the classes loaded in an app's runtime, with the dex file of each
+     * (requires root)
+     *
+     * @param pid Process ID whose classes are listed
+     * @return The report as text, or null if the native library isn't loaded
+     * @throws NativeExtractionException if the process has no ART heap or can't be read
+     */
+    fun listJavaClasses(pid: Int): String? {
+        return if (nativeLibraryLoaded) {
+            nativeListJavaClasses(pid)
+        } else {
+            Timber.w("Native library not loaded - cannot list classes")
+            null
+        }
+    }
+
+    /**
+     * Write each DEX file found in the memory of a process to its own file
+     * (requires root)
+     *
+     * @param pid Process ID to search
+     * @param outputDir Directory the files are written to, created if missing
+     * @return The report as text, or null if the native library isn't loaded
+     * @throws NativeExtractionException if the process can't be read or [outputDir] can't be written
+     */
+    fun dumpDexFiles(pid: Int, outputDir: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeDumpDexFiles(pid, outputDir)
+        } else {
+            Timber.w("Native library not loaded - cannot dump DEX files")
+            null
+        }
+    }
+
+    /**
+     * Rebuild a shared library from the memory of a process as an ELF file
+     * (requires root)
+     *
+     * @param pid Process ID the library is mapped in
+     * @param library Path or file name of the library
+     * @param outputPath File the library is written to
+     * @return The report as text, or null if the native library isn't loaded
+     * @throws NativeExtractionException if the library isn't mapped or [outputPath] can't be written
+     */
+    fun dumpLibrary(pid: Int, library: String, outputPath: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeDumpLibrary(pid, library, outputPath)
+        } else {
+            Timber.w("Native library not loaded - cannot dump a library")
+            null
+        }
+    }
+
+    /**
+     * Find the executable regions of a process that no file on disk backs
+     *
+     * @param pid Process ID to check
+     * @return The report as text, or null if the native library isn't loaded
+     * @throws NativeExtractionException if the process can't be read
+     */
+    fun checkCodeIntegrity(pid: Int): String? {
+        return if (nativeLibraryLoaded) {
+            nativeCheckCodeIntegrity(pid)
+        } else {
+            Timber.w("Native library not loaded - cannot check code")
+            null
+        }
+    }
+
+    /**
+     * Look for signs of Frida, Xposed and other instrumentation in a process
+     *
+     * @param pid Process ID to check
+     * @return The report as text, or null if the native library isn't loaded
+     * @throws NativeExtractionException if the process can't be read
+     */
+    fun detectInstrumentation(pid: Int): String? {
+        return if (nativeLibraryLoaded) {
+            nativeDetectInstrumentation(pid)
+        } else {
+            Timber.w("Native library not loaded - cannot detect instrumentation")
+            null
+        }
+    }
+
+    /**
+     * Compare the code mapped from libraries in a process with the files on disk
+     *
+     * @param pid Process ID to check
+     * @return The report as text, or null if the native library isn't loaded
+     * @throws NativeExtractionException if the process can't be read
+     */
+    fun verifyLibraries(pid: Int): String? {
+        return if (nativeLibraryLoaded) {
+            nativeVerifyLibraries(pid)
+        } else {
+            Timber.w("Native library not loaded - cannot verify libraries")
+            null
+        }
+    }
+
+    /**
+     * List the memfd and ashmem objects a process maps or holds open
+     *
+     * @param pid Process ID whose objects are listed
+     * @return The report as text, or null if the native library isn't loaded
+     * @throws NativeExtractionException if the process can't be read
+     */
+    fun listSharedMemory(pid: Int): String? {
+        return if (nativeLibraryLoaded) {
+            nativeListSharedMemory(pid)
+        } else {
+            Timber.w("Native library not loaded - cannot list shared memory")
+            null
+        }
+    }
+
+    /**
+     * Write the memfd and ashmem objects of a process whose name matches a pattern
+     * (requires root)
+     *
+     * @param pid Process ID whose objects are dumped
+     * @param name Wildcard pattern the object names are matched against
+     * @param outputDir Directory the files are written to, created if missing
+     * @return The report as text, or null if the native library isn't loaded
+     * @throws NativeExtractionException if no object matches or [outputDir] can't be written
+     */
+    fun dumpSharedMemory(pid: Int, name: String, outputDir: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeDumpSharedMemory(pid, name, outputDir)
+        } else {
+            Timber.w("Native library not loaded - cannot dump shared memory")
+            null
+        }
+    }
+
+    /**
+     * Identify the packer or protector a process uses (requires root)
+     *
+     * @param pid Process ID to check
+     * @param signaturesPath JSON file of signatures added to the built-in ones, or empty for none
+     * @return The report as text, or null if the native library isn't loaded
+     * @throws NativeExtractionException if the signatures can't be read or the process can't be scanned
+     */
+    fun detectPacker(pid: Int, signaturesPath: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeDetectPacker(pid, signaturesPath)
+        } else {
+            Timber.w("Native library not loaded - cannot detect packers")
+            null
+        }
+    }
+
+    /**
+     * Write the regions of ART's JIT code cache to files, as [dumpProcess] does
+     * (requires root)
+     *
+     * @param pid Process ID to dump
+     * @param outputDir Directory the files are written to, created if missing
+     * @return The report as text, or null if the native library isn't loaded
+     * @throws NativeExtractionException if the process can't be read or [outputDir] can't be written
+     */
+    fun dumpJitCache(pid: Int, outputDir: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeDumpJitCache(pid, outputDir)
+        } else {
+            Timber.w("Native library not loaded - cannot dump memory")
+            null
+        }
+    }
+
+    /**
+     * List the memory regions of a process with their RSS, PSS and swap usage
+     *
+     * @param pid Process ID whose smaps are read
+     * @return JSON array of regions, or null if the native library isn't loaded
+     * @throws NativeExtractionException if the smaps can't be read
+     */
+    fun getMemoryUsage(pid: Int): String? {
+        return if (nativeLibraryLoaded) {
+            nativeGetMemoryUsage(pid)
+        } else {
+            Timber.w("Native library not loaded - cannot read memory usage")
+            null
+        }
+    }
+
+    /**
+     * Re-read a session's maps from smaps, with the usage of each region
+     *
+     * @param session Handle returned by [openSession]
+     * @return False if the handle is unknown or the native library isn't loaded
+     * @throws NativeExtractionException if the smaps can't be read
+     */
+    fun sessionRefreshUsage(session: Long): Boolean {
+        return if (nativeLibraryLoaded) {
+            nativeSessionRefreshUsage(session)
+        } else {
+            Timber.w("Native library not loaded - cannot refresh a session")
+            false
+        }
+    }
+
+    /**
+     * Find the runs of high-entropy bytes in process memory, candidates for keys
+     * and other secrets (requires root)
+     *
+     * @param pid Process ID to scan
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @return The report, as text unless the options ask for JSON,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if the process can't be scanned
+     */
+    fun scanEntropy(pid: Int, optionsJson: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeScanEntropy(pid, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot scan memory")
+            null
+        }
+    }
+
+    /**
+     * Scan a session for high-entropy runs, as [scanEntropy] does
+     *
+     * @param session Handle returned by [openSession]
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @return The report, in the options' output format or else the session's,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if the session is unknown or can't be scanned
+     */
+    fun sessionScanEntropy(session: Long, optionsJson: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeSessionScanEntropy(session, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot scan memory")
+            null
+        }
+    }
+
+    /**
+     * Run the built-in secret detectors over every readable region of a process
+     * (requires root)
+     *
+     * @param pid Process ID to scan
+     * @return The report as text, or null if the native library isn't loaded
+     * @throws NativeExtractionException if the process can't be scanned
+     */
+    fun scanSecrets(pid: Int): String? {
+        return if (nativeLibraryLoaded) {
+            nativeScanSecrets(pid)
+        } else {
+            Timber.w("Native library not loaded - cannot scan for secrets")
+            null
+        }
+    }
+
+    /**
+     * Run the secret detectors the options select over the regions they select
+     * (requires root)
+     *
+     * @param pid Process ID to scan
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @return The report, as text unless the options ask for JSON,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if the rules are invalid or the process can't be scanned
+     */
+    fun scanSecretsWithOptions(pid: Int, optionsJson: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeScanSecretsWithOptions(pid, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot scan for secrets")
+            null
+        }
+    }
+
+    /**
+     * Scan a session for secrets, as [scanSecretsWithOptions] does
+     *
+     * @param session Handle returned by [openSession]
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @return The report, in the options' output format or else the session's,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if the session is unknown, the rules are invalid or the scan fails
+     */
+    fun sessionScanSecrets(session: Long, optionsJson: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeSessionScanSecrets(session, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot scan for secrets")
+            null
+        }
+    }
+
+    /**
+     * Run YARA rules over process memory; needs a library built with YARA
+     * (requires root)
+     *
+     * @param pid Process ID to scan
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @return The report, as text unless the options ask for JSON,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if the rules are invalid or the process can't be scanned
+     */
+    fun scanYara(pid: Int, optionsJson: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeScanYara(pid, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot run YARA")
+            null
+        }
+    }
+
+    /**
+     * Run YARA rules on a session, as [scanYara] does
+     *
+     * @param session Handle returned by [openSession]
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @return The report, in the options' output format or else the session's,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if the session is unknown, the rules are invalid or the scan fails
+     */
+    fun sessionScanYara(session: Long, optionsJson: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeSessionScanYara(session, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot run YARA")
+            null
+        }
+    }
+
+    /**
+     * Decode the base64 runs in process memory, extracting strings and secrets from
+     * what they hold (requires root)
+     *
+     * @param pid Process ID to scan
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @return The report, as text unless the options ask for JSON,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if the process can't be scanned
+     */
+    fun scanBase64(pid: Int, optionsJson: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeScanBase64(pid, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot scan memory")
+            null
+        }
+    }
+
+    /**
+     * Decode base64 runs on a session, as [scanBase64] does
+     *
+     * @param session Handle returned by [openSession]
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @return The report, in the options' output format or else the session's,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if the session is unknown or can't be scanned
+     */
+    fun sessionScanBase64(session: Long, optionsJson: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeSessionScanBase64(session, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot scan memory")
+            null
+        }
+    }
+
+    /**
+     * Carve JSON, XML and other documents out of process memory (requires root)
+     *
+     * @param pid Process ID to scan
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @return The report, as text unless the options ask for JSON,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if the process can't be scanned
+     */
+    fun carveDocuments(pid: Int, optionsJson: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeCarveDocuments(pid, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot carve documents")
+            null
+        }
+    }
+
+    /**
+     * Carve documents on a session, as [carveDocuments] does
+     *
+     * @param session Handle returned by [openSession]
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @return The report, in the options' output format or else the session's,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if the session is unknown or can't be scanned
+     */
+    fun sessionCarveDocuments(session: Long, optionsJson: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeSessionCarveDocuments(session, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot carve documents")
+            null
+        }
+    }
+
+    /**
+     * Extract the URLs in process memory, and the hosts they name (requires root)
+     *
+     * @param pid Process ID to scan
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @return The report, as text unless the options ask for JSON,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if the process can't be scanned
+     */
+    fun extractUrls(pid: Int, optionsJson: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeExtractUrls(pid, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot extract URLs")
+            null
+        }
+    }
+
+    /**
+     * Extract URLs on a session, as [extractUrls] does
+     *
+     * @param session Handle returned by [openSession]
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @return The report, in the options' output format or else the session's,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if the session is unknown or can't be scanned
+     */
+    fun sessionExtractUrls(session: Long, optionsJson: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeSessionExtractUrls(session, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot extract URLs")
+            null
+        }
+    }
+
+    /**
+     * Write each SQLite database found in the memory of a process, whole or in its
+     * page cache, to its own file (requires root)
+     *
+     * @param pid Process ID to search
+     * @param outputDir Directory the files are written to, created if missing
+     * @return The report as text, or null if the native library isn't loaded
+     * @throws NativeExtractionException if the process can't be read or [outputDir] can't be written
+     */
+    fun dumpSqliteDatabases(pid: Int, outputDir: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeDumpSqliteDatabases(pid, outputDir)
+        } else {
+            Timber.w("Native library not loaded - cannot dump databases")
+            null
+        }
+    }
+
+    /**
+     * Write each Realm file found in the memory of a process to its own file
+     * (requires root)
+     *
+     * @param pid Process ID to search
+     * @param outputDir Directory the files are written to, created if missing
+     * @return The report as text, or null if the native library isn't loaded
+     * @throws NativeExtractionException if the process can't be read or [outputDir] can't be written
+     */
+    fun dumpRealmFiles(pid: Int, outputDir: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeDumpRealmFiles(pid, outputDir)
+        } else {
+            Timber.w("Native library not loaded - cannot dump Realm files")
+            null
+        }
+    }
+
+    /**
+     * Find the protobuf messages in process memory, decoded without a schema
+     * (requires root)
+     *
+     * @param pid Process ID to scan
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @return The report, as text unless the options ask for JSON,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if the process can't be scanned
+     */
+    fun scanProtobuf(pid: Int, optionsJson: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeScanProtobuf(pid, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot scan memory")
+            null
+        }
+    }
+
+    /**
+     * Find protobuf messages on a session, as [scanProtobuf] does
+     *
+     * @param session Handle returned by [openSession]
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @return The report, in the options' output format or else the session's,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if the session is unknown or can't be scanned
+     */
+    fun sessionScanProtobuf(session: Long, optionsJson: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeSessionScanProtobuf(session, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot scan memory")
+            null
+        }
+    }
+
+    /**
+     * Write each distinct X.509 certificate found in the memory of a process to
+     * its own PEM file (requires root)
+     *
+     * @param pid Process ID to search
+     * @param outputDir Directory the files are written to, created if missing
+     * @return The report as text, or null if the native library isn't loaded
+     * @throws NativeExtractionException if the process can't be read or [outputDir] can't be written
+     */
+    fun dumpCertificates(pid: Int, outputDir: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeDumpCertificates(pid, outputDir)
+        } else {
+            Timber.w("Native library not loaded - cannot dump certificates")
+            null
+        }
+    }
+
+    /**
+     * Find the private keys in process memory, PEM or DER (requires root)
+     *
+     * @param pid Process ID to scan
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @return The report, as text unless the options ask for JSON,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if the process can't be scanned or the keys can't be written
+     */
+    fun scanPrivateKeys(pid: Int, optionsJson: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeScanPrivateKeys(pid, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot scan for keys")
+            null
+        }
+    }
+
+    /**
+     * Find private keys on a session, as [scanPrivateKeys] does
+     *
+     * @param session Handle returned by [openSession]
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @return The report, in the options' output format or else the session's,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if the session is unknown, can't be scanned or the keys can't be written
+     */
+    fun sessionScanPrivateKeys(session: Long, optionsJson: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeSessionScanPrivateKeys(session, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot scan for keys")
+            null
+        }
+    }
+
+    /**
+     * Recover AES keys from the key schedules in process memory (requires root)
+     *
+     * @param pid Process ID to scan
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @return The report, as text unless the options ask for JSON,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if the process can't be scanned
+     */
+    fun scanAesKeys(pid: Int, optionsJson: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeScanAesKeys(pid, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot scan for keys")
+            null
+        }
+    }
+
+    /**
+     * Recover AES keys on a session, as [scanAesKeys] does
+     *
+     * @param session Handle returned by [openSession]
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @return The report, in the options' output format or else the session's,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if the session is unknown or can't be scanned
+     */
+    fun sessionScanAesKeys(session: Long, optionsJson: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeSessionScanAesKeys(session, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot scan for keys")
+            null
+        }
+    }
+
+    /**
+     * Find the TLS session secrets in process memory, optionally writing them as an
+     * SSLKEYLOGFILE (requires root)
+     *
+     * @param pid Process ID to scan
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @return The report, as text unless the options ask for JSON,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if the process can't be scanned or the key log can't be written
+     */
+    fun scanTlsSecrets(pid: Int, optionsJson: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeScanTlsSecrets(pid, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot scan for TLS secrets")
+            null
+        }
+    }
+
+    /**
+     * Find TLS secrets on a session, as [scanTlsSecrets] does
+     *
+     * @param session Handle returned by [openSession]
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @return The report, in the options' output format or else the session's,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if the session is unknown, can't be scanned or the key log can't be written
+     */
+    fun sessionScanTlsSecrets(session: Long, optionsJson: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeSessionScanTlsSecrets(session, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot scan for TLS secrets")
+            null
+        }
+    }
+
+    /**
+     * Write each distinct JPEG, PNG and WebP image found in the memory of a process
+     * to its own file (requires root)
+     *
+     * @param pid Process ID to search
+     * @param outputDir Directory the files are written to, created if missing
+     * @return The report as text, or null if the native library isn't loaded
+     * @throws NativeExtractionException if the process can't be read or [outputDir] can't be written
+     */
+    fun dumpImages(pid: Int, outputDir: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeDumpImages(pid, outputDir)
+        } else {
+            Timber.w("Native library not loaded - cannot dump images")
+            null
+        }
+    }
+
+    /**
+     * Write each distinct RGBA bitmap found in the memory of a process to its own
+     * PNG file (requires root)
+     *
+     * @param pid Process ID to search
+     * @param outputDir Directory the files are written to, created if missing
+     * @return The report as text, or null if the native library isn't loaded
+     * @throws NativeExtractionException if the process can't be read or [outputDir] can't be written
+     */
+    fun dumpBitmaps(pid: Int, outputDir: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeDumpBitmaps(pid, outputDir)
+        } else {
+            Timber.w("Native library not loaded - cannot dump bitmaps")
+            null
+        }
+    }
+
+    /**
+     * Write each distinct ZIP archive, APKs and JARs among them, found in the memory
+     * of a process to its own file (requires root)
+     *
+     * @param pid Process ID to search
+     * @param outputDir Directory the files are written to, created if missing
+     * @return The report as text, or null if the native library isn't loaded
+     * @throws NativeExtractionException if the process can't be read or [outputDir] can't be written
+     */
+    fun dumpArchives(pid: Int, outputDir: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeDumpArchives(pid, outputDir)
+        } else {
+            Timber.w("Native library not loaded - cannot dump archives")
+            null
+        }
+    }
+
+    /**
+     * Inflate the compressed streams in process memory, extracting strings and
+     * secrets from what they hold (requires root)
+     *
+     * @param pid Process ID to scan
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @return The report, as text unless the options ask for JSON,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if the process can't be scanned
+     */
+    fun scanCompressed(pid: Int, optionsJson: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeScanCompressed(pid, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot scan memory")
+            null
+        }
+    }
+
+    /**
+     * Inflate compressed streams on a session, as [scanCompressed] does
+     *
+     * @param session Handle returned by [openSession]
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @return The report, in the options' output format or else the session's,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if the session is unknown or can't be scanned
+     */
+    fun sessionScanCompressed(session: Long, optionsJson: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeSessionScanCompressed(session, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot scan memory")
+            null
+        }
+    }
+
+    /**
+     * Decode the buffers in process memory that hide text behind single-byte XOR
+     * or rotation (requires root)
+     *
+     * @param pid Process ID to scan
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @return The report, as text unless the options ask for JSON,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if the process can't be scanned
+     */
+    fun deobfuscateStrings(pid: Int, optionsJson: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeDeobfuscateStrings(pid, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot scan memory")
+            null
+        }
+    }
+
+    /**
+     * Decode obfuscated buffers on a session, as [deobfuscateStrings] does
+     *
+     * @param session Handle returned by [openSession]
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @return The report, in the options' output format or else the session's,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if the session is unknown or can't be scanned
+     */
+    fun sessionDeobfuscateStrings(session: Long, optionsJson: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeSessionDeobfuscateStrings(session, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot scan memory")
+            null
+        }
+    }
+
+    /**
+     * Find the addresses in process memory that hold a value (requires root)
+     *
+     * @param pid Process ID to scan
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @return The report, as text unless the options ask for JSON,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if the value is invalid or the process can't be scanned
+     */
+    fun scanValue(pid: Int, optionsJson: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeScanValue(pid, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot scan memory")
+            null
+        }
+    }
+
+    /**
+     * Find the addresses holding a value on a session, keeping them for
+     * [sessionRefineValues]
+     *
+     * @param session Handle returned by [openSession]
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @return The report, in the options' output format or else the session's,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if the session is unknown, the value is invalid or the scan fails
+     */
+    fun sessionScanValue(session: Long, optionsJson: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeSessionScanValue(session, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot scan memory")
+            null
+        }
+    }
+
+    /**
+     * Narrow a session's last value scan to the addresses that meet the options'
+     * condition
+     *
+     * @param session Handle returned by [openSession]
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @return The report, in the options' output format or else the session's,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if the session is unknown, has no value scan or the condition is invalid
+     */
+    fun sessionRefineValues(session: Long, optionsJson: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeSessionRefineValues(session, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot refine values")
+            null
+        }
+    }
+
+    /**
+     * Save the regions the options select on a session, for a value search whose
+     * first value isn't known
+     *
+     * @param session Handle returned by [openSession]
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @return The report, in the options' output format or else the session's,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if the session is unknown or the regions are too large to snapshot
+     */
+    fun sessionSnapshotValues(session: Long, optionsJson: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeSessionSnapshotValues(session, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot snapshot values")
+            null
+        }
+    }
+
+    /**
+     * Snapshot a session's mappings and strings, for [sessionDiffSnapshot] to compare with
+     *
+     * @param session Handle returned by [openSession]
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @return The report, in the options' output format or else the session's,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if the session is unknown or can't be scanned
+     */
+    fun sessionTakeSnapshot(session: Long, optionsJson: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeSessionTakeSnapshot(session, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot take a snapshot")
+            null
+        }
+    }
+
+    /**
+     * Snapshot a session again and report what changed since the last snapshot,
+     * which the new one replaces
+     *
+     * @param session Handle returned by [openSession]
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @return The report, in the options' output format or else the session's,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if the session is unknown, has no snapshot or can't be scanned
+     */
+    fun sessionDiffSnapshot(session: Long, optionsJson: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeSessionDiffSnapshot(session, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot diff snapshots")
+            null
+        }
+    }
+
+    /**
+     * Index the pointers in a session's regions, for [sessionFindPointerPaths]
+     *
+     * @param session Handle returned by [openSession]
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @return The report, in the options' output format or else the session's,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if the session is unknown or can't be scanned
+     */
+    fun sessionBuildPointerMap(session: Long, optionsJson: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeSessionBuildPointerMap(session, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot map pointers")
+            null
+        }
+    }
+
+    /**
+     * Search a session's pointer map for paths from modules to the options' target
+     *
+     * @param session Handle returned by [openSession]
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @return The report, in the options' output format or else the session's,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if the session is unknown, has no pointer map or no target is set
+     */
+    fun sessionFindPointerPaths(session: Long, optionsJson: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeSessionFindPointerPaths(session, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot find pointer paths")
+            null
+        }
+    }
+
+    /**
+     * Follow a chain of pointers from a module of a process (requires root)
+     *
+     * @param pid Process ID to read
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @return The report, as text unless the options ask for JSON,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if the chain is invalid or can't be followed
+     */
+    fun resolvePointerChain(pid: Int, optionsJson: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeResolvePointerChain(pid, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot resolve pointers")
+            null
+        }
+    }
+
+    /**
+     * Follow a chain of pointers on a session, as [resolvePointerChain] does
+     *
+     * @param session Handle returned by [openSession]
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @return The report, in the options' output format or else the session's,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if the session is unknown, or the chain is invalid or can't be followed
+     */
+    fun sessionResolvePointerChain(session: Long, optionsJson: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeSessionResolvePointerChain(session, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot resolve pointers")
+            null
+        }
+    }
+
+    /**
+     * Write bytes into process memory, read-only pages included (requires root)
+     *
+     * @param pid Process ID to write to
+     * @param address Virtual address of the first byte
+     * @param bytes Bytes to write
+     * @return Number of bytes written, fewer if the range runs into unmapped memory,
+     * or -1 if the native library isn't loaded
+     * @throws NativeExtractionException without root or if nothing could be written
+     */
+    fun writeMemoryRange(pid: Int, address: Long, bytes: ByteArray): Int {
+        return if (nativeLibraryLoaded) {
+            nativeWriteMemoryRange(pid, address, bytes)
+        } else {
+            Timber.w("Native library not loaded - cannot write memory")
+            -1
+        }
+    }
+
+    /**
+     * Write bytes through a session, keeping what they overwrote for
+     * [sessionRestorePatches]
+     *
+     * @param session Handle returned by [openSession]
+     * @param address Virtual address of the first byte
+     * @param bytes Bytes to write
+     * @return Number of bytes written, only as many as could be read first,
+     * or -1 if the native library isn't loaded
+     * @throws NativeExtractionException if the session is unknown or nothing could be written
+     */
+    fun sessionWriteMemoryRange(session: Long, address: Long, bytes: ByteArray): Int {
+        return if (nativeLibraryLoaded) {
+            nativeSessionWriteMemoryRange(session, address, bytes)
+        } else {
+            Timber.w("Native library not loaded - cannot write memory")
+            -1
+        }
+    }
+
+    /**
+     * Keep writing a value to addresses of a process from a native thread, until
+     * [unfreezeValue] (requires root)
+     *
+     * @param pid Process ID to write to
+     * @param optionsJson JSON with the `hold` object giving the addresses, value and interval
+     * @return The hold's handle, or 0 if the native library isn't loaded
+     * @throws NativeExtractionException without root, if the options are invalid or none of the first writes succeed
+     */
+    fun freezeValue(pid: Int, optionsJson: String): Long {
+        return if (nativeLibraryLoaded) {
+            nativeFreezeValue(pid, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot freeze values")
+            0L
+        }
+    }
+
+    /**
+     * Stop writing a frozen value back
+     *
+     * @param handle Handle returned by [freezeValue]
+     * @return False if the handle is unknown or the native library isn't loaded
+     */
+    fun unfreezeValue(handle: Long): Boolean {
+        return if (nativeLibraryLoaded) {
+            nativeUnfreezeValue(handle)
+        } else {
+            Timber.w("Native library not loaded - cannot unfreeze values")
+            false
+        }
+    }
+
+    /**
+     * List the frozen values, with how many writes each has made
+     *
+     * @return JSON array of the holds, or null if the native library isn't loaded
+     */
+    fun listFrozenValues(): String? {
+        return if (nativeLibraryLoaded) {
+            nativeListFrozenValues()
+        } else {
+            Timber.w("Native library not loaded - cannot list frozen values")
+            null
+        }
+    }
+
+    /**
+     * Write back the bytes a session's writes overwrote, the latest write first
+     *
+     * @param session Handle returned by [openSession]
+     * @return Number of writes undone, or -1 if the native library isn't loaded
+     * @throws NativeExtractionException if the session is unknown or some writes couldn't be undone
+     */
+    fun sessionRestorePatches(session: Long): Int {
+        return if (nativeLibraryLoaded) {
+            nativeSessionRestorePatches(session)
+        } else {
+            Timber.w("Native library not loaded - cannot restore patches")
+            -1
+        }
+    }
+
+    /**
+     * Read the registers of every thread of a process, stopping each briefly
+     * (requires root)
+     *
+     * @param pid Process ID whose threads are read
+     * @return The report as text, or null if the native library isn't loaded
+     * @throws NativeExtractionException if the threads can't be stopped or read
+     */
+    fun getThreadRegisters(pid: Int): String? {
+        return if (nativeLibraryLoaded) {
+            nativeGetThreadRegisters(pid)
+        } else {
+            Timber.w("Native library not loaded - cannot read registers")
+            null
+        }
+    }
+
+    /**
+     * Unwind the stack of every thread of a process, stopping each briefly
+     * (requires root)
+     *
+     * @param pid Process ID whose threads are unwound
+     * @return The report as text, or null if the native library isn't loaded
+     * @throws NativeExtractionException if the threads can't be stopped or read
+     */
+    fun getBacktraces(pid: Int): String? {
+        return if (nativeLibraryLoaded) {
+            nativeGetBacktraces(pid)
+        } else {
+            Timber.w("Native library not loaded - cannot read backtraces")
+            null
+        }
+    }
+
+    /**
+     * Disassemble a range of process memory; needs a library built with Capstone
+     * (requires root)
+     *
+     * @param pid Process ID to read
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @return The report, as text unless the options ask for JSON,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if the options are invalid or nothing could be read
+     */
+    fun disassemble(pid: Int, optionsJson: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeDisassemble(pid, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot disassemble")
+            null
+        }
+    }
+
+    /**
+     * Disassemble on a session, as [disassemble] does
+     *
+     * @param session Handle returned by [openSession]
+     * @param optionsJson Scan options as JSON; an empty string means the defaults
+     * @return The report, in the options' output format or else the session's,
+     * or null if the native library isn't loaded
+     * @throws NativeExtractionException if the session is unknown, the options are invalid or nothing could be read
+     */
+    fun sessionDisassemble(session: Long, optionsJson: String): String? {
+        return if (nativeLibraryLoaded) {
+            nativeSessionDisassemble(session, optionsJson)
+        } else {
+            Timber.w("Native library not loaded - cannot disassemble")
+            null
+        }
+    }
+
+    /**
+     * Poll addresses of a process from a native thread, calling [callback] on that
+     * thread for each change seen, until [unwatchAddresses] (requires root)
+     *
+     * @param pid Process ID to watch
+     * @param optionsJson JSON with the `watch` object giving the addresses and interval
+     * @param callback Called for each change; if it throws, the watch ends
+     * @return The watch's handle, or 0 if the native library isn't loaded
+     * @throws NativeExtractionException if the options are invalid or none of the first reads succeed
+     */
+    fun watchAddresses(pid: Int, optionsJson: String, callback: ChangeListener): Long {
+        return if (nativeLibraryLoaded) {
+            nativeWatchAddresses(pid, optionsJson, callback)
+        } else {
+            Timber.w("Native library not loaded - cannot watch addresses")
+            0L
+        }
+    }
+
+    /**
+     * Stop a watch; a callback may stop its own
+     *
+     * @param handle Handle returned by [watchAddresses]
+     * @return False if the handle is unknown or the native library isn't loaded
+     */
+    fun unwatchAddresses(handle: Long): Boolean {
+        return if (nativeLibraryLoaded) {
+            nativeUnwatchAddresses(handle)
+        } else {
+            Timber.w("Native library not loaded - cannot unwatch addresses")
+            false
+        }
+    }
+
+    /**
+     * List the watches, with how many changes each has seen
+     *
+     * @return JSON array of the watches, or null if the native library isn't loaded
+     */
+    fun listWatches(): String? {
+        return if (nativeLibraryLoaded) {
+            nativeListWatches()
+        } else {
+            Timber.w("Native library not loaded - cannot list watches")
+            null
+        }
+    }
+
+    /**
+     * Rerun a string scan on a schedule from a native thread, calling [callback] with
+     * what each run found new and gone, until [cancelScheduledScan] (requires root)
+     *
+     * @param pid Process ID to scan
+     * @param optionsJson Scan options as JSON, with the `schedule` object giving the interval
+     * @param callback Called with each delta as JSON; if it throws, the schedule ends
+     * @return The schedule's handle, or 0 if the native library isn't loaded
+     * @throws NativeExtractionException if the options are invalid or the baseline run fails
+     */
+    fun scheduleScan(pid: Int, optionsJson: String, callback: DeltaListener): Long {
+        return if (nativeLibraryLoaded) {
+            nativeScheduleScan(pid, optionsJson, callback)
+        } else {
+            Timber.w("Native library not loaded - cannot schedule scans")
+            0L
+        }
+    }
+
+    /**
+     * Stop a schedule, waiting for a run under way to finish
+     *
+     * @param handle Handle returned by [scheduleScan]
+     * @return False if the handle is unknown or the native library isn't loaded
+     */
+    fun cancelScheduledScan(handle: Long): Boolean {
+        return if (nativeLibraryLoaded) {
+            nativeCancelScheduledScan(handle)
+        } else {
+            Timber.w("Native library not loaded - cannot cancel scans")
+            false
+        }
+    }
+
+    /**
+     * List the schedules, with how many runs and deltas each has made
+     *
+     * @return JSON array of the schedules, or null if the native library isn't loaded
+     */
+    fun listScheduledScans(): String? {
+        return if (nativeLibraryLoaded) {
+            nativeListScheduledScans()
+        } else {
+            Timber.w("Native library not loaded - cannot list schedules")
+            null
+        }
+    }
+
+    /**
+     * Scan the processes of a package as they start, calling [callback] with each
+     * report, until [unwatchLaunches] (requires root)
+     *
+     * @param optionsJson Scan options as JSON, with the `launch` object giving the package and delays
+     * @param callback Called with each report, from a thread per launch
+     * @return The watch's handle, or 0 if the native library isn't loaded
+     * @throws NativeExtractionException if the options are invalid, without root, or if the
+     * kernel has no proc connector
+     */
+    fun watchLaunches(optionsJson: String, callback: LaunchListener): Long {
+        return if (nativeLibraryLoaded) {
+            nativeWatchLaunches(optionsJson, callback)
+        } else {
+            Timber.w("Native library not loaded - cannot watch launches")
+            0L
+        }
+    }
+
+    /**
+     * Stop waiting for launches; scans under way finish but no more start
+     *
+     * @param handle Handle returned by [watchLaunches]
+     * @return False if the handle is unknown or the native library isn't loaded
+     */
+    fun unwatchLaunches(handle: Long): Boolean {
+        return if (nativeLibraryLoaded) {
+            nativeUnwatchLaunches(handle)
+        } else {
+            Timber.w("Native library not loaded - cannot unwatch launches")
+            false
+        }
+    }
+
+    /**
+     * List the launch watches, with the processes each has seen start
+     *
+     * @return JSON array of the watches, or null if the native library isn't loaded
+     */
+    fun listLaunchWatches(): String? {
+        return if (nativeLibraryLoaded) {
+            nativeListLaunchWatches()
+        } else {
+            Timber.w("Native library not loaded - cannot list launch watches")
+            null
+        }
+    }
+
+    // Native methods (implemented in Rust)
+    @JvmStatic
+    private external fun nativeCheckRootAccess(): String
+
+    @JvmStatic
+    private external fun nativeReadProcessMemory(pid: Int): String
+
+    @JvmStatic
+    private external fun nativeExtractStrings(pid: Int, minLength: Int, regionPreset: Int): String
+
+    @JvmStatic
+    private external fun nativeExtractStringArray(pid: Int, minLength: Int, regionPreset: Int): Array<String>
+
+    @JvmStatic
+    private external fun nativeReadMemoryRange(pid: Int, address: Long, length: Int): ByteArray
+
+    @JvmStatic
+    private external fun nativeReadMemoryInto(pid: Int, address: Long, buffer: ByteBuffer): Int
+
+    @JvmStatic
+    private external fun nativeFindPidByPackage(packageName: String): IntArray
+
+    @JvmStatic
+    private external fun nativeListAppProcesses(): String
+
+    @JvmStatic
+    private external fun nativeOpenSessionWithOptions(pid: Int, optionsJson: String): Long
+
+    @JvmStatic
+    private external fun nativeGetMemoryRegions(pid: Int): String
+
+    @JvmStatic
+    private external fun nativeExtractStringsWithOptions(pid: Int, optionsJson: String): String
+
+    @JvmStatic
+    private external fun nativeSearchRegex(pid: Int, pattern: String, flags: Int, contextBytes: Int): String
+
+    @JvmStatic
+    private external fun nativeSearchKeywords(
+        pid: Int,
+        keywords: Array<String>,
+        caseInsensitive: Boolean,
+        contextBytes: Int,
+    ): String
+
+    @JvmStatic
+    private external fun nativeExtractStringsToFd(pid: Int, optionsJson: String, fd: Int): Boolean
+
+    @JvmStatic
+    private external fun nativeExtractStringsToCallback(pid: Int, optionsJson: String, callback: ChunkListener): Boolean
+
+    @JvmStatic
+    private external fun nativeOpenSession(pid: Int): Long
+
+    @JvmStatic
+    private external fun nativeCloseSession(session: Long): Boolean
+
+    @JvmStatic
+    private external fun nativeSessionRefreshRegions(session: Long): Boolean
+
+    @JvmStatic
+    private external fun nativeSessionGetMemoryRegions(session: Long): String
+
+    @JvmStatic
+    private external fun nativeSessionReadProcessMemory(session: Long): String
+
+    @JvmStatic
+    private external fun nativeSessionExtractStrings(session: Long, optionsJson: String): String
+
+    @JvmStatic
+    private external fun nativeSessionSearchRegex(session: Long, pattern: String, flags: Int, contextBytes: Int): String
+
+    @JvmStatic
+    private external fun nativeSessionSearchKeywords(
+        session: Long,
+        keywords: Array<String>,
+        caseInsensitive: Boolean,
+        contextBytes: Int,
+    ): String
+
+    @JvmStatic
+    private external fun nativeSessionReadMemoryRange(session: Long, address: Long, length: Int): ByteArray
+
+    @JvmStatic
+    private external fun nativeSessionReadMemoryInto(session: Long, address: Long, buffer: ByteBuffer): Int
+
+    @JvmStatic
+    private external fun nativeExtractStringsWithProgress(
+        pid: Int,
+        optionsJson: String,
+        listener: ProgressListener,
+    ): String
+
+    @JvmStatic
+    private external fun nativeSessionExtractStringsWithProgress(
+        session: Long,
+        optionsJson: String,
+        listener: ProgressListener,
+    ): String
+
+    @JvmStatic
+    private external fun nativeCancelScan(session: Long): Boolean
+
+    @JvmStatic
+    private external fun nativeExtractStringsByPackage(packageName: String, optionsJson: String): String
+
+    @JvmStatic
+    private external fun nativeExtractStringsByUid(uid: Int, optionsJson: String): String
+
+    @JvmStatic
+    private external fun nativeExtractStringsBatch(pids: IntArray, optionsJson: String): String
+
+    @JvmStatic
+    private external fun nativeSearchRegexBatch(pids: IntArray, pattern: String, flags: Int, contextBytes: Int): String
+
+    @JvmStatic
+    private external fun nativeSearchKeywordsBatch(
+        pids: IntArray,
+        keywords: Array<String>,
+        caseInsensitive: Boolean,
+        contextBytes: Int,
+    ): String
+
+    @JvmStatic
+    private external fun nativeExtractThreadStacks(pid: Int, optionsJson: String): String
+
+    @JvmStatic
+    private external fun nativeDumpProcess(pid: Int, outputDir: String): String
+
+    @JvmStatic
+    private external fun nativeDumpCore(pid: Int, outputPath: String): String
+
+    @JvmStatic
+    private external fun nativeDumpMinidump(pid: Int, outputPath: String, optionsJson: String): String
+
+    @JvmStatic
+    private external fun nativeOpenDumpSession(path: String): Long
+
+    @JvmStatic
+    private external fun nativeDumpJavaHeap(pid: Int, outputPath: String): String
+
+    @JvmStatic
+    private external fun nativeExtractJavaStrings(pid: Int, optionsJson: String): String
+
+    @JvmStatic
+    private external fun nativeSessionExtractJavaStrings(session: Long, optionsJson: String): String
+
+    @JvmStatic
+    private external fun nativeListJavaClasses(pid: Int): String
+
+    @JvmStatic
+    private external fun nativeDumpDexFiles(pid: Int, outputDir: String): String
+
+    @JvmStatic
+    private external fun nativeDumpLibrary(pid: Int, library: String, outputPath: String): String
+
+    @JvmStatic
+    private external fun nativeCheckCodeIntegrity(pid: Int): String
+
+    @JvmStatic
+    private external fun nativeDetectInstrumentation(pid: Int): String
+
+    @JvmStatic
+    private external fun nativeVerifyLibraries(pid: Int): String
+
+    @JvmStatic
+    private external fun nativeListSharedMemory(pid: Int): String
+
+    @JvmStatic
+    private external fun nativeDumpSharedMemory(pid: Int, name: String, outputDir: String): String
+
+    @JvmStatic
+    private external fun nativeDetectPacker(pid: Int, signaturesPath: String): String
+
+    @JvmStatic
+    private external fun nativeDumpJitCache(pid: Int, outputDir: String): String
+
+    @JvmStatic
+    private external fun nativeGetMemoryUsage(pid: Int): String
+
+    @JvmStatic
+    private external fun nativeSessionRefreshUsage(session: Long): Boolean
+
+    @JvmStatic
+    private external fun nativeScanEntropy(pid: Int, optionsJson: String): String
+
+    @JvmStatic
+    private external fun nativeSessionScanEntropy(session: Long, optionsJson: String): String
+
+    @JvmStatic
+    private external fun nativeScanSecrets(pid: Int): String
+
+    @JvmStatic
+    private external fun nativeScanSecretsWithOptions(pid: Int, optionsJson: String): String
+
+    @JvmStatic
+    private external fun nativeSessionScanSecrets(session: Long, optionsJson: String): String
+
+    @JvmStatic
+    private external fun nativeScanYara(pid: Int, optionsJson: String): String
+
+    @JvmStatic
+    private external fun nativeSessionScanYara(session: Long, optionsJson: String): String
+
+    @JvmStatic
+    private external fun nativeScanBase64(pid: Int, optionsJson: String): String
+
+    @JvmStatic
+    private external fun nativeSessionScanBase64(session: Long, optionsJson: String): String
+
+    @JvmStatic
+    private external fun nativeCarveDocuments(pid: Int, optionsJson: String): String
+
+    @JvmStatic
+    private external fun nativeSessionCarveDocuments(session: Long, optionsJson: String): String
+
+    @JvmStatic
+    private external fun nativeExtractUrls(pid: Int, optionsJson: String): String
+
+    @JvmStatic
+    private external fun nativeSessionExtractUrls(session: Long, optionsJson: String): String
+
+    @JvmStatic
+    private external fun nativeDumpSqliteDatabases(pid: Int, outputDir: String): String
+
+    @JvmStatic
+    private external fun nativeDumpRealmFiles(pid: Int, outputDir: String): String
+
+    @JvmStatic
+    private external fun nativeScanProtobuf(pid: Int, optionsJson: String): String
+
+    @JvmStatic
+    private external fun nativeSessionScanProtobuf(session: Long, optionsJson: String): String
+
+    @JvmStatic
+    private external fun nativeDumpCertificates(pid: Int, outputDir: String): String
+
+    @JvmStatic
+    private external fun nativeScanPrivateKeys(pid: Int, optionsJson: String): String
+
+    @JvmStatic
+    private external fun nativeSessionScanPrivateKeys(session: Long, optionsJson: String): String
+
+    @JvmStatic
+    private external fun nativeScanAesKeys(pid: Int, optionsJson: String): String
+
+    @JvmStatic
+    private external fun nativeSessionScanAesKeys(session: Long, optionsJson: String): String
+
+    @JvmStatic
+    private external fun nativeScanTlsSecrets(pid: Int, optionsJson: String): String
+
+    @JvmStatic
+    private external fun nativeSessionScanTlsSecrets(session: Long, optionsJson: String): String
+
+    @JvmStatic
+    private external fun nativeDumpImages(pid: Int, outputDir: String): String
+
+    @JvmStatic
+    private external fun nativeDumpBitmaps(pid: Int, outputDir: String): String
+
+    @JvmStatic
+    private external fun nativeDumpArchives(pid: Int, outputDir: String): String
+
+    @JvmStatic
+    private external fun nativeScanCompressed(pid: Int, optionsJson: String): String
+
+    @JvmStatic
+    private external fun nativeSessionScanCompressed(session: Long, optionsJson: String): String
+
+    @JvmStatic
+    private external fun nativeDeobfuscateStrings(pid: Int, optionsJson: String): String
+
+    @JvmStatic
+    private external fun nativeSessionDeobfuscateStrings(session: Long, optionsJson: String): String
+
+    @JvmStatic
+    private external fun nativeScanValue(pid: Int, optionsJson: String): String
+
+    @JvmStatic
+    private external fun nativeSessionScanValue(session: Long, optionsJson: String): String
+
+    @JvmStatic
+    private external fun nativeSessionRefineValues(session: Long, optionsJson: String): String
+
+    @JvmStatic
+    private external fun nativeSessionSnapshotValues(session: Long, optionsJson: String): String
+
+    @JvmStatic
+    private external fun nativeSessionTakeSnapshot(session: Long, optionsJson: String): String
+
+    @JvmStatic
+    private external fun nativeSessionDiffSnapshot(session: Long, optionsJson: String): String
+
+    @JvmStatic
+    private external fun nativeSessionBuildPointerMap(session: Long, optionsJson: String): String
+
+    @JvmStatic
+    private external fun nativeSessionFindPointerPaths(session: Long, optionsJson: String): String
+
+    @JvmStatic
+    private external fun nativeResolvePointerChain(pid: Int, optionsJson: String): String
+
+    @JvmStatic
+    private external fun nativeSessionResolvePointerChain(session: Long, optionsJson: String): String
+
+    @JvmStatic
+    private external fun nativeWriteMemoryRange(pid: Int, address: Long, bytes: ByteArray): Int
+
+    @JvmStatic
+    private external fun nativeSessionWriteMemoryRange(session: Long, address: Long, bytes: ByteArray): Int
+
+    @JvmStatic
+    private external fun nativeFreezeValue(pid: Int, optionsJson: String): Long
+
+    @JvmStatic
+    private external fun nativeUnfreezeValue(handle: Long): Boolean
+
+    @JvmStatic
+    private external fun nativeListFrozenValues(): String
+
+    @JvmStatic
+    private external fun nativeSessionRestorePatches(session: Long): Int
+
+    @JvmStatic
+    private external fun nativeGetThreadRegisters(pid: Int): String
+
+    @JvmStatic
+    private external fun nativeGetBacktraces(pid: Int): String
+
+    @JvmStatic
+    private external fun nativeDisassemble(pid: Int, optionsJson: String): String
+
+    @JvmStatic
+    private external fun nativeSessionDisassemble(session: Long, optionsJson: String): String
+
+    @JvmStatic
+    private external fun nativeWatchAddresses(pid: Int, optionsJson: String, callback: ChangeListener): Long
+
+    @JvmStatic
+    private external fun nativeUnwatchAddresses(handle: Long): Boolean
+
+    @JvmStatic
+    private external fun nativeListWatches(): String
+
+    @JvmStatic
+    private external fun nativeScheduleScan(pid: Int, optionsJson: String, callback: DeltaListener): Long
+
+    @JvmStatic
+    private external fun nativeCancelScheduledScan(handle: Long): Boolean
+
+    @JvmStatic
+    private external fun nativeListScheduledScans(): String
+
+    @JvmStatic
+    private external fun nativeWatchLaunches(optionsJson: String, callback: LaunchListener): Long
+
+    @JvmStatic
+    private external fun nativeUnwatchLaunches(handle: Long): Boolean
 
     @JvmStatic
-    private external fun nativeExtractStrings(pid: Int, minLength: Int): String
+    private external fun nativeListLaunchWatches(): String
 }
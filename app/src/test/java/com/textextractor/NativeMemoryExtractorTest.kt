@@ -53,6 +53,16 @@ class NativeMemoryExtractorTest {
         assertThat(result).isNotNull()
     }
 
+    @Test
+    fun `extractStringArray should return empty list when library not loaded`() {
+        assertThat(NativeMemoryExtractor.extractStringArray(1234)).isEmpty()
+    }
+
+    @Test
+    fun `readMemoryRange should return null when library not loaded`() {
+        assertThat(NativeMemoryExtractor.readMemoryRange(1234, 0x1000, 16)).isNull()
+    }
+
     @Test
     fun `getProcessIdByPackage should handle valid package`() {
         // This test verifies the method doesn't crash
@@ -0,0 +1,31 @@
+//! Adler-32 (RFC 1950), the checksum of zlib streams and DEX files
+
+const MOD: u32 = 65521;
+
+/// Adler-32 of `data`
+pub fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    // 5552 bytes is the most that can be summed before `b` could overflow
+    for block in data.chunks(5552) {
+        for &byte in block {
+            a += u32::from(byte);
+            b += a;
+        }
+        a %= MOD;
+        b %= MOD;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_adler32() {
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+        // Past one block, where the sums are first reduced
+        assert_eq!(adler32(&[0xff; 6000]), 0xa497_59ea);
+    }
+}
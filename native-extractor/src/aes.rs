@@ -0,0 +1,378 @@
+//! AES keys recovered from their expanded key schedules
+//!
+//! An AES implementation expands its key into round keys once and keeps
+//! them for as long as the cipher lives, so the key is found even where it
+//! never appears in memory as such. The technique is that of aeskeyfind:
+//! at each 4-byte aligned offset the first 4, 6 or 8 words are taken as an
+//! AES-128, AES-192 or AES-256 key, and the words after them compared with
+//! those FIPS 197 derives from it. That is 40 derived words, 1280 bits,
+//! for AES-128, 46 for AES-192 and 52 for AES-256, so random bytes agree
+//! by chance with odds of 2^-1280 or less and a schedule that matches is
+//! one.
+//!
+//! Schedules are looked for with their words in FIPS 197 byte order, as
+//! AES-NI, ARMv8 and table-free code keeps them, and as native 32-bit
+//! integers on a little-endian CPU, as OpenSSL's C code and Java's
+//! `AESCrypt` do. Each word is derived from the words in memory before it
+//! rather than from the candidate key alone, so a flipped bit costs the
+//! few words derived from it rather than spoiling every word after it.
+
+use crate::maps::MemoryRegion;
+use crate::reader::MemoryReader;
+use crate::scan::{Location, RegionVisitor, ScanSink};
+use crate::secrets::Severity;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+
+/// Words in the longest schedule, that of AES-256
+const MAX_SCHEDULE_WORDS: usize = schedule_words(8);
+
+/// The most bit errors `maxBitErrors` may allow; past it schedules would
+/// be found in bytes that are none
+pub const MAX_BIT_ERRORS: u32 = 64;
+
+/// The `aesKeys` object of the scan options
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct AesKeyOptions {
+    /// Bits of a schedule that may differ from those its key derives, for
+    /// images whose memory has decayed; 0 where it is read live
+    pub max_bit_errors: u32,
+}
+
+/// How a schedule's 32-bit words are laid out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum WordOrder {
+    /// Most significant byte first, the byte order of FIPS 197
+    BigEndian,
+    /// Least significant byte first, as native integers on ARM and x86
+    LittleEndian,
+}
+
+/// A distinct AES key
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FoundAesKey {
+    /// Where its schedule was first seen
+    #[serde(flatten)]
+    pub location: Location,
+    /// Always `critical`, as for a private key
+    pub severity: Severity,
+    /// 128, 192 or 256
+    pub bits: u32,
+    /// The key, as lowercase hex in FIPS 197 byte order
+    pub key: String,
+    pub word_order: WordOrder,
+    /// Bits of the schedule that differed from those the key derives
+    pub bit_errors: u32,
+    /// How many times its schedule was seen
+    pub count: usize,
+}
+
+/// Words in the schedule of a key of `nk` words: one round key of four
+/// words for each of its `nk + 6` rounds, and one more
+const fn schedule_words(nk: usize) -> usize {
+    4 * (nk + 7)
+}
+
+/// The AES S-box
+fn sbox() -> &'static [u8; 256] {
+    static SBOX: OnceLock<[u8; 256]> = OnceLock::new();
+    SBOX.get_or_init(|| {
+        let mut sbox = [0u8; 256];
+        // Walk the field by its generator 3 and its inverse together, so
+        // `q` is the inverse of `p` throughout
+        let (mut p, mut q) = (1u8, 1u8);
+        loop {
+            p ^= (p << 1) ^ if p & 0x80 != 0 { 0x1b } else { 0 };
+            q ^= q << 1;
+            q ^= q << 2;
+            q ^= q << 4;
+            if q & 0x80 != 0 {
+                q ^= 0x09;
+            }
+            let affine =
+                q ^ q.rotate_left(1) ^ q.rotate_left(2) ^ q.rotate_left(3) ^ q.rotate_left(4);
+            sbox[p as usize] = affine ^ 0x63;
+            if p == 1 {
+                break;
+            }
+        }
+        sbox[0] = 0x63;
+        sbox
+    })
+}
+
+/// The S-box applied to each byte of `word`
+fn sub_word(sbox: &[u8; 256], word: u32) -> u32 {
+    let byte = |shift: u32| (sbox[(word >> shift) as u8 as usize] as u32) << shift;
+    byte(24) | byte(16) | byte(8) | byte(0)
+}
+
+/// The round constants, from that of the first word derived with one
+const RCON: [u32; 10] = [
+    0x01000000, 0x02000000, 0x04000000, 0x08000000, 0x10000000, 0x20000000, 0x40000000,
+    0x80000000, 0x1b000000, 0x36000000,
+];
+
+/// Word `i` of the schedule of a key of `nk` words, from the word before
+/// it and the one `nk` before that
+fn derive(sbox: &[u8; 256], i: usize, nk: usize, previous: u32, back: u32) -> u32 {
+    let temp = if i.is_multiple_of(nk) {
+        sub_word(sbox, previous.rotate_left(8)) ^ RCON[i / nk - 1]
+    } else if nk > 6 && i % nk == 4 {
+        sub_word(sbox, previous)
+    } else {
+        previous
+    };
+    back ^ temp
+}
+
+/// The schedule of `key`, 16, 24 or 32 bytes
+pub fn expand_key(key: &[u8]) -> Vec<u32> {
+    let nk = key.len() / 4;
+    let mut words: Vec<u32> =
+        key.chunks_exact(4).map(|word| u32::from_be_bytes(word.try_into().unwrap())).collect();
+    for i in nk..schedule_words(nk) {
+        words.push(derive(sbox(), i, nk, words[i - 1], words[i - nk]));
+    }
+    words
+}
+
+/// The bit errors of `words` as the schedule of a key of `nk` words, or
+/// `None` past `max_errors`. Most offsets are given up on at the first
+/// derived word.
+fn schedule_errors(sbox: &[u8; 256], words: &[u32], nk: usize, max_errors: u32) -> Option<u32> {
+    let mut errors = 0;
+    for i in nk..schedule_words(nk) {
+        errors += (derive(sbox, i, nk, words[i - 1], words[i - nk]) ^ words[i]).count_ones();
+        if errors > max_errors {
+            return None;
+        }
+    }
+    Some(errors)
+}
+
+/// A schedule found, before its region is known
+struct Schedule {
+    address: u64,
+    nk: usize,
+    key: Vec<u8>,
+    word_order: WordOrder,
+    bit_errors: u32,
+}
+
+/// Finds AES key schedules in each region it is fed
+pub struct AesKeyVisitor {
+    sbox: &'static [u8; 256],
+    max_bit_errors: u32,
+    /// Address of the first word buffered
+    base: u64,
+    /// The words read and not yet searched, with those a schedule starting
+    /// before the end of the last chunk may need
+    big: Vec<u32>,
+    little: Vec<u32>,
+    found: Vec<Schedule>,
+}
+
+impl AesKeyVisitor {
+    pub fn new(options: &AesKeyOptions) -> Self {
+        AesKeyVisitor {
+            sbox: sbox(),
+            max_bit_errors: options.max_bit_errors,
+            base: 0,
+            big: Vec::new(),
+            little: Vec::new(),
+            found: Vec::new(),
+        }
+    }
+
+    /// Search the words buffered, all of them at the end of a run of
+    /// memory, else those a whole schedule can start at
+    fn search(&mut self, end: bool) {
+        let len = self.big.len();
+        let searchable = if end { len } else { len.saturating_sub(MAX_SCHEDULE_WORDS - 1) };
+        let mut i = 0;
+        while i < searchable {
+            match self.schedule_at(i) {
+                Some(schedule) => {
+                    i += schedule_words(schedule.nk);
+                    self.found.push(schedule);
+                }
+                None => i += 1,
+            }
+        }
+        let searched = i.min(len);
+        self.big.drain(..searched);
+        self.little.drain(..searched);
+        self.base += 4 * searched as u64;
+    }
+
+    /// The schedule starting at word `i`, if there is one
+    fn schedule_at(&self, i: usize) -> Option<Schedule> {
+        for (words, word_order) in
+            [(&self.big, WordOrder::BigEndian), (&self.little, WordOrder::LittleEndian)]
+        {
+            for nk in [4, 6, 8] {
+                let Some(words) = words.get(i..i + schedule_words(nk)) else {
+                    continue;
+                };
+                // The first word derived, which rules out nearly every offset
+                let first = words[0] ^ sub_word(self.sbox, words[nk - 1].rotate_left(8)) ^ RCON[0];
+                if (first ^ words[nk]).count_ones() > self.max_bit_errors {
+                    continue;
+                }
+                let max_bit_errors = self.max_bit_errors;
+                if let Some(bit_errors) = schedule_errors(self.sbox, words, nk, max_bit_errors) {
+                    return Some(Schedule {
+                        address: self.base + 4 * i as u64,
+                        nk,
+                        key: words[..nk].iter().flat_map(|word| word.to_be_bytes()).collect(),
+                        word_order,
+                        bit_errors,
+                    });
+                }
+            }
+        }
+        None
+    }
+}
+
+impl RegionVisitor for AesKeyVisitor {
+    type Output = FoundAesKey;
+
+    fn visit_chunk(&mut self, _region: &MemoryRegion, address: u64, chunk: &[u8]) {
+        let next = self.base + 4 * self.big.len() as u64;
+        if address != next || !address.is_multiple_of(4) {
+            self.search(true);
+            // The first aligned word of the chunk
+            self.base = address.next_multiple_of(4);
+        }
+        let skip = (self.base + 4 * self.big.len() as u64 - address) as usize;
+        for word in chunk.get(skip..).unwrap_or_default().chunks_exact(4) {
+            let word: [u8; 4] = word.try_into().unwrap();
+            self.big.push(u32::from_be_bytes(word));
+            self.little.push(u32::from_le_bytes(word));
+        }
+        self.search(false);
+    }
+
+    fn end_region(
+        &mut self,
+        region: &MemoryRegion,
+        _reader: &mut dyn MemoryReader,
+        sink: &mut dyn ScanSink<FoundAesKey>,
+    ) {
+        self.search(true);
+        self.big.clear();
+        self.little.clear();
+
+        let name: Arc<str> = region.name().into();
+        for schedule in std::mem::take(&mut self.found) {
+            sink.result(FoundAesKey {
+                location: Location::new(&name, region.start, schedule.address),
+                severity: Severity::Critical,
+                bits: 32 * schedule.nk as u32,
+                key: schedule.key.iter().map(|b| format!("{:02x}", b)).collect(),
+                word_order: schedule.word_order,
+                bit_errors: schedule.bit_errors,
+                count: 1,
+            });
+        }
+    }
+}
+
+/// Collapse keys seen more than once, from the same process, into the
+/// first occurrence
+pub fn aggregate(keys: Vec<FoundAesKey>) -> Vec<FoundAesKey> {
+    let mut unique: Vec<FoundAesKey> = Vec::new();
+    let mut seen: HashMap<(Option<i32>, String), usize> = HashMap::new();
+    for key in keys {
+        let id = (key.location.pid, key.key.clone());
+        match seen.get(&id) {
+            Some(&index) => unique[index].count += key.count,
+            None => {
+                seen.insert(id, unique.len());
+                unique.push(key);
+            }
+        }
+    }
+    unique
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps::parse_maps_line;
+    use crate::reader::ProcfsReader;
+    use crate::scan::{self, Scan, ScanControl};
+
+    /// The AES-128 key of FIPS 197 appendix A.1
+    const KEY_128: [u8; 16] = *b"\x2b\x7e\x15\x16\x28\xae\xd2\xa6\xab\xf7\x15\x88\x09\xcf\x4f\x3c";
+
+    #[test]
+    fn test_expand_key() {
+        assert_eq!((sbox()[0x00], sbox()[0x53], sbox()[0xff]), (0x63, 0xed, 0x16));
+        let words = expand_key(&KEY_128);
+        assert_eq!((words.len(), words[4], words[43]), (44, 0xa0fafe17, 0xb6630ca6));
+
+        // FIPS 197 appendix A.3
+        let key: Vec<u8> = (0..32).map(|i| [0x60, 0x3d, 0xeb, 0x10][i % 4] ^ i as u8).collect();
+        let words = expand_key(&key);
+        assert_eq!(words.len(), 60);
+        assert_eq!(schedule_errors(sbox(), &words, 8, 0), Some(0));
+        assert_eq!(schedule_errors(sbox(), &words, 4, 0), None);
+    }
+
+    #[test]
+    fn test_finds_schedules() {
+        let schedule = |key: &[u8], little: bool| -> Vec<u8> {
+            let words = expand_key(key);
+            let bytes = |word: &u32| if little { word.to_le_bytes() } else { word.to_be_bytes() };
+            words.iter().flat_map(bytes).collect()
+        };
+        let key_256: Vec<u8> = (0..32).collect();
+        let mut data = vec![0u8; 0x3000];
+        let at = |data: &mut Vec<u8>, offset: usize, bytes: &[u8]| {
+            data[offset..offset + bytes.len()].copy_from_slice(bytes);
+        };
+        at(&mut data, 0x104, &schedule(&KEY_128, false));
+        at(&mut data, 0xfc0, &schedule(&key_256, true));
+        at(&mut data, 0x2000, &schedule(&KEY_128, false));
+        // A flipped bit, found only when errors are allowed, and counted in
+        // the word it is in and the two derived from it
+        let mut decayed = schedule(&key_256[..24], false);
+        decayed[100] ^= 0x10;
+        at(&mut data, 0x2800, &decayed);
+        let start = data.as_ptr() as u64;
+        let end = start + data.len() as u64;
+        let region = parse_maps_line(&format!("{:x}-{:x} rw-p 00000000 00:00 0", start, end));
+
+        let pid = std::process::id() as i32;
+        let mut reader = ProcfsReader::open(pid).unwrap();
+        let regions = [region.unwrap()];
+        let find = |reader: &mut ProcfsReader, max_bit_errors: u32| {
+            let mut scan = Scan::default();
+            let mut visitor = AesKeyVisitor::new(&AesKeyOptions { max_bit_errors });
+            // Chunks of a page, so the AES-256 schedule straddles two
+            let mut control = ScanControl::new().chunk_size(Some(0x1000));
+            scan::walk_regions(reader, &regions, &mut visitor, &mut scan, &mut control);
+            aggregate(scan.results)
+        };
+
+        let keys = find(&mut reader, 0);
+        assert_eq!(keys.len(), 2);
+        assert_eq!((keys[0].location.address, keys[0].count), (start + 0x104, 2));
+        assert_eq!(keys[0].bits, 128);
+        assert_eq!(keys[0].key, "2b7e151628aed2a6abf7158809cf4f3c");
+        assert_eq!((keys[1].bits, keys[1].word_order), (256, WordOrder::LittleEndian));
+        assert_eq!(keys[1].key, "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f");
+
+        let keys = find(&mut reader, 4);
+        assert_eq!(keys.len(), 3);
+        assert_eq!((keys[2].location.address, keys[2].bits), (start + 0x2800, 192));
+        assert_eq!(keys[2].bit_errors, 3);
+    }
+}
@@ -0,0 +1,1547 @@
+//! Java objects in the heap of an ART process, found without its help
+//!
+//! The runtime keeps no map of its objects that can be read from outside,
+//! so objects are recognized by their layout instead. Every object starts
+//! with a 32-bit reference to its class and a monitor word, 8-byte
+//! aligned; a `java.lang.String` follows them with
+//!
+//! - `count`: the length shifted left by one, the low bit clear if the
+//!   characters are stored compressed, one byte each (Android 8 and later)
+//! - `hash`: the cached `hashCode()`, or 0 until it is first computed
+//! - the characters
+//!
+//! A cached hash that matches the characters is a 32-bit check no random
+//! data passes, so the class most such strings point at is taken to be
+//! `java.lang.String`; every well-formed object of that class is then a
+//! string, hashed or not.
+//!
+//! Loaded classes are read from the runtime's class tables. libart.so's
+//! `Runtime::instance_` points at the `Runtime`, whose `class_linker_`
+//! holds the boot class table and the list of class loaders, each with a
+//! table of its own; a table's `ClassSet`s hold a reference to each class.
+//! Where those fields are moves from release to release, so each is found
+//! by searching its structure for what holds up as one: a list whose links
+//! lead back to its head, a hash set as many of whose slots are taken as
+//! it counts elements, and classes that are all instances of
+//! `java.lang.Class`, the one object whose class reference points at
+//! itself. Where the tables can't be found, as when libart.so's symbols
+//! can't be read for a dump pulled from another device, the heap is
+//! searched for instances of that self-referencing class instead
+//! ([`search_classes`]), which finds classes no table lists as well as
+//! chance matches. A class is named from the descriptor its dex file gives
+//! its type, so names are right even when no `getName()` has cached one.
+//!
+//! For a heap dump, [`JavaHeap`] lays each class out from its class object
+//! and the `ArtField`s it lists, and [`walk_objects`] walks the heap spaces
+//! object by object: every space lays its objects out one after another,
+//! so an instance of a known class is taken to be an object, sized by its
+//! class, and the walk goes on after it.
+
+use crate::maps::MemoryRegion;
+use crate::pointers;
+use crate::reader::{self, MemoryReader};
+use crate::scan::{self, RegionVisitor, Scan, ScanControl, ScanSink, StopReason};
+use crate::symbols::SymbolTable;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::mem::size_of;
+use std::path::Path;
+
+/// Size of a `java.lang.String` before its characters
+pub const STRING_HEADER: u64 = 16;
+
+const OBJECT_ALIGN: usize = 8;
+
+/// Native pointers in the runtime, which has the ABI of this library
+const WORD: usize = size_of::<usize>();
+
+/// Offsets in `mirror::Class` (Android 8 and later)
+const CLASS_LOADER: u64 = 8;
+const CLASS_COMPONENT_TYPE: u64 = 12;
+const CLASS_DEX_CACHE: u64 = 16;
+const CLASS_NAME: u64 = 28;
+const CLASS_SUPER_CLASS: u64 = 32;
+const CLASS_IFIELDS: u64 = 40;
+const CLASS_SFIELDS: u64 = 56;
+const CLASS_CLASS_SIZE: u64 = 72;
+const CLASS_DEX_TYPE_INDEX: u64 = 84;
+const CLASS_OBJECT_SIZE: u64 = 96;
+const CLASS_PRIMITIVE_TYPE: u64 = 104;
+
+/// Bytes of a class object read to size it, through `class_size_`
+const CLASS_HEADER: usize = CLASS_CLASS_SIZE as usize + 4;
+
+/// An `art::ArtField`: `declaring_class_`, `access_flags_`,
+/// `field_dex_idx_`, then `offset_`. A class's fields are a
+/// `LengthPrefixedArray` of them, its 32-bit length first.
+const FIELD_SIZE: usize = 16;
+const FIELD_DEX_INDEX: usize = 8;
+const FIELD_OFFSET: usize = 12;
+
+/// Dex files hold no more fields than this
+const MAX_FIELDS: u32 = 1 << 16;
+
+/// Offset in `mirror::Array` of its length, after which its elements
+/// start, 8-byte aligned if they are 8 bytes each
+const ARRAY_LENGTH: u64 = 8;
+
+/// Bigger objects are rare enough to leave out of a heap walk rather than
+/// hold in memory
+const MAX_OBJECT_SIZE: u64 = 64 << 20;
+
+/// Offsets in `mirror::DexCache`: `location_` moved when `class_loader_`
+/// was added before it in Android 12
+const DEX_CACHE_LOCATIONS: [u64; 2] = [12, 8];
+const DEX_CACHE_DEX_FILE: u64 = 16;
+
+/// `art::Runtime::instance_`, where the runtime keeps a pointer to itself
+const RUNTIME_INSTANCE: &str = "_ZN3art7Runtime9instance_E";
+
+/// Bytes of the `Runtime` searched for its `class_linker_`, and of the
+/// `ClassLinker` for its `boot_class_table_` and `class_loaders_`; the
+/// fields move between releases but stay well within these
+const RUNTIME_SIZE: usize = 0x800;
+const CLASS_LINKER_SIZE: usize = 0x300;
+
+/// Bytes of a `ClassTable` searched for its `classes_` vector, which
+/// follows its lock
+const CLASS_TABLE_SIZE: usize = 0x80;
+
+/// Offsets in `art::HashSet`, a table's `ClassSet`: four empty function
+/// objects, `num_elements_`, `num_buckets_`, `elements_until_expand_`,
+/// `owns_data_` and `data_`, then the two load factors
+const SET_ELEMENTS: usize = WORD;
+const SET_BUCKETS: usize = 2 * WORD;
+const SET_DATA: usize = 5 * WORD;
+const SET_LOAD_FACTORS: usize = 6 * WORD;
+const SET_SIZE: usize = SET_LOAD_FACTORS + 16;
+
+/// Limits on what is taken for a class table, far past any app's
+const MAX_CLASS_SETS: u64 = 64;
+const MAX_BUCKETS: u64 = 1 << 24;
+const MAX_CLASS_LOADERS: u64 = 1 << 16;
+
+/// Array classes nest no deeper than this in the JVM
+const MAX_ARRAY_DIMENSIONS: usize = 255;
+
+/// Longer strings exist, but are too rare to be worth the false matches
+const MAX_STRING_LENGTH: u32 = 1 << 20;
+
+/// A `java.lang.String` found in the heap
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JavaString {
+    /// Address of the object
+    pub address: u64,
+    /// Reference to its class
+    pub class: u32,
+    pub value: String,
+    /// Stored one byte per character rather than as UTF-16
+    pub compressed: bool,
+    /// Its cached hash was set, and matched
+    pub hashed: bool,
+}
+
+impl JavaString {
+    /// Number of UTF-16 units, the length Java sees
+    pub fn length(&self) -> usize {
+        self.value.encode_utf16().count()
+    }
+}
+
+/// Whether `region` is one of the spaces ART allocates objects in,
+/// including the boot image
+pub fn is_heap(region: &MemoryRegion) -> bool {
+    region.path.as_deref().is_some_and(|path| {
+        let space = path.starts_with("[anon:dalvik-")
+            && (path.contains(" space") && !path.contains("bitmap") || path.ends_with(".art]"));
+        space || path.ends_with(".art")
+    })
+}
+
+/// Find every `java.lang.String` in `regions`, which should be the heap
+/// spaces (see [`is_heap`]).
+///
+/// Nothing is found until the scan has seen enough strings with a cached
+/// hash to tell which class is `java.lang.String`, which any app's heap
+/// has thousands of.
+pub fn find_strings(
+    reader: &mut dyn MemoryReader,
+    regions: &[MemoryRegion],
+    scan: &mut Scan<JavaString>,
+    control: &mut ScanControl,
+) -> Option<StopReason> {
+    let mut candidates = Scan::default();
+    let mut visitor = StringObjectVisitor::default();
+    let stopped = scan::walk_regions(reader, regions, &mut visitor, &mut candidates, control);
+
+    scan.regions.extend(candidates.regions);
+    if let Some(class) = string_class(&candidates.results) {
+        scan.results
+            .extend(candidates.results.into_iter().filter(|string| string.class == class));
+    }
+    stopped
+}
+
+/// The class most strings with a matching hash refer to
+fn string_class(candidates: &[JavaString]) -> Option<u32> {
+    let mut votes: HashMap<u32, usize> = HashMap::new();
+    for string in candidates.iter().filter(|string| string.hashed) {
+        *votes.entry(string.class).or_default() += 1;
+    }
+    votes.into_iter().max_by_key(|&(_, count)| count).map(|(class, _)| class)
+}
+
+/// `String.hashCode()` of UTF-16 units
+pub fn java_hash(units: impl Iterator<Item = u16>) -> u32 {
+    units.fold(0u32, |hash, unit| hash.wrapping_mul(31).wrapping_add(u32::from(unit)))
+}
+
+/// Header fields of what may be a string
+#[derive(Debug, Clone, Copy)]
+struct Header {
+    address: u64,
+    class: u32,
+    count: u32,
+    hash: u32,
+}
+
+impl Header {
+    fn parse(address: u64, bytes: &[u8]) -> Option<Self> {
+        let word = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+        let (class, count) = (word(0), word(8));
+        let length = count >> 1;
+        if class == 0 || !(class as usize).is_multiple_of(OBJECT_ALIGN) || length == 0 {
+            return None;
+        }
+        (length <= MAX_STRING_LENGTH).then_some(Header {
+            address,
+            class,
+            count,
+            hash: word(12),
+        })
+    }
+
+    fn compressed(&self) -> bool {
+        self.count & 1 == 0
+    }
+
+    /// Bytes of characters after the header
+    fn payload(&self) -> usize {
+        let length = (self.count >> 1) as usize;
+        if self.compressed() {
+            length
+        } else {
+            2 * length
+        }
+    }
+
+    /// The string, if `payload` holds well-formed characters matching the
+    /// cached hash
+    fn decode(&self, payload: &[u8]) -> Option<JavaString> {
+        let payload = payload.get(..self.payload())?;
+        let (value, hash) = if self.compressed() {
+            // Only 0x01..=0x7f are stored compressed
+            if !payload.iter().all(|&byte| (1..0x80).contains(&byte)) {
+                return None;
+            }
+            let value = String::from_utf8(payload.to_vec()).ok()?;
+            (value, java_hash(payload.iter().map(|&byte| u16::from(byte))))
+        } else {
+            let units: Vec<u16> = payload
+                .chunks_exact(2)
+                .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+                .collect();
+            // All-ASCII text would have been compressed
+            if units.iter().all(|&unit| (1..0x80).contains(&unit)) {
+                return None;
+            }
+            (String::from_utf16(&units).ok()?, java_hash(units.iter().copied()))
+        };
+        if self.hash != 0 && self.hash != hash {
+            return None;
+        }
+        Some(JavaString {
+            address: self.address,
+            class: self.class,
+            value,
+            compressed: self.compressed(),
+            hashed: self.hash != 0,
+        })
+    }
+}
+
+/// Collects strings of any class, keeping the bytes of the last chunk that
+/// a header may start in
+#[derive(Default)]
+struct StringObjectVisitor {
+    /// Unscanned bytes carried over, and the address they start at
+    carry: Vec<u8>,
+    carry_start: u64,
+    found: Vec<JavaString>,
+    /// Headers whose characters run past the bytes seen so far
+    pending: Vec<Header>,
+}
+
+impl RegionVisitor for StringObjectVisitor {
+    type Output = JavaString;
+
+    fn visit_chunk(&mut self, region: &MemoryRegion, address: u64, chunk: &[u8]) {
+        if self.carry_start + self.carry.len() as u64 != address {
+            self.carry.clear();
+            // Objects are aligned from the start of the space
+            let skew = ((address - region.start) % OBJECT_ALIGN as u64) as usize;
+            let skip = (OBJECT_ALIGN - skew) % OBJECT_ALIGN;
+            self.carry_start = address + skip as u64;
+            self.carry.extend_from_slice(chunk.get(skip..).unwrap_or_default());
+        } else {
+            self.carry.extend_from_slice(chunk);
+        }
+
+        let header = STRING_HEADER as usize;
+        let mut at = 0;
+        while at + header <= self.carry.len() {
+            let object = self.carry_start + at as u64;
+            if let Some(parsed) = Header::parse(object, &self.carry[at..at + header]) {
+                let payload = &self.carry[at + header..];
+                if parsed.payload() <= payload.len() {
+                    self.found.extend(parsed.decode(payload));
+                } else if object + STRING_HEADER + parsed.payload() as u64 <= region.end {
+                    self.pending.push(parsed);
+                }
+            }
+            at += OBJECT_ALIGN;
+        }
+        self.carry.drain(..at);
+        self.carry_start += at as u64;
+    }
+
+    fn end_region(
+        &mut self,
+        _region: &MemoryRegion,
+        reader: &mut dyn MemoryReader,
+        sink: &mut dyn ScanSink<JavaString>,
+    ) {
+        self.carry.clear();
+        self.carry_start = 0;
+
+        for header in self.pending.drain(..) {
+            let mut payload = vec![0u8; header.payload()];
+            let address = header.address + STRING_HEADER;
+            if reader::read_into(reader, address, &mut payload).ok() == Some(payload.len()) {
+                self.found.extend(header.decode(&payload));
+            }
+        }
+        self.found.sort_by_key(|string| string.address);
+        for string in self.found.drain(..) {
+            sink.result(string);
+        }
+    }
+}
+
+/// The string object at `address`, if there is a well-formed one
+pub fn read_string(reader: &mut dyn MemoryReader, address: u64) -> Option<String> {
+    let mut header = [0u8; STRING_HEADER as usize];
+    if reader::read_into(reader, address, &mut header).ok()? < header.len() {
+        return None;
+    }
+    let header = Header::parse(address, &header)?;
+    let mut payload = vec![0u8; header.payload()];
+    if reader::read_into(reader, address + STRING_HEADER, &mut payload).ok()? < payload.len() {
+        return None;
+    }
+    header.decode(&payload).map(|string| string.value)
+}
+
+/// A class loaded in the runtime
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JavaClass {
+    /// Address of its `java.lang.Class` object
+    pub address: u64,
+    /// Binary name, e.g. `java.lang.String` or `int[]`
+    pub name: String,
+    /// Where the dex file defining it was loaded from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dex_location: Option<String>,
+}
+
+/// Find the classes in the runtime's class tables (see the
+/// [module docs](self)), in address order; `regions` are every mapping of
+/// the process, libart.so's among them.
+///
+/// Fails, saying why, where the tables can't be found, for the caller to
+/// fall back on [`search_classes`]. Classes that can't be named are left
+/// out.
+pub fn find_linked_classes(
+    reader: &mut dyn MemoryReader,
+    regions: &[MemoryRegion],
+    scan: &mut Scan<JavaClass>,
+    control: &mut ScanControl,
+) -> Result<Option<StopReason>, String> {
+    let runtime = runtime(reader, regions)?;
+    runtime_classes(reader, regions, runtime, scan, control)
+}
+
+/// Address of the `art::Runtime`, read from `Runtime::instance_`
+fn runtime(reader: &mut dyn MemoryReader, regions: &[MemoryRegion]) -> Result<u64, String> {
+    let modules = pointers::modules(regions);
+    let libart = modules
+        .iter()
+        .find(|module| module.name() == "libart.so")
+        .ok_or("libart.so isn't mapped")?;
+    let symbols = SymbolTable::read(Path::new(&libart.path))
+        .map_err(|e| format!("Failed to read the symbols of {}: {}", libart.path, e))?
+        .ok_or_else(|| format!("{} isn't an ELF file", libart.path))?;
+    let instance = symbols
+        .address(RUNTIME_INSTANCE, libart.base)
+        .ok_or_else(|| format!("{} doesn't define {}", libart.path, RUNTIME_INSTANCE))?;
+    read_word(reader, instance).ok_or_else(|| "Runtime::instance_ isn't set".to_string())
+}
+
+/// The classes in the tables of the `Runtime` at `runtime`
+fn runtime_classes(
+    reader: &mut dyn MemoryReader,
+    regions: &[MemoryRegion],
+    runtime: u64,
+    scan: &mut Scan<JavaClass>,
+    control: &mut ScanControl,
+) -> Result<Option<StopReason>, String> {
+    let mapped = Mapped::new(regions);
+    // Of the words of the runtime that could be its class linker, the one
+    // whose tables list the most classes
+    let mut classes = read_words(reader, runtime, RUNTIME_SIZE)
+        .into_iter()
+        .filter(|&word| mapped.contains(word))
+        .map(|linker| class_linker_classes(reader, &mapped, linker))
+        .max_by_key(Vec::len)
+        .filter(|classes| !classes.is_empty())
+        .ok_or("No class tables found in the runtime")?;
+    classes.sort_unstable();
+    classes.dedup();
+
+    let mut namer = ClassNamer::default();
+    for address in classes {
+        if let Some(stopped) = control.check() {
+            return Ok(Some(stopped));
+        }
+        scan.results.extend(namer.class(reader, address));
+    }
+    Ok(None)
+}
+
+/// The classes in the tables the `ClassLinker` at `linker` would hold,
+/// none if it isn't one
+fn class_linker_classes(
+    reader: &mut dyn MemoryReader,
+    mapped: &Mapped,
+    linker: u64,
+) -> Vec<u64> {
+    let words = read_words(reader, linker, CLASS_LINKER_SIZE);
+    let mut sets = HashSet::new();
+    let mut classes = Vec::new();
+    for (i, &word) in words.iter().enumerate() {
+        // `boot_class_table_`
+        table_classes(reader, mapped, word, &mut sets, &mut classes);
+        // `class_loaders_`, each a weak root to the loader, then its table
+        let head = linker + (i * WORD) as u64;
+        for node in list_nodes(reader, mapped, head, &words[i..]) {
+            if let Some(table) = read_word(reader, node + 3 * WORD as u64) {
+                table_classes(reader, mapped, table, &mut sets, &mut classes);
+            }
+        }
+    }
+
+    // Every class is an instance of java.lang.Class, the class most
+    // entries are instances of that is an instance of itself
+    let class_of: Vec<(u64, u64)> = classes
+        .into_iter()
+        .filter_map(|class| Some((class, u64::from(read_u32(reader, class)?))))
+        .collect();
+    let mut votes: HashMap<u64, usize> = HashMap::new();
+    for &(_, of) in &class_of {
+        *votes.entry(of).or_default() += 1;
+    }
+    let mut votes: Vec<(u64, usize)> = votes.into_iter().collect();
+    votes.sort_unstable_by_key(|&(of, count)| (std::cmp::Reverse(count), of));
+    let Some(class_class) = votes
+        .into_iter()
+        .map(|(of, _)| of)
+        .find(|&of| read_u32(reader, of).is_some_and(|class| u64::from(class) == of))
+    else {
+        return Vec::new();
+    };
+    class_of.into_iter().filter(|&(_, of)| of == class_class).map(|(class, _)| class).collect()
+}
+
+/// The nodes of the `std::list` whose head, its `prev`, `next` and `size`,
+/// is at `head` and starts `words`; none unless they link up as a list's do
+fn list_nodes(
+    reader: &mut dyn MemoryReader,
+    mapped: &Mapped,
+    head: u64,
+    words: &[u64],
+) -> Vec<u64> {
+    let &[_, first, size, ..] = words else {
+        return Vec::new();
+    };
+    if !(1..=MAX_CLASS_LOADERS).contains(&size) {
+        return Vec::new();
+    }
+    let mut nodes = Vec::new();
+    let (mut previous, mut node) = (head, first);
+    while node != head {
+        if nodes.len() as u64 == size || !mapped.contains(node) {
+            return Vec::new();
+        }
+        let &[prev, next] = read_words(reader, node, 2 * WORD).as_slice() else {
+            return Vec::new();
+        };
+        if prev != previous {
+            return Vec::new();
+        }
+        nodes.push(node);
+        (previous, node) = (node, next);
+    }
+    if nodes.len() as u64 == size {
+        nodes
+    } else {
+        Vec::new()
+    }
+}
+
+/// Add the entries of the `ClassTable` at `table` to `classes`, skipping
+/// the sets in `sets`, which it adds to
+fn table_classes(
+    reader: &mut dyn MemoryReader,
+    mapped: &Mapped,
+    table: u64,
+    sets: &mut HashSet<u64>,
+    classes: &mut Vec<u64>,
+) {
+    if !mapped.contains(table) {
+        return;
+    }
+    let words = read_words(reader, table, CLASS_TABLE_SIZE);
+    // `classes_`, a vector of sets: `begin`, `end`, then `end_cap`
+    for vector in words.windows(3) {
+        let (begin, end, capacity) = (vector[0], vector[1], vector[2]);
+        let size = end.wrapping_sub(begin);
+        let count = size / SET_SIZE as u64;
+        if end < begin
+            || capacity < end
+            || !(1..=MAX_CLASS_SETS).contains(&count)
+            || size % SET_SIZE as u64 != 0
+            || !mapped.contains(begin)
+        {
+            continue;
+        }
+        let mut bytes = vec![0u8; size as usize];
+        if reader::read_into(reader, begin, &mut bytes).ok() != Some(bytes.len()) {
+            continue;
+        }
+        let found: Option<Vec<(u64, Vec<u64>)>> =
+            bytes.chunks_exact(SET_SIZE).map(|set| set_classes(reader, set)).collect();
+        for (data, found) in found.into_iter().flatten() {
+            if sets.insert(data) {
+                classes.extend(found);
+            }
+        }
+    }
+}
+
+/// The `data_` of the `ClassSet` held in `set`, and the classes in its
+/// slots; `None` unless its sizes, load factors and slots agree
+fn set_classes(reader: &mut dyn MemoryReader, set: &[u8]) -> Option<(u64, Vec<u64>)> {
+    let word = |at: usize| {
+        let mut bytes = [0u8; 8];
+        bytes[..WORD].copy_from_slice(&set[at..at + WORD]);
+        u64::from_le_bytes(bytes)
+    };
+    let factor = |at: usize| f64::from_le_bytes(set[at..at + 8].try_into().unwrap());
+    let (elements, buckets, data) = (word(SET_ELEMENTS), word(SET_BUCKETS), word(SET_DATA));
+    let (min, max) = (factor(SET_LOAD_FACTORS), factor(SET_LOAD_FACTORS + 8));
+    if data == 0
+        || elements > buckets
+        || !(1..=MAX_BUCKETS).contains(&buckets)
+        || !(min > 0.0 && min < max && max <= 1.0)
+    {
+        return None;
+    }
+
+    let mut slots = vec![0u8; buckets as usize * 4];
+    if reader::read_into(reader, data, &mut slots).ok()? < slots.len() {
+        return None;
+    }
+    // Each taken slot is a reference to a class, its low bits some of the
+    // class's hash
+    let classes: Vec<u64> = slots
+        .chunks_exact(4)
+        .map(|slot| u32::from_le_bytes(slot.try_into().unwrap()))
+        .filter(|&slot| slot != 0)
+        .map(|slot| u64::from(slot & !(OBJECT_ALIGN as u32 - 1)))
+        .collect();
+    (classes.len() as u64 == elements).then_some((data, classes))
+}
+
+/// The readable mappings of a process, to tell words that can't be
+/// pointers without reading them
+struct Mapped(Vec<(u64, u64)>);
+
+impl Mapped {
+    fn new(regions: &[MemoryRegion]) -> Self {
+        let mut ranges: Vec<(u64, u64)> = regions
+            .iter()
+            .filter(|region| region.perms.read)
+            .map(|region| (region.start, region.end))
+            .collect();
+        ranges.sort_unstable();
+        Mapped(ranges)
+    }
+
+    fn contains(&self, address: u64) -> bool {
+        let index = self.0.partition_point(|&(start, _)| start <= address);
+        index > 0 && address < self.0[index - 1].1
+    }
+}
+
+/// Find the classes loaded in the heap spaces `regions` (see the
+/// [module docs](self)), in address order, by searching them for instances
+/// of `java.lang.Class`: the fallback for [`find_linked_classes`].
+///
+/// Classes that can't be named are left out; `None` means there's no
+/// `java.lang.Class` among the regions.
+pub fn search_classes(
+    reader: &mut dyn MemoryReader,
+    regions: &[MemoryRegion],
+    scan: &mut Scan<JavaClass>,
+    control: &mut ScanControl,
+) -> Option<StopReason> {
+    let mut candidates = Scan::default();
+    let mut visitor = ObjectVisitor::default();
+    let stopped = scan::walk_regions(reader, regions, &mut visitor, &mut candidates, control);
+    if stopped.is_some() {
+        scan.regions.extend(candidates.regions);
+        return stopped;
+    }
+
+    // Chance self-references aside, the candidate most objects are
+    // instances of is java.lang.Class
+    let mut classes = Scan::default();
+    let mut visitor = ObjectVisitor {
+        instances: true,
+        classes: candidates.results.iter().map(|&class| (class, Vec::new())).collect(),
+        found: Vec::new(),
+    };
+    let stopped = scan::walk_regions(reader, regions, &mut visitor, &mut classes, control);
+    scan.regions.extend(classes.regions);
+    let Some((_, objects)) = visitor.classes.into_iter().max_by_key(|(_, objects)| objects.len())
+    else {
+        return stopped;
+    };
+
+    let mut namer = ClassNamer::default();
+    for address in objects {
+        scan.results.extend(namer.class(reader, address));
+    }
+    stopped
+}
+
+/// In a first walk, finds the objects whose class reference points at
+/// themselves; in a second, with `instances` set, the instances of each of
+/// `classes`
+#[derive(Default)]
+struct ObjectVisitor {
+    instances: bool,
+    classes: HashMap<u64, Vec<u64>>,
+    found: Vec<u64>,
+}
+
+impl RegionVisitor for ObjectVisitor {
+    type Output = u64;
+
+    fn visit_chunk(&mut self, region: &MemoryRegion, address: u64, chunk: &[u8]) {
+        let skew = ((address - region.start) % OBJECT_ALIGN as u64) as usize;
+        let first = (OBJECT_ALIGN - skew) % OBJECT_ALIGN;
+        let Some(chunk) = chunk.get(first..) else {
+            return;
+        };
+        let start = address + first as u64;
+
+        for (i, word) in chunk.chunks_exact(OBJECT_ALIGN).enumerate() {
+            let class = u64::from(u32::from_le_bytes(word[..4].try_into().unwrap()));
+            let object = start + (i * OBJECT_ALIGN) as u64;
+            if !self.instances {
+                if class == object {
+                    self.found.push(object);
+                }
+            } else if let Some(objects) = self.classes.get_mut(&class) {
+                objects.push(object);
+            }
+        }
+    }
+
+    fn end_region(
+        &mut self,
+        _region: &MemoryRegion,
+        _reader: &mut dyn MemoryReader,
+        sink: &mut dyn ScanSink<u64>,
+    ) {
+        for object in self.found.drain(..) {
+            sink.result(object);
+        }
+    }
+}
+
+/// Names classes from their dex files, caching what each dex cache says
+#[derive(Default)]
+struct ClassNamer {
+    dex_files: HashMap<u64, Option<DexFile>>,
+}
+
+/// The parts of a dex file in memory needed to look up type descriptors
+#[derive(Clone, Copy)]
+struct DexFile {
+    /// Base of string data offsets: `begin` for standard dex files, the
+    /// shared data section for compact ones
+    data: u64,
+    string_ids: u64,
+    string_count: u32,
+    type_ids: u64,
+    type_count: u32,
+    field_ids: u64,
+    field_count: u32,
+}
+
+impl ClassNamer {
+    /// The class at `address`, if it can be named
+    fn class(&mut self, reader: &mut dyn MemoryReader, address: u64) -> Option<JavaClass> {
+        let name = self.name(reader, address, 0)?;
+        let dex_location = self.dex_location(reader, address);
+        Some(JavaClass {
+            address,
+            name,
+            dex_location,
+        })
+    }
+
+    fn name(&mut self, reader: &mut dyn MemoryReader, class: u64, depth: usize) -> Option<String> {
+        let descriptor = self.descriptor(reader, class, depth)?;
+        descriptor_to_name(&descriptor)
+    }
+
+    /// Descriptor of `class`, e.g. `Ljava/lang/String;`
+    fn descriptor(
+        &mut self,
+        reader: &mut dyn MemoryReader,
+        class: u64,
+        depth: usize,
+    ) -> Option<String> {
+        let dex_cache = read_u32(reader, class + CLASS_DEX_CACHE)?;
+        if dex_cache != 0 {
+            let type_index = read_u32(reader, class + CLASS_DEX_TYPE_INDEX)?;
+            let dex = self.dex_file(reader, u64::from(dex_cache))?;
+            return dex.descriptor(reader, type_index);
+        }
+
+        // Arrays and primitives have no dex file of their own
+        let component = read_u32(reader, class + CLASS_COMPONENT_TYPE)?;
+        if component != 0 && depth < MAX_ARRAY_DIMENSIONS {
+            let component = self.descriptor(reader, u64::from(component), depth + 1)?;
+            return Some(format!("[{}", component));
+        }
+        let name = read_u32(reader, class + CLASS_NAME)?;
+        let name = read_string(reader, u64::from(name)).filter(|_| name != 0)?;
+        primitive_descriptor(&name).map(String::from)
+    }
+
+    fn dex_file(&mut self, reader: &mut dyn MemoryReader, dex_cache: u64) -> Option<DexFile> {
+        *self.dex_files.entry(dex_cache).or_insert_with(|| {
+            let dex_file = read_word(reader, dex_cache + DEX_CACHE_DEX_FILE)?;
+            DexFile::read(reader, dex_file)
+        })
+    }
+
+    fn dex_location(&mut self, reader: &mut dyn MemoryReader, class: u64) -> Option<String> {
+        let dex_cache = u64::from(read_u32(reader, class + CLASS_DEX_CACHE)?);
+        if dex_cache == 0 {
+            return None;
+        }
+        DEX_CACHE_LOCATIONS.iter().find_map(|&offset| {
+            let location = read_u32(reader, dex_cache + offset)?;
+            read_string(reader, u64::from(location))
+                .filter(|location| location.starts_with('/') || location.starts_with('['))
+        })
+    }
+}
+
+impl DexFile {
+    /// Read the `art::DexFile` at `address`: a vtable, `begin_`, `size_`,
+    /// then `data_begin_`
+    fn read(reader: &mut dyn MemoryReader, address: u64) -> Option<Self> {
+        let begin = read_word(reader, address + WORD as u64)?;
+        let mut header = [0u8; 0x70];
+        if reader::read_into(reader, begin, &mut header).ok()? < header.len() {
+            return None;
+        }
+        let data = match &header[..4] {
+            b"dex\n" => begin,
+            b"cdex" => read_word(reader, address + 3 * WORD as u64)?,
+            _ => return None,
+        };
+        let field = |at: usize| u32::from_le_bytes(header[at..at + 4].try_into().unwrap());
+        Some(DexFile {
+            data,
+            string_count: field(0x38),
+            string_ids: begin + u64::from(field(0x3c)),
+            type_count: field(0x40),
+            type_ids: begin + u64::from(field(0x44)),
+            field_count: field(0x50),
+            field_ids: begin + u64::from(field(0x54)),
+        })
+    }
+
+    fn descriptor(&self, reader: &mut dyn MemoryReader, type_index: u32) -> Option<String> {
+        if type_index >= self.type_count {
+            return None;
+        }
+        let string_index = read_u32(reader, self.type_ids + 4 * u64::from(type_index))?;
+        self.string(reader, string_index)
+    }
+
+    /// Name and type descriptor of field `field_index`, from its
+    /// `field_id_item`: the 16-bit indexes of its class and type, then
+    /// that of its name
+    fn field(&self, reader: &mut dyn MemoryReader, field_index: u32) -> Option<(String, String)> {
+        if field_index >= self.field_count {
+            return None;
+        }
+        let mut item = [0u8; 8];
+        let address = self.field_ids + 8 * u64::from(field_index);
+        if reader::read_into(reader, address, &mut item).ok()? < item.len() {
+            return None;
+        }
+        let type_index = u16::from_le_bytes([item[2], item[3]]);
+        let name_index = u32::from_le_bytes(item[4..].try_into().unwrap());
+        let name = self.string(reader, name_index)?;
+        Some((name, self.descriptor(reader, u32::from(type_index))?))
+    }
+
+    fn string(&self, reader: &mut dyn MemoryReader, string_index: u32) -> Option<String> {
+        if string_index >= self.string_count {
+            return None;
+        }
+        let offset = read_u32(reader, self.string_ids + 4 * u64::from(string_index))?;
+        let mut data = [0u8; 512];
+        let read = reader::read_into(reader, self.data + u64::from(offset), &mut data).ok()?;
+        // A ULEB128 length in UTF-16 units, then NUL-terminated MUTF-8
+        let data = &data[..read];
+        let start = data.iter().position(|&byte| byte & 0x80 == 0)? + 1;
+        let end = start + data.get(start..)?.iter().position(|&byte| byte == 0)?;
+        String::from_utf8(data[start..end].to_vec()).ok()
+    }
+}
+
+/// Type of a field or of an array's elements
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind {
+    Object,
+    Boolean,
+    Byte,
+    Char,
+    Short,
+    Int,
+    Long,
+    Float,
+    Double,
+}
+
+impl FieldKind {
+    fn from_descriptor(descriptor: &str) -> Option<Self> {
+        Some(match descriptor.as_bytes().first()? {
+            b'L' | b'[' => FieldKind::Object,
+            b'Z' => FieldKind::Boolean,
+            b'B' => FieldKind::Byte,
+            b'C' => FieldKind::Char,
+            b'S' => FieldKind::Short,
+            b'I' => FieldKind::Int,
+            b'J' => FieldKind::Long,
+            b'F' => FieldKind::Float,
+            b'D' => FieldKind::Double,
+            _ => return None,
+        })
+    }
+
+    /// From the low half of `mirror::Class::primitive_type_`; `None` for
+    /// `void`
+    fn from_primitive(primitive: u32) -> Option<Self> {
+        Some(match primitive & 0xffff {
+            0 => FieldKind::Object,
+            1 => FieldKind::Boolean,
+            2 => FieldKind::Byte,
+            3 => FieldKind::Char,
+            4 => FieldKind::Short,
+            5 => FieldKind::Int,
+            6 => FieldKind::Long,
+            7 => FieldKind::Float,
+            8 => FieldKind::Double,
+            _ => return None,
+        })
+    }
+
+    /// Bytes a value takes in the heap, where references are 32 bits
+    pub fn size(self) -> usize {
+        match self {
+            FieldKind::Boolean | FieldKind::Byte => 1,
+            FieldKind::Char | FieldKind::Short => 2,
+            FieldKind::Object | FieldKind::Int | FieldKind::Float => 4,
+            FieldKind::Long | FieldKind::Double => 8,
+        }
+    }
+}
+
+/// A field of a class
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JavaField {
+    pub name: String,
+    pub kind: FieldKind,
+    /// Where its value is in an instance or, for a static field, in the
+    /// class object
+    pub offset: u32,
+}
+
+/// A class as a heap dump describes it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClassLayout {
+    pub class: JavaClass,
+    /// References to its superclass and class loader, 0 for none
+    pub superclass: u64,
+    pub class_loader: u64,
+    /// Bytes of an instance, its header included
+    pub object_size: u32,
+    /// Its own instance fields, not those it inherits
+    pub fields: Vec<JavaField>,
+    pub static_fields: Vec<JavaField>,
+    /// Type of the elements of an array class
+    pub component: Option<FieldKind>,
+}
+
+/// The classes of a heap, by which its objects are told apart
+#[derive(Debug, Default)]
+pub struct JavaHeap {
+    /// In address order
+    pub classes: Vec<ClassLayout>,
+    index: HashMap<u64, usize>,
+    class_class: Option<u64>,
+    string_class: Option<u64>,
+}
+
+impl JavaHeap {
+    /// A heap of `classes`, java.lang.Class and java.lang.String among
+    /// them for class objects and strings to be told apart
+    pub fn new(mut classes: Vec<ClassLayout>) -> Self {
+        classes.sort_unstable_by_key(|layout| layout.class.address);
+        let index = (0..classes.len()).map(|i| (classes[i].class.address, i)).collect();
+        let named = |name: &str| {
+            let layout = classes.iter().find(|layout| layout.class.name == name)?;
+            Some(layout.class.address)
+        };
+        JavaHeap {
+            class_class: named("java.lang.Class"),
+            string_class: named("java.lang.String"),
+            classes,
+            index,
+        }
+    }
+
+    /// Lay out `classes`, as [`find_linked_classes`] or [`search_classes`]
+    /// find them, from their class objects and dex files. Fields whose dex
+    /// file can't be read are left out.
+    pub fn read(reader: &mut dyn MemoryReader, classes: Vec<JavaClass>) -> Self {
+        let mut namer = ClassNamer::default();
+        let layouts = classes
+            .into_iter()
+            .map(|class| {
+                let address = class.address;
+                let reference = |reader: &mut dyn MemoryReader, offset: u64| {
+                    read_u32(reader, address + offset).map_or(0, u64::from)
+                };
+                let component = match reference(reader, CLASS_COMPONENT_TYPE) {
+                    0 => None,
+                    component => read_u32(reader, component + CLASS_PRIMITIVE_TYPE)
+                        .and_then(FieldKind::from_primitive),
+                };
+                ClassLayout {
+                    superclass: reference(reader, CLASS_SUPER_CLASS),
+                    class_loader: reference(reader, CLASS_LOADER),
+                    object_size: read_u32(reader, address + CLASS_OBJECT_SIZE).unwrap_or(0),
+                    fields: namer.fields(reader, address, CLASS_IFIELDS),
+                    static_fields: namer.fields(reader, address, CLASS_SFIELDS),
+                    component,
+                    class,
+                }
+            })
+            .collect();
+        JavaHeap::new(layouts)
+    }
+
+    pub fn class(&self, address: u64) -> Option<&ClassLayout> {
+        self.index.get(&address).map(|&i| &self.classes[i])
+    }
+
+    pub fn is_string_class(&self, address: u64) -> bool {
+        self.string_class == Some(address)
+    }
+
+    /// The fields of an instance of `class`, its own first and then those
+    /// of each superclass in turn, as a heap dump lists their values
+    pub fn instance_fields(&self, class: u64) -> Vec<&JavaField> {
+        let mut fields = Vec::new();
+        let mut class = self.class(class);
+        // Bounded in case of a cycle read from a heap changing underneath
+        for _ in 0..self.classes.len() {
+            let Some(layout) = class else {
+                break;
+            };
+            fields.extend(&layout.fields);
+            class = self.class(layout.superclass);
+        }
+        fields
+    }
+
+    /// The class of the object `bytes` start with and its size, if it is
+    /// an instance of one of the classes
+    fn object(&self, bytes: &[u8]) -> Option<(&ClassLayout, u64)> {
+        let word = |at: u64| {
+            let word = bytes.get(at as usize..at as usize + 4)?;
+            Some(u64::from(u32::from_le_bytes(word.try_into().unwrap())))
+        };
+        let layout = self.class(word(0)?)?;
+        let size = if Some(layout.class.address) == self.class_class {
+            word(CLASS_CLASS_SIZE)?
+        } else if let Some(kind) = layout.component {
+            array_data(kind) + word(ARRAY_LENGTH)? * kind.size() as u64
+        } else if self.is_string_class(layout.class.address) {
+            let count = word(ARRAY_LENGTH)?;
+            STRING_HEADER + (count >> 1) * if count & 1 == 0 { 1 } else { 2 }
+        } else {
+            u64::from(layout.object_size)
+        };
+        (OBJECT_ALIGN as u64..=MAX_OBJECT_SIZE).contains(&size).then_some((layout, size))
+    }
+}
+
+/// Offset of the first element of an array of `kind`
+fn array_data(kind: FieldKind) -> u64 {
+    (ARRAY_LENGTH + 4).next_multiple_of(kind.size() as u64)
+}
+
+impl ClassNamer {
+    /// The fields listed at `offset` in `class`, its `ifields_` or
+    /// `sfields_`, named from its dex file
+    fn fields(&mut self, reader: &mut dyn MemoryReader, class: u64, offset: u64) -> Vec<JavaField> {
+        let mut fields = Vec::new();
+        let Some(array) = read_u64(reader, class + offset).filter(|&array| array != 0) else {
+            return fields;
+        };
+        let dex_cache = read_u32(reader, class + CLASS_DEX_CACHE).unwrap_or(0);
+        let Some(dex) = self.dex_file(reader, u64::from(dex_cache)).filter(|_| dex_cache != 0)
+        else {
+            return fields;
+        };
+        let count = read_u32(reader, array).unwrap_or(0);
+        if count > MAX_FIELDS {
+            return fields;
+        }
+        let mut items = vec![0u8; count as usize * FIELD_SIZE];
+        if reader::read_into(reader, array + 4, &mut items).ok() != Some(items.len()) {
+            return fields;
+        }
+        for item in items.chunks_exact(FIELD_SIZE) {
+            let word = |at: usize| u32::from_le_bytes(item[at..at + 4].try_into().unwrap());
+            let Some((name, descriptor)) = dex.field(reader, word(FIELD_DEX_INDEX)) else {
+                continue;
+            };
+            if let Some(kind) = FieldKind::from_descriptor(&descriptor) {
+                let offset = word(FIELD_OFFSET);
+                fields.push(JavaField { name, kind, offset });
+            }
+        }
+        fields
+    }
+}
+
+/// An object found walking the heap
+pub enum HeapObject<'a> {
+    /// An instance of a class that isn't an array, a string or a class,
+    /// with its fields at their offsets in `bytes`
+    Instance {
+        address: u64,
+        class: &'a ClassLayout,
+        bytes: &'a [u8],
+    },
+    /// A string, with the bytes of its object
+    String { string: JavaString, bytes: &'a [u8] },
+    /// An array, its elements little-endian in `elements`
+    Array {
+        address: u64,
+        class: &'a ClassLayout,
+        kind: FieldKind,
+        length: usize,
+        elements: &'a [u8],
+    },
+}
+
+/// Walk the heap spaces `regions` object by object, passing each to
+/// `visit` but for class objects, which `heap` describes already.
+///
+/// Objects are laid out one after another in every space, so an instance
+/// of one of the classes of `heap` is taken to be an object and the walk
+/// goes on after it; anything else is skipped a word at a time. Objects
+/// bigger than 64 MiB are skipped too.
+pub fn walk_objects(
+    reader: &mut dyn MemoryReader,
+    regions: &[MemoryRegion],
+    heap: &JavaHeap,
+    scan: &mut Scan<()>,
+    control: &mut ScanControl,
+    visit: &mut dyn FnMut(HeapObject<'_>),
+) -> Option<StopReason> {
+    let mut visitor = HeapObjectVisitor {
+        heap,
+        visit,
+        carry: Vec::new(),
+        carry_start: 0,
+        objects: 0,
+    };
+    scan::walk_regions(reader, regions, &mut visitor, scan, control)
+}
+
+struct HeapObjectVisitor<'a> {
+    heap: &'a JavaHeap,
+    visit: &'a mut dyn FnMut(HeapObject<'_>),
+    /// Unwalked bytes carried over, and the address they start at
+    carry: Vec<u8>,
+    carry_start: u64,
+    /// Objects found in the region so far
+    objects: usize,
+}
+
+impl HeapObjectVisitor<'_> {
+    /// Take the objects from the start of the carried bytes, leaving any
+    /// that run past them unless the region is `finished`
+    fn walk(&mut self, region: &MemoryRegion, finished: bool) {
+        let mut at = 0;
+        while at + OBJECT_ALIGN <= self.carry.len() {
+            let bytes = &self.carry[at..];
+            let address = self.carry_start + at as u64;
+            if bytes.len() < CLASS_HEADER && !finished {
+                break;
+            }
+            let Some((class, size)) = self.heap.object(bytes) else {
+                at += OBJECT_ALIGN;
+                continue;
+            };
+            if size as usize > bytes.len() {
+                if !finished && address + size <= region.end {
+                    break;
+                }
+                at += OBJECT_ALIGN;
+                continue;
+            }
+            let bytes = &bytes[..size as usize];
+            let object = if let Some(kind) = class.component {
+                let start = array_data(kind) as usize;
+                Some(HeapObject::Array {
+                    address,
+                    class,
+                    kind,
+                    length: (size as usize - start) / kind.size(),
+                    elements: &bytes[start..],
+                })
+            } else if self.heap.is_string_class(class.class.address) {
+                let header = &bytes[..STRING_HEADER as usize];
+                Header::parse(address, header)
+                    .and_then(|header| header.decode(&bytes[STRING_HEADER as usize..]))
+                    .map(|string| HeapObject::String { string, bytes })
+            } else if self.heap.class_class == Some(class.class.address) {
+                None
+            } else {
+                Some(HeapObject::Instance {
+                    address,
+                    class,
+                    bytes,
+                })
+            };
+            if let Some(object) = object {
+                (self.visit)(object);
+                self.objects += 1;
+            }
+            at += (size as usize).next_multiple_of(OBJECT_ALIGN);
+        }
+        let at = at.min(self.carry.len());
+        self.carry.drain(..at);
+        self.carry_start += at as u64;
+    }
+}
+
+impl RegionVisitor for HeapObjectVisitor<'_> {
+    type Output = ();
+
+    fn visit_chunk(&mut self, region: &MemoryRegion, address: u64, chunk: &[u8]) {
+        if self.carry_start + self.carry.len() as u64 != address {
+            // Past a gap, an object left over can't be finished
+            self.carry.clear();
+            let skew = ((address - region.start) % OBJECT_ALIGN as u64) as usize;
+            let skip = (OBJECT_ALIGN - skew) % OBJECT_ALIGN;
+            self.carry_start = address + skip as u64;
+            self.carry.extend_from_slice(chunk.get(skip..).unwrap_or_default());
+        } else {
+            self.carry.extend_from_slice(chunk);
+        }
+        self.walk(region, false);
+    }
+
+    fn end_region(
+        &mut self,
+        region: &MemoryRegion,
+        _reader: &mut dyn MemoryReader,
+        sink: &mut dyn ScanSink<()>,
+    ) {
+        self.walk(region, true);
+        self.carry.clear();
+        self.carry_start = 0;
+        for _ in 0..std::mem::take(&mut self.objects) {
+            sink.result(());
+        }
+    }
+}
+
+/// Java binary name of a type descriptor
+fn descriptor_to_name(descriptor: &str) -> Option<String> {
+    let dimensions = descriptor.bytes().take_while(|&byte| byte == b'[').count();
+    let element = &descriptor[dimensions..];
+    let name = match element.strip_prefix('L').and_then(|name| name.strip_suffix(';')) {
+        Some(name) if !name.is_empty() => name.replace('/', "."),
+        _ => primitive_name(element)?.to_string(),
+    };
+    Some(format!("{}{}", name, "[]".repeat(dimensions)))
+}
+
+const PRIMITIVES: [(&str, &str); 9] = [
+    ("Z", "boolean"),
+    ("B", "byte"),
+    ("C", "char"),
+    ("S", "short"),
+    ("I", "int"),
+    ("J", "long"),
+    ("F", "float"),
+    ("D", "double"),
+    ("V", "void"),
+];
+
+fn primitive_name(descriptor: &str) -> Option<&'static str> {
+    PRIMITIVES.iter().find(|(d, _)| *d == descriptor).map(|(_, name)| *name)
+}
+
+fn primitive_descriptor(name: &str) -> Option<&'static str> {
+    PRIMITIVES.iter().find(|(_, n)| *n == name).map(|(descriptor, _)| *descriptor)
+}
+
+fn read_u32(reader: &mut dyn MemoryReader, address: u64) -> Option<u32> {
+    let mut bytes = [0u8; 4];
+    (reader::read_into(reader, address, &mut bytes).ok()? == 4).then(|| u32::from_le_bytes(bytes))
+}
+
+/// The native words in `size` bytes at `address`, as many as could be read
+fn read_words(reader: &mut dyn MemoryReader, address: u64, size: usize) -> Vec<u64> {
+    let mut bytes = vec![0u8; size];
+    let read = reader::read_into(reader, address, &mut bytes).unwrap_or(0);
+    bytes[..read]
+        .chunks_exact(WORD)
+        .map(|word| {
+            let mut bytes = [0u8; 8];
+            bytes[..WORD].copy_from_slice(word);
+            u64::from_le_bytes(bytes)
+        })
+        .collect()
+}
+
+fn read_u64(reader: &mut dyn MemoryReader, address: u64) -> Option<u64> {
+    let mut bytes = [0u8; 8];
+    (reader::read_into(reader, address, &mut bytes).ok()? == 8).then(|| u64::from_le_bytes(bytes))
+}
+
+fn read_word(reader: &mut dyn MemoryReader, address: u64) -> Option<u64> {
+    let mut bytes = [0u8; 8];
+    if reader::read_into(reader, address, &mut bytes[..WORD]).ok()? < WORD {
+        return None;
+    }
+    Some(u64::from_le_bytes(bytes)).filter(|&word| word != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps::{self, Permissions};
+    use crate::reader::ProcfsReader;
+
+    /// Lay out a string object the way ART does
+    fn string_object(class: u32, value: &str, hashed: bool) -> Vec<u8> {
+        let units: Vec<u16> = value.encode_utf16().collect();
+        let compressed = units.iter().all(|&unit| (1..0x80).contains(&unit));
+        let count = (units.len() as u32) << 1 | u32::from(!compressed);
+        let hash = if hashed { java_hash(units.iter().copied()) } else { 0 };
+
+        let mut object = Vec::new();
+        for word in [class, 0, count, hash] {
+            object.extend_from_slice(&word.to_le_bytes());
+        }
+        if compressed {
+            object.extend(units.iter().map(|&unit| unit as u8));
+        } else {
+            object.extend(units.iter().flat_map(|unit| unit.to_le_bytes()));
+        }
+        object.resize(object.len().next_multiple_of(OBJECT_ALIGN), 0);
+        object
+    }
+
+    #[test]
+    fn test_finds_strings_of_the_voted_class() {
+        let mut heap = Vec::new();
+        for value in ["android.app.Activity", "session-token", "naïve"] {
+            heap.extend(string_object(0x7010, value, true));
+        }
+        heap.extend(string_object(0x7010, "not hashed yet", false));
+        // Same layout, other class and no hash: not a string
+        heap.extend(string_object(0x7a28, "decoy", false));
+        // A hash that doesn't match rules the object out
+        let mut corrupt = string_object(0x7010, "corrupt", true);
+        corrupt[12] ^= 1;
+        heap.extend(corrupt);
+
+        // Hold the heap in words so its objects are aligned
+        let storage: Vec<u64> = heap
+            .chunks_exact(8)
+            .map(|word| u64::from_le_bytes(word.try_into().unwrap()))
+            .collect();
+        let start = storage.as_ptr() as u64;
+        let pid = std::process::id() as i32;
+        let containing = maps::read_maps(pid)
+            .unwrap()
+            .into_iter()
+            .find(|region| region.contains(start))
+            .unwrap();
+        let region = MemoryRegion {
+            start,
+            end: start + heap.len() as u64,
+            ..containing
+        };
+
+        let mut reader = ProcfsReader::open(pid).unwrap();
+        let mut scan = Scan::default();
+        find_strings(&mut reader, &[region], &mut scan, &mut ScanControl::new());
+
+        let values: Vec<&str> = scan.results.iter().map(|string| string.value.as_str()).collect();
+        assert_eq!(values, ["android.app.Activity", "session-token", "naïve", "not hashed yet"]);
+        assert!(scan.results.iter().all(|string| string.class == 0x7010));
+        assert_eq!(scan.results[0].address, start);
+        assert!(!scan.results[2].compressed);
+    }
+
+    #[test]
+    fn test_names_classes_from_their_dex_file() {
+        // Layout of a small heap, with a dex file mapped after it
+        let (class_class, payload, array, dex_cache, location, dex_file, dex) =
+            (0u64, 0x100, 0x200, 0x300, 0x340, 0x400, 0x800);
+        // Heap references are 32 bits, so the heap has to be low
+        let base = 0x3000_0000u64;
+        let reference = |offset: u64| ((base + offset) as u32).to_le_bytes();
+
+        let mut heap = vec![0u8; 4096];
+        let mut put = |offset: u64, bytes: &[u8]| {
+            heap[offset as usize..offset as usize + bytes.len()].copy_from_slice(bytes);
+        };
+        for (class, type_index) in [(class_class, 0u32), (payload, 1)] {
+            put(class, &reference(class_class));
+            put(class + CLASS_DEX_CACHE, &reference(dex_cache));
+            put(class + CLASS_DEX_TYPE_INDEX, &type_index.to_le_bytes());
+        }
+        put(array, &reference(class_class));
+        put(array + CLASS_COMPONENT_TYPE, &reference(payload));
+
+        put(dex_cache + DEX_CACHE_LOCATIONS[0], &reference(location));
+        put(dex_cache + DEX_CACHE_DEX_FILE, &((base + dex_file) as usize).to_le_bytes());
+        put(dex_file + WORD as u64, &((base + dex) as usize).to_le_bytes());
+        let path = "/data/local/tmp/payload.dex";
+        put(location, &string_object(0x7010, path, false));
+
+        put(dex, b"dex\n035\0");
+        let header = [(0x38, 4u32), (0x3c, 0x70), (0x40, 3), (0x44, 0x80), (0x50, 1), (0x54, 0x90)];
+        for (field, value) in header {
+            put(dex + field, &value.to_le_bytes());
+        }
+        let string_ids = [0xc0u32, 0xe0, 0x100, 0x108];
+        for (i, value) in string_ids.into_iter().chain([0, 1, 2]).enumerate() {
+            put(dex + 0x70 + 4 * i as u64, &value.to_le_bytes());
+        }
+        // Payload's int field `token`
+        put(dex + 0x90, &[1, 0, 2, 0, 3, 0, 0, 0]);
+        let strings = ["Ljava/lang/Class;", "Lcom/example/Payload;", "I", "token"];
+        for (offset, string) in string_ids.into_iter().zip(strings) {
+            let length = string.len() as u8;
+            put(dex + u64::from(offset), &[&[length], string.as_bytes(), &[0]].concat());
+        }
+        let fields = [1, base as u32 + payload as u32, 0, 0, 8];
+        put(payload + CLASS_IFIELDS, &(fields.as_ptr() as u64).to_le_bytes());
+        put(payload + CLASS_OBJECT_SIZE, &12u32.to_le_bytes());
+
+        let mapped = unsafe {
+            libc::mmap(
+                base as *mut libc::c_void,
+                heap.len(),
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_FIXED_NOREPLACE,
+                -1,
+                0,
+            )
+        };
+        assert_eq!(mapped as u64, base);
+        unsafe { std::ptr::copy_nonoverlapping(heap.as_ptr(), mapped.cast(), heap.len()) };
+
+        let region = MemoryRegion {
+            start: base,
+            end: base + heap.len() as u64,
+            perms: Permissions::parse("rw-p").unwrap(),
+            offset: 0,
+            dev: "00:00".to_string(),
+            inode: 0,
+            path: Some("[anon:dalvik-main space]".to_string()),
+            usage: None,
+        };
+        let mut reader = ProcfsReader::open(std::process::id() as i32).unwrap();
+        let mut scan = Scan::default();
+        search_classes(&mut reader, &[region], &mut scan, &mut ScanControl::new());
+        let layouts = JavaHeap::read(&mut reader, scan.results.clone());
+        unsafe { libc::munmap(mapped, heap.len()) };
+
+        let classes: Vec<(u64, &str, Option<&str>)> = scan
+            .results
+            .iter()
+            .map(|class| (class.address - base, class.name.as_str(), class.dex_location.as_deref()))
+            .collect();
+        assert_eq!(
+            classes,
+            [
+                (class_class, "java.lang.Class", Some(path)),
+                (payload, "com.example.Payload", Some(path)),
+                (array, "com.example.Payload[]", None),
+            ]
+        );
+        let layout = layouts.class(base + payload).unwrap();
+        let token = JavaField {
+            name: "token".to_string(),
+            kind: FieldKind::Int,
+            offset: 8,
+        };
+        assert_eq!((layout.object_size, &layout.fields[..]), (12, &[token][..]));
+        assert_eq!(layouts.class(base + array).unwrap().component, Some(FieldKind::Object));
+    }
+
+    #[test]
+    fn test_walks_the_class_linker_tables() {
+        // Classes in a low heap: java.lang.Class, two primitives and an
+        // object of another class, with their names after them
+        let (class_class, int, long, decoy, int_name, long_name) =
+            (0u64, 0x100, 0x200, 0x300, 0x400, 0x480);
+        let base = 0x3100_0000u64;
+        let reference = |offset: u64| ((base + offset) as u32).to_le_bytes();
+        let mut heap = vec![0u8; 4096];
+        let mut put = |offset: u64, bytes: &[u8]| {
+            heap[offset as usize..offset as usize + bytes.len()].copy_from_slice(bytes);
+        };
+        for (class, name) in [(class_class, None), (int, Some(int_name)), (long, Some(long_name))] {
+            put(class, &reference(class_class));
+            if let Some(name) = name {
+                put(class + CLASS_NAME, &reference(name));
+            }
+        }
+        put(decoy, &reference(0x7010));
+        put(int_name, &string_object(0x7010, "int", false));
+        put(long_name, &string_object(0x7010, "long", false));
+        let mapped = unsafe {
+            libc::mmap(
+                base as *mut libc::c_void,
+                heap.len(),
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_FIXED_NOREPLACE,
+                -1,
+                0,
+            )
+        };
+        assert_eq!(mapped as u64, base);
+        unsafe { std::ptr::copy_nonoverlapping(heap.as_ptr(), mapped.cast(), heap.len()) };
+
+        // A class set over `slots`, each class's low bits some of its hash
+        let slots = |classes: &[u64], buckets: usize| -> Vec<u32> {
+            let mut slots = vec![0u32; buckets];
+            for (i, &class) in classes.iter().enumerate() {
+                slots[i * 2] = (base + class) as u32 | (i as u32 + 1);
+            }
+            slots
+        };
+        let set = |elements: usize, slots: &[u32]| -> Vec<u8> {
+            let mut set = vec![0u8; SET_SIZE];
+            for (at, word) in [(SET_ELEMENTS, elements), (SET_BUCKETS, slots.len())]
+                .into_iter()
+                .chain([(SET_DATA, slots.as_ptr() as usize)])
+            {
+                set[at..at + WORD].copy_from_slice(&word.to_le_bytes());
+            }
+            set[SET_LOAD_FACTORS..SET_LOAD_FACTORS + 8].copy_from_slice(&0.4f64.to_le_bytes());
+            set[SET_LOAD_FACTORS + 8..].copy_from_slice(&0.7f64.to_le_bytes());
+            set
+        };
+        // A class table whose `classes_` hold `set`, after its lock
+        let table = |set: &[u8]| -> Vec<usize> {
+            let mut table = vec![0usize; CLASS_TABLE_SIZE / WORD];
+            let begin = set.as_ptr() as usize;
+            table[3..6].copy_from_slice(&[begin, begin + set.len(), begin + set.len()]);
+            table
+        };
+        let boot_slots = slots(&[class_class, int, decoy], 8);
+        let boot_set = set(3, &boot_slots);
+        let boot_table = table(&boot_set);
+        let loader_slots = slots(&[long], 4);
+        let loader_set = set(1, &loader_slots);
+        let loader_table = table(&loader_set);
+
+        // The class linker: the boot table, then the list of class loaders
+        let mut linker = vec![0usize; CLASS_LINKER_SIZE / WORD];
+        let head = linker.as_ptr() as usize + 10 * WORD;
+        let node = [head, head, 0, loader_table.as_ptr() as usize];
+        linker[5] = boot_table.as_ptr() as usize;
+        let node_address = node.as_ptr() as usize;
+        linker[10..13].copy_from_slice(&[node_address, node_address, 1]);
+        let mut runtime = vec![0usize; RUNTIME_SIZE / WORD];
+        runtime[40] = linker.as_ptr() as usize;
+
+        let pid = std::process::id() as i32;
+        let regions = maps::read_maps(pid).unwrap();
+        let mut reader = ProcfsReader::open(pid).unwrap();
+        let mut scan = Scan::default();
+        let runtime = runtime.as_ptr() as u64;
+        let mut control = ScanControl::new();
+        let found = runtime_classes(&mut reader, &regions, runtime, &mut scan, &mut control);
+        unsafe { libc::munmap(mapped, heap.len()) };
+
+        assert_eq!(found, Ok(None));
+        let classes: Vec<(u64, &str)> =
+            scan.results.iter().map(|class| (class.address - base, class.name.as_str())).collect();
+        assert_eq!(classes, [(int, "int"), (long, "long")]);
+
+        // No libart.so here, so no tables to find
+        let mut scan = Scan::default();
+        let found = find_linked_classes(&mut reader, &regions, &mut scan, &mut ScanControl::new());
+        assert_eq!(found, Err("libart.so isn't mapped".to_string()));
+    }
+}
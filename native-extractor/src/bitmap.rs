@@ -0,0 +1,395 @@
+//! Recovery of raw bitmap pixel buffers from process memory
+//!
+//! A decoded `android.graphics.Bitmap` keeps its pixels in native memory,
+//! so what an app has drawn or shown on screen can be recovered even if it
+//! was never saved as a file. The default `ARGB_8888` configuration stores
+//! each pixel as four bytes, red, green, blue then alpha, with the colors
+//! premultiplied by the alpha, so no color byte is greater than the alpha
+//! byte. Runs of 4-byte aligned words that all hold to that are few in
+//! other data: pointers, small integers, floats and UTF-16 text all break
+//! it.
+//!
+//! A buffer carries no dimensions of its own, and rows follow each other
+//! without padding, so the width is guessed from the pixels: each row is
+//! much like the one before it, so the width is the offset at which pixels
+//! differ least from those they are compared with. Rows at either end that
+//! are fully transparent are dropped. Hardware bitmaps, held in graphics
+//! memory, and other pixel formats aren't found.
+
+use crate::crc32::crc32;
+use crate::dump::DumpedFile;
+use crate::maps::MemoryRegion;
+use crate::reader::{self, MemoryReader};
+use crate::scan::{self, RegionVisitor, ScanControl, ScanSink, StopReason};
+use crate::sha256::Sha256;
+use crate::zlib;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Fewest rows and columns of a bitmap recovered
+const MIN_SIDE: usize = 16;
+
+/// Widest bitmap recovered
+const MAX_WIDTH: usize = 4096;
+
+/// Most pixels of a bitmap recovered, 64 MiB of them
+const MAX_PIXELS: usize = 16 << 20;
+
+/// Pixels compared for each width tried
+const SAMPLES: usize = 2048;
+
+/// A bitmap found in memory and written to disk as a PNG
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoveredBitmap {
+    #[serde(flatten)]
+    pub file: DumpedFile,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Whether `pixel` can be a premultiplied RGBA pixel
+fn is_pixel(pixel: &[u8]) -> bool {
+    let alpha = pixel[3];
+    pixel[0] <= alpha && pixel[1] <= alpha && pixel[2] <= alpha
+}
+
+/// How much the pixels of `data` differ from those `width` pixels after
+/// them, summed over a sample of them
+fn row_cost(data: &[u8], width: usize) -> u64 {
+    let pixels = data.len() / 4 - width;
+    let step = pixels.div_ceil(SAMPLES).max(1);
+    (0..pixels)
+        .step_by(step)
+        .map(|i| {
+            let (a, b) = (&data[i * 4..i * 4 + 4], &data[(i + width) * 4..(i + width) * 4 + 4]);
+            a.iter().zip(b).map(|(&x, &y)| u64::from(x.abs_diff(y))).sum::<u64>()
+        })
+        .sum()
+}
+
+/// The width of the bitmap whose pixels are `data`, if the rows of any
+/// width tried are alike enough to be those of an image
+fn estimate_width(data: &[u8]) -> Option<usize> {
+    let pixels = data.len() / 4;
+    let widest = MAX_WIDTH.min(pixels / MIN_SIDE);
+    if widest < MIN_SIDE {
+        return None;
+    }
+    let costs: Vec<u64> = (MIN_SIDE..=widest).map(|width| row_cost(data, width)).collect();
+    let (best, &cost) = costs.iter().enumerate().min_by_key(|&(_, cost)| cost)?;
+    let mut sorted = costs.clone();
+    sorted.sort_unstable();
+    // Uniform data is alike at every width, and isn't worth recovering
+    if cost * 2 >= sorted[sorted.len() / 2] {
+        return None;
+    }
+    // Rows two apart are nearly as alike as neighbouring ones, so prefer
+    // a fraction of the best width that does about as well
+    let best = best + MIN_SIDE;
+    let width = (2..=8)
+        .rev()
+        .filter(|k| best.is_multiple_of(*k) && best / k >= MIN_SIDE)
+        .map(|k| best / k)
+        .find(|&width| costs[width - MIN_SIDE] * 10 <= cost * 11)
+        .unwrap_or(best);
+    Some(width)
+}
+
+/// A PNG of the straight, not premultiplied, RGBA pixels `rgba`. The image
+/// data is stored uncompressed.
+pub fn encode_png(width: u32, height: u32, rgba: &[u8]) -> Vec<u8> {
+    let row = width as usize * 4;
+    let mut raw = Vec::with_capacity((row + 1) * height as usize);
+    for line in rgba.chunks(row) {
+        // Filter type none
+        raw.push(0);
+        raw.extend_from_slice(line);
+    }
+
+    let zlib = zlib::zlib_stored(&raw);
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    let mut chunk = |kind: &[u8], data: &[u8]| {
+        png.extend((data.len() as u32).to_be_bytes());
+        let start = png.len();
+        png.extend_from_slice(kind);
+        png.extend_from_slice(data);
+        let crc = crc32(&png[start..]);
+        png.extend(crc.to_be_bytes());
+    };
+    let mut header = width.to_be_bytes().to_vec();
+    header.extend(height.to_be_bytes());
+    // 8 bits per channel, RGBA, no interlacing
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+    chunk(b"IHDR", &header);
+    chunk(b"IDAT", &zlib);
+    chunk(b"IEND", b"");
+    png
+}
+
+/// Undo the premultiplication of the colors of `pixels` by their alpha
+fn unpremultiply(pixels: &mut [u8]) {
+    for pixel in pixels.chunks_exact_mut(4) {
+        let alpha = u32::from(pixel[3]);
+        if alpha == 0 || alpha == 255 {
+            continue;
+        }
+        for color in &mut pixel[..3] {
+            *color = ((u32::from(*color) * 255 + alpha / 2) / alpha) as u8;
+        }
+    }
+}
+
+/// File a bitmap found at `address` is written to
+pub fn bitmap_file_name(address: u64) -> String {
+    format!("{:08x}.png", address)
+}
+
+/// Search `regions` for RGBA pixel buffers and write each distinct one to
+/// `dir` as a PNG, delivering a [`RecoveredBitmap`] for each.
+///
+/// Failing to write to `dir` is an error; files written before the
+/// failure are left in place.
+pub fn recover_bitmaps(
+    reader: &mut dyn MemoryReader,
+    regions: &[MemoryRegion],
+    dir: &Path,
+    sink: &mut dyn ScanSink<RecoveredBitmap>,
+    control: &mut ScanControl,
+) -> io::Result<Option<StopReason>> {
+    let mut visitor = BitmapVisitor {
+        dir,
+        run: None,
+        found: Vec::new(),
+        seen: HashSet::new(),
+        failed: None,
+    };
+    let stopped = scan::walk_regions(reader, regions, &mut visitor, sink, control);
+    match visitor.failed {
+        Some(e) => Err(e),
+        None => Ok(stopped),
+    }
+}
+
+/// Pixels in a row that can all be premultiplied RGBA
+#[derive(Debug, Clone, Copy)]
+struct Run {
+    start: u64,
+    pixels: usize,
+    /// The first and last pixels that aren't fully transparent, by index
+    visible: Option<(usize, usize)>,
+}
+
+impl Run {
+    /// The pixels of the run worth reading, if there are enough of them
+    fn visible(&self) -> Option<(usize, usize)> {
+        self.visible.filter(|(first, last)| last - first >= MIN_SIDE * MIN_SIDE)
+    }
+}
+
+struct BitmapVisitor<'a> {
+    dir: &'a Path,
+    /// The run the last chunk read ended in
+    run: Option<Run>,
+    /// The runs found in the region being read
+    found: Vec<Run>,
+    /// SHA-256 of the files written
+    seen: HashSet<String>,
+    /// The first write failure; nothing more is written after one
+    failed: Option<io::Error>,
+}
+
+impl BitmapVisitor<'_> {
+    fn end_run(&mut self) {
+        if let Some(run) = self.run.take().filter(|run| run.visible().is_some()) {
+            self.found.push(run);
+        }
+    }
+
+    /// Write the bitmap in `run` to a file of its own, if it is new
+    fn recover(&mut self, run: Run, reader: &mut dyn MemoryReader) -> Option<RecoveredBitmap> {
+        let (first, last) = run.visible()?;
+        let mut data = vec![0u8; (last + 1 - first) * 4];
+        let address = run.start + first as u64 * 4;
+        if reader::read_into(reader, address, &mut data).ok()? < data.len() {
+            return None;
+        }
+        let width = estimate_width(&data)?;
+        // Start at a whole row from the start of the run, the transparent
+        // pixels before the first visible one being the start of its row
+        let lead = first % width;
+        let mut pixels = vec![0u8; lead * 4];
+        pixels.append(&mut data);
+        let height = (pixels.len() / 4).div_ceil(width);
+        pixels.resize(width * height * 4, 0);
+        unpremultiply(&mut pixels);
+
+        let png = encode_png(width as u32, height as u32, &pixels);
+        let mut hash = Sha256::new();
+        hash.update(&png);
+        let sha256 = hash.finish_hex();
+        if !self.seen.insert(sha256.clone()) {
+            return None;
+        }
+        let start = address - lead as u64 * 4;
+        let name = bitmap_file_name(start);
+        if let Err(e) = fs::write(self.dir.join(&name), &png) {
+            self.failed = Some(e);
+            return None;
+        }
+        Some(RecoveredBitmap {
+            file: DumpedFile {
+                start,
+                file: name,
+                size: png.len() as u64,
+                sha256,
+            },
+            width: width as u32,
+            height: height as u32,
+        })
+    }
+}
+
+impl RegionVisitor for BitmapVisitor<'_> {
+    type Output = RecoveredBitmap;
+
+    fn visit_chunk(&mut self, _region: &MemoryRegion, address: u64, chunk: &[u8]) {
+        let skip = (address.wrapping_neg() % 4) as usize;
+        let address = address + skip as u64;
+        if self.run.is_some_and(|run| run.start + run.pixels as u64 * 4 != address) {
+            self.end_run();
+        }
+        let Some(chunk) = chunk.get(skip..) else {
+            return;
+        };
+        for (i, pixel) in chunk.chunks_exact(4).enumerate() {
+            if !is_pixel(pixel) {
+                self.end_run();
+                continue;
+            }
+            let run = self.run.get_or_insert(Run {
+                start: address + i as u64 * 4,
+                pixels: 0,
+                visible: None,
+            });
+            if pixel != [0; 4] {
+                let index = run.pixels;
+                let first = run.visible.map_or(index, |(first, _)| first);
+                run.visible = Some((first, index));
+            }
+            run.pixels += 1;
+            if run.pixels == MAX_PIXELS {
+                self.end_run();
+            }
+        }
+    }
+
+    fn end_region(
+        &mut self,
+        _region: &MemoryRegion,
+        reader: &mut dyn MemoryReader,
+        sink: &mut dyn ScanSink<RecoveredBitmap>,
+    ) {
+        self.end_run();
+        for run in std::mem::take(&mut self.found) {
+            if self.failed.is_some() {
+                return;
+            }
+            if let Some(bitmap) = self.recover(run, reader) {
+                sink.result(bitmap);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps::parse_maps_line;
+    use crate::reader::ProcfsReader;
+    use crate::scan::Scan;
+
+    /// Premultiplied pixels of a `width` by `height` image of gradients
+    /// and squares, half transparent on the right
+    fn image(width: usize, height: usize) -> Vec<u8> {
+        let mut pixels = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                let alpha = if x < width / 2 { 0xff } else { 0x80 };
+                let square = if (x / 4 + y / 4) % 2 == 0 { 0xc0 } else { 0x10 };
+                let color = [(x * 5) as u8, (y * 9) as u8, square];
+                pixels.extend(color.map(|c| (u32::from(c) * alpha / 255) as u8));
+                pixels.push(alpha as u8);
+            }
+        }
+        pixels
+    }
+
+    /// The pixels of the PNG `png`, as `encode_png` writes it
+    fn decode_png(png: &[u8]) -> (u32, u32, Vec<u8>) {
+        let width = u32::from_be_bytes(png[16..20].try_into().unwrap());
+        let height = u32::from_be_bytes(png[20..24].try_into().unwrap());
+        let length = u32::from_be_bytes(png[33..37].try_into().unwrap()) as usize;
+        let raw = zlib::inflate_zlib(&png[41..41 + length], usize::MAX).unwrap().data;
+        let row = width as usize * 4 + 1;
+        let pixels = raw.chunks(row).flat_map(|line| line[1..].to_vec()).collect();
+        (width, height, pixels)
+    }
+
+    #[test]
+    fn test_estimate_width() {
+        for (width, height) in [(40, 24), (17, 300), (600, 20)] {
+            assert_eq!(estimate_width(&image(width, height)), Some(width));
+        }
+        assert_eq!(estimate_width(&[0x80; 64 * 64 * 4]), None);
+        assert_eq!(estimate_width(&[0x80; 15 * 15 * 4]), None);
+    }
+
+    #[test]
+    fn test_recovers_bitmaps() {
+        let pixels = image(40, 24);
+        // Not pixels, as the high half of a pointer isn't
+        let mut data = [0x12, 0x34, 0x00, 0x00].repeat(0x1400);
+        // Two transparent rows and a partly transparent one, then the rest
+        let at = 0x1004;
+        data[at..at + pixels.len()].copy_from_slice(&pixels);
+        data[at..at + 90 * 4].fill(0);
+        // Two copies of it, written once
+        data[0x2800..0x2800 + pixels.len()].copy_from_slice(&pixels);
+        data[0x3c00..0x3c00 + pixels.len()].copy_from_slice(&pixels);
+        let start = data.as_ptr() as u64;
+        let end = start + data.len() as u64;
+        let region = parse_maps_line(&format!("{:x}-{:x} rw-p 00000000 00:00 0", start, end));
+
+        let pid = std::process::id() as i32;
+        let dir = std::env::temp_dir().join(format!("bitmap-test-{}", pid));
+        fs::create_dir_all(&dir).unwrap();
+        let mut reader = ProcfsReader::open(pid).unwrap();
+        let mut scan = Scan::default();
+        let regions = [region.unwrap()];
+        let mut control = ScanControl::new().chunk_size(Some(0x1000));
+        let stopped = recover_bitmaps(&mut reader, &regions, &dir, &mut scan, &mut control);
+
+        assert_eq!(stopped.unwrap(), None);
+        let bitmaps: Vec<_> = scan
+            .results
+            .iter()
+            .map(|bitmap| (bitmap.file.start - start, bitmap.width, bitmap.height))
+            .collect();
+        assert_eq!(bitmaps, [(at as u64 + 80 * 4, 40, 22), (0x2800, 40, 24)]);
+        let (width, height, decoded) =
+            decode_png(&fs::read(dir.join(&scan.results[1].file.file)).unwrap());
+        assert_eq!((width, height), (40, 24));
+        // Colors come back to within rounding of what was premultiplied
+        let expected = image(40, 24);
+        let pixel = (5 * 40 + 30) * 4;
+        assert_eq!(decoded[pixel + 3], 0x80);
+        assert!(decoded[pixel].abs_diff((30 * 5) as u8) <= 1);
+        assert_eq!(decoded[..80], expected[..80]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,173 @@
+//! Approximate de-duplication of very large result sets
+//!
+//! Exact de-duplication keeps every distinct string, which for a sweep of
+//! every process on a device is most of what was found. A Bloom filter
+//! answers "seen before?" in a fixed number of bits per expected string. It
+//! never wrongly answers no, and wrongly answers yes at a rate chosen up
+//! front, so a few distinct strings are dropped as duplicates in exchange
+//! for memory that doesn't grow with the scan.
+
+use crate::scan::{ExtractedString, RegionResult, ScanSink};
+use serde::Deserialize;
+use std::collections::hash_map::DefaultHasher;
+use std::f64::consts::LN_2;
+use std::hash::Hasher;
+
+/// Largest filter built, 512 MiB, whatever the options ask for
+const MAX_BITS: u64 = 1 << 32;
+
+/// Most hash functions used, past which lookups only get slower
+const MAX_HASHES: u32 = 32;
+
+/// The `approximateDedupe` object of the scan options
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct BloomOptions {
+    /// Distinct strings the filter is sized for; past this, the false
+    /// positive rate climbs
+    pub expected_strings: u64,
+    /// Share of distinct strings that may be wrongly dropped as duplicates
+    pub false_positive_rate: f64,
+}
+
+impl Default for BloomOptions {
+    fn default() -> Self {
+        BloomOptions {
+            expected_strings: 1_000_000,
+            false_positive_rate: 0.01,
+        }
+    }
+}
+
+/// A Bloom filter over byte strings
+#[derive(Debug, Clone)]
+pub struct BloomFilter {
+    bits: Vec<u64>,
+    bit_count: u64,
+    hashes: u32,
+    /// How many insertions found their item already there
+    pub duplicates: u64,
+}
+
+impl BloomFilter {
+    /// A filter sized for `options`
+    pub fn new(options: &BloomOptions) -> Self {
+        let expected = options.expected_strings.max(1) as f64;
+        let rate = options.false_positive_rate.clamp(1e-9, 0.5);
+        let bit_count = (-expected * rate.ln() / (LN_2 * LN_2)).ceil() as u64;
+        let bit_count = bit_count.clamp(64, MAX_BITS).next_multiple_of(64);
+        let hashes = (bit_count as f64 / expected * LN_2).round() as u32;
+        BloomFilter {
+            bits: vec![0; (bit_count / 64) as usize],
+            bit_count,
+            hashes: hashes.clamp(1, MAX_HASHES),
+            duplicates: 0,
+        }
+    }
+
+    /// Memory the filter takes
+    pub fn size_bytes(&self) -> usize {
+        self.bits.len() * 8
+    }
+
+    /// Whether `item` was probably added
+    pub fn contains(&self, item: &[u8]) -> bool {
+        self.positions(item).all(|(word, mask)| self.bits[word] & mask != 0)
+    }
+
+    /// Add `item`, returning whether it was probably there already
+    pub fn insert(&mut self, item: &[u8]) -> bool {
+        let mut present = true;
+        for (word, mask) in self.positions(item) {
+            if self.bits[word] & mask == 0 {
+                self.bits[word] |= mask;
+                present = false;
+            }
+        }
+        if present {
+            self.duplicates += 1;
+        }
+        present
+    }
+
+    /// The words and bits of `item`'s positions in the filter
+    fn positions(&self, item: &[u8]) -> impl Iterator<Item = (usize, u64)> {
+        // Double hashing: the k bit positions are h1 + i * h2
+        let (h1, h2) = hash_pair(item);
+        let bit_count = self.bit_count;
+        (0..self.hashes as u64).map(move |i| {
+            let bit = h1.wrapping_add(i.wrapping_mul(h2)) % bit_count;
+            ((bit / 64) as usize, 1u64 << (bit % 64))
+        })
+    }
+}
+
+/// Two independent hashes of `item`, the second odd so every step of the
+/// double hashing moves
+fn hash_pair(item: &[u8]) -> (u64, u64) {
+    let mut first = DefaultHasher::new();
+    first.write(item);
+    let mut second = DefaultHasher::new();
+    second.write_u8(0xb1);
+    second.write(item);
+    (first.finish(), second.finish() | 1)
+}
+
+/// Passes on to another sink the strings a [`BloomFilter`] hasn't seen,
+/// or every string if there is no filter
+pub struct UniqueSink<'a> {
+    filter: Option<&'a mut BloomFilter>,
+    inner: &'a mut dyn ScanSink<ExtractedString>,
+}
+
+impl<'a> UniqueSink<'a> {
+    pub fn new(
+        filter: Option<&'a mut BloomFilter>,
+        inner: &'a mut dyn ScanSink<ExtractedString>,
+    ) -> Self {
+        UniqueSink { filter, inner }
+    }
+}
+
+impl ScanSink<ExtractedString> for UniqueSink<'_> {
+    fn region(&mut self, result: RegionResult) {
+        self.inner.region(result);
+    }
+
+    fn result(&mut self, item: ExtractedString) {
+        let seen = self.filter.as_mut().is_some_and(|filter| filter.insert(item.value.as_bytes()));
+        if !seen {
+            self.inner.result(item);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_false_positive_rate_is_near_the_target() {
+        let options = BloomOptions {
+            expected_strings: 10_000,
+            false_positive_rate: 0.01,
+        };
+        let mut filter = BloomFilter::new(&options);
+        // About 9.6 bits and 7 hashes per string for 1%
+        assert!((11_000..13_000).contains(&filter.size_bytes()));
+        assert_eq!(filter.hashes, 7);
+
+        for i in 0..10_000 {
+            filter.insert(format!("label {}", i).as_bytes());
+        }
+        for i in 0..10_000 {
+            assert!(filter.insert(format!("label {}", i).as_bytes()));
+        }
+        let wrong = (0..10_000)
+            .filter(|i| filter.contains(format!("other {}", i).as_bytes()))
+            .count();
+        assert!(wrong < 200, "{} false positives", wrong);
+        // The second pass, and a few false positives in the first
+        assert!(filter.duplicates >= 10_000);
+    }
+}
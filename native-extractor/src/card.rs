@@ -0,0 +1,134 @@
+//! Payment card numbers (PANs)
+//!
+//! A card number is 13 to 19 digits, often grouped by spaces or dashes,
+//! whose last digit is a Luhn check digit over the rest. One in ten random
+//! digit runs passes the Luhn check, so a number only counts as a card if
+//! its leading digits (the IIN) also belong to a known card network.
+
+use serde::Serialize;
+
+/// Card networks told apart by their IIN ranges
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CardNetwork {
+    Visa,
+    Mastercard,
+    Amex,
+    Discover,
+    Jcb,
+    DinersClub,
+    UnionPay,
+    Maestro,
+}
+
+/// The digits of `text`, if the rest of it is only spaces and dashes
+fn digits(text: &[u8]) -> Option<Vec<u8>> {
+    let mut digits = Vec::with_capacity(text.len());
+    for &byte in text {
+        match byte {
+            b'0'..=b'9' => digits.push(byte - b'0'),
+            b' ' | b'-' => {}
+            _ => return None,
+        }
+    }
+    Some(digits)
+}
+
+/// Whether `digits` end in a valid Luhn check digit
+pub fn luhn_valid(digits: &[u8]) -> bool {
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &digit)| {
+            let digit = digit as u32;
+            if i % 2 == 1 {
+                let doubled = digit * 2;
+                if doubled > 9 {
+                    doubled - 9
+                } else {
+                    doubled
+                }
+            } else {
+                digit
+            }
+        })
+        .sum();
+    !digits.is_empty() && sum.is_multiple_of(10)
+}
+
+/// The network whose IIN range and lengths `digits` fit
+pub fn network(digits: &[u8]) -> Option<CardNetwork> {
+    let prefix = |len: usize| digits[..len].iter().fold(0u32, |n, &d| n * 10 + d as u32);
+    let len = digits.len();
+    if len < 12 {
+        return None;
+    }
+    let (p1, p2, p3, p4) = (prefix(1), prefix(2), prefix(3), prefix(4));
+    let network = match () {
+        _ if p1 == 4 && matches!(len, 13 | 16 | 19) => CardNetwork::Visa,
+        _ if ((51..=55).contains(&p2) || (2221..=2720).contains(&p4)) && len == 16 => {
+            CardNetwork::Mastercard
+        }
+        _ if matches!(p2, 34 | 37) && len == 15 => CardNetwork::Amex,
+        _ if (p4 == 6011 || (644..=649).contains(&p3) || p2 == 65) && len >= 16 => {
+            CardNetwork::Discover
+        }
+        _ if (3528..=3589).contains(&p4) && len >= 16 => CardNetwork::Jcb,
+        _ if ((300..=305).contains(&p3) || matches!(p2, 36 | 38 | 39)) && len >= 14 => {
+            CardNetwork::DinersClub
+        }
+        _ if p2 == 62 && len >= 16 => CardNetwork::UnionPay,
+        _ if (matches!(p2, 50 | 56..=58) || p1 == 6) && len >= 12 => CardNetwork::Maestro,
+        _ => return None,
+    };
+    Some(network)
+}
+
+/// The network of the card number `text`, if it is one: 13 to 19 digits,
+/// possibly grouped, passing the Luhn check, in a known network's range
+pub fn identify(text: &[u8]) -> Option<CardNetwork> {
+    let digits = digits(text)?;
+    if !(13..=19).contains(&digits.len()) || !luhn_valid(&digits) {
+        return None;
+    }
+    network(&digits)
+}
+
+/// `text` with every digit but the last four replaced by `*`, keeping the
+/// grouping
+pub fn mask(text: &str) -> String {
+    let total = text.bytes().filter(u8::is_ascii_digit).count();
+    let mut seen = 0;
+    text.chars()
+        .map(|c| {
+            if !c.is_ascii_digit() {
+                return c;
+            }
+            seen += 1;
+            if seen + 4 > total {
+                c
+            } else {
+                '*'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identify_card_numbers() {
+        assert_eq!(identify(b"4111111111111111"), Some(CardNetwork::Visa));
+        assert_eq!(identify(b"5500-0000-0000-0004"), Some(CardNetwork::Mastercard));
+        assert_eq!(identify(b"3782 822463 10005"), Some(CardNetwork::Amex));
+        assert_eq!(identify(b"6011111111111117"), Some(CardNetwork::Discover));
+        assert_eq!(identify(b"3530111333300000"), Some(CardNetwork::Jcb));
+        // Fails the Luhn check, and passes it outside any network
+        assert_eq!(identify(b"4111111111111112"), None);
+        assert_eq!(identify(b"1234567812345670"), None);
+        assert_eq!(mask("4111 1111 1111 1111"), "**** **** **** 1111");
+    }
+}
@@ -0,0 +1,467 @@
+//! Whole structured documents carved out of memory
+//!
+//! App state, API responses and config blobs are mostly JSON, which string
+//! extraction breaks into pieces at every short value. A carver finds
+//! where a document starts, follows it to its end, and checks that the
+//! bytes between parse, so each document is reported whole at its address.
+//!
+//! A JSON document is an object or array whose brackets balance, outside
+//! strings, with nothing but JSON between them. An XML document is a
+//! well-formed element, with any prolog before it, such as the contents of
+//! a SharedPreferences file, a layout dump or a SOAP payload. HTML carves
+//! as XML where it is well-formed, its void elements such as `<br>` taking
+//! no end tag. HTTP messages are found too, and parsed (see
+//! [`crate::http`]). Documents nested in one already found are part of it
+//! and not reported apart.
+
+use crate::http::{self, HttpMessage, Parsed};
+use crate::maps::MemoryRegion;
+use crate::reader::MemoryReader;
+use crate::scan::{Location, RegionVisitor, ScanSink};
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Largest `maxLength` allowed
+pub const MAX_DOCUMENT_LENGTH: usize = 16 << 20;
+
+/// Deepest nesting followed, as deep as `serde_json` parses
+const MAX_NESTING: usize = 128;
+
+/// Kinds of document carved
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DocumentFormat {
+    Json,
+    Xml,
+    Http,
+}
+
+/// The `carve` object of the scan options
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct CarveOptions {
+    /// Kinds of document to look for
+    pub formats: Vec<DocumentFormat>,
+    /// Shortest document reported, in bytes
+    pub min_length: usize,
+    /// Longest document followed, in bytes; longer ones aren't found
+    pub max_length: usize,
+}
+
+impl Default for CarveOptions {
+    fn default() -> Self {
+        CarveOptions {
+            formats: vec![DocumentFormat::Json],
+            min_length: 16,
+            max_length: 1 << 20,
+        }
+    }
+}
+
+/// A document carved out of memory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CarvedDocument {
+    #[serde(flatten)]
+    pub location: Location,
+    pub format: DocumentFormat,
+    pub length: usize,
+    /// The document; for an HTTP message, its start line and headers
+    pub text: String,
+    /// An HTTP message, parsed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http: Option<Box<HttpMessage>>,
+}
+
+/// How far a document starting at some byte got
+#[derive(Debug, PartialEq, Eq)]
+enum Extent {
+    /// It ends after this many bytes
+    Complete(usize),
+    /// It can't be a document
+    Invalid,
+    /// It goes on past the end of the bytes
+    Incomplete,
+}
+
+/// Whether a JSON document can start at `data[0]`: an object opening on
+/// a key or closing, or an array opening on a value or closing
+fn json_starts(data: &[u8]) -> bool {
+    let next = data[1..].iter().find(|b| !b.is_ascii_whitespace());
+    match (data[0], next) {
+        (b'{', Some(b'"' | b'}')) => true,
+        (b'[', Some(&b)) => b"{[\"]-tfn".contains(&b) || b.is_ascii_digit(),
+        (_, None) => matches!(data[0], b'{' | b'['),
+        _ => false,
+    }
+}
+
+/// HTML elements that never have content or an end tag
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+fn is_name_start(byte: u8) -> bool {
+    byte.is_ascii_alphabetic() || byte == b'_' || byte == b':' || byte >= 0x80
+}
+
+fn is_name_byte(byte: u8) -> bool {
+    is_name_start(byte) || byte.is_ascii_digit() || byte == b'-' || byte == b'.'
+}
+
+/// Whether an XML document can start at `data[0]`: a declaration or an
+/// element's start tag
+fn xml_starts(data: &[u8]) -> bool {
+    data.starts_with(b"<?xml") || data.get(1).is_some_and(|&b| is_name_start(b))
+}
+
+/// Follow the XML document at `data[0]` to the end of its root element
+fn xml_extent(data: &[u8], max_length: usize) -> Extent {
+    let ran_out = if data.len() < max_length { Extent::Incomplete } else { Extent::Invalid };
+    let data = &data[..data.len().min(max_length)];
+    let find = |from: usize, end: &[u8]| {
+        data.get(from..)?.windows(end.len()).position(|w| w == end).map(|at| from + at + end.len())
+    };
+    let name_end = |from: usize| {
+        from + data[from..].iter().take_while(|&&b| is_name_byte(b)).count()
+    };
+    let skip_space = |from: usize| {
+        from + data[from..].iter().take_while(|b| b.is_ascii_whitespace()).count()
+    };
+
+    let mut open: Vec<&[u8]> = Vec::new();
+    let mut seen_root = false;
+    let mut i = 0;
+    loop {
+        if seen_root && open.is_empty() {
+            return Extent::Complete(i);
+        }
+        let Some(&byte) = data.get(i) else {
+            return ran_out;
+        };
+        if byte != b'<' {
+            let text = !open.is_empty() && (byte >= 0x20 || byte.is_ascii_whitespace());
+            if !text && !byte.is_ascii_whitespace() {
+                return Extent::Invalid;
+            }
+            i += 1;
+            continue;
+        }
+        let rest = &data[i..];
+        let end = if rest.starts_with(b"<!--") {
+            find(i + 4, b"-->")
+        } else if rest.starts_with(b"<![CDATA[") && !open.is_empty() {
+            find(i + 9, b"]]>")
+        } else if rest.starts_with(b"<?") {
+            find(i + 2, b"?>")
+        } else if rest.starts_with(b"<!") && open.is_empty() && !seen_root {
+            find(i + 2, b">")
+        } else if rest.starts_with(b"</") {
+            let name = &data[i + 2..name_end(i + 2)];
+            let close = skip_space(i + 2 + name.len());
+            match data.get(close) {
+                None => return ran_out,
+                Some(b'>') if open.pop() == Some(name) => Some(close + 1),
+                _ => return Extent::Invalid,
+            }
+        } else {
+            // A start tag and its attributes
+            let name = &data[i + 1..name_end(i + 1)];
+            if name.is_empty() {
+                return Extent::Invalid;
+            }
+            let mut at = i + 1 + name.len();
+            loop {
+                at = skip_space(at);
+                match data.get(at..at + 2) {
+                    _ if data.get(at) == Some(&b'>') => {
+                        let void = VOID_ELEMENTS.iter().any(|v| v.as_bytes() == name);
+                        if !void {
+                            open.push(name);
+                        }
+                        break Some(at + 1);
+                    }
+                    Some(b"/>") => break Some(at + 2),
+                    None => return ran_out,
+                    _ => {}
+                }
+                let attribute = name_end(at);
+                if attribute == at {
+                    return Extent::Invalid;
+                }
+                at = skip_space(attribute);
+                if data.get(at) != Some(&b'=') {
+                    // HTML's bare attributes, such as `disabled`
+                    continue;
+                }
+                at = skip_space(at + 1);
+                let Some(&quote) = data.get(at) else {
+                    return ran_out;
+                };
+                if quote != b'"' && quote != b'\'' {
+                    return Extent::Invalid;
+                }
+                match data[at + 1..].iter().position(|&b| b == quote || b == b'<') {
+                    Some(len) if data[at + 1 + len] == quote => at += len + 2,
+                    Some(_) => return Extent::Invalid,
+                    None => return ran_out,
+                }
+            }
+        };
+        match end {
+            Some(end) => {
+                seen_root |= rest[1] != b'!' && rest[1] != b'?';
+                i = end;
+            }
+            None => return ran_out,
+        }
+    }
+}
+
+/// Follow the JSON document at `data[0]` to its end
+fn json_extent(data: &[u8], max_length: usize) -> Extent {
+    let mut closers: Vec<u8> = Vec::new();
+    let (mut in_string, mut escaped) = (false, false);
+    for (i, &byte) in data.iter().enumerate().take(max_length) {
+        if in_string {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                0..=0x1f => return Extent::Invalid,
+                _ => {}
+            }
+            continue;
+        }
+        match byte {
+            b'{' | b'[' if closers.len() < MAX_NESTING => {
+                closers.push(if byte == b'{' { b'}' } else { b']' });
+            }
+            b'}' | b']' => {
+                if closers.pop() != Some(byte) {
+                    return Extent::Invalid;
+                }
+                if closers.is_empty() {
+                    return Extent::Complete(i + 1);
+                }
+            }
+            b'"' => in_string = true,
+            b'0'..=b'9' | b'-' | b'+' | b'.' | b'e' | b'E' | b',' | b':' => {}
+            b't' | b'r' | b'u' | b'f' | b'a' | b'l' | b's' | b'n' => {}
+            b' ' | b'\t' | b'\r' | b'\n' => {}
+            _ => return Extent::Invalid,
+        }
+    }
+    if data.len() < max_length {
+        Extent::Incomplete
+    } else {
+        Extent::Invalid
+    }
+}
+
+/// A document found in a buffer
+struct Found {
+    offset: usize,
+    length: usize,
+    format: DocumentFormat,
+    http: Option<Box<HttpMessage>>,
+}
+
+/// The documents in `data`, and how much of it is done with: unless
+/// `last`, a document running past the end is left for when more bytes
+/// follow
+fn carve(data: &[u8], options: &CarveOptions, last: bool) -> (Vec<Found>, usize) {
+    let json = options.formats.contains(&DocumentFormat::Json);
+    let xml = options.formats.contains(&DocumentFormat::Xml);
+    let web = options.formats.contains(&DocumentFormat::Http);
+    let mut found = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let rest = &data[i..];
+        if web && rest[0].is_ascii_uppercase() && http::starts(rest) {
+            match http::parse(rest, options.max_length, last) {
+                Parsed::Message(length, message) if length >= options.min_length => {
+                    found.push(Found {
+                        offset: i,
+                        length,
+                        format: DocumentFormat::Http,
+                        http: Some(message),
+                    });
+                    i += length;
+                    continue;
+                }
+                Parsed::Incomplete => return (found, i),
+                _ => {}
+            }
+        }
+        let (format, extent) = match rest[0] {
+            b'{' | b'[' if json && json_starts(rest) => {
+                (DocumentFormat::Json, json_extent(rest, options.max_length))
+            }
+            b'<' if xml && xml_starts(rest) => {
+                (DocumentFormat::Xml, xml_extent(rest, options.max_length))
+            }
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+        match extent {
+            Extent::Complete(length)
+                if length >= options.min_length && parses(format, &rest[..length]) =>
+            {
+                found.push(Found {
+                    offset: i,
+                    length,
+                    format,
+                    http: None,
+                });
+                i += length;
+            }
+            Extent::Incomplete if !last => return (found, i),
+            _ => i += 1,
+        }
+    }
+    (found, i)
+}
+
+/// Whether `document`, whose extent is known, is valid as a whole
+fn parses(format: DocumentFormat, document: &[u8]) -> bool {
+    match format {
+        DocumentFormat::Json => serde_json::from_slice::<IgnoredAny>(document).is_ok(),
+        DocumentFormat::Xml => std::str::from_utf8(document).is_ok(),
+        DocumentFormat::Http => true,
+    }
+}
+
+/// Carves documents out of each region it is fed
+pub struct CarveVisitor {
+    options: CarveOptions,
+    buffer: Vec<u8>,
+    /// Address of the first byte in `buffer`
+    start: u64,
+    found: Vec<CarvedDocument>,
+}
+
+impl CarveVisitor {
+    pub fn new(options: &CarveOptions) -> Self {
+        CarveVisitor {
+            options: options.clone(),
+            buffer: Vec::new(),
+            start: 0,
+            found: Vec::new(),
+        }
+    }
+
+    fn flush(&mut self, last: bool) {
+        let (documents, done) = carve(&self.buffer, &self.options, last);
+        for document in documents {
+            let mut text = &self.buffer[document.offset..document.offset + document.length];
+            if document.http.is_some() {
+                let head = text.windows(4).position(|w| w == b"\r\n\r\n");
+                let head = head.map_or(text.len(), |at| at + 4);
+                text = &text[..head];
+            }
+            self.found.push(CarvedDocument {
+                // Set in end_region, once the region is known
+                location: Location::new(&Arc::from(""), 0, self.start + document.offset as u64),
+                format: document.format,
+                length: document.length,
+                text: String::from_utf8_lossy(text).into_owned(),
+                http: document.http,
+            });
+        }
+        self.buffer.drain(..done);
+        self.start += done as u64;
+    }
+}
+
+impl RegionVisitor for CarveVisitor {
+    type Output = CarvedDocument;
+
+    fn visit_chunk(&mut self, _region: &MemoryRegion, address: u64, chunk: &[u8]) {
+        // A gap is where pages were skipped, which no document spans
+        if address != self.start + self.buffer.len() as u64 {
+            self.flush(true);
+            self.buffer.clear();
+            self.start = address;
+        }
+        self.buffer.extend_from_slice(chunk);
+        // Carve once there is a document's worth past the oldest open one
+        if self.buffer.len() >= 2 * self.options.max_length.max(chunk.len()) {
+            self.flush(false);
+        }
+    }
+
+    fn end_region(
+        &mut self,
+        region: &MemoryRegion,
+        _reader: &mut dyn MemoryReader,
+        sink: &mut dyn ScanSink<CarvedDocument>,
+    ) {
+        self.flush(true);
+        self.buffer.clear();
+        let name: Arc<str> = region.name().into();
+        for mut document in self.found.drain(..) {
+            document.location = Location::new(&name, region.start, document.location.address);
+            sink.result(document);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps::parse_maps_line;
+    use crate::reader::AutoReader;
+    use crate::scan::Scan;
+
+    #[test]
+    fn test_carves_whole_json_documents() {
+        let first = r#"{"user": {"id": 42, "name": "a} b", "roles": ["admin"]}, "ok": true}"#;
+        let second = r#"[{"k": "v\"}"}, null, -1.5e3]"#;
+        let mut data = b"\x00\x01{not json} {\"x\": \x07} [1, 2] ".to_vec();
+        let first_at = data.len() as u64;
+        data.extend_from_slice(first.as_bytes());
+        data.extend_from_slice(b"\x00\xff");
+        let at = data.len();
+        data.extend_from_slice(second.as_bytes());
+        data.extend_from_slice(b" {\"cut\": [1, 2,");
+
+        let region = parse_maps_line("10000-10100 rw-p 00000000 00:00 0 [heap]").unwrap();
+        let mut visitor = CarveVisitor::new(&CarveOptions::default());
+        // The second document straddles two chunks
+        visitor.visit_chunk(&region, 0x10000, &data[..at + 8]);
+        visitor.visit_chunk(&region, 0x10000 + at as u64 + 8, &data[at + 8..]);
+        let mut scan = Scan::default();
+        visitor.end_region(&region, &mut AutoReader::new(0), &mut scan);
+
+        let found: Vec<(u64, &str)> =
+            scan.results.iter().map(|d| (d.location.offset, d.text.as_str())).collect();
+        assert_eq!(found, [(first_at, first), (at as u64, second)]);
+    }
+
+    #[test]
+    fn test_carves_well_formed_xml() {
+        let prefs = "<?xml version='1.0' encoding='utf-8' standalone='yes' ?>\n\
+            <map>\n    <string name=\"token\">a&amp;b</string>\n    \
+            <boolean name=\"seen\" value=\"true\" />\n    <!-- note -->\n</map>";
+        let html = "<div class=\"x\"><p>Hi<br>there</p><input disabled></div>";
+        let data = format!(
+            "\u{1}<b>unclosed <i>x</b></i> {}\u{0}<a x=1>no</a> {} <p>open",
+            prefs, html
+        );
+        let options = CarveOptions {
+            formats: vec![DocumentFormat::Xml],
+            ..CarveOptions::default()
+        };
+
+        let (found, done) = carve(data.as_bytes(), &options, false);
+        let texts: Vec<&str> = found.iter().map(|f| &data[f.offset..f.offset + f.length]).collect();
+        assert_eq!(texts, [prefs, html]);
+        // The last element may still end in the next chunk
+        assert_eq!(&data[done..], "<p>open");
+    }
+}
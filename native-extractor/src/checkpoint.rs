@@ -0,0 +1,265 @@
+//! Checkpoints that let an interrupted string scan resume
+//!
+//! With a checkpoint file, every string and region a scan finishes is also
+//! written there, as NDJSON lines in the [`crate::stream`] format after a
+//! `checkpoint` line naming the process, and the file is flushed as each
+//! region finishes. If the scan is cut short, by a timeout, a cancel or the
+//! app being killed, the next scan given the same file keeps the regions
+//! finished before and reads only the rest. A region that was being read
+//! when the scan stopped is read again from its start.
+//!
+//! A checkpoint made for another process, told apart by PID and command
+//! line, is thrown away and the scan starts over; after a reboot that is
+//! always the case.
+
+use crate::maps::MemoryRegion;
+use crate::scan::{ExtractedString, RegionResult, Scan, ScanSink};
+use crate::stream::{NdjsonWriter, Record};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+/// First line of a checkpoint file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Header {
+    pid: i32,
+    #[serde(default)]
+    cmdline: Option<String>,
+}
+
+impl Record for Header {
+    const KIND: &'static str = "checkpoint";
+}
+
+/// A line of a checkpoint file, by its `type`
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum Line {
+    Checkpoint(Header),
+    String(ExtractedString),
+    Region(RegionResult),
+}
+
+/// An open checkpoint file, holding what an earlier scan finished
+pub struct Checkpoint {
+    path: PathBuf,
+    writer: NdjsonWriter<BufWriter<File>>,
+    resumed: Scan<ExtractedString>,
+    /// Whether an earlier checkpoint was thrown away, being for another
+    /// process
+    pub discarded: bool,
+}
+
+impl Checkpoint {
+    /// Open the checkpoint at `path` for a scan of `pid`, creating it if
+    /// needed, and load what it holds.
+    ///
+    /// The file is written afresh with only what is kept: lines cut off by
+    /// a crash, and strings of regions that weren't finished, are dropped.
+    pub fn open(path: &Path, pid: i32, cmdline: Option<&str>) -> io::Result<Self> {
+        let header = Header {
+            pid,
+            cmdline: cmdline.map(str::to_string),
+        };
+        let (resumed, discarded) = match File::open(path) {
+            Ok(file) => read_checkpoint(BufReader::new(file), &header)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => (Scan::default(), false),
+            Err(e) => return Err(e),
+        };
+
+        let mut writer = NdjsonWriter::new(BufWriter::new(File::create(path)?));
+        writer.write(&header);
+        for string in &resumed.results {
+            writer.write(string);
+        }
+        for region in &resumed.regions {
+            writer.write(region);
+        }
+        writer.flush();
+        Ok(Checkpoint {
+            path: path.to_path_buf(),
+            writer,
+            resumed,
+            discarded,
+        })
+    }
+
+    /// The regions of `regions` an earlier scan didn't finish
+    pub fn remaining(&self, regions: &[MemoryRegion]) -> Vec<MemoryRegion> {
+        regions
+            .iter()
+            .filter(|region| !self.resumed.regions.iter().any(|done| is_same(&done.region, region)))
+            .cloned()
+            .collect()
+    }
+
+    /// How many regions an earlier scan finished
+    pub fn resumed_regions(&self) -> usize {
+        self.resumed.regions.len()
+    }
+
+    /// Move what an earlier scan finished into `scan`, ahead of anything
+    /// already there
+    pub fn take_resumed(&mut self, scan: &mut Scan<ExtractedString>) {
+        let resumed = std::mem::take(&mut self.resumed);
+        scan.regions.splice(0..0, resumed.regions);
+        scan.results.splice(0..0, resumed.results);
+    }
+
+    /// A sink that records what reaches `inner` in the checkpoint. Regions
+    /// the walk stopped in aren't recorded as finished.
+    pub fn sink<'a>(
+        &'a mut self,
+        inner: &'a mut dyn ScanSink<ExtractedString>,
+    ) -> CheckpointSink<'a> {
+        CheckpointSink {
+            writer: &mut self.writer,
+            inner,
+        }
+    }
+
+    /// Close the checkpoint, deleting it once the scan is `complete` so the
+    /// next one starts over, and returning the first write error there was
+    pub fn finish(self, complete: bool) -> io::Result<()> {
+        self.writer.close()?;
+        if complete {
+            fs::remove_file(&self.path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether two regions are the same mapping
+fn is_same(a: &MemoryRegion, b: &MemoryRegion) -> bool {
+    a.start == b.start && a.end == b.end && a.perms == b.perms && a.path == b.path
+}
+
+/// The finished regions of a checkpoint and the strings found in them, if
+/// it was for the process of `header`, and whether it was thrown away
+fn read_checkpoint(
+    reader: impl BufRead,
+    header: &Header,
+) -> io::Result<(Scan<ExtractedString>, bool)> {
+    let mut lines = reader.lines();
+    let first = match lines.next() {
+        Some(line) => serde_json::from_str::<Line>(&line?).ok(),
+        None => return Ok((Scan::default(), false)),
+    };
+    if !matches!(first, Some(Line::Checkpoint(found)) if found == *header) {
+        return Ok((Scan::default(), true));
+    }
+
+    let mut scan = Scan::default();
+    let mut strings = Vec::new();
+    for line in lines {
+        // A line cut off when the app was killed is left out
+        match serde_json::from_str::<Line>(&line?) {
+            Ok(Line::String(string)) => strings.push(string),
+            Ok(Line::Region(region)) => scan.regions.push(region),
+            Ok(Line::Checkpoint(_)) | Err(_) => {}
+        }
+    }
+
+    scan.regions.sort_by_key(|result| result.region.start);
+    scan.results = strings
+        .into_iter()
+        .filter(|string| {
+            let address = string.location.address;
+            let index = scan.regions.partition_point(|result| result.region.end <= address);
+            scan.regions.get(index).is_some_and(|result| result.region.start <= address)
+        })
+        .collect();
+    Ok((scan, false))
+}
+
+/// Passes results on to another sink, recording them in a [`Checkpoint`]
+pub struct CheckpointSink<'a> {
+    writer: &'a mut NdjsonWriter<BufWriter<File>>,
+    inner: &'a mut dyn ScanSink<ExtractedString>,
+}
+
+impl ScanSink<ExtractedString> for CheckpointSink<'_> {
+    fn region(&mut self, result: RegionResult) {
+        if !result.interrupted {
+            self.writer.write(&result);
+            self.writer.flush();
+        }
+        self.inner.region(result);
+    }
+
+    fn result(&mut self, item: ExtractedString) {
+        self.writer.write(&item);
+        self.inner.result(item);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps::parse_maps_line;
+    use crate::scan::Location;
+    use crate::strings::StringEncoding;
+
+    fn region_result(line: &str) -> RegionResult {
+        RegionResult {
+            region: parse_maps_line(line).unwrap(),
+            bytes_read: 0x1000,
+            error: None,
+            skipped: Vec::new(),
+            elapsed_us: 0,
+            interrupted: false,
+            pid: None,
+        }
+    }
+
+    fn string_at(address: u64) -> ExtractedString {
+        ExtractedString {
+            location: Location::new(&"[anon:test]".into(), address & !0xfff, address),
+            encoding: StringEncoding::Ascii,
+            value: format!("string at {:x}", address),
+            count: None,
+            addresses: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_resumes_finished_regions_only() {
+        let path = std::env::temp_dir().join(format!("checkpoint-{}.ndjson", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let regions: Vec<MemoryRegion> = ["1000-2000", "2000-3000", "3000-4000"]
+            .iter()
+            .map(|range| parse_maps_line(&format!("{} rw-p 00000000 00:00 0", range)).unwrap())
+            .collect();
+
+        let mut checkpoint = Checkpoint::open(&path, 42, Some("app")).unwrap();
+        assert_eq!(checkpoint.remaining(&regions).len(), 3);
+        let mut scan = Scan::default();
+        let mut sink = checkpoint.sink(&mut scan);
+        sink.result(string_at(0x1010));
+        sink.region(region_result("1000-2000 rw-p 00000000 00:00 0"));
+        sink.result(string_at(0x2020));
+        // Stopped partway through the second region
+        let mut interrupted = region_result("2000-3000 rw-p 00000000 00:00 0");
+        interrupted.interrupted = true;
+        sink.region(interrupted);
+        checkpoint.finish(false).unwrap();
+
+        let mut checkpoint = Checkpoint::open(&path, 42, Some("app")).unwrap();
+        assert!(!checkpoint.discarded);
+        assert_eq!(checkpoint.resumed_regions(), 1);
+        let remaining: Vec<u64> =
+            checkpoint.remaining(&regions).iter().map(|region| region.start).collect();
+        assert_eq!(remaining, [0x2000, 0x3000]);
+        let mut scan = Scan::default();
+        checkpoint.take_resumed(&mut scan);
+        assert_eq!(scan.results, [string_at(0x1010)]);
+        checkpoint.finish(false).unwrap();
+
+        let checkpoint = Checkpoint::open(&path, 43, Some("app")).unwrap();
+        assert!(checkpoint.discarded);
+        assert_eq!(checkpoint.resumed_regions(), 0);
+        checkpoint.finish(true).unwrap();
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,515 @@
+//! ELF core files of live processes
+//!
+//! The file is laid out the way the kernel writes one, so gdb and lldb load
+//! it as they would a crash dump: an ELF header, a `PT_NOTE` segment, then a
+//! `PT_LOAD` segment per region. The notes hold an `NT_PRSTATUS` with the
+//! registers of every thread (the main thread first), the process's
+//! `NT_AUXV` so debuggers can find the dynamic linker's state, and an
+//! `NT_FILE` table mapping regions back to the files they were mapped from.
+//!
+//! The core is written in the layout of the ABI this library was built for.
+
+use crate::elf::{ET_CORE, NT_AUXV, NT_FILE, NT_PRSTATUS, PF_R, PF_W, PF_X, PT_LOAD, PT_NOTE};
+use crate::maps::MemoryRegion;
+use crate::reader::{self, MemoryReader};
+use crate::scan::{self, RegionVisitor, ScanControl, ScanSink, StopReason};
+use crate::threads;
+use nix::errno::Errno;
+use nix::sys::ptrace;
+use nix::sys::wait::{waitpid, WaitPidFlag};
+use nix::unistd::Pid;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::mem::size_of;
+
+/// Segments start on a page boundary, like the kernel's cores
+const SEGMENT_ALIGN: u64 = 4096;
+
+/// The machine word of the target ABI; ELF32 or ELF64 follows from it
+const WORD: usize = size_of::<usize>();
+
+#[cfg(target_arch = "x86_64")]
+const MACHINE: Option<(u16, usize)> = Some((62, 27 * 8));
+#[cfg(target_arch = "aarch64")]
+const MACHINE: Option<(u16, usize)> = Some((183, 34 * 8));
+#[cfg(target_arch = "x86")]
+const MACHINE: Option<(u16, usize)> = Some((3, 17 * 4));
+#[cfg(target_arch = "arm")]
+const MACHINE: Option<(u16, usize)> = Some((40, 18 * 4));
+#[cfg(target_arch = "riscv64")]
+const MACHINE: Option<(u16, usize)> = Some((243, 32 * 8));
+/// `e_machine` and the size of `elf_gregset_t`, where cores are supported
+#[cfg(not(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "x86",
+    target_arch = "arm",
+    target_arch = "riscv64"
+)))]
+const MACHINE: Option<(u16, usize)> = None;
+
+/// General-purpose registers of one thread, as `elf_gregset_t`
+#[derive(Debug, Clone)]
+pub struct ThreadRegisters {
+    pub tid: i32,
+    pub regs: Vec<u8>,
+}
+
+/// The threads of a process held in ptrace-stop, resumed when dropped.
+///
+/// Unlike SIGSTOP, a tracer exiting unexpectedly detaches and resumes its
+/// tracees, so the target can't be left stopped.
+pub struct StoppedThreads {
+    tids: Vec<i32>,
+}
+
+impl StoppedThreads {
+    /// Stop every thread of `pid` with PTRACE_SEIZE and PTRACE_INTERRUPT.
+    ///
+    /// Threads that can't be stopped are listed in `errors` and skipped;
+    /// it is an error only if none could be.
+    pub fn stop(pid: i32, errors: &mut Vec<String>) -> io::Result<Self> {
+        let mut stopped = StoppedThreads { tids: Vec::new() };
+        let mut first_error = None;
+        let mut tids = threads::list_threads(pid)?;
+        // Debuggers take the first thread status note to be the main thread's
+        tids.sort_by_key(|&tid| tid != pid);
+        for tid in tids {
+            match stop_thread(tid) {
+                Ok(()) => stopped.tids.push(tid),
+                Err(e) => {
+                    errors.push(format!("Failed to stop thread {}: {}", tid, e));
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        match (stopped.tids.is_empty(), first_error) {
+            (true, Some(e)) => Err(e),
+            _ => Ok(stopped),
+        }
+    }
+
+    pub fn tids(&self) -> &[i32] {
+        &self.tids
+    }
+
+    /// Registers of each stopped thread; threads whose registers can't be
+    /// read are listed in `errors` and left out
+    pub fn registers(&self, errors: &mut Vec<String>) -> Vec<ThreadRegisters> {
+        let Some((_, gregset_size)) = MACHINE else {
+            return Vec::new();
+        };
+        let mut registers = Vec::new();
+        for &tid in &self.tids {
+            match read_gregset(tid, gregset_size) {
+                Ok(regs) => registers.push(ThreadRegisters { tid, regs }),
+                Err(e) => errors.push(format!("Failed to read registers of {}: {}", tid, e)),
+            }
+        }
+        registers
+    }
+}
+
+impl Drop for StoppedThreads {
+    fn drop(&mut self) {
+        for &tid in &self.tids {
+            let _ = ptrace::detach(Pid::from_raw(tid), None);
+        }
+    }
+}
+
+fn stop_thread(tid: i32) -> io::Result<()> {
+    let tid = Pid::from_raw(tid);
+    ptrace::seize(tid, ptrace::Options::empty()).map_err(io::Error::from)?;
+    let waited = ptrace::interrupt(tid).and_then(|()| loop {
+        match waitpid(tid, Some(WaitPidFlag::__WALL)) {
+            Err(Errno::EINTR) => continue,
+            result => break result.map(drop),
+        }
+    });
+    if let Err(e) = waited {
+        let _ = ptrace::detach(tid, None);
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+/// Read a stopped thread's general-purpose registers, padded to `size`
+fn read_gregset(tid: i32, size: usize) -> io::Result<Vec<u8>> {
+    let mut regs = read_regset(tid)?;
+    regs.resize(size, 0);
+    Ok(regs)
+}
+
+/// Read a stopped thread's general-purpose registers with PTRACE_GETREGSET,
+/// in the layout the kernel gives for the thread: that of a 32-bit ABI for
+/// a 32-bit process, even under a 64-bit kernel
+pub fn read_regset(tid: i32) -> io::Result<Vec<u8>> {
+    // Larger than the `elf_gregset_t` of any ABI; the kernel says how much
+    // it filled
+    let mut regs = vec![0u8; 512];
+    let mut iov = libc::iovec {
+        iov_base: regs.as_mut_ptr().cast(),
+        iov_len: regs.len(),
+    };
+    // SAFETY: the kernel writes at most iov_len bytes to iov_base, which
+    // points to a live buffer of that size
+    let result = unsafe {
+        libc::ptrace(
+            libc::PTRACE_GETREGSET,
+            tid,
+            NT_PRSTATUS as usize as *mut libc::c_void,
+            &mut iov as *mut libc::iovec,
+        )
+    };
+    if result == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    regs.truncate(iov.iov_len);
+    Ok(regs)
+}
+
+/// Write an ELF core of `pid` to `path`, with a `PT_LOAD` segment for each
+/// of `regions` and a thread status note for each of `registers`.
+///
+/// Parts of regions that can't be read are zero in the file; they are
+/// recorded in `sink` like any other scan.
+pub fn write_core(
+    pid: i32,
+    reader: &mut dyn MemoryReader,
+    regions: &[MemoryRegion],
+    registers: &[ThreadRegisters],
+    path: &str,
+    sink: &mut dyn ScanSink<()>,
+    control: &mut ScanControl,
+) -> io::Result<Option<StopReason>> {
+    let Some((machine, gregset_size)) = MACHINE else {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Core files aren't supported on this architecture",
+        ));
+    };
+    let phnum = u16::try_from(regions.len() + 1)
+        .ok()
+        .filter(|&phnum| phnum < u16::MAX)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Too many regions"))?;
+
+    let ppid = parent_pid(pid);
+    let mut notes = Vec::new();
+    for thread in registers {
+        let status = prstatus(thread, pid, ppid, gregset_size);
+        push_note(&mut notes, NT_PRSTATUS, &status);
+    }
+    if let Ok(auxv) = fs::read(format!("/proc/{}/auxv", pid)) {
+        push_note(&mut notes, NT_AUXV, &auxv);
+    }
+    push_note(&mut notes, NT_FILE, &file_note(regions));
+
+    let headers_size = ehdr_size() + usize::from(phnum) * phdr_size();
+    let notes_offset = headers_size as u64;
+    let mut file_size = notes_offset + notes.len() as u64;
+    let mut offset = align_up(file_size, SEGMENT_ALIGN);
+    let mut offsets = Vec::with_capacity(regions.len());
+    let mut headers = Vec::with_capacity(headers_size);
+    push_ehdr(&mut headers, machine, phnum);
+    push_phdr(
+        &mut headers,
+        Phdr {
+            kind: PT_NOTE,
+            flags: 0,
+            offset: notes_offset,
+            vaddr: 0,
+            size: notes.len() as u64,
+            align: 4,
+        },
+    );
+    for region in regions {
+        let mut flags = 0;
+        if region.perms.read {
+            flags |= PF_R;
+        }
+        if region.perms.write {
+            flags |= PF_W;
+        }
+        if region.perms.execute {
+            flags |= PF_X;
+        }
+        push_phdr(
+            &mut headers,
+            Phdr {
+                kind: PT_LOAD,
+                flags,
+                offset,
+                vaddr: region.start,
+                size: region.size(),
+                align: SEGMENT_ALIGN,
+            },
+        );
+        offsets.push(offset);
+        file_size = offset + region.size();
+        offset = align_up(file_size, SEGMENT_ALIGN);
+    }
+
+    let file = File::create(path)?;
+    let mut out = BufWriter::new(&file);
+    out.write_all(&headers)?;
+    out.write_all(&notes)?;
+
+    let mut visitor = SegmentWriter {
+        out,
+        position: notes_offset + notes.len() as u64,
+        regions,
+        offsets: &offsets,
+        next: 0,
+        failed: None,
+    };
+    let stopped = scan::walk_regions(reader, regions, &mut visitor, sink, control);
+    if let Some(e) = visitor.failed {
+        return Err(e);
+    }
+    visitor.out.flush()?;
+    drop(visitor);
+    // Unread pages, and gaps the writes skipped over, read as zeros
+    file.set_len(file_size)?;
+    Ok(stopped)
+}
+
+/// Copies each region's chunks to its segment's place in the file
+struct SegmentWriter<'a> {
+    out: BufWriter<&'a File>,
+    /// Where the next write lands without seeking
+    position: u64,
+    regions: &'a [MemoryRegion],
+    offsets: &'a [u64],
+    /// Index of the region the walk is expected to reach next
+    next: usize,
+    failed: Option<io::Error>,
+}
+
+impl RegionVisitor for SegmentWriter<'_> {
+    type Output = ();
+
+    fn visit_chunk(&mut self, region: &MemoryRegion, address: u64, chunk: &[u8]) {
+        if self.failed.is_some() {
+            return;
+        }
+        // Regions are walked in order, so the lookup only moves forward
+        while self.regions[self.next].start != region.start {
+            self.next += 1;
+        }
+        let position = self.offsets[self.next] + (address - region.start);
+        let mut written = Ok(());
+        if position != self.position {
+            written = self.out.seek(SeekFrom::Start(position)).map(drop);
+        }
+        match written.and_then(|()| self.out.write_all(chunk)) {
+            Ok(()) => self.position = position + chunk.len() as u64,
+            Err(e) => self.failed = Some(e),
+        }
+    }
+
+    fn end_region(
+        &mut self,
+        _region: &MemoryRegion,
+        _reader: &mut dyn MemoryReader,
+        _sink: &mut dyn ScanSink<()>,
+    ) {
+    }
+}
+
+struct Phdr {
+    kind: u32,
+    flags: u32,
+    offset: u64,
+    vaddr: u64,
+    size: u64,
+    align: u64,
+}
+
+fn ehdr_size() -> usize {
+    if WORD == 8 {
+        64
+    } else {
+        52
+    }
+}
+
+fn phdr_size() -> usize {
+    if WORD == 8 {
+        56
+    } else {
+        32
+    }
+}
+
+fn push_ehdr(out: &mut Vec<u8>, machine: u16, phnum: u16) {
+    let class = if WORD == 8 { 2 } else { 1 };
+    let data = if cfg!(target_endian = "little") { 1 } else { 2 };
+    out.extend_from_slice(&[0x7f, b'E', b'L', b'F', class, data, 1, 0]);
+    out.extend_from_slice(&[0; 8]);
+    out.extend_from_slice(&ET_CORE.to_ne_bytes());
+    out.extend_from_slice(&machine.to_ne_bytes());
+    out.extend_from_slice(&1u32.to_ne_bytes());
+    push_word(out, 0); // e_entry
+    push_word(out, ehdr_size() as u64); // e_phoff
+    push_word(out, 0); // e_shoff
+    out.extend_from_slice(&0u32.to_ne_bytes()); // e_flags
+    for half in [ehdr_size() as u16, phdr_size() as u16, phnum, 0, 0, 0] {
+        out.extend_from_slice(&half.to_ne_bytes());
+    }
+}
+
+/// Program headers differ in field order, not just width, between classes
+fn push_phdr(out: &mut Vec<u8>, phdr: Phdr) {
+    out.extend_from_slice(&phdr.kind.to_ne_bytes());
+    if WORD == 8 {
+        out.extend_from_slice(&phdr.flags.to_ne_bytes());
+    }
+    push_word(out, phdr.offset);
+    push_word(out, phdr.vaddr);
+    push_word(out, 0); // p_paddr
+    push_word(out, phdr.size); // p_filesz
+    push_word(out, phdr.size); // p_memsz
+    if WORD == 4 {
+        out.extend_from_slice(&phdr.flags.to_ne_bytes());
+    }
+    push_word(out, phdr.align);
+}
+
+fn push_word(out: &mut Vec<u8>, value: u64) {
+    out.extend_from_slice(&value.to_ne_bytes()[..WORD]);
+}
+
+/// Append a note named `CORE`, padding name and descriptor to 4 bytes
+fn push_note(out: &mut Vec<u8>, kind: u32, desc: &[u8]) {
+    const NAME: &[u8] = b"CORE\0";
+    out.extend_from_slice(&(NAME.len() as u32).to_ne_bytes());
+    out.extend_from_slice(&(desc.len() as u32).to_ne_bytes());
+    out.extend_from_slice(&kind.to_ne_bytes());
+    for bytes in [NAME, desc] {
+        out.extend_from_slice(bytes);
+        out.resize(align_up(out.len() as u64, 4) as usize, 0);
+    }
+}
+
+/// `struct elf_prstatus` for one thread. Only the IDs and registers are
+/// filled in; signal state and CPU times are left zero.
+fn prstatus(thread: &ThreadRegisters, pid: i32, ppid: i32, gregset_size: usize) -> Vec<u8> {
+    // elf_siginfo and pr_cursig, then pr_sigpend and pr_sighold
+    let ids = 16 + 2 * WORD;
+    // pr_pid, pr_ppid, pr_pgrp, pr_sid, then four struct timevals
+    let regs = ids + 16 + 8 * WORD;
+    let size = align_up((regs + gregset_size + 4) as u64, WORD as u64) as usize;
+
+    let mut status = vec![0u8; size];
+    for (i, id) in [thread.tid, ppid, pid, pid].into_iter().enumerate() {
+        status[ids + 4 * i..ids + 4 * i + 4].copy_from_slice(&id.to_ne_bytes());
+    }
+    status[regs..regs + gregset_size].copy_from_slice(&thread.regs);
+    status
+}
+
+/// The `NT_FILE` table: each file-backed region with the page offset it
+/// maps, then their paths
+fn file_note(regions: &[MemoryRegion]) -> Vec<u8> {
+    let page_size = reader::page_size() as u64;
+    let mapped: Vec<_> = regions
+        .iter()
+        .filter(|region| region.path.as_deref().is_some_and(|path| path.starts_with('/')))
+        .collect();
+
+    let mut note = Vec::new();
+    push_word(&mut note, mapped.len() as u64);
+    push_word(&mut note, page_size);
+    for region in &mapped {
+        push_word(&mut note, region.start);
+        push_word(&mut note, region.end);
+        push_word(&mut note, region.offset / page_size);
+    }
+    for region in &mapped {
+        note.extend_from_slice(region.name().as_bytes());
+        note.push(0);
+    }
+    note
+}
+
+fn parent_pid(pid: i32) -> i32 {
+    fs::read_to_string(format!("/proc/{}/stat", pid))
+        .ok()
+        .and_then(|stat| {
+            let (_, fields) = stat.rsplit_once(')')?;
+            fields.split_whitespace().nth(1)?.parse().ok()
+        })
+        .unwrap_or(0)
+}
+
+fn align_up(value: u64, align: u64) -> u64 {
+    value.div_ceil(align) * align
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dump;
+    use crate::maps;
+    use crate::reader::ProcfsReader;
+    use std::path::Path;
+    use crate::scan::Scan;
+
+    #[test]
+    fn test_core_has_loadable_segment_with_region_contents() {
+        let data = vec![0xa5u8; 2 * 4096];
+        let start = data.as_ptr() as u64;
+        let pid = std::process::id() as i32;
+        let containing = maps::read_maps(pid)
+            .unwrap()
+            .into_iter()
+            .find(|region| region.contains(start))
+            .unwrap();
+        let region = MemoryRegion {
+            start,
+            end: start + data.len() as u64,
+            ..containing
+        };
+        let registers = [ThreadRegisters {
+            tid: pid,
+            regs: vec![0; MACHINE.unwrap().1],
+        }];
+
+        let path = std::env::temp_dir().join(format!("core-test-{}", pid));
+        let path = path.to_str().unwrap();
+        let mut reader = ProcfsReader::open(pid).unwrap();
+        let mut scan = Scan::default();
+        let regions = [region];
+        let mut control = ScanControl::new();
+        write_core(pid, &mut reader, &regions, &registers, path, &mut scan, &mut control)
+            .unwrap();
+        let core = fs::read(path).unwrap();
+        let mut loaded = dump::load_dump(Path::new(path)).unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(&core[..4], b"\x7fELF");
+        assert_eq!(u16::from_ne_bytes([core[16], core[17]]), ET_CORE);
+        // The load segment follows the note segment's header
+        let phdr = ehdr_size() + phdr_size();
+        assert_eq!(u32::from_ne_bytes(core[phdr..phdr + 4].try_into().unwrap()), PT_LOAD);
+        let word = |at: usize| {
+            let mut bytes = [0u8; 8];
+            bytes[..WORD].copy_from_slice(&core[at..at + WORD]);
+            u64::from_ne_bytes(bytes)
+        };
+        let (offset, vaddr) = if WORD == 8 { (phdr + 8, phdr + 16) } else { (phdr + 4, phdr + 8) };
+        assert_eq!(word(vaddr), start);
+        let offset = word(offset) as usize;
+        assert_eq!(&core[offset..offset + data.len()], &data[..]);
+        assert!(core[ehdr_size() + 2 * phdr_size()..].windows(5).any(|name| name == b"CORE\0"));
+
+        // Loaded back, the region is at its original address
+        assert_eq!(loaded.pid, pid);
+        assert_eq!((loaded.regions[0].start, loaded.regions[0].end), (start, regions[0].end));
+        assert_eq!(loaded.regions[0].path, regions[0].path.clone().filter(|p| p.starts_with('/')));
+        let mut read_back = vec![0u8; data.len()];
+        reader::read_into(loaded.reader.as_mut(), start, &mut read_back).unwrap();
+        assert_eq!(read_back, data);
+    }
+}
@@ -0,0 +1,72 @@
+//! CRC-32 (ISO 3309), the checksum of PNG chunks, ZIP entries and gzip
+//! members
+
+use std::sync::OnceLock;
+
+/// The reflected polynomial
+const POLYNOMIAL: u32 = 0xedb88320;
+
+fn table() -> &'static [u32; 256] {
+    static TABLE: OnceLock<[u32; 256]> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let mut table = [0u32; 256];
+        for (n, entry) in table.iter_mut().enumerate() {
+            *entry = (0..8).fold(n as u32, |c, _| {
+                if c & 1 != 0 { POLYNOMIAL ^ (c >> 1) } else { c >> 1 }
+            });
+        }
+        table
+    })
+}
+
+/// Incremental CRC-32 over data fed in any number of pieces
+#[derive(Clone)]
+pub struct Crc32 {
+    value: u32,
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Crc32 { value: !0 }
+    }
+}
+
+impl Crc32 {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        let table = table();
+        for &byte in data {
+            let index = (self.value ^ u32::from(byte)) & 0xff;
+            self.value = table[index as usize] ^ (self.value >> 8);
+        }
+    }
+
+    pub fn finish(&self) -> u32 {
+        !self.value
+    }
+}
+
+/// CRC-32 of `data`
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(data);
+    crc.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+        let mut crc = Crc32::new();
+        crc.update(b"1234");
+        crc.update(b"56789");
+        assert_eq!(crc.finish(), 0xcbf43926);
+    }
+}
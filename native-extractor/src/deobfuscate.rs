@@ -0,0 +1,308 @@
+//! Strings hidden by single-byte XOR or rotation
+//!
+//! Obfuscators and hand-rolled string protection often do no more than
+//! XOR each byte of a string with a fixed key, or add a fixed amount to it,
+//! so string extraction sees nothing but noise. Candidate buffers are runs
+//! of non-zero bytes between NULs, as a C string or a zero-padded array is,
+//! that don't already read as text. Each is decoded with every XOR key and every
+//! rotation, subtracting `n` from each byte modulo 256, and the decoding
+//! with the most printable bytes, the one reading most like English text
+//! among those, is reported if nearly all of it is printable and most of it
+//! letters, digits and spaces.
+//!
+//! A decoding must be mostly printable ASCII, the 95 bytes from space to
+//! tilde, so a buffer is only tried if nearly all of it falls within four
+//! adjacent 32-byte spans, as an XORed or rotated string does; random bytes
+//! are spread over all eight. Rotations of letters alone, such as ROT13, leave text
+//! printable, so they can't be told apart this way and aren't undone.
+
+use crate::maps::MemoryRegion;
+use crate::reader::MemoryReader;
+use crate::scan::{Location, RegionVisitor, ScanSink};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Longest `maxLength` allowed
+pub const MAX_BUFFER_LENGTH: usize = 1 << 20;
+
+/// Share of a decoding, besides being printable, that must be letters,
+/// digits or spaces
+const MIN_TEXT_SHARE: f64 = 0.75;
+
+/// The `deobfuscate` object of the scan options
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct DeobfuscateOptions {
+    /// Shortest buffer tried, in bytes
+    pub min_length: usize,
+    /// Longest buffer tried; longer runs are more likely binary data
+    pub max_length: usize,
+    /// Share of a decoding, from 0 to 1, that must be printable
+    pub min_printable: f64,
+}
+
+impl Default for DeobfuscateOptions {
+    fn default() -> Self {
+        DeobfuscateOptions {
+            min_length: 10,
+            max_length: 1024,
+            min_printable: 0.95,
+        }
+    }
+}
+
+/// Ways a string can be hidden
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Method {
+    /// Each byte XORed with the key
+    Xor,
+    /// The key added to each byte, modulo 256
+    Rot,
+}
+
+impl Method {
+    fn decode(self, byte: u8, key: u8) -> u8 {
+        match self {
+            Method::Xor => byte ^ key,
+            Method::Rot => byte.wrapping_sub(key),
+        }
+    }
+}
+
+/// A buffer that decodes to text
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeobfuscatedString {
+    #[serde(flatten)]
+    pub location: Location,
+    pub method: Method,
+    pub key: u8,
+    pub length: usize,
+    /// Share of the decoded bytes that are printable
+    pub printable: f64,
+    /// The decoded bytes, with anything non-printable escaped
+    pub text: String,
+}
+
+/// Whether `byte` is printable ASCII or whitespace text would hold
+fn is_printable(byte: u8) -> bool {
+    (32..=126).contains(&byte) || matches!(byte, b'\t' | b'\n' | b'\r')
+}
+
+fn is_text(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b' '
+}
+
+/// How often each letter is seen in English text, per thousand letters
+const LETTER_FREQUENCIES: [u32; 26] = [
+    82, 15, 28, 43, 127, 22, 20, 61, 70, 2, 8, 40, 24, 67, 75, 19, 1, 60, 63, 91, 28, 10, 24, 2,
+    20, 1,
+];
+
+/// How like text `byte` is, so that of the decodings that are all
+/// printable the one reading most like text is picked, rather than one
+/// with every letter shifted by one
+fn text_weight(byte: u8) -> u32 {
+    match byte {
+        b'a'..=b'z' => LETTER_FREQUENCIES[usize::from(byte - b'a')],
+        b'A'..=b'Z' => LETTER_FREQUENCIES[usize::from(byte - b'A')] / 2,
+        b' ' => 150,
+        b'0'..=b'9' => 20,
+        b'.' | b'/' | b':' | b'-' | b'_' | b',' => 10,
+        _ => 0,
+    }
+}
+
+/// The method and key `buffer` decodes to text best with, and how many of
+/// its bytes are then printable, if nearly all of them are
+pub fn best_decoding(buffer: &[u8], min_printable: f64) -> Option<(Method, u8, usize)> {
+    let len = buffer.len();
+    let needed = (min_printable * len as f64).ceil() as usize;
+    let mut spans = [0usize; 8];
+    let mut counts = [0usize; 256];
+    for &byte in buffer {
+        spans[usize::from(byte >> 5)] += 1;
+        counts[usize::from(byte)] += 1;
+    }
+    if (0..8).all(|first| (first..first + 4).map(|span| spans[span % 8]).sum::<usize>() < needed) {
+        return None;
+    }
+    let bytes: Vec<(u8, usize)> =
+        (0..=255u8).zip(counts).filter(|&(_, count)| count > 0).collect();
+    let score = |method: Method, key: u8| {
+        let (mut printable, mut weight, mut text) = (0, 0, 0);
+        for &(byte, count) in &bytes {
+            let decoded = method.decode(byte, key);
+            if is_printable(decoded) {
+                printable += count;
+            }
+            weight += text_weight(decoded) as usize * count;
+            if is_text(decoded) {
+                text += count;
+            }
+        }
+        (printable, weight, text)
+    };
+    let is_text_share = |text: usize| text as f64 >= MIN_TEXT_SHARE * len as f64;
+    // Text already, for string extraction to find
+    let (printable, _, text) = score(Method::Xor, 0);
+    if printable >= needed && is_text_share(text) {
+        return None;
+    }
+    let mut best = None;
+    let mut best_score = (0, 0, 0);
+    for method in [Method::Xor, Method::Rot] {
+        for key in 1..=255 {
+            let score = score(method, key);
+            if score > best_score {
+                best = Some((method, key));
+                best_score = score;
+            }
+        }
+    }
+    let (printable, _, text) = best_score;
+    let (method, key) = best?;
+    (printable >= needed && is_text_share(text)).then_some((method, key, printable))
+}
+
+/// Finds the buffers in each region it is fed that decode to text
+pub struct DeobfuscateVisitor<'a> {
+    options: &'a DeobfuscateOptions,
+    /// Start of the run of non-zero bytes being read
+    start: u64,
+    /// The run, unless it grew past `maxLength`
+    run: Option<Vec<u8>>,
+    /// Where the next chunk starts if it carries on from the last one
+    next: Option<u64>,
+    /// The strings found in the region being read
+    found: Vec<(u64, Vec<u8>, Method, u8, usize)>,
+}
+
+impl<'a> DeobfuscateVisitor<'a> {
+    pub fn new(options: &'a DeobfuscateOptions) -> Self {
+        DeobfuscateVisitor {
+            options,
+            start: 0,
+            run: Some(Vec::new()),
+            next: None,
+            found: Vec::new(),
+        }
+    }
+
+    /// Try the run that just ended, and start a new one at `next`
+    fn end_run(&mut self, next: u64) {
+        if let Some(run) = self.run.replace(Vec::new()) {
+            if run.len() >= self.options.min_length {
+                if let Some((method, key, printable)) =
+                    best_decoding(&run, self.options.min_printable)
+                {
+                    self.found.push((self.start, run, method, key, printable));
+                }
+            }
+        }
+        self.start = next;
+    }
+
+    /// Add `bytes` to the current run, dropping it once too long
+    fn extend(&mut self, bytes: &[u8]) {
+        if let Some(run) = &mut self.run {
+            if run.len() + bytes.len() > self.options.max_length {
+                self.run = None;
+            } else {
+                run.extend_from_slice(bytes);
+            }
+        }
+    }
+}
+
+impl RegionVisitor for DeobfuscateVisitor<'_> {
+    type Output = DeobfuscatedString;
+
+    fn visit_chunk(&mut self, _region: &MemoryRegion, address: u64, chunk: &[u8]) {
+        if self.next != Some(address) {
+            self.end_run(address);
+        }
+        self.next = Some(address + chunk.len() as u64);
+        let mut from = 0;
+        while let Some(zero) = chunk[from..].iter().position(|&byte| byte == 0) {
+            self.extend(&chunk[from..from + zero]);
+            from += zero + 1;
+            self.end_run(address + from as u64);
+        }
+        self.extend(&chunk[from..]);
+    }
+
+    fn end_region(
+        &mut self,
+        region: &MemoryRegion,
+        _reader: &mut dyn MemoryReader,
+        sink: &mut dyn ScanSink<DeobfuscatedString>,
+    ) {
+        self.end_run(0);
+        self.next = None;
+        let name: Arc<str> = region.name().into();
+        for (address, run, method, key, printable) in std::mem::take(&mut self.found) {
+            let decoded: Vec<u8> = run.iter().map(|&byte| method.decode(byte, key)).collect();
+            sink.result(DeobfuscatedString {
+                location: Location::new(&name, region.start, address),
+                method,
+                key,
+                length: run.len(),
+                printable: printable as f64 / run.len() as f64,
+                text: decoded.escape_ascii().to_string(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps::parse_maps_line;
+    use crate::reader::ProcfsReader;
+    use crate::scan::{self, Scan, ScanControl};
+
+    const TEXT: &[u8] = b"https://api.example.com/v1 token abc123";
+
+    #[test]
+    fn test_best_decoding() {
+        let xored: Vec<u8> = TEXT.iter().map(|&b| b ^ 0x5a).collect();
+        let length = TEXT.len();
+        assert_eq!(best_decoding(&xored, 0.95), Some((Method::Xor, 0x5a, length)));
+        let rotated: Vec<u8> = TEXT.iter().map(|&b| b.wrapping_add(0x93)).collect();
+        assert_eq!(best_decoding(&rotated, 0.95), Some((Method::Rot, 0x93, length)));
+        assert_eq!(best_decoding(TEXT, 0.95), None);
+        // Bytes spread over the whole range
+        let spread: Vec<u8> = (1..=40u8).map(|i| i.wrapping_mul(97)).collect();
+        assert_eq!(best_decoding(&spread, 0.95), None);
+    }
+
+    #[test]
+    fn test_finds_hidden_strings() {
+        let mut data = vec![0u8; 0x2000];
+        let xored: Vec<u8> = TEXT.iter().map(|&b| b ^ 0xc3).collect();
+        // Across the end of a chunk
+        data[0xff0..0xff0 + xored.len()].copy_from_slice(&xored);
+        data[0x1100..0x1100 + TEXT.len()].copy_from_slice(TEXT);
+        // Too long to be tried
+        data[0x1400..0x1400 + 0x500].fill(b'x' ^ 0xc3);
+        let start = data.as_ptr() as u64;
+        let end = start + data.len() as u64;
+        let region = parse_maps_line(&format!("{:x}-{:x} rw-p 00000000 00:00 0", start, end));
+
+        let options = DeobfuscateOptions::default();
+        let mut visitor = DeobfuscateVisitor::new(&options);
+        let mut reader = ProcfsReader::open(std::process::id() as i32).unwrap();
+        let mut scan = Scan::default();
+        let regions = [region.unwrap()];
+        let mut control = ScanControl::new().chunk_size(Some(0x1000));
+        scan::walk_regions(&mut reader, &regions, &mut visitor, &mut scan, &mut control);
+
+        assert_eq!(scan.results.len(), 1);
+        let found = &scan.results[0];
+        assert_eq!(found.location.address - start, 0xff0);
+        assert_eq!((found.method, found.key, found.printable), (Method::Xor, 0xc3, 1.0));
+        assert_eq!(found.text.as_bytes(), TEXT);
+    }
+}
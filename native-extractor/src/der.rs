@@ -0,0 +1,246 @@
+//! Just enough DER to read certificates and keys, and PEM to wrap them in
+//!
+//! Elements are read with definite lengths in their shortest form only,
+//! as DER has them, so a candidate that isn't DER fails early. Nothing is
+//! copied: an [`Element`] borrows its bytes from the data read.
+
+use crate::base64;
+use crate::reader::{self, MemoryReader};
+use serde::Serialize;
+
+pub const BOOLEAN: u8 = 0x01;
+pub const INTEGER: u8 = 0x02;
+pub const BIT_STRING: u8 = 0x03;
+pub const OCTET_STRING: u8 = 0x04;
+pub const NULL: u8 = 0x05;
+pub const OID: u8 = 0x06;
+pub const UTC_TIME: u8 = 0x17;
+pub const GENERALIZED_TIME: u8 = 0x18;
+pub const SEQUENCE: u8 = 0x30;
+pub const SET: u8 = 0x31;
+
+/// The tag of `[n]`, constructed
+pub const fn context(n: u8) -> u8 {
+    0xa0 | n
+}
+
+/// How a structure was held in memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Encoding {
+    Der,
+    Pem,
+}
+
+/// A DER element's tag and contents, and the whole of it, header and all
+#[derive(Debug, Clone, Copy)]
+pub struct Element<'a> {
+    pub tag: u8,
+    pub contents: &'a [u8],
+    pub whole: &'a [u8],
+}
+
+/// The elements of a constructed one, in turn
+pub struct Elements<'a> {
+    pub rest: &'a [u8],
+}
+
+impl<'a> Elements<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Elements { rest: data }
+    }
+
+    pub fn peek(&self) -> Option<u8> {
+        self.rest.first().copied()
+    }
+
+    /// The next element, which must be tagged `tag`
+    pub fn tagged(&mut self, tag: u8) -> Option<Element<'a>> {
+        self.next().filter(|element| element.tag == tag)
+    }
+
+    /// The next element if it is tagged `tag`, leaving any other for later
+    pub fn optional(&mut self, tag: u8) -> Option<Element<'a>> {
+        if self.peek() == Some(tag) {
+            self.next()
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a> Iterator for Elements<'a> {
+    type Item = Element<'a>;
+
+    /// The next element, or `None` at the end or if it doesn't hold
+    /// together
+    fn next(&mut self) -> Option<Element<'a>> {
+        let (header, length) = header(self.rest)?;
+        let whole = self.rest.get(..header + length)?;
+        self.rest = &self.rest[whole.len()..];
+        Some(Element {
+            tag: whole[0],
+            contents: &whole[header..],
+            whole,
+        })
+    }
+}
+
+/// The sizes of the header and contents of the element at the start of
+/// `data`, from its tag and length alone
+fn header(data: &[u8]) -> Option<(usize, usize)> {
+    let (&tag, rest) = data.split_first()?;
+    let (&first, rest) = rest.split_first()?;
+    // High tag numbers aren't used in certificates or keys
+    if tag & 0x1f == 0x1f {
+        return None;
+    }
+    match first {
+        0..=0x7f => Some((2, first as usize)),
+        0x81..=0x84 => {
+            let bytes = rest.get(..(first & 0x7f) as usize)?;
+            if bytes[0] == 0 || (bytes.len() == 1 && bytes[0] < 0x80) {
+                return None;
+            }
+            Some((2 + bytes.len(), bytes.iter().fold(0, |length, &b| length << 8 | b as usize)))
+        }
+        _ => None,
+    }
+}
+
+/// The one element of `data`, or `None` if anything follows it
+pub fn single(data: &[u8], tag: u8) -> Option<Element<'_>> {
+    let mut elements = Elements::new(data);
+    let element = elements.tagged(tag)?;
+    elements.rest.is_empty().then_some(element)
+}
+
+/// The bytes the element at the start of `data` takes up, header and
+/// all, read from its tag and length alone, or `None` if they aren't DER
+pub fn element_length(data: &[u8]) -> Option<usize> {
+    header(data).map(|(header, length)| header + length)
+}
+
+/// An element tagged `tag` holding `contents`
+pub fn encode(tag: u8, contents: &[u8]) -> Vec<u8> {
+    let length = contents.len();
+    let mut out = vec![tag];
+    if length < 0x80 {
+        out.push(length as u8);
+    } else {
+        let bytes = length.to_be_bytes();
+        let skip = bytes.iter().take_while(|&&b| b == 0).count();
+        out.push(0x80 | (bytes.len() - skip) as u8);
+        out.extend_from_slice(&bytes[skip..]);
+    }
+    out.extend_from_slice(contents);
+    out
+}
+
+/// An OID in dotted form
+pub fn oid(bytes: &[u8]) -> Option<String> {
+    let mut arcs = Vec::new();
+    let mut arc = 0u64;
+    for (i, &byte) in bytes.iter().enumerate() {
+        if arc == 0 && byte == 0x80 || arc >> 56 != 0 {
+            return None;
+        }
+        arc = arc << 7 | (byte & 0x7f) as u64;
+        if byte & 0x80 == 0 {
+            if arcs.is_empty() {
+                let first = (arc / 40).min(2);
+                arcs.push(first);
+                arcs.push(arc - first * 40);
+            } else {
+                arcs.push(arc);
+            }
+            arc = 0;
+        } else if i == bytes.len() - 1 {
+            return None;
+        }
+    }
+    if arcs.is_empty() {
+        return None;
+    }
+    let arcs: Vec<String> = arcs.iter().map(u64::to_string).collect();
+    Some(arcs.join("."))
+}
+
+/// `der` as PEM text with the `label` of its `BEGIN` and `END` lines
+pub fn to_pem(label: &str, der: &[u8]) -> String {
+    let encoded = base64::encode(der);
+    let mut pem = format!("-----BEGIN {}-----\n", label);
+    for line in encoded.as_bytes().chunks(64) {
+        pem.push_str(std::str::from_utf8(line).expect("base64 is ASCII"));
+        pem.push('\n');
+    }
+    pem.push_str(&format!("-----END {}-----\n", label));
+    pem
+}
+
+/// The label and decoded body of the PEM text at the start of `text`, or
+/// `None` if it doesn't end in the matching `END` line or has headers or
+/// anything else that isn't base64 in its body
+pub fn from_pem(text: &[u8]) -> Option<(&str, Vec<u8>)> {
+    let rest = text.strip_prefix(b"-----BEGIN ")?;
+    let label_end = rest.iter().take(64).position(|&b| b == b'-')?;
+    let label = std::str::from_utf8(&rest[..label_end]).ok()?;
+    let rest = rest[label_end..].strip_prefix(b"-----")?;
+    let end = format!("-----END {}-----", label);
+    let end = rest.windows(end.len()).position(|window| window == end.as_bytes())?;
+    let body: Vec<u8> =
+        rest[..end].iter().copied().filter(|b| !b.is_ascii_whitespace()).collect();
+    Some((label, base64::decode(&body)?))
+}
+
+/// Read the DER structure at `address`, given as DER or as PEM text, up
+/// to `max_length` bytes of either. What follows the structure in PEM is
+/// left out.
+pub fn read_at(
+    reader: &mut dyn MemoryReader,
+    address: u64,
+    max_length: usize,
+) -> Option<(Encoding, Vec<u8>)> {
+    let mut head = [0u8; 6];
+    let read = reader::read_into(reader, address, &mut head).unwrap_or(0);
+    if head[0] == SEQUENCE {
+        let mut der = vec![0u8; element_length(&head[..read])?];
+        if der.len() > max_length
+            || reader::read_into(reader, address, &mut der).unwrap_or(0) < der.len()
+        {
+            return None;
+        }
+        Some((Encoding::Der, der))
+    } else {
+        let mut text = vec![0u8; max_length];
+        let read = reader::read_into(reader, address, &mut text).unwrap_or(0);
+        let (_, decoded) = from_pem(&text[..read])?;
+        let length = element_length(&decoded).filter(|&length| length <= decoded.len())?;
+        Some((Encoding::Pem, decoded[..length].to_vec()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_elements() {
+        // SEQUENCE { INTEGER 5, OID 1.2.840.113549.1.1.1, NULL }
+        let der = b"\x30\x10\x02\x01\x05\x06\x09\x2a\x86\x48\x86\xf7\x0d\x01\x01\x01\x05\x00";
+        let sequence = single(der, SEQUENCE).unwrap();
+        assert_eq!(element_length(der), Some(der.len()));
+        let mut fields = Elements::new(sequence.contents);
+        assert_eq!(fields.tagged(INTEGER).unwrap().contents, [5]);
+        assert!(fields.optional(BOOLEAN).is_none());
+        assert_eq!(oid(fields.tagged(OID).unwrap().contents).unwrap(), "1.2.840.113549.1.1.1");
+        assert_eq!(fields.tagged(NULL).unwrap().whole, [NULL, 0]);
+        assert!(fields.next().is_none());
+        assert_eq!(encode(SEQUENCE, sequence.contents), der);
+
+        // Long form where the short one fits, and a length past the end
+        assert!(single(b"\x04\x81\x01\x00", OCTET_STRING).is_none());
+        assert!(single(b"\x04\x05\x00", OCTET_STRING).is_none());
+        assert_eq!(encode(OCTET_STRING, &[0; 300])[..4], [OCTET_STRING, 0x82, 0x01, 0x2c]);
+    }
+}
@@ -0,0 +1,256 @@
+//! Carving of DEX files out of process memory
+//!
+//! Packed and protected apps keep their real bytecode encrypted on disk and
+//! only decrypt it in memory, where ART loads it from. A DEX file starts
+//! with a fixed header: the `dex\n` magic and a three-digit version, the
+//! Adler-32 checksum and SHA-1 signature of the rest of the file, then its
+//! size, the size of the header itself and an endian tag. Regions are
+//! searched for the magic at 4-byte alignment, which ART requires of the
+//! DEX files it opens, and each header that holds together is followed to
+//! its `file_size` and written out.
+//!
+//! The checksum is verified but not required to match: protectors often
+//! scrub or alter headers once a file is loaded, and the bytecode is still
+//! worth having.
+
+use crate::adler32::adler32;
+use crate::dump::DumpedFile;
+use crate::maps::MemoryRegion;
+use crate::reader::{self, MemoryReader};
+use crate::scan::{self, RegionVisitor, ScanControl, ScanSink, StopReason};
+use crate::sha256::Sha256;
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Size of the header of every DEX file
+pub const HEADER_SIZE: usize = 0x70;
+
+const MAGIC: &[u8; 4] = b"dex\n";
+const ENDIAN_CONSTANT: u32 = 0x1234_5678;
+const ALIGN: usize = 4;
+
+/// Largest DEX file carved; dex files are limited to 65536 methods, which
+/// keeps real ones far below this
+const MAX_FILE_SIZE: u32 = 256 * 1024 * 1024;
+
+/// The fields of a DEX header that [`parse_header`] checks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DexHeader {
+    /// Format version, e.g. 35 or 39
+    pub version: u32,
+    pub checksum: u32,
+    pub file_size: u32,
+}
+
+/// A DEX file found in memory and written to disk
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CarvedDex {
+    #[serde(flatten)]
+    pub file: DumpedFile,
+    pub version: u32,
+    /// Whether the Adler-32 checksum in the header matches the contents
+    pub checksum_valid: bool,
+}
+
+/// Parse the DEX header at the start of `bytes`, if it is one: the magic
+/// and version, a header size of [`HEADER_SIZE`], little-endian, and a file
+/// size large enough to hold the header and the map at `map_off`
+pub fn parse_header(bytes: &[u8]) -> Option<DexHeader> {
+    let header = bytes.get(..HEADER_SIZE)?;
+    let version = header.get(4..8)?;
+    if &header[..4] != MAGIC || version[3] != 0 || !version[..3].iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    let u32_at = |offset: usize| u32::from_le_bytes(header[offset..offset + 4].try_into().unwrap());
+    let file_size = u32_at(0x20);
+    let map_off = u32_at(0x34);
+    let valid = u32_at(0x24) == HEADER_SIZE as u32
+        && u32_at(0x28) == ENDIAN_CONSTANT
+        && (HEADER_SIZE as u32..=MAX_FILE_SIZE).contains(&file_size)
+        && map_off < file_size;
+    valid.then(|| DexHeader {
+        version: version[..3].iter().fold(0, |n, digit| n * 10 + u32::from(digit - b'0')),
+        checksum: u32_at(8),
+        file_size,
+    })
+}
+
+/// File a DEX file found at `address` is written to
+pub fn dex_file_name(address: u64) -> String {
+    format!("{:08x}.dex", address)
+}
+
+/// Search `regions` for DEX files and write each to its own file in `dir`,
+/// delivering a [`CarvedDex`] for each.
+///
+/// A file that runs past the end of its region, or into memory that can't
+/// be read, is left out. Failing to write to `dir` is an error; files
+/// written before the failure are left in place.
+pub fn carve_dex_files(
+    reader: &mut dyn MemoryReader,
+    regions: &[MemoryRegion],
+    dir: &Path,
+    sink: &mut dyn ScanSink<CarvedDex>,
+    control: &mut ScanControl,
+) -> io::Result<Option<StopReason>> {
+    let mut visitor = DexVisitor {
+        dir,
+        found: Vec::new(),
+        failed: None,
+    };
+    let stopped = scan::walk_regions(reader, regions, &mut visitor, sink, control);
+    match visitor.failed {
+        Some(e) => Err(e),
+        None => Ok(stopped),
+    }
+}
+
+struct DexVisitor<'a> {
+    dir: &'a Path,
+    /// Addresses of the magics found in the region being read
+    found: Vec<u64>,
+    /// The first write failure; nothing more is written after one
+    failed: Option<io::Error>,
+}
+
+impl DexVisitor<'_> {
+    /// Read the DEX file at `address`, if its header holds together and it
+    /// lies within `region`, and write it out
+    fn carve(
+        &self,
+        region: &MemoryRegion,
+        reader: &mut dyn MemoryReader,
+        address: u64,
+    ) -> io::Result<Option<CarvedDex>> {
+        let mut header = [0u8; HEADER_SIZE];
+        if reader::read_into(reader, address, &mut header).unwrap_or(0) < HEADER_SIZE {
+            return Ok(None);
+        }
+        let Some(header) = parse_header(&header) else {
+            return Ok(None);
+        };
+        let size = u64::from(header.file_size);
+        if address + size > region.end {
+            return Ok(None);
+        }
+        let mut data = vec![0u8; size as usize];
+        if reader::read_into(reader, address, &mut data).unwrap_or(0) < data.len() {
+            return Ok(None);
+        }
+
+        let name = dex_file_name(address);
+        fs::write(self.dir.join(&name), &data)?;
+        let mut hash = Sha256::new();
+        hash.update(&data);
+        Ok(Some(CarvedDex {
+            file: DumpedFile {
+                start: address,
+                file: name,
+                size,
+                sha256: hash.finish_hex(),
+            },
+            version: header.version,
+            checksum_valid: adler32(&data[12..]) == header.checksum,
+        }))
+    }
+}
+
+impl RegionVisitor for DexVisitor<'_> {
+    type Output = CarvedDex;
+
+    fn visit_chunk(&mut self, _region: &MemoryRegion, address: u64, chunk: &[u8]) {
+        // Chunks start page-aligned, so no aligned magic spans two of them
+        let skew = (address % ALIGN as u64) as usize;
+        let first = (ALIGN - skew) % ALIGN;
+        let Some(chunk) = chunk.get(first..) else {
+            return;
+        };
+        for (i, word) in chunk.chunks_exact(ALIGN).enumerate() {
+            if word == MAGIC {
+                self.found.push(address + (first + i * ALIGN) as u64);
+            }
+        }
+    }
+
+    fn end_region(
+        &mut self,
+        region: &MemoryRegion,
+        reader: &mut dyn MemoryReader,
+        sink: &mut dyn ScanSink<CarvedDex>,
+    ) {
+        for address in std::mem::take(&mut self.found) {
+            if self.failed.is_some() {
+                return;
+            }
+            match self.carve(region, reader, address) {
+                Ok(Some(dex)) => sink.result(dex),
+                Ok(None) => {}
+                Err(e) => self.failed = Some(e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps::parse_maps_line;
+    use crate::reader::ProcfsReader;
+    use crate::scan::Scan;
+
+    /// A minimal well-formed DEX file of `size` bytes
+    fn dex_file(size: usize) -> Vec<u8> {
+        let mut dex = vec![0u8; size];
+        dex[..8].copy_from_slice(b"dex\n039\0");
+        dex[0x20..0x24].copy_from_slice(&(size as u32).to_le_bytes());
+        dex[0x24..0x28].copy_from_slice(&(HEADER_SIZE as u32).to_le_bytes());
+        dex[0x28..0x2c].copy_from_slice(&ENDIAN_CONSTANT.to_le_bytes());
+        dex[0x34..0x38].copy_from_slice(&(HEADER_SIZE as u32).to_le_bytes());
+        for (i, byte) in dex[HEADER_SIZE..].iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+        let checksum = adler32(&dex[12..]);
+        dex[8..12].copy_from_slice(&checksum.to_le_bytes());
+        dex
+    }
+
+    #[test]
+    fn test_carves_dex_files_within_their_region() {
+        let first = dex_file(0x200);
+        let second = dex_file(0x1000);
+        // The second header straddles the boundary of the first read chunk
+        let second_at = reader::READ_CHUNK_SIZE - 0x40;
+        let size = second_at + second.len() + 0x100;
+        let mut data = vec![0u8; size + ALIGN];
+        let start = (data.as_ptr() as u64).next_multiple_of(ALIGN as u64);
+        let skew = (start - data.as_ptr() as u64) as usize;
+        let bytes = &mut data[skew..skew + size];
+        bytes[0x40..0x40 + first.len()].copy_from_slice(&first);
+        bytes[second_at..second_at + second.len()].copy_from_slice(&second);
+        // A header claiming more than is left of the region
+        bytes[size - 0x80..size - 0x80 + HEADER_SIZE].copy_from_slice(&second[..HEADER_SIZE]);
+        let end = start + size as u64;
+        let region =
+            parse_maps_line(&format!("{:x}-{:x} rw-p 00000000 00:00 0 [anon:test]", start, end))
+                .unwrap();
+
+        let pid = std::process::id() as i32;
+        let dir = std::env::temp_dir().join(format!("dex-test-{}", pid));
+        fs::create_dir_all(&dir).unwrap();
+        let mut reader = ProcfsReader::open(pid).unwrap();
+        let mut scan = Scan::default();
+        let stopped =
+            carve_dex_files(&mut reader, &[region], &dir, &mut scan, &mut ScanControl::new());
+
+        assert_eq!(stopped.unwrap(), None);
+        let found: Vec<(u64, u64)> =
+            scan.results.iter().map(|dex| (dex.file.start, dex.file.size)).collect();
+        assert_eq!(found, [(start + 0x40, 0x200), (start + second_at as u64, 0x1000)]);
+        assert!(scan.results.iter().all(|dex| dex.version == 39 && dex.checksum_valid));
+        assert_eq!(fs::read(dir.join(&scan.results[1].file.file)).unwrap(), second);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
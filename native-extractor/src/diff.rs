@@ -0,0 +1,249 @@
+//! What changed in a process between two snapshots
+//!
+//! A [`ProcessSnapshot`] is the process's mappings, with the pages of each
+//! resident and swapped out as smaps gives them, and the distinct strings
+//! its memory held. Comparing two gives the mappings created and removed
+//! between them, those whose size, permissions, page counts or proportional
+//! sizes changed, and the strings that appeared and disappeared. Mappings are matched by start
+//! address and path, so a heap that grew in place is a changed mapping rather
+//! than a removed and a new one.
+
+use crate::maps::{MemoryRegion, Permissions};
+use crate::scan::ExtractedString;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
+
+/// A process's mappings and strings at one moment
+pub struct ProcessSnapshot {
+    pub taken: Instant,
+    pub regions: Vec<MemoryRegion>,
+    pub strings: HashSet<String>,
+}
+
+impl ProcessSnapshot {
+    pub fn new(regions: Vec<MemoryRegion>, strings: &[ExtractedString]) -> Self {
+        ProcessSnapshot {
+            taken: Instant::now(),
+            regions,
+            strings: strings.iter().map(|string| string.value.clone()).collect(),
+        }
+    }
+
+    /// What changed from this snapshot to `after`, whose `strings` were
+    /// found at the locations given; `page_size` is the size of a page of
+    /// the process
+    pub fn diff(
+        &self,
+        after: &ProcessSnapshot,
+        strings: Vec<ExtractedString>,
+        page_size: u64,
+    ) -> SnapshotDiff {
+        let key = |region: &MemoryRegion| (region.start, region.path.clone());
+        let before: HashMap<_, &MemoryRegion> =
+            self.regions.iter().map(|region| (key(region), region)).collect();
+        let later: HashSet<_> = after.regions.iter().map(key).collect();
+
+        let mut diff = SnapshotDiff {
+            elapsed_ms: after.taken.saturating_duration_since(self.taken).as_millis() as u64,
+            ..SnapshotDiff::default()
+        };
+        for region in &after.regions {
+            let Some(old) = before.get(&key(region)) else {
+                diff.new_regions.push(region.clone());
+                continue;
+            };
+            let pages = |region: &MemoryRegion| {
+                region.usage.map(|usage| (usage.rss / page_size, usage.swap / page_size))
+            };
+            let (resident, swapped) = pages(region).unzip();
+            let (resident_before, swapped_before) = pages(old).unzip();
+            let proportional = |region: &MemoryRegion| {
+                region.usage.map(|usage| (usage.pss, usage.swap_pss))
+            };
+            let (pss, swap_pss) = proportional(region).unzip();
+            let (pss_before, swap_pss_before) = proportional(old).unzip();
+            if old.end != region.end
+                || old.perms != region.perms
+                || resident != resident_before
+                || swapped != swapped_before
+                || pss != pss_before
+                || swap_pss != swap_pss_before
+            {
+                diff.changed_regions.push(RegionChange {
+                    region: region.clone(),
+                    size_before: old.size(),
+                    perms_before: old.perms,
+                    resident_pages_before: resident_before,
+                    resident_pages: resident,
+                    swapped_pages_before: swapped_before,
+                    swapped_pages: swapped,
+                    pss_before,
+                    pss,
+                    swap_pss_before,
+                    swap_pss,
+                });
+            }
+        }
+        diff.removed_regions = self
+            .regions
+            .iter()
+            .filter(|region| !later.contains(&key(region)))
+            .cloned()
+            .collect();
+
+        let mut reported = HashSet::new();
+        diff.new_strings = strings
+            .into_iter()
+            .filter(|string| !self.strings.contains(&string.value))
+            .filter(|string| reported.insert(string.value.clone()))
+            .collect();
+        let mut gone: Vec<String> = self.strings.difference(&after.strings).cloned().collect();
+        gone.sort_unstable();
+        diff.gone_strings = gone;
+        diff
+    }
+}
+
+/// A mapping in both snapshots that changed between them
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RegionChange {
+    /// The mapping as it is now
+    #[serde(flatten)]
+    pub region: MemoryRegion,
+    pub size_before: u64,
+    pub perms_before: Permissions,
+    /// Pages resident in RAM, if smaps gave them
+    pub resident_pages_before: Option<u64>,
+    pub resident_pages: Option<u64>,
+    /// Pages swapped out, if smaps gave them
+    pub swapped_pages_before: Option<u64>,
+    pub swapped_pages: Option<u64>,
+    /// Proportional set size in bytes, if smaps gave it: falls as other
+    /// processes come to share the mapping's pages even when none leave RAM
+    pub pss_before: Option<u64>,
+    pub pss: Option<u64>,
+    /// Swap divided between the processes sharing it, in bytes, if smaps
+    /// gave it
+    pub swap_pss_before: Option<u64>,
+    pub swap_pss: Option<u64>,
+}
+
+/// What changed between two snapshots
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotDiff {
+    /// Time between the snapshots
+    pub elapsed_ms: u64,
+    pub new_regions: Vec<MemoryRegion>,
+    pub removed_regions: Vec<MemoryRegion>,
+    pub changed_regions: Vec<RegionChange>,
+    /// Strings not in the first snapshot, the first place each was found
+    pub new_strings: Vec<ExtractedString>,
+    /// Strings the first snapshot held that are gone, sorted
+    pub gone_strings: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps::{parse_maps_line, parse_smaps, MemoryUsage};
+    use crate::scan::Location;
+    use crate::strings::StringEncoding;
+
+    fn string(value: &str) -> ExtractedString {
+        ExtractedString {
+            location: Location::new(&"[heap]".into(), 0x1000, 0x1000),
+            encoding: StringEncoding::Ascii,
+            value: value.to_string(),
+            count: None,
+            addresses: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_diff_snapshots() {
+        let region = |line: &str, rss: u64| MemoryRegion {
+            usage: Some(MemoryUsage {
+                rss,
+                ..MemoryUsage::default()
+            }),
+            ..parse_maps_line(line).unwrap()
+        };
+        let heap = region("1000-3000 rw-p 00000000 00:00 0 [heap]", 0x1000);
+        let library = region("7000-8000 r-xp 00000000 fd:00 12 /system/lib64/libc.so", 0x1000);
+        let stack = region("9000-a000 rw-p 00000000 00:00 0 [stack]", 0x1000);
+        let before = ProcessSnapshot::new(
+            vec![heap.clone(), library.clone(), stack.clone()],
+            &[string("kept"), string("gone")],
+        );
+
+        let grown = region("1000-4000 rw-p 00000000 00:00 0 [heap]", 0x3000);
+        let mapped = region("5000-6000 rw-p 00000000 00:00 0", 0);
+        let paged = region("9000-a000 rw-p 00000000 00:00 0 [stack]", 0);
+        let found = vec![string("kept"), string("new"), string("new")];
+        let after = ProcessSnapshot::new(vec![grown, mapped, paged], &found);
+        let diff = before.diff(&after, found, 0x1000);
+
+        assert_eq!(diff.new_regions.len(), 1);
+        assert_eq!(diff.new_regions[0].start, 0x5000);
+        assert_eq!(diff.removed_regions, [library]);
+        assert_eq!(diff.changed_regions.len(), 2);
+        let heap = &diff.changed_regions[0];
+        assert_eq!((heap.size_before, heap.region.size()), (0x2000, 0x3000));
+        assert_eq!((heap.resident_pages_before, heap.resident_pages), (Some(1), Some(3)));
+        assert_eq!(diff.changed_regions[1].resident_pages, Some(0));
+        let new: Vec<&str> = diff.new_strings.iter().map(|s| s.value.as_str()).collect();
+        assert_eq!(new, ["new"]);
+        assert_eq!(diff.gone_strings, ["gone"]);
+    }
+
+    #[test]
+    fn test_diff_reports_proportional_sizes() {
+        // Same pages resident and swapped, but the library came to be shared
+        // by a second process and half the heap's swap by a forked child
+        let before = parse_smaps(
+            "12c00000-12c04000 rw-p 00000000 00:00 0 [anon:dalvik-main space]\n\
+             Rss:                   8 kB\n\
+             Pss:                   8 kB\n\
+             Swap:                  8 kB\n\
+             SwapPss:               8 kB\n\
+             7f000000-7f002000 r-xp 00000000 fd:05 12 /system/lib64/libc.so\n\
+             Rss:                   8 kB\n\
+             Pss:                   4 kB\n\
+             7f100000-7f101000 r--p 00000000 fd:05 13 /system/lib64/libm.so\n\
+             Rss:                   4 kB\n\
+             Pss:                   4 kB\n",
+        );
+        let after = parse_smaps(
+            "12c00000-12c04000 rw-p 00000000 00:00 0 [anon:dalvik-main space]\n\
+             Rss:                   8 kB\n\
+             Pss:                   8 kB\n\
+             Swap:                  8 kB\n\
+             SwapPss:               4 kB\n\
+             7f000000-7f002000 r-xp 00000000 fd:05 12 /system/lib64/libc.so\n\
+             Rss:                   8 kB\n\
+             Pss:                   2 kB\n\
+             7f100000-7f101000 r--p 00000000 fd:05 13 /system/lib64/libm.so\n\
+             Rss:                   4 kB\n\
+             Pss:                   4 kB\n",
+        );
+        let before = ProcessSnapshot::new(before, &[]);
+        let after = ProcessSnapshot::new(after, &[]);
+        let diff = before.diff(&after, Vec::new(), 0x1000);
+
+        assert_eq!(diff.changed_regions.len(), 2);
+        let heap = &diff.changed_regions[0];
+        assert_eq!((heap.resident_pages_before, heap.resident_pages), (Some(2), Some(2)));
+        assert_eq!((heap.pss_before, heap.pss), (Some(8192), Some(8192)));
+        assert_eq!((heap.swap_pss_before, heap.swap_pss), (Some(8192), Some(4096)));
+        let library = &diff.changed_regions[1];
+        assert_eq!((library.pss_before, library.pss), (Some(4096), Some(2048)));
+
+        let json = serde_json::to_value(library).unwrap();
+        assert_eq!(json["pssBefore"], 4096);
+        assert_eq!(json["pss"], 2048);
+        assert_eq!(json["swapPss"], 0);
+    }
+}
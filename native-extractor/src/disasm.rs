@@ -0,0 +1,396 @@
+//! Disassembly of process memory, with the `capstone` feature
+//!
+//! Code found in memory, such as a stub an injector wrote or what a JIT
+//! emitted, can be read on the device rather than after pulling a dump to a
+//! workstation. The library links against Capstone 4 or 5 through the
+//! bindings below; RISC-V needs Capstone 5.
+//!
+//! Capstone decodes instructions one after another and knows nothing of
+//! functions, so where functions begin and end is guessed from the code:
+//! targets of direct calls within the range begin one, and so does the
+//! instruction after a return or unconditional jump when padding or a
+//! prologue follows. A return followed by more code of the same function
+//! doesn't split it, so a function with an early return can still be found
+//! whole, but one without a recognisable prologue that directly follows
+//! another is taken as part of it.
+
+use crate::registers::Arch;
+use crate::scan;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::ffi::{c_char, c_int, CStr};
+use std::ptr;
+
+/// Most bytes disassembled at once
+pub const MAX_LENGTH: usize = 64 << 10;
+
+/// The `disassemble` object of the scan options
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct DisassembleOptions {
+    /// Where disassembly starts
+    pub address: Option<u64>,
+    /// Bytes disassembled
+    pub length: usize,
+    /// Instruction set, by default that of the module `address` is in, or
+    /// the process's for memory outside any module
+    pub arch: Option<Arch>,
+    /// Decode arm32 code as Thumb, as is done too for odd addresses
+    pub thumb: bool,
+}
+
+impl Default for DisassembleOptions {
+    fn default() -> Self {
+        DisassembleOptions {
+            address: None,
+            length: 256,
+            arch: None,
+            thumb: false,
+        }
+    }
+}
+
+/// How an instruction changes the flow of control
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Flow {
+    Call,
+    Jump,
+    Return,
+}
+
+/// One decoded instruction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Instruction {
+    pub address: u64,
+    #[serde(serialize_with = "scan::serialize_hex")]
+    pub bytes: Vec<u8>,
+    /// e.g. `stp`, or `.byte` for bytes that aren't an instruction
+    pub mnemonic: String,
+    /// e.g. `x29, x30, [sp, #-0x10]!`
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub operands: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flow: Option<Flow>,
+    /// Where a direct call or jump goes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<u64>,
+}
+
+/// Where a function was guessed to lie
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Function {
+    pub start: u64,
+    /// Address after its last instruction
+    pub end: u64,
+    /// Whether the range cut the function off, or began where no function
+    /// recognisably does
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+}
+
+/// The code in a range of memory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Disassembly {
+    pub address: u64,
+    pub arch: Arch,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub thumb: bool,
+    /// The module `address` is in, and its offset from the module's base
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub module: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u64>,
+    pub instructions: Vec<Instruction>,
+    pub functions: Vec<Function>,
+}
+
+mod sys {
+    use std::ffi::{c_char, c_int, c_uint, c_void};
+
+    /// `csh`
+    pub type Handle = usize;
+
+    pub const CS_ERR_OK: c_int = 0;
+    pub const CS_ARCH_ARM: c_int = 0;
+    pub const CS_ARCH_ARM64: c_int = 1;
+    pub const CS_ARCH_X86: c_int = 3;
+    pub const CS_ARCH_RISCV: c_int = 15;
+    pub const CS_MODE_ARM: c_int = 0;
+    pub const CS_MODE_32: c_int = 1 << 2;
+    pub const CS_MODE_64: c_int = 1 << 3;
+    pub const CS_MODE_THUMB: c_int = 1 << 4;
+    pub const CS_MODE_RISCV64: c_int = 1 << 1;
+    pub const CS_MODE_RISCVC: c_int = 1 << 2;
+    pub const CS_OPT_DETAIL: c_int = 2;
+    pub const CS_OPT_SKIPDATA: c_int = 5;
+    pub const CS_OPT_ON: usize = 3;
+    pub const CS_GRP_JUMP: c_uint = 1;
+    pub const CS_GRP_CALL: c_uint = 2;
+    pub const CS_GRP_RET: c_uint = 3;
+
+    /// `cs_insn`, as laid out by Capstone 4 and 5
+    #[repr(C)]
+    pub struct Insn {
+        pub id: c_uint,
+        pub address: u64,
+        pub size: u16,
+        pub bytes: [u8; 24],
+        pub mnemonic: [c_char; 32],
+        pub op_str: [c_char; 160],
+        pub detail: *mut c_void,
+    }
+
+    #[link(name = "capstone")]
+    extern "C" {
+        pub fn cs_open(arch: c_int, mode: c_int, handle: *mut Handle) -> c_int;
+        pub fn cs_close(handle: *mut Handle) -> c_int;
+        pub fn cs_option(handle: Handle, kind: c_int, value: usize) -> c_int;
+        pub fn cs_disasm(
+            handle: Handle,
+            code: *const u8,
+            size: usize,
+            address: u64,
+            count: usize,
+            insn: *mut *mut Insn,
+        ) -> usize;
+        pub fn cs_free(insn: *mut Insn, count: usize);
+        pub fn cs_insn_group(handle: Handle, insn: *const Insn, group: c_uint) -> bool;
+        pub fn cs_strerror(code: c_int) -> *const c_char;
+    }
+}
+
+fn error_text(code: c_int) -> String {
+    let message = unsafe { sys::cs_strerror(code) };
+    if message.is_null() {
+        return format!("error {}", code);
+    }
+    unsafe { CStr::from_ptr(message) }.to_string_lossy().into_owned()
+}
+
+fn c_text(text: &[c_char]) -> String {
+    let bytes: Vec<u8> = text.iter().take_while(|&&c| c != 0).map(|&c| c as u8).collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// A Capstone handle set up for one instruction set
+pub struct Disassembler {
+    handle: sys::Handle,
+    arch: Arch,
+}
+
+impl Disassembler {
+    /// Open Capstone for `arch`, decoding arm32 as Thumb if `thumb`
+    pub fn new(arch: Arch, thumb: bool) -> Result<Self, String> {
+        let (cs_arch, mode) = match arch {
+            Arch::Arm64 => (sys::CS_ARCH_ARM64, sys::CS_MODE_ARM),
+            Arch::Arm if thumb => (sys::CS_ARCH_ARM, sys::CS_MODE_THUMB),
+            Arch::Arm => (sys::CS_ARCH_ARM, sys::CS_MODE_ARM),
+            Arch::X86_64 => (sys::CS_ARCH_X86, sys::CS_MODE_64),
+            Arch::X86 => (sys::CS_ARCH_X86, sys::CS_MODE_32),
+            Arch::Riscv64 => (sys::CS_ARCH_RISCV, sys::CS_MODE_RISCV64 | sys::CS_MODE_RISCVC),
+        };
+        let mut handle = 0;
+        let status = unsafe { sys::cs_open(cs_arch, mode, &mut handle) };
+        if status != sys::CS_ERR_OK {
+            return Err(format!("Capstone can't decode {}: {}", arch.name(), error_text(status)));
+        }
+        let disassembler = Disassembler { handle, arch };
+        // Details are needed for instruction groups; skipping data decodes
+        // what isn't an instruction as `.byte` and carries on after it
+        for option in [sys::CS_OPT_DETAIL, sys::CS_OPT_SKIPDATA] {
+            let status = unsafe { sys::cs_option(handle, option, sys::CS_OPT_ON) };
+            if status != sys::CS_ERR_OK {
+                return Err(format!("Failed to set up Capstone: {}", error_text(status)));
+            }
+        }
+        Ok(disassembler)
+    }
+
+    /// Decode `code`, which was read at `address`
+    pub fn disassemble(&mut self, code: &[u8], address: u64) -> Vec<Instruction> {
+        let mut insns = ptr::null_mut();
+        let count = unsafe {
+            sys::cs_disasm(self.handle, code.as_ptr(), code.len(), address, 0, &mut insns)
+        };
+        if count == 0 || insns.is_null() {
+            return Vec::new();
+        }
+        let decoded = unsafe { std::slice::from_raw_parts(insns, count) };
+        let instructions = decoded.iter().map(|insn| self.instruction(insn)).collect();
+        unsafe { sys::cs_free(insns, count) };
+        instructions
+    }
+
+    fn instruction(&self, insn: &sys::Insn) -> Instruction {
+        let mnemonic = c_text(&insn.mnemonic);
+        let operands = c_text(&insn.op_str);
+        let in_group = |group| unsafe { sys::cs_insn_group(self.handle, insn, group) };
+        let flow = if is_return(self.arch, &mnemonic, &operands) || in_group(sys::CS_GRP_RET) {
+            Some(Flow::Return)
+        } else if in_group(sys::CS_GRP_CALL) {
+            Some(Flow::Call)
+        } else if in_group(sys::CS_GRP_JUMP) {
+            Some(Flow::Jump)
+        } else {
+            None
+        };
+        // RISC-V branches show an offset, not where they go
+        let target = match flow {
+            Some(Flow::Call | Flow::Jump) if self.arch != Arch::Riscv64 => immediate(&operands),
+            _ => None,
+        };
+        let size = usize::from(insn.size).min(insn.bytes.len());
+        Instruction {
+            address: insn.address,
+            bytes: insn.bytes[..size].to_vec(),
+            mnemonic,
+            operands,
+            flow,
+            target,
+        }
+    }
+}
+
+impl Drop for Disassembler {
+    fn drop(&mut self) {
+        unsafe { sys::cs_close(&mut self.handle) };
+    }
+}
+
+/// The address a branch's last operand gives, if it is an immediate
+fn immediate(operands: &str) -> Option<u64> {
+    let last = operands.rsplit(',').next()?.trim().trim_start_matches('#');
+    u64::from_str_radix(last.strip_prefix("0x")?, 16).ok()
+}
+
+/// Returns Capstone may not put in its return group
+fn is_return(arch: Arch, mnemonic: &str, operands: &str) -> bool {
+    match arch {
+        Arch::Arm => {
+            (mnemonic == "bx" && operands == "lr")
+                || (matches!(mnemonic, "pop" | "ldm" | "ldmia") && operands.contains("pc"))
+        }
+        Arch::Riscv64 => matches!(mnemonic, "ret" | "c.jr") && operands.is_empty(),
+        _ => mnemonic.starts_with("ret"),
+    }
+}
+
+fn is_unconditional_jump(arch: Arch, mnemonic: &str) -> bool {
+    match arch {
+        Arch::Arm64 => mnemonic == "b" || mnemonic.starts_with("br"),
+        Arch::Arm => matches!(mnemonic, "b" | "b.w" | "bx"),
+        Arch::X86_64 | Arch::X86 => mnemonic == "jmp",
+        Arch::Riscv64 => matches!(mnemonic, "j" | "jr" | "c.j" | "c.jr"),
+    }
+}
+
+/// What compilers put between functions
+fn is_padding(instruction: &Instruction) -> bool {
+    matches!(instruction.mnemonic.as_str(), "nop" | "int3" | ".byte" | ".short" | ".long")
+}
+
+/// The first instruction of a typical function
+fn is_prologue(arch: Arch, instruction: &Instruction) -> bool {
+    let operands = instruction.operands.as_str();
+    match (arch, instruction.mnemonic.as_str()) {
+        (Arch::Arm64, "paciasp" | "pacibsp") => true,
+        (Arch::Arm64, "bti") => operands == "c",
+        (Arch::Arm64, "stp") => operands.starts_with("x29, x30, [sp") && operands.ends_with('!'),
+        (Arch::Arm, "push" | "push.w") => operands.contains("lr"),
+        (Arch::X86_64, "endbr64") | (Arch::X86, "endbr32") => true,
+        (Arch::X86_64, "push") => operands == "rbp",
+        (Arch::X86, "push") => operands == "ebp",
+        (Arch::Riscv64, "addi" | "c.addi" | "c.addi16sp") => operands.starts_with("sp, sp, -"),
+        _ => false,
+    }
+}
+
+/// Guess where the functions among `instructions` begin and end
+pub fn functions(arch: Arch, instructions: &[Instruction]) -> Vec<Function> {
+    let called: HashSet<u64> = instructions
+        .iter()
+        .filter(|instruction| instruction.flow == Some(Flow::Call))
+        .filter_map(|instruction| instruction.target)
+        .collect();
+    let mut functions: Vec<Function> = Vec::new();
+    // Whether the last instruction that wasn't padding could end a function
+    let mut ended = false;
+    let mut padded = false;
+    for instruction in instructions {
+        if is_padding(instruction) {
+            padded = true;
+            continue;
+        }
+        let prologue = is_prologue(arch, instruction);
+        let entry = called.contains(&instruction.address)
+            || (prologue && (ended || functions.is_empty()))
+            || (ended && padded);
+        let end = instruction.address + instruction.bytes.len() as u64;
+        match functions.last_mut() {
+            Some(function) if !entry => function.end = end,
+            _ => {
+                if let Some(function) = functions.last_mut() {
+                    function.truncated |= !ended;
+                }
+                functions.push(Function {
+                    start: instruction.address,
+                    end,
+                    truncated: !entry,
+                });
+            }
+        }
+        ended = instruction.flow == Some(Flow::Return)
+            || (instruction.flow == Some(Flow::Jump)
+                && is_unconditional_jump(arch, &instruction.mnemonic));
+        padded = false;
+    }
+    if let Some(function) = functions.last_mut() {
+        function.truncated |= !ended;
+    }
+    functions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disassembles_functions() {
+        // A leaf function, then one calling it; x86-64 as encoded by gcc
+        let code: &[u8] = &[
+            0xb8, 0x2a, 0x00, 0x00, 0x00, // mov eax, 0x2a
+            0xc3, // ret
+            0xcc, 0xcc, // int3
+            0x55, // push rbp
+            0x48, 0x89, 0xe5, // mov rbp, rsp
+            0xe8, 0xef, 0xff, 0xff, 0xff, // call 0x1000
+            0x5d, // pop rbp
+            0xc3, // ret
+        ];
+        let mut disassembler = Disassembler::new(Arch::X86_64, false).unwrap();
+        let instructions = disassembler.disassemble(code, 0x1000);
+        assert_eq!(instructions.len(), 9);
+        assert_eq!(instructions[0].mnemonic, "mov");
+        assert_eq!(instructions[0].operands, "eax, 0x2a");
+        assert_eq!(instructions[1].flow, Some(Flow::Return));
+        assert_eq!(instructions[6].flow, Some(Flow::Call));
+        assert_eq!(instructions[6].target, Some(0x1000));
+
+        let functions = functions(Arch::X86_64, &instructions);
+        let bounds: Vec<(u64, u64, bool)> =
+            functions.iter().map(|f| (f.start, f.end, f.truncated)).collect();
+        assert_eq!(bounds, [(0x1000, 0x1006, false), (0x1008, 0x1013, false)]);
+    }
+
+    #[test]
+    fn test_skips_data() {
+        // 0xffffffff is no arm64 instruction, then `ret`
+        let code = [0xff, 0xff, 0xff, 0xff, 0xc0, 0x03, 0x5f, 0xd6];
+        let mut disassembler = Disassembler::new(Arch::Arm64, false).unwrap();
+        let instructions = disassembler.disassemble(&code, 0x4000);
+        let mnemonics: Vec<&str> = instructions.iter().map(|i| i.mnemonic.as_str()).collect();
+        assert_eq!(mnemonics, [".byte", "ret"]);
+        assert_eq!(instructions[1].flow, Some(Flow::Return));
+    }
+}
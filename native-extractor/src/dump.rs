@@ -0,0 +1,446 @@
+//! Raw dumps of process memory to disk
+//!
+//! Each region is written to its own file in the output directory, named
+//! after its address range the way the maps file shows it, e.g.
+//! `12c00000-32c00000.bin`. Alongside them, [`MANIFEST_FILE`] holds the
+//! report of the dump: the metadata of every region along with the size
+//! and SHA-256 of each file, so the dump can be checked and fed to other
+//! tools without the target.
+//!
+//! [`load_dump`] opens a dump again for offline analysis, with or without
+//! its manifest, as well as ELF core files such as those written by the
+//! kernel, debuggerd or [`crate::coredump`], and Java heap dumps (see
+//! [`crate::hprof`]).
+
+use crate::elf::{self, ElfHeader, ET_CORE, NT_FILE, NT_PRPSINFO, NT_PRSTATUS, PF_R, PF_W, PF_X};
+use crate::elf::{PT_LOAD, PT_NOTE};
+use crate::hprof;
+use crate::maps::{MemoryRegion, Permissions};
+use crate::reader::{DumpReader, MemoryReader};
+use crate::report::ProcessInfo;
+use crate::scan::{self, RegionVisitor, ScanControl, ScanSink, StopReason};
+use crate::sha256::Sha256;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Name of the manifest written next to the region files
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// One region written to disk
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DumpedFile {
+    /// Address of the first byte in the file
+    pub start: u64,
+    /// File name, relative to the dump directory
+    pub file: String,
+    /// Bytes written; less than the region's size if it ended early
+    pub size: u64,
+    /// SHA-256 of the file contents, as lowercase hex
+    pub sha256: String,
+}
+
+/// File a region is dumped to
+pub fn region_file_name(region: &MemoryRegion) -> String {
+    format!("{:08x}-{:08x}.bin", region.start, region.end)
+}
+
+/// Address range of a region file named by [`region_file_name`]
+pub fn parse_region_file_name(name: &str) -> Option<(u64, u64)> {
+    let (start, end) = name.strip_suffix(".bin")?.split_once('-')?;
+    let start = u64::from_str_radix(start, 16).ok()?;
+    let end = u64::from_str_radix(end, 16).ok()?;
+    (start < end).then_some((start, end))
+}
+
+/// A dump opened for offline analysis
+pub struct LoadedDump {
+    /// PID the dump was taken of; 0 if that isn't known
+    pub pid: i32,
+    pub process: Option<ProcessInfo>,
+    /// The regions there is data for, in address order
+    pub regions: Vec<MemoryRegion>,
+    pub reader: Box<dyn MemoryReader>,
+}
+
+/// The parts of a manifest needed to load a dump back
+#[derive(Deserialize)]
+struct Manifest {
+    pid: i32,
+    process: Option<ProcessInfo>,
+    regions: Vec<MemoryRegion>,
+    #[serde(default)]
+    files: Vec<DumpedFile>,
+}
+
+/// Open the dump at `path`: a directory of region files, a core file or a
+/// heap dump.
+///
+/// With a manifest, regions keep the metadata recorded when they were
+/// dumped. Without one, every file named like a region file is loaded as
+/// a read-only anonymous region, so region files from other tools can be
+/// analyzed too.
+pub fn load_dump(path: &Path) -> io::Result<LoadedDump> {
+    let dump = if !fs::metadata(path)?.is_dir() {
+        let mut magic = Vec::with_capacity(hprof::MAGIC.len());
+        File::open(path)?.take(hprof::MAGIC.len() as u64).read_to_end(&mut magic)?;
+        if magic == hprof::MAGIC {
+            hprof::load_hprof(path)?
+        } else {
+            load_core(path)?
+        }
+    } else {
+        match fs::read_to_string(path.join(MANIFEST_FILE)) {
+            Ok(json) => load_with_manifest(path, &json)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => load_region_files(path)?,
+            Err(e) => return Err(e),
+        }
+    };
+    if dump.regions.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Nothing was saved in the dump"));
+    }
+    Ok(dump)
+}
+
+fn load_with_manifest(dir: &Path, json: &str) -> io::Result<LoadedDump> {
+    let manifest: Manifest = serde_json::from_str(json).map_err(|e| {
+        io::Error::new(io::ErrorKind::InvalidData, format!("Invalid {}: {}", MANIFEST_FILE, e))
+    })?;
+
+    let mut reader = DumpReader::new();
+    let mut regions = Vec::new();
+    for file in &manifest.files {
+        // Only plain names, so a manifest can't point outside the dump
+        if Path::new(&file.file).file_name().and_then(|name| name.to_str()) != Some(&file.file) {
+            continue;
+        }
+        let Some(region) = manifest.regions.iter().find(|region| region.contains(file.start))
+        else {
+            continue;
+        };
+        reader.add_segment(dir.join(&file.file), file.start, 0, file.size)?;
+        regions.push(region.clone());
+    }
+    regions.sort_by_key(|region| region.start);
+
+    Ok(LoadedDump {
+        pid: manifest.pid,
+        process: manifest.process,
+        regions,
+        reader: Box::new(reader),
+    })
+}
+
+fn load_region_files(dir: &Path) -> io::Result<LoadedDump> {
+    let mut reader = DumpReader::new();
+    let mut regions = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let Some((start, end)) = name.to_str().and_then(parse_region_file_name) else {
+            continue;
+        };
+        let size = entry.metadata()?.len().min(end - start);
+        reader.add_segment(entry.path(), start, 0, size)?;
+        regions.push(MemoryRegion {
+            start,
+            end,
+            perms: Permissions {
+                read: true,
+                ..Permissions::default()
+            },
+            offset: 0,
+            dev: "00:00".to_string(),
+            inode: 0,
+            path: None,
+            usage: None,
+        });
+    }
+    regions.sort_by_key(|region| region.start);
+
+    Ok(LoadedDump {
+        pid: 0,
+        process: None,
+        regions,
+        reader: Box::new(reader),
+    })
+}
+
+/// Open an ELF core file: each `PT_LOAD` segment becomes a region at its
+/// original address, named after the file the `NT_FILE` note says it maps.
+///
+/// Cores often leave out the contents of file-backed read-only segments;
+/// their regions are listed all the same and fail to read.
+fn load_core(path: &Path) -> io::Result<LoadedDump> {
+    let invalid = |what: &str| io::Error::new(io::ErrorKind::InvalidData, what.to_string());
+    let mut file = File::open(path)?;
+    let mut header = Vec::with_capacity(64);
+    (&file).take(64).read_to_end(&mut header)?;
+    let header = elf::parse_header(&header)
+        .filter(|header| header.kind == ET_CORE)
+        .ok_or_else(|| invalid("Not an ELF core file"))?;
+
+    let mut table = vec![0u8; elf::program_headers_size(&header)];
+    file.seek(SeekFrom::Start(header.phoff))?;
+    file.read_exact(&mut table)?;
+    let segments = elf::parse_program_headers(&header, &table)
+        .ok_or_else(|| invalid("Invalid program headers"))?;
+
+    let mut core = CoreNotes::default();
+    for segment in segments.iter().filter(|segment| segment.kind == PT_NOTE) {
+        // Even NT_FILE of thousands of mappings is well under this
+        if segment.filesz > 64 << 20 {
+            continue;
+        }
+        let mut notes = vec![0u8; segment.filesz as usize];
+        file.seek(SeekFrom::Start(segment.offset))?;
+        file.read_exact(&mut notes)?;
+        core.add_notes(&header, &notes);
+    }
+
+    let mut reader = DumpReader::new();
+    let mut regions = Vec::new();
+    for segment in segments.iter().filter(|segment| segment.kind == PT_LOAD) {
+        let Some(end) = segment.vaddr.checked_add(segment.memsz).filter(|&end| end > segment.vaddr)
+        else {
+            continue;
+        };
+        let mapping = core.files.iter().find(|mapping| mapping.start == segment.vaddr);
+        regions.push(MemoryRegion {
+            start: segment.vaddr,
+            end,
+            perms: Permissions {
+                read: segment.flags & PF_R != 0,
+                write: segment.flags & PF_W != 0,
+                execute: segment.flags & PF_X != 0,
+                shared: false,
+            },
+            offset: mapping.map_or(0, |mapping| mapping.offset),
+            dev: "00:00".to_string(),
+            inode: 0,
+            path: mapping.map(|mapping| mapping.path.clone()),
+            usage: None,
+        });
+        let saved = segment.filesz.min(segment.memsz);
+        if saved > 0 {
+            reader.add_segment(path, segment.vaddr, segment.offset, saved)?;
+        }
+    }
+    regions.sort_by_key(|region| region.start);
+
+    Ok(LoadedDump {
+        pid: core.pid.unwrap_or(0),
+        process: core.cmdline.map(|cmdline| ProcessInfo {
+            cmdline,
+            environment: Vec::new(),
+        }),
+        regions,
+        reader: Box::new(reader),
+    })
+}
+
+/// What the notes of a core say about the process
+#[derive(Default)]
+struct CoreNotes {
+    /// From the first `NT_PRSTATUS`, which describes the main thread
+    pid: Option<i32>,
+    cmdline: Option<String>,
+    files: Vec<elf::FileMapping>,
+}
+
+impl CoreNotes {
+    fn add_notes(&mut self, header: &ElfHeader, notes: &[u8]) {
+        for note in elf::parse_notes(notes).into_iter().filter(|note| note.name == b"CORE") {
+            match note.kind {
+                NT_PRSTATUS if self.pid.is_none() => {
+                    self.pid = elf::prstatus_pid(header.class64, note.desc);
+                }
+                NT_PRPSINFO => {
+                    if let Some((pid, cmdline)) = elf::parse_prpsinfo(header.class64, note.desc) {
+                        self.pid.get_or_insert(pid);
+                        self.cmdline = Some(cmdline);
+                    }
+                }
+                NT_FILE => {
+                    if let Some(files) = elf::parse_file_note(header.class64, note.desc) {
+                        self.files = files;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Write each of `regions` to its own file in `dir`, delivering a
+/// [`DumpedFile`] for each region any bytes were read from.
+///
+/// Regions that can't be read get no file and are recorded in `sink` like
+/// any other scan. Failing to write to `dir` is an error; files written
+/// before the failure are left in place.
+pub fn dump_regions(
+    reader: &mut dyn MemoryReader,
+    regions: &[MemoryRegion],
+    dir: &Path,
+    sink: &mut dyn ScanSink<DumpedFile>,
+    control: &mut ScanControl,
+) -> io::Result<Option<StopReason>> {
+    let mut visitor = DumpVisitor {
+        dir,
+        open: None,
+        failed: None,
+    };
+    let stopped = scan::walk_regions(reader, regions, &mut visitor, sink, control);
+    match visitor.failed {
+        Some(e) => Err(e),
+        None => Ok(stopped),
+    }
+}
+
+/// Write the manifest of a dump to `dir`
+pub fn write_manifest(dir: &Path, json: &str) -> io::Result<()> {
+    fs::write(dir.join(MANIFEST_FILE), json)
+}
+
+/// The file of the region being dumped
+struct OpenFile {
+    out: BufWriter<File>,
+    name: String,
+    start: u64,
+    size: u64,
+    hash: Sha256,
+}
+
+struct DumpVisitor<'a> {
+    dir: &'a Path,
+    open: Option<OpenFile>,
+    /// The first write failure; nothing more is written after one
+    failed: Option<io::Error>,
+}
+
+impl DumpVisitor<'_> {
+    fn write_chunk(&mut self, region: &MemoryRegion, address: u64, chunk: &[u8]) -> io::Result<()> {
+        let open = match &mut self.open {
+            Some(open) => open,
+            None => {
+                let name = region_file_name(region);
+                let out = BufWriter::new(File::create(self.dir.join(&name))?);
+                self.open.insert(OpenFile {
+                    out,
+                    name,
+                    start: region.start,
+                    size: 0,
+                    hash: Sha256::new(),
+                })
+            }
+        };
+        // Pages the reader skipped read back as zeros, keeping every byte at
+        // its offset from the start of the region
+        let gap = address - (open.start + open.size);
+        if gap > 0 {
+            let zeros = vec![0u8; gap as usize];
+            open.out.write_all(&zeros)?;
+            open.hash.update(&zeros);
+            open.size += gap;
+        }
+        open.out.write_all(chunk)?;
+        open.hash.update(chunk);
+        open.size += chunk.len() as u64;
+        Ok(())
+    }
+}
+
+impl RegionVisitor for DumpVisitor<'_> {
+    type Output = DumpedFile;
+
+    fn visit_chunk(&mut self, region: &MemoryRegion, address: u64, chunk: &[u8]) {
+        if self.failed.is_some() {
+            return;
+        }
+        if let Err(e) = self.write_chunk(region, address, chunk) {
+            self.failed = Some(e);
+        }
+    }
+
+    fn end_region(
+        &mut self,
+        _region: &MemoryRegion,
+        _reader: &mut dyn MemoryReader,
+        sink: &mut dyn ScanSink<DumpedFile>,
+    ) {
+        let Some(mut open) = self.open.take() else {
+            return;
+        };
+        if let Err(e) = open.out.flush() {
+            self.failed.get_or_insert(e);
+            return;
+        }
+        sink.result(DumpedFile {
+            start: open.start,
+            file: open.name,
+            size: open.size,
+            sha256: open.hash.finish_hex(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps::{self, MemoryRegion};
+    use crate::reader::{self, ProcfsReader};
+    use crate::report::Report;
+    use crate::scan::Scan;
+
+    #[test]
+    fn test_dumps_region_with_checksum() {
+        let data = vec![0x5au8; 3 * 4096];
+        let start = data.as_ptr() as u64;
+        let pid = std::process::id() as i32;
+        let containing = maps::read_maps(pid)
+            .unwrap()
+            .into_iter()
+            .find(|region| region.contains(start))
+            .unwrap();
+        let region = MemoryRegion {
+            start,
+            end: start + data.len() as u64,
+            ..containing
+        };
+
+        let dir = std::env::temp_dir().join(format!("dump-test-{}", pid));
+        fs::create_dir_all(&dir).unwrap();
+        let mut reader = ProcfsReader::open(pid).unwrap();
+        let mut scan = Scan::default();
+        let regions = [region.clone()];
+        let stopped =
+            dump_regions(&mut reader, &regions, &dir, &mut scan, &mut ScanControl::new()).unwrap();
+
+        assert_eq!(stopped, None);
+        let file = &scan.results[0];
+        assert_eq!(file.file, region_file_name(&region));
+        assert_eq!(file.size, data.len() as u64);
+        let mut hash = Sha256::new();
+        hash.update(&data);
+        assert_eq!(file.sha256, hash.finish_hex());
+        assert_eq!(fs::read(dir.join(&file.file)).unwrap(), data);
+
+        // Without a manifest the region comes back from the file name alone
+        let mut dump = load_dump(&dir).unwrap();
+        assert_eq!((dump.regions[0].start, dump.regions[0].end), (region.start, region.end));
+        let mut read_back = vec![0u8; data.len()];
+        let read = reader::read_into(dump.reader.as_mut(), start, &mut read_back).unwrap();
+        assert_eq!(read, data.len());
+        assert_eq!(read_back, data);
+
+        // With one, the region keeps its metadata
+        let mut report = Report::new(pid);
+        report.files = Some(report.add_scan(scan));
+        write_manifest(&dir, &report.to_json()).unwrap();
+        let dump = load_dump(&dir).unwrap();
+        assert_eq!((dump.pid, &dump.regions[..]), (pid, &regions[..]));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,396 @@
+//! Just enough ELF to find segments, notes and symbols
+//!
+//! Both classes are understood, in little-endian byte order only, which
+//! covers every Android ABI. Parsing never trusts the input: anything
+//! truncated or out of range is `None` rather than a panic.
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+pub const ET_CORE: u16 = 4;
+pub const PT_LOAD: u32 = 1;
+pub const PT_DYNAMIC: u32 = 2;
+pub const PT_NOTE: u32 = 4;
+pub const PT_GNU_EH_FRAME: u32 = 0x6474_e550;
+pub const SHT_SYMTAB: u32 = 2;
+pub const SHT_DYNSYM: u32 = 11;
+pub const STT_OBJECT: u8 = 1;
+pub const STT_FUNC: u8 = 2;
+pub const STT_GNU_IFUNC: u8 = 10;
+pub const PF_X: u32 = 1;
+pub const PF_W: u32 = 2;
+pub const PF_R: u32 = 4;
+pub const NT_PRSTATUS: u32 = 1;
+pub const NT_PRPSINFO: u32 = 3;
+pub const NT_AUXV: u32 = 6;
+pub const NT_FILE: u32 = 0x4649_4c45;
+pub const NT_GNU_BUILD_ID: u32 = 3;
+
+/// The fields of the file header needed to find the program headers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ElfHeader {
+    /// ELFCLASS64 rather than ELFCLASS32
+    pub class64: bool,
+    /// `e_type`, e.g. [`ET_CORE`]
+    pub kind: u16,
+    pub machine: u16,
+    pub phoff: u64,
+    pub phentsize: u16,
+    pub phnum: u16,
+    pub shoff: u64,
+    pub shentsize: u16,
+    pub shnum: u16,
+}
+
+/// One program header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProgramHeader {
+    /// `p_type`, e.g. [`PT_LOAD`]
+    pub kind: u32,
+    pub flags: u32,
+    pub offset: u64,
+    pub vaddr: u64,
+    pub filesz: u64,
+    pub memsz: u64,
+}
+
+/// One section header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SectionHeader {
+    /// `sh_type`, e.g. [`SHT_SYMTAB`]
+    pub kind: u32,
+    /// For a symbol table, the index of the section holding its names
+    pub link: u32,
+    pub offset: u64,
+    pub size: u64,
+}
+
+/// One entry of a symbol table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    /// `STT_*` from `st_info`, e.g. [`STT_FUNC`]
+    pub kind: u8,
+    /// Whether the symbol is defined in this file rather than imported
+    pub defined: bool,
+    /// Link-time address
+    pub value: u64,
+    pub size: u64,
+}
+
+/// One entry of a note segment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Note<'a> {
+    /// Owner name without its terminating NUL, e.g. `CORE` or `GNU`
+    pub name: &'a [u8],
+    pub kind: u32,
+    pub desc: &'a [u8],
+}
+
+/// One entry of an `NT_FILE` note: a mapping of `path` at `start..end`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileMapping {
+    pub start: u64,
+    pub end: u64,
+    /// Offset into the file of `start`, in bytes
+    pub offset: u64,
+    pub path: String,
+}
+
+/// Parse the file header at the start of `bytes`
+pub fn parse_header(bytes: &[u8]) -> Option<ElfHeader> {
+    if bytes.get(..4)? != b"\x7fELF" || *bytes.get(5)? != 1 {
+        return None;
+    }
+    let class64 = match bytes[4] {
+        1 => false,
+        2 => true,
+        _ => return None,
+    };
+    let (phoff, shoff, rest) = if class64 {
+        (u64_at(bytes, 32)?, u64_at(bytes, 40)?, 52)
+    } else {
+        (u64::from(u32_at(bytes, 28)?), u64::from(u32_at(bytes, 32)?), 40)
+    };
+    Some(ElfHeader {
+        class64,
+        kind: u16_at(bytes, 16)?,
+        machine: u16_at(bytes, 18)?,
+        phoff,
+        phentsize: u16_at(bytes, rest + 2)?,
+        phnum: u16_at(bytes, rest + 4)?,
+        shoff,
+        shentsize: u16_at(bytes, rest + 6)?,
+        shnum: u16_at(bytes, rest + 8)?,
+    })
+}
+
+/// Size of the program header table described by `header`
+pub fn program_headers_size(header: &ElfHeader) -> usize {
+    usize::from(header.phentsize) * usize::from(header.phnum)
+}
+
+/// Parse the program header table held in `table`, which starts at the
+/// header's `phoff`
+pub fn parse_program_headers(header: &ElfHeader, table: &[u8]) -> Option<Vec<ProgramHeader>> {
+    let entry_size = usize::from(header.phentsize);
+    if entry_size < if header.class64 { 56 } else { 32 } {
+        return None;
+    }
+    let mut headers = Vec::with_capacity(usize::from(header.phnum));
+    for i in 0..usize::from(header.phnum) {
+        let entry = table.get(i * entry_size..(i + 1) * entry_size)?;
+        headers.push(if header.class64 {
+            ProgramHeader {
+                kind: u32_at(entry, 0)?,
+                flags: u32_at(entry, 4)?,
+                offset: u64_at(entry, 8)?,
+                vaddr: u64_at(entry, 16)?,
+                filesz: u64_at(entry, 32)?,
+                memsz: u64_at(entry, 40)?,
+            }
+        } else {
+            // ELF32 moves the flags after the sizes
+            ProgramHeader {
+                kind: u32_at(entry, 0)?,
+                offset: u32_at(entry, 4)?.into(),
+                vaddr: u32_at(entry, 8)?.into(),
+                filesz: u32_at(entry, 16)?.into(),
+                memsz: u32_at(entry, 20)?.into(),
+                flags: u32_at(entry, 24)?,
+            }
+        });
+    }
+    Some(headers)
+}
+
+/// Size of the section header table described by `header`
+pub fn section_headers_size(header: &ElfHeader) -> usize {
+    usize::from(header.shentsize) * usize::from(header.shnum)
+}
+
+/// Parse the section header table held in `table`, which starts at the
+/// header's `shoff`
+pub fn parse_section_headers(header: &ElfHeader, table: &[u8]) -> Option<Vec<SectionHeader>> {
+    let entry_size = usize::from(header.shentsize);
+    if entry_size < if header.class64 { 64 } else { 40 } {
+        return None;
+    }
+    let mut headers = Vec::with_capacity(usize::from(header.shnum));
+    for i in 0..usize::from(header.shnum) {
+        let entry = table.get(i * entry_size..(i + 1) * entry_size)?;
+        headers.push(if header.class64 {
+            SectionHeader {
+                kind: u32_at(entry, 4)?,
+                offset: u64_at(entry, 24)?,
+                size: u64_at(entry, 32)?,
+                link: u32_at(entry, 40)?,
+            }
+        } else {
+            SectionHeader {
+                kind: u32_at(entry, 4)?,
+                offset: u32_at(entry, 16)?.into(),
+                size: u32_at(entry, 20)?.into(),
+                link: u32_at(entry, 24)?,
+            }
+        });
+    }
+    Some(headers)
+}
+
+/// Parse a symbol table, naming each symbol from `strings`, the string
+/// table the symbol table's section links to
+pub fn parse_symbols(class64: bool, table: &[u8], strings: &[u8]) -> Vec<Symbol> {
+    let entry_size = if class64 { 24 } else { 16 };
+    let name_at = |at: u32| {
+        let name = strings.get(at as usize..).unwrap_or_default();
+        let end = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+        String::from_utf8_lossy(&name[..end]).into_owned()
+    };
+    let symbol = |entry: &[u8]| -> Option<Symbol> {
+        let (info, shndx, value, size) = if class64 {
+            (entry[4], u16_at(entry, 6)?, u64_at(entry, 8)?, u64_at(entry, 16)?)
+        } else {
+            let value = u32_at(entry, 4)?.into();
+            (entry[12], u16_at(entry, 14)?, value, u32_at(entry, 8)?.into())
+        };
+        Some(Symbol {
+            name: name_at(u32_at(entry, 0)?),
+            kind: info & 0xf,
+            defined: shndx != 0,
+            value,
+            size,
+        })
+    };
+    table.chunks_exact(entry_size).filter_map(symbol).collect()
+}
+
+/// Parse the notes of a note segment, stopping at the first malformed one
+pub fn parse_notes(mut bytes: &[u8]) -> Vec<Note<'_>> {
+    let mut notes = Vec::new();
+    while let (Some(namesz), Some(descsz), Some(kind)) =
+        (u32_at(bytes, 0), u32_at(bytes, 4), u32_at(bytes, 8))
+    {
+        let name_end = 12 + namesz as usize;
+        let desc_start = align4(name_end);
+        let desc_end = desc_start + descsz as usize;
+        let (Some(name), Some(desc)) = (bytes.get(12..name_end), bytes.get(desc_start..desc_end))
+        else {
+            break;
+        };
+        notes.push(Note {
+            name: name.strip_suffix(b"\0").unwrap_or(name),
+            kind,
+            desc,
+        });
+        bytes = bytes.get(align4(desc_end)..).unwrap_or_default();
+    }
+    notes
+}
+
+/// The GNU build ID of the ELF file at `path`, from its note segments;
+/// `None` if it has none or isn't ELF
+pub fn read_build_id(path: &Path) -> io::Result<Option<Vec<u8>>> {
+    let mut file = File::open(path)?;
+    let mut header = [0u8; 64];
+    let read = file.read(&mut header)?;
+    let Some(header) = parse_header(&header[..read]) else {
+        return Ok(None);
+    };
+
+    let mut table = vec![0u8; program_headers_size(&header)];
+    file.seek(SeekFrom::Start(header.phoff))?;
+    file.read_exact(&mut table)?;
+    let Some(segments) = parse_program_headers(&header, &table) else {
+        return Ok(None);
+    };
+
+    for segment in segments.iter().filter(|segment| segment.kind == PT_NOTE) {
+        // Note segments are small; a huge one is corrupt
+        if segment.filesz > 1 << 20 {
+            continue;
+        }
+        let mut notes = vec![0u8; segment.filesz as usize];
+        file.seek(SeekFrom::Start(segment.offset))?;
+        file.read_exact(&mut notes)?;
+        if let Some(build_id) = find_build_id(&notes) {
+            return Ok(Some(build_id.to_vec()));
+        }
+    }
+    Ok(None)
+}
+
+/// The GNU build ID among the notes of a note segment
+pub fn find_build_id(notes: &[u8]) -> Option<&[u8]> {
+    parse_notes(notes)
+        .into_iter()
+        .find(|note| note.name == b"GNU" && note.kind == NT_GNU_BUILD_ID)
+        .map(|note| note.desc)
+}
+
+/// Parse the table of an `NT_FILE` note
+pub fn parse_file_note(class64: bool, desc: &[u8]) -> Option<Vec<FileMapping>> {
+    let word = if class64 { 8 } else { 4 };
+    let count = usize::try_from(word_at(class64, desc, 0)?).ok()?;
+    let page_size = word_at(class64, desc, word)?;
+    let names_start = count.checked_mul(3 * word)?.checked_add(2 * word)?;
+    let mut names = desc.get(names_start..)?.split(|&byte| byte == 0);
+
+    let mut mappings = Vec::with_capacity(count.min(desc.len() / word));
+    for i in 0..count {
+        let entry = 2 * word + i * 3 * word;
+        mappings.push(FileMapping {
+            start: word_at(class64, desc, entry)?,
+            end: word_at(class64, desc, entry + word)?,
+            offset: word_at(class64, desc, entry + 2 * word)?.checked_mul(page_size)?,
+            path: String::from_utf8_lossy(names.next()?).into_owned(),
+        });
+    }
+    Some(mappings)
+}
+
+/// `pr_pid` of an `NT_PRSTATUS` note: the ID of the thread it describes
+pub fn prstatus_pid(class64: bool, desc: &[u8]) -> Option<i32> {
+    // elf_siginfo and pr_cursig, then pr_sigpend and pr_sighold
+    let at = if class64 { 32 } else { 24 };
+    Some(u32_at(desc, at)? as i32)
+}
+
+/// The PID and command line (`pr_psargs`, truncated to 80 bytes by the
+/// kernel) of an `NT_PRPSINFO` note
+pub fn parse_prpsinfo(class64: bool, desc: &[u8]) -> Option<(i32, String)> {
+    // 32-bit ABIs have 16-bit pr_uid and pr_gid
+    let (pid, psargs) = if class64 { (24, 56) } else { (12, 44) };
+    let args = desc.get(psargs..psargs + 80)?;
+    let args = args.split(|&byte| byte == 0).next().unwrap_or_default();
+    Some((u32_at(desc, pid)? as i32, String::from_utf8_lossy(args).trim_end().to_string()))
+}
+
+/// Parse the `(d_tag, d_val)` entries of a dynamic section, up to its
+/// `DT_NULL`
+pub fn parse_dynamic(class64: bool, bytes: &[u8]) -> Vec<(u64, u64)> {
+    let word = if class64 { 8 } else { 4 };
+    let mut entries = Vec::new();
+    let mut at = 0;
+    while let (Some(tag), Some(value)) =
+        (word_at(class64, bytes, at), word_at(class64, bytes, at + word))
+    {
+        if tag == 0 {
+            break;
+        }
+        entries.push((tag, value));
+        at += 2 * word;
+    }
+    entries
+}
+
+pub fn word_at(class64: bool, bytes: &[u8], at: usize) -> Option<u64> {
+    if class64 {
+        u64_at(bytes, at)
+    } else {
+        u32_at(bytes, at).map(u64::from)
+    }
+}
+
+fn align4(value: usize) -> usize {
+    value.div_ceil(4) * 4
+}
+
+fn u16_at(bytes: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?))
+}
+
+pub fn u32_at(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn u64_at(bytes: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(bytes.get(at..at + 8)?.try_into().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reads_headers_and_notes_of_own_executable() {
+        let exe = std::fs::read("/proc/self/exe").unwrap();
+        let header = parse_header(&exe).unwrap();
+        assert_eq!(header.class64, cfg!(target_pointer_width = "64"));
+
+        let table = &exe[header.phoff as usize..];
+        let segments = parse_program_headers(&header, table).unwrap();
+        assert!(segments.iter().any(|segment| segment.kind == PT_LOAD));
+        assert!(parse_header(b"\x7fELF").is_none());
+
+        let mut note = Vec::new();
+        for word in [4u32, 3, NT_GNU_BUILD_ID] {
+            note.extend_from_slice(&word.to_le_bytes());
+        }
+        note.extend_from_slice(b"GNU\0\x01\x02\x03\0");
+        let notes = parse_notes(&note);
+        assert_eq!(notes.len(), 1);
+        assert_eq!((notes[0].name, notes[0].desc), (&b"GNU"[..], &[1u8, 2, 3][..]));
+    }
+}
@@ -0,0 +1,290 @@
+//! High-entropy byte runs, the likely home of keys and random tokens
+//!
+//! Encryption keys, session tokens and nonces are random bytes, and rarely
+//! look like text, so string extraction misses them. Random data has close
+//! to the highest Shannon entropy a window of its size can have, while code,
+//! text, pointers and zero fill fall well short. A window slides over each
+//! region a byte at a time, its entropy kept up to date from the byte
+//! counts, and every stretch where consecutive windows stay at or above a
+//! threshold is reported as one run, with its bytes. A window only partly
+//! over random bytes can still pass, so runs can start and end some way
+//! into the data around them.
+//!
+//! Compressed and encrypted data is just as random, so large runs are more
+//! likely resources than secrets; runs of key size are the interesting ones.
+
+use crate::maps::MemoryRegion;
+use crate::reader::MemoryReader;
+use crate::scan::{self, Location, RegionVisitor, ScanSink};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Bytes of each run kept in the report
+pub const MAX_RUN_BYTES: usize = 256;
+
+/// Widest window allowed
+pub const MAX_WINDOW: usize = 4096;
+
+/// The `entropy` object of the scan options
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct EntropyOptions {
+    /// Bytes the entropy is measured over
+    pub window: usize,
+    /// Entropy in bits per byte at or above which a window counts as
+    /// random. It can't exceed log2 of `window`, 5 for 32 bytes.
+    pub threshold: f64,
+    /// Shortest run reported, in bytes; `window` if unset
+    pub min_length: Option<usize>,
+    /// Longest run reported, in bytes; longer runs, more likely compressed
+    /// data than secrets, are left out
+    pub max_length: Option<usize>,
+}
+
+impl Default for EntropyOptions {
+    fn default() -> Self {
+        EntropyOptions {
+            window: 32,
+            threshold: 4.5,
+            min_length: None,
+            max_length: None,
+        }
+    }
+}
+
+/// A run of bytes whose every window was above the threshold
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntropyRun {
+    #[serde(flatten)]
+    pub location: Location,
+    pub length: usize,
+    /// Highest entropy of a window in the run, in bits per byte
+    pub entropy: f64,
+    /// The run's first [`MAX_RUN_BYTES`] bytes, as hex
+    #[serde(serialize_with = "scan::serialize_hex")]
+    pub bytes: Vec<u8>,
+}
+
+/// Shannon entropy of `data` in bits per byte
+pub fn shannon_entropy(data: &[u8]) -> f64 {
+    let mut counts = [0u32; 256];
+    for &byte in data {
+        counts[byte as usize] += 1;
+    }
+    let len = data.len() as f64;
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// The run being grown
+struct OpenRun {
+    start: u64,
+    length: usize,
+    entropy: f64,
+    bytes: Vec<u8>,
+}
+
+/// Finds high-entropy runs in each region it is fed
+pub struct EntropyVisitor {
+    options: EntropyOptions,
+    /// `c * log2(c)` for every count a window can hold
+    terms: Vec<f64>,
+    counts: [u32; 256],
+    /// Sum of `terms` over `counts`
+    sum: f64,
+    /// The window's bytes, oldest at `head` once it is full
+    ring: Vec<u8>,
+    head: usize,
+    filled: usize,
+    /// Address just past the last byte seen
+    next: u64,
+    run: Option<OpenRun>,
+    found: Vec<EntropyRun>,
+}
+
+impl EntropyVisitor {
+    pub fn new(options: &EntropyOptions) -> Self {
+        let window = options.window.clamp(2, MAX_WINDOW);
+        EntropyVisitor {
+            options: EntropyOptions {
+                window,
+                ..options.clone()
+            },
+            terms: (0..=window).map(|c| c as f64 * (c.max(1) as f64).log2()).collect(),
+            counts: [0; 256],
+            sum: 0.0,
+            ring: vec![0; window],
+            head: 0,
+            filled: 0,
+            next: 0,
+            run: None,
+            found: Vec::new(),
+        }
+    }
+
+    /// Forget the window, ending any open run, as at a gap in the memory
+    fn reset(&mut self) {
+        self.close_run();
+        self.counts = [0; 256];
+        self.sum = 0.0;
+        self.head = 0;
+        self.filled = 0;
+    }
+
+    fn count(&mut self, byte: u8, up: bool) {
+        let count = &mut self.counts[byte as usize];
+        self.sum -= self.terms[*count as usize];
+        if up {
+            *count += 1;
+        } else {
+            *count -= 1;
+        }
+        self.sum += self.terms[*count as usize];
+    }
+
+    /// Entropy of the full window
+    fn entropy(&self) -> f64 {
+        let window = self.options.window as f64;
+        window.log2() - self.sum / window
+    }
+
+    fn push(&mut self, address: u64, byte: u8) {
+        let window = self.options.window;
+        if self.filled == window {
+            let oldest = self.ring[self.head];
+            self.count(oldest, false);
+        } else {
+            self.filled += 1;
+        }
+        self.ring[self.head] = byte;
+        self.head = (self.head + 1) % window;
+        self.count(byte, true);
+        if self.filled < window {
+            return;
+        }
+
+        let entropy = self.entropy();
+        if entropy < self.options.threshold {
+            self.close_run();
+            return;
+        }
+        match &mut self.run {
+            Some(run) => {
+                run.length += 1;
+                run.entropy = run.entropy.max(entropy);
+                if run.bytes.len() < MAX_RUN_BYTES {
+                    run.bytes.push(byte);
+                }
+            }
+            None => {
+                // The ring holds the window oldest first from `head`
+                let (newer, older) = self.ring.split_at(self.head);
+                let mut bytes = older.to_vec();
+                bytes.extend_from_slice(newer);
+                bytes.truncate(MAX_RUN_BYTES);
+                self.run = Some(OpenRun {
+                    start: address + 1 - window as u64,
+                    length: window,
+                    entropy,
+                    bytes,
+                });
+            }
+        }
+    }
+
+    fn close_run(&mut self) {
+        let Some(run) = self.run.take() else {
+            return;
+        };
+        let min = self.options.min_length.unwrap_or(0);
+        if run.length < min || self.options.max_length.is_some_and(|max| run.length > max) {
+            return;
+        }
+        self.found.push(EntropyRun {
+            // Set in end_region, once the region is known
+            location: Location::new(&Arc::from(""), 0, run.start),
+            length: run.length,
+            entropy: (run.entropy * 1000.0).round() / 1000.0,
+            bytes: run.bytes,
+        });
+    }
+}
+
+impl RegionVisitor for EntropyVisitor {
+    type Output = EntropyRun;
+
+    fn visit_chunk(&mut self, region: &MemoryRegion, address: u64, chunk: &[u8]) {
+        if address == region.start || address != self.next {
+            self.reset();
+        }
+        for (i, &byte) in chunk.iter().enumerate() {
+            self.push(address + i as u64, byte);
+        }
+        self.next = address + chunk.len() as u64;
+    }
+
+    fn end_region(
+        &mut self,
+        region: &MemoryRegion,
+        _reader: &mut dyn MemoryReader,
+        sink: &mut dyn ScanSink<EntropyRun>,
+    ) {
+        self.reset();
+        let name: Arc<str> = region.name().into();
+        for mut run in self.found.drain(..) {
+            run.location = Location::new(&name, region.start, run.location.address);
+            sink.result(run);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps::parse_maps_line;
+    use crate::reader::AutoReader;
+    use crate::scan::Scan;
+
+    #[test]
+    fn test_finds_the_random_run_between_text_and_zeros() {
+        // A fixed pseudo-random key, so the test doesn't depend on luck
+        let mut state = 0x2545f491u32;
+        let key: Vec<u8> = (0..48)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect();
+        let mut data = b"Settings saved. Settings saved. Settings saved. ".to_vec();
+        data.extend_from_slice(&key);
+        data.extend_from_slice(&[0; 64]);
+        assert!(shannon_entropy(&key) > 5.0);
+
+        let region = parse_maps_line("10000-10100 rw-p 00000000 00:00 0 [heap]").unwrap();
+        let mut visitor = EntropyVisitor::new(&EntropyOptions::default());
+        // Fed in two chunks, the run spanning the boundary
+        visitor.visit_chunk(&region, 0x10000, &data[..64]);
+        visitor.visit_chunk(&region, 0x10040, &data[64..]);
+        let mut scan = Scan::default();
+        visitor.end_region(&region, &mut AutoReader::new(0), &mut scan);
+
+        assert_eq!(scan.results.len(), 1);
+        let run = &scan.results[0];
+        let start = run.location.offset as usize;
+        // Windows straddling the key and the text are random enough too
+        assert!((16..=48).contains(&start), "run starts at {}", start);
+        let end = start + run.length;
+        assert!((96..=128).contains(&end), "run ends at {}", end);
+        assert_eq!(run.bytes, data[start..start + run.length]);
+        assert_eq!(&*run.location.region, "[heap]");
+    }
+}
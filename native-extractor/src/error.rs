@@ -0,0 +1,156 @@
+//! Errors reported to Java
+//!
+//! Failed calls throw a `NativeExtractionException` carrying the error's
+//! [`ExtractError::code`], or one of its subclasses for failures callers
+//! commonly handle on their own. The codes are stable and mirrored by
+//! `NativeErrorCode` in the app, so callers can branch on the kind of
+//! failure without parsing messages.
+
+use crate::scan::StopReason;
+use std::fmt;
+use std::io;
+
+/// JNI name of the base class of every exception thrown for an [`ExtractError`]
+pub const EXCEPTION_CLASS: &str = "com/textextractor/NativeExtractionException";
+
+/// Why a call failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExtractError {
+    /// The target process doesn't exist, or exited during the call
+    NoSuchProcess(String),
+    /// Root, SELinux or ptrace restrictions denied access
+    PermissionDenied(String),
+    /// An argument passed from Java was invalid
+    InvalidArgument(String),
+    /// Any other I/O or JNI failure
+    Io(String),
+    /// The call was cancelled before it could produce a result
+    Cancelled(String),
+    /// The call ran out of time before it could produce a result
+    Timeout(String),
+    /// The kernel lacks a facility the call needs (ENOSYS)
+    UnsupportedKernel(String),
+}
+
+impl ExtractError {
+    /// Classify a failure to access a process's /proc files or memory.
+    ///
+    /// A missing /proc entry means the process is gone, so ENOENT counts as
+    /// [`ExtractError::NoSuchProcess`] along with ESRCH.
+    pub fn from_io(context: impl fmt::Display, error: &io::Error) -> Self {
+        match error.raw_os_error() {
+            Some(libc::ESRCH) | Some(libc::ENOENT) => {
+                ExtractError::NoSuchProcess(format!("{}: {}", context, error))
+            }
+            Some(libc::EPERM) | Some(libc::EACCES) => {
+                ExtractError::PermissionDenied(format!("{}: {} (requires root)", context, error))
+            }
+            Some(libc::ENOSYS) => {
+                ExtractError::UnsupportedKernel(format!("{}: {}", context, error))
+            }
+            _ => ExtractError::Io(format!("{}: {}", context, error)),
+        }
+    }
+
+    /// Classify a failure to write to `path`, an output path passed from
+    /// Java. Unlike [`from_io`](Self::from_io), a missing directory is a bad
+    /// argument rather than a process that's gone, and a denied one says
+    /// nothing of root.
+    pub fn from_output_io(path: impl fmt::Display, error: &io::Error) -> Self {
+        let message = format!("{}: {}", path, error);
+        match error.raw_os_error() {
+            Some(libc::ENOENT | libc::ENOTDIR | libc::EISDIR | libc::ENAMETOOLONG) => {
+                ExtractError::InvalidArgument(message)
+            }
+            _ => ExtractError::Io(message),
+        }
+    }
+
+    /// Stable code identifying the kind of error, mirrored by
+    /// `NativeErrorCode` on the Java side. Codes are never reused.
+    pub fn code(&self) -> i32 {
+        match self {
+            ExtractError::NoSuchProcess(_) => 1,
+            ExtractError::PermissionDenied(_) => 2,
+            ExtractError::InvalidArgument(_) => 3,
+            ExtractError::Io(_) => 4,
+            ExtractError::Cancelled(_) => 5,
+            ExtractError::Timeout(_) => 6,
+            ExtractError::UnsupportedKernel(_) => 7,
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            ExtractError::NoSuchProcess(message)
+            | ExtractError::PermissionDenied(message)
+            | ExtractError::InvalidArgument(message)
+            | ExtractError::Io(message)
+            | ExtractError::Cancelled(message)
+            | ExtractError::Timeout(message)
+            | ExtractError::UnsupportedKernel(message) => message,
+        }
+    }
+
+    /// JNI name of the dedicated subclass of [`EXCEPTION_CLASS`] thrown for
+    /// this error, if it has one. Subclasses take just the message; the
+    /// base class also takes the code.
+    pub fn exception_subclass(&self) -> Option<&'static str> {
+        match self {
+            ExtractError::NoSuchProcess(_) => Some("com/textextractor/ProcessNotFoundException"),
+            ExtractError::PermissionDenied(_) => {
+                Some("com/textextractor/PermissionDeniedException")
+            }
+            _ => None,
+        }
+    }
+}
+
+impl From<StopReason> for ExtractError {
+    fn from(reason: StopReason) -> Self {
+        match reason {
+            StopReason::Cancelled => ExtractError::Cancelled("Cancelled".to_string()),
+            StopReason::Timeout => ExtractError::Timeout("Timed out".to_string()),
+        }
+    }
+}
+
+impl fmt::Display for ExtractError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for ExtractError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_io_errors_are_classified_by_errno() {
+        let errno = io::Error::from_raw_os_error;
+
+        let gone = ExtractError::from_io("Failed to read maps", &errno(libc::ENOENT));
+        assert!(matches!(gone, ExtractError::NoSuchProcess(_)));
+        assert_eq!(gone.code(), 1);
+        assert_eq!(gone.exception_subclass(), Some("com/textextractor/ProcessNotFoundException"));
+
+        let denied = ExtractError::from_io("Failed to attach", &errno(libc::EPERM));
+        assert!(matches!(denied, ExtractError::PermissionDenied(_)));
+        assert!(denied.message().starts_with("Failed to attach: "));
+
+        let missing = ExtractError::from_io("Failed to open process memory", &errno(libc::ENOSYS));
+        assert_eq!(missing.code(), 7);
+        assert_eq!(missing.exception_subclass(), None);
+
+        let output = ExtractError::from_output_io("/sdcard/missing", &errno(libc::ENOENT));
+        assert!(matches!(output, ExtractError::InvalidArgument(_)));
+        let output = ExtractError::from_output_io("/system/out", &errno(libc::EACCES));
+        assert_eq!(output, ExtractError::Io(format!("/system/out: {}", errno(libc::EACCES))));
+
+        let other = ExtractError::from_io("Failed to read", &errno(libc::EIO));
+        assert!(matches!(other, ExtractError::Io(_)));
+        assert_eq!(ExtractError::from(StopReason::Timeout).code(), 6);
+    }
+}
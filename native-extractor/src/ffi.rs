@@ -0,0 +1,86 @@
+//! Keeping Rust panics out of the JVM
+//!
+//! Unwinding across an `extern "C"` function into Java is undefined
+//! behavior, so every JNI entry point runs its body through [`guard`], which
+//! catches a panic and turns it into a thrown `RuntimeException`.
+
+use jni::sys::{jboolean, jint, jlong, jobject, JNI_FALSE};
+use jni::JNIEnv;
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+
+/// Value a JNI function returns when it has thrown instead of producing a
+/// result
+pub trait ErrorValue {
+    const ERROR: Self;
+}
+
+/// `null`, for every object and array return type
+impl ErrorValue for jobject {
+    const ERROR: Self = std::ptr::null_mut();
+}
+
+impl ErrorValue for jboolean {
+    const ERROR: Self = JNI_FALSE;
+}
+
+/// `-1`, which no call returning a count or size uses for success
+impl ErrorValue for jint {
+    const ERROR: Self = -1;
+}
+
+/// `0`, which is never a valid session handle
+impl ErrorValue for jlong {
+    const ERROR: Self = 0;
+}
+
+/// Run the body of a JNI function, throwing a `RuntimeException` with the
+/// panic message and returning [`ErrorValue::ERROR`] if it panics.
+///
+/// An exception already pending when the panic happened is left in place.
+pub fn guard<'local, T, F>(env: &mut JNIEnv<'local>, body: F) -> T
+where
+    T: ErrorValue,
+    F: FnOnce(&mut JNIEnv<'local>) -> T,
+{
+    // The env and anything the body borrowed are dropped unused after a
+    // panic, so there's no broken state left to observe
+    match panic::catch_unwind(AssertUnwindSafe(|| body(env))) {
+        Ok(value) => value,
+        Err(payload) => {
+            if !env.exception_check().unwrap_or(false) {
+                let message = format!("Native panic: {}", panic_message(payload.as_ref()));
+                let _ = env.throw_new("java/lang/RuntimeException", message);
+            }
+            T::ERROR
+        }
+    }
+}
+
+/// The message a panic was raised with, if it was a string
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown panic"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_panic_message_from_payload() {
+        let payload = panic::catch_unwind(|| panic!("bad region {}", 7)).unwrap_err();
+        assert_eq!(panic_message(payload.as_ref()), "bad region 7");
+
+        let payload = panic::catch_unwind(|| panic!("static message")).unwrap_err();
+        assert_eq!(panic_message(payload.as_ref()), "static message");
+
+        let payload = panic::catch_unwind(|| panic::panic_any(42)).unwrap_err();
+        assert_eq!(panic_message(payload.as_ref()), "unknown panic");
+    }
+}
@@ -0,0 +1,261 @@
+//! Selection of which memory regions a scan visits
+
+use crate::maps::MemoryRegion;
+use serde::{Deserialize, Serialize};
+
+/// Match `text` against a wildcard pattern where `*` matches any run of
+/// characters and `?` matches exactly one.
+///
+/// Everything else is literal. In particular `[` and `]` are not character
+/// classes, so kernel pseudo-paths like `[anon:dalvik-*]` can be written
+/// as-is.
+pub fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` and the text index it is currently absorbing up to
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, absorbed)) => {
+                    p = star + 1;
+                    t = absorbed + 1;
+                    backtrack = Some((star, absorbed + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// The part of ART's JIT code cache a region holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum JitCache {
+    /// Compiled code
+    Code,
+    /// Profiling info, stack maps and the GC roots of compiled code
+    Data,
+}
+
+/// Which part of the JIT code cache `region` is, if it is one.
+///
+/// Since Android 10 the cache lives in one memfd, `jit-cache` or
+/// `jit-zygote-cache`, mapped twice so that no mapping is both writable and
+/// executable; the executable mapping is the code, while the writable ones,
+/// including ART's writable view of that code, count as data. Older releases
+/// name separate `jit-code-cache` and `data-code-cache` mappings, as anonymous
+/// memory or ashmem.
+pub fn jit_cache(region: &MemoryRegion) -> Option<JitCache> {
+    let path = region.path.as_deref()?;
+    if path.contains("data-code-cache") || path.contains("jit-data-cache") {
+        Some(JitCache::Data)
+    } else if path.contains("jit-code-cache") {
+        Some(JitCache::Code)
+    } else if path.starts_with("/memfd:jit-cache") || path.starts_with("/memfd:jit-zygote-cache") {
+        Some(if region.perms.execute { JitCache::Code } else { JitCache::Data })
+    } else {
+        None
+    }
+}
+
+/// Coarse region selection applied before the finer-grained patterns
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RegionPreset {
+    /// Every readable region
+    #[default]
+    All,
+    /// The native heap and the ART/Dalvik managed heap spaces
+    Heap,
+    /// The main thread stack and per-thread stacks
+    Stack,
+    /// Mappings with no backing file, named or not
+    Anonymous,
+    /// ART's JIT code cache, code and data (see [`jit_cache`])
+    JitCache,
+}
+
+impl RegionPreset {
+    /// Map the integer constant used on the Java side to a preset
+    pub fn from_code(code: i32) -> Option<Self> {
+        match code {
+            0 => Some(RegionPreset::All),
+            1 => Some(RegionPreset::Heap),
+            2 => Some(RegionPreset::Stack),
+            3 => Some(RegionPreset::Anonymous),
+            4 => Some(RegionPreset::JitCache),
+            _ => None,
+        }
+    }
+
+    pub fn matches(self, region: &MemoryRegion) -> bool {
+        let path = region.path.as_deref();
+        match self {
+            RegionPreset::All => true,
+            RegionPreset::Heap => path.is_some_and(|path| {
+                // dalvik- covers the ART spaces; scudo and jemalloc name
+                // their arenas on newer bionic versions
+                path == "[heap]"
+                    || path == "[anon:libc_malloc]"
+                    || path.starts_with("[anon:dalvik-")
+                    || path.starts_with("[anon:scudo:")
+                    || path.starts_with("[anon:je")
+            }),
+            RegionPreset::Stack => path.is_some_and(|path| {
+                // [stack:tid] is the per-thread form used by older kernels,
+                // stack_and_tls is how bionic names pthread stacks
+                path == "[stack]"
+                    || path.starts_with("[stack:")
+                    || path.starts_with("[anon:stack_and_tls:")
+                    || path == "[anon:thread signal stack]"
+            }),
+            RegionPreset::Anonymous => match path {
+                None => true,
+                Some(path) => path.starts_with("[anon:") || path == "[heap]",
+            },
+            RegionPreset::JitCache => jit_cache(region).is_some(),
+        }
+    }
+}
+
+/// Include/exclude rules applied to regions before they are read.
+///
+/// Permission patterns are wildcards matched against the four-character
+/// maps column (`rw-?` for any readable and writable, non-executable region;
+/// `---p` for inaccessible guard mappings). Path patterns are matched against
+/// [`MemoryRegion::name`], so unnamed anonymous mappings match `[anonymous]`.
+///
+/// An empty include list includes everything; excludes always win. JIT code
+/// cache regions, whose strings are mostly noise, can be left out with
+/// `exclude_jit_cache` without knowing how the running release names them.
+///
+/// `min_resident` leaves out regions with less than that many bytes in RAM,
+/// which are mostly untouched reservations; it needs the regions' usage from
+/// smaps, and regions without it are kept.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RegionFilter {
+    pub preset: RegionPreset,
+    pub include_perms: Vec<String>,
+    pub exclude_perms: Vec<String>,
+    pub include_paths: Vec<String>,
+    pub exclude_paths: Vec<String>,
+    pub exclude_jit_cache: bool,
+    pub min_resident: u64,
+}
+
+impl RegionFilter {
+    pub fn matches(&self, region: &MemoryRegion) -> bool {
+        let perms = region.perms.to_string();
+        let name = region.name();
+
+        let any = |patterns: &[String], text: &str| {
+            patterns.iter().any(|pattern| wildcard_match(pattern, text))
+        };
+
+        self.preset.matches(region)
+            && (self.include_perms.is_empty() || any(&self.include_perms, &perms))
+            && !any(&self.exclude_perms, &perms)
+            && (self.include_paths.is_empty() || any(&self.include_paths, name))
+            && !any(&self.exclude_paths, name)
+            && !(self.exclude_jit_cache && jit_cache(region).is_some())
+            && region.usage.is_none_or(|usage| usage.rss >= self.min_resident)
+    }
+
+    /// Keep only the regions that pass the filter
+    pub fn apply(&self, regions: &mut Vec<MemoryRegion>) {
+        regions.retain(|region| self.matches(region));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps::parse_maps_line;
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("[anon:dalvik-*]", "[anon:dalvik-main space]"));
+        assert!(wildcard_match("/system/lib64/*", "/system/lib64/libc.so"));
+        assert!(wildcard_match("rw-?", "rw-p"));
+        assert!(wildcard_match("*.so", "/data/app/lib/arm64/libfoo.so"));
+        assert!(wildcard_match("a*b*c", "aXXbYYbc"));
+        assert!(!wildcard_match("[anon:dalvik-*]", "[anon:scudo:primary]"));
+        assert!(!wildcard_match("rw-?", "r--p"));
+        assert!(!wildcard_match("*.so", "libfoo.so.1"));
+    }
+
+    #[test]
+    fn test_region_filter_include_and_exclude() {
+        let filter = RegionFilter {
+            include_perms: vec!["rw-?".into()],
+            exclude_paths: vec!["/system/lib64/*".into()],
+            ..Default::default()
+        };
+
+        let heap = parse_maps_line("1000-2000 rw-p 00000000 00:00 0 [heap]").unwrap();
+        let lib_data =
+            parse_maps_line("2000-3000 rw-p 00010000 fd:00 7 /system/lib64/libc.so").unwrap();
+        let guard = parse_maps_line("3000-4000 ---p 00000000 00:00 0").unwrap();
+
+        assert!(filter.matches(&heap));
+        assert!(!filter.matches(&lib_data));
+        assert!(!filter.matches(&guard));
+    }
+
+    #[test]
+    fn test_region_presets() {
+        let parse = |line| parse_maps_line(line).unwrap();
+        let heap = parse("1000-2000 rw-p 00000000 00:00 0 [anon:dalvik-main space]");
+        let stack = parse("2000-3000 rw-p 00000000 00:00 0 [anon:stack_and_tls:4321]");
+        let unnamed = parse("3000-4000 rw-p 00000000 00:00 0");
+        let library = parse("4000-5000 r--p 00000000 fd:00 7 /system/lib64/libc.so");
+
+        assert!(RegionPreset::Heap.matches(&heap));
+        assert!(!RegionPreset::Heap.matches(&stack));
+        assert!(RegionPreset::Stack.matches(&stack));
+        assert!(!RegionPreset::Stack.matches(&unnamed));
+        assert!(RegionPreset::Anonymous.matches(&heap));
+        assert!(RegionPreset::Anonymous.matches(&unnamed));
+        assert!(!RegionPreset::Anonymous.matches(&library));
+        assert!(RegionPreset::All.matches(&library));
+    }
+
+    #[test]
+    fn test_jit_cache_regions() {
+        let parse = |line| parse_maps_line(line).unwrap();
+        let code = parse("1000-2000 r-xs 02000000 00:01 9 /memfd:jit-cache (deleted)");
+        let view = parse("2000-3000 rw-s 02000000 00:01 9 /memfd:jit-cache (deleted)");
+        let old = parse("3000-4000 rwxp 00000000 00:00 0 [anon:dalvik-jit-code-cache]");
+        let data = parse("4000-5000 rw-p 00000000 00:00 0 [anon:dalvik-data-code-cache]");
+        let heap = parse("5000-6000 rw-p 00000000 00:00 0 [anon:dalvik-main space]");
+
+        assert_eq!(jit_cache(&code), Some(JitCache::Code));
+        assert_eq!(jit_cache(&view), Some(JitCache::Data));
+        assert_eq!(jit_cache(&old), Some(JitCache::Code));
+        assert_eq!(jit_cache(&data), Some(JitCache::Data));
+        assert_eq!(jit_cache(&heap), None);
+        assert!(RegionPreset::JitCache.matches(&data));
+
+        let filter = RegionFilter {
+            exclude_jit_cache: true,
+            ..Default::default()
+        };
+        assert!(!filter.matches(&code));
+        assert!(filter.matches(&heap));
+    }
+}
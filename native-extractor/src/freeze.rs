@@ -0,0 +1,165 @@
+//! Stopping a target while it is read
+//!
+//! Reading a live process races with its allocator and produces torn
+//! strings. A [`Freeze`] stops every thread with SIGSTOP for the duration
+//! of a scan and resumes them with SIGCONT when dropped. A watchdog thread
+//! resumes the target after a timeout even if the scan hangs, so it is never
+//! left stopped by us.
+
+use nix::sys::signal::{self, Signal};
+use nix::unistd::Pid;
+use std::fs;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Longest a target is kept stopped when no timeout is given
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long to wait for every thread to reach the stopped state. Threads
+/// in uninterruptible sleep can take a while; the scan goes ahead anyway.
+const STOP_WAIT: Duration = Duration::from_millis(500);
+
+/// A stopped target, resumed on drop
+pub struct Freeze {
+    pid: i32,
+    /// Wakes the watchdog early; `None` when the target was already stopped
+    /// before and is left that way
+    done: Option<mpsc::Sender<()>>,
+    watchdog: Option<JoinHandle<()>>,
+    expired: Arc<AtomicBool>,
+}
+
+impl Freeze {
+    /// Stop every thread of `pid`, resuming them after `timeout` at the
+    /// latest.
+    ///
+    /// A process that is already stopped (by a debugger, say) is left alone
+    /// and is not resumed afterwards.
+    pub fn new(pid: i32, timeout: Duration) -> io::Result<Self> {
+        if is_stopped(pid)? {
+            return Ok(Freeze {
+                pid,
+                done: None,
+                watchdog: None,
+                expired: Arc::new(AtomicBool::new(false)),
+            });
+        }
+
+        signal::kill(Pid::from_raw(pid), Signal::SIGSTOP)
+            .map_err(|e| io::Error::from_raw_os_error(e as i32))?;
+
+        let (done, wake) = mpsc::channel::<()>();
+        let expired = Arc::new(AtomicBool::new(false));
+        let watchdog = {
+            let expired = Arc::clone(&expired);
+            thread::spawn(move || {
+                if let Err(RecvTimeoutError::Timeout) = wake.recv_timeout(timeout) {
+                    expired.store(true, Ordering::Relaxed);
+                    let _ = signal::kill(Pid::from_raw(pid), Signal::SIGCONT);
+                }
+            })
+        };
+        let freeze = Freeze {
+            pid,
+            done: Some(done),
+            watchdog: Some(watchdog),
+            expired,
+        };
+
+        let deadline = Instant::now() + STOP_WAIT;
+        while Instant::now() < deadline && !all_threads_stopped(pid) {
+            thread::sleep(Duration::from_millis(1));
+        }
+        Ok(freeze)
+    }
+
+    pub fn pid(&self) -> i32 {
+        self.pid
+    }
+
+    /// Whether the watchdog had to resume the target, so reads after that
+    /// point were live
+    pub fn expired(&self) -> bool {
+        self.expired.load(Ordering::Relaxed)
+    }
+
+    /// Resume the target, returning false if it had already been resumed
+    /// by the watchdog
+    pub fn resume(mut self) -> bool {
+        self.release();
+        !self.expired()
+    }
+
+    fn release(&mut self) {
+        let Some(done) = self.done.take() else {
+            return;
+        };
+        drop(done);
+        if let Some(watchdog) = self.watchdog.take() {
+            let _ = watchdog.join();
+        }
+        if !self.expired() {
+            let _ = signal::kill(Pid::from_raw(self.pid), Signal::SIGCONT);
+        }
+    }
+}
+
+impl Drop for Freeze {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+/// Scheduler state of a process or thread, the field after the name in
+/// its stat file
+fn task_state(stat_path: &str) -> io::Result<char> {
+    let stat = fs::read_to_string(stat_path)?;
+    stat.rsplit_once(')')
+        .and_then(|(_, fields)| fields.trim_start().chars().next())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "malformed stat"))
+}
+
+fn is_stopped(pid: i32) -> io::Result<bool> {
+    Ok(matches!(task_state(&format!("/proc/{}/stat", pid))?, 'T' | 't'))
+}
+
+fn all_threads_stopped(pid: i32) -> bool {
+    let Ok(tasks) = fs::read_dir(format!("/proc/{}/task", pid)) else {
+        return false;
+    };
+    tasks.flatten().all(|task| {
+        let stat = task.path().join("stat");
+        // A thread that exited meanwhile doesn't hold anything up
+        stat.to_str().is_none_or(|path| matches!(task_state(path), Ok('T' | 't') | Err(_)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    #[test]
+    fn test_freeze_stops_and_resumes_target() {
+        let mut child = Command::new("sleep").arg("10").spawn().unwrap();
+        let pid = child.id() as i32;
+
+        let freeze = Freeze::new(pid, Duration::from_secs(5)).unwrap();
+        assert!(is_stopped(pid).unwrap());
+        assert!(freeze.resume());
+        assert!(!is_stopped(pid).unwrap());
+
+        let freeze = Freeze::new(pid, Duration::from_millis(20)).unwrap();
+        thread::sleep(Duration::from_millis(200));
+        assert!(freeze.expired());
+        assert!(!is_stopped(pid).unwrap());
+        assert!(!freeze.resume());
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+}
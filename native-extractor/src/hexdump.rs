@@ -0,0 +1,72 @@
+//! Hexdump rendering for memory snippets
+
+use std::fmt::Write;
+
+const BYTES_PER_LINE: usize = 16;
+
+/// Render `bytes` read from `address` in the classic hexdump layout:
+/// address, sixteen hex bytes split into two groups of eight, and the
+/// printable ASCII column.
+///
+/// Lines are aligned to 16-byte boundaries, so the first and last line may
+/// be padded with blanks.
+pub fn hexdump(address: u64, bytes: &[u8]) -> String {
+    let mut out = String::new();
+    if bytes.is_empty() {
+        return out;
+    }
+
+    let end = address + bytes.len() as u64;
+    let mut line = address - address % BYTES_PER_LINE as u64;
+
+    while line < end {
+        let _ = write!(out, "{:016x} ", line);
+        let mut ascii = String::with_capacity(BYTES_PER_LINE);
+
+        for column in 0..BYTES_PER_LINE as u64 {
+            if column == 8 {
+                out.push(' ');
+            }
+            let position = line + column;
+            if position < address || position >= end {
+                out.push_str("   ");
+                ascii.push(' ');
+                continue;
+            }
+            let byte = bytes[(position - address) as usize];
+            let _ = write!(out, " {:02x}", byte);
+            ascii.push(if byte.is_ascii_graphic() || byte == b' ' {
+                byte as char
+            } else {
+                '.'
+            });
+        }
+
+        let _ = writeln!(out, "  |{}|", ascii);
+        line += BYTES_PER_LINE as u64;
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hexdump_aligns_and_pads_lines() {
+        let dump = hexdump(0x100e, b"\x00\x01token=abc\n");
+        let lines: Vec<&str> = dump.lines().collect();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[0],
+            "0000000000001000                                             00 01  |              ..|"
+        );
+        assert_eq!(
+            lines[1],
+            "0000000000001010  74 6f 6b 65 6e 3d 61 62  63 0a                    |token=abc.      |"
+        );
+        assert_eq!(hexdump(0x1000, b""), "");
+    }
+}
@@ -0,0 +1,301 @@
+//! Values held fixed in a live process
+//!
+//! A memory editor freezes a value by writing it back faster than the app
+//! changes it. Each [`Hold`] has a thread of its own that writes its value
+//! to every address it was given once per interval, until it is released or
+//! the process exits; a Java loop making a JNI call per write would wake up
+//! late and unevenly. This is unrelated to [`crate::freeze`], which stops
+//! the whole process.
+
+use crate::error::ExtractError;
+use crate::scan;
+use crate::value::{self, Endian, ValueType};
+use crate::writer::MemoryWriter;
+use serde::{Deserialize, Serialize};
+use serde_json::Number;
+use std::collections::HashMap;
+use std::io;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// The `hold` object of the options `freezeValue` takes
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct HoldOptions {
+    /// Where the value is written
+    pub addresses: Vec<u64>,
+    #[serde(rename = "type")]
+    pub value_type: ValueType,
+    /// The value written
+    pub value: Option<Number>,
+    pub endian: Endian,
+    /// Time from the start of one round of writes to the next
+    pub interval_ms: u64,
+}
+
+impl Default for HoldOptions {
+    fn default() -> Self {
+        HoldOptions {
+            addresses: Vec::new(),
+            value_type: ValueType::default(),
+            value: None,
+            endian: Endian::default(),
+            interval_ms: 50,
+        }
+    }
+}
+
+impl HoldOptions {
+    /// Parse the `hold` object of the options JSON
+    pub fn from_json(json: &str) -> Result<Self, ExtractError> {
+        let options: HoldOptions = scan::parse_object(json, "hold")?;
+        if options.interval_ms == 0 {
+            return Err(ExtractError::InvalidArgument(
+                "Invalid hold options: intervalMs must be at least 1".into(),
+            ));
+        }
+        Ok(options)
+    }
+}
+
+/// How a hold has gone so far
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HoldStatus {
+    /// Values written in full
+    pub writes: u64,
+    /// Writes that failed, or were cut short by unmapped memory
+    pub failures: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    /// Whether the hold stopped by itself because the process exited
+    pub ended: bool,
+}
+
+/// A registered hold, as listed
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HoldInfo {
+    pub handle: i64,
+    pub pid: i32,
+    pub addresses: Vec<u64>,
+    #[serde(rename = "type")]
+    pub value_type: ValueType,
+    pub value: Number,
+    pub endian: Endian,
+    pub interval_ms: u64,
+    #[serde(flatten)]
+    pub status: HoldStatus,
+}
+
+/// A value being written back to a process, until dropped
+pub struct Hold {
+    info: HoldInfo,
+    status: Arc<Mutex<HoldStatus>>,
+    /// Wakes the writer thread to stop it
+    stop: Option<mpsc::Sender<()>>,
+    writer: Option<JoinHandle<()>>,
+}
+
+impl Hold {
+    /// Write the value `options` give to each of their addresses, and start
+    /// the thread writing it again every interval.
+    ///
+    /// Fails if the first round writes nothing, so a wrong address or a
+    /// missing process is reported rather than retried forever.
+    pub fn start(pid: i32, options: &HoldOptions) -> Result<Self, String> {
+        let number = options.value.clone().ok_or("hold value must be set")?;
+        if options.addresses.is_empty() {
+            return Err("hold addresses must be set".into());
+        }
+        let bytes = value::value_bytes(options.value_type, options.endian, &number)?;
+        let mut writer = MemoryWriter::new(pid);
+        let mut status = HoldStatus::default();
+        write_all(&mut writer, &options.addresses, &bytes, &mut status);
+        if status.writes == 0 {
+            return Err(status.last_error.unwrap_or_default());
+        }
+
+        let status = Arc::new(Mutex::new(status));
+        let (stop, wake) = mpsc::channel::<()>();
+        let interval = Duration::from_millis(options.interval_ms);
+        let thread = {
+            let status = Arc::clone(&status);
+            let addresses = options.addresses.clone();
+            thread::spawn(move || {
+                let mut next = Instant::now() + interval;
+                loop {
+                    match wake.recv_timeout(next.saturating_duration_since(Instant::now())) {
+                        Err(RecvTimeoutError::Timeout) => {}
+                        _ => return,
+                    }
+                    let mut status = status.lock().unwrap_or_else(|e| e.into_inner());
+                    if !write_all(&mut writer, &addresses, &bytes, &mut status) {
+                        status.ended = true;
+                        return;
+                    }
+                    drop(status);
+                    // After a stall, carry on from now rather than catching up
+                    next = (next + interval).max(Instant::now());
+                }
+            })
+        };
+        Ok(Hold {
+            info: HoldInfo {
+                handle: 0,
+                pid,
+                addresses: options.addresses.clone(),
+                value_type: options.value_type,
+                value: number,
+                endian: options.endian,
+                interval_ms: options.interval_ms,
+                status: HoldStatus::default(),
+            },
+            status,
+            stop: Some(stop),
+            writer: Some(thread),
+        })
+    }
+
+    /// The hold as it stands now
+    pub fn info(&self) -> HoldInfo {
+        HoldInfo {
+            status: self.status.lock().unwrap_or_else(|e| e.into_inner()).clone(),
+            ..self.info.clone()
+        }
+    }
+}
+
+impl Drop for Hold {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+/// Write `bytes` to every address, returning false once the process has
+/// exited
+fn write_all(
+    writer: &mut MemoryWriter,
+    addresses: &[u64],
+    bytes: &[u8],
+    status: &mut HoldStatus,
+) -> bool {
+    for &address in addresses {
+        match writer.write_at(address, bytes) {
+            Ok(written) if written == bytes.len() => status.writes += 1,
+            Ok(_) => {
+                status.failures += 1;
+                status.last_error = Some(format!("Short write at 0x{:x}", address));
+            }
+            Err(e) if is_gone(&e) => {
+                status.last_error = Some(format!("Process {} exited", writer.pid()));
+                return false;
+            }
+            Err(e) => {
+                status.failures += 1;
+                status.last_error = Some(format!("Failed to write 0x{:x}: {}", address, e));
+            }
+        }
+    }
+    true
+}
+
+fn is_gone(error: &io::Error) -> bool {
+    matches!(error.raw_os_error(), Some(libc::ESRCH) | Some(libc::ENOENT))
+}
+
+type HoldTable = Mutex<HashMap<i64, Hold>>;
+
+/// Handles are never reused, so a stale handle can't release a newer hold
+static NEXT_HANDLE: AtomicI64 = AtomicI64::new(1);
+
+fn holds() -> &'static HoldTable {
+    static HOLDS: OnceLock<HoldTable> = OnceLock::new();
+    HOLDS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Store a hold, returning its handle. Handles are never 0.
+pub fn register(mut hold: Hold) -> i64 {
+    let handle = NEXT_HANDLE.fetch_add(1, Ordering::Relaxed);
+    hold.info.handle = handle;
+    holds().lock().unwrap_or_else(|e| e.into_inner()).insert(handle, hold);
+    handle
+}
+
+/// Stop a hold, leaving the value as last written. Returns false for an
+/// unknown handle.
+pub fn release(handle: i64) -> bool {
+    let hold = holds().lock().unwrap_or_else(|e| e.into_inner()).remove(&handle);
+    // Dropped outside the lock, as it waits for the writer thread
+    hold.is_some()
+}
+
+/// Every registered hold, including those that ended with their process,
+/// in the order they were made
+pub fn list() -> Vec<HoldInfo> {
+    let holds = holds().lock().unwrap_or_else(|e| e.into_inner());
+    let mut list: Vec<HoldInfo> = holds.values().map(Hold::info).collect();
+    list.sort_unstable_by_key(|info| info.handle);
+    list
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_holds_value() {
+        let mut data = vec![0u8; 16];
+        let base = data.as_mut_ptr();
+        let address = base as u64;
+        let options = HoldOptions {
+            addresses: vec![address, address + 8],
+            value: Some(1234.into()),
+            interval_ms: 1,
+            ..HoldOptions::default()
+        };
+        let handle = register(Hold::start(std::process::id() as i32, &options).unwrap());
+        // SAFETY: both values lie within `data`, which outlives the hold
+        let read = |offset| unsafe { (base.add(offset) as *const [u8; 4]).read_volatile() };
+        assert_eq!((read(0), read(8)), (1234i32.to_le_bytes(), 1234i32.to_le_bytes()));
+
+        // Overwritten by the target, then written back
+        // SAFETY: as above
+        unsafe { (base as *mut [u8; 4]).write_volatile(7i32.to_le_bytes()) };
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while read(0) != 1234i32.to_le_bytes() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(read(0), 1234i32.to_le_bytes());
+
+        let info = list().into_iter().find(|info| info.handle == handle).unwrap();
+        assert!(info.status.writes >= 2);
+        assert!(release(handle));
+        assert!(!release(handle));
+
+        let unmapped = HoldOptions {
+            addresses: vec![8],
+            ..options
+        };
+        assert!(Hold::start(std::process::id() as i32, &unmapped).is_err());
+        drop(data);
+    }
+
+    #[test]
+    fn test_hold_options_from_json() {
+        let options = HoldOptions::from_json(
+            r#"{"minLength": 8, "hold": {"addresses": [4096], "value": 7, "intervalMs": 5}}"#,
+        )
+        .unwrap();
+        assert_eq!((options.addresses, options.interval_ms), (vec![4096], 5));
+        assert_eq!(HoldOptions::from_json("").unwrap().interval_ms, 50);
+        assert!(HoldOptions::from_json(r#"{"hold": {"intervalMs": 0}}"#).is_err());
+        assert!(HoldOptions::from_json(r#"{"hold": {"addresses": "x"}}"#).is_err());
+    }
+}
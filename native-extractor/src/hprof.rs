@@ -0,0 +1,964 @@
+//! Java heap dumps in HPROF format, such as `am dumpheap` writes
+//!
+//! A heap dump holds no address space to scan, but the text a process
+//! keeps on the Java heap lives in its `char[]` and `byte[]` arrays, which
+//! back every `String` and most buffers. [`load_hprof`] turns each such
+//! array into a region whose address is the array's object ID, named after
+//! the object holding it (`java.lang.String@12c4f0a0`, say) when an
+//! instance field refers to it, or after its type. The usual scans then run
+//! over a heap dump as they would over a process.
+//!
+//! Both the standard format and Android's extensions to it are read, so
+//! dumps don't need converting with `hprof-conv` first.
+//!
+//! [`write_heap`] goes the other way, writing the classes and objects
+//! found in a live heap by [`crate::art`] as a standard heap dump that MAT
+//! and other HPROF tools open.
+
+use crate::art::{self, ClassLayout, FieldKind, HeapObject, JavaHeap};
+use crate::dump::LoadedDump;
+use crate::maps::{MemoryRegion, Permissions};
+use crate::reader::{self, MemoryReader};
+use crate::scan::{Scan, ScanControl, StopReason};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Start of the header, before the version
+pub const MAGIC: &[u8] = b"JAVA PROFILE ";
+
+const TAG_STRING: u8 = 0x01;
+const TAG_LOAD_CLASS: u8 = 0x02;
+const TAG_HEAP_DUMP: u8 = 0x0c;
+const TAG_HEAP_DUMP_SEGMENT: u8 = 0x1c;
+const TAG_HEAP_DUMP_END: u8 = 0x2c;
+
+const CLASS_DUMP: u8 = 0x20;
+const INSTANCE_DUMP: u8 = 0x21;
+const OBJECT_ARRAY_DUMP: u8 = 0x22;
+const PRIMITIVE_ARRAY_DUMP: u8 = 0x23;
+/// Android: an array whose contents were left out
+const PRIMITIVE_ARRAY_NODATA: u8 = 0xc3;
+
+const ROOT_UNKNOWN: u8 = 0xff;
+const ROOT_STICKY_CLASS: u8 = 0x05;
+
+const TYPE_OBJECT: u8 = 2;
+const TYPE_CHAR: u8 = 5;
+const TYPE_BYTE: u8 = 8;
+const TYPE_INT: u8 = 10;
+
+/// Arrays shorter than this can't hold anything worth finding
+const MIN_ARRAY_BYTES: u64 = 4;
+
+/// Size of a value of basic type `kind`, given the dump's ID size
+fn type_size(kind: u8, id_size: u64) -> Option<u64> {
+    match kind {
+        TYPE_OBJECT => Some(id_size),
+        4 | 8 => Some(1),
+        5 | 9 => Some(2),
+        6 | 10 => Some(4),
+        7 | 11 => Some(8),
+        _ => None,
+    }
+}
+
+/// Size of the body of a heap dump sub-record that carries nothing read
+/// here, or `None` for one that does or isn't known
+fn root_size(tag: u8, id: u64) -> Option<u64> {
+    match tag {
+        // Unknown, sticky class, monitor used, and Android's interned
+        // string, finalizing, debugger, reference cleanup, VM internal and
+        // unreachable roots
+        0xff | 0x05 | 0x07 | 0x89..=0x8d | 0x90 => Some(id),
+        // JNI global
+        0x01 => Some(2 * id),
+        // JNI local, Java frame, thread object, Android JNI monitor
+        0x02 | 0x03 | 0x08 | 0x8e => Some(id + 8),
+        // Native stack, thread block
+        0x04 | 0x06 => Some(id + 4),
+        // Android heap dump info: heap type and name
+        0xfe => Some(4 + id),
+        _ => None,
+    }
+}
+
+/// One `char[]` or `byte[]` kept as a region
+struct Array {
+    id: u64,
+    /// Where its contents start in the file
+    offset: u64,
+    length: u64,
+    chars: bool,
+    /// Class and ID of the first instance found referring to it
+    owner: Option<(u64, u64)>,
+}
+
+/// Instance fields declared by a class, and its superclass
+struct ClassInfo {
+    superclass: u64,
+    fields: Vec<u8>,
+}
+
+/// What a first pass over the dump collects
+#[derive(Default)]
+struct Heap {
+    strings: HashMap<u64, String>,
+    /// Class ID to the ID of its name
+    class_names: HashMap<u64, u64>,
+    classes: HashMap<u64, ClassInfo>,
+    arrays: Vec<Array>,
+}
+
+impl Heap {
+    fn class_name(&self, class: u64) -> String {
+        self.class_names
+            .get(&class)
+            .and_then(|name| self.strings.get(name))
+            // Standard dumps use internal names, Android's dotted ones
+            .map(|name| name.replace('/', "."))
+            .unwrap_or_else(|| format!("class@{:x}", class))
+    }
+
+    /// The offsets in an instance of `class` of its object fields, own
+    /// fields first as instance dumps lay them out
+    fn object_fields(&self, mut class: u64, id_size: u64) -> Vec<u64> {
+        let mut offsets = Vec::new();
+        let mut offset = 0;
+        // Bounded in case of a cycle in a corrupt dump
+        for _ in 0..64 {
+            let Some(info) = self.classes.get(&class) else {
+                break;
+            };
+            for &kind in &info.fields {
+                if kind == TYPE_OBJECT {
+                    offsets.push(offset);
+                }
+                offset += type_size(kind, id_size).unwrap_or(0);
+            }
+            class = info.superclass;
+        }
+        offsets
+    }
+}
+
+/// Sequential reads of big-endian values from the dump
+struct Input {
+    file: BufReader<File>,
+    position: u64,
+    id_size: u64,
+}
+
+impl Input {
+    fn bytes<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let mut bytes = [0u8; N];
+        self.file.read_exact(&mut bytes)?;
+        self.position += N as u64;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.bytes::<1>()?[0])
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        self.bytes().map(u16::from_be_bytes)
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        self.bytes().map(u32::from_be_bytes)
+    }
+
+    fn id(&mut self) -> io::Result<u64> {
+        if self.id_size == 8 {
+            self.bytes().map(u64::from_be_bytes)
+        } else {
+            self.u32().map(u64::from)
+        }
+    }
+
+    fn vec(&mut self, length: u64) -> io::Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        (&mut self.file).take(length).read_to_end(&mut bytes)?;
+        if (bytes.len() as u64) < length {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        self.position += length;
+        Ok(bytes)
+    }
+
+    fn skip(&mut self, length: u64) -> io::Result<()> {
+        let length = i64::try_from(length).map_err(|_| invalid("Record too long"))?;
+        self.file.seek_relative(length)?;
+        self.position += length as u64;
+        Ok(())
+    }
+}
+
+fn invalid(what: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, what.to_string())
+}
+
+/// Open the heap dump at `path` for scanning: see the [module docs](self)
+pub fn load_hprof(path: &Path) -> io::Result<LoadedDump> {
+    let mut heap = Heap::default();
+    walk(path, &mut heap, false)?;
+    if heap.arrays.is_empty() {
+        return dump_of(path, Vec::new(), Vec::new());
+    }
+    // Instances can come before the classes describing them, so owners
+    // are found in a second pass
+    walk(path, &mut heap, true)?;
+
+    heap.arrays.sort_by_key(|array| array.id);
+    let mut segments: Vec<Segment> = Vec::with_capacity(heap.arrays.len());
+    let mut regions = Vec::with_capacity(heap.arrays.len());
+    for array in &heap.arrays {
+        // IDs are addresses in the dumps of every common VM, so arrays
+        // don't overlap; skip any that do rather than misattribute bytes
+        if segments.last().is_some_and(|last| last.end > array.id) {
+            continue;
+        }
+        let Some(end) = array.id.checked_add(array.length) else {
+            continue;
+        };
+        segments.push(Segment {
+            start: array.id,
+            end,
+            offset: array.offset,
+            chars: array.chars,
+        });
+        let name = match array.owner {
+            Some((class, owner)) => format!("{}@{:x}", heap.class_name(class), owner),
+            None => format!("{}@{:x}", if array.chars { "char[]" } else { "byte[]" }, array.id),
+        };
+        regions.push(MemoryRegion {
+            start: array.id,
+            end,
+            perms: Permissions {
+                read: true,
+                write: true,
+                ..Permissions::default()
+            },
+            offset: 0,
+            dev: "00:00".to_string(),
+            inode: 0,
+            path: Some(name),
+            usage: None,
+        });
+    }
+    dump_of(path, regions, segments)
+}
+
+fn dump_of(
+    path: &Path,
+    regions: Vec<MemoryRegion>,
+    segments: Vec<Segment>,
+) -> io::Result<LoadedDump> {
+    Ok(LoadedDump {
+        pid: 0,
+        process: None,
+        regions,
+        reader: Box::new(HprofReader {
+            file: File::open(path)?,
+            segments,
+        }),
+    })
+}
+
+/// Read every record of the dump at `path`: the strings, classes and
+/// arrays into `heap`, or with `instances`, the owners of its arrays
+fn walk(path: &Path, heap: &mut Heap, instances: bool) -> io::Result<()> {
+    let mut file = BufReader::with_capacity(1 << 16, File::open(path)?);
+    let mut version = Vec::new();
+    file.read_until(0, &mut version)?;
+    if !version.starts_with(MAGIC) {
+        return Err(invalid("Not an HPROF file"));
+    }
+    let mut input = Input {
+        file,
+        position: version.len() as u64,
+        id_size: 4,
+    };
+    input.id_size = u64::from(input.u32()?);
+    if input.id_size != 4 && input.id_size != 8 {
+        return Err(invalid("Unsupported HPROF ID size"));
+    }
+    input.skip(8)?;
+
+    let mut owners = HashMap::new();
+    if instances {
+        for (index, array) in heap.arrays.iter().enumerate() {
+            owners.insert(array.id, index);
+        }
+    }
+    let mut layouts = HashMap::new();
+
+    loop {
+        let tag = match input.u8() {
+            Ok(tag) => tag,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        input.skip(4)?;
+        let length = u64::from(input.u32()?);
+        let end = input.position + length;
+
+        match tag {
+            TAG_STRING if !instances => {
+                let id = input.id()?;
+                let name = input.vec(length.saturating_sub(input.id_size))?;
+                heap.strings.insert(id, String::from_utf8_lossy(&name).into_owned());
+            }
+            TAG_LOAD_CLASS if !instances => {
+                input.skip(4)?;
+                let class = input.id()?;
+                input.skip(4)?;
+                let name = input.id()?;
+                heap.class_names.insert(class, name);
+            }
+            TAG_HEAP_DUMP | TAG_HEAP_DUMP_SEGMENT => {
+                while input.position < end {
+                    let tag = input.u8()?;
+                    if instances && tag == INSTANCE_DUMP {
+                        let id = input.id()?;
+                        input.skip(4)?;
+                        let class = input.id()?;
+                        let size = u64::from(input.u32()?);
+                        let data = input.vec(size)?;
+                        let fields = layouts
+                            .entry(class)
+                            .or_insert_with(|| heap.object_fields(class, input.id_size));
+                        for &offset in fields.iter() {
+                            let Some(value) = id_at(&data, offset, input.id_size) else {
+                                continue;
+                            };
+                            if let Some(&index) = owners.get(&value) {
+                                heap.arrays[index].owner.get_or_insert((class, id));
+                            }
+                        }
+                    } else {
+                        read_sub_record(&mut input, tag, heap, instances)?;
+                    }
+                }
+            }
+            _ => {}
+        }
+        if input.position > end {
+            return Err(invalid("Truncated HPROF record"));
+        }
+        input.skip(end - input.position)?;
+    }
+}
+
+/// Read one heap dump sub-record other than an instance dump in a second
+/// pass, keeping what the first pass needs
+fn read_sub_record(input: &mut Input, tag: u8, heap: &mut Heap, instances: bool) -> io::Result<()> {
+    let id_size = input.id_size;
+    if let Some(size) = root_size(tag, id_size) {
+        return input.skip(size);
+    }
+    match tag {
+        CLASS_DUMP => {
+            let class = input.id()?;
+            input.skip(4)?;
+            let superclass = input.id()?;
+            input.skip(5 * id_size + 4)?;
+            for _ in 0..input.u16()? {
+                input.skip(2)?;
+                let kind = input.u8()?;
+                input.skip(type_size(kind, id_size).ok_or_else(|| invalid("Bad constant type"))?)?;
+            }
+            for _ in 0..input.u16()? {
+                input.skip(id_size)?;
+                let kind = input.u8()?;
+                input.skip(type_size(kind, id_size).ok_or_else(|| invalid("Bad static type"))?)?;
+            }
+            let mut fields = Vec::new();
+            for _ in 0..input.u16()? {
+                input.skip(id_size)?;
+                fields.push(input.u8()?);
+            }
+            if !instances {
+                heap.classes.insert(class, ClassInfo { superclass, fields });
+            }
+        }
+        INSTANCE_DUMP => {
+            input.skip(2 * id_size + 4)?;
+            let size = input.u32()?;
+            input.skip(u64::from(size))?;
+        }
+        OBJECT_ARRAY_DUMP => {
+            input.skip(id_size + 4)?;
+            let count = input.u32()?;
+            input.skip(id_size + u64::from(count) * id_size)?;
+        }
+        PRIMITIVE_ARRAY_DUMP => {
+            let id = input.id()?;
+            input.skip(4)?;
+            let count = u64::from(input.u32()?);
+            let kind = input.u8()?;
+            let length = count * type_size(kind, id_size).ok_or_else(|| invalid("Bad array type"))?;
+            if !instances && matches!(kind, TYPE_CHAR | TYPE_BYTE) && length >= MIN_ARRAY_BYTES {
+                heap.arrays.push(Array {
+                    id,
+                    offset: input.position,
+                    length,
+                    chars: kind == TYPE_CHAR,
+                    owner: None,
+                });
+            }
+            input.skip(length)?;
+        }
+        PRIMITIVE_ARRAY_NODATA => input.skip(id_size + 9)?,
+        _ => return Err(invalid(&format!("Unknown heap dump record 0x{:02x}", tag))),
+    }
+    Ok(())
+}
+
+fn id_at(data: &[u8], offset: u64, id_size: u64) -> Option<u64> {
+    let offset = usize::try_from(offset).ok()?;
+    let bytes = data.get(offset..offset + id_size as usize)?;
+    Some(bytes.iter().fold(0, |value, &byte| value << 8 | u64::from(byte)))
+}
+
+/// The contents of one array in the dump file
+struct Segment {
+    start: u64,
+    end: u64,
+    offset: u64,
+    /// `char[]`, stored big-endian and read back little-endian
+    chars: bool,
+}
+
+/// Reads the arrays of a heap dump at their object IDs. `char[]` contents
+/// come back little-endian, as ART holds strings in memory, so the
+/// `utf16le` encoding finds them.
+struct HprofReader {
+    file: File,
+    /// Sorted by address, not overlapping
+    segments: Vec<Segment>,
+}
+
+impl MemoryReader for HprofReader {
+    fn name(&self) -> &'static str {
+        "hprof"
+    }
+
+    fn read_at(&mut self, address: u64, buf: &mut [u8]) -> io::Result<usize> {
+        let index = self.segments.partition_point(|segment| segment.end <= address);
+        let segment = self
+            .segments
+            .get(index)
+            .filter(|segment| segment.start <= address)
+            .ok_or_else(|| io::Error::from_raw_os_error(libc::EFAULT))?;
+
+        let want = (segment.end - address).min(buf.len() as u64) as usize;
+        if !segment.chars {
+            let offset = segment.offset + (address - segment.start);
+            return read_full(&self.file, &mut buf[..want], offset);
+        }
+
+        // Swap whole characters, so start on one and take the one the read
+        // ends in
+        let skew = ((address - segment.start) % 2) as usize;
+        let mut chars = vec![0u8; (skew + want).next_multiple_of(2)];
+        let offset = segment.offset + (address - segment.start) - skew as u64;
+        let read = read_full(&self.file, &mut chars, offset)? & !1;
+        for pair in chars[..read].chunks_exact_mut(2) {
+            pair.swap(0, 1);
+        }
+        let read = read.saturating_sub(skew).min(want);
+        buf[..read].copy_from_slice(&chars[skew..skew + read]);
+        Ok(read)
+    }
+}
+
+fn read_full(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match file.read_at(&mut buf[filled..], offset + filled as u64) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// Records are flushed once a heap dump segment reaches this size
+const SEGMENT_SIZE: usize = 1 << 20;
+
+/// Write the objects of the heap spaces `regions`, read through `reader`,
+/// to `path` as a heap dump, with the classes of `heap`.
+///
+/// Each class is a class dump with its fields and the values of its static
+/// fields, and a root (`ROOT STICKY CLASS`), as the runtime holds on to
+/// it. Objects keep their addresses as IDs, with the values of their
+/// fields; as in ART's own dumps, a string's characters are a `char[]` in
+/// its `value` field, at the address they start at. What refers to an
+/// object from outside the heap, such as a thread's stack or native code,
+/// isn't known, so each object nothing in the heap refers to is a root
+/// (`ROOT UNKNOWN`), which keeps HPROF tools from discarding it as
+/// unreachable.
+///
+/// The regions read are recorded in `scan`, with a result for each class
+/// and object written. If `control` stops the walk, the objects found so
+/// far are still written and the reason is returned.
+pub fn write_heap(
+    reader: &mut dyn MemoryReader,
+    heap: &JavaHeap,
+    regions: &[MemoryRegion],
+    path: &Path,
+    scan: &mut Scan<()>,
+    control: &mut ScanControl,
+) -> io::Result<Option<StopReason>> {
+    let mut out = HeapWriter::create(path)?;
+    for (serial, layout) in (1u32..).zip(&heap.classes) {
+        out.load_class(serial, layout)?;
+    }
+    for layout in &heap.classes {
+        out.class_dump(reader, heap, layout)?;
+    }
+    scan.results.extend(heap.classes.iter().map(|_| ()));
+    let mut visit = |object: HeapObject<'_>| out.object(heap, object);
+    let stopped = art::walk_objects(reader, regions, heap, scan, control, &mut visit);
+    out.finish()?;
+    Ok(stopped)
+}
+
+/// Writes a heap dump, a segment at a time
+struct HeapWriter {
+    out: BufWriter<File>,
+    /// The heap dump segment being filled
+    segment: Vec<u8>,
+    /// IDs of the strings written
+    names: HashMap<String, u32>,
+    /// Objects written, and the objects their fields refer to
+    objects: Vec<u32>,
+    referenced: HashSet<u32>,
+    /// The first write that failed, after which objects are dropped
+    error: Option<io::Error>,
+}
+
+impl HeapWriter {
+    fn create(path: &Path) -> io::Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(b"JAVA PROFILE 1.0.2\0")?;
+        out.write_all(&4u32.to_be_bytes())?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        out.write_all(&(now.as_millis() as u64).to_be_bytes())?;
+        Ok(HeapWriter {
+            out,
+            segment: Vec::new(),
+            names: HashMap::new(),
+            objects: Vec::new(),
+            referenced: HashSet::new(),
+            error: None,
+        })
+    }
+
+    /// ID of the string `name`, written the first time it is used
+    fn name(&mut self, name: &str) -> io::Result<u32> {
+        if let Some(&id) = self.names.get(name) {
+            return Ok(id);
+        }
+        let id = self.names.len() as u32 + 1;
+        let body = [&id.to_be_bytes()[..], name.as_bytes()].concat();
+        write_record(&mut self.out, TAG_STRING, &body)?;
+        self.names.insert(name.to_string(), id);
+        Ok(id)
+    }
+
+    fn load_class(&mut self, serial: u32, layout: &ClassLayout) -> io::Result<()> {
+        let name = self.name(&layout.class.name)?;
+        let mut record = serial.to_be_bytes().to_vec();
+        push_id(&mut record, layout.class.address);
+        record.extend_from_slice(&0u32.to_be_bytes());
+        record.extend_from_slice(&name.to_be_bytes());
+        write_record(&mut self.out, TAG_LOAD_CLASS, &record)
+    }
+
+    /// The class dump of `layout`, its static fields' values read from its
+    /// class object, and its root
+    fn class_dump(
+        &mut self,
+        reader: &mut dyn MemoryReader,
+        heap: &JavaHeap,
+        layout: &ClassLayout,
+    ) -> io::Result<()> {
+        let extent = layout
+            .static_fields
+            .iter()
+            .map(|field| field.offset as usize + field.kind.size())
+            .max()
+            .unwrap_or(0);
+        let mut statics = vec![0u8; extent];
+        let read = reader::read_into(reader, layout.class.address, &mut statics).unwrap_or(0);
+        statics.truncate(read);
+
+        let mut fields = Vec::new();
+        if heap.is_string_class(layout.class.address) {
+            fields.push((self.name("value")?, TYPE_OBJECT));
+        }
+        for field in &layout.fields {
+            fields.push((self.name(&field.name)?, basic_type(field.kind)));
+        }
+        let mut static_names = Vec::new();
+        for field in &layout.static_fields {
+            static_names.push(self.name(&field.name)?);
+        }
+
+        self.segment.push(CLASS_DUMP);
+        push_id(&mut self.segment, layout.class.address);
+        self.segment.extend_from_slice(&0u32.to_be_bytes());
+        push_id(&mut self.segment, layout.superclass);
+        push_id(&mut self.segment, layout.class_loader);
+        // Signers, protection domain and two reserved IDs
+        self.segment.extend_from_slice(&[0; 4 * 4]);
+        self.segment.extend_from_slice(&layout.object_size.to_be_bytes());
+        // No constants
+        self.segment.extend_from_slice(&0u16.to_be_bytes());
+        self.segment.extend_from_slice(&(static_names.len() as u16).to_be_bytes());
+        for (field, name) in layout.static_fields.iter().zip(static_names) {
+            self.segment.extend_from_slice(&name.to_be_bytes());
+            self.segment.push(basic_type(field.kind));
+            self.push_value(field.kind, &statics, field.offset as usize);
+        }
+        self.segment.extend_from_slice(&(fields.len() as u16).to_be_bytes());
+        for (name, kind) in fields {
+            self.segment.extend_from_slice(&name.to_be_bytes());
+            self.segment.push(kind);
+        }
+        self.referenced.insert(layout.class_loader as u32);
+
+        self.segment.push(ROOT_STICKY_CLASS);
+        push_id(&mut self.segment, layout.class.address);
+        self.flush_segment(false)
+    }
+
+    fn object(&mut self, heap: &JavaHeap, object: HeapObject<'_>) {
+        if self.error.is_some() {
+            return;
+        }
+        match object {
+            HeapObject::Instance {
+                address,
+                class,
+                bytes,
+            } => self.instance(heap, address, class, None, bytes),
+            HeapObject::String { string, bytes } => {
+                let class = heap.class(u64::from(string.class));
+                let value = string.address + art::STRING_HEADER;
+                if let Some(class) = class {
+                    self.instance(heap, string.address, class, Some(value), bytes);
+                }
+                let units: Vec<u16> = string.value.encode_utf16().collect();
+                self.segment.push(PRIMITIVE_ARRAY_DUMP);
+                push_id(&mut self.segment, value);
+                self.segment.extend_from_slice(&0u32.to_be_bytes());
+                self.segment.extend_from_slice(&(units.len() as u32).to_be_bytes());
+                self.segment.push(TYPE_CHAR);
+                self.segment.extend(units.iter().flat_map(|unit| unit.to_be_bytes()));
+            }
+            HeapObject::Array {
+                address,
+                class,
+                kind,
+                length,
+                elements,
+            } => {
+                self.objects.push(address as u32);
+                let objects = kind == FieldKind::Object;
+                self.segment.push(if objects { OBJECT_ARRAY_DUMP } else { PRIMITIVE_ARRAY_DUMP });
+                push_id(&mut self.segment, address);
+                self.segment.extend_from_slice(&0u32.to_be_bytes());
+                self.segment.extend_from_slice(&(length as u32).to_be_bytes());
+                if objects {
+                    push_id(&mut self.segment, class.class.address);
+                } else {
+                    self.segment.push(basic_type(kind));
+                }
+                for i in 0..length {
+                    self.push_value(kind, elements, i * kind.size());
+                }
+            }
+        }
+        if let Err(e) = self.flush_segment(false) {
+            self.error = Some(e);
+        }
+    }
+
+    /// The instance dump of the object at `address`, its fields' values
+    /// read from `bytes`, a string's `value` first
+    fn instance(
+        &mut self,
+        heap: &JavaHeap,
+        address: u64,
+        class: &ClassLayout,
+        value: Option<u64>,
+        bytes: &[u8],
+    ) {
+        self.objects.push(address as u32);
+        let fields = heap.instance_fields(class.class.address);
+        let size: usize = fields.iter().map(|field| field.kind.size()).sum::<usize>()
+            + value.map_or(0, |_| 4);
+        self.segment.push(INSTANCE_DUMP);
+        push_id(&mut self.segment, address);
+        self.segment.extend_from_slice(&0u32.to_be_bytes());
+        push_id(&mut self.segment, class.class.address);
+        self.segment.extend_from_slice(&(size as u32).to_be_bytes());
+        if let Some(value) = value {
+            self.referenced.insert(value as u32);
+            push_id(&mut self.segment, value);
+        }
+        for field in fields {
+            self.push_value(field.kind, bytes, field.offset as usize);
+        }
+    }
+
+    /// Append the value of type `kind` at `offset` in the little-endian
+    /// `bytes`, big-endian as HPROF values are, noting what it refers to
+    fn push_value(&mut self, kind: FieldKind, bytes: &[u8], offset: usize) {
+        let size = kind.size();
+        let mut value = [0u8; 8];
+        if let Some(field) = bytes.get(offset..offset + size) {
+            value[..size].copy_from_slice(field);
+        }
+        if kind == FieldKind::Object {
+            self.referenced.insert(u32::from_le_bytes(value[..4].try_into().unwrap()));
+        }
+        self.segment.extend(value[..size].iter().rev());
+    }
+
+    fn flush_segment(&mut self, force: bool) -> io::Result<()> {
+        if self.segment.len() >= SEGMENT_SIZE || force && !self.segment.is_empty() {
+            write_record(&mut self.out, TAG_HEAP_DUMP_SEGMENT, &self.segment)?;
+            self.segment.clear();
+        }
+        Ok(())
+    }
+
+    /// The roots of the objects nothing refers to, then the end of the dump
+    fn finish(mut self) -> io::Result<()> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        for &object in &self.objects {
+            if !self.referenced.contains(&object) {
+                self.segment.push(ROOT_UNKNOWN);
+                push_id(&mut self.segment, u64::from(object));
+            }
+            if self.segment.len() >= SEGMENT_SIZE {
+                write_record(&mut self.out, TAG_HEAP_DUMP_SEGMENT, &self.segment)?;
+                self.segment.clear();
+            }
+        }
+        self.flush_segment(true)?;
+        write_record(&mut self.out, TAG_HEAP_DUMP_END, &[])?;
+        self.out.flush()
+    }
+}
+
+/// HPROF's code for values of type `kind`
+fn basic_type(kind: FieldKind) -> u8 {
+    match kind {
+        FieldKind::Object => TYPE_OBJECT,
+        FieldKind::Boolean => 4,
+        FieldKind::Char => TYPE_CHAR,
+        FieldKind::Float => 6,
+        FieldKind::Double => 7,
+        FieldKind::Byte => TYPE_BYTE,
+        FieldKind::Short => 9,
+        FieldKind::Int => TYPE_INT,
+        FieldKind::Long => 11,
+    }
+}
+
+fn write_record(out: &mut impl Write, tag: u8, body: &[u8]) -> io::Result<()> {
+    out.write_all(&[tag])?;
+    out.write_all(&0u32.to_be_bytes())?;
+    out.write_all(&(body.len() as u32).to_be_bytes())?;
+    out.write_all(body)
+}
+
+/// Append an ID; the objects of an ART heap are all below 4 GiB, as its
+/// references are 32 bits
+fn push_id(out: &mut Vec<u8>, id: u64) {
+    out.extend_from_slice(&(id as u32).to_be_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader;
+
+    /// Append a record with the given tag and body
+    fn record(out: &mut Vec<u8>, tag: u8, body: &[u8]) {
+        out.push(tag);
+        out.extend_from_slice(&0u32.to_be_bytes());
+        out.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        out.extend_from_slice(body);
+    }
+
+    fn ids(values: &[u32]) -> Vec<u8> {
+        values.iter().flat_map(|value| value.to_be_bytes()).collect()
+    }
+
+    #[test]
+    fn test_string_value_is_attributed_to_its_owner() {
+        let mut hprof = b"JAVA PROFILE 1.0.3\0".to_vec();
+        hprof.extend_from_slice(&ids(&[4, 0, 0]));
+        record(&mut hprof, TAG_STRING, &[&ids(&[1])[..], b"java.lang.String"].concat());
+        record(&mut hprof, TAG_STRING, &[&ids(&[2])[..], b"value"].concat());
+        record(&mut hprof, TAG_LOAD_CLASS, &ids(&[1, 0x100, 0, 1]));
+
+        let mut heap = Vec::new();
+        // The instance comes before its class, as it may in a real dump
+        heap.push(INSTANCE_DUMP);
+        heap.extend_from_slice(&ids(&[0x2000, 0, 0x100, 8, 7, 0x3000]));
+        heap.push(CLASS_DUMP);
+        heap.extend_from_slice(&ids(&[0x100, 0, 0, 0, 0, 0, 0, 0, 8]));
+        heap.extend_from_slice(&[0, 0, 0, 0, 0, 2]);
+        heap.extend_from_slice(&[&ids(&[3])[..], &[10], &ids(&[2]), &[TYPE_OBJECT]].concat());
+        heap.push(PRIMITIVE_ARRAY_DUMP);
+        heap.extend_from_slice(&ids(&[0x3000, 0, 6]));
+        heap.push(TYPE_CHAR);
+        heap.extend("secret".encode_utf16().flat_map(|unit| unit.to_be_bytes()));
+        heap.extend_from_slice(&[0x89, 0, 0, 0x20, 0]);
+        record(&mut hprof, TAG_HEAP_DUMP_SEGMENT, &heap);
+
+        let path = std::env::temp_dir().join(format!("hprof-test-{}", std::process::id()));
+        std::fs::write(&path, &hprof).unwrap();
+        let mut dump = load_hprof(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(dump.regions.len(), 1);
+        let region = &dump.regions[0];
+        assert_eq!((region.start, region.end), (0x3000, 0x300c));
+        assert_eq!(region.name(), "java.lang.String@2000");
+        let mut text = [0u8; 11];
+        let read = reader::read_into(dump.reader.as_mut(), 0x3001, &mut text).unwrap();
+        assert_eq!(&text[..read], &b"secret".map(|c| [c, 0]).concat()[1..]);
+    }
+
+    #[test]
+    fn test_written_heap_loads_back() {
+        use crate::art::{JavaClass, JavaField};
+
+        // A low heap, as references are 32 bits, with the class objects
+        // after the space walked
+        let base = 0x3200_0000u64;
+        let mut memory = vec![0u8; 0x2000];
+        let mut put = |offset: u64, bytes: &[u8]| {
+            memory[offset as usize..offset as usize + bytes.len()].copy_from_slice(bytes);
+        };
+        let (object, string, token, ints) = (0x1000u64, 0x1100, 0x1200, 0x1300);
+        let reference = |offset: u64| ((base + offset) as u32).to_le_bytes();
+        // A token referring to a string, then an int[] nothing refers to
+        put(0, &[&reference(token)[..], &[0; 4], &reference(0x10), &7u32.to_le_bytes()].concat());
+        put(0x10, &[&reference(string)[..], &[0; 4], &14u32.to_le_bytes(), &[0; 4]].concat());
+        put(0x20, b"hunter2");
+        put(0x28, &[&reference(ints)[..], &[0; 4], &2u32.to_le_bytes()].concat());
+        put(0x34, &[&5u32.to_le_bytes()[..], &6u32.to_le_bytes()].concat());
+        put(token + 0x80, &0x0123_4567_89ab_cdefu64.to_le_bytes());
+        let mapped = unsafe {
+            libc::mmap(
+                base as *mut libc::c_void,
+                memory.len(),
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_FIXED_NOREPLACE,
+                -1,
+                0,
+            )
+        };
+        assert_eq!(mapped as u64, base);
+        unsafe { std::ptr::copy_nonoverlapping(memory.as_ptr(), mapped.cast(), memory.len()) };
+
+        let field = |name: &str, kind, offset| JavaField {
+            name: name.to_string(),
+            kind,
+            offset,
+        };
+        let layout = |address: u64, name: &str, fields: Vec<JavaField>| ClassLayout {
+            class: JavaClass {
+                address: base + address,
+                name: name.to_string(),
+                dex_location: None,
+            },
+            superclass: if address == object { 0 } else { base + object },
+            class_loader: 0,
+            object_size: 16,
+            fields,
+            static_fields: Vec::new(),
+            component: None,
+        };
+        let heap = JavaHeap::new(vec![
+            layout(object, "java.lang.Object", Vec::new()),
+            layout(
+                string,
+                "java.lang.String",
+                vec![field("count", FieldKind::Int, 8), field("hash", FieldKind::Int, 12)],
+            ),
+            ClassLayout {
+                static_fields: vec![field("created", FieldKind::Long, 0x80)],
+                ..layout(
+                    token,
+                    "com.example.Token",
+                    vec![field("secret", FieldKind::Object, 8), field("id", FieldKind::Int, 12)],
+                )
+            },
+            ClassLayout {
+                component: Some(FieldKind::Int),
+                ..layout(ints, "int[]", Vec::new())
+            },
+        ]);
+        let region = MemoryRegion {
+            start: base,
+            end: base + 0x1000,
+            perms: Permissions::parse("rw-p").unwrap(),
+            offset: 0,
+            dev: "00:00".to_string(),
+            inode: 0,
+            path: Some("[anon:dalvik-main space]".to_string()),
+            usage: None,
+        };
+
+        let path = std::env::temp_dir().join(format!("hprof-write-{}", std::process::id()));
+        let mut reader = reader::ProcfsReader::open(std::process::id() as i32).unwrap();
+        let mut scan = Scan::default();
+        let mut control = ScanControl::new();
+        let written = write_heap(&mut reader, &heap, &[region], &path, &mut scan, &mut control);
+        unsafe { libc::munmap(mapped, memory.len()) };
+        assert!(matches!(written, Ok(None)));
+        // The four classes, the token, the string and the int[]
+        assert_eq!(scan.results.len(), 7);
+
+        let hprof = std::fs::read(&path).unwrap();
+        let id = |offset: u64| ((base + offset) as u32).to_be_bytes();
+        let holds = |bytes: &[u8]| hprof.windows(bytes.len()).any(|window| window == bytes);
+        let token_dump = [&[INSTANCE_DUMP][..], &id(0), &[0; 4], &id(token), &8u32.to_be_bytes()];
+        assert!(holds(&[&token_dump.concat()[..], &id(0x10), &7u32.to_be_bytes()].concat()));
+        assert!(holds(&[&id(0x20)[..], &14u32.to_be_bytes(), &[0; 4]].concat()));
+        let ints_dump = [&[PRIMITIVE_ARRAY_DUMP][..], &id(0x28), &[0; 4], &2u32.to_be_bytes()];
+        assert!(holds(&[&ints_dump.concat()[..], &[TYPE_INT], &5u32.to_be_bytes()].concat()));
+        assert!(holds(&0x0123_4567_89ab_cdefu64.to_be_bytes()));
+        assert!(holds(&[&[ROOT_STICKY_CLASS][..], &id(token)].concat()));
+        // Only what nothing in the heap refers to is a root
+        assert!(holds(&[&[ROOT_UNKNOWN][..], &id(0)].concat()));
+        assert!(holds(&[&[ROOT_UNKNOWN][..], &id(0x28)].concat()));
+        assert!(!holds(&[&[ROOT_UNKNOWN][..], &id(0x10)].concat()));
+
+        let mut dump = load_hprof(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let names: Vec<&str> = dump.regions.iter().map(MemoryRegion::name).collect();
+        assert_eq!(names, [format!("java.lang.String@{:x}", base + 0x10)]);
+        let mut text = [0u8; 14];
+        reader::read_into(dump.reader.as_mut(), base + 0x20, &mut text).unwrap();
+        assert_eq!(text, b"hunter2".map(|c| [c, 0]).concat()[..]);
+    }
+}
@@ -0,0 +1,289 @@
+//! HTTP/1.x messages reassembled from memory
+//!
+//! Apps build requests and read responses in plain text before TLS
+//! encrypts them and after it decrypts them, and those buffers linger. A
+//! message is a request or status line, header lines ending in an empty
+//! line, all with CRLF line ends, then a body: as long as
+//! `Content-Length` says, in chunks for `Transfer-Encoding: chunked`,
+//! which are joined, or for a response with neither, up to the first NUL.
+//! A body cut short by the end of the readable memory is kept as far as
+//! it goes and marked truncated.
+
+use serde::Serialize;
+
+/// Request methods recognized at the start of a message
+const METHODS: &[&str] = &[
+    "GET", "POST", "PUT", "DELETE", "PATCH", "HEAD", "OPTIONS", "CONNECT",
+];
+
+/// Longest request or status line and header block followed
+pub const MAX_HEAD_LENGTH: usize = 64 << 10;
+
+/// Body bytes kept in the report
+pub const MAX_BODY_BYTES: usize = 64 << 10;
+
+/// An HTTP request or response
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HttpMessage {
+    /// Method and target of a request
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    /// Status code and reason phrase of a response
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// `HTTP/1.0` or `HTTP/1.1`
+    pub version: String,
+    /// Names and values, in order
+    pub headers: Vec<(String, String)>,
+    /// The body, chunks joined, its first [`MAX_BODY_BYTES`] bytes with
+    /// anything non-printable escaped
+    pub body: String,
+    pub body_length: usize,
+    pub chunked: bool,
+    /// Whether the body ended before it should have
+    pub truncated: bool,
+}
+
+/// How far a message starting at some byte got
+#[derive(Debug, PartialEq, Eq)]
+pub enum Parsed {
+    /// A message of this many bytes
+    Message(usize, Box<HttpMessage>),
+    Invalid,
+    /// It goes on past the end of the bytes
+    Incomplete,
+}
+
+/// Whether a message can start at `data[0]`
+pub fn starts(data: &[u8]) -> bool {
+    data.starts_with(b"HTTP/1.")
+        || METHODS.iter().any(|method| {
+            data.starts_with(method.as_bytes()) && data.get(method.len()) == Some(&b' ')
+        })
+}
+
+fn is_token(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte)
+}
+
+/// The line at `from`, without its CRLF, and where the next starts: `Err`
+/// with [`Parsed::Incomplete`] if it doesn't end in `data`, or
+/// [`Parsed::Invalid`] if it holds control bytes or runs too long
+fn line(data: &[u8], from: usize) -> Result<(&[u8], usize), Parsed> {
+    let rest = &data[from.min(data.len())..];
+    for (i, &byte) in rest.iter().enumerate().take(MAX_HEAD_LENGTH) {
+        match byte {
+            b'\r' if rest.get(i + 1) == Some(&b'\n') => return Ok((&rest[..i], from + i + 2)),
+            b'\r' if i + 1 == rest.len() => return Err(Parsed::Incomplete),
+            b'\t' => {}
+            0..=0x1f | 0x7f => return Err(Parsed::Invalid),
+            _ => {}
+        }
+    }
+    if rest.len() < MAX_HEAD_LENGTH {
+        Err(Parsed::Incomplete)
+    } else {
+        Err(Parsed::Invalid)
+    }
+}
+
+fn text(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+/// Parse the message at `data[0]`, reading no more than `max_length`
+/// bytes. Unless `last`, a message running past the end of `data` is
+/// [`Parsed::Incomplete`]; if `last`, it is cut short there.
+pub fn parse(data: &[u8], max_length: usize, last: bool) -> Parsed {
+    let data = &data[..data.len().min(max_length)];
+    let last = last || data.len() == max_length;
+    match parse_message(data, last) {
+        Ok(parsed) => parsed,
+        Err(Parsed::Incomplete) if !last => Parsed::Incomplete,
+        Err(_) => Parsed::Invalid,
+    }
+}
+
+fn parse_message(data: &[u8], last: bool) -> Result<Parsed, Parsed> {
+    let (start, mut next) = line(data, 0)?;
+    let parts: Vec<&[u8]> = start.splitn(3, |&b| b == b' ').collect();
+    let is_version = |part: &[u8]| part == b"HTTP/1.0" || part == b"HTTP/1.1";
+    let mut message = HttpMessage {
+        method: None,
+        target: None,
+        status: None,
+        reason: None,
+        version: String::new(),
+        headers: Vec::new(),
+        body: String::new(),
+        body_length: 0,
+        chunked: false,
+        truncated: false,
+    };
+    match parts[..] {
+        [version, code, ..] if is_version(version) => {
+            let status = std::str::from_utf8(code).ok().and_then(|code| code.parse().ok());
+            if code.len() != 3 || !status.is_some_and(|status| (100..600).contains(&status)) {
+                return Err(Parsed::Invalid);
+            }
+            message.version = text(version);
+            message.status = status;
+            message.reason = parts.get(2).map(|reason| text(reason));
+        }
+        [method, target, version] if is_version(version) && !target.is_empty() => {
+            if target.contains(&b' ') {
+                return Err(Parsed::Invalid);
+            }
+            message.method = Some(text(method));
+            message.target = Some(text(target));
+            message.version = text(version);
+        }
+        _ => return Err(Parsed::Invalid),
+    }
+
+    loop {
+        let (header, after) = line(data, next)?;
+        next = after;
+        if header.is_empty() {
+            break;
+        }
+        let colon = header.iter().position(|&b| b == b':').ok_or(Parsed::Invalid)?;
+        let (name, value) = (&header[..colon], &header[colon + 1..]);
+        if name.is_empty() || !name.iter().all(|&b| is_token(b)) {
+            return Err(Parsed::Invalid);
+        }
+        message.headers.push((text(name), text(value.trim_ascii())));
+    }
+
+    let header = |wanted: &str| {
+        message.headers.iter().find(|(name, _)| name.eq_ignore_ascii_case(wanted)).map(|(_, v)| v)
+    };
+    let chunked = header("transfer-encoding")
+        .is_some_and(|v| v.to_ascii_lowercase().contains("chunked"));
+    let length = match header("content-length") {
+        Some(length) => Some(length.parse::<usize>().map_err(|_| Parsed::Invalid)?),
+        None => None,
+    };
+    let bodiless = message.method.is_some() && length.is_none() && !chunked
+        || message.status.is_some_and(|status| status < 200 || status == 204 || status == 304);
+
+    let rest = &data[next..];
+    let (body, consumed, truncated) = if bodiless {
+        (Vec::new(), 0, false)
+    } else if chunked {
+        join_chunks(rest, last)?
+    } else if let Some(length) = length {
+        if rest.len() < length && !last {
+            return Err(Parsed::Incomplete);
+        }
+        let taken = length.min(rest.len());
+        (rest[..taken].to_vec(), taken, taken < length)
+    } else {
+        // A response read until the connection closed
+        match rest.iter().position(|&b| b == 0) {
+            Some(end) => (rest[..end].to_vec(), end, false),
+            None if last => (rest.to_vec(), rest.len(), false),
+            None => return Err(Parsed::Incomplete),
+        }
+    };
+
+    message.body_length = body.len();
+    message.body = body[..body.len().min(MAX_BODY_BYTES)].escape_ascii().to_string();
+    message.chunked = chunked;
+    message.truncated = truncated;
+    Ok(Parsed::Message(next + consumed, Box::new(message)))
+}
+
+/// The joined chunks of a chunked body, the bytes they took up, and
+/// whether they were cut short
+fn join_chunks(data: &[u8], last: bool) -> Result<(Vec<u8>, usize, bool), Parsed> {
+    let mut body = Vec::new();
+    let mut next = 0;
+    loop {
+        let (size_line, after) = match line(data, next) {
+            Ok(found) => found,
+            Err(Parsed::Incomplete) if last => return Ok((body, data.len(), true)),
+            Err(e) => return Err(e),
+        };
+        let digits = size_line.split(|&b| b == b';').next().unwrap_or_default();
+        let size = std::str::from_utf8(digits)
+            .ok()
+            .and_then(|digits| usize::from_str_radix(digits.trim(), 16).ok())
+            .ok_or(Parsed::Invalid)?;
+        next = after;
+        if size == 0 {
+            // Trailer fields, up to an empty line
+            loop {
+                match line(data, next) {
+                    Ok((trailer, after)) => {
+                        next = after;
+                        if trailer.is_empty() {
+                            return Ok((body, next, false));
+                        }
+                    }
+                    Err(Parsed::Incomplete) if last => return Ok((body, data.len(), false)),
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        let end = next.saturating_add(size);
+        if end + 2 > data.len() {
+            if !last {
+                return Err(Parsed::Incomplete);
+            }
+            body.extend_from_slice(&data[next..end.min(data.len())]);
+            return Ok((body, data.len(), true));
+        }
+        if &data[end..end + 2] != b"\r\n" {
+            return Err(Parsed::Invalid);
+        }
+        body.extend_from_slice(&data[next..end]);
+        next = end + 2;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(data: &[u8], last: bool) -> (usize, HttpMessage) {
+        match parse(data, 1 << 20, last) {
+            Parsed::Message(length, message) => (length, *message),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_requests_and_responses() {
+        let request = b"POST /v1/login HTTP/1.1\r\nHost: api.example.com\r\n\
+            Content-Type: application/json\r\nContent-Length: 17\r\n\r\n{\"user\":\"alice\"}\nGET";
+        let (length, parsed) = message(request, false);
+        assert_eq!(length, request.len() - 3);
+        assert_eq!(parsed.method.as_deref(), Some("POST"));
+        assert_eq!(parsed.target.as_deref(), Some("/v1/login"));
+        assert_eq!(parsed.headers[0], ("Host".to_string(), "api.example.com".to_string()));
+        assert_eq!(parsed.body, "{\\\"user\\\":\\\"alice\\\"}\\n");
+
+        let response = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+            5\r\nhello\r\n7;ext=1\r\n, world\r\n0\r\n\r\n\x00\x00";
+        let (length, parsed) = message(response, false);
+        assert_eq!(length, response.len() - 2);
+        assert_eq!((parsed.status, parsed.body.as_str()), (Some(200), "hello, world"));
+        assert!(parsed.chunked && !parsed.truncated);
+
+        // Cut short: waits for more bytes, unless there are none
+        let cut = b"HTTP/1.1 404 Not Found\r\nContent-Length: 100\r\n\r\nnot the whole";
+        assert_eq!(parse(cut, 1 << 20, false), Parsed::Incomplete);
+        let (_, parsed) = message(cut, true);
+        assert!(parsed.truncated && parsed.body == "not the whole");
+
+        assert_eq!(parse(b"GET /a b HTTP/1.1\r\n\r\n", 1 << 20, true), Parsed::Invalid);
+        assert_eq!(parse(b"HTTP/1.1 99 Odd\r\n\r\n", 1 << 20, true), Parsed::Invalid);
+        assert_eq!(parse(b"GET / HTTP/1.1\r\nBad Header\r\n\r\n", 1 << 20, true), Parsed::Invalid);
+    }
+}
@@ -0,0 +1,501 @@
+//! Carving of JPEG, PNG and WebP images out of process memory
+//!
+//! Avatars, QR codes, thumbnails and screenshots are held encoded in the
+//! buffers they were downloaded or read into, and in image caches. Each
+//! starts with its magic, and is read from there to its end by its
+//! structure, so an image counts only if it holds together whole:
+//!
+//! - PNG: the signature, an `IHDR` chunk, then chunks to `IEND`, the CRC of
+//!   every one matching
+//! - JPEG: `SOI`, marker segments with a frame header among them, then
+//!   the entropy-coded data of each scan to `EOI`
+//! - WebP: a RIFF header whose size covers chunks that add up to it, the
+//!   first of them a `VP8 `, `VP8L` or `VP8X` chunk giving the size of the
+//!   image
+//!
+//! Images inside one already carved, such as the thumbnail in a JPEG's
+//! EXIF data, are part of it and not carved apart, and an image seen
+//! before is written once.
+
+use crate::crc32::crc32;
+use crate::dump::DumpedFile;
+use crate::maps::MemoryRegion;
+use crate::reader::{self, MemoryReader};
+use crate::scan::{self, RegionVisitor, ScanControl, ScanSink, StopReason};
+use crate::search::{Hit, PatternScanner};
+use crate::sha256::Sha256;
+use regex::bytes::Regex;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Largest image carved
+const MAX_IMAGE_SIZE: usize = 32 << 20;
+
+/// Bytes of a JPEG read at first, doubled until its end is found
+const JPEG_READ_SIZE: usize = 64 << 10;
+
+/// Longest magic matched, that of WebP
+const MAX_MATCH_LENGTH: usize = 12;
+
+const PNG_SIGNATURE: &[u8; 8] = b"\x89PNG\r\n\x1a\n";
+
+/// The magic each format starts with
+fn candidates() -> &'static Regex {
+    static CANDIDATES: OnceLock<Regex> = OnceLock::new();
+    CANDIDATES.get_or_init(|| {
+        Regex::new(r"(?s-u)\x89PNG\r\n\x1a\n|\xff\xd8\xff|RIFF....WEBP")
+            .expect("image pattern is valid")
+    })
+}
+
+/// Kinds of image carved
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ImageFormat {
+    Jpeg,
+    Png,
+    Webp,
+}
+
+impl ImageFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::Png => "png",
+            ImageFormat::Webp => "webp",
+        }
+    }
+}
+
+/// An image found in memory and written to disk
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CarvedImage {
+    #[serde(flatten)]
+    pub file: DumpedFile,
+    pub format: ImageFormat,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// How far an image starting at some byte got
+#[derive(Debug, PartialEq, Eq)]
+enum Extent {
+    /// It ends after this many bytes, and has this width and height
+    End(usize, u32, u32),
+    /// More bytes are needed to tell
+    Open,
+    /// It isn't one
+    Invalid,
+}
+
+fn be16(data: &[u8], at: usize) -> Option<u32> {
+    data.get(at..at + 2).map(|b| u32::from(b[0]) << 8 | u32::from(b[1]))
+}
+
+fn le16(data: &[u8], at: usize) -> Option<u32> {
+    data.get(at..at + 2).map(|b| u32::from(b[1]) << 8 | u32::from(b[0]))
+}
+
+fn be32(data: &[u8], at: usize) -> Option<u32> {
+    data.get(at..at + 4).map(|b| u32::from_be_bytes(b.try_into().unwrap()))
+}
+
+fn le32(data: &[u8], at: usize) -> Option<u32> {
+    data.get(at..at + 4).map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+}
+
+/// The extent of the JPEG at the start of `data`
+fn jpeg_extent(data: &[u8]) -> Extent {
+    let mut size = None;
+    let mut pos = 2;
+    loop {
+        let Some(&[0xff, marker]) = data.get(pos..pos + 2) else {
+            return if pos + 2 > data.len() { Extent::Open } else { Extent::Invalid };
+        };
+        match marker {
+            // Fill bytes before a marker
+            0xff => {
+                pos += 1;
+                continue;
+            }
+            0xd8 | 0xd9 | 0x00 => return Extent::Invalid,
+            0x01 | 0xd0..=0xd7 => {
+                pos += 2;
+                continue;
+            }
+            _ => {}
+        }
+        let Some(length) = be16(data, pos + 2) else {
+            return Extent::Open;
+        };
+        if length < 2 {
+            return Extent::Invalid;
+        }
+        // A frame header, which every image has: precision, height, width
+        if matches!(marker, 0xc0..=0xcf) && !matches!(marker, 0xc4 | 0xc8 | 0xcc) {
+            let (Some(height), Some(width)) = (be16(data, pos + 5), be16(data, pos + 7)) else {
+                return Extent::Open;
+            };
+            size = Some((width, height));
+        }
+        pos += 2 + length as usize;
+        if marker != 0xda {
+            continue;
+        }
+
+        // Entropy-coded data, in which a 0xff is followed by a zero or a
+        // restart marker, up to the marker after the scan
+        loop {
+            let Some(offset) = data.get(pos..).and_then(|rest| rest.iter().position(|&b| b == 0xff))
+            else {
+                return Extent::Open;
+            };
+            pos += offset;
+            match data.get(pos + 1) {
+                None => return Extent::Open,
+                Some(0x00 | 0xd0..=0xd7) => pos += 2,
+                Some(0xff) => pos += 1,
+                Some(0xd9) => {
+                    return match size {
+                        Some((width, height)) if width > 0 && height > 0 => {
+                            Extent::End(pos + 2, width, height)
+                        }
+                        _ => Extent::Invalid,
+                    };
+                }
+                // The next scan of a progressive JPEG, or tables between
+                Some(_) => break,
+            }
+        }
+    }
+}
+
+/// The extent of the PNG at the start of `data`, its chunks walked and
+/// their CRCs checked
+fn png_extent(data: &[u8]) -> Extent {
+    let mut pos = PNG_SIGNATURE.len();
+    let (mut width, mut height) = (0, 0);
+    loop {
+        let (Some(length), Some(kind)) = (be32(data, pos), data.get(pos + 4..pos + 8)) else {
+            return Extent::Open;
+        };
+        let length = length as usize;
+        if !kind.iter().all(u8::is_ascii_alphabetic) || length > MAX_IMAGE_SIZE {
+            return Extent::Invalid;
+        }
+        let first = pos == PNG_SIGNATURE.len();
+        if first != (kind == b"IHDR") || (first && length != 13) {
+            return Extent::Invalid;
+        }
+        let Some(stored) = be32(data, pos + 8 + length) else {
+            return Extent::Open;
+        };
+        if crc32(&data[pos + 4..pos + 8 + length]) != stored {
+            return Extent::Invalid;
+        }
+        if first {
+            width = be32(data, pos + 8).unwrap_or(0);
+            height = be32(data, pos + 12).unwrap_or(0);
+        }
+        pos += 12 + length;
+        if kind == b"IEND" {
+            if width == 0 || height == 0 {
+                return Extent::Invalid;
+            }
+            return Extent::End(pos, width, height);
+        }
+    }
+}
+
+/// The size of the image in the first chunk of a WebP file, `data` being
+/// its contents
+fn webp_size(kind: &[u8], data: &[u8]) -> Option<(u32, u32)> {
+    let le24 = |at: usize| data.get(at..at + 3).map(|b| u32::from_le_bytes([b[0], b[1], b[2], 0]));
+    match kind {
+        b"VP8X" => Some((le24(4)? + 1, le24(7)? + 1)),
+        b"VP8L" => {
+            let header = le32(data, 1).filter(|_| data.first() == Some(&0x2f))?;
+            Some(((header & 0x3fff) + 1, (header >> 14 & 0x3fff) + 1))
+        }
+        b"VP8 " => {
+            if data.get(3..6)? != b"\x9d\x01\x2a" {
+                return None;
+            }
+            Some((le16(data, 6)? & 0x3fff, le16(data, 8)? & 0x3fff))
+        }
+        _ => None,
+    }
+}
+
+/// The extent of the WebP file at the start of `data`, whose chunks must
+/// fill its RIFF size exactly
+fn webp_extent(data: &[u8]) -> Extent {
+    let Some(riff) = le32(data, 4) else {
+        return Extent::Open;
+    };
+    let end = riff as usize + 8;
+    if end > MAX_IMAGE_SIZE || riff < 4 + 8 {
+        return Extent::Invalid;
+    }
+    if data.len() < end {
+        return Extent::Open;
+    }
+    let mut size = None;
+    let mut pos = 12;
+    while pos < end {
+        let (Some(kind), Some(length)) = (data.get(pos..pos + 4), le32(data, pos + 4)) else {
+            return Extent::Invalid;
+        };
+        let length = length as usize;
+        let Some(contents) = data.get(pos + 8..(pos + 8 + length).min(end)) else {
+            return Extent::Invalid;
+        };
+        if !kind.iter().all(|b| b.is_ascii_alphanumeric() || *b == b' ') {
+            return Extent::Invalid;
+        }
+        if pos == 12 {
+            size = webp_size(kind, contents);
+            if size.is_none() {
+                return Extent::Invalid;
+            }
+        }
+        pos += 8 + length + (length & 1);
+    }
+    match size {
+        Some((width, height)) if pos == end && width > 0 && height > 0 => {
+            Extent::End(end, width, height)
+        }
+        _ => Extent::Invalid,
+    }
+}
+
+/// Read the image at `address`, if there is one, with its format and size
+fn read_image(
+    reader: &mut dyn MemoryReader,
+    address: u64,
+) -> Option<(ImageFormat, Vec<u8>, u32, u32)> {
+    let mut head = [0u8; MAX_MATCH_LENGTH];
+    let read = reader::read_into(reader, address, &mut head).unwrap_or(0);
+    let (format, extent): (_, fn(&[u8]) -> Extent) = if head.starts_with(PNG_SIGNATURE) {
+        (ImageFormat::Png, png_extent)
+    } else if head.starts_with(b"\xff\xd8\xff") {
+        (ImageFormat::Jpeg, jpeg_extent)
+    } else if read == MAX_MATCH_LENGTH && &head[..4] == b"RIFF" && &head[8..] == b"WEBP" {
+        (ImageFormat::Webp, webp_extent)
+    } else {
+        return None;
+    };
+
+    let mut data = Vec::new();
+    let mut want = JPEG_READ_SIZE;
+    loop {
+        let mut more = vec![0u8; want - data.len()];
+        let read = reader::read_into(reader, address + data.len() as u64, &mut more).unwrap_or(0);
+        data.extend_from_slice(&more[..read]);
+        match extent(&data) {
+            Extent::End(length, width, height) => {
+                data.truncate(length);
+                return Some((format, data, width, height));
+            }
+            Extent::Open if read == more.len() && want < MAX_IMAGE_SIZE => {
+                want = (want * 2).min(MAX_IMAGE_SIZE);
+            }
+            _ => return None,
+        }
+    }
+}
+
+/// File an image found at `address` is written to
+pub fn image_file_name(address: u64, format: ImageFormat) -> String {
+    format!("{:08x}.{}", address, format.extension())
+}
+
+/// Search `regions` for JPEG, PNG and WebP images and write each distinct
+/// one to its own file in `dir`, delivering a [`CarvedImage`] for each.
+///
+/// Failing to write to `dir` is an error; files written before the
+/// failure are left in place.
+pub fn carve_images(
+    reader: &mut dyn MemoryReader,
+    regions: &[MemoryRegion],
+    dir: &Path,
+    sink: &mut dyn ScanSink<CarvedImage>,
+    control: &mut ScanControl,
+) -> io::Result<Option<StopReason>> {
+    let mut visitor = ImageVisitor {
+        dir,
+        candidates: PatternScanner::with_overlap(candidates().clone(), MAX_MATCH_LENGTH),
+        found: Vec::new(),
+        seen: HashSet::new(),
+        failed: None,
+    };
+    let stopped = scan::walk_regions(reader, regions, &mut visitor, sink, control);
+    match visitor.failed {
+        Some(e) => Err(e),
+        None => Ok(stopped),
+    }
+}
+
+struct ImageVisitor<'a> {
+    dir: &'a Path,
+    candidates: PatternScanner<Regex>,
+    /// The candidates found in the region being read
+    found: Vec<Hit>,
+    /// SHA-256 of the images written
+    seen: HashSet<String>,
+    /// The first write failure; nothing more is written after one
+    failed: Option<io::Error>,
+}
+
+impl RegionVisitor for ImageVisitor<'_> {
+    type Output = CarvedImage;
+
+    fn visit_chunk(&mut self, _region: &MemoryRegion, address: u64, chunk: &[u8]) {
+        self.candidates.feed(address, chunk, &mut self.found);
+    }
+
+    fn end_region(
+        &mut self,
+        _region: &MemoryRegion,
+        reader: &mut dyn MemoryReader,
+        sink: &mut dyn ScanSink<CarvedImage>,
+    ) {
+        self.candidates.finish(&mut self.found);
+        // End of the last image carved, so those inside it are left be
+        let mut until = 0;
+        for hit in std::mem::take(&mut self.found) {
+            if self.failed.is_some() {
+                return;
+            }
+            if hit.address < until {
+                continue;
+            }
+            let Some((format, data, width, height)) = read_image(reader, hit.address) else {
+                continue;
+            };
+            until = hit.address + data.len() as u64;
+            let mut hash = Sha256::new();
+            hash.update(&data);
+            let sha256 = hash.finish_hex();
+            if !self.seen.insert(sha256.clone()) {
+                continue;
+            }
+            let name = image_file_name(hit.address, format);
+            if let Err(e) = fs::write(self.dir.join(&name), &data) {
+                self.failed = Some(e);
+                return;
+            }
+            sink.result(CarvedImage {
+                file: DumpedFile {
+                    start: hit.address,
+                    file: name,
+                    size: data.len() as u64,
+                    sha256,
+                },
+                format,
+                width,
+                height,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps::parse_maps_line;
+    use crate::reader::ProcfsReader;
+    use crate::scan::Scan;
+
+    /// A 1x1 PNG
+    fn png() -> Vec<u8> {
+        let chunk = |kind: &[u8], data: &[u8]| {
+            let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+            chunk.extend_from_slice(kind);
+            chunk.extend_from_slice(data);
+            chunk.extend(crc32(&chunk[4..]).to_be_bytes());
+            chunk
+        };
+        let mut png = PNG_SIGNATURE.to_vec();
+        png.extend(chunk(b"IHDR", b"\0\0\0\x01\0\0\0\x01\x08\x00\x00\x00\x00"));
+        png.extend(chunk(b"IDAT", b"\x78\x9c\x63\x60\x00\x00\x00\x02\x00\x01"));
+        png.extend(chunk(b"IEND", b""));
+        png
+    }
+
+    /// A JPEG of `width` by `height`, its entropy-coded data holding a
+    /// stuffed byte and a restart marker
+    fn jpeg(width: u16, height: u16) -> Vec<u8> {
+        let mut jpeg = b"\xff\xd8\xff\xe0\x00\x10JFIF\0\x01\x01\0\0\x01\0\x01\0\0".to_vec();
+        jpeg.extend_from_slice(b"\xff\xc0\x00\x0b\x08");
+        jpeg.extend(height.to_be_bytes());
+        jpeg.extend(width.to_be_bytes());
+        jpeg.extend_from_slice(b"\x01\x01\x11\x00");
+        jpeg.extend_from_slice(b"\xff\xda\x00\x08\x01\x01\x00\x00\x3f\x00");
+        jpeg.extend_from_slice(b"\x12\xff\x00\x34\xff\xd0\x56\xff\xd9");
+        jpeg
+    }
+
+    #[test]
+    fn test_extents() {
+        let png = png();
+        assert_eq!(png_extent(&png), Extent::End(png.len(), 1, 1));
+        assert_eq!(png_extent(&png[..40]), Extent::Open);
+        let mut bad = png.clone();
+        bad[30] ^= 1;
+        assert_eq!(png_extent(&bad), Extent::Invalid);
+
+        let jpeg = jpeg(640, 480);
+        assert_eq!(jpeg_extent(&jpeg), Extent::End(jpeg.len(), 640, 480));
+        assert_eq!(jpeg_extent(&jpeg[..jpeg.len() - 1]), Extent::Open);
+
+        // A lossless WebP of 3x2
+        let webp = b"RIFF\x1a\x00\x00\x00WEBPVP8L\x0d\x00\x00\x00\x2f\x02\x40\x00\x00\
+            \0\0\0\0\0\0\0\0\0";
+        assert_eq!(webp_extent(webp), Extent::End(webp.len(), 3, 2));
+    }
+
+    #[test]
+    fn test_carves_images() {
+        let (png, jpeg) = (png(), jpeg(16, 8));
+        // A JPEG with another inside it, as a thumbnail would be
+        let mut outer = jpeg[..20].to_vec();
+        outer.extend_from_slice(b"\xff\xe1");
+        outer.extend(((jpeg.len() + 2) as u16).to_be_bytes());
+        outer.extend_from_slice(&jpeg);
+        outer.extend_from_slice(&jpeg[20..]);
+        let mut data = vec![0u8; 0x3000];
+        data[0x100..0x100 + png.len()].copy_from_slice(&png);
+        data[0x801..0x801 + outer.len()].copy_from_slice(&outer);
+        data[0x1ff0..0x1ff0 + jpeg.len()].copy_from_slice(&jpeg);
+        data[0x2800..0x2800 + png.len()].copy_from_slice(&png);
+        let start = data.as_ptr() as u64;
+        let end = start + data.len() as u64;
+        let region = parse_maps_line(&format!("{:x}-{:x} rw-p 00000000 00:00 0", start, end));
+
+        let pid = std::process::id() as i32;
+        let dir = std::env::temp_dir().join(format!("image-test-{}", pid));
+        fs::create_dir_all(&dir).unwrap();
+        let mut reader = ProcfsReader::open(pid).unwrap();
+        let mut scan = Scan::default();
+        let regions = [region.unwrap()];
+        let mut control = ScanControl::new().chunk_size(Some(0x1000));
+        let stopped = carve_images(&mut reader, &regions, &dir, &mut scan, &mut control);
+
+        assert_eq!(stopped.unwrap(), None);
+        let images: Vec<_> =
+            scan.results.iter().map(|image| (image.file.start - start, image.format)).collect();
+        assert_eq!(
+            images,
+            [(0x100, ImageFormat::Png), (0x801, ImageFormat::Jpeg), (0x1ff0, ImageFormat::Jpeg)]
+        );
+        assert_eq!((scan.results[2].width, scan.results[2].height), (16, 8));
+        assert_eq!(fs::read(dir.join(&scan.results[1].file.file)).unwrap(), outer);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
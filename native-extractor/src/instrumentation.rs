@@ -0,0 +1,249 @@
+//! Detection of instrumentation frameworks attached to a process
+//!
+//! Frida, the Xposed family and the Zygisk modules they ride on all leave
+//! traces a process can't easily hide from outside: the libraries they
+//! inject show up in the maps, the files and pipes they use among the open
+//! descriptors, and Frida's agent runs threads with fixed names. Frida's
+//! server also listens on a well-known port, visible in the process's
+//! network namespace. Each trace found is reported as an [`Artifact`] of
+//! the framework it belongs to.
+
+use crate::maps::MemoryRegion;
+use crate::threads;
+use serde::Serialize;
+use std::fs;
+use std::io;
+
+/// An instrumentation framework
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Framework {
+    Frida,
+    /// Xposed and EdXposed
+    Xposed,
+    LSPosed,
+    Substrate,
+    Riru,
+    Zygisk,
+}
+
+/// Where an artifact was found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ArtifactSource {
+    /// A mapping, by its path
+    Map,
+    /// An open file descriptor, by its target
+    Fd,
+    /// A thread, by its name
+    Thread,
+    /// A listening socket, by its port
+    Socket,
+}
+
+/// One trace of a framework
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Artifact {
+    pub framework: Framework,
+    pub source: ArtifactSource,
+    /// The path, thread name or port that matched
+    pub detail: String,
+    /// Start of the first mapping of the path, for [`ArtifactSource::Map`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<u64>,
+    /// Thread ID, for [`ArtifactSource::Thread`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tid: Option<i32>,
+}
+
+/// The frameworks found and the evidence for each
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Instrumentation {
+    /// Every framework with at least one artifact, without repeats
+    pub detected: Vec<Framework>,
+    pub artifacts: Vec<Artifact>,
+}
+
+/// Substrings of paths, case-insensitive, and the framework they betray
+const PATH_SIGNATURES: [(&str, Framework); 12] = [
+    ("frida", Framework::Frida),
+    ("linjector", Framework::Frida),
+    ("xposedbridge", Framework::Xposed),
+    ("libxposed", Framework::Xposed),
+    ("edxp", Framework::Xposed),
+    ("lspd", Framework::LSPosed),
+    ("lsposed", Framework::LSPosed),
+    ("substrate", Framework::Substrate),
+    ("libriru", Framework::Riru),
+    ("/riru", Framework::Riru),
+    ("zygisk", Framework::Zygisk),
+    ("gum-js", Framework::Frida),
+];
+
+/// Names of the threads Frida's agent starts
+const FRIDA_THREADS: [&str; 4] = ["gum-js-loop", "gmain", "gdbus", "pool-frida"];
+
+/// Ports frida-server listens on by default
+const FRIDA_PORTS: [u16; 2] = [27042, 27043];
+
+/// TCP state of a listening socket in /proc/net/tcp
+const TCP_LISTEN: &str = "0A";
+
+/// The framework a path or name betrays, if any
+pub fn match_signature(text: &str) -> Option<Framework> {
+    let text = text.to_ascii_lowercase();
+    PATH_SIGNATURES
+        .iter()
+        .find(|(signature, _)| text.contains(signature))
+        .map(|&(_, framework)| framework)
+}
+
+/// Look for instrumentation in `pid`, whose maps are `regions`.
+///
+/// Descriptors and threads that go away while they are listed are skipped.
+pub fn detect(pid: i32, regions: &[MemoryRegion]) -> io::Result<Instrumentation> {
+    let mut artifacts: Vec<Artifact> = Vec::new();
+    for region in regions {
+        let Some(path) = &region.path else {
+            continue;
+        };
+        let framework = match_signature(path);
+        let seen = artifacts.iter().any(|artifact| artifact.detail == *path);
+        if let (Some(framework), false) = (framework, seen) {
+            artifacts.push(Artifact {
+                framework,
+                source: ArtifactSource::Map,
+                detail: path.clone(),
+                address: Some(region.start),
+                tid: None,
+            });
+        }
+    }
+
+    let mut fds = Vec::new();
+    for entry in fs::read_dir(format!("/proc/{}/fd", pid))? {
+        if let Ok(target) = fs::read_link(entry?.path()) {
+            fds.push(target.to_string_lossy().into_owned());
+        }
+    }
+    fds.sort();
+    fds.dedup();
+    for target in fds {
+        if let Some(framework) = match_signature(&target) {
+            artifacts.push(Artifact {
+                framework,
+                source: ArtifactSource::Fd,
+                detail: target,
+                address: None,
+                tid: None,
+            });
+        }
+    }
+
+    for tid in threads::list_threads(pid)? {
+        let Ok(name) = fs::read_to_string(format!("/proc/{}/task/{}/comm", pid, tid)) else {
+            continue;
+        };
+        let name = name.trim_end().to_string();
+        let framework = if FRIDA_THREADS.contains(&name.as_str()) {
+            Some(Framework::Frida)
+        } else {
+            match_signature(&name)
+        };
+        if let Some(framework) = framework {
+            artifacts.push(Artifact {
+                framework,
+                source: ArtifactSource::Thread,
+                detail: name,
+                address: None,
+                tid: Some(tid),
+            });
+        }
+    }
+
+    for table in ["tcp", "tcp6"] {
+        // Missing without IPv6, or in a namespace without network
+        let Ok(sockets) = fs::read_to_string(format!("/proc/{}/net/{}", pid, table)) else {
+            continue;
+        };
+        for port in listening_ports(&sockets).filter(|port| FRIDA_PORTS.contains(port)) {
+            artifacts.push(Artifact {
+                framework: Framework::Frida,
+                source: ArtifactSource::Socket,
+                detail: port.to_string(),
+                address: None,
+                tid: None,
+            });
+        }
+    }
+
+    let mut detected: Vec<Framework> =
+        artifacts.iter().map(|artifact| artifact.framework).collect();
+    detected.sort();
+    detected.dedup();
+    Ok(Instrumentation {
+        detected,
+        artifacts,
+    })
+}
+
+/// Local ports of the listening sockets in a /proc/net/tcp table, whose
+/// lines read `sl local_address rem_address st ...` with the address as
+/// hex `IP:PORT`
+fn listening_ports(table: &str) -> impl Iterator<Item = u16> + '_ {
+    table.lines().skip(1).filter_map(|line| {
+        let mut fields = line.split_whitespace().skip(1);
+        let local = fields.next()?;
+        let state = fields.nth(1)?;
+        let port = local.rsplit_once(':')?.1;
+        (state == TCP_LISTEN).then(|| u16::from_str_radix(port, 16).ok()).flatten()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps::parse_maps_line;
+
+    #[test]
+    fn test_detects_frida_threads_and_files() {
+        let pid = std::process::id() as i32;
+        let path = std::env::temp_dir().join(format!("frida-agent-test-{}.so", pid));
+        let file = fs::File::create(&path).unwrap();
+        let line = "7f0000000000-7f0000001000 r-xp 00000000 00:00 0 /data/adb/lspd/liblspd.so";
+        let regions = [parse_maps_line(line).unwrap()];
+
+        let (ready, wait) = std::sync::mpsc::channel();
+        let (done, finish) = std::sync::mpsc::channel::<()>();
+        let thread = std::thread::Builder::new()
+            .name("gum-js-loop".to_string())
+            .spawn(move || {
+                ready.send(()).unwrap();
+                finish.recv().ok();
+            })
+            .unwrap();
+        wait.recv().unwrap();
+        let found = detect(pid, &regions).unwrap();
+        done.send(()).unwrap();
+        thread.join().unwrap();
+        drop(file);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(found.detected, [Framework::Frida, Framework::LSPosed]);
+        let sources: Vec<(Framework, ArtifactSource)> =
+            found.artifacts.iter().map(|artifact| (artifact.framework, artifact.source)).collect();
+        assert!(sources.contains(&(Framework::LSPosed, ArtifactSource::Map)));
+        assert!(sources.contains(&(Framework::Frida, ArtifactSource::Fd)));
+        assert!(sources.contains(&(Framework::Frida, ArtifactSource::Thread)));
+    }
+
+    #[test]
+    fn test_listening_ports() {
+        let table = "  sl  local_address rem_address   st tx_queue rx_queue\n\
+            0: 0100007F:69A2 00000000:0000 0A 00000000:00000000\n\
+            1: 0100007F:1F90 0100007F:C000 01 00000000:00000000\n";
+        assert_eq!(listening_ports(table).collect::<Vec<_>>(), [27042]);
+    }
+}
@@ -0,0 +1,535 @@
+//! Detection of executable memory not backed by the file it claims
+//!
+//! Injected code has to live somewhere executable. Every executable
+//! mapping of a process is checked: one with no backing file, or whose file
+//! has been deleted or no longer exists, is reported as it is, and for the
+//! rest the mapped bytes are compared with the file's, which catches code
+//! patched in memory such as inline hooks. Code is position-independent on
+//! Android, so a mapping of an unmodified file reads back byte for byte.
+//!
+//! The JIT code cache is legitimately anonymous and executable; it is still
+//! reported, marked as such, rather than hidden.
+//!
+//! [`verify_libraries`] goes further for the code mapped from files. An ELF
+//! file is laid out from disk the way the loader maps it (see
+//! [`FileImage`]), so the mapping is compared with what it should hold at
+//! each address, and the words dynamic relocations write to are masked out
+//! of both sides. Each mapping is hashed on both sides and its differing
+//! runs of bytes reported.
+
+use crate::filter;
+use crate::library::FileImage;
+use crate::maps::MemoryRegion;
+use crate::reader::MemoryReader;
+use crate::scan::{self, RegionVisitor, ScanControl, ScanSink, StopReason};
+use crate::sha256::Sha256;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::os::unix::fs::FileExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Pseudo-paths of code the kernel maps into every process
+const KERNEL_CODE: [&str; 4] = ["[vdso]", "[vectors]", "[vsyscall]", "[uprobes]"];
+
+/// Suffix the kernel appends to the path of a mapped file that was deleted
+const DELETED: &str = " (deleted)";
+
+/// Why executable code isn't backed by its file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum UnbackedReason {
+    /// No file: an anonymous mapping, memfd or ashmem
+    Anonymous,
+    /// The file was deleted after it was mapped
+    Deleted,
+    /// The path doesn't exist in the process's file system
+    Missing,
+    /// The mapped bytes differ from the file's
+    Modified,
+}
+
+/// An executable region flagged as possibly injected code
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnbackedCode {
+    pub region: MemoryRegion,
+    pub reason: UnbackedReason,
+    /// Whether the region is ART's JIT code cache
+    pub jit: bool,
+    /// For [`UnbackedReason::Modified`], the address of the first byte that
+    /// differs from the file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_difference: Option<u64>,
+    /// For [`UnbackedReason::Modified`], how many bytes differ
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modified_bytes: Option<u64>,
+}
+
+/// Whether `region` holds code this check applies to
+pub fn is_checked(region: &MemoryRegion) -> bool {
+    region.perms.execute
+        && !region.path.as_deref().is_some_and(|path| KERNEL_CODE.contains(&path))
+}
+
+/// A [`RegionVisitor`] over executable regions, delivering those not
+/// backed by their file. Paths are resolved under `root`, normally the
+/// target's `/proc/[pid]/root`.
+pub struct IntegrityVisitor {
+    root: PathBuf,
+    started: bool,
+    /// The file of the region being read
+    file: Option<File>,
+    finding: Option<UnbackedCode>,
+    buffer: Vec<u8>,
+    /// Files that couldn't be opened for a reason other than not existing
+    pub errors: Vec<String>,
+}
+
+impl IntegrityVisitor {
+    pub fn new(root: &Path) -> Self {
+        IntegrityVisitor {
+            root: root.to_path_buf(),
+            started: false,
+            file: None,
+            finding: None,
+            buffer: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// Decide what can be said of `region` before any of it is compared
+    fn start_region(&mut self, region: &MemoryRegion) {
+        self.started = true;
+        let path = region.path.as_deref().unwrap_or_default();
+        let jit = filter::jit_cache(region).is_some();
+        let anonymous = ["/memfd:", "/dev/ashmem"].iter().any(|prefix| path.starts_with(prefix));
+        let reason = if anonymous || !path.starts_with('/') {
+            Some(UnbackedReason::Anonymous)
+        } else if path.ends_with(DELETED) {
+            Some(UnbackedReason::Deleted)
+        } else {
+            match File::open(self.root.join(path.trim_start_matches('/'))) {
+                Ok(file) => {
+                    self.file = Some(file);
+                    None
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => Some(UnbackedReason::Missing),
+                Err(e) => {
+                    self.errors.push(format!("Failed to open {}: {}", path, e));
+                    None
+                }
+            }
+        };
+        self.finding = reason.map(|reason| UnbackedCode {
+            region: region.clone(),
+            reason,
+            jit,
+            first_difference: None,
+            modified_bytes: None,
+        });
+    }
+
+    /// Count the bytes of `chunk` that differ from the file
+    fn compare(&mut self, region: &MemoryRegion, address: u64, chunk: &[u8]) {
+        let Some(file) = &self.file else {
+            return;
+        };
+        self.buffer.resize(chunk.len(), 0);
+        let offset = region.offset + (address - region.start);
+        let read = read_full_at(file, &mut self.buffer, offset);
+        // Past the end of the file, the mapping is zero-filled
+        self.buffer[read..].fill(0);
+
+        let differing = chunk.iter().zip(&self.buffer).filter(|(a, b)| a != b).count() as u64;
+        if differing == 0 {
+            return;
+        }
+        let first = chunk.iter().zip(&self.buffer).position(|(a, b)| a != b).unwrap_or(0);
+        let finding = self.finding.get_or_insert_with(|| UnbackedCode {
+            region: region.clone(),
+            reason: UnbackedReason::Modified,
+            jit: false,
+            first_difference: Some(address + first as u64),
+            modified_bytes: Some(0),
+        });
+        *finding.modified_bytes.get_or_insert(0) += differing;
+    }
+}
+
+impl RegionVisitor for IntegrityVisitor {
+    type Output = UnbackedCode;
+
+    fn visit_chunk(&mut self, region: &MemoryRegion, address: u64, chunk: &[u8]) {
+        if address == region.start {
+            self.start_region(region);
+        }
+        self.compare(region, address, chunk);
+    }
+
+    fn end_region(
+        &mut self,
+        region: &MemoryRegion,
+        _reader: &mut dyn MemoryReader,
+        sink: &mut dyn ScanSink<UnbackedCode>,
+    ) {
+        // Execute-only code can't be compared, but can still lack a file
+        if !self.started {
+            self.start_region(region);
+        }
+        self.started = false;
+        self.file = None;
+        if let Some(finding) = self.finding.take() {
+            sink.result(finding);
+        }
+    }
+}
+
+/// Most differing ranges kept for a mapping
+const MAX_RANGES: usize = 64;
+
+/// One executable mapping of a file, compared with the file and hashed
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TextCheck {
+    pub path: String,
+    pub start: u64,
+    pub end: u64,
+    /// SHA-256 of the bytes read, and of the file's bytes for them, with
+    /// relocated words zeroed in both
+    pub memory_sha256: String,
+    pub file_sha256: String,
+    pub matches: bool,
+    /// Relocated words left out of the comparison
+    pub masked_words: usize,
+    pub modified_bytes: u64,
+    /// The runs of differing bytes, up to [`MAX_RANGES`] of them
+    pub modified: Vec<ModifiedRange>,
+}
+
+/// A run of bytes that differ from the file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ModifiedRange {
+    pub address: u64,
+    pub length: u64,
+}
+
+/// An ELF file as loaded, and the link-time addresses of its relocated words
+struct Layout {
+    image: FileImage,
+    slots: Vec<u64>,
+}
+
+/// What a mapping should hold
+enum Expected {
+    /// The ELF file whose header is mapped at `base`, laid out as loaded,
+    /// with the memory addresses of its relocated words
+    Elf {
+        layout: Arc<Layout>,
+        base: u64,
+        slots: Vec<u64>,
+    },
+    /// The file's bytes from the mapping's offset
+    Raw(Arc<File>),
+}
+
+/// Compare the executable mappings of files among `regions`, all the
+/// regions of a process, with the files under `root`, delivering a
+/// [`TextCheck`] for each mapping read. Files that can't be opened or laid
+/// out are noted in `errors`.
+pub fn verify_libraries(
+    reader: &mut dyn MemoryReader,
+    regions: &[MemoryRegion],
+    root: &Path,
+    sink: &mut dyn ScanSink<TextCheck>,
+    control: &mut ScanControl,
+    errors: &mut Vec<String>,
+) -> Option<StopReason> {
+    let mut files: HashMap<String, Option<Arc<File>>> = HashMap::new();
+    let mut layouts: HashMap<(String, u64), Option<Arc<Layout>>> = HashMap::new();
+    let mut checked = Vec::new();
+    let mut expected = Vec::new();
+
+    for (i, region) in regions.iter().enumerate() {
+        let Some(path) = region.path.as_deref() else {
+            continue;
+        };
+        if !is_checked(region) || !path.starts_with('/') || path.ends_with(DELETED) {
+            continue;
+        }
+        let file = files.entry(path.to_string()).or_insert_with(|| {
+            File::open(root.join(path.trim_start_matches('/')))
+                .map_err(|e| errors.push(format!("Failed to open {}: {}", path, e)))
+                .ok()
+                .map(Arc::new)
+        });
+        let Some(file) = file.clone() else {
+            continue;
+        };
+
+        // The library's ELF header is in the nearest mapping of the file at
+        // or before this one that starts with one
+        let header = regions[..=i]
+            .iter()
+            .rev()
+            .filter(|other| other.path.as_deref() == Some(path))
+            .find(|other| {
+                let mut magic = [0u8; 4];
+                file.read_exact_at(&mut magic, other.offset).is_ok() && &magic == b"\x7fELF"
+            });
+        let layout = header.and_then(|header| {
+            let key = (path.to_string(), header.offset);
+            let entry = layouts.entry(key).or_insert_with(|| {
+                let mut image = FileImage::read(&file, header.offset)
+                    .map_err(|e| errors.push(format!("Failed to read {}: {}", path, e)))
+                    .ok()
+                    .flatten()?;
+                let (slots, packed) = image.relocated_slots();
+                if packed {
+                    errors.push(format!("{}: packed relocations can't be masked", path));
+                }
+                Some(Arc::new(Layout { image, slots }))
+            });
+            entry.clone().map(|layout| (layout, header.start))
+        });
+        expected.push(match layout {
+            Some((layout, base)) => Expected::Elf {
+                slots: layout
+                    .slots
+                    .iter()
+                    .map(|slot| base + slot.saturating_sub(layout.image.min_vaddr))
+                    .collect(),
+                layout,
+                base,
+            },
+            None => Expected::Raw(file),
+        });
+        checked.push(region.clone());
+    }
+
+    let mut visitor = TextVisitor {
+        expected,
+        index: 0,
+        current: None,
+    };
+    scan::walk_regions(reader, &checked, &mut visitor, sink, control)
+}
+
+/// The comparison of the mapping being read
+struct Comparison {
+    memory_hash: Sha256,
+    file_hash: Sha256,
+    masked_words: usize,
+    modified_bytes: u64,
+    modified: Vec<ModifiedRange>,
+    memory: Vec<u8>,
+    file: Vec<u8>,
+}
+
+struct TextVisitor {
+    /// What each region walked should hold, in order
+    expected: Vec<Expected>,
+    index: usize,
+    current: Option<Comparison>,
+}
+
+impl RegionVisitor for TextVisitor {
+    type Output = TextCheck;
+
+    fn visit_chunk(&mut self, region: &MemoryRegion, address: u64, chunk: &[u8]) {
+        let comparison = self.current.get_or_insert_with(|| Comparison {
+            memory_hash: Sha256::new(),
+            file_hash: Sha256::new(),
+            masked_words: 0,
+            modified_bytes: 0,
+            modified: Vec::new(),
+            memory: Vec::new(),
+            file: Vec::new(),
+        });
+        comparison.memory.clear();
+        comparison.memory.extend_from_slice(chunk);
+        comparison.file.clear();
+        comparison.file.resize(chunk.len(), 0);
+
+        match &self.expected[self.index] {
+            Expected::Elf {
+                layout,
+                base,
+                slots,
+            } => {
+                let from = (address - base) as usize;
+                let word = layout.image.word_size();
+                if let Some(bytes) = layout.image.bytes.get(from..) {
+                    let len = bytes.len().min(chunk.len());
+                    comparison.file[..len].copy_from_slice(&bytes[..len]);
+                }
+                let end = address + chunk.len() as u64;
+                let first = slots.partition_point(|&slot| slot < address);
+                for &slot in slots[first..].iter().take_while(|&&slot| slot < end) {
+                    let at = (slot - address) as usize;
+                    let to = (at + word).min(chunk.len());
+                    comparison.memory[at..to].fill(0);
+                    comparison.file[at..to].fill(0);
+                    comparison.masked_words += 1;
+                }
+            }
+            Expected::Raw(file) => {
+                let offset = region.offset + (address - region.start);
+                read_full_at(file, &mut comparison.file, offset);
+            }
+        }
+
+        comparison.memory_hash.update(&comparison.memory);
+        comparison.file_hash.update(&comparison.file);
+        for (i, (a, b)) in comparison.memory.iter().zip(&comparison.file).enumerate() {
+            if a == b {
+                continue;
+            }
+            comparison.modified_bytes += 1;
+            let at = address + i as u64;
+            let ranges = &mut comparison.modified;
+            let extends = ranges.last().is_some_and(|last| last.address + last.length == at);
+            if extends {
+                ranges.last_mut().unwrap().length += 1;
+            } else if ranges.len() < MAX_RANGES {
+                ranges.push(ModifiedRange {
+                    address: at,
+                    length: 1,
+                });
+            }
+        }
+    }
+
+    fn end_region(
+        &mut self,
+        region: &MemoryRegion,
+        _reader: &mut dyn MemoryReader,
+        sink: &mut dyn ScanSink<TextCheck>,
+    ) {
+        self.index += 1;
+        let Some(comparison) = self.current.take() else {
+            return;
+        };
+        let memory_sha256 = comparison.memory_hash.finish_hex();
+        let file_sha256 = comparison.file_hash.finish_hex();
+        sink.result(TextCheck {
+            path: region.name().to_string(),
+            start: region.start,
+            end: region.end,
+            matches: memory_sha256 == file_sha256,
+            memory_sha256,
+            file_sha256,
+            masked_words: comparison.masked_words,
+            modified_bytes: comparison.modified_bytes,
+            modified: comparison.modified,
+        });
+    }
+}
+
+/// Read as much of `buf` as the file holds at `offset`
+fn read_full_at(file: &File, buf: &mut [u8], offset: u64) -> usize {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match file.read_at(&mut buf[filled..], offset + filled as u64) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(_) => break,
+        }
+    }
+    filled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps;
+    use crate::reader::ProcfsReader;
+    use crate::scan::Scan;
+
+    #[test]
+    fn test_flags_anonymous_and_patched_code() {
+        let pid = std::process::id() as i32;
+        let exe = std::fs::read_link("/proc/self/exe").unwrap();
+        let code = maps::read_maps(pid)
+            .unwrap()
+            .into_iter()
+            .find(|region| region.perms.execute && region.path.as_deref() == exe.to_str())
+            .unwrap();
+
+        // The executable matches its file
+        let mut reader = ProcfsReader::open(pid).unwrap();
+        let regions = [code.clone()];
+        let mut visitor = IntegrityVisitor::new(Path::new("/"));
+        let mut scan = Scan::default();
+        scan::walk_regions(&mut reader, &regions, &mut visitor, &mut scan, &mut ScanControl::new());
+        assert!(scan.results.is_empty(), "{:?}", scan.results);
+
+        // The same region checked against another file looks patched, and an
+        // anonymous one is reported as such
+        let anonymous = MemoryRegion {
+            path: Some("[anon:test]".to_string()),
+            ..code.clone()
+        };
+        let patched = MemoryRegion {
+            path: Some("/proc/self/cmdline".to_string()),
+            offset: 0,
+            ..code.clone()
+        };
+        let mut scan = Scan::default();
+        let regions = [anonymous, patched];
+        scan::walk_regions(&mut reader, &regions, &mut visitor, &mut scan, &mut ScanControl::new());
+        let reasons: Vec<UnbackedReason> = scan.results.iter().map(|code| code.reason).collect();
+        assert_eq!(reasons, [UnbackedReason::Anonymous, UnbackedReason::Modified]);
+        assert!(scan.results[1].modified_bytes.unwrap() > 0);
+    }
+
+    #[test]
+    fn test_verifies_mapped_files_and_finds_patches() {
+        let pid = std::process::id() as i32;
+        let path = std::env::temp_dir().join(format!("integrity-test-{}", pid));
+        std::fs::write(&path, vec![0x90u8; 8192]).unwrap();
+        let file = File::open(&path).unwrap();
+        let prot = libc::PROT_READ | libc::PROT_WRITE | libc::PROT_EXEC;
+        // SAFETY: a fresh private mapping of a file this test owns, unmapped below
+        let mapped = unsafe {
+            use std::os::fd::AsRawFd;
+            libc::mmap(std::ptr::null_mut(), 8192, prot, libc::MAP_PRIVATE, file.as_raw_fd(), 0)
+        };
+        assert_ne!(mapped, libc::MAP_FAILED);
+        // SAFETY: within the mapping made above
+        unsafe { *(mapped as *mut u8).add(5000) = 0xcc };
+
+        let exe = std::fs::read_link("/proc/self/exe").unwrap();
+        let checked = [exe.to_str().unwrap(), path.to_str().unwrap()];
+        let regions: Vec<MemoryRegion> = maps::read_maps(pid)
+            .unwrap()
+            .into_iter()
+            .filter(|region| region.path.as_deref().is_some_and(|path| checked.contains(&path)))
+            .collect();
+        let mut reader = ProcfsReader::open(pid).unwrap();
+        let mut scan = Scan::default();
+        let mut errors = Vec::new();
+        let stopped = verify_libraries(
+            &mut reader,
+            &regions,
+            Path::new("/"),
+            &mut scan,
+            &mut ScanControl::new(),
+            &mut errors,
+        );
+        // SAFETY: the mapping made above, no longer used
+        unsafe { libc::munmap(mapped, 8192) };
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!((stopped, errors), (None, Vec::<String>::new()));
+        let patched = scan.results.iter().find(|check| check.path == checked[1]).unwrap();
+        let address = mapped as u64 + 5000;
+        assert!(!patched.matches);
+        assert_eq!(patched.modified, [ModifiedRange { address, length: 1 }]);
+        let exe = scan.results.iter().find(|check| check.path == checked[0]).unwrap();
+        assert!(exe.matches, "{:?}", exe.modified);
+    }
+}
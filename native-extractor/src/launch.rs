@@ -0,0 +1,581 @@
+//! Processes of a package caught as they start
+//!
+//! Secrets an app only holds while starting up are gone by the time it can
+//! be found in a process list and attached to by hand. The kernel's proc
+//! connector multicasts an event over netlink for every fork, exec and
+//! rename, which a [`LaunchWatch`] listens to on a thread of its own. An app
+//! process is forked from a zygote and named after its package only once
+//! specialized, so each new process is rechecked every few milliseconds
+//! until it has a name, and a scan is started the moment it is the
+//! package's. Listening needs `CAP_NET_ADMIN`, so root.
+
+use crate::error::ExtractError;
+use crate::process;
+use crate::scan;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Most scans made of one launch
+pub const MAX_DELAYS: usize = 32;
+
+/// The proc connector's multicast group and its `cb_id`
+const CN_IDX_PROC: u32 = 1;
+const CN_VAL_PROC: u32 = 1;
+
+const PROC_CN_MCAST_LISTEN: u32 = 1;
+
+/// `what` of the events used; `PROC_EVENT_NONE` acknowledges a listen
+const PROC_EVENT_NONE: u32 = 0;
+const PROC_EVENT_FORK: u32 = 1;
+const PROC_EVENT_EXEC: u32 = 2;
+const PROC_EVENT_COMM: u32 = 0x200;
+
+/// Bytes of an `nlmsghdr` and a `cn_msg`, which precede each event
+const NLMSG_HEADER: usize = 16;
+const CN_MSG_HEADER: usize = 20;
+
+/// Bytes of a `proc_event` before its data
+const PROC_EVENT_HEADER: usize = 16;
+
+/// Longest name a thread can have, which Android fits a package name to
+const TASK_COMM_LEN: usize = 15;
+
+/// Names a zygote's children run under before they are given an app
+const UNSPECIALIZED: [&str; 3] = ["usap32", "usap64", "<pre-initialized>"];
+
+/// How long a new process may take to be named before it is ignored
+const NAMING_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How often unnamed processes are rechecked, and how often the listener
+/// wakes otherwise to see whether it was stopped
+const RECHECK_INTERVAL: Duration = Duration::from_millis(5);
+const IDLE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long the kernel is given to acknowledge the listen
+const ACK_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The `launch` object of the options `watchLaunches` takes
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct LaunchOptions {
+    /// The package whose processes are scanned, secondary processes
+    /// included
+    pub package: Option<String>,
+    /// When each scan of a launch starts, in milliseconds after the
+    /// process is named
+    pub delays_ms: Vec<u64>,
+}
+
+impl Default for LaunchOptions {
+    fn default() -> Self {
+        LaunchOptions {
+            package: None,
+            delays_ms: vec![0],
+        }
+    }
+}
+
+impl LaunchOptions {
+    /// Parse the `launch` object of the options JSON
+    pub fn from_json(json: &str) -> Result<Self, ExtractError> {
+        let options: LaunchOptions = scan::parse_object(json, "launch")?;
+        if !(1..=MAX_DELAYS).contains(&options.delays_ms.len()) {
+            return Err(ExtractError::InvalidArgument(format!(
+                "Invalid launch options: delaysMs must hold 1 to {} delays",
+                MAX_DELAYS
+            )));
+        }
+        Ok(options)
+    }
+}
+
+/// Scans a process just launched, on a thread of its own; it should stop
+/// between scans once the flag is set
+pub type Scan = Arc<dyn Fn(i32, &AtomicBool) -> Result<(), ExtractError> + Send + Sync>;
+
+/// How a launch watch has gone so far
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LaunchStatus {
+    /// Processes of the package seen starting, in the order they did
+    pub pids: Vec<i32>,
+    /// Launches whose scans failed
+    pub failures: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    /// Whether the watch stopped by itself, because the connector failed
+    pub ended: bool,
+}
+
+/// A registered launch watch, as listed
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LaunchInfo {
+    pub handle: i64,
+    pub package: String,
+    pub delays_ms: Vec<u64>,
+    #[serde(flatten)]
+    pub status: LaunchStatus,
+}
+
+/// A package watched for launches, until dropped
+pub struct LaunchWatch {
+    info: LaunchInfo,
+    status: Arc<Mutex<LaunchStatus>>,
+    /// Stops the listener, and the scans of launches between two scans
+    stopped: Arc<AtomicBool>,
+    listener: Option<JoinHandle<()>>,
+}
+
+impl LaunchWatch {
+    /// Listen to the proc connector and start the thread calling `scan`
+    /// for each process of the package `options` give as it starts.
+    ///
+    /// Fails if the connector can't be listened to, so a missing root or a
+    /// kernel built without it is reported rather than waited on forever.
+    /// Processes already running aren't scanned.
+    pub fn start(options: &LaunchOptions, scan: Scan) -> Result<Self, ExtractError> {
+        let package = match &options.package {
+            Some(package) if !package.is_empty() => package.clone(),
+            _ => return Err(ExtractError::InvalidArgument("launch package must be set".into())),
+        };
+        let socket = listen()?;
+
+        let status = Arc::new(Mutex::new(LaunchStatus::default()));
+        let stopped = Arc::new(AtomicBool::new(false));
+        let listener = {
+            let status = Arc::clone(&status);
+            let stopped = Arc::clone(&stopped);
+            let package = package.clone();
+            thread::spawn(move || {
+                let result = watch_launches(&socket, &package, &stopped, |pid| {
+                    status.lock().unwrap_or_else(|e| e.into_inner()).pids.push(pid);
+                    let status = Arc::clone(&status);
+                    let stopped = Arc::clone(&stopped);
+                    let scan = Arc::clone(&scan);
+                    thread::spawn(move || {
+                        if let Err(e) = scan(pid, &stopped) {
+                            let mut status = status.lock().unwrap_or_else(|e| e.into_inner());
+                            status.failures += 1;
+                            status.last_error = Some(format!("Scan of {} failed: {}", pid, e));
+                        }
+                    });
+                });
+                if let Err(e) = result {
+                    let mut status = status.lock().unwrap_or_else(|e| e.into_inner());
+                    status.last_error = Some(format!("Proc connector failed: {}", e));
+                    status.ended = true;
+                }
+            })
+        };
+        Ok(LaunchWatch {
+            info: LaunchInfo {
+                handle: 0,
+                package,
+                delays_ms: options.delays_ms.clone(),
+                status: LaunchStatus::default(),
+            },
+            status,
+            stopped,
+            listener: Some(listener),
+        })
+    }
+
+    /// The watch as it stands now
+    pub fn info(&self) -> LaunchInfo {
+        LaunchInfo {
+            status: self.status.lock().unwrap_or_else(|e| e.into_inner()).clone(),
+            ..self.info.clone()
+        }
+    }
+}
+
+impl Drop for LaunchWatch {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+        if let Some(listener) = self.listener.take() {
+            let _ = listener.join();
+        }
+    }
+}
+
+/// Open a netlink socket on the proc connector's group and ask for events
+fn listen() -> Result<OwnedFd, ExtractError> {
+    let failed = |context: &str| {
+        let e = io::Error::last_os_error();
+        match e.raw_os_error() {
+            Some(libc::EPROTONOSUPPORT) => ExtractError::UnsupportedKernel(format!(
+                "{}: the kernel has no proc connector: {}",
+                context, e
+            )),
+            _ => ExtractError::from_io(context, &e),
+        }
+    };
+
+    // SAFETY: plain syscalls on a socket this function owns; the address
+    // and message outlive the calls that read them
+    unsafe {
+        let fd = libc::socket(
+            libc::AF_NETLINK,
+            libc::SOCK_DGRAM | libc::SOCK_CLOEXEC,
+            libc::NETLINK_CONNECTOR,
+        );
+        if fd < 0 {
+            return Err(failed("Failed to open a netlink socket"));
+        }
+        let socket = OwnedFd::from_raw_fd(fd);
+
+        let mut address: libc::sockaddr_nl = std::mem::zeroed();
+        address.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        address.nl_groups = CN_IDX_PROC;
+        let bound = libc::bind(
+            fd,
+            &address as *const libc::sockaddr_nl as *const libc::sockaddr,
+            std::mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+        );
+        if bound < 0 {
+            return Err(failed("Failed to join the proc connector's group"));
+        }
+
+        let message = listen_message();
+        if libc::send(fd, message.as_ptr().cast(), message.len(), 0) < 0 {
+            return Err(failed("Failed to listen to the proc connector"));
+        }
+
+        // Turned down only where another listener hears it too, so this
+        // mostly times out when not allowed
+        let deadline = Instant::now() + ACK_TIMEOUT;
+        let mut buffer = vec![0u8; 8192];
+        while let Some(timeout) = deadline.checked_duration_since(Instant::now()) {
+            if !readable(&socket, timeout).map_err(|e| ExtractError::from_io("poll", &e))? {
+                break;
+            }
+            let read = libc::recv(fd, buffer.as_mut_ptr().cast(), buffer.len(), 0);
+            if read < 0 {
+                return Err(failed("Failed to read from the proc connector"));
+            }
+            let events = parse_events(&buffer[..read as usize]);
+            match events.into_iter().find_map(|event| match event {
+                Event::Ack(err) => Some(err),
+                _ => None,
+            }) {
+                Some(0) => break,
+                Some(err) => {
+                    let e = io::Error::from_raw_os_error(err);
+                    return Err(ExtractError::from_io("The proc connector refused to listen", &e));
+                }
+                None => {}
+            }
+        }
+        Ok(socket)
+    }
+}
+
+/// An `nlmsghdr`, `cn_msg` and `PROC_CN_MCAST_LISTEN`, asking for events
+fn listen_message() -> Vec<u8> {
+    let length = NLMSG_HEADER + CN_MSG_HEADER + 4;
+    let mut message = Vec::with_capacity(length);
+    message.extend_from_slice(&(length as u32).to_le_bytes());
+    message.extend_from_slice(&(libc::NLMSG_DONE as u16).to_le_bytes());
+    message.extend_from_slice(&0u16.to_le_bytes());
+    message.extend_from_slice(&0u32.to_le_bytes());
+    message.extend_from_slice(&std::process::id().to_le_bytes());
+    message.extend_from_slice(&CN_IDX_PROC.to_le_bytes());
+    message.extend_from_slice(&CN_VAL_PROC.to_le_bytes());
+    message.extend_from_slice(&0u32.to_le_bytes());
+    message.extend_from_slice(&0u32.to_le_bytes());
+    message.extend_from_slice(&4u16.to_le_bytes());
+    message.extend_from_slice(&0u16.to_le_bytes());
+    message.extend_from_slice(&PROC_CN_MCAST_LISTEN.to_le_bytes());
+    message
+}
+
+/// Wait up to `timeout` for the socket to have something to read
+fn readable(socket: &OwnedFd, timeout: Duration) -> io::Result<bool> {
+    let mut poll = libc::pollfd {
+        fd: socket.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    // SAFETY: `poll` is a valid pollfd for the duration of the call
+    let ready = unsafe { libc::poll(&mut poll, 1, timeout.as_millis().max(1) as libc::c_int) };
+    match ready {
+        n if n >= 0 => Ok(n > 0),
+        _ => {
+            let e = io::Error::last_os_error();
+            if e.kind() == io::ErrorKind::Interrupted {
+                Ok(false)
+            } else {
+                Err(e)
+            }
+        }
+    }
+}
+
+/// A proc connector event of a whole process; events of other threads are
+/// dropped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Event {
+    Fork { parent: i32, child: i32 },
+    Exec(i32),
+    Comm(i32),
+    /// The answer to a listen, with its errno
+    Ack(i32),
+}
+
+/// The events of a datagram from the proc connector
+fn parse_events(buffer: &[u8]) -> Vec<Event> {
+    let u32_at = |message: &[u8], offset: usize| -> Option<u32> {
+        Some(u32::from_le_bytes(message.get(offset..offset + 4)?.try_into().ok()?))
+    };
+    let mut events = Vec::new();
+    let mut offset = 0;
+    while let Some(length) = u32_at(buffer, offset) {
+        let length = length as usize;
+        let Some(message) = buffer.get(offset..offset + length).filter(|_| length >= NLMSG_HEADER)
+        else {
+            break;
+        };
+        offset += (length + 3) & !3;
+
+        let cn = NLMSG_HEADER;
+        if u32_at(message, cn) != Some(CN_IDX_PROC) || u32_at(message, cn + 4) != Some(CN_VAL_PROC)
+        {
+            continue;
+        }
+        let event = cn + CN_MSG_HEADER;
+        let data = event + PROC_EVENT_HEADER;
+        let word = |index: usize| u32_at(message, data + index * 4).map(|word| word as i32);
+        // The PID and thread group ID at `index`, when they are the same
+        let process = |index: usize| match (word(index), word(index + 1)) {
+            (Some(pid), Some(tgid)) if pid == tgid => Some(tgid),
+            _ => None,
+        };
+        let parsed = match u32_at(message, event) {
+            Some(PROC_EVENT_NONE) => word(0).map(Event::Ack),
+            Some(PROC_EVENT_FORK) => process(2)
+                .zip(word(1))
+                .map(|(child, parent)| Event::Fork { parent, child }),
+            Some(PROC_EVENT_EXEC) => process(0).map(Event::Exec),
+            Some(PROC_EVENT_COMM) => process(0).map(Event::Comm),
+            _ => None,
+        };
+        events.extend(parsed);
+    }
+    events
+}
+
+/// What a process's name says of it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Naming {
+    /// It is one of the package's
+    Package,
+    /// Its name is still the zygote's it was forked from
+    Unnamed,
+    /// It is another's, or gone
+    Other,
+}
+
+fn naming(pid: i32, package: &str) -> Naming {
+    let Ok(name) = process::process_name(pid) else {
+        return Naming::Other;
+    };
+    if process::belongs_to_package(&name, package) {
+        return Naming::Package;
+    }
+    // The thread is renamed before the command line is rewritten, to the
+    // end of the package name where it is too long
+    let comm = fs::read_to_string(format!("/proc/{}/comm", pid)).unwrap_or_default();
+    if comm.trim_end() == thread_name(package) {
+        return Naming::Package;
+    }
+    if process::is_zygote(&name) || UNSPECIALIZED.contains(&name.as_str()) {
+        Naming::Unnamed
+    } else {
+        Naming::Other
+    }
+}
+
+/// The name Android gives the main thread of a process named `name`: the
+/// last characters that fit, for a dotted name too long
+fn thread_name(name: &str) -> &str {
+    if name.len() <= TASK_COMM_LEN || name.contains('@') || !name.contains('.') {
+        return name.get(..TASK_COMM_LEN).unwrap_or(name);
+    }
+    name.get(name.len() - TASK_COMM_LEN..).unwrap_or(name)
+}
+
+/// Read events until `stopped` is set, calling `launched` with each process
+/// of `package` once named
+fn watch_launches(
+    socket: &OwnedFd,
+    package: &str,
+    stopped: &AtomicBool,
+    mut launched: impl FnMut(i32),
+) -> io::Result<()> {
+    let mut buffer = vec![0u8; 8192];
+    // New processes not yet named, and when each stops being waited for
+    let mut unnamed: HashMap<i32, Instant> = HashMap::new();
+    let mut seen: HashSet<i32> = HashSet::new();
+    while !stopped.load(Ordering::Relaxed) {
+        let interval = if unnamed.is_empty() { IDLE_INTERVAL } else { RECHECK_INTERVAL };
+        if readable(socket, interval)? {
+            // SAFETY: `buffer` is valid for writes of its length
+            let read = unsafe {
+                libc::recv(
+                    socket.as_raw_fd(),
+                    buffer.as_mut_ptr().cast(),
+                    buffer.len(),
+                    libc::MSG_DONTWAIT,
+                )
+            };
+            if read < 0 {
+                let e = io::Error::last_os_error();
+                match e.raw_os_error() {
+                    // Events came faster than they were read, and some are
+                    // lost; the processes still unnamed are rechecked below
+                    Some(libc::ENOBUFS) | Some(libc::EAGAIN) | Some(libc::EINTR) => {}
+                    _ => return Err(e),
+                }
+            }
+            let read = read.max(0) as usize;
+            let deadline = Instant::now() + NAMING_TIMEOUT;
+            for event in parse_events(&buffer[..read]) {
+                let pid = match event {
+                    Event::Fork { parent, child } => {
+                        let name = process::process_name(parent).unwrap_or_default();
+                        // Processes forked by the app, or its app zygote,
+                        // start named after it
+                        if !process::is_zygote(&name) && !name.starts_with(package) {
+                            continue;
+                        }
+                        child
+                    }
+                    Event::Exec(pid) | Event::Comm(pid) => pid,
+                    Event::Ack(_) => continue,
+                };
+                if !seen.contains(&pid) {
+                    unnamed.entry(pid).or_insert(deadline);
+                }
+            }
+        }
+
+        let now = Instant::now();
+        unnamed.retain(|&pid, &mut deadline| match naming(pid, package) {
+            Naming::Package => {
+                // A PID reused after the process it was exited
+                seen.retain(|&pid| Path::new(&format!("/proc/{}", pid)).exists());
+                seen.insert(pid);
+                launched(pid);
+                false
+            }
+            Naming::Unnamed => now < deadline,
+            Naming::Other => false,
+        });
+    }
+    Ok(())
+}
+
+type LaunchTable = Mutex<HashMap<i64, LaunchWatch>>;
+
+/// Handles are never reused, so a stale handle can't release a newer watch
+static NEXT_HANDLE: AtomicI64 = AtomicI64::new(1);
+
+fn launches() -> &'static LaunchTable {
+    static LAUNCHES: OnceLock<LaunchTable> = OnceLock::new();
+    LAUNCHES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Store a launch watch, returning its handle. Handles are never 0.
+pub fn register(mut watch: LaunchWatch) -> i64 {
+    let handle = NEXT_HANDLE.fetch_add(1, Ordering::Relaxed);
+    watch.info.handle = handle;
+    launches().lock().unwrap_or_else(|e| e.into_inner()).insert(handle, watch);
+    handle
+}
+
+/// Stop a launch watch; scans under way stop before their next scan.
+/// Returns false for an unknown handle.
+pub fn release(handle: i64) -> bool {
+    let watch = launches().lock().unwrap_or_else(|e| e.into_inner()).remove(&handle);
+    // Dropped outside the lock, as it waits for the listener thread
+    watch.is_some()
+}
+
+/// Every registered launch watch, including those that ended, in the order
+/// they were made
+pub fn list() -> Vec<LaunchInfo> {
+    let launches = launches().lock().unwrap_or_else(|e| e.into_inner());
+    let mut list: Vec<LaunchInfo> = launches.values().map(LaunchWatch::info).collect();
+    list.sort_unstable_by_key(|info| info.handle);
+    list
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A datagram holding one event of `what` with `data` words
+    fn message(what: u32, data: &[u32]) -> Vec<u8> {
+        let length = NLMSG_HEADER + CN_MSG_HEADER + PROC_EVENT_HEADER + data.len() * 4;
+        let mut message = listen_message();
+        message.truncate(NLMSG_HEADER + CN_MSG_HEADER);
+        message[..4].copy_from_slice(&(length as u32).to_le_bytes());
+        message.extend_from_slice(&what.to_le_bytes());
+        message.extend_from_slice(&[0; 12]);
+        message.extend(data.iter().flat_map(|word| word.to_le_bytes()));
+        message
+    }
+
+    #[test]
+    fn test_parse_events() {
+        let mut buffer = message(PROC_EVENT_FORK, &[100, 100, 200, 200]);
+        // A new thread, not a process
+        buffer.extend(message(PROC_EVENT_FORK, &[100, 100, 201, 100]));
+        buffer.extend(message(PROC_EVENT_COMM, &[200, 200, 0, 0, 0, 0]));
+        buffer.extend(message(PROC_EVENT_EXEC, &[300, 300]));
+        buffer.extend(message(PROC_EVENT_NONE, &[libc::EPERM as u32]));
+        buffer.extend(message(0x80000000, &[300, 300, 1, 0]));
+        assert_eq!(
+            parse_events(&buffer),
+            [
+                Event::Fork { parent: 100, child: 200 },
+                Event::Comm(200),
+                Event::Exec(300),
+                Event::Ack(libc::EPERM),
+            ]
+        );
+        // A truncated message ends the datagram
+        assert_eq!(parse_events(&buffer[..20]), []);
+    }
+
+    #[test]
+    fn test_thread_name() {
+        assert_eq!(thread_name("com.example"), "com.example");
+        assert_eq!(thread_name("com.example.wallet"), ".example.wallet");
+        assert_eq!(thread_name("com.example.wallet:sync"), "ple.wallet:sync");
+        assert_eq!(thread_name("system_server_extra"), "system_server_e");
+        assert_eq!(naming(std::process::id() as i32, "com.example.wallet"), Naming::Other);
+    }
+
+    #[test]
+    fn test_launch_options_from_json() {
+        let json = r#"{"launch": {"package": "com.example.wallet", "delaysMs": [0, 250]}}"#;
+        let options = LaunchOptions::from_json(json).unwrap();
+        assert_eq!(options.package.as_deref(), Some("com.example.wallet"));
+        assert_eq!(options.delays_ms, [0, 250]);
+        assert_eq!(LaunchOptions::from_json("").unwrap().delays_ms, [0]);
+        assert!(LaunchOptions::from_json(r#"{"launch": {"delaysMs": []}}"#).is_err());
+        let delays = vec![0; MAX_DELAYS + 1];
+        let json = format!(r#"{{"launch": {{"delaysMs": {:?}}}}}"#, delays);
+        assert!(LaunchOptions::from_json(&json).is_err());
+    }
+}
@@ -1,171 +1,6016 @@
+//! Native library for advanced text extraction from Android app memory
+//! Requires root access for full functionality
+//!
+//! This module provides low-level memory access capabilities for:
+//! - Reading process memory directly from /proc/[pid]/mem
+//! - Scanning memory regions for text patterns
+//! - Extracting strings from application heap
+//!
+//! WARNING: These operations require root access and should only be used
+//! in authorized security research, penetration testing, or educational contexts.
+
+use aho_corasick::AhoCorasick;
 use jni::JNIEnv;
-use jni::objects::{JClass, JString};
-use jni::sys::jstring;
-use std::ffi::CString;
-use std::fs;
-use std::io::Read;
+use jni::objects::{
+    JByteArray, JByteBuffer, JClass, JIntArray, JObject, JObjectArray, JString, JThrowable, JValue,
+};
+use jni::sys::{
+    jboolean, jbyteArray, jint, jintArray, jlong, jobjectArray, jstring, JNI_FALSE, JNI_TRUE,
+};
+use regex::bytes::Regex;
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::mem::ManuallyDrop;
+use std::os::fd::FromRawFd;
+use std::path::Path;
+#[cfg(feature = "yara")]
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+pub mod error;
+pub mod adler32;
+pub mod aes;
+pub mod art;
+pub mod base64;
+pub mod bitmap;
+pub mod bloom;
+pub mod card;
+pub mod carve;
+pub mod checkpoint;
+pub mod compressed;
+pub mod coredump;
+pub mod crc32;
+pub mod deobfuscate;
+pub mod der;
+pub mod dex;
+pub mod diff;
+#[cfg(feature = "capstone")]
+pub mod disasm;
+pub mod dump;
+pub mod elf;
+pub mod entropy;
+pub mod ffi;
+pub mod filter;
+pub mod freeze;
+pub mod hexdump;
+pub mod hold;
+pub mod hprof;
+pub mod image;
+pub mod http;
+pub mod instrumentation;
+pub mod integrity;
+pub mod keys;
+pub mod launch;
+pub mod library;
+pub mod maps;
+pub mod minidump;
+pub mod packer;
+pub mod pagemap;
+pub mod patch;
+pub mod pointers;
+pub mod process;
+pub mod protobuf;
+pub mod realm;
+pub mod reader;
+pub mod registers;
+pub mod report;
+pub mod scan;
+pub mod schedule;
+pub mod search;
+pub mod secrets;
+pub mod session;
+pub mod shmem;
+pub mod spill;
+pub mod sqlite;
+pub mod sha256;
+pub mod stream;
+pub mod strings;
+pub mod symbols;
+pub mod threads;
+pub mod throttle;
+pub mod tls;
+pub mod unwind;
+pub mod urls;
+pub mod value;
+pub mod watch;
+pub mod writer;
+pub mod x509;
+#[cfg(feature = "yara")]
+pub mod yara;
+pub mod zip;
+pub mod zlib;
+
+use error::ExtractError;
+use filter::{RegionFilter, RegionPreset};
+use bloom::UniqueSink;
+use checkpoint::Checkpoint;
+use coredump::StoppedThreads;
+use diff::ProcessSnapshot;
+use spill::SpillSink;
+use freeze::Freeze;
+use hold::{Hold, HoldOptions};
+use launch::{LaunchOptions, LaunchWatch};
+use maps::MemoryRegion;
+use minidump::MinidumpOptions;
+use pagemap::PageMap;
+use process::AppSelector;
+use reader::{MemoryReader, ReaderStrategy};
+use report::{BatchReport, OutputFormat, ProcessInfo, Rendered, Report};
+use scan::{ExtractedString, Location, Scan, ScanControl, ScanObserver, ScanOptions, ScanSink};
+use schedule::{Schedule, ScheduleOptions};
+use search::{Matcher, PatternScanner};
+use session::Session;
+use stream::NdjsonWriter;
+use strings::StringEncoding;
+use symbols::Symbolizer;
+use throttle::Throttle;
+use unwind::Unwinder;
+use watch::{Watch, WatchOptions};
+use writer::MemoryWriter;
+
+/// Read memory from a specific process ID
+/// Requires root access
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeReadProcessMemory(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let result = open_session(pid, ReaderStrategy::Auto)
+            .and_then(|mut session| read_process_memory(&mut session));
+        respond(env, OutputFormat::Text, result, memory_text)
+    })
+}
+
+/// Write every readable region of a process to its own file in
+/// `output_dir`, created if missing, along with a `manifest.json` holding
+/// the returned report: region metadata, and the size and SHA-256 of each
+/// file (see [`dump`])
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeDumpProcess(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+    output_dir: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let dump = |session: &mut Session, dir: &Path| {
+            dump_process(session, &RegionFilter::default(), dir)
+        };
+        respond_dump(env, pid, &output_dir, dump, dump_text)
+    })
+}
+
+/// Write the regions of ART's JIT code cache, code and data, to `output_dir`
+/// as [`dumpProcess`] would, for study of what the JIT compiled
+///
+/// [`dumpProcess`]: Java_com_textextractor_NativeMemoryExtractor_nativeDumpProcess
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeDumpJitCache(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+    output_dir: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let filter = RegionFilter {
+            preset: RegionPreset::JitCache,
+            ..RegionFilter::default()
+        };
+        let dump = |session: &mut Session, dir: &Path| dump_process(session, &filter, dir);
+        respond_dump(env, pid, &output_dir, dump, dump_text)
+    })
+}
+
+/// Find the DEX files in every readable region of a process and write each
+/// to its own file in `output_dir`, created if missing (see [`dex`])
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeDumpDexFiles(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+    output_dir: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| respond_dump(env, pid, &output_dir, dump_dex_files, dex_text))
+}
+
+/// Find the Realm files in every readable region of a process and write
+/// each to its own file in `output_dir`, created if missing (see
+/// [`realm`])
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeDumpRealmFiles(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+    output_dir: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| respond_dump(env, pid, &output_dir, dump_realm_files, realm_text))
+}
+
+/// Find the SQLite databases in every readable region of a process, whole
+/// or in its page cache, and write each to its own file in `output_dir`,
+/// created if missing (see [`sqlite`])
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeDumpSqliteDatabases(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+    output_dir: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        respond_dump(env, pid, &output_dir, dump_sqlite_databases, sqlite_text)
+    })
+}
+
+/// Find the X.509 certificates in every readable region of a process, DER
+/// or PEM, and write each distinct one to its own PEM file in
+/// `output_dir`, created if missing (see [`x509`])
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeDumpCertificates(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+    output_dir: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        respond_dump(env, pid, &output_dir, dump_certificates, certificates_text)
+    })
+}
+
+/// Find the JPEG, PNG and WebP images in every readable region of a
+/// process and write each distinct one to its own file in `output_dir`,
+/// created if missing (see [`image`])
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeDumpImages(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+    output_dir: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| respond_dump(env, pid, &output_dir, dump_images, images_text))
+}
+
+/// Find the RGBA bitmap pixel buffers in every readable region of a
+/// process and write each distinct one to its own PNG file in
+/// `output_dir`, created if missing (see [`bitmap`])
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeDumpBitmaps(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+    output_dir: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| respond_dump(env, pid, &output_dir, dump_bitmaps, bitmaps_text))
+}
+
+/// Find the ZIP archives, APKs and JARs among them, in every readable
+/// region of a process and write each distinct one to its own file in
+/// `output_dir`, created if missing (see [`zip`])
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeDumpArchives(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+    output_dir: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| respond_dump(env, pid, &output_dir, dump_archives, archives_text))
+}
+
+/// Rebuild the shared library `library`, a path or file name, from the
+/// memory of a process and write it to `output_path` as an ELF file (see
+/// [`library`])
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeDumpLibrary(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+    library: JString,
+    output_path: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let library = env
+            .get_string(&library)
+            .map(String::from)
+            .map_err(|e| ExtractError::InvalidArgument(format!("Invalid library: {}", e)));
+        let path = env
+            .get_string(&output_path)
+            .map(String::from)
+            .map_err(|e| ExtractError::InvalidArgument(format!("Invalid output path: {}", e)));
+        let result = library.and_then(|library| {
+            let path = path?;
+            let mut session = open_session(pid, ReaderStrategy::Auto)?;
+            dump_library(&mut session, &library, Path::new(&path))
+        });
+        respond(env, OutputFormat::Text, result, dump_text)
+    })
+}
+
+/// List the memfd and ashmem objects a process maps or holds open, with
+/// their descriptors, sizes and mappings (see [`shmem`])
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeListSharedMemory(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let result = open_session(pid, ReaderStrategy::Auto).and_then(|session| {
+            let mut report = Report::new(pid);
+            report.process = session_process_info(&session, &mut report.errors)?;
+            let objects = shmem::list_shared_memory(pid, session.all_regions())
+                .map_err(|e| ExtractError::from_io(format_args!("PID {}", pid), &e))?;
+            report.stats.results = objects.len();
+            report.shared_memory = Some(objects);
+            report.finish();
+            Ok(report)
+        });
+        respond(env, OutputFormat::Text, result, shared_memory_text)
+    })
+}
+
+/// Write the memfd and ashmem objects of a process whose name matches
+/// `name`, a wildcard pattern, to `output_dir`, created if missing. Objects
+/// still open are read whole through their descriptor, others through their
+/// mappings (see [`shmem`]).
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeDumpSharedMemory(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+    name: JString,
+    output_dir: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let name = env
+            .get_string(&name)
+            .map(String::from)
+            .map_err(|e| ExtractError::InvalidArgument(format!("Invalid name: {}", e)));
+        let dir = env
+            .get_string(&output_dir)
+            .map(String::from)
+            .map_err(|e| ExtractError::InvalidArgument(format!("Invalid output directory: {}", e)));
+        let result = name.and_then(|name| {
+            let dir = dir?;
+            let mut session = open_session(pid, ReaderStrategy::Auto)?;
+            dump_shared_memory(&mut session, &name, Path::new(&dir))
+        });
+        respond(env, OutputFormat::Text, result, dump_text)
+    })
+}
+
+/// Write an ELF core file of a process to `output_path`, loadable in gdb
+/// or lldb (see [`coredump`]). Every thread is held in ptrace-stop while the
+/// core is written, so it is a consistent snapshot.
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeDumpCore(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+    output_path: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let result = env
+            .get_string(&output_path)
+            .map(String::from)
+            .map_err(|e| ExtractError::InvalidArgument(format!("Invalid output path: {}", e)))
+            .and_then(|path| {
+                let mut session = open_session(pid, ReaderStrategy::Auto)?;
+                dump_core(&mut session, &path)
+            });
+        respond(env, OutputFormat::Text, result, core_text)
+    })
+}
+
+/// Read the general-purpose registers of every thread of a process,
+/// holding each in ptrace-stop while they are read (see [`registers`])
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeGetThreadRegisters(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let result = thread_registers(pid);
+        respond(env, OutputFormat::Text, result, registers_text)
+    })
+}
+
+/// Unwind the stack of every thread of a process, holding each in
+/// ptrace-stop meanwhile, with every frame's module and offset (see
+/// [`unwind`])
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeGetBacktraces(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let result = open_session(pid, ReaderStrategy::Auto)
+            .and_then(|mut session| backtraces(&mut session));
+        respond(env, OutputFormat::Text, result, backtraces_text)
+    })
+}
+
+/// Write the classes and objects of an app's ART heap to `output_path` as
+/// an HPROF heap dump (see [`art`] and [`hprof`]). The
+/// process is frozen while its heap is read.
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeDumpJavaHeap(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+    output_path: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let result = env
+            .get_string(&output_path)
+            .map(String::from)
+            .map_err(|e| ExtractError::InvalidArgument(format!("Invalid output path: {}", e)))
+            .and_then(|path| {
+                let mut session = open_session(pid, ReaderStrategy::Auto)?;
+                dump_java_heap(&mut session, Path::new(&path))
+            });
+        respond(env, OutputFormat::Text, result, java_heap_text)
+    })
+}
+
+/// Write a minidump of a process to `output_path` (see [`minidump`]), with
+/// every thread stopped while it is taken. `options` may select regions to
+/// save in full as well as the stacks.
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeDumpMinidump(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+    output_path: JString,
+    options: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = env
+            .get_string(&options)
+            .map_err(|e| ExtractError::InvalidArgument(format!("Invalid options string: {}", e)))
+            .and_then(|json| MinidumpOptions::from_json(&String::from(json)));
+        let result = env
+            .get_string(&output_path)
+            .map(String::from)
+            .map_err(|e| ExtractError::InvalidArgument(format!("Invalid output path: {}", e)))
+            .and_then(|path| {
+                let options = options?;
+                let mut session = open_session(pid, ReaderStrategy::Auto)?;
+                dump_minidump(&mut session, &path, &options)
+            });
+        respond(env, OutputFormat::Text, result, core_text)
+    })
+}
+
+/// Extract printable strings from process memory
+///
+/// `region_preset` limits the scan to a class of regions
+/// (0 = all, 1 = heap, 2 = stacks, 3 = anonymous mappings, 4 = JIT code
+/// cache)
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeExtractStrings(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+    min_length: i32,
+    region_preset: i32,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = preset_options(min_length, region_preset);
+        let result = options
+            .as_ref()
+            .map_err(ExtractError::clone)
+            .and_then(|options| {
+                let mut session = open_session(pid, options.reader_strategy)?;
+                extract_strings_from_process(&mut session, options, None)
+            });
+        let options = options.unwrap_or_default();
+
+        respond(env, OutputFormat::Text, result, |report| strings_text(report, &options))
+    })
+}
+
+/// Extract printable strings from process memory as a `String[]` holding
+/// one element per string, without locations or the process header
+///
+/// Throws (see [`error`]) and returns null if the scan fails
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeExtractStringArray(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+    min_length: i32,
+    region_preset: i32,
+) -> jobjectArray {
+    ffi::guard(&mut env, |env| {
+        let array = preset_options(min_length, region_preset)
+            .and_then(|options| {
+                let mut session = open_session(pid, options.reader_strategy)?;
+                extract_strings_from_process(&mut session, &options, None)
+            })
+            .and_then(|report| {
+                let strings = report.strings.unwrap_or_default();
+                new_string_array(env, strings.iter().map(|string| string.value.as_str()))
+            });
+
+        match array {
+            Ok(array) => array.into_raw(),
+            Err(e) => {
+                throw_error(env, &e);
+                std::ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Scan options for the `extractStrings` variants taking a minimum length
+/// and a [`RegionPreset`] code
+fn preset_options(min_length: i32, region_preset: i32) -> Result<ScanOptions, ExtractError> {
+    let preset = RegionPreset::from_code(region_preset).ok_or_else(|| {
+        ExtractError::InvalidArgument(format!("Unknown region preset: {}", region_preset))
+    })?;
+    if min_length < 1 {
+        let message = format!("minLength must be at least 1, not {}", min_length);
+        return Err(ExtractError::InvalidArgument(message));
+    }
+    let mut options = ScanOptions {
+        min_length: min_length as usize,
+        ..ScanOptions::default()
+    };
+    options.regions.preset = preset;
+    Ok(options)
+}
+
+/// Extract printable strings from process memory, configured by a JSON
+/// options object (see [`ScanOptions`])
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeExtractStringsWithOptions(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+    options: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, OutputFormat::Text);
+        let result = options
+            .as_ref()
+            .map_err(ExtractError::clone)
+            .and_then(|options| {
+                let mut session = open_session(pid, options.reader_strategy)?;
+                extract_strings_from_process(&mut session, options, None)
+            });
+        let options = options.unwrap_or_default();
+
+        respond(env, format, result, |report| strings_text(report, &options))
+    })
+}
+
+/// Extract strings like `extractStringsWithOptions`, reporting progress to
+/// `listener.onProgress(long bytesScanned, long totalBytes, String regionName)`
+/// about every [`PROGRESS_INTERVAL`] while the scan runs (on the calling
+/// thread), and once more when it finishes
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeExtractStringsWithProgress<
+    'local,
+>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    pid: i32,
+    options: JString<'local>,
+    listener: JObject<'local>,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, OutputFormat::Text);
+        let mut progress = ProgressListener::new(env, &listener);
+        let result = options.as_ref().map_err(ExtractError::clone).and_then(|options| {
+            let mut session = open_session(pid, options.reader_strategy)?;
+            extract_strings_from_process(&mut session, options, Some(&mut progress))
+        });
+        progress.finish();
+        let options = options.unwrap_or_default();
+
+        respond(env, format, result, |report| strings_text(report, &options))
+    })
+}
+
+/// `extractStringsWithProgress` on a session
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeSessionExtractStringsWithProgress<
+    'local,
+>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    handle: jlong,
+    options: JString<'local>,
+    listener: JObject<'local>,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, session_format(handle));
+        let mut progress = ProgressListener::new(env, &listener);
+        let result = with_session(handle, |session| {
+            let options = options.as_ref().map_err(ExtractError::clone)?;
+            extract_strings_from_process(session, options, Some(&mut progress))
+        });
+        progress.finish();
+        let options = options.unwrap_or_default();
+
+        respond(env, format, result, |report| strings_text(report, &options))
+    })
+}
+
+/// Extract strings like `extractStringsWithOptions` from every process of
+/// an app (see [`process::find_app_processes`]), tagging each result with
+/// the `pid` it came from
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeExtractStringsByPackage(
+    mut env: JNIEnv,
+    _class: JClass,
+    package_name: JString,
+    options: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, OutputFormat::Text);
+        let selector = env
+            .get_string(&package_name)
+            .map(|package| AppSelector::Package(package.into()))
+            .map_err(|e| ExtractError::InvalidArgument(format!("Invalid package name: {}", e)));
+        let result = selector.and_then(|selector| {
+            let options = options.as_ref().map_err(ExtractError::clone)?;
+            extract_strings_from_app(&selector, options)
+        });
+        let options = options.unwrap_or_default();
+
+        respond(env, format, result, |report| strings_text(report, &options))
+    })
+}
+
+/// `extractStringsByPackage` for every process running under `uid`, and
+/// their children
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeExtractStringsByUid(
+    mut env: JNIEnv,
+    _class: JClass,
+    uid: jint,
+    options: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, OutputFormat::Text);
+        let result = options.as_ref().map_err(ExtractError::clone).and_then(|options| {
+            extract_strings_from_app(&AppSelector::Uid(uid as u32), options)
+        });
+        let options = options.unwrap_or_default();
+
+        respond(env, format, result, |report| strings_text(report, &options))
+    })
+}
+
+/// Extract strings like `extractStringsWithOptions`, but only from the
+/// stacks of the process's threads, found through /proc/[pid]/task (see
+/// [`threads`]). The report lists each thread and its stack.
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeExtractThreadStacks(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+    options: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, OutputFormat::Text);
+        let result = options
+            .as_ref()
+            .map_err(ExtractError::clone)
+            .and_then(|options| {
+                let mut session = open_session(pid, options.reader_strategy)?;
+                extract_strings_from_stacks(&mut session, options)
+            });
+        let options = options.unwrap_or_default();
+
+        respond(env, format, result, |report| strings_text(report, &options))
+    })
+}
+
+/// Extract the `java.lang.String` objects of an app's ART heap rather than
+/// every printable run, see [`art`]. `options` apply as for
+/// `extractStringsWithOptions`, except `encodings`: each string is reported
+/// in the encoding it is stored in.
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeExtractJavaStrings(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+    options: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, OutputFormat::Text);
+        let result = options
+            .as_ref()
+            .map_err(ExtractError::clone)
+            .and_then(|options| {
+                let mut session = open_session(pid, options.reader_strategy)?;
+                extract_java_strings(&mut session, options)
+            });
+        let options = options.unwrap_or_default();
+
+        respond(env, format, result, |report| strings_text(report, &options))
+    })
+}
+
+/// `extractJavaStrings` on a session, which may be a dump
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeSessionExtractJavaStrings(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    options: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, session_format(handle));
+        let result = with_session(handle, |session| {
+            let options = options.as_ref().map_err(ExtractError::clone)?;
+            extract_java_strings(session, options)
+        });
+        let options = options.unwrap_or_default();
+
+        respond(env, format, result, |report| strings_text(report, &options))
+    })
+}
+
+/// Report the runs of high-entropy bytes among the regions `options`
+/// select, where keys and random tokens that don't look like text live (see
+/// [`entropy`]). The `entropy` option sets the window and threshold.
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeScanEntropy(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+    options: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, OutputFormat::Text);
+        let result = options
+            .as_ref()
+            .map_err(ExtractError::clone)
+            .and_then(|options| {
+                let mut session = open_session(pid, options.reader_strategy)?;
+                scan_entropy(&mut session, options)
+            });
+
+        respond(env, format, result, entropy_text)
+    })
+}
+
+/// `scanEntropy` on a session, which may be a dump
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeSessionScanEntropy(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    options: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, session_format(handle));
+        let result = with_session(handle, |session| {
+            let options = options.as_ref().map_err(ExtractError::clone)?;
+            scan_entropy(session, options)
+        });
+
+        respond(env, format, result, entropy_text)
+    })
+}
+
+/// Carve whole documents, JSON by default, out of the regions `options`
+/// select, where string extraction would break them up (see [`carve`])
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeCarveDocuments(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+    options: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, OutputFormat::Text);
+        let result = options
+            .as_ref()
+            .map_err(ExtractError::clone)
+            .and_then(|options| {
+                let mut session = open_session(pid, options.reader_strategy)?;
+                carve_documents(&mut session, options)
+            });
+
+        respond(env, format, result, documents_text)
+    })
+}
+
+/// `carveDocuments` on a session, which may be a dump
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeSessionCarveDocuments(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    options: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, session_format(handle));
+        let result = with_session(handle, |session| {
+            let options = options.as_ref().map_err(ExtractError::clone)?;
+            carve_documents(session, options)
+        });
+
+        respond(env, format, result, documents_text)
+    })
+}
+
+/// Find the protobuf messages in the regions `options` select and decode
+/// them without a schema (see [`protobuf`])
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeScanProtobuf(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+    options: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, OutputFormat::Text);
+        let result = options
+            .as_ref()
+            .map_err(ExtractError::clone)
+            .and_then(|options| {
+                let mut session = open_session(pid, options.reader_strategy)?;
+                scan_protobuf(&mut session, options)
+            });
+
+        respond(env, format, result, protobuf_text)
+    })
+}
+
+/// `scanProtobuf` on a session, which may be a dump
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeSessionScanProtobuf(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    options: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, session_format(handle));
+        let result = with_session(handle, |session| {
+            let options = options.as_ref().map_err(ExtractError::clone)?;
+            scan_protobuf(session, options)
+        });
+
+        respond(env, format, result, protobuf_text)
+    })
+}
+
+/// Extract the URLs in the strings of the regions `options` select, each
+/// distinct one once, and sum them up by host (see [`urls`])
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeExtractUrls(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+    options: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, OutputFormat::Text);
+        let result = options
+            .as_ref()
+            .map_err(ExtractError::clone)
+            .and_then(|options| {
+                let mut session = open_session(pid, options.reader_strategy)?;
+                extract_urls(&mut session, options)
+            });
+
+        respond(env, format, result, urls_text)
+    })
+}
+
+/// `extractUrls` on a session, which may be a dump
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeSessionExtractUrls(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    options: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, session_format(handle));
+        let result = with_session(handle, |session| {
+            let options = options.as_ref().map_err(ExtractError::clone)?;
+            extract_urls(session, options)
+        });
+
+        respond(env, format, result, urls_text)
+    })
+}
+
+/// Run YARA rules over the regions `options` select, reporting the rules
+/// that match in each region (see [`yara`]). Only built with the `yara`
+/// feature; without it, calling this throws `UnsatisfiedLinkError`.
+#[cfg(feature = "yara")]
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeScanYara(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+    options: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, OutputFormat::Text);
+        let result = options
+            .as_ref()
+            .map_err(ExtractError::clone)
+            .and_then(|options| {
+                let mut session = open_session(pid, options.reader_strategy)?;
+                scan_yara(&mut session, options)
+            });
+
+        respond(env, format, result, yara_text)
+    })
+}
+
+/// `scanYara` on a session, which may be a dump
+#[cfg(feature = "yara")]
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeSessionScanYara(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    options: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, session_format(handle));
+        let result = with_session(handle, |session| {
+            let options = options.as_ref().map_err(ExtractError::clone)?;
+            scan_yara(session, options)
+        });
+
+        respond(env, format, result, yara_text)
+    })
+}
+
+/// Disassemble the range the `disassemble` object of `options` gives,
+/// guessing where the functions in it begin and end (see [`disasm`]). Only
+/// built with the `capstone` feature; without it, calling this throws
+/// `UnsatisfiedLinkError`.
+#[cfg(feature = "capstone")]
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeDisassemble(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+    options: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, OutputFormat::Text);
+        let result = options
+            .as_ref()
+            .map_err(ExtractError::clone)
+            .and_then(|options| {
+                let mut session = open_session(pid, options.reader_strategy)?;
+                disassemble(&mut session, options)
+            });
+
+        respond(env, format, result, disassembly_text)
+    })
+}
+
+/// `disassemble` on a session, which may be a dump
+#[cfg(feature = "capstone")]
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeSessionDisassemble(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    options: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, session_format(handle));
+        let result = with_session(handle, |session| {
+            let options = options.as_ref().map_err(ExtractError::clone)?;
+            disassemble(session, options)
+        });
+
+        respond(env, format, result, disassembly_text)
+    })
+}
+
+/// Decode the long base64 runs among the regions `options` select and
+/// report the strings and secrets the decoded bytes hold (see [`base64`])
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeScanBase64(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+    options: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, OutputFormat::Text);
+        let result = options
+            .as_ref()
+            .map_err(ExtractError::clone)
+            .and_then(|options| {
+                let mut session = open_session(pid, options.reader_strategy)?;
+                scan_base64(&mut session, options)
+            });
+
+        respond(env, format, result, base64_text)
+    })
+}
+
+/// `scanBase64` on a session, which may be a dump
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeSessionScanBase64(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    options: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, session_format(handle));
+        let result = with_session(handle, |session| {
+            let options = options.as_ref().map_err(ExtractError::clone)?;
+            scan_base64(session, options)
+        });
+
+        respond(env, format, result, base64_text)
+    })
+}
+
+/// Inflate the zlib and gzip streams among the regions `options` select
+/// and report the strings and secrets they hold (see [`compressed`])
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeScanCompressed(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+    options: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, OutputFormat::Text);
+        let result = options
+            .as_ref()
+            .map_err(ExtractError::clone)
+            .and_then(|options| {
+                let mut session = open_session(pid, options.reader_strategy)?;
+                scan_compressed(&mut session, options)
+            });
+
+        respond(env, format, result, compressed_text)
+    })
+}
+
+/// `scanCompressed` on a session, which may be a dump
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeSessionScanCompressed(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    options: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, session_format(handle));
+        let result = with_session(handle, |session| {
+            let options = options.as_ref().map_err(ExtractError::clone)?;
+            scan_compressed(session, options)
+        });
+
+        respond(env, format, result, compressed_text)
+    })
+}
+
+/// Decode the strings hidden by single-byte XOR or rotation among the
+/// regions `options` select (see [`deobfuscate`])
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeDeobfuscateStrings(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+    options: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, OutputFormat::Text);
+        let result = options
+            .as_ref()
+            .map_err(ExtractError::clone)
+            .and_then(|options| {
+                let mut session = open_session(pid, options.reader_strategy)?;
+                deobfuscate_strings(&mut session, options)
+            });
+
+        respond(env, format, result, deobfuscated_text)
+    })
+}
+
+/// `deobfuscateStrings` on a session, which may be a dump
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeSessionDeobfuscateStrings(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    options: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, session_format(handle));
+        let result = with_session(handle, |session| {
+            let options = options.as_ref().map_err(ExtractError::clone)?;
+            deobfuscate_strings(session, options)
+        });
+
+        respond(env, format, result, deobfuscated_text)
+    })
+}
+
+/// Find the addresses among the regions `options` select that hold the
+/// value its `value` object describes (see [`value`])
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeScanValue(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+    options: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, OutputFormat::Text);
+        let result = options
+            .as_ref()
+            .map_err(ExtractError::clone)
+            .and_then(|options| {
+                let mut session = open_session(pid, options.reader_strategy)?;
+                scan_value(&mut session, options)
+            });
+
+        respond(env, format, result, values_text)
+    })
+}
+
+/// `scanValue` on a session, which may be a dump
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeSessionScanValue(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    options: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, session_format(handle));
+        let result = with_session(handle, |session| {
+            let options = options.as_ref().map_err(ExtractError::clone)?;
+            scan_value(session, options)
+        });
+
+        respond(env, format, result, values_text)
+    })
+}
+
+/// Narrow the addresses the last `sessionScanValue` on a session found, or
+/// the values its snapshot saved, by what they hold now (see [`value`])
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeSessionRefineValues(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    options: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, session_format(handle));
+        let result = with_session(handle, |session| {
+            let options = options.as_ref().map_err(ExtractError::clone)?;
+            refine_values(session, options)
+        });
+
+        respond(env, format, result, values_text)
+    })
+}
+
+/// Save the regions `options` select in a session, for `sessionRefineValues`
+/// to compare with when the value searched for isn't known
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeSessionSnapshotValues(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    options: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, session_format(handle));
+        let result = with_session(handle, |session| {
+            let options = options.as_ref().map_err(ExtractError::clone)?;
+            snapshot_values(session, options)
+        });
+
+        respond(env, format, result, value_snapshot_text)
+    })
+}
+
+/// Snapshot a session's mappings and the strings `options` extract, for
+/// `sessionDiffSnapshot` to compare with (see [`diff`])
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeSessionTakeSnapshot(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    options: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, session_format(handle));
+        let result = with_session(handle, |session| {
+            let options = options.as_ref().map_err(ExtractError::clone)?;
+            take_snapshot(session, options)
+        });
+
+        respond(env, format, result, snapshot_text)
+    })
+}
+
+/// Snapshot a session again and report what changed since its last
+/// snapshot, which the new one replaces
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeSessionDiffSnapshot(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    options: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, session_format(handle));
+        let result = with_session(handle, |session| {
+            let options = options.as_ref().map_err(ExtractError::clone)?;
+            diff_snapshot(session, options)
+        });
+
+        respond(env, format, result, diff_text)
+    })
+}
+
+/// Index the pointers among the regions `options` select in a session, for
+/// `sessionFindPointerPaths` to search (see [`pointers`])
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeSessionBuildPointerMap(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    options: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, session_format(handle));
+        let result = with_session(handle, |session| {
+            let options = options.as_ref().map_err(ExtractError::clone)?;
+            build_pointer_map(session, options)
+        });
+
+        respond(env, format, result, pointer_map_text)
+    })
+}
+
+/// Find the pointer paths from modules to the `target` of the `pointers`
+/// object of `options`, in the session's pointer map
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeSessionFindPointerPaths(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    options: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, session_format(handle));
+        let result = with_session(handle, |session| {
+            let options = options.as_ref().map_err(ExtractError::clone)?;
+            find_pointer_paths(session, options)
+        });
+
+        respond(env, format, result, pointer_paths_text)
+    })
+}
+
+/// Follow the pointer chain the `pointers` object of `options` gives, from
+/// its `module` by its `offsets`
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeResolvePointerChain(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+    options: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, OutputFormat::Text);
+        let result = options
+            .as_ref()
+            .map_err(ExtractError::clone)
+            .and_then(|options| {
+                let mut session = open_session(pid, options.reader_strategy)?;
+                resolve_pointer_chain(&mut session, options)
+            });
+
+        respond(env, format, result, pointer_chain_text)
+    })
+}
+
+/// `resolvePointerChain` on a session, which may be a dump
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeSessionResolvePointerChain(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    options: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, session_format(handle));
+        let result = with_session(handle, |session| {
+            let options = options.as_ref().map_err(ExtractError::clone)?;
+            resolve_pointer_chain(session, options)
+        });
+
+        respond(env, format, result, pointer_chain_text)
+    })
+}
+
+/// Run the built-in secret detectors over every readable region of a
+/// process, returning typed findings with their rule and severity (see
+/// [`secrets`])
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeScanSecrets(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let result = open_session(pid, ReaderStrategy::Auto)
+            .and_then(|mut session| scan_secrets(&mut session, &ScanOptions::default()));
+        respond(env, OutputFormat::Text, result, secrets_text)
+    })
+}
+
+/// `scanSecrets` configured by a JSON options object, whose `secrets`
+/// object picks the rules and the least severity reported
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeScanSecretsWithOptions(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+    options: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, OutputFormat::Text);
+        let result = options
+            .as_ref()
+            .map_err(ExtractError::clone)
+            .and_then(|options| {
+                let mut session = open_session(pid, options.reader_strategy)?;
+                scan_secrets(&mut session, options)
+            });
+
+        respond(env, format, result, secrets_text)
+    })
+}
+
+/// `scanSecretsWithOptions` on a session, which may be a dump
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeSessionScanSecrets(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    options: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, session_format(handle));
+        let result = with_session(handle, |session| {
+            let options = options.as_ref().map_err(ExtractError::clone)?;
+            scan_secrets(session, options)
+        });
+
+        respond(env, format, result, secrets_text)
+    })
+}
+
+/// Find the PKCS#1, SEC1 and PKCS#8 private keys in the regions the
+/// options select, as DER or PEM, and write them out if the options give a
+/// directory (see [`keys`])
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeScanPrivateKeys(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+    options: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, OutputFormat::Text);
+        let result = options
+            .as_ref()
+            .map_err(ExtractError::clone)
+            .and_then(|options| {
+                let mut session = open_session(pid, options.reader_strategy)?;
+                scan_private_keys(&mut session, options)
+            });
+
+        respond(env, format, result, private_keys_text)
+    })
+}
+
+/// `scanPrivateKeys` on a session, which may be a dump
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeSessionScanPrivateKeys(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    options: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, session_format(handle));
+        let result = with_session(handle, |session| {
+            let options = options.as_ref().map_err(ExtractError::clone)?;
+            scan_private_keys(session, options)
+        });
+
+        respond(env, format, result, private_keys_text)
+    })
+}
+
+/// Recover the AES keys whose expanded key schedules are in the regions
+/// the options select (see [`aes`])
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeScanAesKeys(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+    options: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, OutputFormat::Text);
+        let result = options
+            .as_ref()
+            .map_err(ExtractError::clone)
+            .and_then(|options| {
+                let mut session = open_session(pid, options.reader_strategy)?;
+                scan_aes_keys(&mut session, options)
+            });
+
+        respond(env, format, result, aes_keys_text)
+    })
+}
+
+/// `scanAesKeys` on a session, which may be a dump
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeSessionScanAesKeys(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    options: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, session_format(handle));
+        let result = with_session(handle, |session| {
+            let options = options.as_ref().map_err(ExtractError::clone)?;
+            scan_aes_keys(session, options)
+        });
+
+        respond(env, format, result, aes_keys_text)
+    })
+}
+
+/// Find the TLS secrets of the connections and sessions in the regions
+/// the options select, and append them to a key log if the options give
+/// one (see [`tls`])
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeScanTlsSecrets(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+    options: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, OutputFormat::Text);
+        let result = options
+            .as_ref()
+            .map_err(ExtractError::clone)
+            .and_then(|options| {
+                let mut session = open_session(pid, options.reader_strategy)?;
+                scan_tls_secrets(&mut session, options)
+            });
+
+        respond(env, format, result, tls_secrets_text)
+    })
+}
+
+/// `scanTlsSecrets` on a session, which may be a dump
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeSessionScanTlsSecrets(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    options: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, session_format(handle));
+        let result = with_session(handle, |session| {
+            let options = options.as_ref().map_err(ExtractError::clone)?;
+            scan_tls_secrets(session, options)
+        });
+
+        respond(env, format, result, tls_secrets_text)
+    })
+}
+
+/// Check every executable region of a process against its backing file,
+/// reporting anonymous code, deleted or missing files, and code that
+/// differs from its file (see [`integrity`])
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeCheckCodeIntegrity(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let result = open_session(pid, ReaderStrategy::Auto)
+            .and_then(|mut session| check_code_integrity(&mut session));
+        respond(env, OutputFormat::Text, result, integrity_text)
+    })
+}
+
+/// Hash the executable mappings of every library of a process and compare
+/// them with the library files, relocated words left out, to find code
+/// patched at runtime (see [`integrity`])
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeVerifyLibraries(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let result = open_session(pid, ReaderStrategy::Auto)
+            .and_then(|mut session| verify_libraries(&mut session));
+        respond(env, OutputFormat::Text, result, libraries_text)
+    })
+}
+
+/// Look for Frida, Xposed, LSPosed and similar frameworks attached to a
+/// process, in its maps, open files, thread names and sockets (see
+/// [`instrumentation`])
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeDetectInstrumentation(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let result = open_session(pid, ReaderStrategy::Auto).and_then(|session| {
+            let mut report = Report::new(pid);
+            report.process = session_process_info(&session, &mut report.errors)?;
+            let found = instrumentation::detect(pid, session.all_regions())
+                .map_err(|e| ExtractError::from_io(format_args!("PID {}", pid), &e))?;
+            report.stats.results = found.artifacts.len();
+            report.instrumentation = Some(found);
+            report.finish();
+            Ok(report)
+        });
+        respond(env, OutputFormat::Text, result, instrumentation_text)
+    })
+}
+
+/// Identify the packer or protector a process uses, from the libraries it
+/// maps and the strings in its memory (see [`packer`]). Signatures from the
+/// JSON file at `signatures_path`, if not empty, are added to the built-in
+/// ones, replacing those of the same name.
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeDetectPacker(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+    signatures_path: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let result = env
+            .get_string(&signatures_path)
+            .map(String::from)
+            .map_err(|e| ExtractError::InvalidArgument(format!("Invalid signatures path: {}", e)))
+            .and_then(|path| {
+                let signatures = packer_signatures(&path)?;
+                let mut session = open_session(pid, ReaderStrategy::Auto)?;
+                detect_packer(&mut session, &signatures)
+            });
+        respond(env, OutputFormat::Text, result, packer_text)
+    })
+}
+
+/// List the classes loaded in an app's runtime, found as `java.lang.Class`
+/// objects in its ART heap (see [`art`]), with the dex file each came from.
+/// The process is frozen while its heap is read.
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeListJavaClasses(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let result = open_session(pid, ReaderStrategy::Auto)
+            .and_then(|mut session| list_java_classes(&mut session));
+        respond(env, OutputFormat::Text, result, classes_text)
+    })
+}
+
+/// Minimum time between two `onProgress` calls
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Forwards scan progress to a Java `onProgress(long, long, String)`
+/// listener, throttled to [`PROGRESS_INTERVAL`].
+///
+/// If the listener throws it is not called again and the exception is left
+/// pending for the caller.
+struct ProgressListener<'a, 'local> {
+    env: &'a mut JNIEnv<'local>,
+    listener: &'a JObject<'local>,
+    last_call: Option<Instant>,
+    /// Last progress seen, reported by `finish`
+    latest: Option<(u64, u64, String)>,
+    failed: bool,
+}
+
+impl<'a, 'local> ProgressListener<'a, 'local> {
+    fn new(env: &'a mut JNIEnv<'local>, listener: &'a JObject<'local>) -> Self {
+        ProgressListener {
+            env,
+            listener,
+            last_call: None,
+            latest: None,
+            failed: false,
+        }
+    }
+
+    fn call(&mut self, bytes_scanned: u64, total_bytes: u64, region: &str) {
+        if self.failed {
+            return;
+        }
+        let Ok(region) = self.env.new_string(region) else {
+            self.failed = true;
+            return;
+        };
+        let called = self.env.call_method(
+            self.listener,
+            "onProgress",
+            "(JJLjava/lang/String;)V",
+            &[
+                JValue::Long(bytes_scanned as jlong),
+                JValue::Long(total_bytes as jlong),
+                JValue::Object(&region),
+            ],
+        );
+        let _ = self.env.delete_local_ref(region);
+        self.failed = called.is_err();
+        self.last_call = Some(Instant::now());
+    }
+
+    /// Report the final position, so the listener always sees the end
+    fn finish(mut self) {
+        if let Some((bytes_scanned, total_bytes, region)) = self.latest.take() {
+            self.call(bytes_scanned, total_bytes, &region);
+        }
+    }
+}
+
+impl ScanObserver for ProgressListener<'_, '_> {
+    fn progress(&mut self, bytes_scanned: u64, total_bytes: u64, region: &MemoryRegion) {
+        let due = self.last_call.is_none_or(|last| last.elapsed() >= PROGRESS_INTERVAL);
+        if due {
+            self.call(bytes_scanned, total_bytes, region.name());
+            self.latest = None;
+        } else {
+            self.latest = Some((bytes_scanned, total_bytes, region.name().to_string()));
+        }
+    }
+}
+
+/// Extract strings like `extractStringsWithOptions`, streaming each result
+/// as a line of NDJSON to the file descriptor `fd` instead of returning them.
+///
+/// The descriptor is not closed. Failures that stop the scan are written as
+/// an `error` line and also thrown; returns false if the scan failed or
+/// writing to the descriptor did.
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeExtractStringsToFd(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+    options: JString,
+    fd: jint,
+) -> jboolean {
+    ffi::guard(&mut env, |env| {
+        // SAFETY: F_GETFD only inspects the descriptor table
+        if fd < 0 || unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1 {
+            let error = ExtractError::InvalidArgument(format!("Invalid file descriptor: {}", fd));
+            throw_error(env, &error);
+            return JNI_FALSE;
+        }
+        let options = read_options(env, &options);
+
+        // SAFETY: the descriptor is open, and ManuallyDrop leaves it to the caller to close
+        let file = ManuallyDrop::new(unsafe { File::from_raw_fd(fd) });
+        let streamed = stream_strings_from_process(pid, options, BufWriter::new(&*file));
+        match streamed {
+            Ok(_) => JNI_TRUE,
+            Err(e) => {
+                throw_error(env, &e);
+                JNI_FALSE
+            }
+        }
+    })
+}
+
+/// Extract strings like `extractStringsWithOptions`, streaming NDJSON to
+/// `callback.onChunk(String)` in chunks of whole lines.
+///
+/// Throws and returns false like `extractStringsToFd`; if the callback
+/// threw, its exception is left pending instead.
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeExtractStringsToCallback<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    pid: i32,
+    options: JString<'local>,
+    callback: JObject<'local>,
+) -> jboolean {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let out = CallbackWriter {
+            env,
+            callback: &callback,
+            buffer: Vec::new(),
+        };
+        match stream_strings_from_process(pid, options, out) {
+            Ok(_) => JNI_TRUE,
+            Err(e) => {
+                throw_error(env, &e);
+                JNI_FALSE
+            }
+        }
+    })
+}
+
+/// Parse a Java options string into [`ScanOptions`]
+fn read_options(env: &mut JNIEnv, options: &JString) -> Result<ScanOptions, ExtractError> {
+    read_json(env, options, ScanOptions::from_json)
+}
+
+/// Parse a Java options string with `parse`, for the calls whose options
+/// aren't, or aren't only, [`ScanOptions`]
+fn read_json<T, F>(env: &mut JNIEnv, options: &JString, parse: F) -> Result<T, ExtractError>
+where
+    F: FnOnce(&str) -> Result<T, ExtractError>,
+{
+    env.get_string(options)
+        .map(String::from)
+        .map_err(|e| ExtractError::InvalidArgument(format!("Invalid options string: {}", e)))
+        .and_then(|json| parse(&json))
+}
+
+/// Run `dump` on a session opened on `pid`, writing to the directory
+/// `output_dir` names, and respond with its report as text
+fn respond_dump<D>(
+    env: &mut JNIEnv,
+    pid: i32,
+    output_dir: &JString,
+    dump: D,
+    render_text: fn(&Report) -> String,
+) -> jstring
+where
+    D: FnOnce(&mut Session, &Path) -> Result<Report, ExtractError>,
+{
+    let result = env
+        .get_string(output_dir)
+        .map(String::from)
+        .map_err(|e| ExtractError::InvalidArgument(format!("Invalid output directory: {}", e)))
+        .and_then(|dir| {
+            let mut session = open_session(pid, ReaderStrategy::Auto)?;
+            dump(&mut session, Path::new(&dir))
+        });
+    respond(env, OutputFormat::Text, result, render_text)
+}
+
+/// Lines of NDJSON collected before they are passed to the callback
+const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Forwards output to a Java `onChunk(String)` callback.
+///
+/// Chunks are only sent once they end in a newline, so with whole lines
+/// written at a time (as [`NdjsonWriter`] does) each chunk is complete lines.
+struct CallbackWriter<'a, 'local> {
+    env: &'a mut JNIEnv<'local>,
+    callback: &'a JObject<'local>,
+    buffer: Vec<u8>,
+}
+
+impl CallbackWriter<'_, '_> {
+    fn send(&mut self) -> io::Result<()> {
+        let chunk = String::from_utf8_lossy(&self.buffer);
+        let chunk = self.env.new_string(chunk).map_err(io::Error::other)?;
+        let called = self.env.call_method(
+            self.callback,
+            "onChunk",
+            "(Ljava/lang/String;)V",
+            &[JValue::Object(&chunk)],
+        );
+        let _ = self.env.delete_local_ref(chunk);
+        self.buffer.clear();
+        called.map(|_| ()).map_err(io::Error::other)
+    }
+}
+
+impl Write for CallbackWriter<'_, '_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        if self.buffer.len() >= STREAM_CHUNK_SIZE && self.buffer.ends_with(b"\n") {
+            self.send()?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        self.send()
+    }
+}
+
+/// Search process memory with a regular expression compiled natively
+///
+/// `flags` takes `java.util.regex.Pattern` constants (CASE_INSENSITIVE,
+/// COMMENTS, MULTILINE, DOTALL); other flags are ignored. A positive
+/// `context_bytes` adds a hexdump of that many bytes around each match.
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeSearchRegex(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+    pattern: JString,
+    flags: jint,
+    context_bytes: jint,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let result = env
+            .get_string(&pattern)
+            .map(String::from)
+            .map_err(|e| ExtractError::InvalidArgument(format!("Invalid pattern string: {}", e)))
+            .and_then(|pattern| {
+                let mut session = open_session(pid, ReaderStrategy::Auto)?;
+                let context = context_bytes.max(0) as usize;
+                search_regex_in_process(&mut session, &pattern, flags, context)
+            });
+
+        respond(env, OutputFormat::Text, result, regex_text)
+    })
+}
+
+/// Search process memory for many literal keywords in a single pass
+///
+/// A positive `context_bytes` adds a hexdump of that many bytes around each hit
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeSearchKeywords(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+    keywords: JObjectArray,
+    case_insensitive: jboolean,
+    context_bytes: jint,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let context = context_bytes.max(0) as usize;
+        let result = read_string_array(env, &keywords).and_then(|keywords| {
+            let mut session = open_session(pid, ReaderStrategy::Auto)?;
+            search_keywords_in_process(&mut session, &keywords, case_insensitive != 0, context)
+        });
+
+        respond(env, OutputFormat::Text, result, keywords_text)
+    })
+}
+
+/// Extract strings like `extractStringsWithOptions` from each of `pids` in
+/// one call, returning a report per PID (see [`BatchReport`]). A timeout
+/// applies to each process separately.
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeExtractStringsBatch(
+    mut env: JNIEnv,
+    _class: JClass,
+    pids: JIntArray,
+    options: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, OutputFormat::Text);
+        let result = read_pids(env, &pids).and_then(|pids| {
+            // One checkpoint can't follow several processes
+            let options = ScanOptions {
+                checkpoint: None,
+                ..options.as_ref().map_err(ExtractError::clone)?.clone()
+            };
+            Ok(run_batch(&pids, options.reader_strategy, |session| {
+                extract_strings_from_process(session, &options, None)
+            }))
+        });
+        let options = options.unwrap_or_default();
+
+        respond(env, format, result, |batch| {
+            batch_text(batch, |report| strings_text(report, &options))
+        })
+    })
+}
+
+/// `searchRegex` over each of `pids`, compiling the pattern once
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeSearchRegexBatch(
+    mut env: JNIEnv,
+    _class: JClass,
+    pids: JIntArray,
+    pattern: JString,
+    flags: jint,
+    context_bytes: jint,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let pattern = env
+            .get_string(&pattern)
+            .map(String::from)
+            .map_err(|e| ExtractError::InvalidArgument(format!("Invalid pattern string: {}", e)));
+        let result = read_pids(env, &pids).and_then(|pids| {
+            let patterns = [pattern?];
+            let mut scanner = regex_scanner(&patterns[0], flags)?;
+            let context = context_bytes.max(0) as usize;
+            Ok(run_batch(&pids, ReaderStrategy::Auto, |session| {
+                Ok(search_process(session, &mut scanner, &patterns, context))
+            }))
+        });
+
+        respond(env, OutputFormat::Text, result, |batch| batch_text(batch, regex_text))
+    })
+}
+
+/// `searchKeywords` over each of `pids`, building the matcher once
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeSearchKeywordsBatch(
+    mut env: JNIEnv,
+    _class: JClass,
+    pids: JIntArray,
+    keywords: JObjectArray,
+    case_insensitive: jboolean,
+    context_bytes: jint,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let keywords = read_string_array(env, &keywords);
+        let result = read_pids(env, &pids).and_then(|pids| {
+            let keywords = keywords?;
+            let mut scanner = keyword_scanner(&keywords, case_insensitive != 0)?;
+            let context = context_bytes.max(0) as usize;
+            Ok(run_batch(&pids, ReaderStrategy::Auto, |session| {
+                Ok(search_process(session, &mut scanner, &keywords, context))
+            }))
+        });
+
+        respond(env, OutputFormat::Text, result, |batch| batch_text(batch, keywords_text))
+    })
+}
+
+/// Copy a Java int[] of PIDs, dropping duplicates
+fn read_pids(env: &mut JNIEnv, array: &JIntArray) -> Result<Vec<i32>, ExtractError> {
+    let invalid =
+        |e: jni::errors::Error| ExtractError::InvalidArgument(format!("Invalid PID array: {}", e));
+    let length = env.get_array_length(array).map_err(invalid)?;
+    let mut pids = vec![0; length as usize];
+    env.get_int_array_region(array, 0, &mut pids).map_err(invalid)?;
+
+    let mut seen = HashSet::new();
+    pids.retain(|&pid| seen.insert(pid));
+    if pids.is_empty() {
+        return Err(ExtractError::InvalidArgument("No PIDs given".to_string()));
+    }
+    Ok(pids)
+}
+
+/// Run `scan` on a one-off session for each PID, opened with `strategy`,
+/// collecting a report for each process that could be scanned and the
+/// error for each that couldn't
+fn run_batch<F>(pids: &[i32], strategy: ReaderStrategy, mut scan: F) -> BatchReport
+where
+    F: FnMut(&mut Session) -> Result<Report, ExtractError>,
+{
+    let started = Instant::now();
+    let mut batch = BatchReport::default();
+    for &pid in pids {
+        match open_session(pid, strategy).and_then(|mut session| scan(&mut session)) {
+            Ok(report) => batch.add(report),
+            Err(e) => batch.add_error(pid, e.to_string()),
+        }
+    }
+    batch.stats.set_elapsed(started.elapsed());
+    batch
+}
+
+/// Each process's text in PID order, failed processes as an error line
+fn batch_text<F>(batch: &BatchReport, render_text: F) -> String
+where
+    F: Fn(&Report) -> String,
+{
+    let mut result = String::new();
+    let mut pids: Vec<i32> = batch.reports.keys().chain(batch.errors.keys()).copied().collect();
+    pids.sort_unstable();
+    for pid in pids {
+        result.push_str(&format!("=== PID {} ===\n", pid));
+        match (batch.reports.get(&pid), batch.errors.get(&pid)) {
+            (Some(report), _) => result.push_str(&render_text(report)),
+            (None, Some(error)) => result.push_str(&format!("Error: {}\n", error)),
+            (None, None) => {}
+        }
+        result.push('\n');
+    }
+    result
+}
+
+/// Build a Java String[] from Rust strings
+fn new_string_array<'local, 'a>(
+    env: &mut JNIEnv<'local>,
+    strings: impl ExactSizeIterator<Item = &'a str>,
+) -> Result<JObjectArray<'local>, ExtractError> {
+    let array = env
+        .new_object_array(strings.len() as i32, "java/lang/String", JObject::null())
+        .map_err(|e| ExtractError::Io(format!("Failed to create string array: {}", e)))?;
+
+    for (index, string) in strings.enumerate() {
+        let element = env
+            .new_string(string)
+            .map_err(|e| ExtractError::Io(format!("Failed to create string: {}", e)))?;
+        env.set_object_array_element(&array, index as i32, &element)
+            .map_err(|e| ExtractError::Io(format!("Failed to fill string array: {}", e)))?;
+        // Millions of strings would overflow the local reference table
+        let _ = env.delete_local_ref(element);
+    }
+    Ok(array)
+}
+
+/// Copy a Java String[] into Rust strings
+fn read_string_array(
+    env: &mut JNIEnv,
+    array: &JObjectArray,
+) -> Result<Vec<String>, ExtractError> {
+    let invalid = |e: jni::errors::Error| {
+        ExtractError::InvalidArgument(format!("Invalid string array: {}", e))
+    };
+    let length = env.get_array_length(array).map_err(invalid)?;
+
+    let mut strings = Vec::with_capacity(length as usize);
+    for index in 0..length {
+        let element = env.get_object_array_element(array, index).map_err(invalid)?;
+        let value: String = env
+            .get_string(&JString::from(element))
+            .map_err(|e| {
+                ExtractError::InvalidArgument(format!("Invalid string at index {}: {}", index, e))
+            })?
+            .into();
+        strings.push(value);
+    }
+    Ok(strings)
+}
+
+/// Read `length` raw bytes at `address` in a process
+///
+/// If the range runs into unreadable memory the array holds only the bytes
+/// before it. Throws (see [`error`]) and returns null if nothing at
+/// `address` could be read.
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeReadMemoryRange(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+    address: jlong,
+    length: jint,
+) -> jbyteArray {
+    ffi::guard(&mut env, |env| {
+        let array = open_reader(pid)
+            .and_then(|mut mem| read_memory_range(mem.as_mut(), address as u64, length))
+            .and_then(|bytes| new_byte_array(env, &bytes));
+
+        match array {
+            Ok(array) => array.into_raw(),
+            Err(e) => {
+                throw_error(env, &e);
+                std::ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Fill a direct `ByteBuffer` with memory at `address`, without an
+/// intermediate copy or allocation, for polling the same range repeatedly
+///
+/// Reads up to the buffer's capacity, ignoring its position and limit.
+/// Returns the number of bytes read, which is less than the capacity if the
+/// range runs into unreadable memory. Throws and returns -1 if the buffer is
+/// not direct or nothing could be read.
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeReadMemoryInto(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+    address: jlong,
+    buffer: JByteBuffer,
+) -> jint {
+    ffi::guard(&mut env, |env| {
+        let result = with_direct_buffer(env, &buffer, |buf| {
+            let mut mem = open_reader(pid)?;
+            read_memory_into(mem.as_mut(), address as u64, buf)
+        });
+
+        match result {
+            Ok(read) => read as jint,
+            Err(e) => {
+                throw_error(env, &e);
+                -1
+            }
+        }
+    })
+}
+
+/// Write `bytes` into a process at `address`
+///
+/// Returns how many bytes were written, fewer than given if the range runs
+/// into unmapped memory. Throws (see [`error`]) and returns -1 without root,
+/// or if nothing at `address` could be written.
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeWriteMemoryRange(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+    address: jlong,
+    bytes: JByteArray,
+) -> jint {
+    ffi::guard(&mut env, |env| {
+        let result = read_byte_array(env, &bytes).and_then(|bytes| {
+            write_memory_range(&mut MemoryWriter::new(pid), address as u64, &bytes)
+        });
+
+        match result {
+            Ok(written) => written as jint,
+            Err(e) => {
+                throw_error(env, &e);
+                -1
+            }
+        }
+    })
+}
+
+/// Run `read` on the memory behind a direct `ByteBuffer`
+fn with_direct_buffer<F>(
+    env: &JNIEnv,
+    buffer: &JByteBuffer,
+    read: F,
+) -> Result<usize, ExtractError>
+where
+    F: FnOnce(&mut [u8]) -> Result<usize, ExtractError>,
+{
+    let not_direct = |e: jni::errors::Error| {
+        ExtractError::InvalidArgument(format!("Not a direct buffer: {}", e))
+    };
+    let pointer = env.get_direct_buffer_address(buffer).map_err(not_direct)?;
+    let capacity = env.get_direct_buffer_capacity(buffer).map_err(not_direct)?;
+
+    // SAFETY: a direct buffer's memory stays valid while the buffer object is
+    // referenced, which `buffer` guarantees for the duration of the call
+    read(unsafe { std::slice::from_raw_parts_mut(pointer, capacity) })
+}
+
+/// Copy bytes into a new Java byte[]
+fn new_byte_array<'local>(
+    env: &JNIEnv<'local>,
+    bytes: &[u8],
+) -> Result<JByteArray<'local>, ExtractError> {
+    env.byte_array_from_slice(bytes)
+        .map_err(|e| ExtractError::Io(format!("Failed to create byte array: {}", e)))
+}
+
+/// Copy a Java byte array
+fn read_byte_array(env: &JNIEnv, array: &JByteArray) -> Result<Vec<u8>, ExtractError> {
+    env.convert_byte_array(array)
+        .map_err(|e| ExtractError::InvalidArgument(format!("Failed to read byte array: {}", e)))
+}
+
+/// Throw the Java exception for a failed call.
+///
+/// An exception already pending (thrown by a Java callback) is left in
+/// place. If the app lacks the exception class, for example because it was
+/// stripped, a `RuntimeException` is thrown instead.
+fn throw_error(env: &mut JNIEnv, error: &ExtractError) {
+    if env.exception_check().unwrap_or(false) {
+        return;
+    }
+    let thrown = match error.exception_subclass() {
+        Some(class) => env.throw_new(class, error.message()),
+        None => new_coded_exception(env, error).and_then(|exception| env.throw(exception)),
+    };
+    if thrown.is_err() {
+        let _ = env.exception_clear();
+        let _ = env.throw_new("java/lang/RuntimeException", error.message());
+    }
+}
+
+/// A `NativeExtractionException(message, code)` for `error`
+fn new_coded_exception<'local>(
+    env: &mut JNIEnv<'local>,
+    error: &ExtractError,
+) -> jni::errors::Result<JThrowable<'local>> {
+    let message = env.new_string(error.message())?;
+    let exception = env.new_object(
+        error::EXCEPTION_CLASS,
+        "(Ljava/lang/String;I)V",
+        &[JValue::Object(&message), JValue::Int(error.code())],
+    )?;
+    Ok(JThrowable::from(exception))
+}
+
+/// Check if the device is rooted and if we have necessary permissions
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeCheckRootAccess(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let has_root = check_root_access();
+        let message = if has_root {
+            "Root access available"
+        } else {
+            "Root access not available - native memory extraction will be limited"
+        };
+
+        new_string(env, message.to_string())
+    })
+}
+
+/// How a call renders its result: in the `outputFormat` its options ask
+/// for, or else in `default`
+fn output_format(
+    options: &Result<ScanOptions, ExtractError>,
+    default: OutputFormat,
+) -> OutputFormat {
+    options.as_ref().ok().and_then(|options| options.output_format).unwrap_or(default)
+}
+
+/// The format calls on a session render in when their options don't say;
+/// text for an unknown handle, which the call then throws for
+fn session_format(handle: jlong) -> OutputFormat {
+    session::get(handle)
+        .map(|session| session.lock().unwrap_or_else(|e| e.into_inner()).output_format())
+        .unwrap_or_default()
+}
+
+/// Render the outcome of a call in `format`, or throw the error and return
+/// null if it failed
+fn respond<R, F>(
+    env: &mut JNIEnv,
+    format: OutputFormat,
+    result: Result<R, ExtractError>,
+    render_text: F,
+) -> jstring
+where
+    R: Rendered,
+    F: FnOnce(&R) -> String,
+{
+    // A Java callback threw; let the exception propagate
+    if env.exception_check().unwrap_or(false) {
+        return std::ptr::null_mut();
+    }
+
+    let output = match (format, result) {
+        (OutputFormat::Text, Ok(report)) => render_text(&report),
+        (OutputFormat::Json, Ok(report)) => Rendered::to_json(&report),
+        (_, Err(e)) => {
+            throw_error(env, &e);
+            return std::ptr::null_mut();
+        }
+    };
+    new_string(env, output)
+}
+
+/// Return a Java string, or throw and return null if it can't be created
+fn new_string(env: &mut JNIEnv, output: String) -> jstring {
+    match env.new_string(output) {
+        Ok(output) => output.into_raw(),
+        Err(e) => {
+            throw_error(env, &ExtractError::Io(format!("Failed to create string: {}", e)));
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// PIDs of the processes running `package`, including its `<package>:<name>`
+/// secondary processes; the main process comes first. Empty if none are
+/// running.
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeFindPidByPackage(
+    mut env: JNIEnv,
+    _class: JClass,
+    package_name: JString,
+) -> jintArray {
+    ffi::guard(&mut env, |env| {
+        let pids = env
+            .get_string(&package_name)
+            .map(String::from)
+            .map_err(|e| ExtractError::InvalidArgument(format!("Invalid package name: {}", e)))
+            .and_then(|package| {
+                if package.is_empty() {
+                    return Err(ExtractError::InvalidArgument("Empty package name".to_string()));
+                }
+                process::find_pids_by_package(&package)
+                    .map_err(|e| ExtractError::from_io("Failed to list processes", &e))
+            })
+            .and_then(|pids| new_int_array(env, &pids));
+
+        match pids {
+            Ok(array) => array.into_raw(),
+            Err(e) => {
+                throw_error(env, &e);
+                std::ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Every running app process (forked from a zygote) as a JSON array of
+/// [`process::ProcessEntry`] objects, for presenting a process picker
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeListAppProcesses(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let json = process::list_app_processes()
+            .map_err(|e| ExtractError::from_io("Failed to list processes", &e))
+            .and_then(|processes| {
+                serde_json::to_string(&processes)
+                    .map_err(|e| ExtractError::Io(format!("Failed to serialize processes: {}", e)))
+            });
+
+        match json {
+            Ok(json) => new_string(env, json),
+            Err(e) => {
+                throw_error(env, &e);
+                std::ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Copy ints into a new Java int[]
+fn new_int_array<'local>(
+    env: &mut JNIEnv<'local>,
+    values: &[i32],
+) -> Result<JIntArray<'local>, ExtractError> {
+    let array = env
+        .new_int_array(values.len() as i32)
+        .map_err(|e| ExtractError::Io(format!("Failed to create int array: {}", e)))?;
+    env.set_int_array_region(&array, 0, values)
+        .map_err(|e| ExtractError::Io(format!("Failed to fill int array: {}", e)))?;
+    Ok(array)
+}
+
+/// Return the parsed /proc/[pid]/maps regions of a process as a JSON array
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeGetMemoryRegions(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let result = maps::read_maps(pid)
+            .map_err(|e| ExtractError::from_io("Failed to read maps", &e))
+            .and_then(|regions| regions_json(&regions));
+
+        match result {
+            Ok(json) => new_string(env, json),
+            Err(e) => {
+                throw_error(env, &e);
+                std::ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// The memory regions of a process as a JSON array, as `getMemoryRegions`
+/// returns them, each with its RSS, PSS, swap and the like read from smaps
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeGetMemoryUsage(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let result = maps::read_smaps(pid)
+            .map_err(|e| ExtractError::from_io("Failed to read smaps", &e))
+            .and_then(|regions| regions_json(&regions));
+
+        match result {
+            Ok(json) => new_string(env, json),
+            Err(e) => {
+                throw_error(env, &e);
+                std::ptr::null_mut()
+            }
+        }
+    })
+}
+
+fn regions_json(regions: &[MemoryRegion]) -> Result<String, ExtractError> {
+    serde_json::to_string(regions)
+        .map_err(|e| ExtractError::Io(format!("Failed to serialize regions: {}", e)))
+}
+
+/// Keep writing the value the options' `hold` object gives to its
+/// addresses, from a native thread, until [`unfreezeValue`] is called
+///
+/// Returns the hold's handle. Throws and returns 0 without root, or if the
+/// first writes fail.
+///
+/// [`unfreezeValue`]: Java_com_textextractor_NativeMemoryExtractor_nativeUnfreezeValue
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeFreezeValue(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+    options: JString,
+) -> jlong {
+    ffi::guard(&mut env, |env| {
+        let result = read_json(env, &options, HoldOptions::from_json).and_then(|options| {
+            require_root_to_write()?;
+            Hold::start(pid, &options).map_err(ExtractError::InvalidArgument)
+        });
+
+        match result {
+            Ok(hold) => hold::register(hold),
+            Err(e) => {
+                throw_error(env, &e);
+                0
+            }
+        }
+    })
+}
+
+/// Stop writing a frozen value back; returns false if the handle is unknown
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeUnfreezeValue(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+) -> jboolean {
+    ffi::guard(&mut env, |_| {
+        if hold::release(handle) {
+            JNI_TRUE
+        } else {
+            JNI_FALSE
+        }
+    })
+}
+
+/// The frozen values, with how many writes each has made, as a JSON array
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeListFrozenValues(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let json = serde_json::to_string(&hold::list())
+            .map_err(|e| ExtractError::Io(format!("Failed to serialize frozen values: {}", e)));
+
+        match json {
+            Ok(json) => new_string(env, json),
+            Err(e) => {
+                throw_error(env, &e);
+                std::ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Poll the addresses the options' `watch` object gives from a native
+/// thread, calling `callback.onChange(address: Long, oldValue: Long,
+/// newValue: Long, timeNanos: Long)` on that thread for each change seen,
+/// until [`unwatchAddresses`] is called
+///
+/// Returns the watch's handle. Throws and returns 0 if the first reads
+/// fail. A callback that throws ends the watch, and its exception is
+/// cleared.
+///
+/// [`unwatchAddresses`]: Java_com_textextractor_NativeMemoryExtractor_nativeUnwatchAddresses
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeWatchAddresses<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    pid: i32,
+    options: JString<'local>,
+    callback: JObject<'local>,
+) -> jlong {
+    ffi::guard(&mut env, |env| {
+        let result = read_json(env, &options, WatchOptions::from_json).and_then(|options| {
+            let notify = change_callback(env, &callback)?;
+            Watch::start(pid, &options, notify).map_err(ExtractError::InvalidArgument)
+        });
+
+        match result {
+            Ok(watch) => watch::register(watch),
+            Err(e) => {
+                throw_error(env, &e);
+                0
+            }
+        }
+    })
+}
+
+/// Stop a watch; returns false if the handle is unknown. A callback may
+/// stop its own watch.
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeUnwatchAddresses(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+) -> jboolean {
+    ffi::guard(&mut env, |_| {
+        if watch::release(handle) {
+            JNI_TRUE
+        } else {
+            JNI_FALSE
+        }
+    })
+}
+
+/// The watches, with how many changes each has seen, as a JSON array
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeListWatches(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let json = serde_json::to_string(&watch::list())
+            .map_err(|e| ExtractError::Io(format!("Failed to serialize watches: {}", e)));
+
+        match json {
+            Ok(json) => new_string(env, json),
+            Err(e) => {
+                throw_error(env, &e);
+                std::ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Call `callback.onChange` for each change a watch sees, from the watch's
+/// thread, which is attached to the JVM as a daemon the first time
+fn change_callback(env: &mut JNIEnv, callback: &JObject) -> Result<watch::Notify, ExtractError> {
+    let invalid = |e: jni::errors::Error| ExtractError::InvalidArgument(e.to_string());
+    let vm = env.get_java_vm().map_err(invalid)?;
+    let callback = env.new_global_ref(callback).map_err(invalid)?;
+    Ok(Box::new(move |change| {
+        let mut env = vm
+            .attach_current_thread_as_daemon()
+            .map_err(|e| format!("Failed to attach to the JVM: {}", e))?;
+        let called = env.call_method(
+            &callback,
+            "onChange",
+            "(JJJJ)V",
+            &[
+                JValue::Long(change.address as jlong),
+                JValue::Long(change.old as jlong),
+                JValue::Long(change.new as jlong),
+                JValue::Long(change.time_nanos),
+            ],
+        );
+        match called {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                if env.exception_check().unwrap_or(false) {
+                    let _ = env.exception_describe();
+                    let _ = env.exception_clear();
+                }
+                Err(format!("Callback failed: {}", e))
+            }
+        }
+    }))
+}
+
+/// Rerun the string scan the options give every `schedule.intervalSecs`
+/// from a native thread, calling `callback.onDelta(json: String)` on that
+/// thread with the strings each run found new and gone since the run
+/// before, until [`cancelScheduledScan`] is called
+///
+/// Returns the schedule's handle. Throws and returns 0 if the baseline run
+/// fails. A callback that throws ends the schedule, and its exception is
+/// cleared.
+///
+/// [`cancelScheduledScan`]: Java_com_textextractor_NativeMemoryExtractor_nativeCancelScheduledScan
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeScheduleScan<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    pid: i32,
+    options: JString<'local>,
+    callback: JObject<'local>,
+) -> jlong {
+    ffi::guard(&mut env, |env| {
+        let parse = |json: &str| {
+            Ok((ScanOptions::from_json(json)?, ScheduleOptions::from_json(json)?))
+        };
+        let result = read_json(env, &options, parse).and_then(|(options, schedule)| {
+            let notify = delta_callback(env, &callback)?;
+            Schedule::start(pid, &schedule, scheduled_scan(pid, options), notify)
+        });
+
+        match result {
+            Ok(schedule) => schedule::register(schedule),
+            Err(e) => {
+                throw_error(env, &e);
+                0
+            }
+        }
+    })
+}
+
+/// Stop a schedule, waiting for any run under way to finish; returns false
+/// if the handle is unknown. A callback may stop its own schedule.
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeCancelScheduledScan(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+) -> jboolean {
+    ffi::guard(&mut env, |_| {
+        if schedule::release(handle) {
+            JNI_TRUE
+        } else {
+            JNI_FALSE
+        }
+    })
+}
+
+/// The schedules, with how many runs and deltas each has made, as a JSON
+/// array
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeListScheduledScans(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let json = serde_json::to_string(&schedule::list())
+            .map_err(|e| ExtractError::Io(format!("Failed to serialize schedules: {}", e)));
+
+        match json {
+            Ok(json) => new_string(env, json),
+            Err(e) => {
+                throw_error(env, &e);
+                std::ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Open `pid` and snapshot it as the baseline, returning the run that
+/// snapshots it again and compares; the session lives in the schedule's
+/// thread, as its reader may be bound to it
+fn scheduled_scan(pid: i32, options: ScanOptions) -> schedule::Setup {
+    Box::new(move || {
+        let mut session = open_session(pid, options.reader_strategy)?;
+        take_snapshot(&mut session, &options)?;
+        Ok(Box::new(move || {
+            let mut report = diff_snapshot(&mut session, &options)?;
+            report
+                .diff
+                .take()
+                .ok_or_else(|| ExtractError::Io("Snapshot diff missing from report".into()))
+        }))
+    })
+}
+
+/// Call `callback.onDelta` with each delta as JSON, from the schedule's
+/// thread, which is attached to the JVM as a daemon the first time
+fn delta_callback(
+    env: &mut JNIEnv,
+    callback: &JObject,
+) -> Result<schedule::Notify, ExtractError> {
+    let invalid = |e: jni::errors::Error| ExtractError::InvalidArgument(e.to_string());
+    let vm = env.get_java_vm().map_err(invalid)?;
+    let callback = env.new_global_ref(callback).map_err(invalid)?;
+    Ok(Box::new(move |delta| {
+        let json = serde_json::to_string(delta)
+            .map_err(|e| format!("Failed to serialize delta: {}", e))?;
+        let mut env = vm
+            .attach_current_thread_as_daemon()
+            .map_err(|e| format!("Failed to attach to the JVM: {}", e))?;
+        let json = env
+            .new_string(json)
+            .map_err(|e| format!("Failed to create delta string: {}", e))?;
+        let called = env.call_method(
+            &callback,
+            "onDelta",
+            "(Ljava/lang/String;)V",
+            &[JValue::Object(&json)],
+        );
+        // The thread stays attached, so nothing else frees the local
+        let _ = env.delete_local_ref(json);
+        match called {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                if env.exception_check().unwrap_or(false) {
+                    let _ = env.exception_describe();
+                    let _ = env.exception_clear();
+                }
+                Err(format!("Callback failed: {}", e))
+            }
+        }
+    }))
+}
+
+/// Wait for processes of the package the options' `launch` object names to
+/// start, scanning each for strings as the options give at every one of
+/// its `delaysMs` and calling `callback.onScan(pid: Int, json: String)`
+/// with each report, on a thread of the launch's own, until
+/// [`unwatchLaunches`] is called
+///
+/// Returns the watch's handle. Throws and returns 0 if the proc connector
+/// can't be listened to. A callback that throws ends the scans of that
+/// launch, and its exception is cleared.
+///
+/// [`unwatchLaunches`]: Java_com_textextractor_NativeMemoryExtractor_nativeUnwatchLaunches
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeWatchLaunches<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    options: JString<'local>,
+    callback: JObject<'local>,
+) -> jlong {
+    ffi::guard(&mut env, |env| {
+        let parse = |json: &str| {
+            Ok((ScanOptions::from_json(json)?, LaunchOptions::from_json(json)?))
+        };
+        let result = read_json(env, &options, parse).and_then(|(options, launch)| {
+            let scan = launch_scan(env, &callback, options, launch.delays_ms.clone())?;
+            LaunchWatch::start(&launch, scan)
+        });
+
+        match result {
+            Ok(watch) => launch::register(watch),
+            Err(e) => {
+                throw_error(env, &e);
+                0
+            }
+        }
+    })
+}
+
+/// Stop waiting for launches; returns false if the handle is unknown.
+/// Launches being scanned stop before their next scan.
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeUnwatchLaunches(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+) -> jboolean {
+    ffi::guard(&mut env, |_| {
+        if launch::release(handle) {
+            JNI_TRUE
+        } else {
+            JNI_FALSE
+        }
+    })
+}
+
+/// The launch watches, with the processes each has seen start, as a JSON
+/// array
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeListLaunchWatches(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let json = serde_json::to_string(&launch::list())
+            .map_err(|e| ExtractError::Io(format!("Failed to serialize launch watches: {}", e)));
+
+        match json {
+            Ok(json) => new_string(env, json),
+            Err(e) => {
+                throw_error(env, &e);
+                std::ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Scan a process just launched at each of `delays_ms`, calling
+/// `callback.onScan` with each report as JSON from the launch's thread,
+/// which is attached to the JVM as a daemon. The session is opened there,
+/// as its reader may be bound to it, and its maps re-read before each scan
+/// as a starting app maps more all the time.
+fn launch_scan(
+    env: &mut JNIEnv,
+    callback: &JObject,
+    options: ScanOptions,
+    delays_ms: Vec<u64>,
+) -> Result<launch::Scan, ExtractError> {
+    let invalid = |e: jni::errors::Error| ExtractError::InvalidArgument(e.to_string());
+    let vm = env.get_java_vm().map_err(invalid)?;
+    let callback = env.new_global_ref(callback).map_err(invalid)?;
+    Ok(Arc::new(move |pid, stopped| {
+        let launched = Instant::now();
+        let mut session: Option<Session> = None;
+        for &delay in &delays_ms {
+            let due = launched + Duration::from_millis(delay);
+            // Slept in steps, so a stopped watch doesn't wait out a long delay
+            while let Some(wait) = due.checked_duration_since(Instant::now()) {
+                if stopped.load(Ordering::Relaxed) {
+                    return Ok(());
+                }
+                std::thread::sleep(wait.min(Duration::from_millis(50)));
+            }
+            if stopped.load(Ordering::Relaxed) {
+                return Ok(());
+            }
+            let session = match &mut session {
+                Some(session) => {
+                    session.refresh_regions()?;
+                    session
+                }
+                None => session.insert(open_session(pid, options.reader_strategy)?),
+            };
+            let report = extract_strings_from_process(session, &options, None)?;
+
+            let jni_failed = |e: jni::errors::Error| ExtractError::Io(e.to_string());
+            let mut env = vm.attach_current_thread_as_daemon().map_err(jni_failed)?;
+            let json = env.new_string(Rendered::to_json(&report)).map_err(jni_failed)?;
+            let called = env.call_method(
+                &callback,
+                "onScan",
+                "(ILjava/lang/String;)V",
+                &[JValue::Int(pid), JValue::Object(&json)],
+            );
+            // The thread stays attached, so nothing else frees the local
+            let _ = env.delete_local_ref(json);
+            if let Err(e) = called {
+                if env.exception_check().unwrap_or(false) {
+                    let _ = env.exception_describe();
+                    let _ = env.exception_clear();
+                }
+                return Err(ExtractError::Io(format!("Callback failed: {}", e)));
+            }
+        }
+        Ok(())
+    }))
+}
+
+/// Open a session on a process, keeping its reader and parsed maps for use
+/// by the `session*` calls until [`closeSession`] is called
+///
+/// Throws and returns 0 if the process can't be opened
+///
+/// [`closeSession`]: Java_com_textextractor_NativeMemoryExtractor_nativeCloseSession
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeOpenSession(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+) -> jlong {
+    ffi::guard(&mut env, |env| {
+        match open_session(pid, ReaderStrategy::Auto) {
+            Ok(session) => session::register(session),
+            Err(e) => {
+                throw_error(env, &e);
+                0
+            }
+        }
+    })
+}
+
+/// `openSession`, with the backend the `readerStrategy` of a JSON options
+/// object (see [`ScanOptions`]) selects, used by every read on the session,
+/// and its `outputFormat` as the format of calls on the session whose own
+/// options don't give one
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeOpenSessionWithOptions(
+    mut env: JNIEnv,
+    _class: JClass,
+    pid: i32,
+    options: JString,
+) -> jlong {
+    ffi::guard(&mut env, |env| {
+        let session = read_options(env, &options).and_then(|options| {
+            let mut session = open_session(pid, options.reader_strategy)?;
+            session.set_output_format(options.output_format.unwrap_or_default());
+            Ok(session)
+        });
+        match session {
+            Ok(session) => session::register(session),
+            Err(e) => {
+                throw_error(env, &e);
+                0
+            }
+        }
+    })
+}
+
+/// Open a session on a dump written by `dumpProcess`, any directory of
+/// region files named like its files, an ELF core file or an HPROF heap
+/// dump, so the `session*` calls analyze it offline as they would the live
+/// process
+///
+/// Throws and returns 0 if the dump can't be loaded
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeOpenDumpSession(
+    mut env: JNIEnv,
+    _class: JClass,
+    path: JString,
+) -> jlong {
+    ffi::guard(&mut env, |env| {
+        let result = env
+            .get_string(&path)
+            .map(String::from)
+            .map_err(|e| ExtractError::InvalidArgument(format!("Invalid dump path: {}", e)))
+            .and_then(|path| open_dump_session(Path::new(&path)));
+
+        match result {
+            Ok(session) => session::register(session),
+            Err(e) => {
+                throw_error(env, &e);
+                0
+            }
+        }
+    })
+}
+
+/// Close a session; returns false if the handle is unknown
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeCloseSession(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+) -> jboolean {
+    ffi::guard(&mut env, |_| {
+        if session::close(handle) {
+            JNI_TRUE
+        } else {
+            JNI_FALSE
+        }
+    })
+}
+
+/// Stop the scan running on a session between two chunks; it returns the
+/// results found so far, marked as truncated
+///
+/// Returns false if the handle is unknown. Cancelling a session with no
+/// scan running has no effect on later scans.
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeCancelScan(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+) -> jboolean {
+    ffi::guard(&mut env, |_| {
+        if session::cancel(handle) {
+            JNI_TRUE
+        } else {
+            JNI_FALSE
+        }
+    })
+}
+
+/// Re-read a session's maps to pick up new or removed mappings
+///
+/// Throws and returns false if the maps can't be read
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeSessionRefreshRegions(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+) -> jboolean {
+    ffi::guard(&mut env, |env| {
+        match with_session(handle, |session| session.refresh_regions()) {
+            Ok(()) => JNI_TRUE,
+            Err(e) => {
+                throw_error(env, &e);
+                JNI_FALSE
+            }
+        }
+    })
+}
+
+/// Re-read a session's regions from smaps, so that they carry their memory
+/// usage in `sessionGetMemoryRegions` and reports
+///
+/// Throws and returns false if smaps can't be read
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeSessionRefreshUsage(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+) -> jboolean {
+    ffi::guard(&mut env, |env| {
+        match with_session(handle, |session| session.refresh_usage()) {
+            Ok(()) => JNI_TRUE,
+            Err(e) => {
+                throw_error(env, &e);
+                JNI_FALSE
+            }
+        }
+    })
+}
+
+/// The session's cached regions as a JSON array, like `getMemoryRegions`
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeSessionGetMemoryRegions(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        match with_session(handle, |session| regions_json(session.all_regions())) {
+            Ok(json) => new_string(env, json),
+            Err(e) => {
+                throw_error(env, &e);
+                std::ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// `readProcessMemory` on a session
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeSessionReadProcessMemory(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let result = with_session(handle, read_process_memory);
+        respond(env, session_format(handle), result, memory_text)
+    })
+}
+
+/// `extractStringsWithOptions` on a session
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeSessionExtractStrings(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    options: JString,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let options = read_options(env, &options);
+        let format = output_format(&options, session_format(handle));
+        let result = with_session(handle, |session| {
+            let options = options.as_ref().map_err(ExtractError::clone)?;
+            extract_strings_from_process(session, options, None)
+        });
+        let options = options.unwrap_or_default();
+
+        respond(env, format, result, |report| strings_text(report, &options))
+    })
+}
+
+/// `searchRegex` on a session
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeSessionSearchRegex(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    pattern: JString,
+    flags: jint,
+    context_bytes: jint,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let pattern = env
+            .get_string(&pattern)
+            .map(String::from)
+            .map_err(|e| ExtractError::InvalidArgument(format!("Invalid pattern string: {}", e)));
+        let result = with_session(handle, |session| {
+            let pattern = pattern.as_ref().map_err(ExtractError::clone)?;
+            search_regex_in_process(session, pattern, flags, context_bytes.max(0) as usize)
+        });
+
+        respond(env, session_format(handle), result, regex_text)
+    })
+}
+
+/// `searchKeywords` on a session
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeSessionSearchKeywords(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    keywords: JObjectArray,
+    case_insensitive: jboolean,
+    context_bytes: jint,
+) -> jstring {
+    ffi::guard(&mut env, |env| {
+        let keywords = read_string_array(env, &keywords);
+        let result = with_session(handle, |session| {
+            let keywords = keywords.as_ref().map_err(ExtractError::clone)?;
+            let context = context_bytes.max(0) as usize;
+            search_keywords_in_process(session, keywords, case_insensitive != 0, context)
+        });
+
+        respond(env, session_format(handle), result, keywords_text)
+    })
+}
+
+/// `readMemoryRange` on a session
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeSessionReadMemoryRange(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    address: jlong,
+    length: jint,
+) -> jbyteArray {
+    ffi::guard(&mut env, |env| {
+        let array = with_session(handle, |session| {
+            read_memory_range(session.reader(), address as u64, length)
+        })
+        .and_then(|bytes| new_byte_array(env, &bytes));
+
+        match array {
+            Ok(array) => array.into_raw(),
+            Err(e) => {
+                throw_error(env, &e);
+                std::ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// `writeMemoryRange` on a session, which must be of a live process,
+/// keeping the bytes overwritten for `sessionRestorePatches`
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeSessionWriteMemoryRange(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    address: jlong,
+    bytes: JByteArray,
+) -> jint {
+    ffi::guard(&mut env, |env| {
+        let result = read_byte_array(env, &bytes).and_then(|bytes| {
+            with_session(handle, |session| {
+                patch_memory_range(session, address as u64, &bytes)
+            })
+        });
+
+        match result {
+            Ok(written) => written as jint,
+            Err(e) => {
+                throw_error(env, &e);
+                -1
+            }
+        }
+    })
+}
+
+/// Write back the bytes every `sessionWriteMemoryRange` call on a session
+/// overwrote, latest first, returning how many writes were undone
+///
+/// Throws and returns -1 if any couldn't be undone; they are kept, so a
+/// later call retries them.
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeSessionRestorePatches(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+) -> jint {
+    ffi::guard(&mut env, |env| {
+        let result = with_session(handle, restore_patches);
+
+        match result {
+            Ok(restored) => restored as jint,
+            Err(e) => {
+                throw_error(env, &e);
+                -1
+            }
+        }
+    })
+}
+
+/// `readMemoryInto` on a session
+#[no_mangle]
+pub extern "C" fn Java_com_textextractor_NativeMemoryExtractor_nativeSessionReadMemoryInto(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    address: jlong,
+    buffer: JByteBuffer,
+) -> jint {
+    ffi::guard(&mut env, |env| {
+        let result = with_direct_buffer(env, &buffer, |buf| {
+            with_session(handle, |session| {
+                read_memory_into(session.reader(), address as u64, buf)
+            })
+        });
+
+        match result {
+            Ok(read) => read as jint,
+            Err(e) => {
+                throw_error(env, &e);
+                -1
+            }
+        }
+    })
+}
+
+/// Run `f` on the session behind `handle`
+fn with_session<T, F>(handle: jlong, f: F) -> Result<T, ExtractError>
+where
+    F: FnOnce(&mut Session) -> Result<T, ExtractError>,
+{
+    let Some(session) = session::get(handle) else {
+        return Err(ExtractError::InvalidArgument(format!("Invalid session handle: {}", handle)));
+    };
+    // A poisoned lock only means an earlier call panicked mid-scan; the
+    // session itself is still valid
+    let mut session = session.lock().unwrap_or_else(|e| e.into_inner());
+    f(&mut session)
+}
+
+/// Open a one-off session for a call that isn't given a session handle,
+/// with the backend its options select
+fn open_session(pid: i32, strategy: ReaderStrategy) -> Result<Session, ExtractError> {
+    Session::open(pid, strategy)
+}
+
+/// Open a session on the dump at `path`
+fn open_dump_session(path: &Path) -> Result<Session, ExtractError> {
+    let context = format!("Failed to load dump {}", path.display());
+    match dump::load_dump(path) {
+        Ok(dump) => Ok(Session::from_dump(dump)),
+        // A missing dump is a bad path, not a missing process as from_io
+        // would have it
+        Err(e) if matches!(e.kind(), io::ErrorKind::NotFound | io::ErrorKind::InvalidData) => {
+            Err(ExtractError::InvalidArgument(format!("{}: {}", context, e)))
+        }
+        Err(e) => Err(ExtractError::from_io(context, &e)),
+    }
+}
+
+/// Open just a reader, for calls that don't need the maps; they take no
+/// options, so the backend is chosen automatically
+fn open_reader(pid: i32) -> Result<Box<dyn MemoryReader>, ExtractError> {
+    reader::open_reader(pid, ReaderStrategy::Auto)
+        .map_err(|e| ExtractError::from_io("Failed to open process memory", &e))
+}
+
+/// Read the contents of every readable memory region of a process
+fn read_process_memory(session: &mut Session) -> Result<Report, ExtractError> {
+    let mut report = Report::new(session.pid());
+    let regions = session.regions(&RegionFilter::default());
+    report.stats.reader = Some(session.reader_name());
+
+    let started = session.start_scan();
+    let mut control = started.control();
+    let mut scan = Scan::default();
+    report.stats.truncated =
+        scan::walk_regions(session.reader(), &regions, &mut ReadOnly, &mut scan, &mut control);
+    report.add_scan(scan);
+    report.finish();
+    Ok(report)
+}
+
+/// A carver: writes what it finds in `regions` to a directory, one file each
+type Carver<T> = fn(
+    &mut dyn MemoryReader,
+    &[MemoryRegion],
+    &Path,
+    &mut dyn ScanSink<T>,
+    &mut ScanControl,
+) -> io::Result<Option<scan::StopReason>>;
+
+/// Create the output directory `dir` if missing
+fn create_output_dir(dir: &Path) -> Result<(), ExtractError> {
+    if dir.as_os_str().is_empty() {
+        return Err(ExtractError::InvalidArgument("Empty output directory".to_string()));
+    }
+    fs::create_dir_all(dir).map_err(|e| ExtractError::from_output_io(dir.display(), &e))
+}
+
+/// Run `carve` over the readable regions of a process that pass `filter`,
+/// writing to `dir`, and return the report along with what was carved for
+/// the caller to file in it
+fn dump_to_dir<T>(
+    session: &mut Session,
+    filter: &RegionFilter,
+    dir: &Path,
+    carve: Carver<T>,
+) -> Result<(Report, Vec<T>), ExtractError> {
+    create_output_dir(dir)?;
+    let mut report = Report::new(session.pid());
+    report.process = session_process_info(session, &mut report.errors)?;
+    let regions = session.regions(filter);
+    report.stats.reader = Some(session.reader_name());
+
+    let started = session.start_scan();
+    let mut control = started.control();
+    let mut scan = Scan::default();
+    report.stats.truncated = carve(session.reader(), &regions, dir, &mut scan, &mut control)
+        .map_err(|e| ExtractError::from_output_io(dir.display(), &e))?;
+    let results = report.add_scan(scan);
+    report.finish();
+
+    Ok((report, results))
+}
+
+/// Dump the readable regions of a process that pass `filter` to `dir`, then
+/// write the report there as the manifest
+fn dump_process(
+    session: &mut Session,
+    filter: &RegionFilter,
+    dir: &Path,
+) -> Result<Report, ExtractError> {
+    let (mut report, files) = dump_to_dir(session, filter, dir, dump::dump_regions)?;
+    report.files = Some(files);
+    dump::write_manifest(dir, &report.to_json())
+        .map_err(|e| ExtractError::from_output_io(dir.display(), &e))?;
+    Ok(report)
+}
+
+/// Carve the DEX files out of every readable region of a process into `dir`
+fn dump_dex_files(session: &mut Session, dir: &Path) -> Result<Report, ExtractError> {
+    let filter = RegionFilter::default();
+    let (mut report, dex_files) = dump_to_dir(session, &filter, dir, dex::carve_dex_files)?;
+    report.dex_files = Some(dex_files);
+    Ok(report)
+}
+
+/// Find the Realm files in a process and write them to `dir`
+fn dump_realm_files(session: &mut Session, dir: &Path) -> Result<Report, ExtractError> {
+    let filter = RegionFilter::default();
+    let (mut report, files) = dump_to_dir(session, &filter, dir, realm::carve_realm_files)?;
+    report.realm_files = Some(files);
+    Ok(report)
+}
+
+/// Find the SQLite databases in a process and write them to `dir`
+fn dump_sqlite_databases(session: &mut Session, dir: &Path) -> Result<Report, ExtractError> {
+    let filter = RegionFilter::default();
+    let (mut report, databases) = dump_to_dir(session, &filter, dir, sqlite::carve_databases)?;
+    report.databases = Some(databases);
+    Ok(report)
+}
+
+/// Find the certificates in a process and write them to `dir`
+fn dump_certificates(session: &mut Session, dir: &Path) -> Result<Report, ExtractError> {
+    let filter = RegionFilter::default();
+    let (mut report, certificates) =
+        dump_to_dir(session, &filter, dir, x509::carve_certificates)?;
+    report.certificates = Some(certificates);
+    Ok(report)
+}
+
+/// Find the images in a process and write them to `dir`
+fn dump_images(session: &mut Session, dir: &Path) -> Result<Report, ExtractError> {
+    let filter = RegionFilter::default();
+    let (mut report, images) = dump_to_dir(session, &filter, dir, image::carve_images)?;
+    report.images = Some(images);
+    Ok(report)
+}
+
+/// Find the bitmaps in a process and write them to `dir` as PNG files
+fn dump_bitmaps(session: &mut Session, dir: &Path) -> Result<Report, ExtractError> {
+    let filter = RegionFilter::default();
+    let (mut report, bitmaps) = dump_to_dir(session, &filter, dir, bitmap::recover_bitmaps)?;
+    report.bitmaps = Some(bitmaps);
+    Ok(report)
+}
+
+/// Find the archives in a process and write them to `dir`
+fn dump_archives(session: &mut Session, dir: &Path) -> Result<Report, ExtractError> {
+    let filter = RegionFilter::default();
+    let (mut report, archives) = dump_to_dir(session, &filter, dir, zip::carve_archives)?;
+    report.archives = Some(archives);
+    Ok(report)
+}
+
+/// Rebuild a library mapped in a process into an ELF file at `path`
+fn dump_library(session: &mut Session, name: &str, path: &Path) -> Result<Report, ExtractError> {
+    let regions = session.regions(&RegionFilter::default());
+    let library = library::find_library(session.reader(), &regions, name).ok_or_else(|| {
+        let message = format!("No ELF file {} is mapped in PID {}", name, session.pid());
+        ExtractError::InvalidArgument(message)
+    })?;
+    let mut report = Report::new(session.pid());
+    report.process = session_process_info(session, &mut report.errors)?;
+    report.stats.reader = Some(session.reader_name());
+
+    let started = session.start_scan();
+    let mut control = started.control();
+    let mut scan = Scan::default();
+    report.stats.truncated = library::rebuild_library(
+        session.reader(),
+        &regions,
+        &library,
+        path,
+        &mut scan,
+        &mut control,
+        &mut report.errors,
+    )
+    .map_err(|e| ExtractError::from_output_io(path.display(), &e))?;
+    report.files = Some(report.add_scan(scan));
+    report.finish();
+
+    Ok(report)
+}
+
+/// Write the shared memory objects of a process whose name matches
+/// `pattern` to `dir`
+fn dump_shared_memory(
+    session: &mut Session,
+    pattern: &str,
+    dir: &Path,
+) -> Result<Report, ExtractError> {
+    let pid = session.pid();
+    let objects: Vec<_> = shmem::list_shared_memory(pid, session.all_regions())
+        .map_err(|e| ExtractError::from_io(format_args!("PID {}", pid), &e))?
+        .into_iter()
+        .filter(|object| filter::wildcard_match(pattern, &object.name))
+        .collect();
+    if objects.is_empty() {
+        let message = format!("No shared memory matching {} in PID {}", pattern, pid);
+        return Err(ExtractError::InvalidArgument(message));
+    }
+    create_output_dir(dir)?;
+
+    let mut report = Report::new(pid);
+    report.process = session_process_info(session, &mut report.errors)?;
+    report.stats.reader = Some(session.reader_name());
+
+    let started = session.start_scan();
+    let mut control = started.control();
+    let mut scan = Scan::default();
+    for object in &objects {
+        let reader = session.reader();
+        report.stats.truncated =
+            shmem::dump_shared_memory(pid, object, reader, dir, &mut scan, &mut control)
+                .map_err(|e| ExtractError::from_output_io(dir.display(), &e))?;
+        if report.stats.truncated.is_some() {
+            break;
+        }
+    }
+    report.files = Some(report.add_scan(scan));
+    report.shared_memory = Some(objects);
+    report.finish();
+
+    Ok(report)
+}
+
+/// Stop every thread of a process and write its core to `path`, listing
+/// the threads in the report
+fn dump_core(session: &mut Session, path: &str) -> Result<Report, ExtractError> {
+    let regions = session.regions(&RegionFilter::default());
+    dump_stopped(session, path, |session, registers, scan, control| {
+        let pid = session.pid();
+        coredump::write_core(pid, session.reader(), &regions, registers, path, scan, control)
+    })
+}
+
+/// Stop every thread of a process and write a minidump of it to `path`,
+/// listing the threads in the report
+fn dump_minidump(
+    session: &mut Session,
+    path: &str,
+    options: &MinidumpOptions,
+) -> Result<Report, ExtractError> {
+    let memory = options
+        .regions
+        .as_ref()
+        .map(|filter| session.regions(filter))
+        .unwrap_or_default();
+    let regions = session.all_regions().to_vec();
+    dump_stopped(session, path, |session, registers, scan, control| {
+        let snapshot = minidump::Snapshot {
+            pid: session.pid(),
+            regions: &regions,
+            threads: registers,
+        };
+        minidump::write_minidump(session.reader(), &snapshot, &memory, path, scan, control)
+    })
+}
+
+/// Find the classes and objects in the ART heap of a process and write
+/// them to `path` as a heap dump, counting them in the report's stats
+fn dump_java_heap(session: &mut Session, path: &Path) -> Result<Report, ExtractError> {
+    if path.as_os_str().is_empty() {
+        return Err(ExtractError::InvalidArgument("Empty output path".to_string()));
+    }
+    let mut report = Report::new(session.pid());
+    report.process = session_process_info(session, &mut report.errors)?;
+    let all_regions = session.all_regions().to_vec();
+    let regions: Vec<MemoryRegion> =
+        all_regions.iter().filter(|region| art::is_heap(region)).cloned().collect();
+    if regions.is_empty() {
+        let message = format!("PID {} has no ART heap", session.pid());
+        return Err(ExtractError::InvalidArgument(message));
+    }
+    report.stats.reader = Some(session.reader_name());
+
+    let started = session.start_scan();
+    let mut control = started.control();
+    let mut scan = Scan::default();
+    // The collector moves objects, so stop it for a consistent heap
+    let frozen = session.is_live().then(|| {
+        Freeze::new(session.pid(), freeze::DEFAULT_TIMEOUT)
+            .map_err(|e| report.errors.push(format!("Failed to freeze, reading live: {}", e)))
+            .ok()
+    });
+    let mut classes = Scan::default();
+    let errors = &mut report.errors;
+    let mut stopped =
+        java_classes(session, &all_regions, &regions, &mut classes, &mut control, errors);
+    if stopped.is_none() {
+        let heap = art::JavaHeap::read(session.reader(), classes.results);
+        let reader = session.reader();
+        stopped = hprof::write_heap(reader, &heap, &regions, path, &mut scan, &mut control)
+            .map_err(|e| ExtractError::from_output_io(path.display(), &e))?;
+    }
+    thaw_target(frozen.flatten(), &mut report.errors);
+    report.stats.truncated = stopped;
+    report.add_scan(scan);
+    report.finish();
+    Ok(report)
+}
+
+/// Hold every thread of a process in ptrace-stop while `write` saves it to
+/// `path`, then build the report of what was saved
+fn dump_stopped<F>(session: &mut Session, path: &str, write: F) -> Result<Report, ExtractError>
+where
+    F: FnOnce(
+        &mut Session,
+        &[coredump::ThreadRegisters],
+        &mut Scan<()>,
+        &mut ScanControl,
+    ) -> io::Result<Option<scan::StopReason>>,
+{
+    if path.is_empty() {
+        return Err(ExtractError::InvalidArgument("Empty output path".to_string()));
+    }
+    let pid = session.pid();
+    let mut report = Report::new(pid);
+    report.process = Some(read_process_info(pid, &mut report.errors)?);
+    report.stats.reader = Some(session.reader_name());
+
+    let stopped = StoppedThreads::stop(pid, &mut report.errors)
+        .map_err(|e| ExtractError::from_io("Failed to stop threads", &e))?;
+    let registers = stopped.registers(&mut report.errors);
+    report.threads = threads::find_thread_stacks(pid, session.all_regions())
+        .map(|stacks| {
+            let stopped = stopped.tids();
+            stacks.into_iter().filter(|stack| stopped.contains(&stack.tid)).collect()
+        })
+        .ok();
+
+    let started = session.start_scan();
+    let mut control = started.control();
+    let mut scan = Scan::default();
+    report.stats.truncated = write(session, &registers, &mut scan, &mut control)
+        .map_err(|e| ExtractError::from_output_io(path, &e))?;
+    drop(stopped);
+    report.add_scan(scan);
+    report.finish();
+    Ok(report)
+}
+
+/// Stop every thread of `pid` and report its registers
+fn thread_registers(pid: i32) -> Result<Report, ExtractError> {
+    let mut report = Report::new(pid);
+    report.process = Some(read_process_info(pid, &mut report.errors)?);
+
+    let stopped = StoppedThreads::stop(pid, &mut report.errors)
+        .map_err(|e| ExtractError::from_io("Failed to stop threads", &e))?;
+    report.registers = Some(registers::read_threads(pid, &stopped, &mut report.errors));
+    drop(stopped);
+    report.finish();
+    Ok(report)
+}
+
+fn registers_text(report: &Report) -> String {
+    let threads = report.registers.as_deref().unwrap_or_default();
+    let mut result = format!("Registers of {} threads of PID {}:\n", threads.len(), report.pid);
+    for thread in threads {
+        result.push_str(&format!(
+            "  {} {} ({}): pc=0x{:x} sp=0x{:x}\n",
+            thread.tid,
+            thread.name,
+            thread.arch.name(),
+            thread.pc,
+            thread.sp
+        ));
+        for registers in thread.registers.chunks(4) {
+            let line: Vec<String> = registers
+                .iter()
+                .map(|register| format!("{}=0x{:x}", register.name, register.value))
+                .collect();
+            result.push_str(&format!("    {}\n", line.join(" ")));
+        }
+    }
+
+    result
+}
+
+/// Stop every thread of a session's process and unwind its stack
+fn backtraces(session: &mut Session) -> Result<Report, ExtractError> {
+    let pid = session.pid();
+    let mut report = Report::new(pid);
+    report.process = Some(read_process_info(pid, &mut report.errors)?);
+    report.stats.reader = Some(session.reader_name());
+
+    let stopped = StoppedThreads::stop(pid, &mut report.errors)
+        .map_err(|e| ExtractError::from_io("Failed to stop threads", &e))?;
+    let threads = registers::read_threads(pid, &stopped, &mut report.errors);
+    let modules = pointers::modules(session.all_regions());
+    let mut unwinder = Unwinder::new(session.reader(), &modules);
+    let mut backtraces: Vec<_> = threads.iter().map(|thread| unwinder.backtrace(thread)).collect();
+    drop(stopped);
+    let mut symbolizer = Symbolizer::new(session.all_regions());
+    for frames in backtraces.iter_mut().map(|backtrace| &mut backtrace.frames) {
+        for frame in frames.iter_mut() {
+            // Callers' pcs are return addresses, after the call
+            frame.symbol = match frame.method {
+                unwind::Method::Context => symbolizer.symbol(frame.pc),
+                _ => symbolizer.caller_symbol(frame.pc),
+            };
+        }
+    }
+    report.backtraces = Some(backtraces);
+    report.finish();
+    Ok(report)
+}
+
+fn backtraces_text(report: &Report) -> String {
+    let backtraces = report.backtraces.as_deref().unwrap_or_default();
+    let mut result = format!("Backtraces of {} threads of PID {}:\n", backtraces.len(), report.pid);
+    for backtrace in backtraces {
+        result.push_str(&format!("  {} {}:\n", backtrace.tid, backtrace.name));
+        for (i, frame) in backtrace.frames.iter().enumerate() {
+            let mut place = match (&frame.module, frame.offset) {
+                (Some(module), Some(offset)) => format!("  {}+0x{:x}", module, offset),
+                _ => String::new(),
+            };
+            if let Some(symbol) = &frame.symbol {
+                place.push_str(&format!(" ({})", symbol));
+            }
+            let guessed = if frame.method == unwind::Method::FramePointer {
+                " (frame pointer)"
+            } else {
+                ""
+            };
+            result.push_str(&format!("    #{:02} pc 0x{:x}{}{}\n", i, frame.pc, place, guessed));
+        }
+        if let Some(stopped) = &backtrace.stopped {
+            result.push_str(&format!("    ({})\n", stopped));
+        }
+    }
+
+    result
+}
+
+fn core_text(report: &Report) -> String {
+    let threads = report.threads.as_deref().unwrap_or_default();
+    let mut result = format!("Core of PID {} with {} threads:\n", report.pid, threads.len());
+    for region in &report.regions {
+        match &region.error {
+            None => result.push_str(&format!("{}\n", region.region)),
+            Some(e) => result.push_str(&format!("{} [unreadable: {}]\n", region.region, e)),
+        }
+    }
+
+    result.push_str(&format!(
+        "\nWrote {} bytes from {} regions ({} unreadable) via {}\n",
+        report.stats.bytes_read,
+        report.stats.regions_scanned,
+        report.stats.regions_unreadable,
+        report.stats.reader.unwrap_or("unknown reader")
+    ));
+    push_truncated(&mut result, report);
+
+    result
+}
+
+fn dex_text(report: &Report) -> String {
+    let files = report.dex_files.as_deref().unwrap_or_default();
+    let mut result = format!("DEX files of PID {}:\n", report.pid);
+    for dex in files {
+        let checksum = if dex.checksum_valid { "" } else { " (checksum mismatch)" };
+        result.push_str(&format!(
+            "{} version {:03} {} bytes sha256:{}{}\n",
+            dex.file.file, dex.version, dex.file.size, dex.file.sha256, checksum
+        ));
+    }
+
+    result.push_str(&format!(
+        "\nWrote {} files after reading {} bytes ({} regions unreadable) via {}\n",
+        files.len(),
+        report.stats.bytes_read,
+        report.stats.regions_unreadable,
+        report.stats.reader.unwrap_or("unknown reader")
+    ));
+    push_truncated(&mut result, report);
+
+    result
+}
+
+fn realm_text(report: &Report) -> String {
+    let files = report.realm_files.as_deref().unwrap_or_default();
+    let mut result = format!("Realm files of PID {}:\n", report.pid);
+    for realm in files {
+        let cut = if realm.file.size < realm.logical_size { " (cut short)" } else { "" };
+        result.push_str(&format!(
+            "{} format {} {} bytes sha256:{}{}\n",
+            realm.file.file, realm.format_version, realm.file.size, realm.file.sha256, cut
+        ));
+        if !realm.tables.is_empty() {
+            result.push_str(&format!("  tables: {}\n", realm.tables.join(", ")));
+        }
+    }
+
+    result.push_str(&format!(
+        "\nWrote {} files after reading {} bytes ({} regions unreadable) via {}\n",
+        files.len(),
+        report.stats.bytes_read,
+        report.stats.regions_unreadable,
+        report.stats.reader.unwrap_or("unknown reader")
+    ));
+    push_truncated(&mut result, report);
+
+    result
+}
+
+fn certificates_text(report: &Report) -> String {
+    let certificates = report.certificates.as_deref().unwrap_or_default();
+    let mut result = format!("Certificates of PID {}:\n", report.pid);
+    for carved in certificates {
+        let certificate = &carved.certificate;
+        let mut kind = String::new();
+        if certificate.ca {
+            kind.push_str(" CA");
+        }
+        if certificate.self_signed {
+            kind.push_str(" self-signed");
+        }
+        result.push_str(&format!(
+            "{} at 0x{:x}{}: {}\n  issuer: {}\n  valid {} to {}, {}, {}\n  {}\n",
+            carved.file.file,
+            carved.file.start,
+            kind,
+            certificate.subject,
+            certificate.issuer,
+            certificate.not_before,
+            certificate.not_after,
+            certificate.public_key,
+            certificate.signature_algorithm,
+            certificate.pin
+        ));
+    }
+
+    result.push_str(&format!(
+        "\nWrote {} files after reading {} bytes ({} regions unreadable) via {}\n",
+        certificates.len(),
+        report.stats.bytes_read,
+        report.stats.regions_unreadable,
+        report.stats.reader.unwrap_or("unknown reader")
+    ));
+    push_truncated(&mut result, report);
+
+    result
+}
+
+fn images_text(report: &Report) -> String {
+    let images = report.images.as_deref().unwrap_or_default();
+    let mut result = format!("Images of PID {}:\n", report.pid);
+    for image in images {
+        result.push_str(&format!(
+            "{} at 0x{:x}: {:?} {}x{}, {} bytes sha256:{}\n",
+            image.file.file,
+            image.file.start,
+            image.format,
+            image.width,
+            image.height,
+            image.file.size,
+            image.file.sha256
+        ));
+    }
+
+    result.push_str(&format!(
+        "\nWrote {} files after reading {} bytes ({} regions unreadable) via {}\n",
+        images.len(),
+        report.stats.bytes_read,
+        report.stats.regions_unreadable,
+        report.stats.reader.unwrap_or("unknown reader")
+    ));
+    push_truncated(&mut result, report);
+
+    result
+}
+
+fn bitmaps_text(report: &Report) -> String {
+    let bitmaps = report.bitmaps.as_deref().unwrap_or_default();
+    let mut result = format!("Bitmaps of PID {}:\n", report.pid);
+    for bitmap in bitmaps {
+        result.push_str(&format!(
+            "{} at 0x{:x}: {}x{}, {} bytes sha256:{}\n",
+            bitmap.file.file,
+            bitmap.file.start,
+            bitmap.width,
+            bitmap.height,
+            bitmap.file.size,
+            bitmap.file.sha256
+        ));
+    }
+
+    result.push_str(&format!(
+        "\nWrote {} files after reading {} bytes ({} regions unreadable) via {}\n",
+        bitmaps.len(),
+        report.stats.bytes_read,
+        report.stats.regions_unreadable,
+        report.stats.reader.unwrap_or("unknown reader")
+    ));
+    push_truncated(&mut result, report);
+
+    result
+}
+
+fn archives_text(report: &Report) -> String {
+    let archives = report.archives.as_deref().unwrap_or_default();
+    let mut result = format!("Archives of PID {}:\n", report.pid);
+    for archive in archives {
+        result.push_str(&format!(
+            "{} at 0x{:x}: {:?}, {} entries, {} of {} bytes sha256:{}\n",
+            archive.file.file,
+            archive.file.start,
+            archive.kind,
+            archive.entries.len(),
+            archive.file.size,
+            archive.length,
+            archive.file.sha256
+        ));
+    }
+
+    result.push_str(&format!(
+        "\nWrote {} files after reading {} bytes ({} regions unreadable) via {}\n",
+        archives.len(),
+        report.stats.bytes_read,
+        report.stats.regions_unreadable,
+        report.stats.reader.unwrap_or("unknown reader")
+    ));
+    push_truncated(&mut result, report);
+
+    result
+}
+
+fn sqlite_text(report: &Report) -> String {
+    let databases = report.databases.as_deref().unwrap_or_default();
+    let mut result = format!("SQLite databases of PID {}:\n", report.pid);
+    for database in databases {
+        result.push_str(&format!(
+            "{} {:?} {} pages of {} bytes, {} missing, sha256:{}\n",
+            database.file.file,
+            database.source,
+            database.pages,
+            database.page_size,
+            database.missing_pages,
+            database.file.sha256
+        ));
+        for object in &database.schema {
+            let rows = object.rows.map(|rows| format!(", {} rows", rows)).unwrap_or_default();
+            result.push_str(&format!("  {} {}{}\n", object.kind, object.name, rows));
+        }
+    }
+
+    result.push_str(&format!(
+        "\nWrote {} files after reading {} bytes ({} regions unreadable) via {}\n",
+        databases.len(),
+        report.stats.bytes_read,
+        report.stats.regions_unreadable,
+        report.stats.reader.unwrap_or("unknown reader")
+    ));
+    push_truncated(&mut result, report);
+
+    result
+}
+
+/// One line per finding, most severe first
+#[cfg(feature = "yara")]
+fn yara_text(report: &Report) -> String {
+    let matches = report.yara_matches.as_deref().unwrap_or_default();
+    let mut result = format!("YARA matches in PID {}: {}\n", report.pid, matches.len());
+    for found in matches {
+        result.push_str(&format!("{} {} ({} bytes)", found.rule, found.location, found.length));
+        if !found.tags.is_empty() {
+            result.push_str(&format!(" #{}", found.tags.join(" #")));
+        }
+        result.push('\n');
+    }
+    push_truncated(&mut result, report);
+
+    result
+}
+
+#[cfg(feature = "capstone")]
+fn disassembly_text(report: &Report) -> String {
+    let Some(disassembly) = &report.disassembly else {
+        return String::new();
+    };
+    let place = match (&disassembly.module, disassembly.offset) {
+        (Some(module), Some(offset)) => format!(" ({}+0x{:x})", module, offset),
+        _ => String::new(),
+    };
+    let mut result = format!(
+        "Disassembly of PID {} at 0x{:x}{}, {}{}:\n",
+        report.pid,
+        disassembly.address,
+        place,
+        disassembly.arch.name(),
+        if disassembly.thumb { " Thumb" } else { "" }
+    );
+    let mut functions = disassembly.functions.iter().peekable();
+    for instruction in &disassembly.instructions {
+        if let Some(function) = functions.next_if(|f| f.start == instruction.address) {
+            let truncated = if function.truncated { ", truncated" } else { "" };
+            result.push_str(&format!(
+                "\nfunction 0x{:x}-0x{:x}{}:\n",
+                function.start, function.end, truncated
+            ));
+        }
+        let bytes: String = instruction.bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        result.push_str(&format!(
+            "  0x{:x}  {:<16} {} {}\n",
+            instruction.address, bytes, instruction.mnemonic, instruction.operands
+        ));
+    }
+
+    result
+}
+
+fn protobuf_text(report: &Report) -> String {
+    let messages = report.protobuf_messages.as_deref().unwrap_or_default();
+    let mut result = format!("Protobuf messages in PID {}: {}\n", report.pid, messages.len());
+    for message in messages {
+        let rendered = protobuf::render(&message.fields);
+        let preview: String = rendered.chars().take(200).collect();
+        let more = if preview.len() < rendered.len() { "..." } else { "" };
+        result.push_str(&format!(
+            "{} {} bytes, {} fields: {}{}\n",
+            message.location,
+            message.length,
+            message.fields.len(),
+            preview,
+            more
+        ));
+    }
+    push_truncated(&mut result, report);
+
+    result
+}
+
+fn urls_text(report: &Report) -> String {
+    let urls = report.urls.as_deref().unwrap_or_default();
+    let hosts = report.hosts.as_deref().unwrap_or_default();
+    let mut result =
+        format!("URLs in PID {}: {} on {} hosts\n", report.pid, urls.len(), hosts.len());
+    for host in hosts {
+        let mut schemes = host.schemes.join(", ");
+        for port in &host.ports {
+            schemes.push_str(&format!(", port {}", port));
+        }
+        result.push_str(&format!(
+            "  {}: {} URLs seen {} times ({})\n",
+            host.host, host.urls, host.count, schemes
+        ));
+    }
+    for url in urls {
+        result.push_str(&format!("{} {} x{}\n", url.location, url.url, url.count));
+    }
+    push_truncated(&mut result, report);
+
+    result
+}
+
+fn documents_text(report: &Report) -> String {
+    let documents = report.documents.as_deref().unwrap_or_default();
+    let mut result = format!("Documents in PID {}: {}\n", report.pid, documents.len());
+    for document in documents {
+        let preview: String = document.text.chars().take(200).collect();
+        let more = if preview.len() < document.text.len() { "..." } else { "" };
+        if let Some(message) = &document.http {
+            let start = match (&message.method, &message.target, message.status) {
+                (Some(method), Some(target), _) => format!("{} {}", method, target),
+                (_, _, Some(status)) => {
+                    format!("{} {}", status, message.reason.as_deref().unwrap_or(""))
+                }
+                _ => String::new(),
+            };
+            result.push_str(&format!(
+                "{} HTTP {}, {} headers, {} byte body{}",
+                document.location,
+                start.trim_end(),
+                message.headers.len(),
+                message.body_length,
+                if message.truncated { " (truncated)" } else { "" }
+            ));
+            result.push('\n');
+            continue;
+        }
+        result.push_str(&format!(
+            "{} {:?}, {} bytes: {}{}\n",
+            document.location, document.format, document.length, preview, more
+        ));
+    }
+    push_truncated(&mut result, report);
+
+    result
+}
+
+fn base64_text(report: &Report) -> String {
+    let blobs = report.base64_blobs.as_deref().unwrap_or_default();
+    let mut result = format!("Base64 blobs in PID {}: {}\n", report.pid, blobs.len());
+    for blob in blobs {
+        let indent = "  ".repeat(blob.depth - 1);
+        let at = match blob.decoded_offset {
+            Some(offset) => format!("+{} in the blob above", offset),
+            None => blob.location.to_string(),
+        };
+        result.push_str(&format!(
+            "{}{} ({} chars, {} bytes decoded): {}\n",
+            indent, at, blob.length, blob.decoded_length, blob.decoded
+        ));
+        for value in &blob.strings {
+            result.push_str(&format!("{}  string: {}\n", indent, value));
+        }
+        for finding in &blob.findings {
+            result.push_str(&format!(
+                "{}  [{:?}] {} {}\n",
+                indent, finding.severity, finding.rule, finding.value
+            ));
+        }
+    }
+    push_truncated(&mut result, report);
+
+    result
+}
+
+fn compressed_text(report: &Report) -> String {
+    let streams = report.compressed_streams.as_deref().unwrap_or_default();
+    let mut result = format!("Compressed streams in PID {}: {}\n", report.pid, streams.len());
+    for stream in streams {
+        let cut = if stream.cut { ", cut" } else { "" };
+        result.push_str(&format!(
+            "{} {:?} ({} bytes, {} inflated{}): {}\n",
+            stream.location,
+            stream.format,
+            stream.compressed_length,
+            stream.inflated_length,
+            cut,
+            stream.inflated
+        ));
+        for value in &stream.strings {
+            result.push_str(&format!("  string: {}\n", value));
+        }
+        for finding in &stream.findings {
+            result.push_str(&format!(
+                "  [{:?}] {} {}\n",
+                finding.severity, finding.rule, finding.value
+            ));
+        }
+    }
+    push_truncated(&mut result, report);
+
+    result
+}
+
+fn deobfuscated_text(report: &Report) -> String {
+    let strings = report.deobfuscated.as_deref().unwrap_or_default();
+    let mut result = format!("Deobfuscated strings in PID {}: {}\n", report.pid, strings.len());
+    for string in strings {
+        result.push_str(&format!(
+            "{} {:?} 0x{:02x} ({} bytes): {}\n",
+            string.location, string.method, string.key, string.length, string.text
+        ));
+    }
+    push_truncated(&mut result, report);
+
+    result
+}
+
+fn values_text(report: &Report) -> String {
+    let Some(values) = &report.values else {
+        return format!("No value scan of PID {}\n", report.pid);
+    };
+    let mut result =
+        format!("{} values in PID {}: {}\n", values.value_type, report.pid, values.count);
+    for found in &values.matches {
+        match found.previous {
+            Some(previous) => result.push_str(&format!(
+                "{} = {} (was {})\n",
+                found.location, found.value, previous
+            )),
+            None => result.push_str(&format!("{} = {}\n", found.location, found.value)),
+        }
+    }
+    if values.matches.len() < values.count {
+        result.push_str(&format!("... {} more\n", values.count - values.matches.len()));
+    }
+    push_truncated(&mut result, report);
+
+    result
+}
+
+fn value_snapshot_text(report: &Report) -> String {
+    let Some(snapshot) = &report.value_snapshot else {
+        return format!("No snapshot of PID {}\n", report.pid);
+    };
+    let mut result = format!(
+        "Saved {} bytes of PID {} to compare as {} values\n",
+        snapshot.length, report.pid, snapshot.value_type
+    );
+    push_truncated(&mut result, report);
+
+    result
+}
+
+fn snapshot_text(report: &Report) -> String {
+    let mut result = format!(
+        "Snapshot of PID {}: {} regions, {} strings\n",
+        report.pid,
+        report.regions.len(),
+        report.stats.results
+    );
+    push_truncated(&mut result, report);
+
+    result
+}
+
+fn diff_text(report: &Report) -> String {
+    let Some(diff) = &report.diff else {
+        return format!("No snapshot of PID {} to compare\n", report.pid);
+    };
+    let mut result = format!(
+        "Changes in PID {} over {} ms: {} new, {} removed and {} changed regions, \
+         {} new and {} gone strings\n",
+        report.pid,
+        diff.elapsed_ms,
+        diff.new_regions.len(),
+        diff.removed_regions.len(),
+        diff.changed_regions.len(),
+        diff.new_strings.len(),
+        diff.gone_strings.len()
+    );
+    for region in &diff.new_regions {
+        result.push_str(&format!("+ {}\n", region));
+    }
+    for region in &diff.removed_regions {
+        result.push_str(&format!("- {}\n", region));
+    }
+    for change in &diff.changed_regions {
+        let pages = |pages: Option<u64>| pages.map_or("?".to_string(), |pages| pages.to_string());
+        result.push_str(&format!(
+            "~ {} (was {} bytes {}, resident pages {} -> {})\n",
+            change.region,
+            change.size_before,
+            change.perms_before,
+            pages(change.resident_pages_before),
+            pages(change.resident_pages)
+        ));
+    }
+    for string in &diff.new_strings {
+        result.push_str(&format!("+ {}: {}\n", string.location, string.value));
+    }
+    for value in &diff.gone_strings {
+        result.push_str(&format!("- {}\n", value));
+    }
+    push_truncated(&mut result, report);
+
+    result
+}
+
+fn pointer_map_text(report: &Report) -> String {
+    let Some(map) = &report.pointer_map else {
+        return format!("No pointer map of PID {}\n", report.pid);
+    };
+    let mut result = format!(
+        "Pointer map of PID {}: {} {}-byte pointers, {} modules\n",
+        report.pid, map.pointers, map.width, map.modules
+    );
+    push_truncated(&mut result, report);
+
+    result
+}
+
+fn pointer_paths_text(report: &Report) -> String {
+    let paths = report.pointer_paths.as_deref().unwrap_or_default();
+    let mut result = format!("Pointer paths in PID {}: {}\n", report.pid, paths.len());
+    for path in paths {
+        let (first, rest) = path.offsets.split_first().unwrap_or((&0, &[]));
+        let mut line = format!("[{}+0x{:x}]", path.module, first);
+        for (index, offset) in rest.iter().enumerate() {
+            if index + 1 < rest.len() {
+                line = format!("[{} + 0x{:x}]", line, offset);
+            } else {
+                line = format!("{} + 0x{:x}", line, offset);
+            }
+        }
+        result.push_str(&line);
+        result.push('\n');
+    }
+
+    result
+}
+
+fn pointer_chain_text(report: &Report) -> String {
+    let Some(chain) = &report.pointer_chain else {
+        return format!("No pointer chain in PID {}\n", report.pid);
+    };
+    let mut result = format!(
+        "{} at 0x{:x} in PID {} leads to 0x{:x}\n",
+        chain.module, chain.base, report.pid, chain.address
+    );
+    for step in &chain.steps {
+        result.push_str(&format!("  0x{:x} -> 0x{:x}\n", step.address, step.value));
+    }
+
+    result
+}
+
+fn private_keys_text(report: &Report) -> String {
+    let keys = report.private_keys.as_deref().unwrap_or_default();
+    let mut result = format!("Private keys in PID {}: {}\n", report.pid, keys.len());
+    for key in keys {
+        result.push_str(&format!(
+            "[{:?}] {:?} {} {} ({:?}, {} bytes, seen {} times)\n",
+            key.severity,
+            key.key.format,
+            key.location,
+            key.key.algorithm,
+            key.encoding,
+            key.length,
+            key.count
+        ));
+        if let Some(pin) = &key.key.pin {
+            result.push_str(&format!("  pin: {}\n", pin));
+        }
+        if let Some(file) = &key.file {
+            result.push_str(&format!("  written to {}\n", file));
+        }
+    }
+    push_truncated(&mut result, report);
+
+    result
+}
+
+fn aes_keys_text(report: &Report) -> String {
+    let keys = report.aes_keys.as_deref().unwrap_or_default();
+    let mut result = format!("AES keys in PID {}: {}\n", report.pid, keys.len());
+    for key in keys {
+        result.push_str(&format!(
+            "[{:?}] AES-{} {} {:?} words (seen {} times)\n  key: {}\n",
+            key.severity,
+            key.bits,
+            key.location,
+            key.word_order,
+            key.count,
+            key.key
+        ));
+        if key.bit_errors > 0 {
+            result.push_str(&format!("  {} bit errors\n", key.bit_errors));
+        }
+    }
+    push_truncated(&mut result, report);
+
+    result
+}
+
+fn tls_secrets_text(report: &Report) -> String {
+    let secrets = report.tls_secrets.as_deref().unwrap_or_default();
+    let mut result = format!("TLS secrets in PID {}: {}\n", report.pid, secrets.len());
+    for secret in secrets {
+        let id = secret.client_random.as_ref().or(secret.session_id.as_ref());
+        result.push_str(&format!(
+            "{} {} {} (seen {} times)\n",
+            secret.label.name(),
+            id.map(String::as_str).unwrap_or_default(),
+            secret.location,
+            secret.count
+        ));
+    }
+    push_truncated(&mut result, report);
+
+    result
+}
+
+fn secrets_text(report: &Report) -> String {
+    let mut findings: Vec<_> = report.findings.iter().flatten().collect();
+    findings.sort_by_key(|finding| std::cmp::Reverse(finding.severity));
+    let mut result = format!("Secrets in PID {}: {}\n", report.pid, findings.len());
+    let mut by_category: BTreeMap<String, usize> = BTreeMap::new();
+    for finding in &findings {
+        *by_category.entry(format!("{:?}", finding.category)).or_default() += 1;
+    }
+    if by_category.len() > 1 {
+        let counts: Vec<String> =
+            by_category.iter().map(|(category, n)| format!("{} {}", category, n)).collect();
+        result.push_str(&format!("By category: {}\n", counts.join(", ")));
+    }
+    for finding in findings {
+        result.push_str(&format!(
+            "[{:?}] {} {} {}",
+            finding.severity, finding.rule, finding.location, finding.value
+        ));
+        if let Some(network) = finding.network {
+            result.push_str(&format!(" ({:?})", network));
+        }
+        if !finding.tags.is_empty() {
+            result.push_str(&format!(" #{}", finding.tags.join(" #")));
+        }
+        result.push('\n');
+    }
+    push_truncated(&mut result, report);
+
+    result
+}
+
+/// One line per run: where, how long, how random, and its first bytes
+fn entropy_text(report: &Report) -> String {
+    let runs = report.entropy_runs.as_deref().unwrap_or_default();
+    let mut result = format!("High-entropy runs in PID {}: {}\n", report.pid, runs.len());
+    for run in runs {
+        let preview: String = run.bytes.iter().take(32).map(|b| format!("{:02x}", b)).collect();
+        let more = if run.length > 32 { "..." } else { "" };
+        result.push_str(&format!(
+            "{} {} bytes, {:.2} bits/byte: {}{}\n",
+            run.location, run.length, run.entropy, preview, more
+        ));
+    }
+    push_truncated(&mut result, report);
+
+    result
+}
+
+fn integrity_text(report: &Report) -> String {
+    let found = report.unbacked_code.as_deref().unwrap_or_default();
+    let mut result = format!("Unbacked code in PID {}: {}\n", report.pid, found.len());
+    for code in found {
+        let reason = match code.reason {
+            integrity::UnbackedReason::Anonymous => "anonymous".to_string(),
+            integrity::UnbackedReason::Deleted => "file deleted".to_string(),
+            integrity::UnbackedReason::Missing => "file missing".to_string(),
+            integrity::UnbackedReason::Modified => format!(
+                "{} bytes modified from 0x{:x}",
+                code.modified_bytes.unwrap_or_default(),
+                code.first_difference.unwrap_or_default()
+            ),
+        };
+        let jit = if code.jit { " (JIT code cache)" } else { "" };
+        result.push_str(&format!("{} [{}]{}\n", code.region, reason, jit));
+    }
+    push_truncated(&mut result, report);
+
+    result
+}
+
+fn libraries_text(report: &Report) -> String {
+    let checks = report.libraries.as_deref().unwrap_or_default();
+    let modified: Vec<_> = checks.iter().filter(|check| !check.matches).collect();
+    let mut result = format!(
+        "Verified {} code mappings of PID {}: {} modified\n",
+        checks.len(),
+        report.pid,
+        modified.len()
+    );
+    for check in modified {
+        result.push_str(&format!(
+            "{:08x}-{:08x} {}: {} bytes modified\n",
+            check.start, check.end, check.path, check.modified_bytes
+        ));
+        for range in &check.modified {
+            result.push_str(&format!("  0x{:x} +{}\n", range.address, range.length));
+        }
+    }
+    push_truncated(&mut result, report);
+
+    result
+}
+
+fn shared_memory_text(report: &Report) -> String {
+    let objects = report.shared_memory.as_deref().unwrap_or_default();
+    let mut result = format!("Shared memory of PID {}: {}\n", report.pid, objects.len());
+    for object in objects {
+        let fds: Vec<String> = object.fds.iter().map(|fd| fd.to_string()).collect();
+        let size = object.size.map(|size| format!(" {} bytes", size)).unwrap_or_default();
+        result.push_str(&format!(
+            "{:?} {} fds [{}]{} {} mappings\n",
+            object.kind,
+            object.name,
+            fds.join(", "),
+            size,
+            object.mappings.len()
+        ));
+    }
+
+    result
+}
+
+fn instrumentation_text(report: &Report) -> String {
+    let Some(found) = &report.instrumentation else {
+        return String::new();
+    };
+    if found.detected.is_empty() {
+        return format!("No instrumentation found in PID {}\n", report.pid);
+    }
+    let names: Vec<String> =
+        found.detected.iter().map(|framework| format!("{:?}", framework)).collect();
+    let mut result = format!("Instrumentation in PID {}: {}\n", report.pid, names.join(", "));
+    for artifact in &found.artifacts {
+        result.push_str(&format!(
+            "{:?} {:?}: {}\n",
+            artifact.framework, artifact.source, artifact.detail
+        ));
+    }
+
+    result
+}
+
+fn packer_text(report: &Report) -> String {
+    let Some(found) = &report.packers else {
+        return String::new();
+    };
+    let mut result = if found.detected.is_empty() {
+        format!("No known packer found in PID {}\n", report.pid)
+    } else {
+        format!("Packers in PID {}: {}\n", report.pid, found.detected.join(", "))
+    };
+    for artifact in &found.artifacts {
+        result.push_str(&format!(
+            "{} {:?}: {} at 0x{:x}\n",
+            artifact.packer, artifact.source, artifact.detail, artifact.address
+        ));
+    }
+    push_truncated(&mut result, report);
+
+    result
+}
+
+fn classes_text(report: &Report) -> String {
+    let classes = report.classes.as_deref().unwrap_or_default();
+    let mut result = format!("Classes of PID {}: {}\n", report.pid, classes.len());
+    for class in classes {
+        match &class.dex_location {
+            Some(location) => {
+                result.push_str(&format!("0x{:x} {} ({})\n", class.address, class.name, location))
+            }
+            None => result.push_str(&format!("0x{:x} {}\n", class.address, class.name)),
+        }
+    }
+    push_truncated(&mut result, report);
+
+    result
+}
+
+fn java_heap_text(report: &Report) -> String {
+    let mut result = format!(
+        "Java heap of PID {}: {} classes and objects in {} regions\n",
+        report.pid, report.stats.results, report.stats.regions_scanned
+    );
+    for region in &report.regions {
+        if let Some(e) = &region.error {
+            result.push_str(&format!("{} [unreadable: {}]\n", region.region, e));
+        }
+    }
+
+    result.push_str(&format!(
+        "\nRead {} bytes ({} regions unreadable) via {}\n",
+        report.stats.bytes_read,
+        report.stats.regions_unreadable,
+        report.stats.reader.unwrap_or("unknown reader")
+    ));
+    push_truncated(&mut result, report);
+
+    result
+}
+
+fn dump_text(report: &Report) -> String {
+    let files = report.files.as_deref().unwrap_or_default();
+    let mut result = format!("Dumped PID {}:\n", report.pid);
+    for file in files {
+        result.push_str(&format!("{} {} bytes sha256:{}\n", file.file, file.size, file.sha256));
+    }
+    for region in &report.regions {
+        if let Some(e) = &region.error {
+            result.push_str(&format!("{} [unreadable: {}]\n", region.region, e));
+        }
+    }
+
+    result.push_str(&format!(
+        "\nWrote {} bytes in {} files ({} regions unreadable) via {}\n",
+        report.stats.bytes_read,
+        files.len(),
+        report.stats.regions_unreadable,
+        report.stats.reader.unwrap_or("unknown reader")
+    ));
+    push_truncated(&mut result, report);
+
+    result
+}
+
+/// Visitor that only reads, for [`read_process_memory`]
+struct ReadOnly;
+
+impl scan::RegionVisitor for ReadOnly {
+    type Output = ();
+
+    fn visit_chunk(&mut self, _region: &MemoryRegion, _address: u64, _chunk: &[u8]) {}
+
+    fn end_region(
+        &mut self,
+        _region: &MemoryRegion,
+        _reader: &mut dyn MemoryReader,
+        _sink: &mut dyn ScanSink<()>,
+    ) {
+    }
+}
+
+fn memory_text(report: &Report) -> String {
+    let mut result = String::new();
+    result.push_str(&format!("Memory regions for PID {}:\n", report.pid));
+
+    for region in &report.regions {
+        match &region.error {
+            None => {
+                result.push_str(&format!(
+                    "{} [{} bytes read]\n",
+                    region.region, region.bytes_read
+                ));
+            }
+            Some(e) => result.push_str(&format!("{} [unreadable: {}]\n", region.region, e)),
+        }
+    }
+
+    result.push_str(&format!(
+        "\nRead {} bytes from {} regions ({} unreadable) via {}\n",
+        report.stats.bytes_read,
+        report.stats.regions_scanned,
+        report.stats.regions_unreadable,
+        report.stats.reader.unwrap_or("unknown reader")
+    ));
+    push_truncated(&mut result, report);
+
+    result
+}
+
+/// Identity of the process behind a session: read from /proc for a live
+/// process, or as recorded in a dump's manifest
+fn session_process_info(
+    session: &Session,
+    errors: &mut Vec<String>,
+) -> Result<Option<ProcessInfo>, ExtractError> {
+    if session.is_live() {
+        read_process_info(session.pid(), errors).map(Some)
+    } else {
+        Ok(session.saved_process().cloned())
+    }
+}
+
+/// Read the command line and environment of a process.
+///
+/// A missing cmdline means the process is gone and fails the call; an
+/// unreadable environment is only reported in `errors`.
+fn read_process_info(pid: i32, errors: &mut Vec<String>) -> Result<ProcessInfo, ExtractError> {
+    // Read /proc/[pid]/cmdline to get process info
+    let cmdline_path = format!("/proc/{}/cmdline", pid);
+    let cmdline = fs::read_to_string(&cmdline_path)
+        .map_err(|e| ExtractError::from_io("Failed to read cmdline", &e))?;
+
+    // Try to read environment variables (often contains useful info)
+    let environ_path = format!("/proc/{}/environ", pid);
+    let environment = match fs::read_to_string(&environ_path) {
+        Ok(environ) => environ
+            .split('\0')
+            .filter(|s| !s.is_empty())
+            .map(String::from)
+            .collect(),
+        Err(e) => {
+            errors.push(format!("Failed to read environ: {}", e));
+            Vec::new()
+        }
+    };
+
+    Ok(ProcessInfo {
+        cmdline: cmdline.replace('\0', " ").trim_end().to_string(),
+        environment,
+    })
+}
+
+/// Read up to `length` bytes at `address`, stopping at the first unreadable byte
+fn read_memory_range(
+    mem: &mut dyn MemoryReader,
+    address: u64,
+    length: i32,
+) -> Result<Vec<u8>, ExtractError> {
+    let length = usize::try_from(length)
+        .map_err(|_| ExtractError::InvalidArgument(format!("Invalid length: {}", length)))?;
+
+    if address.checked_add(length as u64).is_none() {
+        let message = format!("Range 0x{:x}+{} overflows", address, length);
+        return Err(ExtractError::InvalidArgument(message));
+    }
+
+    let mut bytes = vec![0u8; length];
+    let read = read_memory_into(mem, address, &mut bytes)?;
+    bytes.truncate(read);
+    Ok(bytes)
+}
+
+/// Write `bytes` at `address`, returning how many bytes could be written
+fn write_memory_range(
+    writer: &mut MemoryWriter,
+    address: u64,
+    bytes: &[u8],
+) -> Result<usize, ExtractError> {
+    require_root_to_write()?;
+    if address.checked_add(bytes.len() as u64).is_none() {
+        let message = format!("Range 0x{:x}+{} overflows", address, bytes.len());
+        return Err(ExtractError::InvalidArgument(message));
+    }
+
+    let written = writer
+        .write_at(address, bytes)
+        .map_err(|e| ExtractError::from_io(format_args!("Failed to write 0x{:x}", address), &e))?;
+    if written == 0 && !bytes.is_empty() {
+        return Err(ExtractError::Io(format!("Nothing writable at 0x{:x}", address)));
+    }
+    Ok(written)
+}
+
+/// Write `bytes` at `address` in a session's process, recording the bytes
+/// overwritten so they can be restored
+fn patch_memory_range(
+    session: &mut Session,
+    address: u64,
+    bytes: &[u8],
+) -> Result<usize, ExtractError> {
+    if !session.is_live() {
+        return Err(ExtractError::InvalidArgument("Can't write to a dump session".into()));
+    }
+    require_root_to_write()?;
+    // Only what could be read can be restored, so nothing past it is written
+    let mut original = vec![0u8; bytes.len()];
+    let read = read_memory_into(session.reader(), address, &mut original)?;
+    let mut writer = MemoryWriter::new(session.pid());
+    let written = write_memory_range(&mut writer, address, &bytes[..read])?;
+    original.truncate(written);
+    session.patches().record(address, original);
+    Ok(written)
+}
+
+/// Undo a session's patches, returning how many were undone
+fn restore_patches(session: &mut Session) -> Result<usize, ExtractError> {
+    if session.patches().is_empty() {
+        return Ok(0);
+    }
+    require_root_to_write()?;
+    let mut writer = MemoryWriter::new(session.pid());
+    let (restored, errors) = session.patches().restore(&mut writer);
+    match errors.first() {
+        None => Ok(restored),
+        Some(error) => Err(ExtractError::Io(format!(
+            "{} of {} patches not restored: {}",
+            errors.len(),
+            errors.len() + restored,
+            error
+        ))),
+    }
+}
+
+/// Fail unless running as root, before anything writes to a process.
+///
+/// Root is required even where ptrace rules would let an app write to a
+/// process of its own user, as patching memory is meant to be deliberate.
+fn require_root_to_write() -> Result<(), ExtractError> {
+    if !check_root_access() {
+        let message = "Writing process memory requires root".to_string();
+        return Err(ExtractError::PermissionDenied(message));
+    }
+    Ok(())
+}
+
+/// Fill `buf` from `address`, returning how many bytes could be read
+fn read_memory_into(
+    mem: &mut dyn MemoryReader,
+    address: u64,
+    buf: &mut [u8],
+) -> Result<usize, ExtractError> {
+    let read = reader::read_into(mem, address, buf)
+        .map_err(|e| ExtractError::from_io(format_args!("Failed to read 0x{:x}", address), &e))?;
+
+    if read == 0 && !buf.is_empty() {
+        return Err(ExtractError::Io(format!("Nothing readable at 0x{:x}", address)));
+    }
+    Ok(read)
+}
+
+/// Extract strings from process memory along with the process's identity
+fn extract_strings_from_process(
+    session: &mut Session,
+    options: &ScanOptions,
+    observer: Option<&mut dyn ScanObserver>,
+) -> Result<Report, ExtractError> {
+    let regions = session.select(&options.regions)?;
+    extract_strings_from_regions(session, &regions, options, observer)
+}
+
+/// Extract strings from the stacks of a process's threads only, listing the
+/// threads in the report
+fn extract_strings_from_stacks(
+    session: &mut Session,
+    options: &ScanOptions,
+) -> Result<Report, ExtractError> {
+    let stacks = threads::find_thread_stacks(session.pid(), session.all_regions())
+        .map_err(|e| ExtractError::from_io("Failed to list threads", &e))?;
+    let regions: Vec<MemoryRegion> = session
+        .select(&options.regions)?
+        .into_iter()
+        .filter(|region| stacks.iter().any(|stack| stack.stack_start == Some(region.start)))
+        .collect();
+
+    let mut report = extract_strings_from_regions(session, &regions, options, None)?;
+    report.threads = Some(stacks);
+    Ok(report)
+}
+
+fn extract_strings_from_regions(
+    session: &mut Session,
+    regions: &[MemoryRegion],
+    options: &ScanOptions,
+    observer: Option<&mut dyn ScanObserver>,
+) -> Result<Report, ExtractError> {
+    let mut report = Report::new(session.pid());
+    report.process = session_process_info(session, &mut report.errors)?;
 
-/// Native library for advanced text extraction from Android app memory
-/// Requires root access for full functionality
-///
-/// This module provides low-level memory access capabilities for:
-/// - Reading process memory directly from /proc/[pid]/mem
-/// - Scanning memory regions for text patterns
-/// - Extracting strings from application heap
-///
-/// WARNING: These operations require root access and should only be used
-/// in authorized security research, penetration testing, or educational contexts.
+    report.stats.reader = Some(session.reader_name());
+    let mut checkpoint = match &options.checkpoint {
+        Some(path) => Some(open_checkpoint(path, session.pid(), &mut report)?),
+        None => None,
+    };
+    let remaining = checkpoint.as_ref().map(|checkpoint| checkpoint.remaining(regions));
+    let regions = remaining.as_deref().unwrap_or(regions);
 
-/// Read memory from a specific process ID
-/// Requires root access
-#[no_mangle]
-pub extern "C" fn Java_com_textextractor_native_1NativeMemoryExtractor_readProcessMemory(
-    env: JNIEnv,
-    _class: JClass,
-    pid: i32,
-) -> jstring {
-    let result = read_process_memory(pid);
+    let started = session.start_scan();
+    let throttle = Throttle::new(&options.throttle);
+    let pages = session
+        .is_live()
+        .then(|| open_page_map(session.pid(), options, &mut report.errors))
+        .flatten();
+    let mut control = started
+        .control()
+        .deadline(options.deadline())
+        .present_pages(pages.as_ref())
+        .chunk_size(options.chunk_size)
+        .throttle(throttle.as_ref());
+    if let Some(observer) = observer {
+        control = control.observer(observer);
+    }
+    let mut scan = Scan::default();
+    // A dump's PID may since have been reused by an unrelated process
+    let frozen = session
+        .is_live()
+        .then(|| freeze_target(session.pid(), options, &mut report.errors))
+        .flatten();
+    let reader = session.reader();
+    let mut filter = options.bloom_filter();
+    let mut results = SpillSink::new(&mut scan, options.result_budget(), &options.spill_dir());
+    report.stats.truncated = match &mut checkpoint {
+        Some(checkpoint) => {
+            let mut sink = checkpoint.sink(&mut results);
+            let mut sink = UniqueSink::new(filter.as_mut(), &mut sink);
+            scan::extract_strings(reader, regions, options, &mut sink, &mut control)
+        }
+        None => {
+            let mut sink = UniqueSink::new(filter.as_mut(), &mut results);
+            scan::extract_strings(reader, regions, options, &mut sink, &mut control)
+        }
+    };
+    finish_spill(results, &mut report);
+    report.stats.duplicates_dropped = filter.map(|filter| filter.duplicates);
+    thaw_target(frozen, &mut report.errors);
+    if let Some(throttle) = &throttle {
+        report.stats.add_throttle(throttle);
+    }
+    if let Some(mut checkpoint) = checkpoint {
+        checkpoint.take_resumed(&mut scan);
+        if let Err(e) = checkpoint.finish(report.stats.truncated.is_none()) {
+            report.errors.push(format!("Failed to write checkpoint: {}", e));
+        }
+    }
+    let mut strings = take_strings(&mut report, scan, options);
+    if options.symbols {
+        Symbolizer::new(session.all_regions()).annotate(&mut strings);
+    }
+    report.strings = Some(strings);
+    report.finish();
+
+    Ok(report)
+}
+
+/// Take the region results of a string scan into `report`, returning its
+/// strings, collapsed if `options` ask for it
+fn take_strings(
+    report: &mut Report,
+    scan: Scan<ExtractedString>,
+    options: &ScanOptions,
+) -> Vec<ExtractedString> {
+    let strings = report.add_scan(scan);
+    if options.dedupe {
+        scan::dedupe_strings(strings)
+    } else {
+        strings
+    }
+}
+
+/// Record in `report` where the results that didn't fit in memory went
+fn finish_spill(results: SpillSink<ExtractedString>, report: &mut Report) {
+    match results.finish() {
+        Ok(spill) => {
+            report.stats.results += spill.as_ref().map_or(0, |spill| spill.results);
+            report.spill = spill;
+        }
+        Err(e) => report.errors.push(format!("Failed to spill results to disk: {}", e)),
+    }
+}
+
+/// Open the checkpoint at `path` for a scan of `pid`, noting in `report`
+/// what became of an earlier one
+fn open_checkpoint(path: &str, pid: i32, report: &mut Report) -> Result<Checkpoint, ExtractError> {
+    let cmdline = report.process.as_ref().map(|process| process.cmdline.as_str());
+    let checkpoint = Checkpoint::open(Path::new(path), pid, cmdline).map_err(|e| {
+        ExtractError::InvalidArgument(format!("Failed to open checkpoint {}: {}", path, e))
+    })?;
+    if checkpoint.discarded {
+        report.errors.push(format!("Checkpoint {} was for another process, starting over", path));
+    }
+    report.stats.resumed_regions = Some(checkpoint.resumed_regions());
+    Ok(checkpoint)
+}
+
+/// Extract the strings of the ART heap among the regions `options` select,
+/// each located at its characters
+fn extract_java_strings(
+    session: &mut Session,
+    options: &ScanOptions,
+) -> Result<Report, ExtractError> {
+    let regions: Vec<MemoryRegion> =
+        session.select(&options.regions)?.into_iter().filter(art::is_heap).collect();
+    if regions.is_empty() {
+        let message = format!("PID {} has no ART heap among the selected regions", session.pid());
+        return Err(ExtractError::InvalidArgument(message));
+    }
+    let (mut report, found) = run_walk(session, options, |reader, scan, control| {
+        art::find_strings(reader, &regions, scan, control)
+    })?;
 
-    match result {
-        Ok(data) => {
-            let output = env.new_string(data)
-                .expect("Couldn't create Java string");
-            output.into_raw()
+    let names: Vec<Arc<str>> = regions.iter().map(|region| region.name().into()).collect();
+    let mut scan = Scan {
+        regions: found.regions,
+        results: Vec::new(),
+    };
+    let filter = options.string_filter();
+    for string in found.results {
+        if string.value.chars().count() < options.min_length || !filter.accepts(&string.value) {
+            continue;
         }
+        let index = regions.partition_point(|region| region.end <= string.address);
+        let address = string.address + art::STRING_HEADER;
+        scan.results.push(ExtractedString {
+            location: Location::new(&names[index], regions[index].start, address),
+            encoding: if string.compressed {
+                StringEncoding::Ascii
+            } else {
+                StringEncoding::Utf16le
+            },
+            value: string.value,
+            count: None,
+            addresses: Vec::new(),
+        });
+    }
+    report.strings = Some(take_strings(&mut report, scan, options));
+    report.finish();
+
+    Ok(report)
+}
+
+/// Find the high-entropy runs among the regions `options` select
+fn scan_entropy(session: &mut Session, options: &ScanOptions) -> Result<Report, ExtractError> {
+    let regions = session.select(&options.regions)?;
+    let (mut report, scan) =
+        run_scan(session, options, &regions, || entropy::EntropyVisitor::new(&options.entropy))?;
+    report.entropy_runs = Some(report.add_scan(scan));
+    report.finish();
+
+    Ok(report)
+}
+
+/// Run the YARA rules of `options` over the regions they select
+/// Decode the range `options` give, in the instruction set of the module
+/// it lies in unless they name one
+#[cfg(feature = "capstone")]
+fn disassemble(session: &mut Session, options: &ScanOptions) -> Result<Report, ExtractError> {
+    let options = &options.disassemble;
+    let Some(mut address) = options.address else {
+        let message = "Invalid scan options: disassemble needs an address".to_string();
+        return Err(ExtractError::InvalidArgument(message));
+    };
+    let mut report = Report::new(session.pid());
+    report.process = session_process_info(session, &mut report.errors)?;
+    report.stats.reader = Some(session.reader_name());
+
+    let modules = pointers::modules(session.all_regions());
+    let module = pointers::module_at(&modules, address);
+    // Outside any module, such as in JIT code, the process's own
+    let arch = match options.arch {
+        Some(arch) => arch,
+        None => module
+            .into_iter()
+            .chain(modules.iter().filter(|module| module.name().starts_with("app_process")))
+            .chain(&modules)
+            .find_map(|module| module_arch(session.reader(), module))
+            .ok_or_else(|| {
+                ExtractError::InvalidArgument(
+                    "Invalid scan options: disassemble arch must be set, as no module's is known"
+                        .to_string(),
+                )
+            })?,
+    };
+    let thumb = arch == registers::Arch::Arm && (options.thumb || address & 1 == 1);
+    if thumb {
+        address &= !1;
+    }
+
+    let mut code = vec![0u8; options.length];
+    let read = read_memory_into(session.reader(), address, &mut code)?;
+    code.truncate(read);
+    if read < options.length {
+        report.errors.push(format!("Only {} bytes at 0x{:x} are readable", read, address));
+    }
+    let mut disassembler = disasm::Disassembler::new(arch, thumb).map_err(ExtractError::Io)?;
+    let instructions = disassembler.disassemble(&code, address);
+    report.stats.bytes_read = read as u64;
+    report.disassembly = Some(disasm::Disassembly {
+        address,
+        arch,
+        thumb,
+        module: module.map(|module| module.path.clone()),
+        offset: module.map(|module| address - module.base),
+        functions: disasm::functions(arch, &instructions),
+        instructions,
+    });
+    report.finish();
+    Ok(report)
+}
+
+/// The instruction set of the ELF image mapped at `module`'s base
+#[cfg(feature = "capstone")]
+fn module_arch(mem: &mut dyn MemoryReader, module: &pointers::Module) -> Option<registers::Arch> {
+    let mut header = [0u8; 64];
+    let read = reader::read_into(mem, module.base, &mut header).ok()?;
+    let header = elf::parse_header(&header[..read])?;
+    registers::Arch::from_machine(header.machine)
+}
+
+#[cfg(feature = "yara")]
+fn scan_yara(session: &mut Session, options: &ScanOptions) -> Result<Report, ExtractError> {
+    let mut source = options.yara.rules.clone().unwrap_or_default();
+    if let Some(path) = &options.yara.rules_file {
+        let file = fs::read_to_string(path)
+            .map_err(|e| ExtractError::Io(format!("Failed to read rules file {}: {}", path, e)))?;
+        source.push('\n');
+        source.push_str(&file);
+    }
+    if source.trim().is_empty() {
+        return Err(ExtractError::InvalidArgument(
+            "Invalid scan options: yara needs rules or a rulesFile".to_string(),
+        ));
+    }
+    let rules = yara::Rules::compile(&source).map_err(ExtractError::InvalidArgument)?;
+    let regions = session.select(&options.regions)?;
+    let failures = AtomicUsize::new(0);
+    let visitor = || yara::YaraVisitor::new(&rules, &options.yara, &failures);
+    let threads = options.thread_count().min(yara::MAX_THREADS);
+    let (mut report, scan) = run_walk(session, options, |reader, scan, control| {
+        scan::walk_regions_parallel(reader, &regions, visitor, threads, scan, control)
+    })?;
+    let failures = failures.into_inner();
+    if failures > 0 {
+        report.errors.push(format!("libyara failed to scan {} windows", failures));
+    }
+    report.yara_matches = Some(report.add_scan(scan));
+    report.finish();
+
+    Ok(report)
+}
+
+/// The secret rules `options` select, built-in and from their rules file
+fn secret_rules(options: &ScanOptions) -> Result<Vec<secrets::Rule>, ExtractError> {
+    let mut rules = secrets::builtin_rules();
+    if let Some(path) = &options.secrets.rules_file {
+        let json = fs::read_to_string(path)
+            .map_err(|e| ExtractError::Io(format!("Failed to read rules file {}: {}", path, e)))?;
+        let extra = secrets::parse_rules(&json).map_err(ExtractError::InvalidArgument)?;
+        rules = secrets::add_rules(rules, extra).map_err(ExtractError::InvalidArgument)?;
+    }
+    secrets::select_rules(rules, &options.secrets).map_err(ExtractError::InvalidArgument)
+}
+
+/// Carve the documents `options` ask for out of the regions they select
+fn carve_documents(session: &mut Session, options: &ScanOptions) -> Result<Report, ExtractError> {
+    let regions = session.select(&options.regions)?;
+    let (mut report, scan) =
+        run_scan(session, options, &regions, || carve::CarveVisitor::new(&options.carve))?;
+    report.documents = Some(report.add_scan(scan));
+    report.finish();
+
+    Ok(report)
+}
+
+/// Find the protobuf messages in the regions `options` select
+fn scan_protobuf(session: &mut Session, options: &ScanOptions) -> Result<Report, ExtractError> {
+    let regions = session.select(&options.regions)?;
+    let (mut report, scan) =
+        run_scan(session, options, &regions, || protobuf::ProtobufVisitor::new(&options.protobuf))?;
+    report.protobuf_messages = Some(report.add_scan(scan));
+    report.finish();
+
+    Ok(report)
+}
+
+/// Extract the URLs in the regions `options` select, and their hosts
+fn extract_urls(session: &mut Session, options: &ScanOptions) -> Result<Report, ExtractError> {
+    let regions = session.select(&options.regions)?;
+    let visitor = || urls::UrlVisitor::new(&options.urls, &options.encodings);
+    let (mut report, scan) = run_scan(session, options, &regions, visitor)?;
+    let (found, hosts) = urls::aggregate(report.add_scan(scan));
+    report.urls = Some(found);
+    report.hosts = Some(hosts);
+    report.finish();
+
+    Ok(report)
+}
+
+/// Run the secret detectors `options` select over the regions they select
+fn scan_secrets(session: &mut Session, options: &ScanOptions) -> Result<Report, ExtractError> {
+    let rules = secret_rules(options)?;
+    let mut scanner = PatternScanner::new(
+        secrets::compile(&rules).map_err(ExtractError::InvalidArgument)?,
+    );
+    let regions = session.select(&options.regions)?;
+    let (mut report, scan) = run_walk(session, options, |reader, scan, control| {
+        scan::search_regions(reader, &regions, &mut scanner, 0, scan, control)
+    })?;
+    let matches = report.add_scan(scan);
+    report.findings = Some(secrets::findings(&rules, matches, &options.secrets));
+    report.finish();
+
+    Ok(report)
+}
+
+/// Find the private keys in the regions `options` select, writing them to
+/// the directory the options give, if any
+fn scan_private_keys(session: &mut Session, options: &ScanOptions) -> Result<Report, ExtractError> {
+    let dir = options.private_keys.output_dir.as_deref().map(Path::new);
+    if dir.is_some_and(|dir| dir.as_os_str().is_empty()) {
+        return Err(ExtractError::InvalidArgument("Empty output directory".to_string()));
+    }
+    let regions = session.select(&options.regions)?;
+    let (mut report, scan) = run_scan(session, options, &regions, keys::KeyVisitor::new)?;
+    let mut found = keys::aggregate(report.add_scan(scan));
+    if let Some(dir) = dir {
+        keys::write_keys(&mut found, dir)
+            .map_err(|e| ExtractError::from_output_io(dir.display(), &e))?;
+    }
+    report.private_keys = Some(found);
+    report.finish();
+
+    Ok(report)
+}
+
+/// Recover the AES keys of the schedules in the regions `options` select
+fn scan_aes_keys(session: &mut Session, options: &ScanOptions) -> Result<Report, ExtractError> {
+    if options.aes_keys.max_bit_errors > aes::MAX_BIT_ERRORS {
+        return Err(ExtractError::InvalidArgument(format!(
+            "maxBitErrors must be at most {}",
+            aes::MAX_BIT_ERRORS
+        )));
+    }
+    let regions = session.select(&options.regions)?;
+    let (mut report, scan) =
+        run_scan(session, options, &regions, || aes::AesKeyVisitor::new(&options.aes_keys))?;
+    report.aes_keys = Some(aes::aggregate(report.add_scan(scan)));
+    report.finish();
+
+    Ok(report)
+}
+
+/// Find the TLS secrets in the regions `options` select
+fn scan_tls_secrets(session: &mut Session, options: &ScanOptions) -> Result<Report, ExtractError> {
+    let keylog = options.tls.keylog_file.as_deref().map(Path::new);
+    if keylog.is_some_and(|keylog| keylog.as_os_str().is_empty()) {
+        return Err(ExtractError::InvalidArgument("Empty key log file".to_string()));
+    }
+    let regions = session.select(&options.regions)?;
+    let (mut report, scan) = run_scan(session, options, &regions, tls::TlsVisitor::new)?;
+    let secrets = tls::resolve(report.add_scan(scan));
+    if let Some(keylog) = keylog {
+        tls::write_keylog(&secrets, keylog)
+            .map_err(|e| ExtractError::from_output_io(keylog.display(), &e))?;
+    }
+    report.tls_secrets = Some(secrets);
+    report.finish();
+
+    Ok(report)
+}
+
+/// Decode the base64 runs in the regions `options` select, extracting
+/// strings and running the secret rules over what they hold
+fn scan_base64(session: &mut Session, options: &ScanOptions) -> Result<Report, ExtractError> {
+    let rules = secret_rules(options)?;
+    let regexes = secrets::compile(&rules).map_err(ExtractError::InvalidArgument)?;
+    let runs = Regex::new(&base64::pattern(options.base64.min_length))
+        .map_err(|e| ExtractError::InvalidArgument(format!("Invalid scan options: {}", e)))?;
+    let mut scanner = PatternScanner::with_overlap(runs, options.base64.max_length);
+    let regions = session.select(&options.regions)?;
+    let (mut report, scan) = run_walk(session, options, |reader, scan, control| {
+        scan::search_regions(reader, &regions, &mut scanner, 0, scan, control)
+    })?;
+    let expander = base64::Expander::new(
+        &options.base64,
+        &options.encodings,
+        options.min_length,
+        &rules,
+        &regexes,
+        &options.secrets,
+    );
+    let runs = report.add_scan(scan);
+    report.base64_blobs = Some(runs.into_iter().flat_map(|run| expander.expand(run)).collect());
+    report.finish();
+
+    Ok(report)
+}
+
+/// Inflate the compressed streams in the regions `options` select,
+/// extracting strings and running the secret rules over what they hold
+fn scan_compressed(session: &mut Session, options: &ScanOptions) -> Result<Report, ExtractError> {
+    let rules = secret_rules(options)?;
+    let regexes = secrets::compile(&rules).map_err(ExtractError::InvalidArgument)?;
+    let regions = session.select(&options.regions)?;
+    let make_visitor = || {
+        compressed::CompressedVisitor::new(
+            &options.compressed,
+            &options.encodings,
+            options.min_length,
+            &rules,
+            &regexes,
+            &options.secrets,
+        )
+    };
+    let (mut report, scan) = run_scan(session, options, &regions, make_visitor)?;
+    report.compressed_streams = Some(report.add_scan(scan));
+    report.finish();
+
+    Ok(report)
+}
+
+/// Decode the buffers in the regions `options` select that hide text
+/// behind single-byte XOR or rotation
+fn deobfuscate_strings(
+    session: &mut Session,
+    options: &ScanOptions,
+) -> Result<Report, ExtractError> {
+    let regions = session.select(&options.regions)?;
+    let visitor = || deobfuscate::DeobfuscateVisitor::new(&options.deobfuscate);
+    let (mut report, scan) = run_scan(session, options, &regions, visitor)?;
+    report.deobfuscated = Some(report.add_scan(scan));
+    report.finish();
+
+    Ok(report)
+}
+
+/// Find the addresses in the regions `options` select that hold the value
+/// of its `value` object, keeping them in the session to be refined
+fn scan_value(session: &mut Session, options: &ScanOptions) -> Result<Report, ExtractError> {
+    let query = &options.value;
+    let needle = query
+        .needle()
+        .map_err(|e| ExtractError::InvalidArgument(format!("Invalid scan options: {}", e)))?;
+    let regions = session.select(&options.regions)?;
+    let visitor = || value::ValueVisitor::new(&needle, query.alignment());
+    let (mut report, scan) = run_scan(session, options, &regions, visitor)?;
+    let mut addresses = report.add_scan(scan);
+    addresses.sort_unstable();
+    let candidates = value::Candidates::found(query, addresses)
+        .map_err(|e| ExtractError::InvalidArgument(format!("Invalid scan options: {}", e)))?;
+    report.values = Some(candidates.report(&regions, query.max_results, None));
+    session.set_value_search(value::ValueSearch::Candidates(candidates));
+    report.finish();
+
+    Ok(report)
+}
+
+/// Snapshot the session's mappings, with their usage, and the strings of
+/// the regions `options` select, returning the strings with their report
+fn capture_snapshot(
+    session: &mut Session,
+    options: &ScanOptions,
+) -> Result<(Report, ProcessSnapshot, Vec<ExtractedString>), ExtractError> {
+    session.refresh_usage()?;
+    let mut report = extract_strings_from_process(session, options, None)?;
+    let strings = report.strings.take().unwrap_or_default();
+    let snapshot = ProcessSnapshot::new(session.all_regions().to_vec(), &strings);
+    Ok((report, snapshot, strings))
+}
+
+/// Keep a snapshot of the session to diff later, in place of any before it
+fn take_snapshot(session: &mut Session, options: &ScanOptions) -> Result<Report, ExtractError> {
+    let (report, snapshot, _) = capture_snapshot(session, options)?;
+    session.set_snapshot(snapshot);
+    Ok(report)
+}
+
+/// Snapshot the session again and compare it with the last snapshot, which
+/// it replaces; the last one is kept if the new one fails
+fn diff_snapshot(session: &mut Session, options: &ScanOptions) -> Result<Report, ExtractError> {
+    let before = session.take_snapshot().ok_or_else(|| {
+        ExtractError::InvalidArgument("No snapshot to compare with on this session".into())
+    })?;
+    let (mut report, after, strings) = match capture_snapshot(session, options) {
+        Ok(captured) => captured,
         Err(e) => {
-            let error_msg = format!("Error reading process memory: {}", e);
-            let output = env.new_string(error_msg)
-                .expect("Couldn't create Java string");
-            output.into_raw()
+            session.set_snapshot(before);
+            return Err(e);
         }
+    };
+    report.diff = Some(before.diff(&after, strings, reader::page_size() as u64));
+    session.set_snapshot(after);
+    Ok(report)
+}
+
+/// Bytes of a pointer in the session's process: those `options` give, or
+/// else the process's own
+fn pointer_width(session: &Session, options: &ScanOptions) -> usize {
+    match options.pointers.width {
+        Some(width) => width,
+        None if session.is_live() => pointers::process_width(session.pid()),
+        None => std::mem::size_of::<usize>(),
     }
 }
 
-/// Extract printable strings from process memory
-#[no_mangle]
-pub extern "C" fn Java_com_textextractor_native_1NativeMemoryExtractor_extractStrings(
-    env: JNIEnv,
-    _class: JClass,
-    pid: i32,
-    min_length: i32,
-) -> jstring {
-    let result = extract_strings_from_process(pid, min_length as usize);
+/// Index the pointers among the regions `options` select that point into
+/// a readable mapping, keeping the map in the session
+fn build_pointer_map(session: &mut Session, options: &ScanOptions) -> Result<Report, ExtractError> {
+    let width = pointer_width(session, options);
+    let regions = session.select(&options.regions)?;
+    let mapped = pointers::mapped_ranges(&session.regions(&RegionFilter::default()));
+    let (mut report, scan) =
+        run_scan(session, options, &regions, || pointers::PointerVisitor::new(width, &mapped))?;
+    let map = pointers::PointerMap::new(width, report.add_scan(scan), session.all_regions());
+    report.pointer_map = Some(map.info());
+    session.set_pointer_map(map);
+    report.finish();
+
+    Ok(report)
+}
+
+/// Search the session's pointer map for paths from modules to the target
+/// of `options`
+fn find_pointer_paths(
+    session: &mut Session,
+    options: &ScanOptions,
+) -> Result<Report, ExtractError> {
+    let target = options.pointers.target.ok_or_else(|| {
+        ExtractError::InvalidArgument("Invalid scan options: pointers target must be set".into())
+    })?;
+    let mut report = Report::new(session.pid());
+    report.process = session_process_info(session, &mut report.errors)?;
+
+    let map = session.pointer_map().ok_or_else(|| {
+        ExtractError::InvalidArgument("No pointer map on this session to search".into())
+    })?;
+    let paths = map.find_paths(target, &options.pointers);
+    report.stats.results = paths.len();
+    report.pointer_paths = Some(paths);
+    report.finish();
+
+    Ok(report)
+}
+
+/// Follow the chain of pointers `options` give from its module
+fn resolve_pointer_chain(
+    session: &mut Session,
+    options: &ScanOptions,
+) -> Result<Report, ExtractError> {
+    let module = options.pointers.module.as_deref().ok_or_else(|| {
+        ExtractError::InvalidArgument("Invalid scan options: pointers module must be set".into())
+    })?;
+    let width = pointer_width(session, options);
+    let mut report = Report::new(session.pid());
+    report.process = session_process_info(session, &mut report.errors)?;
+    report.stats.reader = Some(session.reader_name());
+
+    let modules = pointers::modules(session.all_regions());
+    let offsets = &options.pointers.offsets;
+    let chain = pointers::resolve(session.reader(), &modules, module, offsets, width)
+        .map_err(ExtractError::InvalidArgument)?;
+    report.pointer_chain = Some(chain);
+    report.finish();
+
+    Ok(report)
+}
+
+/// Save the regions `options` select in the session, for a value search
+/// whose first value isn't known
+fn snapshot_values(session: &mut Session, options: &ScanOptions) -> Result<Report, ExtractError> {
+    let regions = session.select(&options.regions)?;
+    let length: u64 = regions.iter().map(MemoryRegion::size).sum();
+    if length > value::MAX_SNAPSHOT_LENGTH {
+        return Err(ExtractError::InvalidArgument(format!(
+            "The regions selected are too large to snapshot ({} MiB, at most {} MiB)",
+            length >> 20,
+            value::MAX_SNAPSHOT_LENGTH >> 20
+        )));
+    }
+    let (mut report, scan) = run_scan(session, options, &regions, value::SnapshotVisitor::default)?;
+    let segments = report.add_scan(scan);
+    let snapshot = value::Snapshot::new(&options.value, regions, segments);
+    report.value_snapshot = Some(snapshot.info());
+    session.set_value_search(value::ValueSearch::Snapshot(snapshot));
+    report.finish();
+
+    Ok(report)
+}
 
-    match result {
-        Ok(strings) => {
-            let output = env.new_string(strings)
-                .expect("Couldn't create Java string");
-            output.into_raw()
+/// Narrow the addresses the session's last value scan found, or compare
+/// the values of its snapshot, by the `condition` of the `value` object of
+/// `options`; the search is left as it was if that fails
+fn refine_values(session: &mut Session, options: &ScanOptions) -> Result<Report, ExtractError> {
+    let search = session.take_value_search().ok_or_else(|| {
+        ExtractError::InvalidArgument("No value scan to refine on this session".into())
+    })?;
+    let refined = match &search {
+        value::ValueSearch::Candidates(candidates) => {
+            refine_candidates(session, candidates, options)
+        }
+        value::ValueSearch::Snapshot(snapshot) => compare_snapshot(session, snapshot, options),
+    };
+    match refined {
+        Ok((report, candidates)) => {
+            session.set_value_search(value::ValueSearch::Candidates(candidates));
+            Ok(report)
         }
         Err(e) => {
-            let error_msg = format!("Error extracting strings: {}", e);
-            let output = env.new_string(error_msg)
-                .expect("Couldn't create Java string");
-            output.into_raw()
+            session.set_value_search(search);
+            Err(e)
         }
     }
 }
 
-/// Check if the device is rooted and if we have necessary permissions
-#[no_mangle]
-pub extern "C" fn Java_com_textextractor_native_1NativeMemoryExtractor_checkRootAccess(
-    env: JNIEnv,
-    _class: JClass,
-) -> jstring {
-    let has_root = check_root_access();
-    let message = if has_root {
-        "Root access available"
-    } else {
-        "Root access not available - native memory extraction will be limited"
-    };
+/// Read each of `candidates` again, keeping those that meet the condition
+fn refine_candidates(
+    session: &mut Session,
+    candidates: &value::Candidates,
+    options: &ScanOptions,
+) -> Result<(Report, value::Candidates), ExtractError> {
+    let query = &options.value;
+    let mut report = Report::new(session.pid());
+    report.process = session_process_info(session, &mut report.errors)?;
+    report.stats.reader = Some(session.reader_name());
+
+    let (refined, previous) = candidates
+        .refine(session.reader(), query)
+        .map_err(|e| ExtractError::InvalidArgument(format!("Invalid scan options: {}", e)))?;
+    report.stats.results = refined.addresses.len();
+    report.values = Some(refined.report(session.all_regions(), query.max_results, Some(&previous)));
+    report.finish();
 
-    let output = env.new_string(message)
-        .expect("Couldn't create Java string");
-    output.into_raw()
+    Ok((report, refined))
 }
 
-/// Read process memory maps and extract readable regions
-fn read_process_memory(pid: i32) -> Result<String, String> {
-    // Read /proc/[pid]/maps to find memory regions
-    let maps_path = format!("/proc/{}/maps", pid);
-    let maps_content = fs::read_to_string(&maps_path)
-        .map_err(|e| format!("Failed to read maps: {} (requires root)", e))?;
+/// Compare every aligned value of the regions `snapshot` saved with what it
+/// held then, keeping those that meet the condition
+fn compare_snapshot(
+    session: &mut Session,
+    snapshot: &value::Snapshot,
+    options: &ScanOptions,
+) -> Result<(Report, value::Candidates), ExtractError> {
+    let query = &options.value;
+    let refinement = value::Refinement::new(query, snapshot.value_type)
+        .map_err(|e| ExtractError::InvalidArgument(format!("Invalid scan options: {}", e)))?;
+    let visitor = || value::DiffVisitor::new(snapshot, refinement);
+    let (mut report, scan) = run_scan(session, options, &snapshot.regions, visitor)?;
+    let (refined, previous) = snapshot.candidates(report.add_scan(scan));
+    report.values = Some(refined.report(session.all_regions(), query.max_results, Some(&previous)));
+    report.finish();
 
-    let mut result = String::new();
-    result.push_str(&format!("Memory maps for PID {}:\n", pid));
-    result.push_str(&maps_content);
+    Ok((report, refined))
+}
 
-    // Try to read actual memory (requires root)
-    let mem_path = format!("/proc/{}/mem", pid);
-    if fs::metadata(&mem_path).is_ok() {
-        result.push_str("\nMemory accessible (root available)\n");
-    } else {
-        result.push_str("\nMemory not accessible (requires root)\n");
+/// Compare the executable regions of a live process with their files
+fn check_code_integrity(session: &mut Session) -> Result<Report, ExtractError> {
+    let regions: Vec<MemoryRegion> = session
+        .all_regions()
+        .iter()
+        .filter(|region| integrity::is_checked(region))
+        .cloned()
+        .collect();
+    let mut report = Report::new(session.pid());
+    report.process = session_process_info(session, &mut report.errors)?;
+    report.stats.reader = Some(session.reader_name());
+
+    // Paths are looked up in the target's mount namespace
+    let root = format!("/proc/{}/root", session.pid());
+    let mut visitor = integrity::IntegrityVisitor::new(Path::new(&root));
+    let started = session.start_scan();
+    let mut control = started.control();
+    let mut scan = Scan::default();
+    report.stats.truncated =
+        scan::walk_regions(session.reader(), &regions, &mut visitor, &mut scan, &mut control);
+    report.errors.append(&mut visitor.errors);
+    report.unbacked_code = Some(report.add_scan(scan));
+    report.finish();
+
+    Ok(report)
+}
+
+/// The built-in packer signatures, with those in the file at `path` merged
+/// in unless it is empty
+fn packer_signatures(path: &str) -> Result<Vec<packer::PackerSignature>, ExtractError> {
+    let mut signatures = packer::builtin_signatures();
+    if !path.is_empty() {
+        let json = fs::read_to_string(path).map_err(|e| {
+            ExtractError::InvalidArgument(format!("Can't read packer signatures {}: {}", path, e))
+        })?;
+        packer::merge(&mut signatures, packer::parse_database(&json)?);
+    }
+    Ok(signatures)
+}
+
+/// Look for the packers among `signatures` in every readable region of a
+/// process
+fn detect_packer(
+    session: &mut Session,
+    signatures: &[packer::PackerSignature],
+) -> Result<Report, ExtractError> {
+    let mut report = Report::new(session.pid());
+    report.process = session_process_info(session, &mut report.errors)?;
+    let regions = session.regions(&RegionFilter::default());
+    report.stats.reader = Some(session.reader_name());
+
+    let started = session.start_scan();
+    let mut control = started.control();
+    let mut scan = Scan::default();
+    report.stats.truncated =
+        packer::identify(session.reader(), &regions, signatures, &mut scan, &mut control);
+    let artifacts = report.add_scan(scan);
+    report.packers = Some(packer::summarize(signatures, artifacts));
+    report.finish();
+
+    Ok(report)
+}
+
+/// Compare the code mapped from files in a live process with the files
+fn verify_libraries(session: &mut Session) -> Result<Report, ExtractError> {
+    let regions = session.all_regions().to_vec();
+    let mut report = Report::new(session.pid());
+    report.process = session_process_info(session, &mut report.errors)?;
+    report.stats.reader = Some(session.reader_name());
+
+    let root = format!("/proc/{}/root", session.pid());
+    let started = session.start_scan();
+    let mut control = started.control();
+    let mut scan = Scan::default();
+    report.stats.truncated = integrity::verify_libraries(
+        session.reader(),
+        &regions,
+        Path::new(&root),
+        &mut scan,
+        &mut control,
+        &mut report.errors,
+    );
+    report.libraries = Some(report.add_scan(scan));
+    report.finish();
+
+    Ok(report)
+}
+
+/// Find the classes loaded in the ART runtime of a process, from its class
+/// tables or, failing that, its heap
+fn list_java_classes(session: &mut Session) -> Result<Report, ExtractError> {
+    let all_regions = session.all_regions().to_vec();
+    let regions: Vec<MemoryRegion> =
+        all_regions.iter().filter(|region| art::is_heap(region)).cloned().collect();
+    if regions.is_empty() {
+        let message = format!("PID {} has no ART heap", session.pid());
+        return Err(ExtractError::InvalidArgument(message));
     }
+    let mut report = Report::new(session.pid());
+    report.process = session_process_info(session, &mut report.errors)?;
+    report.stats.reader = Some(session.reader_name());
 
-    Ok(result)
+    let started = session.start_scan();
+    let mut control = started.control();
+    let mut scan = Scan::default();
+    let frozen = session.is_live().then(|| {
+        Freeze::new(session.pid(), freeze::DEFAULT_TIMEOUT)
+            .map_err(|e| report.errors.push(format!("Failed to freeze, reading live: {}", e)))
+            .ok()
+    });
+    report.stats.truncated =
+        java_classes(session, &all_regions, &regions, &mut scan, &mut control, &mut report.errors);
+    thaw_target(frozen.flatten(), &mut report.errors);
+    report.classes = Some(report.add_scan(scan));
+    report.finish();
+
+    Ok(report)
 }
 
-/// Extract printable ASCII strings from process memory
-fn extract_strings_from_process(pid: i32, min_length: usize) -> Result<String, String> {
-    // Read /proc/[pid]/cmdline to get process info
-    let cmdline_path = format!("/proc/{}/cmdline", pid);
-    let cmdline = fs::read_to_string(&cmdline_path)
-        .map_err(|e| format!("Failed to read cmdline: {}", e))?;
+/// Find the classes of a process from its class tables or, failing that,
+/// by searching its heap spaces `heap_regions`, noting why in `errors`
+fn java_classes(
+    session: &mut Session,
+    all_regions: &[MemoryRegion],
+    heap_regions: &[MemoryRegion],
+    scan: &mut Scan<art::JavaClass>,
+    control: &mut ScanControl,
+    errors: &mut Vec<String>,
+) -> Option<scan::StopReason> {
+    match art::find_linked_classes(session.reader(), all_regions, scan, control) {
+        Ok(stopped) => stopped,
+        Err(reason) => {
+            errors.push(format!("Class tables not found, searching the heap: {}", reason));
+            art::search_classes(session.reader(), heap_regions, scan, control)
+        }
+    }
+}
+
+/// Stop `pid` for a scan if `options` ask for it. Failing to is not fatal:
+/// the scan goes ahead on the live process and the failure is noted.
+fn freeze_target(pid: i32, options: &ScanOptions, errors: &mut Vec<String>) -> Option<Freeze> {
+    if !options.freeze {
+        return None;
+    }
+    Freeze::new(pid, options.freeze_timeout())
+        .map_err(|e| errors.push(format!("Failed to freeze PID {}, reading live: {}", pid, e)))
+        .ok()
+}
+
+/// The page map of `pid` if `options` ask to read only present pages. If it
+/// can't be opened every page is read, as `errors` notes.
+fn open_page_map(pid: i32, options: &ScanOptions, errors: &mut Vec<String>) -> Option<PageMap> {
+    if !options.present_pages_only {
+        return None;
+    }
+    PageMap::open(pid)
+        .map_err(|e| {
+            errors.push(format!("Failed to open pagemap of PID {}, reading every page: {}", pid, e))
+        })
+        .ok()
+}
+
+fn thaw_target(frozen: Option<Freeze>, errors: &mut Vec<String>) {
+    if let Some(freeze) = frozen.filter(|freeze| freeze.expired()) {
+        errors.push(format!(
+            "PID {} was resumed by the freeze timeout; later reads were live",
+            freeze.pid()
+        ));
+    }
+}
+
+/// Run `walk` over the session as `options` ask: against their deadline,
+/// paced by their throttle, skipping pages that aren't present and with the
+/// target frozen if they say so. Returns the report, with the process and
+/// the scan's stats, and the scan for the caller to file in it.
+fn run_walk<T, W>(
+    session: &mut Session,
+    options: &ScanOptions,
+    walk: W,
+) -> Result<(Report, Scan<T>), ExtractError>
+where
+    W: FnOnce(&mut dyn MemoryReader, &mut Scan<T>, &mut ScanControl) -> Option<scan::StopReason>,
+{
+    let mut report = Report::new(session.pid());
+    report.process = session_process_info(session, &mut report.errors)?;
+    report.stats.reader = Some(session.reader_name());
+
+    let started = session.start_scan();
+    let throttle = Throttle::new(&options.throttle);
+    let pages = session
+        .is_live()
+        .then(|| open_page_map(session.pid(), options, &mut report.errors))
+        .flatten();
+    let mut control = started
+        .control()
+        .deadline(options.deadline())
+        .present_pages(pages.as_ref())
+        .chunk_size(options.chunk_size)
+        .throttle(throttle.as_ref());
+    let mut scan = Scan::default();
+    // A dump's PID may since have been reused by an unrelated process
+    let frozen = session
+        .is_live()
+        .then(|| freeze_target(session.pid(), options, &mut report.errors))
+        .flatten();
+    report.stats.truncated = walk(session.reader(), &mut scan, &mut control);
+    thaw_target(frozen, &mut report.errors);
+    if let Some(throttle) = &throttle {
+        report.stats.add_throttle(throttle);
+    }
+
+    Ok((report, scan))
+}
+
+/// Run the visitors `make_visitor` builds over `regions` on as many threads
+/// as `options` allow, the rest as for [`run_walk`]
+fn run_scan<V, F>(
+    session: &mut Session,
+    options: &ScanOptions,
+    regions: &[MemoryRegion],
+    make_visitor: F,
+) -> Result<(Report, Scan<V::Output>), ExtractError>
+where
+    V: scan::RegionVisitor,
+    V::Output: Send,
+    F: Fn() -> V + Sync,
+{
+    let threads = options.thread_count();
+    run_walk(session, options, |reader, scan, control| {
+        scan::walk_regions_parallel(reader, regions, make_visitor, threads, scan, control)
+    })
+}
+
+/// Extract strings from every process of an app into one report.
+///
+/// Processes that can't be opened are listed in `errors` and skipped; the
+/// call only fails if none could be. A timeout covers the whole scan.
+fn extract_strings_from_app(
+    selector: &AppSelector,
+    options: &ScanOptions,
+) -> Result<Report, ExtractError> {
+    let processes = process::find_app_processes(selector)
+        .map_err(|e| ExtractError::from_io("Failed to list processes", &e))?;
+    let Some(first) = processes.first() else {
+        return Err(ExtractError::NoSuchProcess(format!("No running processes for {}", selector)));
+    };
+
+    let mut report = Report::new(first.pid);
+    let deadline = options.deadline();
+    let mut scan = Scan::default();
+    let mut results = SpillSink::new(&mut scan, options.result_budget(), &options.spill_dir());
+    // One filter for every process, so a string is kept once per app
+    let mut filter = options.bloom_filter();
+    let mut first_error = None;
+    for process in &processes {
+        let opened = open_session(process.pid, options.reader_strategy)
+            .and_then(|mut session| Ok((session.select(&options.regions)?, session)));
+        let (regions, mut session) = match opened {
+            Ok(opened) => opened,
+            Err(e) => {
+                report.errors.push(format!("PID {}: {}", process.pid, e));
+                first_error.get_or_insert(e);
+                continue;
+            }
+        };
+        report.stats.reader.get_or_insert(session.reader_name());
+
+        let pages = open_page_map(process.pid, options, &mut report.errors);
+        let throttle = Throttle::new(&options.throttle);
+        let mut control = ScanControl::new()
+            .deadline(deadline)
+            .present_pages(pages.as_ref())
+            .chunk_size(options.chunk_size)
+            .throttle(throttle.as_ref());
+        results.set_pid(process.pid);
+        let frozen = freeze_target(process.pid, options, &mut report.errors);
+        let mut sink = UniqueSink::new(filter.as_mut(), &mut results);
+        report.stats.truncated =
+            scan::extract_strings(session.reader(), &regions, options, &mut sink, &mut control);
+        thaw_target(frozen, &mut report.errors);
+        if let Some(throttle) = &throttle {
+            report.stats.add_throttle(throttle);
+        }
+        if report.stats.truncated.is_some() {
+            break;
+        }
+    }
+    finish_spill(results, &mut report);
+    report.stats.duplicates_dropped = filter.map(|filter| filter.duplicates);
+    if let (None, Some(e)) = (report.stats.reader, first_error) {
+        return Err(e);
+    }
+
+    report.processes = Some(processes);
+    report.strings = Some(take_strings(&mut report, scan, options));
+    report.finish();
+    Ok(report)
+}
+
+/// Extract strings from process memory, streaming them to `out` as NDJSON
+/// (see [`stream`]). Failures are written to the stream as `error` lines;
+/// one that stopped the scan is also returned, after the stream is finished.
+fn stream_strings_from_process<W: Write>(
+    pid: i32,
+    options: Result<ScanOptions, ExtractError>,
+    out: W,
+) -> Result<W, ExtractError> {
+    let mut writer = NdjsonWriter::new(out);
+    let mut errors = Vec::new();
+
+    let scanned = options.and_then(|options| {
+        let process = read_process_info(pid, &mut errors)?;
+        writer.write(&process);
+
+        let mut session = open_session(pid, options.reader_strategy)?;
+        let regions = session.select(&options.regions)?;
+        writer.stats_mut().reader = Some(session.reader_name());
+        let pages = open_page_map(pid, &options, &mut errors);
+        let throttle = Throttle::new(&options.throttle);
+        let mut control = ScanControl::new()
+            .deadline(options.deadline())
+            .present_pages(pages.as_ref())
+            .chunk_size(options.chunk_size)
+            .throttle(throttle.as_ref());
+        let frozen = freeze_target(pid, &options, &mut errors);
+        let mut filter = options.bloom_filter();
+        let mut sink = UniqueSink::new(filter.as_mut(), &mut writer);
+        let truncated =
+            scan::extract_strings(session.reader(), &regions, &options, &mut sink, &mut control);
+        writer.stats_mut().truncated = truncated;
+        writer.stats_mut().duplicates_dropped = filter.map(|filter| filter.duplicates);
+        thaw_target(frozen, &mut errors);
+        if let Some(throttle) = &throttle {
+            writer.stats_mut().add_throttle(throttle);
+        }
+        Ok(())
+    });
+    for error in &errors {
+        writer.error(error);
+    }
+    if let Err(e) = &scanned {
+        writer.fatal(e);
+    }
+    let out = writer
+        .finish()
+        .map_err(|e| ExtractError::Io(format!("Failed to write stream: {}", e)))?;
+    scanned.map(|()| out)
+}
 
+/// Process header, environment, then one line per string with its location
+fn strings_text(report: &Report, options: &ScanOptions) -> String {
     let mut result = String::new();
-    result.push_str(&format!("Process: {}\n", cmdline.replace('\0', " ")));
-    result.push_str(&format!("PID: {}\n", pid));
-    result.push_str(&format!("Minimum string length: {}\n\n", min_length));
+    if let Some(process) = &report.process {
+        result.push_str(&format!("Process: {}\n", process.cmdline));
+    }
+    match &report.processes {
+        Some(processes) => {
+            result.push_str(&format!("Processes: {}\n", processes.len()));
+            for process in processes {
+                result.push_str(&format!(
+                    "  PID {}: {} (uid {})\n",
+                    process.pid, process.name, process.uid
+                ));
+            }
+        }
+        None => result.push_str(&format!("PID: {}\n", report.pid)),
+    }
+    result.push_str(&format!("Minimum string length: {}\n\n", options.min_length));
 
-    // Try to read environment variables (often contains useful info)
-    let environ_path = format!("/proc/{}/environ", pid);
-    if let Ok(environ) = fs::read_to_string(&environ_path) {
+    if let Some(process) = report.process.as_ref().filter(|p| !p.environment.is_empty()) {
         result.push_str("Environment variables:\n");
-        for env_var in environ.split('\0').filter(|s| !s.is_empty()) {
+        for env_var in &process.environment {
             result.push_str(&format!("  {}\n", env_var));
         }
-        result.push_str("\n");
+        result.push('\n');
+    }
+
+    if let Some(threads) = &report.threads {
+        result.push_str(&format!("Threads: {}\n", threads.len()));
+        for thread in threads {
+            match (thread.stack_start, thread.stack_end) {
+                (Some(start), Some(end)) => result.push_str(&format!(
+                    "  {} {}: stack 0x{:x}-0x{:x}\n",
+                    thread.tid, thread.name, start, end
+                )),
+                _ => result.push_str(&format!(
+                    "  {} {}: stack not found\n",
+                    thread.tid, thread.name
+                )),
+            }
+        }
+        result.push('\n');
+    }
+
+    let strings = report.strings.as_deref().unwrap_or_default();
+    result.push_str(&format!("Strings found: {}\n", strings.len()));
+    for string in strings {
+        match string.count {
+            Some(count) if count > 1 => result.push_str(&format!(
+                "{} {} (x{})\n",
+                string.location, string.value, count
+            )),
+            _ => result.push_str(&format!("{} {}\n", string.location, string.value)),
+        }
     }
+    if let Some(spill) = &report.spill {
+        result.push_str(&format!("More strings, past the memory limit: {}\n", spill.results));
+        result.push_str(&format!("  in {}\n", spill.path));
+    }
+    push_truncated(&mut result, report);
 
-    // For actual memory scanning, we'd need root access to read /proc/[pid]/mem
-    // This is a simplified version that demonstrates the concept
-    result.push_str("Note: Full memory scanning requires root access\n");
-    result.push_str("Use Accessibility Service for non-root text extraction\n");
+    result
+}
 
-    Ok(result)
+/// Search every readable region of a process for a regex
+fn search_regex_in_process(
+    session: &mut Session,
+    pattern: &str,
+    flags: i32,
+    context: usize,
+) -> Result<Report, ExtractError> {
+    let mut scanner = regex_scanner(pattern, flags)?;
+    Ok(search_process(session, &mut scanner, &[pattern.to_string()], context))
 }
 
-/// Check if we have root access
-fn check_root_access() -> bool {
-    // Check if we can access /proc/1/mem (init process)
-    // This typically requires root
-    fs::metadata("/proc/1/mem").is_ok()
+fn regex_scanner(pattern: &str, flags: i32) -> Result<PatternScanner<Regex>, ExtractError> {
+    let regex = search::compile_regex(pattern, flags)
+        .map_err(|e| ExtractError::InvalidArgument(format!("Invalid regex: {}", e)))?;
+    Ok(PatternScanner::new(regex))
+}
+
+/// Search every readable region of a process with a prepared scanner,
+/// which can be reused for the next process
+fn search_process<M: Matcher>(
+    session: &mut Session,
+    scanner: &mut PatternScanner<M>,
+    patterns: &[String],
+    context: usize,
+) -> Report {
+    let mut report = Report::new(session.pid());
+    let regions = session.regions(&RegionFilter::default());
+    report.stats.reader = Some(session.reader_name());
+    let started = session.start_scan();
+    let mut control = started.control();
+    let mut scan = Scan::default();
+    report.stats.truncated = scan::search_regions(
+        session.reader(),
+        &regions,
+        scanner,
+        context,
+        &mut scan,
+        &mut control,
+    );
+    report.patterns = Some(patterns.to_vec());
+    let mut matches = report.add_scan(scan);
+    // Matches are few enough to always name the symbols they lie in
+    Symbolizer::new(session.all_regions()).annotate(&mut matches);
+    report.matches = Some(matches);
+    report.finish();
+    report
 }
 
-/// Scan a byte buffer for printable ASCII strings
-fn extract_printable_strings(data: &[u8], min_length: usize) -> Vec<String> {
-    let mut strings = Vec::new();
-    let mut current_string = String::new();
+/// One line per match
+fn regex_text(report: &Report) -> String {
+    let matches = report.matches.as_deref().unwrap_or_default();
 
-    for &byte in data {
-        if byte >= 32 && byte <= 126 {
-            // Printable ASCII
-            current_string.push(byte as char);
-        } else {
-            if current_string.len() >= min_length {
-                strings.push(current_string.clone());
-            }
-            current_string.clear();
-        }
+    let mut result = String::new();
+    result.push_str(&format!("Regex matches for PID {}: {}\n", report.pid, matches.len()));
+    for found in matches {
+        result.push_str(&format!("{} {}\n", found.location, found.bytes.escape_ascii()));
+        push_context(&mut result, found);
+    }
+    push_truncated(&mut result, report);
+    result
+}
+
+/// Search every readable region of a process for a set of keywords
+fn search_keywords_in_process(
+    session: &mut Session,
+    keywords: &[String],
+    case_insensitive: bool,
+    context: usize,
+) -> Result<Report, ExtractError> {
+    let mut scanner = keyword_scanner(keywords, case_insensitive)?;
+    Ok(search_process(session, &mut scanner, keywords, context))
+}
+
+fn keyword_scanner(
+    keywords: &[String],
+    case_insensitive: bool,
+) -> Result<PatternScanner<AhoCorasick>, ExtractError> {
+    if keywords.is_empty() {
+        return Err(ExtractError::InvalidArgument("No keywords given".to_string()));
+    }
+    let matcher = search::build_keyword_matcher(keywords, case_insensitive)
+        .map_err(|e| ExtractError::InvalidArgument(format!("Invalid keywords: {}", e)))?;
+    let longest = keywords.iter().map(String::len).max().unwrap_or(0);
+    Ok(PatternScanner::with_overlap(matcher, longest.max(search::DEFAULT_OVERLAP)))
+}
+
+/// Hits per keyword followed by one line per hit
+fn keywords_text(report: &Report) -> String {
+    let keywords = report.patterns.as_deref().unwrap_or_default();
+    let matches = report.matches.as_deref().unwrap_or_default();
+
+    let mut counts = vec![0usize; keywords.len()];
+    let mut lines = String::new();
+    for found in matches {
+        counts[found.pattern] += 1;
+        lines.push_str(&format!("{} {}\n", found.location, keywords[found.pattern]));
+        push_context(&mut lines, found);
+    }
+
+    let matched = counts.iter().filter(|&&count| count > 0).count();
+    let mut result = String::new();
+    result.push_str(&format!(
+        "Keyword hits for PID {}: {} ({} of {} keywords matched)\n",
+        report.pid,
+        matches.len(),
+        matched,
+        keywords.len()
+    ));
+    for (keyword, count) in keywords.iter().zip(&counts).filter(|(_, &count)| count > 0) {
+        result.push_str(&format!("{}: {}\n", keyword, count));
+    }
+    result.push('\n');
+    result.push_str(&lines);
+    push_truncated(&mut result, report);
+    result
+}
+
+/// Note at the end of a text result that the scan stopped early
+fn push_truncated(out: &mut String, report: &Report) {
+    if let Some(reason) = report.stats.truncated {
+        out.push_str(&format!("\nScan stopped early ({}); results are partial\n", reason));
     }
+}
 
-    // Don't forget the last string
-    if current_string.len() >= min_length {
-        strings.push(current_string);
+/// Append the hexdump of a match's context, indented under its result line
+fn push_context(out: &mut String, found: &scan::Match) {
+    if let Some(context) = &found.context {
+        for line in context.hexdump().lines() {
+            out.push_str("    ");
+            out.push_str(line);
+            out.push('\n');
+        }
     }
+}
 
-    strings
+/// Check if we have root access
+fn check_root_access() -> bool {
+    // Check if we can access /proc/1/mem (init process)
+    // This typically requires root
+    fs::metadata("/proc/1/mem").is_ok()
 }
 
 #[cfg(test)]
@@ -173,11 +6018,43 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_extract_printable_strings() {
-        let data = b"Hello\x00World\x00Test123\x00";
-        let strings = extract_printable_strings(data, 3);
-        assert!(strings.contains(&"Hello".to_string()));
-        assert!(strings.contains(&"World".to_string()));
-        assert!(strings.contains(&"Test123".to_string()));
+    fn test_read_memory_range_of_own_process() {
+        let data = b"raw bytes for follow-up analysis".to_vec();
+        let pid = std::process::id() as i32;
+
+        let mut mem = open_reader(pid).unwrap();
+
+        let bytes =
+            read_memory_range(mem.as_mut(), data.as_ptr() as u64, data.len() as i32).unwrap();
+        assert_eq!(bytes, data);
+
+        assert!(read_memory_range(mem.as_mut(), data.as_ptr() as u64, -1).is_err());
+        assert!(read_memory_range(mem.as_mut(), 0, 16).is_err());
+    }
+
+    #[test]
+    fn test_preset_options_reject_lengths_below_one() {
+        assert_eq!(preset_options(8, 1).unwrap().min_length, 8);
+        for min_length in [0, -1, i32::MIN] {
+            let options = preset_options(min_length, 0);
+            assert!(matches!(options, Err(ExtractError::InvalidArgument(_))));
+        }
+    }
+
+    #[test]
+    fn test_batch_reuses_scanner_and_keeps_failures() {
+        let data = b"batch-marker-7731".to_vec();
+        let pid = std::process::id() as i32;
+
+        let patterns = ["batch-marker-[0-9]+".to_string()];
+        let mut scanner = regex_scanner(&patterns[0], 0).unwrap();
+        let batch = run_batch(&[pid, -1], ReaderStrategy::Auto, |session| {
+            Ok(search_process(session, &mut scanner, &patterns, 0))
+        });
+
+        let matches = batch.reports[&pid].matches.as_deref().unwrap();
+        assert!(matches.iter().any(|found| found.location.address == data.as_ptr() as u64));
+        assert!(batch.errors.contains_key(&-1));
+        assert_eq!(batch.stats.results, matches.len());
     }
 }
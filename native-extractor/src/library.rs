@@ -0,0 +1,788 @@
+//! Reconstruction of loaded shared libraries from memory
+//!
+//! Protectors commonly ship native code encrypted and decrypt it only once
+//! the library is loaded, so the file on disk is of little use. Like
+//! SoFixer, [`rebuild_library`] turns the mapped image back into an ELF
+//! file that disassemblers and `readelf` load:
+//!
+//! - The image is written as it is laid out in memory, from the ELF header
+//!   to the end of the last segment, so each loadable segment's file offset
+//!   becomes its address relative to the first and its file size its memory
+//!   size, `.bss` included.
+//! - Relative relocations, which the linker applied by adding the load
+//!   bias, are undone so that pointers within the library are back to
+//!   link-time addresses. Relocations against symbols keep the addresses
+//!   they were resolved to.
+//! - The section headers, which are never loaded, are replaced by a table
+//!   of `.dynsym`, `.dynstr` and `.dynamic` found from the dynamic section,
+//!   so tools that read symbols from sections find them.
+//!
+//! The ELF header and program headers must still be in memory: a library
+//! whose headers the protector erased after loading can't be rebuilt.
+
+use crate::dump::DumpedFile;
+use crate::elf::{self, ElfHeader, ProgramHeader, PT_DYNAMIC, PT_LOAD};
+use crate::maps::MemoryRegion;
+use crate::reader::{self, MemoryReader};
+use crate::scan::{self, RegionVisitor, ScanControl, ScanSink, StopReason};
+use crate::sha256::Sha256;
+use std::fs::{self, File};
+use std::io;
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+
+const EM_386: u16 = 3;
+const EM_ARM: u16 = 40;
+const EM_X86_64: u16 = 62;
+const EM_AARCH64: u16 = 183;
+
+const DT_HASH: u64 = 4;
+const DT_STRTAB: u64 = 5;
+const DT_SYMTAB: u64 = 6;
+const DT_RELA: u64 = 7;
+const DT_RELASZ: u64 = 8;
+const DT_STRSZ: u64 = 10;
+const DT_SYMENT: u64 = 11;
+const DT_REL: u64 = 17;
+const DT_RELSZ: u64 = 18;
+const DT_PLTREL: u64 = 20;
+const DT_DEBUG: u64 = 21;
+const DT_JMPREL: u64 = 23;
+const DT_PLTRELSZ: u64 = 2;
+const DT_RELRSZ: u64 = 35;
+const DT_RELR: u64 = 36;
+const DT_ANDROID_REL: u64 = 0x6000_000f;
+const DT_ANDROID_RELA: u64 = 0x6000_0011;
+const DT_GNU_HASH: u64 = 0x6fff_fef5;
+
+/// Tags whose value is an address in the library
+const POINTER_TAGS: [u64; 20] = [
+    3, DT_HASH, DT_STRTAB, DT_SYMTAB, DT_RELA, 12, 13, DT_REL, DT_JMPREL, 25, 26, 32, DT_RELR,
+    DT_ANDROID_REL, DT_ANDROID_RELA, DT_GNU_HASH, 0x6fff_fff0, 0x6fff_fffc, 0x6fff_fffe,
+    0x6fff_fef6,
+];
+
+const SHT_STRTAB: u32 = 3;
+const SHT_DYNAMIC: u32 = 6;
+const SHT_DYNSYM: u32 = 11;
+const SHF_WRITE: u64 = 1;
+const SHF_ALLOC: u64 = 2;
+
+/// Largest program header table read; real ones hold a dozen entries
+const MAX_PROGRAM_HEADERS_SIZE: usize = 64 * 1024;
+
+/// Page size the loader is assumed to map segments with
+const PAGE_SIZE: u64 = 4096;
+
+/// Largest file laid out by [`FileImage::read`]
+const MAX_IMAGE_SIZE: u64 = 1 << 30;
+
+/// A shared library found mapped in a process
+#[derive(Debug, Clone)]
+pub struct LoadedLibrary {
+    pub path: String,
+    /// Address of the ELF header
+    pub base: u64,
+    /// End of the last loadable segment, rounded up to a page
+    pub end: u64,
+    header: ElfHeader,
+    segments: Vec<ProgramHeader>,
+    /// Link-time address of the ELF header
+    min_vaddr: u64,
+}
+
+impl LoadedLibrary {
+    /// What the linker added to link-time addresses
+    fn load_bias(&self) -> u64 {
+        self.base.wrapping_sub(self.min_vaddr)
+    }
+}
+
+/// Find the ELF file loaded from `name`, a full path or a file name, among
+/// `regions`: the first mapping of it that starts with a readable ELF
+/// header and program headers
+pub fn find_library(
+    reader: &mut dyn MemoryReader,
+    regions: &[MemoryRegion],
+    name: &str,
+) -> Option<LoadedLibrary> {
+    let suffix = format!("/{}", name);
+    regions
+        .iter()
+        .filter(|region| {
+            region
+                .path
+                .as_deref()
+                .is_some_and(|path| path == name || path.ends_with(&suffix))
+        })
+        .find_map(|region| read_library(reader, region))
+}
+
+fn read_library(reader: &mut dyn MemoryReader, region: &MemoryRegion) -> Option<LoadedLibrary> {
+    let mut bytes = [0u8; 64];
+    reader::read_into(reader, region.start, &mut bytes).ok()?;
+    let header = elf::parse_header(&bytes)?;
+    let size = elf::program_headers_size(&header);
+    if size > MAX_PROGRAM_HEADERS_SIZE {
+        return None;
+    }
+    let mut table = vec![0u8; size];
+    if reader::read_into(reader, region.start + header.phoff, &mut table).ok()? < size {
+        return None;
+    }
+    let segments = elf::parse_program_headers(&header, &table)?;
+
+    let loads = || segments.iter().filter(|segment| segment.kind == PT_LOAD);
+    let first = loads().min_by_key(|segment| segment.vaddr)?;
+    let min_vaddr = first.vaddr.checked_sub(first.offset)?;
+    let max_vaddr = loads().map(|segment| segment.vaddr.saturating_add(segment.memsz)).max()?;
+    let end = region.start.checked_add(max_vaddr - min_vaddr)?.checked_next_multiple_of(PAGE_SIZE)?;
+    Some(LoadedLibrary {
+        path: region.path.clone()?,
+        base: region.start,
+        end,
+        header,
+        segments,
+        min_vaddr,
+    })
+}
+
+/// Read the image of `library` from `regions` and write it to `path` as a
+/// rebuilt ELF file, delivering its [`DumpedFile`]. What couldn't be
+/// rebuilt is noted in `errors`.
+///
+/// Pages of the image that can't be read are written as zeros. Nothing is
+/// written if `control` stops the read.
+pub fn rebuild_library(
+    reader: &mut dyn MemoryReader,
+    regions: &[MemoryRegion],
+    library: &LoadedLibrary,
+    path: &Path,
+    sink: &mut dyn ScanSink<DumpedFile>,
+    control: &mut ScanControl,
+    errors: &mut Vec<String>,
+) -> io::Result<Option<StopReason>> {
+    let mapped: Vec<MemoryRegion> = regions
+        .iter()
+        .filter(|region| region.start < library.end && region.end > library.base)
+        .map(|region| MemoryRegion {
+            start: region.start.max(library.base),
+            end: region.end.min(library.end),
+            ..region.clone()
+        })
+        .collect();
+    let mut visitor = ImageVisitor {
+        base: library.base,
+        image: vec![0u8; (library.end - library.base) as usize],
+    };
+    let stopped = scan::walk_regions(reader, &mapped, &mut visitor, sink, control);
+    if stopped.is_some() {
+        return Ok(stopped);
+    }
+
+    let mut image = visitor.image;
+    fix_image(library, &mut image, errors);
+    fs::write(path, &image)?;
+    let mut hash = Sha256::new();
+    hash.update(&image);
+    sink.result(DumpedFile {
+        start: library.base,
+        file: path.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+        size: image.len() as u64,
+        sha256: hash.finish_hex(),
+    });
+    Ok(None)
+}
+
+/// Copies each chunk read into its place in the image
+struct ImageVisitor {
+    base: u64,
+    image: Vec<u8>,
+}
+
+impl RegionVisitor for ImageVisitor {
+    type Output = DumpedFile;
+
+    fn visit_chunk(&mut self, _region: &MemoryRegion, address: u64, chunk: &[u8]) {
+        let at = (address - self.base) as usize;
+        self.image[at..at + chunk.len()].copy_from_slice(chunk);
+    }
+
+    fn end_region(
+        &mut self,
+        _region: &MemoryRegion,
+        _reader: &mut dyn MemoryReader,
+        _sink: &mut dyn ScanSink<DumpedFile>,
+    ) {
+    }
+}
+
+/// An ELF file laid out the way the linker maps it, read from the file
+/// rather than from memory
+pub struct FileImage {
+    /// Link-time address of the first byte of `bytes`
+    pub min_vaddr: u64,
+    /// The loadable segments at their addresses, zero-filled between
+    pub bytes: Vec<u8>,
+    class64: bool,
+    segments: Vec<ProgramHeader>,
+}
+
+impl FileImage {
+    /// Lay out the ELF file that starts `offset` bytes into `file`, such as
+    /// a library stored uncompressed in an APK; `None` if it isn't ELF
+    pub fn read(file: &File, offset: u64) -> io::Result<Option<Self>> {
+        let mut bytes = [0u8; 64];
+        if !read_exact_at(file, &mut bytes, offset)? {
+            return Ok(None);
+        }
+        let Some(header) = elf::parse_header(&bytes) else {
+            return Ok(None);
+        };
+        let size = elf::program_headers_size(&header);
+        if size > MAX_PROGRAM_HEADERS_SIZE {
+            return Ok(None);
+        }
+        let mut table = vec![0u8; size];
+        if !read_exact_at(file, &mut table, offset + header.phoff)? {
+            return Ok(None);
+        }
+        let Some(segments) = elf::parse_program_headers(&header, &table) else {
+            return Ok(None);
+        };
+
+        let loads: Vec<&ProgramHeader> =
+            segments.iter().filter(|segment| segment.kind == PT_LOAD).collect();
+        let Some(first) = loads.iter().min_by_key(|segment| segment.vaddr) else {
+            return Ok(None);
+        };
+        let min_vaddr = first.vaddr.saturating_sub(first.offset);
+        let max_vaddr = loads.iter().map(|segment| segment.vaddr.saturating_add(segment.memsz));
+        let size = max_vaddr.max().unwrap_or(0) - min_vaddr;
+        if size > MAX_IMAGE_SIZE {
+            return Ok(None);
+        }
+        // The loader maps whole pages, so each segment brings the file's
+        // bytes around it on its first and last page, up to the next segment
+        let mut image = vec![0u8; size.next_multiple_of(PAGE_SIZE) as usize];
+        let mut loads = loads;
+        loads.sort_by_key(|segment| segment.vaddr);
+        for segment in loads {
+            let skew = segment.vaddr % PAGE_SIZE;
+            let at = (segment.vaddr - skew - min_vaddr) as usize;
+            let mapped = (skew + segment.filesz).next_multiple_of(PAGE_SIZE) as usize;
+            let Some(slot) = image.get_mut(at..at + mapped) else {
+                return Ok(None);
+            };
+            let Some(from) = segment.offset.checked_sub(skew) else {
+                return Ok(None);
+            };
+            read_full_at(file, slot, offset + from)?;
+            if segment.memsz > segment.filesz {
+                slot[(skew + segment.filesz) as usize..].fill(0);
+            }
+        }
+        Ok(Some(FileImage {
+            min_vaddr,
+            bytes: image,
+            class64: header.class64,
+            segments,
+        }))
+    }
+
+    /// Size of the words relocations write
+    pub fn word_size(&self) -> usize {
+        if self.class64 {
+            8
+        } else {
+            4
+        }
+    }
+
+    /// Link-time addresses of the words the dynamic relocations write to,
+    /// and whether some are in Android's packed format, which isn't decoded
+    pub fn relocated_slots(&mut self) -> (Vec<u64>, bool) {
+        let elf = Image {
+            bytes: &mut self.bytes,
+            class64: self.class64,
+            min_vaddr: self.min_vaddr,
+        };
+        let Some(segment) = self.segments.iter().find(|segment| segment.kind == PT_DYNAMIC) else {
+            return (Vec::new(), false);
+        };
+        let Some(start) = elf.offset(segment.vaddr) else {
+            return (Vec::new(), false);
+        };
+        let end = (start + segment.filesz as usize).min(elf.bytes.len());
+        let dynamic = elf::parse_dynamic(elf.class64, &elf.bytes[start..end]);
+        let value = |tag: u64| dynamic.iter().find(|(t, _)| *t == tag).map(|&(_, value)| value);
+
+        let mut slots: Vec<u64> =
+            relocations(&elf, &dynamic).into_iter().map(|relocation| relocation.offset).collect();
+        if let (Some(table), Some(size)) = (value(DT_RELR), value(DT_RELRSZ)) {
+            slots.extend(relr_slots(&elf, table, size));
+        }
+        slots.sort_unstable();
+        slots.dedup();
+        let packed = value(DT_ANDROID_REL).is_some() || value(DT_ANDROID_RELA).is_some();
+        (slots, packed)
+    }
+}
+
+/// Read what the file holds of `buf` at `offset`, leaving the rest as is
+fn read_full_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<()> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match file.read_at(&mut buf[filled..], offset + filled as u64) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// Fill `buf` from `offset` in `file`; `false` if the file ends first
+fn read_exact_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<bool> {
+    match file.read_exact_at(buf, offset) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Rewrite the headers, dynamic section and relocated pointers of an image
+/// read from memory, as described in the module documentation
+fn fix_image(library: &LoadedLibrary, image: &mut Vec<u8>, errors: &mut Vec<String>) {
+    let mut elf = Image {
+        bytes: image,
+        class64: library.header.class64,
+        min_vaddr: library.min_vaddr,
+    };
+    fix_program_headers(library, &mut elf);
+    let dynamic = fix_dynamic(library, &mut elf);
+    let Some(dynamic) = dynamic else {
+        errors.push("No dynamic section; relocations are left applied".to_string());
+        elf.write_section_headers(&[]);
+        return;
+    };
+    undo_relocations(library, &mut elf, &dynamic, errors);
+
+    let sections = dynamic_sections(&elf, &dynamic, &library.segments);
+    elf.write_section_headers(&sections);
+}
+
+fn fix_program_headers(library: &LoadedLibrary, elf: &mut Image) {
+    let header = &library.header;
+    let (offset_at, filesz_at) = if header.class64 { (8, 32) } else { (4, 16) };
+    for (i, segment) in library.segments.iter().enumerate() {
+        let entry = header.phoff as usize + i * usize::from(header.phentsize);
+        let offset = segment.vaddr.saturating_sub(library.min_vaddr);
+        elf.put_word(entry + offset_at, offset);
+        if segment.kind == PT_LOAD {
+            elf.put_word(entry + filesz_at, segment.memsz);
+        }
+    }
+}
+
+/// Restore the pointers of the dynamic section to link-time addresses and
+/// clear `DT_DEBUG`, returning its entries
+fn fix_dynamic(library: &LoadedLibrary, elf: &mut Image) -> Option<Vec<(u64, u64)>> {
+    let segment = library.segments.iter().find(|segment| segment.kind == PT_DYNAMIC)?;
+    let start = elf.offset(segment.vaddr)?;
+    let end = (start + segment.memsz as usize).min(elf.bytes.len());
+    let mut entries = elf::parse_dynamic(elf.class64, &elf.bytes[start..end]);
+
+    let bias = library.load_bias();
+    let entry_size = 2 * elf.word();
+    for (i, (tag, value)) in entries.iter_mut().enumerate() {
+        // bionic leaves the dynamic section as linked, but not every loader does
+        let relocated = (library.base..library.end).contains(value) && bias != 0;
+        let fixed = match *tag {
+            DT_DEBUG => 0,
+            tag if POINTER_TAGS.contains(&tag) && relocated => value.wrapping_sub(bias),
+            _ => continue,
+        };
+        *value = fixed;
+        elf.put_word(start + i * entry_size + elf.word(), fixed);
+    }
+    Some(entries)
+}
+
+fn undo_relocations(
+    library: &LoadedLibrary,
+    elf: &mut Image,
+    dynamic: &[(u64, u64)],
+    errors: &mut Vec<String>,
+) {
+    let bias = library.load_bias();
+    if bias == 0 {
+        return;
+    }
+    let value = |tag: u64| dynamic.iter().find(|(t, _)| *t == tag).map(|&(_, value)| value);
+    if value(DT_ANDROID_REL).is_some() || value(DT_ANDROID_RELA).is_some() {
+        errors.push("Android packed relocations are left applied".to_string());
+    }
+    let relative = match library.header.machine {
+        EM_AARCH64 => 1027,
+        EM_ARM => 23,
+        EM_X86_64 | EM_386 => 8,
+        machine => {
+            errors.push(format!("Relocations of machine {} are left applied", machine));
+            return;
+        }
+    };
+
+    for relocation in relocations(elf, dynamic).into_iter().filter(|r| r.kind == relative) {
+        let Some(slot) = elf.offset(relocation.offset) else {
+            continue;
+        };
+        let original = match relocation.addend {
+            Some(addend) => Some(addend),
+            None => elf.word_at(slot).map(|value| value.wrapping_sub(bias)),
+        };
+        if let Some(original) = original {
+            elf.put_word(slot, original);
+        }
+    }
+    if let (Some(table), Some(size)) = (value(DT_RELR), value(DT_RELRSZ)) {
+        for slot in relr_slots(elf, table, size) {
+            elf.unrelocate(slot, bias);
+        }
+    }
+}
+
+/// One entry of a `DT_REL` or `DT_RELA` table
+struct Relocation {
+    /// Link-time address of the word relocated
+    offset: u64,
+    kind: u64,
+    /// Only `DT_RELA` entries have one; `DT_REL` ones use the word itself
+    addend: Option<u64>,
+}
+
+/// The entries of the relocation tables the dynamic section points to,
+/// lazy-binding ones included, leaving out `DT_RELR` and packed tables
+fn relocations(elf: &Image, dynamic: &[(u64, u64)]) -> Vec<Relocation> {
+    let value = |tag: u64| dynamic.iter().find(|(t, _)| *t == tag).map(|&(_, value)| value);
+    let mut tables = Vec::new();
+    if let (Some(table), Some(size)) = (value(DT_RELA), value(DT_RELASZ)) {
+        tables.push((table, size, true));
+    }
+    if let (Some(table), Some(size)) = (value(DT_REL), value(DT_RELSZ)) {
+        tables.push((table, size, false));
+    }
+    if let (Some(table), Some(size), Some(kind)) =
+        (value(DT_JMPREL), value(DT_PLTRELSZ), value(DT_PLTREL))
+    {
+        tables.push((table, size, kind == DT_RELA));
+    }
+
+    let word = elf.word();
+    let mut relocations = Vec::new();
+    for (table, size, with_addend) in tables {
+        let entry_size = if with_addend { 3 * word } else { 2 * word };
+        let Some(start) = elf.offset(table) else {
+            continue;
+        };
+        for entry in (start..start.saturating_add(size as usize)).step_by(entry_size) {
+            let (Some(offset), Some(info)) = (elf.word_at(entry), elf.word_at(entry + word)) else {
+                break;
+            };
+            relocations.push(Relocation {
+                offset,
+                kind: if elf.class64 { info & 0xffff_ffff } else { info & 0xff },
+                addend: if with_addend { elf.word_at(entry + 2 * word) } else { None },
+            });
+        }
+    }
+    relocations
+}
+
+/// The words a `DT_RELR` table relocates: an address, then bitmaps of
+/// which of the following words are relocated too
+fn relr_slots(elf: &Image, table: u64, size: u64) -> Vec<u64> {
+    let word = elf.word();
+    let Some(start) = elf.offset(table) else {
+        return Vec::new();
+    };
+    let mut slots = Vec::new();
+    let mut next = 0u64;
+    for entry in (start..start.saturating_add(size as usize)).step_by(word) {
+        let Some(value) = elf.word_at(entry) else {
+            break;
+        };
+        if value & 1 == 0 {
+            slots.push(value);
+            next = value + word as u64;
+            continue;
+        }
+        for bit in 1..8 * word {
+            if (value >> bit) & 1 != 0 {
+                slots.push(next + ((bit - 1) * word) as u64);
+            }
+        }
+        next += ((8 * word - 1) * word) as u64;
+    }
+    slots
+}
+
+/// One entry of the rebuilt section header table
+struct Section {
+    name: &'static str,
+    kind: u32,
+    flags: u64,
+    addr: u64,
+    size: u64,
+    link: u32,
+    entsize: u64,
+}
+
+/// The sections the dynamic section describes: `.dynstr`, `.dynsym`, then
+/// `.dynamic` itself
+fn dynamic_sections(
+    elf: &Image,
+    dynamic: &[(u64, u64)],
+    segments: &[ProgramHeader],
+) -> Vec<Section> {
+    let value = |tag: u64| dynamic.iter().find(|(t, _)| *t == tag).map(|&(_, value)| value);
+    let mut sections = Vec::new();
+    let (Some(strtab), Some(strsz), Some(symtab)) =
+        (value(DT_STRTAB), value(DT_STRSZ), value(DT_SYMTAB))
+    else {
+        return sections;
+    };
+    let syment = value(DT_SYMENT).unwrap_or(if elf.class64 { 24 } else { 16 });
+    let symbols = value(DT_HASH)
+        .and_then(|hash| elf.u32_at(elf.offset(hash)? + 4).map(u64::from))
+        .or_else(|| value(DT_GNU_HASH).and_then(|hash| gnu_hash_symbols(elf, hash)))
+        // Linkers put the string table right after the symbols
+        .or_else(|| (strtab > symtab).then(|| (strtab - symtab) / syment));
+
+    sections.push(Section {
+        name: ".dynstr",
+        kind: SHT_STRTAB,
+        flags: SHF_ALLOC,
+        addr: strtab,
+        size: strsz,
+        link: 0,
+        entsize: 0,
+    });
+    if let Some(symbols) = symbols {
+        sections.push(Section {
+            name: ".dynsym",
+            kind: SHT_DYNSYM,
+            flags: SHF_ALLOC,
+            addr: symtab,
+            size: symbols * syment,
+            link: 1,
+            entsize: syment,
+        });
+    }
+    if let Some(segment) = segments.iter().find(|segment| segment.kind == PT_DYNAMIC) {
+        sections.push(Section {
+            name: ".dynamic",
+            kind: SHT_DYNAMIC,
+            flags: SHF_ALLOC | SHF_WRITE,
+            addr: segment.vaddr,
+            size: segment.memsz,
+            link: 1,
+            entsize: 2 * elf.word() as u64,
+        });
+    }
+    sections
+}
+
+/// Number of symbols in the table a `DT_GNU_HASH` section indexes: one
+/// past the last symbol of the longest chain of the last bucket
+fn gnu_hash_symbols(elf: &Image, hash: u64) -> Option<u64> {
+    let start = elf.offset(hash)?;
+    let buckets = elf.u32_at(start)? as usize;
+    let first = elf.u32_at(start + 4)?;
+    let bloom = elf.u32_at(start + 8)? as usize;
+    let buckets_at = start + 16 + bloom * elf.word();
+    let last = (0..buckets).filter_map(|i| elf.u32_at(buckets_at + 4 * i)).max()?;
+    if last < first {
+        return Some(u64::from(first));
+    }
+    let chains_at = buckets_at + 4 * buckets;
+    let mut symbol = last;
+    while elf.u32_at(chains_at + 4 * (symbol - first) as usize)? & 1 == 0 {
+        symbol += 1;
+    }
+    Some(u64::from(symbol) + 1)
+}
+
+/// The image being rebuilt, laid out from the ELF header at offset 0
+struct Image<'a> {
+    bytes: &'a mut Vec<u8>,
+    class64: bool,
+    min_vaddr: u64,
+}
+
+impl Image<'_> {
+    fn word(&self) -> usize {
+        if self.class64 {
+            8
+        } else {
+            4
+        }
+    }
+
+    /// Offset in the image of the link-time address `vaddr`
+    fn offset(&self, vaddr: u64) -> Option<usize> {
+        let offset = usize::try_from(vaddr.checked_sub(self.min_vaddr)?).ok()?;
+        (offset < self.bytes.len()).then_some(offset)
+    }
+
+    fn u32_at(&self, at: usize) -> Option<u32> {
+        elf::u32_at(self.bytes, at)
+    }
+
+    fn word_at(&self, at: usize) -> Option<u64> {
+        elf::word_at(self.class64, self.bytes, at)
+    }
+
+    /// Write `value` as a word at `at`, if that lies within the image
+    fn put_word(&mut self, at: usize, value: u64) {
+        let word = self.word();
+        if let Some(slot) = self.bytes.get_mut(at..at + word) {
+            slot.copy_from_slice(&value.to_le_bytes()[..word]);
+        }
+    }
+
+    /// Subtract `bias` from the word at link-time address `vaddr`
+    fn unrelocate(&mut self, vaddr: u64, bias: u64) {
+        if let Some(at) = self.offset(vaddr) {
+            if let Some(value) = self.word_at(at) {
+                self.put_word(at, value.wrapping_sub(bias));
+            }
+        }
+    }
+
+    /// Append `sections` and their name table to the image and point the
+    /// ELF header at them; with none, the header is left without sections
+    fn write_section_headers(&mut self, sections: &[Section]) {
+        let (shoff_at, shentsize_at) = if self.class64 { (40, 58) } else { (32, 46) };
+        if sections.is_empty() {
+            self.put_word(shoff_at, 0);
+            self.bytes[shentsize_at + 2..shentsize_at + 6].fill(0);
+            return;
+        }
+
+        let mut names = vec![0u8];
+        let mut name_offsets = Vec::new();
+        for name in sections.iter().map(|section| section.name).chain([".shstrtab"]) {
+            name_offsets.push(names.len() as u32);
+            names.extend_from_slice(name.as_bytes());
+            names.push(0);
+        }
+        let names_at = self.bytes.len() as u64;
+        self.bytes.extend_from_slice(&names);
+        let table_at = self.bytes.len().next_multiple_of(self.word());
+        self.bytes.resize(table_at, 0);
+
+        let names_section = Section {
+            name: ".shstrtab",
+            kind: SHT_STRTAB,
+            flags: 0,
+            addr: 0,
+            size: names.len() as u64,
+            link: 0,
+            entsize: 0,
+        };
+        let entry_size = if self.class64 { 64 } else { 40 };
+        self.bytes.resize(table_at + entry_size, 0);
+        for (section, &name) in sections.iter().chain([&names_section]).zip(&name_offsets) {
+            let offset = match section.addr {
+                0 => names_at,
+                addr => addr.saturating_sub(self.min_vaddr),
+            };
+            let align = if section.kind == SHT_STRTAB { 1 } else { self.word() as u64 };
+            // (value, whether it is a word rather than 32 bits in ELF64)
+            let fields = [
+                (u64::from(name), false),
+                (u64::from(section.kind), false),
+                (section.flags, true),
+                (section.addr, true),
+                (offset, true),
+                (section.size, true),
+                (u64::from(section.link), false),
+                (0, false),
+                (align, true),
+                (section.entsize, true),
+            ];
+            for (value, wide) in fields {
+                let size = if wide && self.class64 { 8 } else { 4 };
+                self.bytes.extend_from_slice(&value.to_le_bytes()[..size]);
+            }
+        }
+
+        let count = sections.len() as u16 + 2;
+        self.put_word(shoff_at, table_at as u64);
+        let fields = [entry_size as u16, count, count - 1];
+        for (i, value) in fields.into_iter().enumerate() {
+            let at = shentsize_at + 2 * i;
+            self.bytes[at..at + 2].copy_from_slice(&value.to_le_bytes());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps;
+    use crate::reader::ProcfsReader;
+    use crate::scan::Scan;
+
+    #[test]
+    fn test_rebuilds_own_executable() {
+        let pid = std::process::id() as i32;
+        let exe = fs::read_link("/proc/self/exe").unwrap();
+        let name = exe.file_name().unwrap().to_str().unwrap();
+        let regions: Vec<MemoryRegion> =
+            maps::read_maps(pid).unwrap().into_iter().filter(|region| region.perms.read).collect();
+        let mut reader = ProcfsReader::open(pid).unwrap();
+        let library = find_library(&mut reader, &regions, name).unwrap();
+
+        let path = std::env::temp_dir().join(format!("library-test-{}.so", pid));
+        let mut scan = Scan::default();
+        let mut errors = Vec::new();
+        let stopped = rebuild_library(
+            &mut reader,
+            &regions,
+            &library,
+            &path,
+            &mut scan,
+            &mut ScanControl::new(),
+            &mut errors,
+        );
+        assert_eq!(stopped.unwrap(), None);
+        let rebuilt = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(scan.results[0].size, rebuilt.len() as u64);
+
+        // Code is laid out as in memory, and unchanged from the file
+        let original = fs::read(&exe).unwrap();
+        let header = elf::parse_header(&rebuilt).unwrap();
+        let segments = elf::parse_program_headers(&header, &rebuilt[header.phoff as usize..]);
+        let code = segments.unwrap().into_iter().find(|segment| segment.flags & elf::PF_X != 0);
+        let code = code.unwrap();
+        let old = library.segments.iter().find(|segment| segment.vaddr == code.vaddr).unwrap();
+        assert_eq!(code.offset, code.vaddr - library.min_vaddr);
+        assert_eq!(code.filesz, code.memsz);
+        let size = old.filesz as usize;
+        assert_eq!(
+            &rebuilt[code.offset as usize..][..size],
+            &original[old.offset as usize..][..size]
+        );
+
+        // The section headers name the dynamic symbols
+        let shnum = u16::from_le_bytes(if header.class64 { [rebuilt[60], rebuilt[61]] } else {
+            [rebuilt[48], rebuilt[49]]
+        });
+        assert_eq!(shnum, 5);
+        assert!(rebuilt.windows(8).any(|window| window == b".dynsym\0"));
+    }
+}
@@ -0,0 +1,309 @@
+//! Parsing of /proc/[pid]/maps into structured [`MemoryRegion`] records
+//!
+//! /proc/[pid]/smaps repeats the maps lines, each followed by the memory
+//! usage of the mapping. It is much slower to read, since the kernel walks
+//! the page tables of every mapping, so it is only parsed on request.
+
+use serde::de::{self, Deserializer};
+use serde::{Deserialize, Serialize, Serializer};
+use std::fmt;
+use std::fs;
+use std::io;
+
+/// Access permissions of a mapping, the `rwxp` column of the maps file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Permissions {
+    pub read: bool,
+    pub write: bool,
+    pub execute: bool,
+    /// `s` (shared) rather than `p` (private, copy-on-write)
+    pub shared: bool,
+}
+
+impl Permissions {
+    /// Parse the four-character permission column, e.g. `r-xp`
+    pub fn parse(perms: &str) -> Option<Self> {
+        let bytes = perms.as_bytes();
+        if bytes.len() != 4 {
+            return None;
+        }
+        Some(Permissions {
+            read: bytes[0] == b'r',
+            write: bytes[1] == b'w',
+            execute: bytes[2] == b'x',
+            shared: bytes[3] == b's',
+        })
+    }
+}
+
+impl fmt::Display for Permissions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}{}{}",
+            if self.read { 'r' } else { '-' },
+            if self.write { 'w' } else { '-' },
+            if self.execute { 'x' } else { '-' },
+            if self.shared { 's' } else { 'p' },
+        )
+    }
+}
+
+impl Serialize for Permissions {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Permissions {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let perms = String::deserialize(deserializer)?;
+        Permissions::parse(&perms)
+            .ok_or_else(|| de::Error::custom(format!("invalid permissions: {}", perms)))
+    }
+}
+
+/// Memory usage of one mapping, from /proc/[pid]/smaps, in bytes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct MemoryUsage {
+    /// Resident in RAM
+    pub rss: u64,
+    /// Proportional set size: resident, with pages shared with other
+    /// processes divided between them
+    pub pss: u64,
+    pub shared_clean: u64,
+    pub shared_dirty: u64,
+    pub private_clean: u64,
+    pub private_dirty: u64,
+    /// Resident and not backed by a file
+    pub anonymous: u64,
+    /// Swapped out, to zram on most devices
+    pub swap: u64,
+    /// Swap divided between the processes sharing it, like `pss`
+    pub swap_pss: u64,
+}
+
+impl MemoryUsage {
+    /// Record an smaps field, `Rss:   8 kB` for example; fields that aren't
+    /// sizes or aren't kept are ignored
+    fn set(&mut self, name: &str, value: &str) {
+        let field = match name {
+            "Rss" => &mut self.rss,
+            "Pss" => &mut self.pss,
+            "Shared_Clean" => &mut self.shared_clean,
+            "Shared_Dirty" => &mut self.shared_dirty,
+            "Private_Clean" => &mut self.private_clean,
+            "Private_Dirty" => &mut self.private_dirty,
+            "Anonymous" => &mut self.anonymous,
+            "Swap" => &mut self.swap,
+            "SwapPss" => &mut self.swap_pss,
+            _ => return,
+        };
+        let kb = value.trim().strip_suffix("kB").map(str::trim);
+        if let Some(kb) = kb.and_then(|kb| kb.parse::<u64>().ok()) {
+            *field = kb * 1024;
+        }
+    }
+
+    /// Sum of `usages`
+    pub fn total<'a>(usages: impl IntoIterator<Item = &'a MemoryUsage>) -> MemoryUsage {
+        usages.into_iter().fold(MemoryUsage::default(), |total, usage| MemoryUsage {
+            rss: total.rss + usage.rss,
+            pss: total.pss + usage.pss,
+            shared_clean: total.shared_clean + usage.shared_clean,
+            shared_dirty: total.shared_dirty + usage.shared_dirty,
+            private_clean: total.private_clean + usage.private_clean,
+            private_dirty: total.private_dirty + usage.private_dirty,
+            anonymous: total.anonymous + usage.anonymous,
+            swap: total.swap + usage.swap,
+            swap_pss: total.swap_pss + usage.swap_pss,
+        })
+    }
+}
+
+/// One mapping from /proc/[pid]/maps
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryRegion {
+    pub start: u64,
+    pub end: u64,
+    pub perms: Permissions,
+    /// Offset into the backing file
+    pub offset: u64,
+    /// Backing device as `major:minor`
+    pub dev: String,
+    pub inode: u64,
+    /// Backing file or pseudo-path such as `[heap]` or `[anon:dalvik-main space]`;
+    /// `None` for unnamed anonymous mappings
+    pub path: Option<String>,
+    /// Memory usage, when the region was read from smaps
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<MemoryUsage>,
+}
+
+impl MemoryRegion {
+    pub fn size(&self) -> u64 {
+        self.end - self.start
+    }
+
+    pub fn contains(&self, address: u64) -> bool {
+        self.start <= address && address < self.end
+    }
+
+    /// Name used to label results found in this region
+    pub fn name(&self) -> &str {
+        self.path.as_deref().unwrap_or("[anonymous]")
+    }
+}
+
+impl fmt::Display for MemoryRegion {
+    /// Render the region in the kernel's own maps format
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:08x}-{:08x} {} {:08x} {} {}",
+            self.start, self.end, self.perms, self.offset, self.dev, self.inode
+        )?;
+        if let Some(path) = &self.path {
+            write!(f, " {}", path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Split off the next whitespace-delimited field
+fn next_field(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim_start();
+    if line.is_empty() {
+        return None;
+    }
+    let end = line.find(char::is_whitespace).unwrap_or(line.len());
+    Some((&line[..end], &line[end..]))
+}
+
+/// Parse a single maps line, returning `None` if it is malformed
+pub fn parse_maps_line(line: &str) -> Option<MemoryRegion> {
+    let (range, rest) = next_field(line)?;
+    let (perms, rest) = next_field(rest)?;
+    let (offset, rest) = next_field(rest)?;
+    let (dev, rest) = next_field(rest)?;
+    let (inode, rest) = next_field(rest)?;
+
+    let (start, end) = range.split_once('-')?;
+
+    // The path is everything after the inode column and may contain spaces
+    let path = rest.trim();
+
+    Some(MemoryRegion {
+        start: u64::from_str_radix(start, 16).ok()?,
+        end: u64::from_str_radix(end, 16).ok()?,
+        perms: Permissions::parse(perms)?,
+        offset: u64::from_str_radix(offset, 16).ok()?,
+        dev: dev.to_string(),
+        inode: inode.parse().ok()?,
+        path: (!path.is_empty()).then(|| path.to_string()),
+        usage: None,
+    })
+}
+
+/// Parse the full contents of a maps file, skipping malformed lines
+pub fn parse_maps(content: &str) -> Vec<MemoryRegion> {
+    content.lines().filter_map(parse_maps_line).collect()
+}
+
+/// Read and parse /proc/[pid]/maps
+pub fn read_maps(pid: i32) -> io::Result<Vec<MemoryRegion>> {
+    let content = fs::read_to_string(format!("/proc/{}/maps", pid))?;
+    Ok(parse_maps(&content))
+}
+
+/// Parse the full contents of an smaps file, skipping malformed lines.
+///
+/// Every region gets a [`MemoryUsage`], zero for the fields it lacks.
+pub fn parse_smaps(content: &str) -> Vec<MemoryRegion> {
+    let mut regions: Vec<MemoryRegion> = Vec::new();
+    for line in content.lines() {
+        // Field names have no `-`, and mapping lines start with a range
+        let field = line.split_once(':').filter(|(name, _)| !name.contains('-'));
+        match (field, regions.last_mut()) {
+            (Some((name, value)), Some(region)) => {
+                region.usage.get_or_insert_with(MemoryUsage::default).set(name, value)
+            }
+            (Some(_), None) => {}
+            (None, _) => {
+                if let Some(mut region) = parse_maps_line(line) {
+                    region.usage = Some(MemoryUsage::default());
+                    regions.push(region);
+                }
+            }
+        }
+    }
+    regions
+}
+
+/// Read and parse /proc/[pid]/smaps
+pub fn read_smaps(pid: i32) -> io::Result<Vec<MemoryRegion>> {
+    let content = fs::read_to_string(format!("/proc/{}/smaps", pid))?;
+    Ok(parse_smaps(&content))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_maps_line_with_path() {
+        let region = parse_maps_line(
+            "7f3c2a1000-7f3c2a5000 r-xp 0001c000 fd:05 1234      /system/lib64/libc.so",
+        )
+        .unwrap();
+
+        assert_eq!(region.start, 0x7f3c2a1000);
+        assert_eq!(region.end, 0x7f3c2a5000);
+        assert!(region.perms.read && region.perms.execute && !region.perms.write);
+        assert_eq!(region.offset, 0x1c000);
+        assert_eq!(region.dev, "fd:05");
+        assert_eq!(region.inode, 1234);
+        assert_eq!(region.path.as_deref(), Some("/system/lib64/libc.so"));
+    }
+
+    #[test]
+    fn test_parse_maps_keeps_spaces_in_path_and_skips_garbage() {
+        let maps = "12c00000-32c00000 rw-p 00000000 00:00 0   [anon:dalvik-main space (region space)]\n\
+                    not a maps line\n\
+                    7ffd000-7ffe000 rw-p 00000000 00:00 0\n";
+        let regions = parse_maps(maps);
+
+        assert_eq!(regions.len(), 2);
+        assert_eq!(regions[0].name(), "[anon:dalvik-main space (region space)]");
+        assert_eq!(regions[1].path, None);
+    }
+
+    #[test]
+    fn test_region_display_round_trips() {
+        let line = "7f3c2a1000-7f3c2a5000 rw-s 00000000 00:05 42 /dev/ashmem/shared (deleted)";
+        let region = parse_maps_line(line).unwrap();
+        assert_eq!(parse_maps_line(&region.to_string()).unwrap(), region);
+    }
+
+    #[test]
+    fn test_parse_smaps() {
+        let smaps = "12c00000-12c02000 rw-p 00000000 00:00 0 [anon:dalvik-main space]\n\
+                     Size:                  8 kB\n\
+                     Rss:                   8 kB\n\
+                     Pss:                   6 kB\n\
+                     Private_Dirty:         4 kB\n\
+                     Swap:                  0 kB\n\
+                     VmFlags: rd wr mr mw me ac\n\
+                     7f000000-7f001000 r--p 00000000 fd:05 12 /system/lib64/libc.so\n\
+                     THPeligible:    0\n";
+        let regions = parse_smaps(smaps);
+
+        assert_eq!(regions.len(), 2);
+        let usage = regions[0].usage.unwrap();
+        assert_eq!((usage.rss, usage.pss, usage.private_dirty), (8192, 6144, 4096));
+        assert_eq!(regions[1].usage, Some(MemoryUsage::default()));
+        assert_eq!(regions[1].path.as_deref(), Some("/system/lib64/libc.so"));
+        assert_eq!(MemoryUsage::total(regions.iter().filter_map(|r| r.usage.as_ref())).rss, 8192);
+    }
+}
@@ -0,0 +1,677 @@
+//! Minidumps of live processes
+//!
+//! A lighter alternative to a core file in the MDMP format read by
+//! Breakpad's and Crashpad's tools: the thread list with each thread's
+//! registers and the live part of its stack, the loaded modules with their
+//! GNU build IDs (as `BpEL` CodeView records, so symbols can be matched),
+//! system and process info, the maps file, and optionally whole regions.
+
+use crate::coredump::ThreadRegisters;
+use crate::elf::{self, PT_LOAD, PT_NOTE};
+use crate::error::ExtractError;
+use crate::filter::RegionFilter;
+use crate::maps::MemoryRegion;
+use crate::reader::{self, MemoryReader};
+use crate::scan::{self, RegionVisitor, ScanControl, ScanSink, StopReason};
+use serde::Deserialize;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::mem::size_of;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SIGNATURE: u32 = 0x504d_444d;
+const VERSION: u32 = 0xa793;
+const THREAD_LIST_STREAM: u32 = 3;
+const MODULE_LIST_STREAM: u32 = 4;
+const MEMORY_LIST_STREAM: u32 = 5;
+const SYSTEM_INFO_STREAM: u32 = 7;
+const MISC_INFO_STREAM: u32 = 15;
+const LINUX_MAPS_STREAM: u32 = 0x4767_0009;
+const PLATFORM_ANDROID: u32 = 0x8203;
+const MISC1_PROCESS_ID: u32 = 1;
+const CV_SIGNATURE_ELF: &[u8] = b"BpEL";
+
+const HEADER_SIZE: u64 = 32;
+const DIRECTORY_ENTRY_SIZE: u64 = 12;
+const STREAMS: u32 = 6;
+
+/// How much of a stack above the stack pointer is saved, at most
+const MAX_STACK: u64 = 1024 * 1024;
+/// Below the stack pointer, the leaf function's red zone may still be live
+const RED_ZONE: u64 = 128;
+
+/// Options for a minidump, deserialized from the JSON object passed over JNI
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct MinidumpOptions {
+    /// Regions to save in full along with the stacks; none by default
+    pub regions: Option<RegionFilter>,
+}
+
+impl MinidumpOptions {
+    /// Parse options from JSON; an empty string means all defaults
+    pub fn from_json(json: &str) -> Result<Self, ExtractError> {
+        if json.trim().is_empty() {
+            return Ok(MinidumpOptions::default());
+        }
+        serde_json::from_str(json)
+            .map_err(|e| ExtractError::InvalidArgument(format!("Invalid minidump options: {}", e)))
+    }
+}
+
+/// How a thread's registers are laid out in the minidump `CONTEXT` of this
+/// ABI: where each `elf_gregset_t` word goes, and at what width
+struct ContextLayout {
+    cpu_architecture: u16,
+    size: usize,
+    /// Offset of the context flags, and their value
+    flags: (usize, u32),
+    /// `(offset in context, index in gregset, width)`
+    fields: &'static [(usize, usize, usize)],
+    stack_pointer: usize,
+}
+
+/// `CONTEXT_AMD64` with control, integer and segment registers
+#[cfg(target_arch = "x86_64")]
+const LAYOUT: Option<ContextLayout> = Some(ContextLayout {
+    cpu_architecture: 9,
+    size: 1232,
+    flags: (48, 0x0010_0007),
+    fields: &[
+        (56, 17, 2),  // SegCs
+        (58, 23, 2),  // SegDs
+        (60, 24, 2),  // SegEs
+        (62, 25, 2),  // SegFs
+        (64, 26, 2),  // SegGs
+        (66, 20, 2),  // SegSs
+        (68, 18, 4),  // EFlags
+        (120, 10, 8), // Rax
+        (128, 11, 8), // Rcx
+        (136, 12, 8), // Rdx
+        (144, 5, 8),  // Rbx
+        (152, 19, 8), // Rsp
+        (160, 4, 8),  // Rbp
+        (168, 13, 8), // Rsi
+        (176, 14, 8), // Rdi
+        (184, 9, 8),  // R8
+        (192, 8, 8),  // R9
+        (200, 7, 8),  // R10
+        (208, 6, 8),  // R11
+        (216, 3, 8),  // R12
+        (224, 2, 8),  // R13
+        (232, 1, 8),  // R14
+        (240, 0, 8),  // R15
+        (248, 16, 8), // Rip
+    ],
+    stack_pointer: 19,
+});
+
+/// `CONTEXT_ARM64` with control and integer registers: x0-x30, sp, pc
+/// follow each other in both layouts
+#[cfg(target_arch = "aarch64")]
+const LAYOUT: Option<ContextLayout> = Some(ContextLayout {
+    cpu_architecture: 12,
+    size: 912,
+    flags: (0, 0x0040_0003),
+    fields: &{
+        let mut fields = [(0, 0, 8); 34];
+        let mut i = 0;
+        while i < 33 {
+            fields[i] = (8 + 8 * i, i, 8);
+            i += 1;
+        }
+        fields[33] = (4, 33, 4); // Cpsr from pstate
+        fields
+    },
+    stack_pointer: 31,
+});
+
+/// `CONTEXT_X86` with control, integer and segment registers
+#[cfg(target_arch = "x86")]
+const LAYOUT: Option<ContextLayout> = Some(ContextLayout {
+    cpu_architecture: 0,
+    size: 716,
+    flags: (0, 0x0001_0007),
+    fields: &[
+        (140, 10, 4), // SegGs
+        (144, 9, 4),  // SegFs
+        (148, 8, 4),  // SegEs
+        (152, 7, 4),  // SegDs
+        (156, 4, 4),  // Edi
+        (160, 3, 4),  // Esi
+        (164, 0, 4),  // Ebx
+        (168, 2, 4),  // Edx
+        (172, 1, 4),  // Ecx
+        (176, 6, 4),  // Eax
+        (180, 5, 4),  // Ebp
+        (184, 12, 4), // Eip
+        (188, 13, 4), // SegCs
+        (192, 14, 4), // EFlags
+        (196, 15, 4), // Esp
+        (200, 16, 4), // SegSs
+    ],
+    stack_pointer: 15,
+});
+
+/// Breakpad's `MDRawContextARM` with integer registers: r0-r15, cpsr
+#[cfg(target_arch = "arm")]
+const LAYOUT: Option<ContextLayout> = Some(ContextLayout {
+    cpu_architecture: 5,
+    size: 368,
+    flags: (0, 0x4000_0002),
+    fields: &{
+        let mut fields = [(0, 0, 4); 17];
+        let mut i = 0;
+        while i < 17 {
+            fields[i] = (4 + 4 * i, i, 4);
+            i += 1;
+        }
+        fields
+    },
+    stack_pointer: 13,
+});
+
+#[cfg(not(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "x86",
+    target_arch = "arm"
+)))]
+const LAYOUT: Option<ContextLayout> = None;
+
+/// What a minidump is taken of
+pub struct Snapshot<'a> {
+    pub pid: i32,
+    /// Every region of the process, to find modules and stacks in
+    pub regions: &'a [MemoryRegion],
+    /// Registers of each thread to list, main thread first
+    pub threads: &'a [ThreadRegisters],
+}
+
+/// Where something was written: `MINIDUMP_LOCATION_DESCRIPTOR`
+#[derive(Debug, Clone, Copy, Default)]
+struct Location {
+    size: u32,
+    rva: u32,
+}
+
+/// Saved memory: `MINIDUMP_MEMORY_DESCRIPTOR`
+#[derive(Debug, Clone, Copy)]
+struct MemoryRange {
+    start: u64,
+    data: Location,
+}
+
+impl MemoryRange {
+    fn end(&self) -> u64 {
+        self.start + u64::from(self.data.size)
+    }
+}
+
+/// Appends to the file, tracking offsets; minidump offsets are 32-bit
+struct Writer {
+    out: BufWriter<File>,
+    position: u64,
+}
+
+impl Writer {
+    fn append(&mut self, bytes: &[u8]) -> io::Result<Location> {
+        let rva = self.position;
+        let end = rva + bytes.len() as u64;
+        if end > u64::from(u32::MAX) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Minidump would exceed 4 GiB; save fewer regions",
+            ));
+        }
+        self.out.write_all(bytes)?;
+        self.position = end;
+        Ok(Location {
+            size: bytes.len() as u32,
+            rva: rva as u32,
+        })
+    }
+
+    /// Pad to a multiple of 8, as streams are expected to be aligned
+    fn align(&mut self) -> io::Result<()> {
+        let padding = self.position.next_multiple_of(8) - self.position;
+        self.append(&vec![0; padding as usize]).map(drop)
+    }
+
+    /// `MINIDUMP_STRING`: byte length, then NUL-terminated UTF-16LE
+    fn append_string(&mut self, text: &str) -> io::Result<Location> {
+        let units: Vec<u16> = text.encode_utf16().collect();
+        let mut bytes = Vec::with_capacity(4 + 2 * units.len() + 2);
+        put_u32(&mut bytes, (2 * units.len()) as u32);
+        for unit in units.iter().chain(&[0]) {
+            bytes.extend_from_slice(&unit.to_le_bytes());
+        }
+        self.append(&bytes)
+    }
+}
+
+/// Write a minidump of `snapshot` to `path`, saving each of `memory` in
+/// full along with the thread stacks.
+///
+/// Regions in `memory` are recorded in `sink` like any other scan.
+pub fn write_minidump(
+    reader: &mut dyn MemoryReader,
+    snapshot: &Snapshot,
+    memory: &[MemoryRegion],
+    path: &str,
+    sink: &mut dyn ScanSink<()>,
+    control: &mut ScanControl,
+) -> io::Result<Option<StopReason>> {
+    let Some(layout) = LAYOUT else {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Minidumps aren't supported on this architecture",
+        ));
+    };
+
+    let mut writer = Writer {
+        out: BufWriter::new(File::create(path)?),
+        position: 0,
+    };
+    // The header and directory are filled in last
+    let directory_size = HEADER_SIZE + DIRECTORY_ENTRY_SIZE * u64::from(STREAMS);
+    writer.append(&vec![0; directory_size as usize])?;
+
+    let mut visitor = RangeWriter {
+        writer: &mut writer,
+        open: None,
+        ranges: Vec::new(),
+        failed: None,
+    };
+    let stopped = scan::walk_regions(reader, memory, &mut visitor, sink, control);
+    if let Some(e) = visitor.failed {
+        return Err(e);
+    }
+    let mut ranges = visitor.ranges;
+
+    let mut threads = Vec::new();
+    for thread in snapshot.threads {
+        let stack =
+            save_stack(reader, &mut writer, &mut ranges, snapshot.regions, &layout, thread)?;
+        writer.align()?;
+        let context = writer.append(&context(&layout, &thread.regs))?;
+        threads.push((thread.tid, stack, context));
+    }
+    let modules = save_modules(reader, &mut writer, snapshot.regions)?;
+    let csd_version = writer.append_string(&kernel_version())?;
+
+    let mut streams = Vec::new();
+    writer.align()?;
+    let mut list = Vec::new();
+    put_u32(&mut list, threads.len() as u32);
+    for (tid, stack, context) in &threads {
+        put_u32(&mut list, *tid as u32);
+        list.extend_from_slice(&[0; 20]); // suspend count, priority, TEB
+        put_range(&mut list, stack);
+        put_location(&mut list, *context);
+    }
+    streams.push((THREAD_LIST_STREAM, writer.append(&list)?));
+
+    writer.align()?;
+    let mut list = Vec::new();
+    put_u32(&mut list, modules.len() as u32);
+    for module in &modules {
+        put_u64(&mut list, module.base);
+        put_u32(&mut list, module.size);
+        list.extend_from_slice(&[0; 8]); // checksum, timestamp
+        put_u32(&mut list, module.name.rva);
+        list.extend_from_slice(&[0; 52]); // VS_FIXEDFILEINFO
+        put_location(&mut list, module.code_view);
+        list.extend_from_slice(&[0; 24]); // misc record, reserved
+    }
+    streams.push((MODULE_LIST_STREAM, writer.append(&list)?));
+
+    writer.align()?;
+    let mut list = Vec::new();
+    put_u32(&mut list, ranges.len() as u32);
+    for range in &ranges {
+        put_range(&mut list, range);
+    }
+    streams.push((MEMORY_LIST_STREAM, writer.append(&list)?));
+
+    let mut info = Vec::new();
+    info.extend_from_slice(&layout.cpu_architecture.to_le_bytes());
+    info.extend_from_slice(&[0; 4]); // processor level and revision
+    info.push(cpu_count());
+    info.push(0); // product type
+    for part in kernel_release_parts() {
+        put_u32(&mut info, part);
+    }
+    put_u32(&mut info, PLATFORM_ANDROID);
+    put_u32(&mut info, csd_version.rva);
+    info.extend_from_slice(&[0; 28]); // suite mask, reserved, CPU info
+    streams.push((SYSTEM_INFO_STREAM, writer.append(&info)?));
+
+    let mut misc = Vec::new();
+    for value in [24, MISC1_PROCESS_ID, snapshot.pid as u32, 0, 0, 0] {
+        put_u32(&mut misc, value);
+    }
+    streams.push((MISC_INFO_STREAM, writer.append(&misc)?));
+
+    let maps = fs::read(format!("/proc/{}/maps", snapshot.pid)).unwrap_or_default();
+    streams.push((LINUX_MAPS_STREAM, writer.append(&maps)?));
+
+    let mut header = Vec::with_capacity(directory_size as usize);
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    for value in [SIGNATURE, VERSION, STREAMS, HEADER_SIZE as u32, 0, timestamp as u32] {
+        put_u32(&mut header, value);
+    }
+    put_u64(&mut header, 0); // flags
+    for (kind, location) in streams {
+        put_u32(&mut header, kind);
+        put_location(&mut header, location);
+    }
+    let mut out = writer.out;
+    out.seek(SeekFrom::Start(0))?;
+    out.write_all(&header)?;
+    out.flush()?;
+    Ok(stopped)
+}
+
+/// Copies each region of a walk to the file, recording its range
+struct RangeWriter<'a> {
+    writer: &'a mut Writer,
+    /// Start of the range being written, and where its data begins
+    open: Option<(u64, u32)>,
+    ranges: Vec<MemoryRange>,
+    failed: Option<io::Error>,
+}
+
+impl RegionVisitor for RangeWriter<'_> {
+    type Output = ();
+
+    fn visit_chunk(&mut self, _region: &MemoryRegion, address: u64, chunk: &[u8]) {
+        if self.failed.is_some() {
+            return;
+        }
+        // A chunk past skipped pages starts a range of its own
+        let contiguous = self.open.is_some_and(|(start, rva)| {
+            start + (self.writer.position - u64::from(rva)) == address
+        });
+        if !contiguous {
+            self.close();
+        }
+        match self.writer.append(chunk) {
+            Ok(location) => {
+                self.open.get_or_insert((address, location.rva));
+            }
+            Err(e) => self.failed = Some(e),
+        }
+    }
+
+    fn end_region(
+        &mut self,
+        _region: &MemoryRegion,
+        _reader: &mut dyn MemoryReader,
+        _sink: &mut dyn ScanSink<()>,
+    ) {
+        self.close();
+    }
+}
+
+impl RangeWriter<'_> {
+    /// Record the range being written, if any
+    fn close(&mut self) {
+        if let Some((start, rva)) = self.open.take() {
+            let size = (self.writer.position - u64::from(rva)) as u32;
+            self.ranges.push(MemoryRange {
+                start,
+                data: Location { size, rva },
+            });
+        }
+    }
+}
+
+/// Save the live part of a thread's stack, from just below its stack
+/// pointer to the top of the stack, unless a saved region already has it
+fn save_stack(
+    reader: &mut dyn MemoryReader,
+    writer: &mut Writer,
+    ranges: &mut Vec<MemoryRange>,
+    regions: &[MemoryRegion],
+    layout: &ContextLayout,
+    thread: &ThreadRegisters,
+) -> io::Result<MemoryRange> {
+    let empty = MemoryRange {
+        start: 0,
+        data: Location::default(),
+    };
+    let Some(sp) = register(&thread.regs, layout.stack_pointer) else {
+        return Ok(empty);
+    };
+    let Some(region) = regions.iter().find(|region| region.contains(sp)) else {
+        return Ok(empty);
+    };
+    let start = sp.saturating_sub(RED_ZONE).max(region.start) & !15;
+    let end = region.end.min(sp.saturating_add(MAX_STACK));
+
+    if let Some(saved) = ranges.iter().find(|range| range.start <= start && end <= range.end()) {
+        return Ok(MemoryRange {
+            start,
+            data: Location {
+                size: (end - start) as u32,
+                rva: saved.data.rva + (start - saved.start) as u32,
+            },
+        });
+    }
+
+    let mut stack = vec![0u8; (end - start) as usize];
+    let read = reader::read_into(reader, start, &mut stack).unwrap_or(0);
+    if read == 0 {
+        return Ok(empty);
+    }
+    let range = MemoryRange {
+        start,
+        data: writer.append(&stack[..read])?,
+    };
+    ranges.push(range);
+    Ok(range)
+}
+
+struct Module {
+    base: u64,
+    size: u32,
+    name: Location,
+    code_view: Location,
+}
+
+/// Every ELF image mapped into the process: runs of mappings of the same
+/// file whose first page holds an ELF header
+fn save_modules(
+    reader: &mut dyn MemoryReader,
+    writer: &mut Writer,
+    regions: &[MemoryRegion],
+) -> io::Result<Vec<Module>> {
+    let mut modules = Vec::new();
+    let mut i = 0;
+    while i < regions.len() {
+        let first = &regions[i];
+        let mut last = first;
+        i += 1;
+        let Some(path) = first.path.as_deref().filter(|path| path.starts_with('/')) else {
+            continue;
+        };
+        while let Some(next) = regions.get(i).filter(|next| next.path == first.path) {
+            last = next;
+            i += 1;
+        }
+
+        let mut page = vec![0u8; reader::page_size()];
+        let read = reader::read_into(reader, first.start, &mut page).unwrap_or(0);
+        let Some(header) = elf::parse_header(&page[..read]) else {
+            continue;
+        };
+        let build_id = read_build_id(reader, first.start, &header, &page[..read]);
+
+        let name = writer.append_string(path)?;
+        let code_view = match build_id {
+            Some(build_id) => writer.append(&[CV_SIGNATURE_ELF, &build_id].concat())?,
+            None => Location::default(),
+        };
+        modules.push(Module {
+            base: first.start,
+            size: u32::try_from(last.end - first.start).unwrap_or(u32::MAX),
+            name,
+            code_view,
+        });
+    }
+    Ok(modules)
+}
+
+/// Read a loaded image's build ID from its note segments in memory.
+/// `first_page` is the start of the image, holding its program headers.
+fn read_build_id(
+    reader: &mut dyn MemoryReader,
+    base: u64,
+    header: &elf::ElfHeader,
+    first_page: &[u8],
+) -> Option<Vec<u8>> {
+    let table = first_page.get(header.phoff as usize..)?;
+    let segments = elf::parse_program_headers(header, table)?;
+    // Shared objects are linked at 0, but executables may not be
+    let linked = segments.iter().find(|segment| segment.kind == PT_LOAD)?.vaddr;
+    let bias = base.wrapping_sub(linked & !(reader::page_size() as u64 - 1));
+
+    for segment in segments.iter().filter(|segment| segment.kind == PT_NOTE) {
+        let mut notes = vec![0u8; segment.filesz.min(4096) as usize];
+        let read = reader::read_into(reader, bias.wrapping_add(segment.vaddr), &mut notes).ok()?;
+        if let Some(build_id) = elf::find_build_id(&notes[..read]) {
+            return Some(build_id.to_vec());
+        }
+    }
+    None
+}
+
+/// Word `index` of a register set
+fn register(regs: &[u8], index: usize) -> Option<u64> {
+    const WORD: usize = size_of::<usize>();
+    let bytes = regs.get(index * WORD..(index + 1) * WORD)?;
+    let mut word = [0u8; 8];
+    word[..WORD].copy_from_slice(bytes);
+    Some(u64::from_le_bytes(word))
+}
+
+/// A thread's `CONTEXT` from its `elf_gregset_t`
+fn context(layout: &ContextLayout, regs: &[u8]) -> Vec<u8> {
+    let mut context = vec![0u8; layout.size];
+    let (flags_at, flags) = layout.flags;
+    context[flags_at..flags_at + 4].copy_from_slice(&flags.to_le_bytes());
+    for &(offset, index, width) in layout.fields {
+        let value = register(regs, index).unwrap_or(0);
+        context[offset..offset + width].copy_from_slice(&value.to_le_bytes()[..width]);
+    }
+    context
+}
+
+fn cpu_count() -> u8 {
+    // SAFETY: sysconf only reads system configuration
+    let count = unsafe { libc::sysconf(libc::_SC_NPROCESSORS_CONF) };
+    count.clamp(0, 255) as u8
+}
+
+fn uname() -> Option<libc::utsname> {
+    // SAFETY: utsname is plain data, and uname fills it in
+    unsafe {
+        let mut name = std::mem::zeroed::<libc::utsname>();
+        (libc::uname(&mut name) == 0).then_some(name)
+    }
+}
+
+fn field(field: &[libc::c_char]) -> String {
+    let bytes: Vec<u8> = field.iter().take_while(|&&c| c != 0).map(|&c| c as u8).collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// `<sysname> <release> <version> <machine>`, as Breakpad records it
+fn kernel_version() -> String {
+    uname().map_or_else(String::new, |name| {
+        let fields = [&name.sysname[..], &name.release, &name.version, &name.machine];
+        fields.map(field).join(" ")
+    })
+}
+
+/// Major, minor and patch numbers of the kernel release
+fn kernel_release_parts() -> [u32; 3] {
+    let release = uname().map(|name| field(&name.release)).unwrap_or_default();
+    let mut parts = [0; 3];
+    let numbers = release.split(['.', '-']).map(|part| part.parse().unwrap_or(0));
+    for (part, number) in parts.iter_mut().zip(numbers) {
+        *part = number;
+    }
+    parts
+}
+
+fn put_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u64(out: &mut Vec<u8>, value: u64) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_location(out: &mut Vec<u8>, location: Location) {
+    put_u32(out, location.size);
+    put_u32(out, location.rva);
+}
+
+fn put_range(out: &mut Vec<u8>, range: &MemoryRange) {
+    put_u64(out, range.start);
+    put_location(out, range.data);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps;
+    use crate::reader::ProcfsReader;
+    use crate::scan::Scan;
+
+    #[test]
+    fn test_minidump_lists_streams_and_modules() {
+        let pid = std::process::id() as i32;
+        let regions = maps::read_maps(pid).unwrap();
+        let layout = LAYOUT.unwrap();
+        let local = 0u64;
+        let mut regs = vec![0u8; (layout.stack_pointer + 1) * size_of::<usize>()];
+        let sp = (&local as *const u64 as usize).to_le_bytes();
+        regs[layout.stack_pointer * sp.len()..].copy_from_slice(&sp);
+        let threads = [ThreadRegisters { tid: pid, regs }];
+        let snapshot = Snapshot {
+            pid,
+            regions: &regions,
+            threads: &threads,
+        };
+
+        let path = std::env::temp_dir().join(format!("minidump-test-{}", pid));
+        let path = path.to_str().unwrap();
+        let mut reader = ProcfsReader::open(pid).unwrap();
+        let mut scan = Scan::default();
+        let mut control = ScanControl::new();
+        write_minidump(&mut reader, &snapshot, &[], path, &mut scan, &mut control).unwrap();
+        let dump = fs::read(path).unwrap();
+        fs::remove_file(path).unwrap();
+
+        let u32_at = |at: usize| u32::from_le_bytes(dump[at..at + 4].try_into().unwrap());
+        assert_eq!(u32_at(0), SIGNATURE);
+        assert_eq!(u32_at(8), STREAMS);
+        let stream = |kind: u32| {
+            (0..STREAMS as usize)
+                .map(|i| 32 + 12 * i)
+                .find(|&entry| u32_at(entry) == kind)
+                .map(|entry| u32_at(entry + 8) as usize)
+                .unwrap()
+        };
+        // One thread, whose stack holds `local`
+        let threads = stream(THREAD_LIST_STREAM);
+        assert_eq!(u32_at(threads), 1);
+        let stack_size = u32_at(threads + 4 + 32) as usize;
+        assert!(stack_size >= size_of::<u64>());
+        // The test binary itself is among the modules
+        assert!(u32_at(stream(MODULE_LIST_STREAM)) >= 1);
+    }
+}
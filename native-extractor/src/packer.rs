@@ -0,0 +1,280 @@
+//! Identification of Android packers and protectors
+//!
+//! Commercial packers such as Jiagu, Bangcle or Legu each ship a native
+//! loader with a recognizable file name and a stub application class that
+//! unpacks the real app. A process using one maps the loader, which shows in
+//! its maps, and holds the stub's class name in memory, in the dex file that
+//! declares it and in the heap. Signatures of both kinds come from a
+//! database built into the library, in the JSON format of [`parse_database`],
+//! which callers can extend or override with their own.
+
+use crate::error::ExtractError;
+use crate::maps::MemoryRegion;
+use crate::reader::MemoryReader;
+use crate::scan::{self, Match, RegionResult, ScanControl, ScanSink, StopReason};
+use crate::search::{self, PatternScanner};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// The signature database built into the library
+const BUILTIN_DATABASE: &str = include_str!("packers.json");
+
+/// How one packer is recognized
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PackerSignature {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vendor: Option<String>,
+    /// Substrings of the paths of libraries it loads, case-insensitive
+    #[serde(default)]
+    pub libraries: Vec<String>,
+    /// Strings held in its memory, such as the class name of its stub
+    /// application
+    #[serde(default)]
+    pub strings: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct Database {
+    packers: Vec<PackerSignature>,
+}
+
+/// Where an artifact was found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PackerSource {
+    /// A mapping, by its path
+    Map,
+    /// Memory holding one of the signature's strings
+    String,
+}
+
+/// One trace of a packer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PackerArtifact {
+    /// Name of the signature that matched
+    pub packer: String,
+    pub source: PackerSource,
+    /// The path or string that matched
+    pub detail: String,
+    /// Start of the first mapping of the path, or where the string was found
+    pub address: u64,
+}
+
+/// The packers found and the evidence for each
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PackerDetection {
+    /// Every packer with at least one artifact, in database order
+    pub detected: Vec<String>,
+    pub artifacts: Vec<PackerArtifact>,
+}
+
+/// Parse a signature database: an object whose `packers` array holds
+/// [`PackerSignature`]s
+pub fn parse_database(json: &str) -> Result<Vec<PackerSignature>, ExtractError> {
+    serde_json::from_str::<Database>(json)
+        .map(|database| database.packers)
+        .map_err(|e| ExtractError::InvalidArgument(format!("Invalid packer signatures: {}", e)))
+}
+
+/// The signatures built into the library
+pub fn builtin_signatures() -> Vec<PackerSignature> {
+    parse_database(BUILTIN_DATABASE).expect("built-in packer signatures are valid")
+}
+
+/// Add `updates` to `signatures`, replacing those of the same name
+pub fn merge(signatures: &mut Vec<PackerSignature>, updates: Vec<PackerSignature>) {
+    for update in updates {
+        match signatures.iter_mut().find(|signature| signature.name == update.name) {
+            Some(signature) => *signature = update,
+            None => signatures.push(update),
+        }
+    }
+}
+
+/// Artifacts of `signatures` among the paths of `regions`, one per path and
+/// packer
+pub fn match_libraries(
+    signatures: &[PackerSignature],
+    regions: &[MemoryRegion],
+) -> Vec<PackerArtifact> {
+    let mut artifacts: Vec<PackerArtifact> = Vec::new();
+    for region in regions {
+        let Some(path) = &region.path else {
+            continue;
+        };
+        let lower = path.to_ascii_lowercase();
+        for signature in signatures {
+            let matches = signature
+                .libraries
+                .iter()
+                .any(|library| lower.contains(&library.to_ascii_lowercase()));
+            let seen = artifacts
+                .iter()
+                .any(|artifact| artifact.packer == signature.name && artifact.detail == *path);
+            if matches && !seen {
+                artifacts.push(PackerArtifact {
+                    packer: signature.name.clone(),
+                    source: PackerSource::Map,
+                    detail: path.clone(),
+                    address: region.start,
+                });
+            }
+        }
+    }
+    artifacts
+}
+
+/// Look for `signatures` in a process: library artifacts among the paths of
+/// `regions`, then string artifacts in their memory, the first occurrence of
+/// each string only.
+///
+/// A string that names a class with dots is also searched with slashes, the
+/// form dex files and ART hold class names in.
+pub fn identify(
+    reader: &mut dyn MemoryReader,
+    regions: &[MemoryRegion],
+    signatures: &[PackerSignature],
+    sink: &mut dyn ScanSink<PackerArtifact>,
+    control: &mut ScanControl,
+) -> Option<StopReason> {
+    for artifact in match_libraries(signatures, regions) {
+        sink.result(artifact);
+    }
+
+    let mut patterns = Vec::new();
+    for signature in signatures {
+        for string in signature.strings.iter().filter(|string| !string.is_empty()) {
+            patterns.push((signature.name.as_str(), string.clone()));
+            if string.contains('.') {
+                patterns.push((signature.name.as_str(), string.replace('.', "/")));
+            }
+        }
+    }
+    if patterns.is_empty() {
+        return None;
+    }
+    let keywords: Vec<&str> = patterns.iter().map(|(_, pattern)| pattern.as_str()).collect();
+    let matcher =
+        search::build_keyword_matcher(&keywords, false).expect("literal patterns always build");
+    let longest = keywords.iter().map(|keyword| keyword.len()).max().unwrap_or(0);
+    let mut scanner = PatternScanner::with_overlap(matcher, longest.max(search::DEFAULT_OVERLAP));
+
+    let mut strings = StringSink {
+        patterns: &patterns,
+        seen: HashSet::new(),
+        sink,
+    };
+    scan::search_regions(reader, regions, &mut scanner, 0, &mut strings, control)
+}
+
+/// Turns the first match of each pattern into a [`PackerArtifact`]
+struct StringSink<'a> {
+    /// Packer and string of each pattern searched
+    patterns: &'a [(&'a str, String)],
+    seen: HashSet<usize>,
+    sink: &'a mut dyn ScanSink<PackerArtifact>,
+}
+
+impl ScanSink<Match> for StringSink<'_> {
+    fn region(&mut self, result: RegionResult) {
+        self.sink.region(result);
+    }
+
+    fn result(&mut self, found: Match) {
+        if !self.seen.insert(found.pattern) {
+            return;
+        }
+        let (packer, string) = &self.patterns[found.pattern];
+        self.sink.result(PackerArtifact {
+            packer: packer.to_string(),
+            source: PackerSource::String,
+            detail: string.clone(),
+            address: found.location.address,
+        });
+    }
+}
+
+/// Sum up `artifacts` by packer, ordered as in `signatures`
+pub fn summarize(
+    signatures: &[PackerSignature],
+    artifacts: Vec<PackerArtifact>,
+) -> PackerDetection {
+    let detected = signatures
+        .iter()
+        .filter(|signature| artifacts.iter().any(|artifact| artifact.packer == signature.name))
+        .map(|signature| signature.name.clone())
+        .collect();
+    PackerDetection {
+        detected,
+        artifacts,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps::parse_maps_line;
+    use crate::reader::ProcfsReader;
+    use crate::scan::Scan;
+
+    #[test]
+    fn test_merges_signatures_and_matches_libraries() {
+        let mut signatures = builtin_signatures();
+        let count = signatures.len();
+        let updates = parse_database(
+            r#"{"packers": [
+                {"name": "Jiagu", "libraries": ["libjiagu_v2"]},
+                {"name": "Custom", "libraries": ["libcustomshell"]}
+            ]}"#,
+        )
+        .unwrap();
+        merge(&mut signatures, updates);
+        assert_eq!(signatures.len(), count + 1);
+
+        let lines = [
+            "7f0000000000-7f0000001000 r-xp 00000000 00:00 0 /data/app/x/lib/arm64/libjiagu_v2.so",
+            "7f0000001000-7f0000002000 r--p 00001000 00:00 0 /data/app/x/lib/arm64/libjiagu_v2.so",
+            "7f0000002000-7f0000003000 r-xp 00000000 00:00 0 /data/app/x/lib/arm64/libSecExe.so",
+            "7f0000003000-7f0000004000 r-xp 00000000 00:00 0 /system/lib64/libc.so",
+        ];
+        let regions: Vec<_> = lines.iter().map(|line| parse_maps_line(line).unwrap()).collect();
+        let found: Vec<(String, u64)> = match_libraries(&signatures, &regions)
+            .into_iter()
+            .map(|artifact| (artifact.packer, artifact.address))
+            .collect();
+        assert_eq!(
+            found,
+            [("Jiagu".to_string(), 0x7f00_0000_0000), ("Bangcle".to_string(), 0x7f00_0000_2000)]
+        );
+        assert!(parse_database(r#"{"packers": [{"vendor": "x"}]}"#).is_err());
+    }
+
+    #[test]
+    fn test_finds_strings_in_memory() {
+        let signatures = parse_database(
+            r#"{"packers": [{"name": "Test", "strings": ["org.example.TestStubApp"]}]}"#,
+        )
+        .unwrap();
+        let mut data = vec![0u8; 0x3000];
+        data[0x1008..0x1008 + 25].copy_from_slice(b"Lorg/example/TestStubApp;");
+        data[0x2000..0x2000 + 23].copy_from_slice(b"org/example/TestStubApp");
+        let start = data.as_ptr() as u64;
+        let line = format!("{:x}-{:x} rw-p 00000000 00:00 0", start, start + data.len() as u64);
+        let region = parse_maps_line(&line).unwrap();
+
+        let pid = std::process::id() as i32;
+        let mut reader = ProcfsReader::open(pid).unwrap();
+        let mut scan = Scan::default();
+        identify(&mut reader, &[region], &signatures, &mut scan, &mut ScanControl::new());
+
+        let detection = summarize(&signatures, scan.results);
+        assert_eq!(detection.detected, ["Test"]);
+        assert_eq!(detection.artifacts.len(), 1);
+        assert_eq!(detection.artifacts[0].detail, "org/example/TestStubApp");
+        assert_eq!(detection.artifacts[0].address, start + 0x1009);
+    }
+}
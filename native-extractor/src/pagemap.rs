@@ -0,0 +1,123 @@
+//! Page residency from /proc/[pid]/pagemap
+//!
+//! pagemap holds one 64-bit entry per virtual page of the process, saying
+//! whether the page is present in RAM, swapped out or neither, the latter
+//! for pages never touched or dropped from the page cache. Reading a page
+//! that isn't present faults it in: swapped pages are decompressed from
+//! zram, untouched anonymous pages read as zeros. Big apps, games above all,
+//! reserve far more than they touch, so walking only the present pages
+//! skips most of their address space.
+
+use crate::reader;
+use std::fs::File;
+use std::io;
+use std::ops::Range;
+use std::os::unix::fs::FileExt;
+
+/// Page is present in RAM
+pub const PRESENT: u64 = 1 << 63;
+/// Page is swapped out
+pub const SWAPPED: u64 = 1 << 62;
+/// Page is file-backed or shared anonymous memory
+pub const FILE_OR_SHARED: u64 = 1 << 61;
+/// Page is mapped only by this process
+pub const EXCLUSIVE: u64 = 1 << 56;
+/// Page was written since the soft-dirty bits were last cleared
+pub const SOFT_DIRTY: u64 = 1 << 55;
+
+const ENTRY_SIZE: usize = 8;
+
+/// Entries read from pagemap at a time, covering 256 MiB of 4 KiB pages
+const BATCH_ENTRIES: usize = 64 * 1024;
+
+/// An open /proc/[pid]/pagemap
+pub struct PageMap {
+    file: File,
+    page_size: u64,
+}
+
+impl PageMap {
+    pub fn open(pid: i32) -> io::Result<Self> {
+        let file = File::open(format!("/proc/{}/pagemap", pid))?;
+        Ok(PageMap {
+            file,
+            page_size: reader::page_size() as u64,
+        })
+    }
+
+    /// The entries of the pages overlapping `start..end`, calling
+    /// `on_entry(address, entry)` for each page in order
+    pub fn for_each_page(
+        &self,
+        start: u64,
+        end: u64,
+        mut on_entry: impl FnMut(u64, u64),
+    ) -> io::Result<()> {
+        let first = start / self.page_size;
+        let last = end.div_ceil(self.page_size);
+        let mut buffer = vec![0u8; BATCH_ENTRIES.min((last - first) as usize) * ENTRY_SIZE];
+        let mut page = first;
+        while page < last {
+            let count = BATCH_ENTRIES.min((last - page) as usize);
+            let bytes = &mut buffer[..count * ENTRY_SIZE];
+            self.file.read_exact_at(bytes, page * ENTRY_SIZE as u64)?;
+            for entry in bytes.chunks_exact(ENTRY_SIZE) {
+                on_entry(page * self.page_size, u64::from_ne_bytes(entry.try_into().unwrap()));
+                page += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// The runs of present pages within `start..end`, clipped to it
+    pub fn present_ranges(&self, start: u64, end: u64) -> io::Result<Vec<Range<u64>>> {
+        let mut ranges: Vec<Range<u64>> = Vec::new();
+        self.for_each_page(start, end, |address, entry| {
+            if entry & PRESENT == 0 {
+                return;
+            }
+            let page = address.max(start)..(address + self.page_size).min(end);
+            match ranges.last_mut() {
+                Some(last) if last.end == page.start => last.end = page.end,
+                _ => ranges.push(page),
+            }
+        })?;
+        Ok(ranges)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_present_ranges_skip_untouched_pages() {
+        let pagemap = PageMap::open(std::process::id() as i32).unwrap();
+        let page = pagemap.page_size as usize;
+        // SAFETY: a fresh private mapping, unmapped below
+        let mapped = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                4 * page,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        assert_ne!(mapped, libc::MAP_FAILED);
+        let start = mapped as u64;
+        // SAFETY: both pages lie within the mapping
+        unsafe {
+            *mapped.cast::<u8>() = 1;
+            *mapped.cast::<u8>().add(2 * page) = 1;
+        }
+
+        let ranges = pagemap.present_ranges(start, start + 4 * page as u64 - 16).unwrap();
+        // SAFETY: the mapping made above, no longer used
+        unsafe { libc::munmap(mapped, 4 * page) };
+
+        let page = page as u64;
+        assert_eq!(ranges, [start..start + page, start + 2 * page..start + 3 * page]);
+    }
+}
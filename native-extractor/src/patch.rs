@@ -0,0 +1,87 @@
+//! Writes to a session's process that can be undone
+//!
+//! Before a session writes to its process, the bytes about to be
+//! overwritten are read and kept as a [`Patch`]. Restoring writes them back
+//! latest first, so bytes patched more than once end up as they were before
+//! the first write.
+
+use crate::writer::MemoryWriter;
+
+/// Bytes a write replaced
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Patch {
+    pub address: u64,
+    pub original: Vec<u8>,
+}
+
+/// The patches made on a session, oldest first
+#[derive(Debug, Default)]
+pub struct Patches {
+    patches: Vec<Patch>,
+}
+
+impl Patches {
+    pub fn record(&mut self, address: u64, original: Vec<u8>) {
+        if !original.is_empty() {
+            self.patches.push(Patch { address, original });
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.patches.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patches.is_empty()
+    }
+
+    /// Write the original bytes back, returning how many patches were
+    /// undone and why the others couldn't be. Those are kept, so restoring
+    /// again retries them.
+    pub fn restore(&mut self, writer: &mut MemoryWriter) -> (usize, Vec<String>) {
+        let mut restored = 0;
+        let mut errors = Vec::new();
+        let mut kept = Vec::new();
+        while let Some(patch) = self.patches.pop() {
+            match writer.write_at(patch.address, &patch.original) {
+                Ok(written) if written == patch.original.len() => restored += 1,
+                result => {
+                    let error = result.map_or_else(|e| e.to_string(), |_| "short write".into());
+                    errors.push(format!("Failed to restore 0x{:x}: {}", patch.address, error));
+                    kept.push(patch);
+                }
+            }
+        }
+        kept.reverse();
+        self.patches = kept;
+        (restored, errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restores_patches() {
+        let mut data = *b"original bytes";
+        let base = data.as_mut_ptr();
+        let address = base as u64;
+        let mut writer = MemoryWriter::new(std::process::id() as i32);
+        let mut patches = Patches::default();
+
+        // Overlapping writes, the second over the first
+        patches.record(address, b"orig".to_vec());
+        writer.write_at(address, b"XXXX").unwrap();
+        patches.record(address + 2, b"XXin".to_vec());
+        writer.write_at(address + 2, b"YYYY").unwrap();
+        patches.record(8, b"unmapped".to_vec());
+
+        let (restored, errors) = patches.restore(&mut writer);
+        assert_eq!((restored, errors.len()), (2, 1));
+        assert_eq!(patches.len(), 1);
+        // SAFETY: `base` points into `data`, which the kernel wrote through
+        let now = unsafe { (base as *const [u8; 14]).read_volatile() };
+        assert_eq!(&now, b"original bytes");
+    }
+}
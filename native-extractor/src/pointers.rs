@@ -0,0 +1,430 @@
+//! Pointers between mappings, and the chains of them leading to an address
+//!
+//! Data allocated at runtime moves between runs, but a chain of pointers
+//! from a library's static data usually doesn't: `[[libgame.so+0x1234] +
+//! 0x10] + 0x8` finds the same field each time. A [`PointerMap`] indexes
+//! every aligned pointer-sized value in the regions read that points into
+//! a mapping, by the address it points at. From a target address it is
+//! searched backwards for pointers to at most `maxOffset` bytes before the
+//! target, then for pointers to those, up to `maxDepth` levels, until a
+//! pointer held in a module is found. A module is a file mapped into the
+//! process, with the `.bss` the loader maps after it, and its base is the
+//! lowest address the file is mapped at.
+
+use crate::elf;
+use crate::maps::MemoryRegion;
+use crate::reader::{self, MemoryReader};
+use crate::scan::{RegionVisitor, ScanSink};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// Most addresses searched for at once at one level of a path search; past
+/// it the rest of the level is dropped
+const MAX_FRONTIER: usize = 1 << 20;
+
+/// The `pointers` object of the scan options
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct PointerOptions {
+    /// Bytes of a pointer, 4 or 8; the target's if unset
+    pub width: Option<usize>,
+    /// Address the paths searched for lead to
+    pub target: Option<u64>,
+    /// Most pointers in a path
+    pub max_depth: usize,
+    /// Most bytes a pointer may point before the address it leads to
+    pub max_offset: u64,
+    /// Most paths reported
+    pub max_results: usize,
+    /// Module a chain to resolve starts at, by file name or path
+    pub module: Option<String>,
+    /// Offsets of a chain to resolve: from the module's base, then from
+    /// each pointer read
+    pub offsets: Vec<u64>,
+}
+
+impl Default for PointerOptions {
+    fn default() -> Self {
+        PointerOptions {
+            width: None,
+            target: None,
+            max_depth: 4,
+            max_offset: 0x1000,
+            max_results: 100,
+            module: None,
+            offsets: Vec::new(),
+        }
+    }
+}
+
+/// Bytes of a pointer in the process `pid`, from the ELF class of its
+/// executable; this build's own if it can't be read
+pub fn process_width(pid: i32) -> usize {
+    let mut header = [0u8; 64];
+    let exe = Path::new("/proc").join(pid.to_string()).join("exe");
+    let class64 = File::open(exe)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .ok()
+        .and_then(|_| elf::parse_header(&header))
+        .map(|header| header.class64);
+    match class64 {
+        Some(true) => 8,
+        Some(false) => 4,
+        None => std::mem::size_of::<usize>(),
+    }
+}
+
+/// A file mapped into the process
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Module {
+    pub path: String,
+    /// Lowest address the file is mapped at
+    pub base: u64,
+    /// The mappings of the file, and the `.bss` after them
+    pub ranges: Vec<(u64, u64)>,
+}
+
+impl Module {
+    /// File name of the module, e.g. `libc.so`
+    pub fn name(&self) -> &str {
+        self.path.rsplit('/').next().unwrap_or(&self.path)
+    }
+
+    /// Whether `name` is this module's file name or path
+    pub fn is_named(&self, name: &str) -> bool {
+        self.path == name || self.name() == name
+    }
+}
+
+/// The modules among `regions`, sorted as the maps list them
+pub fn modules(regions: &[MemoryRegion]) -> Vec<Module> {
+    let mut modules: Vec<Module> = Vec::new();
+    let mut last: Option<(usize, u64)> = None;
+    for region in regions {
+        match region.path.as_deref() {
+            Some(path) if path.starts_with('/') && !path.starts_with("/dev/") => {
+                let index = match modules.iter().position(|module| module.path == path) {
+                    Some(index) => index,
+                    None => {
+                        modules.push(Module {
+                            path: path.to_string(),
+                            base: region.start,
+                            ranges: Vec::new(),
+                        });
+                        modules.len() - 1
+                    }
+                };
+                let module = &mut modules[index];
+                module.base = module.base.min(region.start);
+                module.ranges.push((region.start, region.end));
+                last = Some((index, region.end));
+            }
+            // The loader's zero-filled tail of a library's data
+            Some("[anon:.bss]") => {
+                if let Some((index, end)) = last.filter(|&(_, end)| end == region.start) {
+                    modules[index].ranges.push((region.start, region.end));
+                    last = Some((index, end.max(region.end)));
+                }
+            }
+            _ => last = None,
+        }
+    }
+    modules
+}
+
+/// The module `address` is in
+pub fn module_at(modules: &[Module], address: u64) -> Option<&Module> {
+    let contains = |&(start, end): &(u64, u64)| start <= address && address < end;
+    modules.iter().find(|module| module.ranges.iter().any(contains))
+}
+
+/// Every pointer found, by the address it points at
+pub struct PointerMap {
+    pub width: usize,
+    /// `(value, address)`, sorted
+    pub entries: Vec<(u64, u64)>,
+    pub modules: Vec<Module>,
+}
+
+impl PointerMap {
+    /// The map of `pointers`, as `(value, address)`, found in a process
+    /// whose mappings are `regions`
+    pub fn new(width: usize, mut pointers: Vec<(u64, u64)>, regions: &[MemoryRegion]) -> Self {
+        pointers.sort_unstable();
+        PointerMap {
+            width,
+            entries: pointers,
+            modules: modules(regions),
+        }
+    }
+
+    pub fn info(&self) -> PointerMapInfo {
+        PointerMapInfo {
+            width: self.width,
+            pointers: self.entries.len(),
+            modules: self.modules.len(),
+        }
+    }
+
+    /// The pointer paths from a module to `target`, the shortest first
+    pub fn find_paths(&self, target: u64, options: &PointerOptions) -> Vec<PointerPath> {
+        let mut paths = Vec::new();
+        let mut visited = HashSet::new();
+        // Addresses to find pointers to, with the offsets from each to the
+        // target
+        let mut frontier: Vec<(u64, Vec<u64>)> = vec![(target, Vec::new())];
+        for depth in 1..=options.max_depth {
+            let mut next = Vec::new();
+            for (address, offsets) in &frontier {
+                let low = address.saturating_sub(options.max_offset);
+                let first = self.entries.partition_point(|&(value, _)| value < low);
+                for &(value, at) in &self.entries[first..] {
+                    if value > *address {
+                        break;
+                    }
+                    let mut chain = Vec::with_capacity(offsets.len() + 1);
+                    chain.push(address - value);
+                    chain.extend_from_slice(offsets);
+                    if let Some(module) = module_at(&self.modules, at) {
+                        chain.insert(0, at - module.base);
+                        paths.push(PointerPath {
+                            module: module.name().to_string(),
+                            offsets: chain,
+                        });
+                        if paths.len() >= options.max_results {
+                            return paths;
+                        }
+                    } else if depth < options.max_depth
+                        && next.len() < MAX_FRONTIER
+                        && visited.insert(at)
+                    {
+                        next.push((at, chain));
+                    }
+                }
+            }
+            frontier = next;
+        }
+        paths
+    }
+}
+
+/// What a pointer map holds
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PointerMapInfo {
+    pub width: usize,
+    pub pointers: usize,
+    pub modules: usize,
+}
+
+/// A chain of pointers from a module to an address: the pointer at the
+/// module's base plus `offsets[0]` is read, then the one at what it points
+/// to plus `offsets[1]`, and so on, the last offset added to the last
+/// pointer read giving the address
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PointerPath {
+    /// File name of the module
+    pub module: String,
+    pub offsets: Vec<u64>,
+}
+
+/// One pointer read while resolving a chain
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ChainStep {
+    pub address: u64,
+    pub value: u64,
+}
+
+/// A chain of pointers, followed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolvedChain {
+    pub module: String,
+    pub base: u64,
+    pub steps: Vec<ChainStep>,
+    /// The address the chain leads to
+    pub address: u64,
+}
+
+/// Follow the chain from `module` by `offsets`, as in [`PointerPath`]
+pub fn resolve(
+    reader: &mut dyn MemoryReader,
+    modules: &[Module],
+    module: &str,
+    offsets: &[u64],
+    width: usize,
+) -> Result<ResolvedChain, String> {
+    let found = modules
+        .iter()
+        .find(|candidate| candidate.is_named(module))
+        .ok_or_else(|| format!("No module {} is mapped", module))?;
+    let (first, rest) = offsets.split_first().ok_or("No offsets to follow")?;
+    let mut address = found.base.wrapping_add(*first);
+    let mut steps = Vec::new();
+    for offset in rest {
+        let mut word = [0u8; 8];
+        match reader::read_into(reader, address, &mut word[..width]) {
+            Ok(read) if read == width => {}
+            _ => return Err(format!("Failed to read the pointer at 0x{:x}", address)),
+        }
+        let value = u64::from_le_bytes(word);
+        steps.push(ChainStep { address, value });
+        address = value.wrapping_add(*offset);
+    }
+    Ok(ResolvedChain {
+        module: found.name().to_string(),
+        base: found.base,
+        steps,
+        address,
+    })
+}
+
+/// Finds the aligned values in each region it is fed that point into one
+/// of `mapped`, as `(value, address)`
+pub struct PointerVisitor<'a> {
+    width: usize,
+    /// Address ranges mapped, sorted and apart
+    mapped: &'a [(u64, u64)],
+    found: Vec<(u64, u64)>,
+}
+
+impl<'a> PointerVisitor<'a> {
+    pub fn new(width: usize, mapped: &'a [(u64, u64)]) -> Self {
+        PointerVisitor {
+            width,
+            mapped,
+            found: Vec::new(),
+        }
+    }
+
+    fn points_in(&self, value: u64) -> bool {
+        let index = self.mapped.partition_point(|&(start, _)| start <= value);
+        index > 0 && value < self.mapped[index - 1].1
+    }
+}
+
+/// The ranges `regions` cover, merged where they touch
+pub fn mapped_ranges(regions: &[MemoryRegion]) -> Vec<(u64, u64)> {
+    let mut ranges: Vec<(u64, u64)> = Vec::new();
+    for region in regions {
+        match ranges.last_mut() {
+            Some(last) if last.1 == region.start => last.1 = region.end,
+            _ => ranges.push((region.start, region.end)),
+        }
+    }
+    ranges
+}
+
+impl RegionVisitor for PointerVisitor<'_> {
+    type Output = (u64, u64);
+
+    fn visit_chunk(&mut self, _region: &MemoryRegion, address: u64, chunk: &[u8]) {
+        let (Some(&(low, _)), Some(&(_, high))) = (self.mapped.first(), self.mapped.last()) else {
+            return;
+        };
+        let skip = (address.next_multiple_of(self.width as u64) - address) as usize;
+        let words = chunk.get(skip..).unwrap_or_default().chunks_exact(self.width);
+        for (index, word) in words.enumerate() {
+            let mut bytes = [0u8; 8];
+            bytes[..self.width].copy_from_slice(word);
+            let value = u64::from_le_bytes(bytes);
+            if (low..high).contains(&value) && self.points_in(value) {
+                let at = address + (skip + index * self.width) as u64;
+                self.found.push((value, at));
+            }
+        }
+    }
+
+    fn end_region(
+        &mut self,
+        _region: &MemoryRegion,
+        _reader: &mut dyn MemoryReader,
+        sink: &mut dyn ScanSink<(u64, u64)>,
+    ) {
+        for pointer in self.found.drain(..) {
+            sink.result(pointer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps::parse_maps_line;
+    use crate::reader::ProcfsReader;
+
+    #[test]
+    fn test_modules() {
+        let regions: Vec<MemoryRegion> = [
+            "1000-2000 r--p 00000000 fd:00 7 /data/app/lib/arm64/libgame.so",
+            "2000-3000 r-xp 00001000 fd:00 7 /data/app/lib/arm64/libgame.so",
+            "3000-4000 rw-p 00002000 fd:00 7 /data/app/lib/arm64/libgame.so",
+            "4000-5000 rw-p 00000000 00:00 0 [anon:.bss]",
+            "5000-6000 rw-p 00000000 00:00 0 [heap]",
+            "7000-8000 rw-p 00000000 00:00 0 [anon:.bss]",
+        ]
+        .iter()
+        .map(|line| parse_maps_line(line).unwrap())
+        .collect();
+        let modules = modules(&regions);
+        assert_eq!(modules.len(), 1);
+        assert_eq!((modules[0].name(), modules[0].base), ("libgame.so", 0x1000));
+        assert_eq!(module_at(&modules, 0x4800).map(|module| module.base), Some(0x1000));
+        assert_eq!(module_at(&modules, 0x7800), None);
+        assert_eq!(mapped_ranges(&regions), [(0x1000, 0x6000), (0x7000, 0x8000)]);
+    }
+
+    #[test]
+    fn test_find_and_resolve_paths() {
+        // A module's data pointing at an object, which points at another
+        // holding the target 8 bytes in
+        let mut data = vec![0u64; 0x300];
+        let base = data.as_ptr() as u64;
+        data[0x10] = base + 0x1000;
+        data[0x201] = base + 0x800;
+        let target = base + 0x800 + 0x18;
+        let modules = vec![Module {
+            path: "/data/app/lib/arm64/libgame.so".into(),
+            base,
+            ranges: vec![(base, base + 0x100)],
+        }];
+        let mut entries: Vec<(u64, u64)> = data
+            .iter()
+            .enumerate()
+            .filter(|&(_, &value)| value != 0)
+            .map(|(index, &value)| (value, base + 8 * index as u64))
+            .collect();
+        entries.sort_unstable();
+        let map = PointerMap {
+            width: 8,
+            entries,
+            modules,
+        };
+
+        let paths = map.find_paths(target, &PointerOptions::default());
+        assert_eq!(
+            paths,
+            [PointerPath {
+                module: "libgame.so".into(),
+                offsets: vec![0x80, 0x8, 0x18],
+            }]
+        );
+        // Not within reach of a module in one pointer
+        let shallow = PointerOptions {
+            max_depth: 1,
+            ..PointerOptions::default()
+        };
+        assert!(map.find_paths(target, &shallow).is_empty());
+
+        let mut reader = ProcfsReader::open(std::process::id() as i32).unwrap();
+        let chain = resolve(&mut reader, &map.modules, "libgame.so", &paths[0].offsets, 8);
+        let chain = chain.unwrap();
+        assert_eq!(chain.steps.len(), 2);
+        assert_eq!(chain.steps[0].value, base + 0x1000);
+        assert_eq!(chain.address, target);
+        assert!(resolve(&mut reader, &map.modules, "libc.so", &[0], 8).is_err());
+    }
+}
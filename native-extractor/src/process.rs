@@ -0,0 +1,351 @@
+//! Discovering processes through /proc
+//!
+//! Android names app processes after their package, with secondary
+//! processes (`android:process=":remote"`) named `<package>:<suffix>`.
+//! Every app process is forked from a zygote, which is how
+//! [`list_app_processes`] tells them apart from native daemons.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+
+/// PIDs of every running process, in no particular order.
+///
+/// Processes hidden from us (`hidepid`) are simply not listed.
+pub fn list_pids() -> io::Result<Vec<i32>> {
+    let mut pids = Vec::new();
+    for entry in fs::read_dir("/proc")? {
+        let entry = entry?;
+        if let Some(pid) = entry.file_name().to_str().and_then(|name| name.parse().ok()) {
+            pids.push(pid);
+        }
+    }
+    Ok(pids)
+}
+
+/// The name a process runs under: the first argument of its command line,
+/// which Android sets to the process name for app processes
+pub fn process_name(pid: i32) -> io::Result<String> {
+    let cmdline = fs::read(format!("/proc/{}/cmdline", pid))?;
+    let name = cmdline.split(|&byte| byte == 0).next().unwrap_or_default();
+    Ok(String::from_utf8_lossy(name).into_owned())
+}
+
+/// Whether a process name belongs to `package`: its main process or one of
+/// its `<package>:<suffix>` secondary processes
+pub fn belongs_to_package(name: &str, package: &str) -> bool {
+    match name.strip_prefix(package) {
+        Some(rest) => rest.is_empty() || rest.starts_with(':'),
+        None => false,
+    }
+}
+
+/// PIDs of the processes of `package`, the main process first and the
+/// rest in PID order.
+///
+/// Processes that exit while /proc is being walked are skipped.
+pub fn find_pids_by_package(package: &str) -> io::Result<Vec<i32>> {
+    let mut found: Vec<(bool, i32)> = list_pids()?
+        .into_iter()
+        .filter_map(|pid| {
+            let name = process_name(pid).ok()?;
+            belongs_to_package(&name, package).then(|| (name != package, pid))
+        })
+        .collect();
+    found.sort_unstable();
+    Ok(found.into_iter().map(|(_, pid)| pid).collect())
+}
+
+/// Names of the zygotes that app processes are forked from
+const ZYGOTES: [&str; 2] = ["zygote", "zygote64"];
+
+/// Whether a process name is a zygote's
+pub fn is_zygote(name: &str) -> bool {
+    ZYGOTES.contains(&name)
+}
+
+/// A running process, as listed by [`list_app_processes`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessEntry {
+    pub pid: i32,
+    pub ppid: i32,
+    /// Real UID; each app has its own, shared by all its processes
+    pub uid: u32,
+    /// Process name, see [`process_name`]
+    pub name: String,
+    /// `name` without a `:<suffix>`, the package for app processes
+    pub package: String,
+    /// Full command line, arguments separated by spaces
+    pub cmdline: String,
+    /// Resident set size
+    pub rss_bytes: u64,
+    /// When the process started, in milliseconds since boot
+    pub start_time_ms: u64,
+}
+
+impl ProcessEntry {
+    /// Read a process's name, UID and /proc/[pid]/stat figures
+    pub fn read(pid: i32) -> io::Result<Self> {
+        let invalid = |what| io::Error::new(io::ErrorKind::InvalidData, what);
+
+        let stat = fs::read_to_string(format!("/proc/{}/stat", pid))?;
+        let stat = parse_stat(&stat).ok_or_else(|| invalid("malformed stat"))?;
+        let status = fs::read_to_string(format!("/proc/{}/status", pid))?;
+        let uid = parse_uid(&status).ok_or_else(|| invalid("no Uid in status"))?;
+
+        let cmdline = fs::read(format!("/proc/{}/cmdline", pid))?;
+        let mut args = cmdline.split(|&byte| byte == 0).filter(|arg| !arg.is_empty());
+        let name = String::from_utf8_lossy(args.next().unwrap_or_default()).into_owned();
+        let mut cmdline = name.clone();
+        for arg in args {
+            cmdline.push(' ');
+            cmdline.push_str(&String::from_utf8_lossy(arg));
+        }
+
+        Ok(ProcessEntry {
+            pid,
+            ppid: stat.ppid,
+            uid,
+            package: name.split(':').next().unwrap_or_default().to_string(),
+            name,
+            cmdline,
+            rss_bytes: stat.rss_pages * sysconf(libc::_SC_PAGESIZE, 4096),
+            start_time_ms: stat.start_ticks * 1000 / sysconf(libc::_SC_CLK_TCK, 100),
+        })
+    }
+}
+
+/// The /proc/[pid]/stat fields [`ProcessEntry`] uses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Stat {
+    ppid: i32,
+    /// Start time in clock ticks since boot
+    start_ticks: u64,
+    rss_pages: u64,
+}
+
+/// Parse /proc/[pid]/stat. The command name in parentheses may itself hold
+/// spaces and parentheses, so fields are counted from the last `)`.
+fn parse_stat(stat: &str) -> Option<Stat> {
+    let (_, fields) = stat.rsplit_once(')')?;
+    // Fields after the name start at field 3 (state)
+    let fields: Vec<&str> = fields.split_whitespace().collect();
+    let field = |number: usize| fields.get(number - 3).copied();
+    Some(Stat {
+        ppid: field(4)?.parse().ok()?,
+        start_ticks: field(22)?.parse().ok()?,
+        rss_pages: field(24)?.parse().ok()?,
+    })
+}
+
+/// Real UID from the `Uid:` line of /proc/[pid]/status
+fn parse_uid(status: &str) -> Option<u32> {
+    let line = status.lines().find_map(|line| line.strip_prefix("Uid:"))?;
+    line.split_whitespace().next()?.parse().ok()
+}
+
+fn sysconf(name: libc::c_int, fallback: u64) -> u64 {
+    // SAFETY: sysconf only reads system configuration
+    match unsafe { libc::sysconf(name) } {
+        value if value > 0 => value as u64,
+        _ => fallback,
+    }
+}
+
+/// Every running process forked from a zygote, directly or through another
+/// process (such as the WebView zygote), in PID order.
+///
+/// Processes that exit while /proc is being walked are skipped.
+pub fn list_app_processes() -> io::Result<Vec<ProcessEntry>> {
+    let processes = list_pids()?
+        .into_iter()
+        .filter_map(|pid| ProcessEntry::read(pid).ok())
+        .collect();
+    Ok(zygote_descendants(processes))
+}
+
+/// Keep the processes with a zygote among their ancestors
+fn zygote_descendants(processes: Vec<ProcessEntry>) -> Vec<ProcessEntry> {
+    let parents: HashMap<i32, (i32, bool)> = processes
+        .iter()
+        .map(|p| (p.pid, (p.ppid, is_zygote(&p.name))))
+        .collect();
+
+    let forked_from_zygote = |mut pid: i32| {
+        // Bounded in case the table is inconsistent from being read live
+        for _ in 0..parents.len() {
+            let Some(&(ppid, _)) = parents.get(&pid) else {
+                return false;
+            };
+            match parents.get(&ppid) {
+                Some(&(_, true)) => return true,
+                Some(_) => pid = ppid,
+                None => return false,
+            }
+        }
+        false
+    };
+
+    let mut apps: Vec<ProcessEntry> =
+        processes.into_iter().filter(|p| forked_from_zygote(p.pid)).collect();
+    apps.sort_unstable_by_key(|p| p.pid);
+    apps
+}
+
+/// The processes of one app, for scans that cover all of them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AppSelector {
+    /// Processes named after the package, and any others with their UIDs
+    Package(String),
+    Uid(u32),
+}
+
+impl fmt::Display for AppSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppSelector::Package(package) => write!(f, "package {}", package),
+            AppSelector::Uid(uid) => write!(f, "UID {}", uid),
+        }
+    }
+}
+
+/// Every process of an app, including children forked from them (such as
+/// processes started by an app zygote), the main process first and the
+/// rest in PID order.
+///
+/// WebView renderers are children of the WebView zygote rather than of the
+/// app, so a [`AppSelector::Package`] selection only finds them when they
+/// run under the app's UID.
+pub fn find_app_processes(selector: &AppSelector) -> io::Result<Vec<ProcessEntry>> {
+    let processes = list_pids()?
+        .into_iter()
+        .filter_map(|pid| ProcessEntry::read(pid).ok())
+        .collect();
+    Ok(select_app_processes(processes, selector))
+}
+
+fn select_app_processes(processes: Vec<ProcessEntry>, selector: &AppSelector) -> Vec<ProcessEntry> {
+    let uids: Vec<u32> = match selector {
+        AppSelector::Package(package) => processes
+            .iter()
+            .filter(|p| belongs_to_package(&p.name, package))
+            .map(|p| p.uid)
+            .collect(),
+        AppSelector::Uid(uid) => vec![*uid],
+    };
+    let mut selected: Vec<bool> = processes.iter().map(|p| uids.contains(&p.uid)).collect();
+
+    // Add descendants until no more are found; isolated children run under
+    // UIDs of their own
+    loop {
+        let parents: Vec<i32> = processes
+            .iter()
+            .zip(&selected)
+            .filter(|(_, &selected)| selected)
+            .map(|(p, _)| p.pid)
+            .collect();
+        let mut added = false;
+        for (process, selected) in processes.iter().zip(&mut selected) {
+            if !*selected && parents.contains(&process.ppid) {
+                *selected = true;
+                added = true;
+            }
+        }
+        if !added {
+            break;
+        }
+    }
+
+    let mut apps: Vec<ProcessEntry> = processes
+        .into_iter()
+        .zip(selected)
+        .filter_map(|(process, selected)| selected.then_some(process))
+        .collect();
+    let is_main = |p: &ProcessEntry| match selector {
+        AppSelector::Package(package) => p.name == *package,
+        AppSelector::Uid(_) => !p.name.contains(':'),
+    };
+    apps.sort_unstable_by_key(|p| (!is_main(p), p.pid));
+    apps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_own_process_by_name() {
+        assert!(belongs_to_package("com.example.app", "com.example.app"));
+        assert!(belongs_to_package("com.example.app:remote", "com.example.app"));
+        assert!(!belongs_to_package("com.example.apple", "com.example.app"));
+
+        let pid = std::process::id() as i32;
+        let name = process_name(pid).unwrap();
+        assert!(find_pids_by_package(&name).unwrap().contains(&pid));
+    }
+
+    #[test]
+    fn test_parse_stat_and_zygote_descendants() {
+        let stat = "1234 (Binder:1234 (x)) S 600 600 0 0 -1 4194624 1000 0 0 0 50 20 0 0 \
+                    10 -10 30 0 98765 2000000000 5120 18446744073709551615";
+        let stat = parse_stat(stat).unwrap();
+        assert_eq!(stat.ppid, 600);
+        assert_eq!(stat.start_ticks, 98765);
+        assert_eq!(stat.rss_pages, 5120);
+        assert_eq!(parse_uid("Name:\tapp\nUid:\t10123\t10123\t10123\t10123\n"), Some(10123));
+
+        let entry = |pid, ppid, name: &str| ProcessEntry {
+            pid,
+            ppid,
+            uid: 10000,
+            name: name.to_string(),
+            package: name.to_string(),
+            cmdline: name.to_string(),
+            rss_bytes: 0,
+            start_time_ms: 0,
+        };
+        let processes = vec![
+            entry(1, 0, "init"),
+            entry(600, 1, "zygote64"),
+            entry(700, 1, "surfaceflinger"),
+            entry(1300, 600, "webview_zygote"),
+            entry(1234, 600, "com.example.app"),
+            entry(1400, 1300, "com.android.webview:sandboxed_process0"),
+        ];
+        let pids: Vec<i32> = zygote_descendants(processes).iter().map(|p| p.pid).collect();
+        assert_eq!(pids, [1234, 1300, 1400]);
+    }
+
+    #[test]
+    fn test_select_app_processes_with_isolated_children() {
+        let entry = |pid, ppid, uid, name: &str| ProcessEntry {
+            pid,
+            ppid,
+            uid,
+            name: name.to_string(),
+            package: name.split(':').next().unwrap().to_string(),
+            cmdline: name.to_string(),
+            rss_bytes: 0,
+            start_time_ms: 0,
+        };
+        let processes = vec![
+            entry(600, 1, 0, "zygote64"),
+            entry(1500, 600, 10123, "com.example.app:remote"),
+            entry(1234, 600, 10123, "com.example.app"),
+            entry(1600, 600, 10123, "com.example.app_zygote"),
+            entry(1700, 1600, 99001, "com.example.app:isolated"),
+            entry(1800, 600, 10456, "com.other.app"),
+        ];
+        let pids = |selector| -> Vec<i32> {
+            select_app_processes(processes.clone(), &selector).iter().map(|p| p.pid).collect()
+        };
+
+        let package = AppSelector::Package("com.example.app".to_string());
+        assert_eq!(pids(package), [1234, 1500, 1600, 1700]);
+        assert_eq!(pids(AppSelector::Uid(10456)), [1800]);
+        assert_eq!(pids(AppSelector::Uid(4242)), Vec::<i32>::new());
+    }
+}
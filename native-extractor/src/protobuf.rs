@@ -0,0 +1,374 @@
+//! Protocol Buffers messages found in memory and decoded without a schema
+//!
+//! gRPC apps keep most of their state in protobuf messages, which are
+//! binary and invisible to string extraction. A message is a run of
+//! fields, each a varint tag giving its number and wire type, then a
+//! value: a varint, a fixed 32 or 64-bit word, or length-delimited bytes.
+//! Without the schema, length-delimited bytes are taken for a string if
+//! they are printable UTF-8, a nested message if they parse as one to the
+//! last byte, and raw bytes otherwise.
+//!
+//! Nearly any byte starts something that parses, so a run only counts as a
+//! message if it looks like what serializers write: fields in ascending
+//! order of number, a repeated one with the same wire type each time,
+//! none above `maxFieldNumber`, no deprecated groups, at least `minFields`
+//! of them over `minLength` bytes, and among them a string or a nested
+//! message of more than one field. Text parses too, so at least two bytes
+//! of its tags, lengths and numbers must be outside printable ASCII, as
+//! the tags of fields 1 to 3, the lengths of short strings and small
+//! numbers are.
+
+use crate::maps::MemoryRegion;
+use crate::reader::MemoryReader;
+use crate::scan::{Location, RegionVisitor, ScanSink};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::sync::Arc;
+
+/// Largest `maxLength` allowed
+pub const MAX_MESSAGE_LENGTH: usize = 1 << 20;
+
+/// Deepest nesting of messages decoded; deeper ones are left as bytes
+const MAX_DEPTH: usize = 32;
+
+/// The `protobuf` object of the scan options
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ProtobufOptions {
+    /// Shortest message reported, in bytes
+    pub min_length: usize,
+    /// Longest message followed, in bytes; longer ones are cut there
+    pub max_length: usize,
+    /// Fewest fields a message must have, not counting nested ones
+    pub min_fields: usize,
+    /// Highest field number taken for one; schemas rarely go past a few
+    /// hundred
+    pub max_field_number: u32,
+}
+
+impl Default for ProtobufOptions {
+    fn default() -> Self {
+        ProtobufOptions {
+            min_length: 24,
+            max_length: 64 << 10,
+            min_fields: 3,
+            max_field_number: 4095,
+        }
+    }
+}
+
+/// A field of a message
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProtoField {
+    pub field: u32,
+    #[serde(flatten)]
+    pub value: ProtoValue,
+}
+
+/// A field value, as far as the wire type tells
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", content = "value", rename_all = "camelCase")]
+pub enum ProtoValue {
+    Varint(u64),
+    Fixed64(u64),
+    Fixed32(u32),
+    String(String),
+    /// Non-printable bytes escaped
+    Bytes(String),
+    Message(Vec<ProtoField>),
+}
+
+/// A message found in memory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProtoMessage {
+    #[serde(flatten)]
+    pub location: Location,
+    pub length: usize,
+    pub fields: Vec<ProtoField>,
+}
+
+/// The varint at `data[at]` and where it ends, or `Err` with whether it
+/// runs off the end of `data` rather than being malformed
+fn varint(data: &[u8], at: usize) -> Result<(u64, usize), bool> {
+    let mut value = 0u64;
+    for i in 0..10 {
+        let &byte = data.get(at + i).ok_or(true)?;
+        if i == 9 && byte > 1 {
+            return Err(false);
+        }
+        value |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok((value, at + i + 1));
+        }
+    }
+    Err(false)
+}
+
+fn printable(bytes: &[u8]) -> Option<&str> {
+    std::str::from_utf8(bytes)
+        .ok()
+        .filter(|text| text.chars().all(|c| !c.is_control() || matches!(c, '\n' | '\r' | '\t')))
+}
+
+/// Called with the number, wire type, tag and length bytes, and value
+/// bytes of a field
+type FieldVisitor<'a> = dyn FnMut(u32, u64, &[u8], &[u8]) + 'a;
+
+/// Walk the fields at the start of `data` for as long as they hold
+/// together, calling `visit` with each. Returns the bytes they take up, and
+/// whether the walk stopped at the end of `data` partway through a field.
+fn walk(
+    data: &[u8],
+    options: &ProtobufOptions,
+    visit: &mut FieldVisitor,
+) -> (usize, bool) {
+    let mut at = 0;
+    let mut last = (0, 0);
+    while at < data.len() {
+        let (tag, after) = match varint(data, at) {
+            Ok(tag) => tag,
+            Err(ran_out) => return (at, ran_out),
+        };
+        let (field, wire) = (tag >> 3, tag & 7);
+        // In order, and a repeated field has one wire type throughout
+        if field == 0
+            || field > u64::from(options.max_field_number)
+            || field < last.0
+            || field == last.0 && wire != last.1
+        {
+            break;
+        }
+        let value = match wire {
+            0 => varint(data, after).map(|(_, end)| (after, end)),
+            1 | 5 => {
+                let end = after + if wire == 1 { 8 } else { 4 };
+                if end <= data.len() { Ok((after, end)) } else { Err(true) }
+            }
+            2 => varint(data, after).and_then(|(length, start)| {
+                let end = usize::try_from(length).ok().and_then(|l| start.checked_add(l));
+                end.filter(|&end| end <= data.len()).map(|end| (start, end)).ok_or(true)
+            }),
+            _ => Err(false),
+        };
+        match value {
+            Ok((start, end)) => {
+                visit(field as u32, wire, &data[at..start], &data[start..end]);
+                at = end;
+                last = (field, wire);
+            }
+            Err(ran_out) => return (at, ran_out),
+        }
+    }
+    (at, false)
+}
+
+/// Decode the fields of a message that takes up all of `data`, or `None`
+/// if it doesn't
+fn decode(data: &[u8], options: &ProtobufOptions, depth: usize) -> Option<Vec<ProtoField>> {
+    let mut fields = Vec::new();
+    let (used, _) = walk(data, options, &mut |field, wire, _, bytes| {
+        let little_endian = || bytes.iter().rev().fold(0, |n, &b| n << 8 | u64::from(b));
+        let value = match wire {
+            0 => ProtoValue::Varint(varint(bytes, 0).map_or(0, |(value, _)| value)),
+            1 => ProtoValue::Fixed64(little_endian()),
+            5 => ProtoValue::Fixed32(little_endian() as u32),
+            _ => decode_bytes(bytes, options, depth),
+        };
+        fields.push(ProtoField { field, value });
+    });
+    (used == data.len()).then_some(fields)
+}
+
+/// A length-delimited value: a string, a message or bytes
+fn decode_bytes(bytes: &[u8], options: &ProtobufOptions, depth: usize) -> ProtoValue {
+    if let Some(text) = printable(bytes) {
+        return ProtoValue::String(text.to_string());
+    }
+    match (depth < MAX_DEPTH).then(|| decode(bytes, options, depth + 1)).flatten() {
+        Some(nested) => ProtoValue::Message(nested),
+        None => ProtoValue::Bytes(bytes.escape_ascii().to_string()),
+    }
+}
+
+/// Whether `fields` have what real messages have: a string, or a nested
+/// message of more than one field
+fn substantial(fields: &[ProtoField]) -> bool {
+    fields.iter().any(|field| match &field.value {
+        ProtoValue::String(text) => text.chars().count() >= 3,
+        ProtoValue::Message(nested) => nested.len() > 1,
+        _ => false,
+    })
+}
+
+/// The messages in `data`, as offsets, lengths and fields, and how much of
+/// it is done with: unless `last`, a message running past the end is left
+/// for when more bytes follow
+pub fn find_messages(
+    data: &[u8],
+    options: &ProtobufOptions,
+    last: bool,
+) -> (Vec<(usize, usize, Vec<ProtoField>)>, usize) {
+    let mut found = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let end = data.len().min(i + options.max_length);
+        // Walked first, and only decoded if the shape is right
+        let (mut count, mut binary) = (0, 0);
+        let (length, ran_out) = walk(&data[i..end], options, &mut |_, wire, header, value| {
+            count += 1;
+            let scalar = if wire == 2 { &[][..] } else { value };
+            let bytes = header.iter().chain(scalar);
+            binary += bytes.filter(|&&b| !(0x20..0x7f).contains(&b)).count();
+        });
+        if ran_out && !last && end == data.len() {
+            return (found, i);
+        }
+        let message = &data[i..i + length];
+        let shape = count >= options.min_fields.max(1) && length >= options.min_length;
+        let fields = (shape && binary >= 2)
+            .then(|| decode(message, options, 0))
+            .flatten()
+            .filter(|fields| substantial(fields));
+        match fields {
+            Some(fields) => {
+                found.push((i, length, fields));
+                i += length;
+            }
+            None => i += 1,
+        }
+    }
+    (found, i)
+}
+
+/// `fields` on one line, as in `1: 150, 2: "abc", 3 {1: 7}`
+pub fn render(fields: &[ProtoField]) -> String {
+    let mut out = String::new();
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        let _ = match &field.value {
+            ProtoValue::Varint(value) | ProtoValue::Fixed64(value) => {
+                write!(out, "{}: {}", field.field, value)
+            }
+            ProtoValue::Fixed32(value) => write!(out, "{}: {}", field.field, value),
+            ProtoValue::String(text) => write!(out, "{}: {:?}", field.field, text),
+            ProtoValue::Bytes(bytes) => write!(out, "{}: b\"{}\"", field.field, bytes),
+            ProtoValue::Message(nested) => write!(out, "{} {{{}}}", field.field, render(nested)),
+        };
+    }
+    out
+}
+
+/// Finds messages in each region it is fed
+pub struct ProtobufVisitor {
+    options: ProtobufOptions,
+    buffer: Vec<u8>,
+    /// Address of the first byte in `buffer`
+    start: u64,
+    found: Vec<ProtoMessage>,
+}
+
+impl ProtobufVisitor {
+    pub fn new(options: &ProtobufOptions) -> Self {
+        ProtobufVisitor {
+            options: options.clone(),
+            buffer: Vec::new(),
+            start: 0,
+            found: Vec::new(),
+        }
+    }
+
+    fn flush(&mut self, last: bool) {
+        let (messages, done) = find_messages(&self.buffer, &self.options, last);
+        for (offset, length, fields) in messages {
+            self.found.push(ProtoMessage {
+                // Set in end_region, once the region is known
+                location: Location::new(&Arc::from(""), 0, self.start + offset as u64),
+                length,
+                fields,
+            });
+        }
+        self.buffer.drain(..done);
+        self.start += done as u64;
+    }
+}
+
+impl RegionVisitor for ProtobufVisitor {
+    type Output = ProtoMessage;
+
+    fn visit_chunk(&mut self, _region: &MemoryRegion, address: u64, chunk: &[u8]) {
+        // A gap is where pages were skipped, which no message spans
+        if address != self.start + self.buffer.len() as u64 {
+            self.flush(true);
+            self.buffer.clear();
+            self.start = address;
+        }
+        self.buffer.extend_from_slice(chunk);
+        if self.buffer.len() >= 2 * self.options.max_length.max(chunk.len()) {
+            self.flush(false);
+        }
+    }
+
+    fn end_region(
+        &mut self,
+        region: &MemoryRegion,
+        _reader: &mut dyn MemoryReader,
+        sink: &mut dyn ScanSink<ProtoMessage>,
+    ) {
+        self.flush(true);
+        self.buffer.clear();
+        let name: Arc<str> = region.name().into();
+        for mut message in self.found.drain(..) {
+            message.location = Location::new(&name, region.start, message.location.address);
+            sink.result(message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `{1: 150, 2: "alice@example.com", 3 {1: 7, 2: "x-token"}, 4: 1}`
+    fn message() -> Vec<u8> {
+        let mut message = vec![0x08, 0x96, 0x01, 0x12, 17];
+        message.extend_from_slice(b"alice@example.com");
+        message.extend_from_slice(&[0x1a, 11, 0x08, 0x07, 0x12, 7]);
+        message.extend_from_slice(b"x-token");
+        message.extend_from_slice(&[0x25, 1, 0, 0, 0]);
+        message
+    }
+
+    #[test]
+    fn test_finds_and_decodes_messages() {
+        let mut data = b"\x00\x00\x07\xff plain text, not a message \x00".to_vec();
+        let at = data.len();
+        data.extend_from_slice(&message());
+        // Field 2 again ends it: fields come in order
+        data.extend_from_slice(&[0x10, 0x01, 0x00, 0xff, 0xff]);
+
+        let (found, done) = find_messages(&data, &ProtobufOptions::default(), true);
+        assert_eq!(done, data.len());
+        assert_eq!(found.len(), 1);
+        let (offset, length, fields) = &found[0];
+        assert_eq!((*offset, *length), (at, message().len()));
+        assert_eq!(
+            render(fields),
+            r#"1: 150, 2: "alice@example.com", 3 {1: 7, 2: "x-token"}, 4: 1"#
+        );
+        let json = serde_json::to_string(&fields[2]).unwrap();
+        assert_eq!(
+            json,
+            concat!(
+                r#"{"field":3,"type":"message","value":[{"field":1,"type":"varint","value":7},"#,
+                r#"{"field":2,"type":"string","value":"x-token"}]}"#
+            )
+        );
+
+        // Cut short, it waits for the rest unless there is none
+        let cut = &data[..at + 10];
+        assert_eq!(find_messages(cut, &ProtobufOptions::default(), false), (Vec::new(), at));
+    }
+}
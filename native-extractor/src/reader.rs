@@ -0,0 +1,793 @@
+//! Memory acquisition backends
+//!
+//! Every way of getting bytes out of a target sits behind the [`MemoryReader`]
+//! trait, so scanners don't care whether the data came from a syscall, a
+//! procfs file, ptrace, or a dump on disk.
+
+use nix::errno::Errno;
+use nix::sys::ptrace;
+use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
+use nix::unistd::Pid;
+use serde::Deserialize;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::ops::{ControlFlow, Deref, DerefMut, Range};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Size of each read issued against the target, unless a scan asks for
+/// another
+pub const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Smallest and largest chunk size a scan can ask for
+pub const MIN_CHUNK_SIZE: usize = 4 * 1024;
+pub const MAX_CHUNK_SIZE: usize = 16 * 1024 * 1024;
+
+/// Most buffers a [`BufferPool`] keeps, enough for a thread per core
+const MAX_POOLED_BUFFERS: usize = 16;
+
+/// Largest number of remote iovecs handed to a single process_vm_readv call
+const MAX_REMOTE_IOVECS: usize = 1024;
+
+/// A source of target memory, addressed by virtual address.
+///
+/// Readers are `Send` so they can live in a [`crate::session::Session`]
+/// used from any Java thread.
+pub trait MemoryReader: Send {
+    /// Short name of the backend, used in reports
+    fn name(&self) -> &'static str;
+
+    /// Read up to `buf.len()` bytes starting at `address`.
+    ///
+    /// A short read means the bytes after it could not be read (an unmapped
+    /// or guard page); an error means nothing at `address` could be read.
+    fn read_at(&mut self, address: u64, buf: &mut [u8]) -> io::Result<usize>;
+
+    /// Another reader of the same memory, for use on another thread, if the
+    /// backend can have several
+    fn try_clone(&self) -> Option<Box<dyn MemoryReader>> {
+        None
+    }
+}
+
+/// Which backend to use when opening a live process, the `readerStrategy`
+/// of the scan options
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ReaderStrategy {
+    /// Try process_vm_readv, then procfs, then ptrace
+    #[default]
+    Auto,
+    VmReadv,
+    Procfs,
+    Ptrace,
+}
+
+/// Open a reader for a live process using the given strategy
+pub fn open_reader(pid: i32, strategy: ReaderStrategy) -> io::Result<Box<dyn MemoryReader>> {
+    Ok(match strategy {
+        ReaderStrategy::Auto => Box::new(AutoReader::new(pid)),
+        ReaderStrategy::VmReadv => Box::new(VmReadvReader::new(pid)),
+        ReaderStrategy::Procfs => Box::new(ProcfsReader::open(pid)?),
+        ReaderStrategy::Ptrace => Box::new(PtraceReader::attach(pid)?),
+    })
+}
+
+/// Read buffers kept for reuse, so that repeated scans of a long-lived
+/// session don't allocate and free a chunk-sized buffer for every walk
+#[derive(Debug, Default)]
+pub struct BufferPool {
+    free: Mutex<Vec<Vec<u8>>>,
+}
+
+impl BufferPool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A buffer of `size` bytes, a free one of that size if there is one.
+    /// Its contents are whatever was last read into it.
+    pub fn take(&self, size: usize) -> PooledBuffer<'_> {
+        let mut free = self.free.lock().unwrap_or_else(|e| e.into_inner());
+        let buffer = match free.iter().position(|buffer| buffer.len() == size) {
+            Some(index) => free.swap_remove(index),
+            None => vec![0u8; size],
+        };
+        PooledBuffer {
+            pool: Some(self),
+            buffer,
+        }
+    }
+
+    /// How many buffers are free
+    pub fn len(&self) -> usize {
+        self.free.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A buffer from a [`BufferPool`], given back to it when dropped
+pub struct PooledBuffer<'a> {
+    pool: Option<&'a BufferPool>,
+    buffer: Vec<u8>,
+}
+
+impl PooledBuffer<'_> {
+    /// A buffer of `size` bytes that belongs to no pool
+    pub fn unpooled(size: usize) -> Self {
+        PooledBuffer {
+            pool: None,
+            buffer: vec![0u8; size],
+        }
+    }
+}
+
+impl Deref for PooledBuffer<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.buffer
+    }
+}
+
+impl DerefMut for PooledBuffer<'_> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.buffer
+    }
+}
+
+impl Drop for PooledBuffer<'_> {
+    fn drop(&mut self) {
+        let Some(pool) = self.pool else {
+            return;
+        };
+        let mut free = pool.free.lock().unwrap_or_else(|e| e.into_inner());
+        if free.len() < MAX_POOLED_BUFFERS {
+            free.push(std::mem::take(&mut self.buffer));
+        }
+    }
+}
+
+/// What [`read_region`] got out of a region
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RegionRead {
+    pub bytes_read: u64,
+    /// Ranges that couldn't be read and were skipped, merged and in order
+    pub skipped: Vec<Range<u64>>,
+}
+
+/// Read one region in chunks the size of `buffer`, handing each chunk to
+/// `on_chunk` together with the virtual address it was read from. `on_chunk`
+/// can stop the read early by returning [`ControlFlow::Break`].
+///
+/// A chunk that fails or comes back short, usually at guard pages or swapped
+/// pages that can't be faulted in, is retried page by page: pages that read
+/// are delivered one at a time, the rest recorded as skipped, and the read
+/// goes on past them. If nothing of the first chunk can be read the region
+/// is taken to be unreadable and not tried further; its error is returned,
+/// if there was one.
+pub fn read_region<F>(
+    reader: &mut dyn MemoryReader,
+    start: u64,
+    end: u64,
+    buffer: &mut [u8],
+    mut on_chunk: F,
+) -> io::Result<RegionRead>
+where
+    F: FnMut(u64, &[u8]) -> ControlFlow<()>,
+{
+    let mut result = RegionRead::default();
+    let mut address = start;
+
+    while address < end {
+        let want = ((end - address) as usize).min(buffer.len());
+        let chunk_end = address + want as u64;
+        let (read, error) = match reader.read_at(address, &mut buffer[..want]) {
+            Ok(n) => (n, None),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => (0, Some(e)),
+        };
+        let mut flow = ControlFlow::Continue(());
+        if read > 0 {
+            result.bytes_read += read as u64;
+            flow = on_chunk(address, &buffer[..read]);
+        }
+        if read < want && flow.is_continue() {
+            let from = address + read as u64;
+            flow = read_pages(reader, from, chunk_end, buffer, &mut on_chunk, &mut result);
+        }
+        if address == start && result.bytes_read == 0 {
+            return match error {
+                Some(e) => Err(e),
+                None => Ok(RegionRead::default()),
+            };
+        }
+        if flow.is_break() {
+            break;
+        }
+        address = chunk_end;
+    }
+
+    Ok(result)
+}
+
+/// Read `from..to` a page at a time for [`read_region`], skipping the pages
+/// that fail
+fn read_pages<F>(
+    reader: &mut dyn MemoryReader,
+    from: u64,
+    to: u64,
+    buffer: &mut [u8],
+    on_chunk: &mut F,
+    result: &mut RegionRead,
+) -> ControlFlow<()>
+where
+    F: FnMut(u64, &[u8]) -> ControlFlow<()>,
+{
+    let page = page_size() as u64;
+    let mut address = from;
+    while address < to {
+        let next = (address / page + 1) * page;
+        let want = (next.min(to) - address) as usize;
+        let read = loop {
+            match reader.read_at(address, &mut buffer[..want]) {
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                read => break read.unwrap_or(0),
+            }
+        };
+        if read > 0 {
+            result.bytes_read += read as u64;
+            on_chunk(address, &buffer[..read])?;
+        }
+        if read < want {
+            let skipped = address + read as u64..next.min(to);
+            match result.skipped.last_mut() {
+                Some(last) if last.end == skipped.start => last.end = skipped.end,
+                _ => result.skipped.push(skipped),
+            }
+        }
+        address = next;
+    }
+    ControlFlow::Continue(())
+}
+
+/// Fill `buf` with memory starting at `address`, in chunks of at most
+/// [`READ_CHUNK_SIZE`].
+///
+/// Returns the number of bytes read, which is less than `buf.len()` if the
+/// range runs into unreadable memory. An error is only returned if nothing
+/// at `address` could be read.
+pub fn read_into(reader: &mut dyn MemoryReader, address: u64, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+
+    while filled < buf.len() {
+        let want = (buf.len() - filled).min(READ_CHUNK_SIZE);
+        let read = match reader.read_at(address + filled as u64, &mut buf[filled..filled + want]) {
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) if filled == 0 => return Err(e),
+            Err(_) => break,
+        };
+        filled += read;
+
+        if read < want {
+            break;
+        }
+    }
+
+    Ok(filled)
+}
+
+/// Whether a failure means the acquisition method can't be used at all, as
+/// opposed to the region itself being unreadable
+fn is_access_unavailable(error: &io::Error) -> bool {
+    matches!(
+        error.raw_os_error(),
+        Some(libc::ENOSYS) | Some(libc::EPERM) | Some(libc::EACCES)
+    )
+}
+
+fn errno_to_io(errno: Errno) -> io::Error {
+    io::Error::from_raw_os_error(errno as i32)
+}
+
+pub fn page_size() -> usize {
+    // SAFETY: sysconf has no preconditions
+    let size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    if size > 0 {
+        size as usize
+    } else {
+        4096
+    }
+}
+
+/// Reads with process_vm_readv, copying directly between address spaces.
+///
+/// Each read is described to the kernel as page-sized remote iovecs gathered
+/// into one local buffer, so a hole in the middle of the range yields a short
+/// read up to the failing page rather than failing the whole call.
+pub struct VmReadvReader {
+    pid: i32,
+    page: usize,
+    remote: Vec<libc::iovec>,
+}
+
+impl VmReadvReader {
+    pub fn new(pid: i32) -> Self {
+        VmReadvReader {
+            pid,
+            page: page_size(),
+            remote: Vec::with_capacity(MAX_REMOTE_IOVECS),
+        }
+    }
+}
+
+// SAFETY: the iovecs are scratch space that only point at a caller's buffer
+// for the duration of a `read_at` call
+unsafe impl Send for VmReadvReader {}
+
+impl MemoryReader for VmReadvReader {
+    fn name(&self) -> &'static str {
+        "process_vm_readv"
+    }
+
+    fn read_at(&mut self, address: u64, buf: &mut [u8]) -> io::Result<usize> {
+        self.remote.clear();
+        let mut offset = 0usize;
+        while offset < buf.len() && self.remote.len() < MAX_REMOTE_IOVECS {
+            let remote_address = address as usize + offset;
+            // Align iovecs to page boundaries so partial reads stop at a page
+            let to_boundary = self.page - (remote_address % self.page);
+            let len = to_boundary.min(buf.len() - offset);
+            self.remote.push(libc::iovec {
+                iov_base: remote_address as *mut libc::c_void,
+                iov_len: len,
+            });
+            offset += len;
+        }
+
+        let local = libc::iovec {
+            iov_base: buf.as_mut_ptr() as *mut libc::c_void,
+            iov_len: offset,
+        };
+
+        // SAFETY: the local iovec covers `offset` bytes of `buf`, and the
+        // remote iovecs describe memory in the target, which the kernel validates
+        let read = unsafe {
+            libc::process_vm_readv(
+                self.pid,
+                &local,
+                1,
+                self.remote.as_ptr(),
+                self.remote.len() as libc::c_ulong,
+                0,
+            )
+        };
+
+        if read < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(read as usize)
+        }
+    }
+
+    fn try_clone(&self) -> Option<Box<dyn MemoryReader>> {
+        Some(Box::new(VmReadvReader::new(self.pid)))
+    }
+}
+
+/// Reads through seek + read on /proc/[pid]/mem
+pub struct ProcfsReader {
+    pid: i32,
+    mem: File,
+}
+
+impl ProcfsReader {
+    pub fn open(pid: i32) -> io::Result<Self> {
+        let mem = File::open(format!("/proc/{}/mem", pid))?;
+        Ok(ProcfsReader { pid, mem })
+    }
+}
+
+impl MemoryReader for ProcfsReader {
+    fn name(&self) -> &'static str {
+        "procfs"
+    }
+
+    fn read_at(&mut self, address: u64, buf: &mut [u8]) -> io::Result<usize> {
+        // File::seek is lseek64 on Linux/Android, so 64-bit addresses are preserved
+        self.mem.seek(SeekFrom::Start(address))?;
+
+        let mut filled = 0;
+        while filled < buf.len() {
+            match self.mem.read(&mut buf[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) if filled == 0 => return Err(e),
+                Err(_) => break,
+            }
+        }
+        Ok(filled)
+    }
+
+    fn try_clone(&self) -> Option<Box<dyn MemoryReader>> {
+        // Its own file, as the reads seek
+        let reader = ProcfsReader::open(self.pid).ok()?;
+        Some(Box::new(reader))
+    }
+}
+
+/// Reads with PTRACE_PEEKDATA one machine word at a time.
+///
+/// Attaching stops the target's main thread; it is resumed by
+/// PTRACE_DETACH when the reader is dropped.
+pub struct PtraceReader {
+    pid: Pid,
+}
+
+impl PtraceReader {
+    /// Attach to the target with PTRACE_ATTACH and wait for it to stop
+    pub fn attach(pid: i32) -> io::Result<Self> {
+        let pid = Pid::from_raw(pid);
+        ptrace::attach(pid).map_err(errno_to_io)?;
+        let reader = PtraceReader { pid };
+
+        loop {
+            match waitpid(pid, Some(WaitPidFlag::__WALL)) {
+                Ok(WaitStatus::Stopped(..)) => return Ok(reader),
+                Ok(_) | Err(Errno::EINTR) => continue,
+                // Dropping the reader detaches
+                Err(e) => return Err(errno_to_io(e)),
+            }
+        }
+    }
+}
+
+impl Drop for PtraceReader {
+    fn drop(&mut self) {
+        let _ = ptrace::detach(self.pid, None);
+    }
+}
+
+impl MemoryReader for PtraceReader {
+    fn name(&self) -> &'static str {
+        "ptrace"
+    }
+
+    fn read_at(&mut self, address: u64, buf: &mut [u8]) -> io::Result<usize> {
+        let pid = self.pid;
+        read_words(address, buf, |word_address| {
+            ptrace::read(pid, word_address as ptrace::AddressType).map_err(errno_to_io)
+        })
+    }
+}
+
+/// Fill `buf` from `address` one machine word at a time through `peek`.
+///
+/// The start is rounded down to a word boundary for the peeks, and only the
+/// bytes inside the requested range are copied out.
+fn read_words<P>(address: u64, buf: &mut [u8], mut peek: P) -> io::Result<usize>
+where
+    P: FnMut(u64) -> io::Result<libc::c_long>,
+{
+    const WORD: u64 = std::mem::size_of::<libc::c_long>() as u64;
+
+    let end = address + buf.len() as u64;
+    let mut filled = 0usize;
+    let mut word_address = address - address % WORD;
+
+    while word_address < end {
+        let word = match peek(word_address) {
+            Ok(word) => word.to_ne_bytes(),
+            Err(e) if filled == 0 => return Err(e),
+            Err(_) => break,
+        };
+
+        let from = address.saturating_sub(word_address) as usize;
+        let to = (end - word_address).min(WORD) as usize;
+        buf[filled..filled + (to - from)].copy_from_slice(&word[from..to]);
+        filled += to - from;
+        word_address += WORD;
+    }
+
+    Ok(filled)
+}
+
+/// Tries process_vm_readv, then /proc/[pid]/mem, then ptrace.
+///
+/// Moves on when a backend is missing (ENOSYS) or blocked by policy
+/// (EPERM/EACCES), and never moves back for the lifetime of the reader.
+pub struct AutoReader {
+    pid: i32,
+    current: Box<dyn MemoryReader>,
+    fallbacks: Vec<ReaderStrategy>,
+}
+
+impl AutoReader {
+    pub fn new(pid: i32) -> Self {
+        AutoReader {
+            pid,
+            current: Box::new(VmReadvReader::new(pid)),
+            fallbacks: vec![ReaderStrategy::Ptrace, ReaderStrategy::Procfs],
+        }
+    }
+}
+
+impl MemoryReader for AutoReader {
+    fn name(&self) -> &'static str {
+        self.current.name()
+    }
+
+    fn read_at(&mut self, address: u64, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let error = match self.current.read_at(address, buf) {
+                Err(e) if is_access_unavailable(&e) => e,
+                result => return result,
+            };
+
+            // Open the next backend, skipping any that also refuse to open
+            loop {
+                let Some(next) = self.fallbacks.pop() else {
+                    return Err(error);
+                };
+                match open_reader(self.pid, next) {
+                    Ok(reader) => {
+                        self.current = reader;
+                        break;
+                    }
+                    Err(e) if is_access_unavailable(&e) => continue,
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+    }
+
+    fn try_clone(&self) -> Option<Box<dyn MemoryReader>> {
+        // Only the tracing thread can use ptrace, so clones never fall back
+        // to it
+        let fallbacks = self
+            .fallbacks
+            .iter()
+            .copied()
+            .filter(|&strategy| strategy != ReaderStrategy::Ptrace)
+            .collect();
+        Some(Box::new(AutoReader {
+            pid: self.pid,
+            current: self.current.try_clone()?,
+            fallbacks,
+        }))
+    }
+}
+
+/// One contiguous range of a dump file mapped back to its virtual address
+struct DumpSegment {
+    start: u64,
+    end: u64,
+    file: File,
+    file_offset: u64,
+}
+
+/// Reads previously captured memory from files on disk, so the same
+/// extraction can run offline against a dump
+#[derive(Default)]
+pub struct DumpReader {
+    segments: Vec<DumpSegment>,
+}
+
+impl DumpReader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Map `length` bytes of `path`, starting at `file_offset`, to the
+    /// virtual address `start`
+    pub fn add_segment<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        start: u64,
+        file_offset: u64,
+        length: u64,
+    ) -> io::Result<()> {
+        let file = File::open(path)?;
+        self.segments.push(DumpSegment {
+            start,
+            end: start + length,
+            file,
+            file_offset,
+        });
+        self.segments.sort_by_key(|segment| segment.start);
+        Ok(())
+    }
+}
+
+impl MemoryReader for DumpReader {
+    fn name(&self) -> &'static str {
+        "dump"
+    }
+
+    fn read_at(&mut self, address: u64, buf: &mut [u8]) -> io::Result<usize> {
+        let segment = self
+            .segments
+            .iter_mut()
+            .find(|segment| segment.start <= address && address < segment.end)
+            .ok_or_else(|| io::Error::from_raw_os_error(libc::EFAULT))?;
+
+        let want = (segment.end - address).min(buf.len() as u64) as usize;
+        segment.file.seek(SeekFrom::Start(
+            segment.file_offset + (address - segment.start),
+        ))?;
+
+        let mut filled = 0;
+        while filled < want {
+            match segment.file.read(&mut buf[filled..want]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(filled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn read_all(reader: &mut dyn MemoryReader, start: u64, end: u64) -> Vec<u8> {
+        let mut read_back = Vec::new();
+        let buffer = &mut [0u8; READ_CHUNK_SIZE];
+        let bytes = read_region(reader, start, end, buffer, |address, chunk| {
+            assert_eq!(address, start + read_back.len() as u64);
+            read_back.extend_from_slice(chunk);
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+        assert_eq!(bytes.bytes_read, read_back.len() as u64);
+        assert!(bytes.skipped.is_empty());
+        read_back
+    }
+
+    #[test]
+    fn test_procfs_reader_reads_own_memory() {
+        let marker = b"native-extractor-procfs-marker".to_vec();
+        let start = marker.as_ptr() as u64;
+
+        let mut reader = ProcfsReader::open(std::process::id() as i32).unwrap();
+        assert_eq!(
+            read_all(&mut reader, start, start + marker.len() as u64),
+            marker
+        );
+    }
+
+    #[test]
+    fn test_auto_reader_reads_across_pages() {
+        // Large enough to span several pages and more than one chunk
+        let data: Vec<u8> = (0..READ_CHUNK_SIZE * 2 + 123).map(|i| i as u8).collect();
+        let start = data.as_ptr() as u64;
+
+        let mut reader = AutoReader::new(std::process::id() as i32);
+        assert_eq!(
+            read_all(&mut reader, start, start + data.len() as u64),
+            data
+        );
+    }
+
+    #[test]
+    fn test_read_region_skips_unreadable_pages() {
+        let page = page_size();
+        // SAFETY: a fresh private mapping, unmapped below
+        let mapped = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                3 * page,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        assert_ne!(mapped, libc::MAP_FAILED);
+        let start = mapped as u64;
+        // SAFETY: the pages lie within the mapping
+        unsafe {
+            std::ptr::write_bytes(mapped.cast::<u8>(), 0xaa, 3 * page);
+            libc::mprotect(mapped.cast::<u8>().add(page).cast(), page, libc::PROT_NONE);
+        }
+
+        let mut reader = VmReadvReader::new(std::process::id() as i32);
+        let mut chunks = Vec::new();
+        let buffer = &mut [0u8; READ_CHUNK_SIZE];
+        let end = start + 3 * page as u64;
+        let read = read_region(&mut reader, start, end, buffer, |address, chunk| {
+            assert!(chunk.iter().all(|&byte| byte == 0xaa));
+            chunks.push((address, chunk.len()));
+            ControlFlow::Continue(())
+        });
+        // SAFETY: the mapping made above, no longer used
+        unsafe { libc::munmap(mapped, 3 * page) };
+
+        let page = page as u64;
+        let read = read.unwrap();
+        assert_eq!(read.bytes_read, 2 * page);
+        assert_eq!(read.skipped.len(), 1);
+        assert_eq!(read.skipped[0], start + page..start + 2 * page);
+        assert_eq!(chunks.last(), Some(&(start + 2 * page, page as usize)));
+    }
+
+    #[test]
+    fn test_buffer_pool_reuses_buffers_of_the_same_size() {
+        let pool = BufferPool::new();
+        let first = pool.take(READ_CHUNK_SIZE).as_ptr();
+        assert_eq!(pool.len(), 1);
+        let again = pool.take(READ_CHUNK_SIZE);
+        assert_eq!(again.as_ptr(), first);
+        assert!(pool.is_empty());
+
+        let other = pool.take(MIN_CHUNK_SIZE);
+        assert_eq!(other.len(), MIN_CHUNK_SIZE);
+        drop((again, other));
+        assert_eq!(pool.len(), 2);
+        assert_eq!(PooledBuffer::unpooled(8).len(), 8);
+
+        // A panic while the pool is locked leaves it usable
+        let panicked = std::thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    let _free = pool.free.lock().unwrap();
+                    panic!("scan panicked");
+                })
+                .join()
+        });
+        assert!(panicked.is_err() && pool.free.is_poisoned());
+        drop(pool.take(MIN_CHUNK_SIZE));
+        assert_eq!(pool.len(), 2);
+    }
+
+    #[test]
+    fn test_read_words_handles_unaligned_range() {
+        let data: Vec<u8> = (0..100u8).collect();
+        let peek = |address: u64| -> io::Result<libc::c_long> {
+            let mut bytes = [0u8; std::mem::size_of::<libc::c_long>()];
+            for (i, byte) in bytes.iter_mut().enumerate() {
+                *byte = data.get(address as usize + i).copied().unwrap_or(0);
+            }
+            Ok(libc::c_long::from_ne_bytes(bytes))
+        };
+
+        let mut buf = [0u8; 37];
+        let filled = read_words(3, &mut buf, peek).unwrap();
+
+        assert_eq!(filled, buf.len());
+        assert_eq!(&buf[..], &data[3..40]);
+    }
+
+    #[test]
+    fn test_dump_reader_maps_file_offsets() {
+        let path =
+            std::env::temp_dir().join(format!("native-extractor-dump-{}", std::process::id()));
+        File::create(&path)
+            .unwrap()
+            .write_all(b"headerHELLO WORLD")
+            .unwrap();
+
+        let mut reader = DumpReader::new();
+        reader.add_segment(&path, 0x1000, 6, 11).unwrap();
+
+        assert_eq!(read_all(&mut reader, 0x1000, 0x100b), b"HELLO WORLD");
+        let mut buf = [0u8; 4];
+        assert!(reader.read_at(0x2000, &mut buf).is_err());
+
+        // A range running past the segment is read up to its end
+        let mut buf = [0u8; 16];
+        assert_eq!(read_into(&mut reader, 0x1006, &mut buf).unwrap(), 5);
+        assert_eq!(&buf[..5], b"WORLD");
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,365 @@
+//! Carving of Realm database files out of process memory
+//!
+//! Realm maps its files whole, and keeps in-memory realms in memfd
+//! objects, so an open database is a file image in the memory of the app.
+//! A Realm file starts with a 24-byte header: two 64-bit refs to top
+//! arrays, the `T-DB` mnemonic, a file format version for each ref, and
+//! flags whose low bit picks the current one. Refs are offsets from the
+//! start of the file, and the current top array holds the file's logical
+//! size and a ref to the names of its tables.
+//!
+//! Every array starts with an 8-byte header: `AAAA`, flags giving its
+//! element width and whether the elements are refs, and a 24-bit
+//! big-endian element count. Headers are searched for at 8-byte
+//! alignment, and each file whose top array holds together is read to its
+//! logical size and written out.
+
+use crate::dump::DumpedFile;
+use crate::maps::MemoryRegion;
+use crate::reader::{self, MemoryReader};
+use crate::scan::{self, RegionVisitor, ScanControl, ScanSink, StopReason};
+use crate::sha256::Sha256;
+use serde::Serialize;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// Size of the header of every Realm file
+pub const HEADER_SIZE: usize = 24;
+
+const MNEMONIC: &[u8; 4] = b"T-DB";
+const ARRAY_MAGIC: &[u8; 4] = b"AAAA";
+const ARRAY_HEADER_SIZE: usize = 8;
+const ALIGN: usize = 8;
+
+/// File format versions, from Realm Core 0.x on
+const FORMAT_VERSIONS: std::ops::RangeInclusive<u8> = 2..=30;
+
+/// Largest Realm file carved
+const MAX_FILE_SIZE: u64 = 1 << 30;
+
+/// Most table names read from a file
+const MAX_TABLES: usize = 1024;
+
+/// The fields of a Realm file header that [`parse_header`] checks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RealmHeader {
+    /// Offset of the current top array
+    pub top_ref: u64,
+    pub format_version: u8,
+}
+
+/// A Realm file found in memory and written to disk
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CarvedRealm {
+    #[serde(flatten)]
+    pub file: DumpedFile,
+    pub format_version: u8,
+    /// Size the file should have; `size` is less if memory ran out first
+    pub logical_size: u64,
+    /// The tables, as Realm names them: `class_` and the model class
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tables: Vec<String>,
+}
+
+/// Parse the Realm header at the start of `bytes`, if it is one: the
+/// mnemonic, a current format version Realm has used, and a current top
+/// ref that is 8-byte aligned and past the header
+pub fn parse_header(bytes: &[u8]) -> Option<RealmHeader> {
+    let header = bytes.get(..HEADER_SIZE)?;
+    let flags = header[23];
+    if &header[16..20] != MNEMONIC || flags > 1 {
+        return None;
+    }
+    let current = usize::from(flags);
+    let top_ref = u64::from_le_bytes(header[current * 8..current * 8 + 8].try_into().unwrap());
+    let format_version = header[20 + current];
+    let valid = FORMAT_VERSIONS.contains(&format_version)
+        && top_ref >= HEADER_SIZE as u64
+        && top_ref < MAX_FILE_SIZE
+        && top_ref.is_multiple_of(ALIGN as u64);
+    valid.then_some(RealmHeader {
+        top_ref,
+        format_version,
+    })
+}
+
+/// The header fields of an array
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ArrayHeader {
+    has_refs: bool,
+    /// Element width, in bits for integer arrays or bytes for strings
+    width: usize,
+    size: usize,
+}
+
+fn parse_array_header(bytes: &[u8]) -> Option<ArrayHeader> {
+    let header = bytes.get(..ARRAY_HEADER_SIZE)?;
+    if &header[..4] != ARRAY_MAGIC {
+        return None;
+    }
+    let flags = header[4];
+    Some(ArrayHeader {
+        has_refs: flags & 0x40 != 0,
+        width: (1 << (flags & 7)) >> 1,
+        size: usize::from(header[5]) << 16 | usize::from(header[6]) << 8 | usize::from(header[7]),
+    })
+}
+
+/// Element `index` of an integer array whose elements are `data`
+fn element(header: &ArrayHeader, data: &[u8], index: usize) -> Option<u64> {
+    let bit = index * header.width;
+    let bytes = data.get(bit / 8..(bit + header.width).div_ceil(8))?;
+    let value = bytes.iter().rev().fold(0u64, |n, &b| n << 8 | u64::from(b));
+    let mask = if header.width == 64 { u64::MAX } else { (1 << header.width) - 1 };
+    Some(value >> (bit % 8) & mask)
+}
+
+/// Read the array at `address`: its header, and as many bytes of
+/// elements as `bytes_per_element` asks for given the header
+fn read_array(
+    reader: &mut dyn MemoryReader,
+    address: u64,
+    bytes_per_element: impl Fn(&ArrayHeader) -> usize,
+) -> Option<(ArrayHeader, Vec<u8>)> {
+    let mut header = [0u8; ARRAY_HEADER_SIZE];
+    if reader::read_into(reader, address, &mut header).ok()? < ARRAY_HEADER_SIZE {
+        return None;
+    }
+    let header = parse_array_header(&header)?;
+    let mut data = vec![0u8; bytes_per_element(&header)];
+    let read = reader::read_into(reader, address + ARRAY_HEADER_SIZE as u64, &mut data).ok()?;
+    (read == data.len()).then_some((header, data))
+}
+
+/// The logical size and table names in the top array at `top`, a ref
+/// from `start`
+fn read_top(reader: &mut dyn MemoryReader, start: u64, top: u64) -> Option<(u64, Vec<String>)> {
+    let (header, data) = read_array(reader, start + top, |h| (3 * h.width).div_ceil(8))?;
+    if !header.has_refs || header.size < 3 || header.width < 8 {
+        return None;
+    }
+    // Integers in arrays of refs are tagged by their low bit
+    let size = element(&header, &data, 2).filter(|size| size & 1 == 1)? >> 1;
+    if size <= top || size > MAX_FILE_SIZE {
+        return None;
+    }
+    let names = element(&header, &data, 0)
+        .filter(|&names| names > 0 && names < size && names.is_multiple_of(ALIGN as u64))
+        .and_then(|names| read_names(reader, start + names))
+        .unwrap_or_default();
+    Some((size, names))
+}
+
+/// The strings of the short string array at `address`: slots of `width`
+/// bytes, each padded with zeros, the last byte counting the padding
+fn read_names(reader: &mut dyn MemoryReader, address: u64) -> Option<Vec<String>> {
+    let (header, data) = read_array(reader, address, |h| h.width * h.size.min(MAX_TABLES))?;
+    if header.has_refs {
+        return None;
+    }
+    if header.width == 0 {
+        return Some(Vec::new());
+    }
+    data.chunks_exact(header.width)
+        .map(|slot| {
+            let length = header.width.checked_sub(1 + usize::from(slot[header.width - 1]))?;
+            Some(String::from_utf8_lossy(&slot[..length]).into_owned())
+        })
+        .collect()
+}
+
+/// File a Realm file found at `address` is written to
+pub fn realm_file_name(address: u64) -> String {
+    format!("{:08x}.realm", address)
+}
+
+/// Search `regions` for Realm files and write each to its own file in
+/// `dir`, delivering a [`CarvedRealm`] for each.
+///
+/// A file is read to its logical size even past the end of its region,
+/// since Realm maps large files in sections, and cut short where memory
+/// can't be read. Failing to write to `dir` is an error; files written
+/// before the failure are left in place.
+pub fn carve_realm_files(
+    reader: &mut dyn MemoryReader,
+    regions: &[MemoryRegion],
+    dir: &Path,
+    sink: &mut dyn ScanSink<CarvedRealm>,
+    control: &mut ScanControl,
+) -> io::Result<Option<StopReason>> {
+    let mut visitor = RealmVisitor {
+        dir,
+        found: Vec::new(),
+        failed: None,
+    };
+    let stopped = scan::walk_regions(reader, regions, &mut visitor, sink, control);
+    match visitor.failed {
+        Some(e) => Err(e),
+        None => Ok(stopped),
+    }
+}
+
+struct RealmVisitor<'a> {
+    dir: &'a Path,
+    /// Addresses of the headers found in the region being read
+    found: Vec<u64>,
+    /// The first write failure; nothing more is written after one
+    failed: Option<io::Error>,
+}
+
+impl RealmVisitor<'_> {
+    /// Read the Realm file at `address`, if its header and top array hold
+    /// together, and write it out
+    fn carve(
+        &self,
+        reader: &mut dyn MemoryReader,
+        address: u64,
+    ) -> io::Result<Option<CarvedRealm>> {
+        let mut header = [0u8; HEADER_SIZE];
+        if reader::read_into(reader, address, &mut header).unwrap_or(0) < HEADER_SIZE {
+            return Ok(None);
+        }
+        let Some(header) = parse_header(&header) else {
+            return Ok(None);
+        };
+        let Some((logical_size, tables)) = read_top(reader, address, header.top_ref) else {
+            return Ok(None);
+        };
+
+        let name = realm_file_name(address);
+        let mut out = BufWriter::new(File::create(self.dir.join(&name))?);
+        let mut hash = Sha256::new();
+        let mut buf = vec![0u8; reader::READ_CHUNK_SIZE];
+        let mut size = 0;
+        while size < logical_size {
+            let want = (logical_size - size).min(buf.len() as u64) as usize;
+            let read = reader::read_into(reader, address + size, &mut buf[..want]).unwrap_or(0);
+            out.write_all(&buf[..read])?;
+            hash.update(&buf[..read]);
+            size += read as u64;
+            if read < want {
+                break;
+            }
+        }
+        out.flush()?;
+        Ok(Some(CarvedRealm {
+            file: DumpedFile {
+                start: address,
+                file: name,
+                size,
+                sha256: hash.finish_hex(),
+            },
+            format_version: header.format_version,
+            logical_size,
+            tables,
+        }))
+    }
+}
+
+impl RegionVisitor for RealmVisitor<'_> {
+    type Output = CarvedRealm;
+
+    fn visit_chunk(&mut self, _region: &MemoryRegion, address: u64, chunk: &[u8]) {
+        // Chunks start page-aligned, so no aligned header spans two of them
+        let skew = (address % ALIGN as u64) as usize;
+        let first = (ALIGN - skew) % ALIGN;
+        let Some(chunk) = chunk.get(first..) else {
+            return;
+        };
+        for (i, header) in chunk.chunks_exact(ALIGN).enumerate() {
+            // The mnemonic is the third word of the header
+            if i >= 2 && &header[..4] == MNEMONIC {
+                self.found.push(address + (first + (i - 2) * ALIGN) as u64);
+            }
+        }
+    }
+
+    fn end_region(
+        &mut self,
+        _region: &MemoryRegion,
+        reader: &mut dyn MemoryReader,
+        sink: &mut dyn ScanSink<CarvedRealm>,
+    ) {
+        for address in std::mem::take(&mut self.found) {
+            if self.failed.is_some() {
+                return;
+            }
+            match self.carve(reader, address) {
+                Ok(Some(realm)) => sink.result(realm),
+                Ok(None) => {}
+                Err(e) => self.failed = Some(e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps::parse_maps_line;
+    use crate::reader::ProcfsReader;
+    use crate::scan::Scan;
+    use std::fs;
+
+    /// A Realm file of `size` bytes with two tables, its top array at 0x40
+    fn realm_file(size: usize) -> Vec<u8> {
+        let mut realm = vec![0u8; size];
+        realm[8..16].copy_from_slice(&0x40u64.to_le_bytes());
+        realm[16..24].copy_from_slice(b"T-DB\x09\x16\x00\x01");
+        // Top array: refs, 64-bit elements, 3 of them
+        realm[0x40..0x48].copy_from_slice(b"AAAA\x47\x00\x00\x03");
+        realm[0x48..0x50].copy_from_slice(&0x80u64.to_le_bytes());
+        realm[0x58..0x60].copy_from_slice(&((size as u64) << 1 | 1).to_le_bytes());
+        // Table names: 16-byte strings, 2 of them
+        realm[0x80..0x88].copy_from_slice(b"AAAA\x0d\x00\x00\x02");
+        realm[0x88..0x98].copy_from_slice(b"class_Message\0\0\x02");
+        realm[0x98..0xa8].copy_from_slice(b"class_User\0\0\0\0\0\x05");
+        realm
+    }
+
+    #[test]
+    fn test_parse_header() {
+        let realm = realm_file(0x100);
+        assert_eq!(
+            parse_header(&realm),
+            Some(RealmHeader {
+                top_ref: 0x40,
+                format_version: 0x16,
+            })
+        );
+        let mut other = realm.clone();
+        other[23] = 0;
+        assert_eq!(parse_header(&other), None);
+    }
+
+    #[test]
+    fn test_carves_realm_files() {
+        let realm = realm_file(0x1000);
+        let size = 0x3000;
+        let mut data = vec![0u8; size + ALIGN];
+        let start = (data.as_ptr() as u64).next_multiple_of(ALIGN as u64);
+        let skew = (start - data.as_ptr() as u64) as usize;
+        data[skew + 0x800..skew + 0x800 + realm.len()].copy_from_slice(&realm);
+        let end = start + size as u64;
+        let line = format!("{:x}-{:x} rw-s 00000000 00:01 7 /memfd:realm (deleted)", start, end);
+        let region = parse_maps_line(&line).unwrap();
+
+        let pid = std::process::id() as i32;
+        let dir = std::env::temp_dir().join(format!("realm-test-{}", pid));
+        fs::create_dir_all(&dir).unwrap();
+        let mut reader = ProcfsReader::open(pid).unwrap();
+        let mut scan = Scan::default();
+        let stopped =
+            carve_realm_files(&mut reader, &[region], &dir, &mut scan, &mut ScanControl::new());
+
+        assert_eq!(stopped.unwrap(), None);
+        assert_eq!(scan.results.len(), 1);
+        let carved = &scan.results[0];
+        assert_eq!((carved.file.start, carved.file.size), (start + 0x800, 0x1000));
+        assert_eq!(carved.tables, ["class_Message", "class_User"]);
+        assert_eq!(fs::read(dir.join(&carved.file.file)).unwrap(), realm);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,225 @@
+//! Register snapshots of a stopped target's threads
+//!
+//! PTRACE_GETREGSET with `NT_PRSTATUS` gives a thread's general-purpose
+//! registers laid out as the kernel's `elf_gregset_t` for the thread's ABI,
+//! which for a 32-bit app under a 64-bit kernel is the 32-bit layout. Every
+//! ABI's layout has a size of its own, so the layout is told from the size,
+//! and an arm32 app reads correctly from either build of this library.
+
+use crate::coredump::{self, StoppedThreads};
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+/// The instruction set a thread runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Arch {
+    #[serde(rename = "arm64")]
+    Arm64,
+    #[serde(rename = "arm")]
+    Arm,
+    #[serde(rename = "x86_64")]
+    X86_64,
+    #[serde(rename = "x86")]
+    X86,
+    #[serde(rename = "riscv64")]
+    Riscv64,
+}
+
+impl Arch {
+    pub fn name(self) -> &'static str {
+        match self {
+            Arch::Arm64 => "arm64",
+            Arch::Arm => "arm",
+            Arch::X86_64 => "x86_64",
+            Arch::X86 => "x86",
+            Arch::Riscv64 => "riscv64",
+        }
+    }
+
+    /// The instruction set of an ELF file's `e_machine`
+    pub fn from_machine(machine: u16) -> Option<Self> {
+        match machine {
+            183 => Some(Arch::Arm64),
+            40 => Some(Arch::Arm),
+            62 => Some(Arch::X86_64),
+            3 => Some(Arch::X86),
+            243 => Some(Arch::Riscv64),
+            _ => None,
+        }
+    }
+}
+
+/// How one ABI lays out its `elf_gregset_t`
+struct Layout {
+    arch: Arch,
+    /// Bytes per register
+    word: usize,
+    names: &'static [&'static str],
+    /// Indexes of the program counter and stack pointer
+    pc: usize,
+    sp: usize,
+}
+
+const LAYOUTS: [Layout; 5] = [
+    // struct user_pt_regs
+    Layout {
+        arch: Arch::Arm64,
+        word: 8,
+        names: &[
+            "x0", "x1", "x2", "x3", "x4", "x5", "x6", "x7", "x8", "x9", "x10", "x11", "x12",
+            "x13", "x14", "x15", "x16", "x17", "x18", "x19", "x20", "x21", "x22", "x23", "x24",
+            "x25", "x26", "x27", "x28", "x29", "x30", "sp", "pc", "pstate",
+        ],
+        pc: 32,
+        sp: 31,
+    },
+    // struct pt_regs
+    Layout {
+        arch: Arch::Arm,
+        word: 4,
+        names: &[
+            "r0", "r1", "r2", "r3", "r4", "r5", "r6", "r7", "r8", "r9", "r10", "r11", "r12", "sp",
+            "lr", "pc", "cpsr", "orig_r0",
+        ],
+        pc: 15,
+        sp: 13,
+    },
+    // struct user_regs_struct
+    Layout {
+        arch: Arch::X86_64,
+        word: 8,
+        names: &[
+            "r15", "r14", "r13", "r12", "rbp", "rbx", "r11", "r10", "r9", "r8", "rax", "rcx",
+            "rdx", "rsi", "rdi", "orig_rax", "rip", "cs", "eflags", "rsp", "ss", "fs_base",
+            "gs_base", "ds", "es", "fs", "gs",
+        ],
+        pc: 16,
+        sp: 19,
+    },
+    // struct user_regs_struct, 32-bit
+    Layout {
+        arch: Arch::X86,
+        word: 4,
+        names: &[
+            "ebx", "ecx", "edx", "esi", "edi", "ebp", "eax", "ds", "es", "fs", "gs", "orig_eax",
+            "eip", "cs", "eflags", "esp", "ss",
+        ],
+        pc: 12,
+        sp: 15,
+    },
+    // struct user_regs_struct
+    Layout {
+        arch: Arch::Riscv64,
+        word: 8,
+        names: &[
+            "pc", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0", "a1", "a2", "a3",
+            "a4", "a5", "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11",
+            "t3", "t4", "t5", "t6",
+        ],
+        pc: 0,
+        sp: 2,
+    },
+];
+
+/// A register and what it held
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Register {
+    pub name: &'static str,
+    pub value: u64,
+}
+
+/// The registers of a thread, as it was stopped
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThreadState {
+    pub tid: i32,
+    /// Thread name, from /proc/[pid]/task/[tid]/comm
+    pub name: String,
+    pub arch: Arch,
+    pub pc: u64,
+    pub sp: u64,
+    /// Every general-purpose register, in the kernel's order
+    pub registers: Vec<Register>,
+}
+
+impl ThreadState {
+    /// Read the registers out of a `regset` in the layout its size is of
+    pub fn decode(tid: i32, name: String, regset: &[u8]) -> Option<Self> {
+        let layout =
+            LAYOUTS.iter().find(|layout| layout.names.len() * layout.word == regset.len())?;
+        let registers: Vec<Register> = regset
+            .chunks_exact(layout.word)
+            .zip(layout.names)
+            .map(|(bytes, &name)| {
+                let mut word = [0u8; 8];
+                word[..bytes.len()].copy_from_slice(bytes);
+                Register {
+                    name,
+                    value: u64::from_le_bytes(word),
+                }
+            })
+            .collect();
+        Some(ThreadState {
+            tid,
+            name,
+            arch: layout.arch,
+            pc: registers[layout.pc].value,
+            sp: registers[layout.sp].value,
+            registers,
+        })
+    }
+}
+
+/// The registers of every thread of `pid` held in `stopped`; threads whose
+/// registers can't be read are listed in `errors` and left out
+pub fn read_threads(
+    pid: i32,
+    stopped: &StoppedThreads,
+    errors: &mut Vec<String>,
+) -> Vec<ThreadState> {
+    let mut threads = Vec::new();
+    for &tid in stopped.tids() {
+        let regset = match coredump::read_regset(tid) {
+            Ok(regset) => regset,
+            Err(e) => {
+                errors.push(format!("Failed to read registers of {}: {}", tid, e));
+                continue;
+            }
+        };
+        let name = fs::read_to_string(format!("/proc/{}/task/{}/comm", pid, tid))
+            .map(|name| name.trim_end().to_string())
+            .unwrap_or_default();
+        match ThreadState::decode(tid, name, &regset) {
+            Some(state) => threads.push(state),
+            None => errors.push(format!(
+                "Registers of {} are in an unknown layout of {} bytes",
+                tid,
+                regset.len()
+            )),
+        }
+    }
+    threads
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_layouts() {
+        // An arm32 thread under a 64-bit kernel
+        let mut regset: Vec<u8> = (0..18u32).flat_map(|i| (0x1000 + i).to_le_bytes()).collect();
+        let arm = ThreadState::decode(7, "main".into(), &regset).unwrap();
+        assert_eq!(arm.arch, Arch::Arm);
+        assert_eq!((arm.pc, arm.sp), (0x100f, 0x100d));
+        assert_eq!(arm.registers[14], Register { name: "lr", value: 0x100e });
+
+        regset = (0..34u64).flat_map(|i| (0x7f00_0000_0000 + i).to_le_bytes()).collect();
+        let arm64 = ThreadState::decode(7, "main".into(), &regset).unwrap();
+        assert_eq!(arm64.arch, Arch::Arm64);
+        assert_eq!((arm64.pc, arm64.sp), (0x7f00_0000_0020, 0x7f00_0000_001f));
+        assert_eq!(arm64.registers.len(), 34);
+
+        assert_eq!(ThreadState::decode(7, "main".into(), &[0; 12]), None);
+    }
+}
@@ -0,0 +1,502 @@
+//! Structured results returned over JNI
+//!
+//! Every JNI call builds a [`Report`] and renders it in the selected
+//! [`OutputFormat`]: the original human-readable text, or JSON that the
+//! Android side can deserialize.
+
+use crate::aes::FoundAesKey;
+use crate::art::JavaClass;
+use crate::base64::Base64Blob;
+use crate::bitmap::RecoveredBitmap;
+use crate::carve::CarvedDocument;
+use crate::compressed::CompressedStream;
+use crate::deobfuscate::DeobfuscatedString;
+use crate::dex::CarvedDex;
+use crate::diff::SnapshotDiff;
+#[cfg(feature = "capstone")]
+use crate::disasm::Disassembly;
+use crate::dump::DumpedFile;
+use crate::entropy::EntropyRun;
+use crate::image::CarvedImage;
+use crate::instrumentation::Instrumentation;
+use crate::integrity::{TextCheck, UnbackedCode};
+use crate::keys::FoundPrivateKey;
+use crate::packer::PackerDetection;
+use crate::pointers::{PointerMapInfo, PointerPath, ResolvedChain};
+use crate::process::ProcessEntry;
+use crate::protobuf::ProtoMessage;
+use crate::realm::CarvedRealm;
+use crate::registers::ThreadState;
+use crate::scan::{ExtractedString, Match, RegionResult, Scan, StopReason};
+use crate::secrets::Finding;
+use crate::shmem::SharedMemory;
+use crate::spill::Spill;
+use crate::sqlite::CarvedDatabase;
+use crate::threads::ThreadStack;
+use crate::throttle::Throttle;
+use crate::tls::TlsSecret;
+use crate::unwind::Backtrace;
+use crate::urls::{FoundUrl, UrlHost};
+use crate::value::{SnapshotInfo, ValueScan};
+use crate::x509::CarvedCertificate;
+#[cfg(feature = "yara")]
+use crate::yara::YaraMatch;
+use crate::zip::CarvedArchive;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+/// How JNI results are rendered, the `outputFormat` of the scan options
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OutputFormat {
+    /// Free-form text meant for display
+    #[default]
+    Text,
+    /// A [`Report`] serialized as a single JSON object
+    Json,
+}
+
+/// Identity of the target process
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessInfo {
+    pub cmdline: String,
+    pub environment: Vec<String>,
+}
+
+/// Totals over a whole scan
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Stats {
+    pub regions_scanned: usize,
+    pub regions_unreadable: usize,
+    /// Regions read only in part, around pages that couldn't be read
+    pub regions_partial: usize,
+    /// Total size of the regions scanned, the address space the scan
+    /// covered
+    pub bytes_mapped: u64,
+    pub bytes_read: u64,
+    /// Bytes in pages of partly read regions that couldn't be read
+    pub bytes_skipped: u64,
+    pub results: usize,
+    pub elapsed_ms: u64,
+    /// Time spent reading and scanning regions, summed over threads; the
+    /// rest of `elapsedMs` went to setup such as reading maps or stopping
+    /// the target
+    pub read_ms: u64,
+    /// `bytesRead` over `elapsedMs`
+    pub bytes_per_second: u64,
+    #[serde(skip)]
+    read_us: u64,
+    /// Strings dropped by `approximateDedupe` as probably seen before
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duplicates_dropped: Option<u64>,
+    /// Regions a checkpointed scan took from its checkpoint rather than
+    /// reading them again
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resumed_regions: Option<usize>,
+    /// Time the scan was paused by its throttle, summed over threads
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub throttled_ms: Option<u64>,
+    /// Hottest thermal zone the throttle saw, in °C
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_temperature: Option<f64>,
+    /// Name of the [`crate::reader::MemoryReader`] backend used
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reader: Option<&'static str>,
+    /// Set when the scan stopped early, so the results are partial
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub truncated: Option<StopReason>,
+}
+
+impl Stats {
+    /// Add the totals of another scan, for a batch covering several. The
+    /// reader and truncation are those of the first scan to set them.
+    pub fn add(&mut self, other: &Stats) {
+        self.regions_scanned += other.regions_scanned;
+        self.regions_unreadable += other.regions_unreadable;
+        self.regions_partial += other.regions_partial;
+        self.bytes_mapped += other.bytes_mapped;
+        self.bytes_read += other.bytes_read;
+        self.bytes_skipped += other.bytes_skipped;
+        self.results += other.results;
+        self.read_us += other.read_us;
+        if let Some(dropped) = other.duplicates_dropped {
+            *self.duplicates_dropped.get_or_insert(0) += dropped;
+        }
+        if let Some(resumed) = other.resumed_regions {
+            *self.resumed_regions.get_or_insert(0) += resumed;
+        }
+        if let Some(throttled) = other.throttled_ms {
+            *self.throttled_ms.get_or_insert(0) += throttled;
+        }
+        self.max_temperature = hotter(self.max_temperature, other.max_temperature);
+        self.reader = self.reader.or(other.reader);
+        self.truncated = self.truncated.or(other.truncated);
+    }
+
+    /// Count a region that has been read
+    pub fn add_region(&mut self, region: &RegionResult) {
+        self.regions_scanned += 1;
+        if region.error.is_some() {
+            self.regions_unreadable += 1;
+        }
+        if !region.skipped.is_empty() {
+            self.regions_partial += 1;
+        }
+        self.bytes_mapped += region.region.size();
+        self.bytes_read += region.bytes_read;
+        let skipped: u64 = region.skipped.iter().map(|range| range.end - range.start).sum();
+        self.bytes_skipped += skipped;
+        self.read_us += region.elapsed_us;
+    }
+
+    /// Record what `throttle` did to the scan
+    pub fn add_throttle(&mut self, throttle: &Throttle) {
+        *self.throttled_ms.get_or_insert(0) += throttle.paused().as_millis() as u64;
+        self.max_temperature = hotter(self.max_temperature, throttle.hottest());
+    }
+
+    /// Record how long the scan took, from start to finish
+    pub fn set_elapsed(&mut self, elapsed: Duration) {
+        self.elapsed_ms = elapsed.as_millis() as u64;
+        self.read_ms = self.read_us / 1000;
+        let micros = elapsed.as_micros().max(1);
+        self.bytes_per_second = (u128::from(self.bytes_read) * 1_000_000 / micros) as u64;
+    }
+}
+
+fn hotter(a: Option<f64>, b: Option<f64>) -> Option<f64> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.max(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Everything a JNI call produced
+///
+/// Fields that don't apply to a call are omitted from the JSON: `strings`
+/// only appears for string extraction, `matches` and `patterns` only for
+/// searches.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Report {
+    pub pid: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub process: Option<ProcessInfo>,
+    /// Every process covered, for scans of a whole app; their results carry
+    /// a `pid`, and `pid` above is the first of them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub processes: Option<Vec<ProcessEntry>>,
+    /// The threads whose stacks were scanned, for stack-only scans, or whose
+    /// registers were saved, for core dumps
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub threads: Option<Vec<ThreadStack>>,
+    pub regions: Vec<RegionResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strings: Option<Vec<ExtractedString>>,
+    /// The file holding the results that didn't fit in `maxResultMemoryMb`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spill: Option<Spill>,
+    /// Runs of random-looking bytes, for entropy scans
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entropy_runs: Option<Vec<EntropyRun>>,
+    /// What the secret detectors found
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub findings: Option<Vec<Finding>>,
+    /// Base64 runs and what they decode to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base64_blobs: Option<Vec<Base64Blob>>,
+    /// Compressed streams inflated, with what they hold
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compressed_streams: Option<Vec<CompressedStream>>,
+    /// Strings hidden by single-byte XOR or rotation, decoded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deobfuscated: Option<Vec<DeobfuscatedString>>,
+    /// Documents carved whole out of memory
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub documents: Option<Vec<CarvedDocument>>,
+    /// Protobuf messages, decoded without a schema
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protobuf_messages: Option<Vec<ProtoMessage>>,
+    /// Distinct URLs, and the hosts they point at, those seen most first
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub urls: Option<Vec<FoundUrl>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hosts: Option<Vec<UrlHost>>,
+    /// The YARA rules that matched, per region
+    #[cfg(feature = "yara")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub yara_matches: Option<Vec<YaraMatch>>,
+    /// The instructions of a range, and the functions they were guessed to
+    /// make up
+    #[cfg(feature = "capstone")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disassembly: Option<Disassembly>,
+    /// The regex or keywords searched for; [`Match::pattern`] indexes this
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub patterns: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub matches: Option<Vec<Match>>,
+    /// The files written, for dumps to disk
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub files: Option<Vec<DumpedFile>>,
+    /// The DEX files carved out of memory
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dex_files: Option<Vec<CarvedDex>>,
+    /// The Realm files carved out of memory
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub realm_files: Option<Vec<CarvedRealm>>,
+    /// The SQLite databases rebuilt from memory
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub databases: Option<Vec<CarvedDatabase>>,
+    /// The distinct certificates carved out of memory
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub certificates: Option<Vec<CarvedCertificate>>,
+    /// The distinct images carved out of memory
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub images: Option<Vec<CarvedImage>>,
+    /// The distinct bitmaps recovered from memory
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bitmaps: Option<Vec<RecoveredBitmap>>,
+    /// The distinct archives carved out of memory
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub archives: Option<Vec<CarvedArchive>>,
+    /// The distinct private keys found in memory
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub private_keys: Option<Vec<FoundPrivateKey>>,
+    /// The distinct AES keys recovered from their schedules
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aes_keys: Option<Vec<FoundAesKey>>,
+    /// The distinct TLS secrets found in memory
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls_secrets: Option<Vec<TlsSecret>>,
+    /// Executable regions not backed by their file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unbacked_code: Option<Vec<UnbackedCode>>,
+    /// The memfd and ashmem objects of the process
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shared_memory: Option<Vec<SharedMemory>>,
+    /// Executable mappings of files compared with their files
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub libraries: Option<Vec<TextCheck>>,
+    /// Instrumentation frameworks found attached to the process
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instrumentation: Option<Instrumentation>,
+    /// The packers identified in the process
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub packers: Option<PackerDetection>,
+    /// The classes loaded in a Java runtime
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub classes: Option<Vec<JavaClass>>,
+    /// The addresses holding the value searched for
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub values: Option<ValueScan>,
+    /// What a snapshot for a value search saved
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value_snapshot: Option<SnapshotInfo>,
+    /// What changed since the session's last snapshot
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff: Option<SnapshotDiff>,
+    /// What the pointer map built holds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pointer_map: Option<PointerMapInfo>,
+    /// Pointer paths from modules to an address
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pointer_paths: Option<Vec<PointerPath>>,
+    /// A pointer chain, followed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pointer_chain: Option<ResolvedChain>,
+    /// The registers of each thread, as it was stopped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub registers: Option<Vec<ThreadState>>,
+    /// The backtrace of each thread, as it was stopped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backtraces: Option<Vec<Backtrace>>,
+    /// Failures that stopped the call or left part of the report empty
+    pub errors: Vec<String>,
+    pub stats: Stats,
+    #[serde(skip)]
+    started: Option<Instant>,
+}
+
+impl Report {
+    /// Start a report for `pid`, timing from now
+    pub fn new(pid: i32) -> Self {
+        Report {
+            pid,
+            process: None,
+            processes: None,
+            threads: None,
+            regions: Vec::new(),
+            strings: None,
+            spill: None,
+            entropy_runs: None,
+            findings: None,
+            base64_blobs: None,
+            compressed_streams: None,
+            deobfuscated: None,
+            documents: None,
+            protobuf_messages: None,
+            urls: None,
+            hosts: None,
+            #[cfg(feature = "yara")]
+            yara_matches: None,
+            #[cfg(feature = "capstone")]
+            disassembly: None,
+            patterns: None,
+            matches: None,
+            files: None,
+            dex_files: None,
+            realm_files: None,
+            databases: None,
+            certificates: None,
+            images: None,
+            bitmaps: None,
+            archives: None,
+            private_keys: None,
+            aes_keys: None,
+            tls_secrets: None,
+            unbacked_code: None,
+            shared_memory: None,
+            libraries: None,
+            instrumentation: None,
+            packers: None,
+            classes: None,
+            values: None,
+            value_snapshot: None,
+            diff: None,
+            pointer_map: None,
+            pointer_paths: None,
+            pointer_chain: None,
+            registers: None,
+            backtraces: None,
+            errors: Vec::new(),
+            stats: Stats::default(),
+            started: Some(Instant::now()),
+        }
+    }
+
+    /// A report for a call that failed before scanning anything
+    pub fn failed(pid: i32, error: String) -> Self {
+        let mut report = Report::new(pid);
+        report.errors.push(error);
+        report.finish();
+        report
+    }
+
+    /// Take the region results of a scan, returning its results
+    pub fn add_scan<T>(&mut self, scan: Scan<T>) -> Vec<T> {
+        for region in &scan.regions {
+            self.stats.add_region(region);
+        }
+        self.stats.results += scan.results.len();
+        self.regions.extend(scan.regions);
+        scan.results
+    }
+
+    /// Stop the clock
+    pub fn finish(&mut self) {
+        if let Some(started) = self.started.take() {
+            self.stats.set_elapsed(started.elapsed());
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|e| {
+            format!("{{\"pid\":{},\"errors\":[\"Failed to serialize report: {}\"]}}", self.pid, e)
+        })
+    }
+}
+
+/// [`Report`]s of a call covering several processes, keyed by PID
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchReport {
+    pub reports: BTreeMap<i32, Report>,
+    /// Why each process that has no report couldn't be scanned
+    pub errors: BTreeMap<i32, String>,
+    /// Totals over every report
+    pub stats: Stats,
+}
+
+impl BatchReport {
+    pub fn add(&mut self, report: Report) {
+        self.stats.add(&report.stats);
+        self.reports.insert(report.pid, report);
+    }
+
+    pub fn add_error(&mut self, pid: i32, error: String) {
+        self.errors.insert(pid, error);
+    }
+}
+
+/// Results that can be returned in either [`OutputFormat`]
+pub trait Rendered {
+    fn to_json(&self) -> String;
+}
+
+impl Rendered for Report {
+    fn to_json(&self) -> String {
+        Report::to_json(self)
+    }
+}
+
+impl Rendered for BatchReport {
+    fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|e| {
+            let error = format!("Failed to serialize report: {}", e);
+            serde_json::json!({"reports": {}, "errors": {}, "error": error}).to_string()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps::parse_maps_line;
+
+    #[test]
+    fn test_report_json_fields() {
+        let region = parse_maps_line("1000-2000 rw-p 00000000 00:00 0 [heap]").unwrap();
+        let scan: Scan<ExtractedString> = Scan {
+            regions: vec![RegionResult {
+                region,
+                bytes_read: 0x1000,
+                error: None,
+                skipped: Vec::new(),
+                elapsed_us: 0,
+                interrupted: false,
+                pid: None,
+            }],
+            results: Vec::new(),
+        };
+
+        let mut report = Report::new(42);
+        report.strings = Some(report.add_scan(scan));
+        report.finish();
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(json["pid"], 42);
+        assert_eq!(json["regions"][0]["path"], "[heap]");
+        assert_eq!(json["regions"][0]["bytesRead"], 0x1000);
+        assert_eq!(json["strings"], serde_json::json!([]));
+        assert_eq!(json["stats"]["bytesRead"], 0x1000);
+        assert_eq!(json["stats"]["bytesMapped"], 0x1000);
+        assert!(json["stats"]["bytesPerSecond"].as_u64().unwrap() > 0);
+        assert!(json.get("matches").is_none());
+
+        let failed: serde_json::Value =
+            serde_json::from_str(&Report::failed(7, "no such process".into()).to_json()).unwrap();
+        assert_eq!(failed["errors"][0], "no such process");
+
+        let mut batch = BatchReport::default();
+        batch.add(report);
+        batch.add_error(7, "no such process".into());
+        let json: serde_json::Value = serde_json::from_str(&Rendered::to_json(&batch)).unwrap();
+        assert_eq!(json["reports"]["42"]["pid"], 42);
+        assert_eq!(json["errors"]["7"], "no such process");
+        assert_eq!(json["stats"]["bytesRead"], 0x1000);
+    }
+}
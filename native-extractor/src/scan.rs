@@ -0,0 +1,1239 @@
+//! Scan options and the drivers that walk a process's regions
+//!
+//! Every result carries a [`Location`]: the absolute address, the region it
+//! was found in, and the offset from the start of that region.
+
+use crate::aes::AesKeyOptions;
+use crate::base64::{self, Base64Options};
+use crate::bloom::{BloomFilter, BloomOptions};
+use crate::carve::{self, CarveOptions};
+use crate::compressed::{self, CompressedOptions};
+use crate::deobfuscate::{self, DeobfuscateOptions};
+#[cfg(feature = "capstone")]
+use crate::disasm::{self, DisassembleOptions};
+use crate::entropy::EntropyOptions;
+use crate::error::ExtractError;
+use crate::filter::RegionFilter;
+use crate::freeze;
+use crate::hexdump;
+use crate::keys::PrivateKeyOptions;
+use crate::maps::MemoryRegion;
+use crate::pagemap::PageMap;
+use crate::pointers::PointerOptions;
+use crate::protobuf::{self, ProtobufOptions};
+use crate::reader::{self, BufferPool, MemoryReader, PooledBuffer, ReaderStrategy};
+use crate::report::OutputFormat;
+use crate::search::{Hit, Matcher, PatternScanner};
+use crate::secrets::SecretOptions;
+use crate::strings::{CharClass, FoundString, StringEncoding, StringExtractor, StringFilter};
+use crate::throttle::{Pacer, Throttle, ThrottleOptions};
+use crate::tls::TlsOptions;
+use crate::urls::UrlOptions;
+use crate::value::ValueOptions;
+#[cfg(feature = "yara")]
+use crate::yara::YaraOptions;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Write};
+use std::ops::{ControlFlow, Range};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Options for a scan, deserialized from the JSON object passed over JNI.
+///
+/// Every field is optional; missing fields take the defaults below.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ScanOptions {
+    /// Minimum length of an extracted string
+    pub min_length: usize,
+    /// Encodings to extract; ASCII only by default
+    pub encodings: Vec<StringEncoding>,
+    /// Longest string to report, in characters; longer ones are dropped
+    pub max_length: Option<usize>,
+    /// Classes of character a string must have at least one of each of
+    pub must_contain: Vec<CharClass>,
+    /// The only characters strings may hold, as in `0-9a-fA-F` (see
+    /// [`StringFilter::parse_alphabet`])
+    pub alphabet: Option<String>,
+    /// Fewest different characters a string must hold, so padding runs
+    /// like `AAAAAAAA` are dropped
+    pub min_distinct_chars: Option<usize>,
+    /// Which regions to visit
+    pub regions: RegionFilter,
+    /// Stop the scan after this many milliseconds and return what was found
+    pub timeout_ms: Option<u64>,
+    /// Stop the target with SIGSTOP while it is read, so the snapshot isn't
+    /// torn by a running allocator
+    pub freeze: bool,
+    /// Resume a frozen target after this many milliseconds even if the scan
+    /// is still running; [`freeze::DEFAULT_TIMEOUT`] if unset
+    pub freeze_timeout_ms: Option<u64>,
+    /// Read only the pages present in RAM, skipping those never touched
+    /// and those swapped out (see [`crate::pagemap`])
+    pub present_pages_only: bool,
+    /// Threads to extract strings on, each reading whole regions; `0` means
+    /// one per CPU core
+    pub threads: usize,
+    /// Bytes asked for in each read, [`reader::READ_CHUNK_SIZE`] if unset.
+    /// Larger chunks mean fewer syscalls, smaller ones finer progress and
+    /// quicker stops.
+    pub chunk_size: Option<usize>,
+    /// Backend the call opens the process with; a session keeps the one it
+    /// was opened with
+    pub reader_strategy: ReaderStrategy,
+    /// How the call renders its result; unset, calls on a session render as
+    /// the session was opened to, and other calls as text
+    pub output_format: Option<OutputFormat>,
+    /// Pacing for scans that shouldn't load the device (see
+    /// [`crate::throttle`])
+    pub throttle: ThrottleOptions,
+    /// File to record progress in, so an interrupted scan can pick up where
+    /// it stopped (see [`crate::checkpoint`])
+    pub checkpoint: Option<String>,
+    /// Most memory collected results may take, in MiB; those past it are
+    /// written to a file in `spillDir` instead (see [`crate::spill`])
+    pub max_result_memory_mb: Option<u64>,
+    /// Directory for results past `maxResultMemoryMb`, the system
+    /// temporary directory if unset
+    pub spill_dir: Option<String>,
+    /// Report each distinct string once, with its count and addresses
+    pub dedupe: bool,
+    /// Drop strings a Bloom filter has probably seen before, in memory that
+    /// doesn't grow with the scan (see [`crate::bloom`])
+    pub approximate_dedupe: Option<BloomOptions>,
+    /// Name the library symbol each string lies in, for strings in a
+    /// library's mappings (see [`crate::symbols`])
+    pub symbols: bool,
+    /// What `scanEntropy` counts as random (see [`crate::entropy`])
+    pub entropy: EntropyOptions,
+    /// Which detectors `scanSecrets` runs (see [`crate::secrets`])
+    pub secrets: SecretOptions,
+    /// Which base64 runs `scanBase64` decodes (see [`crate::base64`])
+    pub base64: Base64Options,
+    /// Which compressed streams `scanCompressed` inflates (see
+    /// [`crate::compressed`])
+    pub compressed: CompressedOptions,
+    /// Which buffers `deobfuscateStrings` tries to decode (see
+    /// [`crate::deobfuscate`])
+    pub deobfuscate: DeobfuscateOptions,
+    /// The number `scanValue` searches for (see [`crate::value`])
+    pub value: ValueOptions,
+    /// The pointer map, paths and chains the pointer calls build, search
+    /// and follow (see [`crate::pointers`])
+    pub pointers: PointerOptions,
+    /// Which documents `carveDocuments` looks for (see [`crate::carve`])
+    pub carve: CarveOptions,
+    /// Which messages `scanProtobuf` reports (see [`crate::protobuf`])
+    pub protobuf: ProtobufOptions,
+    /// Which URLs `extractUrls` reports (see [`crate::urls`])
+    pub urls: UrlOptions,
+    /// Where `scanPrivateKeys` writes the keys it finds (see [`crate::keys`])
+    pub private_keys: PrivateKeyOptions,
+    /// How closely `scanAesKeys` matches key schedules (see [`crate::aes`])
+    pub aes_keys: AesKeyOptions,
+    /// Where `scanTlsSecrets` writes its key log (see [`crate::tls`])
+    pub tls: TlsOptions,
+    /// The rules `scanYara` runs (see [`crate::yara`])
+    #[cfg(feature = "yara")]
+    pub yara: YaraOptions,
+    /// The range `disassemble` decodes (see [`crate::disasm`])
+    #[cfg(feature = "capstone")]
+    pub disassemble: DisassembleOptions,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        ScanOptions {
+            min_length: 4,
+            encodings: vec![StringEncoding::Ascii],
+            max_length: None,
+            must_contain: Vec::new(),
+            alphabet: None,
+            min_distinct_chars: None,
+            regions: RegionFilter::default(),
+            timeout_ms: None,
+            freeze: false,
+            freeze_timeout_ms: None,
+            present_pages_only: false,
+            threads: 1,
+            chunk_size: None,
+            reader_strategy: ReaderStrategy::Auto,
+            output_format: None,
+            throttle: ThrottleOptions::default(),
+            checkpoint: None,
+            max_result_memory_mb: None,
+            spill_dir: None,
+            dedupe: false,
+            approximate_dedupe: None,
+            symbols: false,
+            entropy: EntropyOptions::default(),
+            secrets: SecretOptions::default(),
+            base64: Base64Options::default(),
+            compressed: CompressedOptions::default(),
+            deobfuscate: DeobfuscateOptions::default(),
+            value: ValueOptions::default(),
+            pointers: PointerOptions::default(),
+            carve: CarveOptions::default(),
+            protobuf: ProtobufOptions::default(),
+            urls: UrlOptions::default(),
+            private_keys: PrivateKeyOptions::default(),
+            aes_keys: AesKeyOptions::default(),
+            tls: TlsOptions::default(),
+            #[cfg(feature = "yara")]
+            yara: YaraOptions::default(),
+            #[cfg(feature = "capstone")]
+            disassemble: DisassembleOptions::default(),
+        }
+    }
+}
+
+impl ScanOptions {
+    /// Parse options from JSON; an empty string means all defaults
+    pub fn from_json(json: &str) -> Result<Self, ExtractError> {
+        if json.trim().is_empty() {
+            return Ok(ScanOptions::default());
+        }
+        let options: ScanOptions = serde_json::from_str(json)
+            .map_err(|e| ExtractError::InvalidArgument(format!("Invalid scan options: {}", e)))?;
+        let sizes = reader::MIN_CHUNK_SIZE..=reader::MAX_CHUNK_SIZE;
+        if options.chunk_size.is_some_and(|size| !sizes.contains(&size)) {
+            return Err(ExtractError::InvalidArgument(format!(
+                "Invalid scan options: chunkSize must be between {} and {}",
+                sizes.start(),
+                sizes.end()
+            )));
+        }
+        if let Some(alphabet) = &options.alphabet {
+            StringFilter::parse_alphabet(alphabet).map_err(|e| {
+                ExtractError::InvalidArgument(format!("Invalid scan options: {}", e))
+            })?;
+        }
+        let rate = options.approximate_dedupe.as_ref().map(|bloom| bloom.false_positive_rate);
+        if rate.is_some_and(|rate| !(rate > 0.0 && rate < 1.0)) {
+            return Err(ExtractError::InvalidArgument(
+                "Invalid scan options: falsePositiveRate must be between 0 and 1".into(),
+            ));
+        }
+        let base64 = &options.base64;
+        if !(base64.min_length..=base64::MAX_RUN_LENGTH).contains(&base64.max_length) {
+            return Err(ExtractError::InvalidArgument(format!(
+                "Invalid scan options: base64 maxLength must be between minLength and {}",
+                base64::MAX_RUN_LENGTH
+            )));
+        }
+        let compressed = &options.compressed;
+        if !(compressed.min_length..=compressed::MAX_OUTPUT_LENGTH).contains(&compressed.max_length)
+        {
+            return Err(ExtractError::InvalidArgument(format!(
+                "Invalid scan options: compressed maxLength must be between minLength and {}",
+                compressed::MAX_OUTPUT_LENGTH
+            )));
+        }
+        let deobfuscate = &options.deobfuscate;
+        let lengths = deobfuscate.min_length..=deobfuscate::MAX_BUFFER_LENGTH;
+        if !lengths.contains(&deobfuscate.max_length) {
+            return Err(ExtractError::InvalidArgument(format!(
+                "Invalid scan options: deobfuscate maxLength must be between minLength and {}",
+                deobfuscate::MAX_BUFFER_LENGTH
+            )));
+        }
+        if !(deobfuscate.min_printable > 0.0 && deobfuscate.min_printable <= 1.0) {
+            return Err(ExtractError::InvalidArgument(
+                "Invalid scan options: deobfuscate minPrintable must be between 0 and 1".into(),
+            ));
+        }
+        if !matches!(options.value.alignment(), 1 | 2 | 4 | 8) {
+            return Err(ExtractError::InvalidArgument(
+                "Invalid scan options: value alignment must be 1, 2, 4 or 8".into(),
+            ));
+        }
+        if options.pointers.width.is_some_and(|width| width != 4 && width != 8) {
+            return Err(ExtractError::InvalidArgument(
+                "Invalid scan options: pointers width must be 4 or 8".into(),
+            ));
+        }
+        let carve = &options.carve;
+        if !(carve.min_length..=carve::MAX_DOCUMENT_LENGTH).contains(&carve.max_length) {
+            return Err(ExtractError::InvalidArgument(format!(
+                "Invalid scan options: carve maxLength must be between minLength and {}",
+                carve::MAX_DOCUMENT_LENGTH
+            )));
+        }
+        let protobuf = &options.protobuf;
+        if !(protobuf.min_length..=protobuf::MAX_MESSAGE_LENGTH).contains(&protobuf.max_length) {
+            return Err(ExtractError::InvalidArgument(format!(
+                "Invalid scan options: protobuf maxLength must be between minLength and {}",
+                protobuf::MAX_MESSAGE_LENGTH
+            )));
+        }
+        #[cfg(feature = "capstone")]
+        if !(1..=disasm::MAX_LENGTH).contains(&options.disassemble.length) {
+            return Err(ExtractError::InvalidArgument(format!(
+                "Invalid scan options: disassemble length must be between 1 and {}",
+                disasm::MAX_LENGTH
+            )));
+        }
+        Ok(options)
+    }
+
+    /// The tests strings must pass besides `minLength`
+    pub fn string_filter(&self) -> StringFilter {
+        StringFilter {
+            max_length: self.max_length,
+            must_contain: self.must_contain.clone(),
+            alphabet: self.alphabet.as_deref().and_then(|a| StringFilter::parse_alphabet(a).ok()),
+            min_distinct_chars: self.min_distinct_chars,
+        }
+    }
+
+    /// The filter `approximateDedupe` asks for, if it is set
+    pub fn bloom_filter(&self) -> Option<BloomFilter> {
+        self.approximate_dedupe.as_ref().map(BloomFilter::new)
+    }
+
+    /// When a scan using these options, starting now, should stop
+    pub fn deadline(&self) -> Option<Instant> {
+        self.timeout_ms.map(|timeout| Instant::now() + Duration::from_millis(timeout))
+    }
+
+    /// Longest the target may be kept stopped when `freeze` is set
+    pub fn freeze_timeout(&self) -> Duration {
+        self.freeze_timeout_ms.map_or(freeze::DEFAULT_TIMEOUT, Duration::from_millis)
+    }
+
+    /// Bytes of results `maxResultMemoryMb` allows, if it is set
+    pub fn result_budget(&self) -> Option<usize> {
+        self.max_result_memory_mb.map(|mb| usize::try_from(mb << 20).unwrap_or(usize::MAX))
+    }
+
+    /// Where results past the budget are written
+    pub fn spill_dir(&self) -> PathBuf {
+        self.spill_dir.as_ref().map_or_else(std::env::temp_dir, PathBuf::from)
+    }
+
+    /// How many threads `threads` asks for
+    pub fn thread_count(&self) -> usize {
+        match self.threads {
+            0 => thread::available_parallelism().map_or(1, usize::from),
+            threads => threads,
+        }
+    }
+}
+
+/// Parse the object `key` of the options JSON on its own, for the one call
+/// that takes it; an empty string or no such object means all defaults
+pub fn parse_object<T>(json: &str, key: &str) -> Result<T, ExtractError>
+where
+    T: DeserializeOwned + Default,
+{
+    let invalid = |e: serde_json::Error| {
+        ExtractError::InvalidArgument(format!("Invalid {} options: {}", key, e))
+    };
+    if json.trim().is_empty() {
+        return Ok(T::default());
+    }
+    let mut options: serde_json::Map<String, serde_json::Value> =
+        serde_json::from_str(json).map_err(invalid)?;
+    match options.remove(key) {
+        Some(object) => serde_json::from_value(object).map_err(invalid),
+        None => Ok(T::default()),
+    }
+}
+
+/// Where in the target a result was found
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Location {
+    pub address: u64,
+    /// Name of the containing region, see [`MemoryRegion::name`]
+    pub region: Arc<str>,
+    /// Offset of `address` from the start of the region
+    pub offset: u64,
+    /// Process the result came from, set when a scan covers several
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pid: Option<i32>,
+    /// The library symbol `address` lies in, as `libfoo.so!name+0x24`, when
+    /// asked for (see [`crate::symbols`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<String>,
+}
+
+impl Location {
+    pub fn new(region: &Arc<str>, region_start: u64, address: u64) -> Self {
+        Location {
+            address,
+            region: Arc::clone(region),
+            offset: address - region_start,
+            pid: None,
+            symbol: None,
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    /// `0x<address> <region>+0x<offset>`, preceded by `pid <pid> ` and
+    /// followed by ` (<symbol>)` if set
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(pid) = self.pid {
+            write!(f, "pid {} ", pid)?;
+        }
+        write!(f, "0x{:x} {}+0x{:x}", self.address, self.region, self.offset)?;
+        if let Some(symbol) = &self.symbol {
+            write!(f, " ({})", symbol)?;
+        }
+        Ok(())
+    }
+}
+
+/// A result with a [`Location`]
+pub trait Located {
+    fn location_mut(&mut self) -> &mut Location;
+}
+
+/// A string extracted from memory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExtractedString {
+    #[serde(flatten)]
+    pub location: Location,
+    pub encoding: StringEncoding,
+    pub value: String,
+    /// How many times the string was found, when duplicates are collapsed
+    /// (see [`dedupe_strings`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub count: Option<usize>,
+    /// Where each of those occurrences is, `address` first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub addresses: Vec<u64>,
+}
+
+impl Located for ExtractedString {
+    fn location_mut(&mut self) -> &mut Location {
+        &mut self.location
+    }
+}
+
+/// Collapse strings with the same value from the same process into the
+/// first of them, giving it their `count` and `addresses`. The strings keep
+/// the order of their first occurrences.
+pub fn dedupe_strings(strings: Vec<ExtractedString>) -> Vec<ExtractedString> {
+    let mut unique: Vec<ExtractedString> = Vec::new();
+    let mut seen: HashMap<Option<i32>, HashMap<String, usize>> = HashMap::new();
+    for mut string in strings {
+        let values = seen.entry(string.location.pid).or_default();
+        match values.get(string.value.as_str()) {
+            Some(&index) => {
+                let first = &mut unique[index];
+                first.count = Some(first.count.unwrap_or(1) + 1);
+                first.addresses.push(string.location.address);
+            }
+            None => {
+                values.insert(string.value.clone(), unique.len());
+                string.count = Some(1);
+                string.addresses = vec![string.location.address];
+                unique.push(string);
+            }
+        }
+    }
+    unique
+}
+
+/// Memory surrounding a match
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Context {
+    /// Address of the first byte of `bytes`
+    pub address: u64,
+    #[serde(serialize_with = "serialize_hex")]
+    pub bytes: Vec<u8>,
+}
+
+impl Context {
+    pub fn hexdump(&self) -> String {
+        hexdump::hexdump(self.address, &self.bytes)
+    }
+}
+
+/// A pattern or keyword match found in memory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Match {
+    #[serde(flatten)]
+    pub location: Location,
+    /// Index of the pattern that matched
+    pub pattern: usize,
+    /// The matched bytes, with anything non-printable escaped
+    #[serde(serialize_with = "serialize_escaped")]
+    pub bytes: Vec<u8>,
+    /// Surrounding memory, when a context window was requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context: Option<Context>,
+}
+
+impl Located for Match {
+    fn location_mut(&mut self) -> &mut Location {
+        &mut self.location
+    }
+}
+
+pub(crate) fn serialize_hex<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        let _ = write!(hex, "{:02x}", byte);
+    }
+    serializer.serialize_str(&hex)
+}
+
+fn serialize_escaped<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(&bytes.escape_ascii())
+}
+
+/// What happened when one region was read
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RegionResult {
+    #[serde(flatten)]
+    pub region: MemoryRegion,
+    pub bytes_read: u64,
+    /// Why nothing could be read from the region
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Ranges within the region that couldn't be read and were skipped
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skipped: Vec<Range<u64>>,
+    /// How long reading and scanning the region took
+    #[serde(default)]
+    pub elapsed_us: u64,
+    /// Set when the scan stopped while reading the region, so its results
+    /// may not cover all of it
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub interrupted: bool,
+    /// Process the region belongs to, set when a scan covers several
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pid: Option<i32>,
+}
+
+/// Where a scan delivers its results as they are found
+pub trait ScanSink<T> {
+    /// Called once each region has been read, after its results
+    fn region(&mut self, result: RegionResult);
+
+    fn result(&mut self, item: T);
+}
+
+/// Results of walking a set of regions, collected in memory
+#[derive(Debug, Clone)]
+pub struct Scan<T> {
+    pub regions: Vec<RegionResult>,
+    pub results: Vec<T>,
+}
+
+impl<T> Default for Scan<T> {
+    fn default() -> Self {
+        Scan {
+            regions: Vec::new(),
+            results: Vec::new(),
+        }
+    }
+}
+
+impl<T: Located> Scan<T> {
+    /// Mark every region and result as coming from `pid`
+    pub fn tag_pid(&mut self, pid: i32) {
+        for region in &mut self.regions {
+            region.pid = Some(pid);
+        }
+        for result in &mut self.results {
+            result.location_mut().pid = Some(pid);
+        }
+    }
+}
+
+impl<T> ScanSink<T> for Scan<T> {
+    fn region(&mut self, result: RegionResult) {
+        self.regions.push(result);
+    }
+
+    fn result(&mut self, item: T) {
+        self.results.push(item);
+    }
+}
+
+/// Watches a scan as it runs
+pub trait ScanObserver {
+    /// Called after each chunk with the bytes covered so far, the total size
+    /// of all regions being walked, and the region being read. Regions that
+    /// end early count in full once they are done.
+    fn progress(&mut self, bytes_scanned: u64, total_bytes: u64, region: &MemoryRegion);
+}
+
+/// Why a scan ended before covering every region
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum StopReason {
+    /// The caller asked for the scan to stop
+    Cancelled,
+    /// The scan ran past its deadline
+    Timeout,
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StopReason::Cancelled => f.write_str("cancelled"),
+            StopReason::Timeout => f.write_str("timeout"),
+        }
+    }
+}
+
+/// Progress reporting and early stopping for a region walk.
+///
+/// Stop conditions are checked between chunks, so a scan stops within one
+/// chunk-sized read of being asked to.
+#[derive(Default)]
+pub struct ScanControl<'a> {
+    observer: Option<&'a mut dyn ScanObserver>,
+    cancel: Option<&'a AtomicBool>,
+    deadline: Option<Instant>,
+    pages: Option<&'a PageMap>,
+    buffers: Option<&'a BufferPool>,
+    chunk_size: Option<usize>,
+    throttle: Option<&'a Throttle>,
+    pacer: Pacer,
+}
+
+impl<'a> ScanControl<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Report progress to `observer`
+    pub fn observer(mut self, observer: &'a mut dyn ScanObserver) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// Stop once `flag` is set
+    pub fn cancel_flag(mut self, flag: &'a AtomicBool) -> Self {
+        self.cancel = Some(flag);
+        self
+    }
+
+    /// Stop once `deadline` has passed, if given
+    pub fn deadline(mut self, deadline: Option<Instant>) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    /// Read only the pages `pages` says are present, if given
+    pub fn present_pages(mut self, pages: Option<&'a PageMap>) -> Self {
+        self.pages = pages;
+        self
+    }
+
+    /// Take read buffers from `buffers` instead of allocating them
+    pub fn buffers(mut self, buffers: &'a BufferPool) -> Self {
+        self.buffers = Some(buffers);
+        self
+    }
+
+    /// Read `size` bytes at a time, if given, rather than
+    /// [`reader::READ_CHUNK_SIZE`]
+    pub fn chunk_size(mut self, size: Option<usize>) -> Self {
+        self.chunk_size = size;
+        self
+    }
+
+    /// Pace the walk with `throttle`, if given
+    pub fn throttle(mut self, throttle: Option<&'a Throttle>) -> Self {
+        self.throttle = throttle;
+        self
+    }
+
+    /// Let the throttle, if any, pause after a chunk of `len` bytes
+    fn pace(&mut self, len: usize) {
+        let Some(throttle) = self.throttle else {
+            return;
+        };
+        let (cancel, deadline) = (self.cancel, self.deadline);
+        throttle.pace(&mut self.pacer, len, &|| stop_reason(cancel, deadline).is_some());
+    }
+
+    /// A buffer to read chunks into, from the pool if there is one
+    fn buffer(&self) -> PooledBuffer<'a> {
+        let size = self.chunk_size.unwrap_or(reader::READ_CHUNK_SIZE);
+        match self.buffers {
+            Some(buffers) => buffers.take(size),
+            None => PooledBuffer::unpooled(size),
+        }
+    }
+
+    /// The parts of `region` to read: all of it, or only its present pages.
+    /// If the page map can't be read the whole region is.
+    fn ranges(&self, region: &MemoryRegion) -> Vec<Range<u64>> {
+        self.pages
+            .and_then(|pages| pages.present_ranges(region.start, region.end).ok())
+            .unwrap_or_else(|| std::iter::once(region.start..region.end).collect())
+    }
+
+    /// Whether the scan should stop, and why
+    pub fn check(&self) -> Option<StopReason> {
+        stop_reason(self.cancel, self.deadline)
+    }
+
+    fn progress(&mut self, bytes_scanned: u64, total_bytes: u64, region: &MemoryRegion) {
+        if let Some(observer) = self.observer.as_deref_mut() {
+            observer.progress(bytes_scanned, total_bytes, region);
+        }
+    }
+}
+
+fn stop_reason(cancel: Option<&AtomicBool>, deadline: Option<Instant>) -> Option<StopReason> {
+    if cancel.is_some_and(|flag| flag.load(Ordering::Relaxed)) {
+        return Some(StopReason::Cancelled);
+    }
+    if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+        return Some(StopReason::Timeout);
+    }
+    None
+}
+
+/// Something that consumes the chunks read while walking regions
+pub trait RegionVisitor {
+    type Output;
+
+    fn visit_chunk(&mut self, region: &MemoryRegion, address: u64, chunk: &[u8]);
+
+    /// Called once the region has been read as far as possible, to deliver
+    /// its results; `reader` is passed back for visitors that need to read
+    /// more around a result
+    fn end_region(
+        &mut self,
+        region: &MemoryRegion,
+        reader: &mut dyn MemoryReader,
+        sink: &mut dyn ScanSink<Self::Output>,
+    );
+}
+
+/// Read each region in turn, feeding its chunks to `visitor`.
+///
+/// Unreadable regions are recorded and skipped; a region that fails partway
+/// keeps whatever was read around the pages that failed, which are listed
+/// in its [`RegionResult::skipped`]. With a page map in `control`, only the
+/// present pages of each region are read, the visitor seeing a gap in the
+/// addresses where pages were left out. If `control` stops the walk, the
+/// results of the region being read are still delivered and the reason
+/// is returned.
+pub fn walk_regions<V: RegionVisitor>(
+    reader: &mut dyn MemoryReader,
+    regions: &[MemoryRegion],
+    visitor: &mut V,
+    sink: &mut dyn ScanSink<V::Output>,
+    control: &mut ScanControl,
+) -> Option<StopReason> {
+    let total: u64 = regions.iter().map(MemoryRegion::size).sum();
+    let mut done = 0u64;
+    let mut buffer = control.buffer();
+
+    for region in regions {
+        if let Some(reason) = control.check() {
+            return Some(reason);
+        }
+
+        let mut stopped = None;
+        let started = Instant::now();
+        let (mut bytes_read, mut error, mut skipped) = (0, None, Vec::new());
+        for range in control.ranges(region) {
+            let (start, end) = (range.start, range.end);
+            let read = reader::read_region(reader, start, end, &mut buffer, |address, chunk| {
+                visitor.visit_chunk(region, address, chunk);
+                let covered = address + chunk.len() as u64 - region.start;
+                control.progress(done + covered, total, region);
+                control.pace(chunk.len());
+
+                stopped = control.check();
+                match stopped {
+                    Some(_) => ControlFlow::Break(()),
+                    None => ControlFlow::Continue(()),
+                }
+            });
+            match read {
+                Ok(mut read) => {
+                    bytes_read += read.bytes_read;
+                    skipped.append(&mut read.skipped);
+                }
+                Err(e) => {
+                    // Only worth reporting if the region gave nothing at all
+                    error = (bytes_read == 0).then(|| e.to_string());
+                    break;
+                }
+            }
+            if stopped.is_some() {
+                break;
+            }
+        }
+        visitor.end_region(region, reader, sink);
+        done += region.size();
+
+        sink.region(RegionResult {
+            region: region.clone(),
+            bytes_read,
+            error,
+            skipped,
+            elapsed_us: started.elapsed().as_micros() as u64,
+            interrupted: stopped.is_some(),
+            pid: None,
+        });
+
+        if stopped.is_some() {
+            return stopped;
+        }
+    }
+
+    None
+}
+
+/// [`walk_regions`] on up to `threads` threads.
+///
+/// Each thread takes the next region no thread has started on and walks it
+/// with a visitor of its own, made by `make_visitor`, and a reader of its
+/// own, the first using `reader` and the others clones of it. Results reach
+/// `sink` in region order, as they would from [`walk_regions`], each
+/// region's once every region before it is done, and progress is reported a
+/// region at a time. With a reader that can't be cloned this is a walk on
+/// the calling thread.
+pub fn walk_regions_parallel<V, F>(
+    reader: &mut dyn MemoryReader,
+    regions: &[MemoryRegion],
+    make_visitor: F,
+    threads: usize,
+    sink: &mut dyn ScanSink<V::Output>,
+    control: &mut ScanControl,
+) -> Option<StopReason>
+where
+    V: RegionVisitor,
+    V::Output: Send,
+    F: Fn() -> V + Sync,
+{
+    let mut clones: Vec<Box<dyn MemoryReader>> = Vec::new();
+    while clones.len() + 1 < threads.min(regions.len()) {
+        match reader.try_clone() {
+            Some(clone) => clones.push(clone),
+            None => break,
+        }
+    }
+    if clones.is_empty() {
+        return walk_regions(reader, regions, &mut make_visitor(), sink, control);
+    }
+
+    let mut readers: Vec<&mut dyn MemoryReader> = vec![reader];
+    readers.extend(clones.iter_mut().map(|clone| &mut **clone as &mut dyn MemoryReader));
+    let (cancel, deadline, pages) = (control.cancel, control.deadline, control.pages);
+    let (buffers, chunk_size, throttle) = (control.buffers, control.chunk_size, control.throttle);
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel::<(usize, Scan<V::Output>, Option<StopReason>)>();
+
+    thread::scope(|scope| {
+        for reader in readers {
+            let (sender, next, make_visitor) = (sender.clone(), &next, &make_visitor);
+            scope.spawn(move || {
+                let mut control = ScanControl {
+                    observer: None,
+                    cancel,
+                    deadline,
+                    pages,
+                    buffers,
+                    chunk_size,
+                    throttle,
+                    pacer: Pacer::default(),
+                };
+                let mut visitor = make_visitor();
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(region) = regions.get(index) else {
+                        break;
+                    };
+                    let mut scan = Scan::default();
+                    let region = std::slice::from_ref(region);
+                    let stopped =
+                        walk_regions(reader, region, &mut visitor, &mut scan, &mut control);
+                    if sender.send((index, scan, stopped)).is_err() || stopped.is_some() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        let total: u64 = regions.iter().map(MemoryRegion::size).sum();
+        let mut done = 0u64;
+        let mut stopped = None;
+        // Regions finished ahead of one still being read, by index
+        let mut waiting = BTreeMap::new();
+        let mut deliver = |index: usize, scan: Scan<V::Output>| {
+            for result in scan.results {
+                sink.result(result);
+            }
+            for result in scan.regions {
+                sink.region(result);
+                done += regions[index].size();
+                control.progress(done, total, &regions[index]);
+            }
+        };
+        let mut delivered = 0;
+        for (index, scan, reason) in receiver {
+            stopped = stopped.or(reason);
+            waiting.insert(index, scan);
+            while let Some(scan) = waiting.remove(&delivered) {
+                deliver(delivered, scan);
+                delivered += 1;
+            }
+        }
+        // After a stop, regions no thread got to leave gaps
+        for (index, scan) in waiting {
+            deliver(index, scan);
+        }
+        stopped
+    })
+}
+
+/// Delivers strings found by a [`StringExtractor`], region by region
+struct StringVisitor {
+    extractor: StringExtractor,
+    filter: StringFilter,
+    found: Vec<FoundString>,
+}
+
+impl RegionVisitor for StringVisitor {
+    type Output = ExtractedString;
+
+    fn visit_chunk(&mut self, _region: &MemoryRegion, address: u64, chunk: &[u8]) {
+        self.extractor.feed(address, chunk, &mut self.found);
+    }
+
+    fn end_region(
+        &mut self,
+        region: &MemoryRegion,
+        _reader: &mut dyn MemoryReader,
+        sink: &mut dyn ScanSink<ExtractedString>,
+    ) {
+        self.extractor.finish(&mut self.found);
+
+        let name: Arc<str> = region.name().into();
+        for string in self.found.drain(..) {
+            if !self.filter.accepts(&string.value) {
+                continue;
+            }
+            sink.result(ExtractedString {
+                location: Location::new(&name, region.start, string.address),
+                encoding: string.encoding,
+                value: string.value,
+                count: None,
+                addresses: Vec::new(),
+            });
+        }
+    }
+}
+
+/// Delivers matches found by a [`PatternScanner`], region by region
+struct MatchVisitor<'a, M> {
+    scanner: &'a mut PatternScanner<M>,
+    context: usize,
+    hits: Vec<Hit>,
+}
+
+impl<M: Matcher> RegionVisitor for MatchVisitor<'_, M> {
+    type Output = Match;
+
+    fn visit_chunk(&mut self, _region: &MemoryRegion, address: u64, chunk: &[u8]) {
+        self.scanner.feed(address, chunk, &mut self.hits);
+    }
+
+    fn end_region(
+        &mut self,
+        region: &MemoryRegion,
+        reader: &mut dyn MemoryReader,
+        sink: &mut dyn ScanSink<Match>,
+    ) {
+        self.scanner.finish(&mut self.hits);
+
+        let name: Arc<str> = region.name().into();
+        for hit in self.hits.drain(..) {
+            let context = (self.context > 0).then(|| {
+                let end = hit.address + hit.bytes.len() as u64;
+                read_context(reader, region, hit.address, end, self.context)
+            });
+            sink.result(Match {
+                location: Location::new(&name, region.start, hit.address),
+                pattern: hit.pattern,
+                bytes: hit.bytes,
+                context,
+            });
+        }
+    }
+}
+
+/// Extract strings from each region, on as many threads as `options` ask
+/// for, returning why it stopped early if it did
+pub fn extract_strings(
+    reader: &mut dyn MemoryReader,
+    regions: &[MemoryRegion],
+    options: &ScanOptions,
+    sink: &mut dyn ScanSink<ExtractedString>,
+    control: &mut ScanControl,
+) -> Option<StopReason> {
+    let filter = options.string_filter();
+    let visitor = || StringVisitor {
+        extractor: StringExtractor::new(&options.encodings, options.min_length),
+        filter: filter.clone(),
+        found: Vec::new(),
+    };
+    walk_regions_parallel(reader, regions, visitor, options.thread_count(), sink, control)
+}
+
+/// Run a pattern scanner over each region in turn.
+///
+/// With a non-zero `context`, up to that many bytes either side of each
+/// match are read back, clipped to the region the match was found in.
+pub fn search_regions<M: Matcher>(
+    reader: &mut dyn MemoryReader,
+    regions: &[MemoryRegion],
+    scanner: &mut PatternScanner<M>,
+    context: usize,
+    sink: &mut dyn ScanSink<Match>,
+    control: &mut ScanControl,
+) -> Option<StopReason> {
+    let mut visitor = MatchVisitor {
+        scanner,
+        context,
+        hits: Vec::new(),
+    };
+    walk_regions(reader, regions, &mut visitor, sink, control)
+}
+
+/// Read `window` bytes either side of `start..end`, within `region`.
+///
+/// Whatever can't be read is left out, so the context may be shorter than
+/// asked for or hold only the match itself.
+fn read_context(
+    reader: &mut dyn MemoryReader,
+    region: &MemoryRegion,
+    start: u64,
+    end: u64,
+    window: usize,
+) -> Context {
+    let from = start.saturating_sub(window as u64).max(region.start);
+    let to = end.saturating_add(window as u64).min(region.end);
+
+    let mut bytes = vec![0u8; (to - from) as usize];
+    let read = reader.read_at(from, &mut bytes).unwrap_or(0);
+    bytes.truncate(read);
+
+    Context {
+        address: from,
+        bytes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps::parse_maps_line;
+    use crate::reader::AutoReader;
+    use crate::search::compile_regex;
+
+    fn region_over(data: &[u8]) -> MemoryRegion {
+        let start = data.as_ptr() as u64;
+        parse_maps_line(&format!(
+            "{:x}-{:x} rw-p 00000000 00:00 0 [anon:test]",
+            start,
+            start + data.len() as u64
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_scan_options_from_json() {
+        let options = ScanOptions::from_json(
+            r#"{
+                "minLength": 8,
+                "encodings": ["ascii", "utf16le"],
+                "regions": {"excludePerms": ["---p"], "includePaths": ["[heap]"]},
+                "readerStrategy": "vmReadv",
+                "outputFormat": "json"
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(options.min_length, 8);
+        assert_eq!(options.encodings, [StringEncoding::Ascii, StringEncoding::Utf16le]);
+        assert_eq!(options.regions.exclude_perms, vec!["---p"]);
+        assert_eq!(options.regions.include_paths, vec!["[heap]"]);
+        assert_eq!(options.timeout_ms, None);
+        assert_eq!(options.reader_strategy, ReaderStrategy::VmReadv);
+        assert_eq!(options.output_format, Some(OutputFormat::Json));
+        assert_eq!(ScanOptions::from_json("").unwrap().encodings, [StringEncoding::Ascii]);
+        assert!(ScanOptions::from_json("{\"minLength\": \"x\"}").is_err());
+        assert!(ScanOptions::from_json("{\"readerStrategy\": \"mmap\"}").is_err());
+        // Only the calls that take them check the objects of other calls
+        assert!(ScanOptions::from_json(r#"{"hold": {"intervalMs": 0}}"#).is_ok());
+    }
+
+    #[test]
+    fn test_extract_strings_reports_location() {
+        let data = b"\x00\x00\x00\x00located string\x00".to_vec();
+        let start = data.as_ptr() as u64;
+        let region = region_over(&data);
+
+        let mut reader = AutoReader::new(std::process::id() as i32);
+        let mut scan = Scan::default();
+        let control = &mut ScanControl::new();
+        extract_strings(&mut reader, &[region], &ScanOptions::default(), &mut scan, control);
+        let strings = scan.results;
+
+        assert_eq!(scan.regions[0].bytes_read, data.len() as u64);
+        assert_eq!(strings.len(), 1);
+        assert_eq!(strings[0].value, "located string");
+        assert_eq!(strings[0].location.address, start + 4);
+        assert_eq!(strings[0].location.offset, 4);
+        assert_eq!(&*strings[0].location.region, "[anon:test]");
+    }
+
+    #[test]
+    fn test_dedupe_counts_each_process_apart() {
+        let string = |pid, address, value: &str| ExtractedString {
+            location: Location {
+                pid,
+                ..Location::new(&"[heap]".into(), 0, address)
+            },
+            encoding: StringEncoding::Ascii,
+            value: value.into(),
+            count: None,
+            addresses: Vec::new(),
+        };
+        let strings = vec![
+            string(Some(1), 0x10, "OK"),
+            string(Some(1), 0x20, "Cancel"),
+            string(Some(1), 0x30, "OK"),
+            string(Some(2), 0x40, "OK"),
+            string(Some(1), 0x50, "OK"),
+        ];
+
+        let unique = dedupe_strings(strings);
+        let summary: Vec<_> = unique
+            .iter()
+            .map(|s| (s.location.pid, s.value.as_str(), s.count, s.addresses.clone()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (Some(1), "OK", Some(3), vec![0x10, 0x30, 0x50]),
+                (Some(1), "Cancel", Some(1), vec![0x20]),
+                (Some(2), "OK", Some(1), vec![0x40]),
+            ]
+        );
+        assert_eq!(unique[0].location.address, 0x10);
+    }
+
+    #[test]
+    fn test_parallel_extraction_keeps_region_order() {
+        let data: Vec<Vec<u8>> = (0..8)
+            .map(|i| format!("\0\0string number {}\0", i).into_bytes())
+            .collect();
+        let regions: Vec<MemoryRegion> = data.iter().map(|data| region_over(data)).collect();
+        let options = ScanOptions {
+            threads: 4,
+            ..ScanOptions::default()
+        };
+
+        let mut reader = AutoReader::new(std::process::id() as i32);
+        let mut scan = Scan::default();
+        let control = &mut ScanControl::new();
+        extract_strings(&mut reader, &regions, &options, &mut scan, control);
+
+        let values: Vec<String> = scan.results.into_iter().map(|string| string.value).collect();
+        let expected: Vec<String> = (0..8).map(|i| format!("string number {}", i)).collect();
+        assert_eq!(values, expected);
+        let starts: Vec<u64> = scan.regions.iter().map(|result| result.region.start).collect();
+        assert_eq!(starts, regions.iter().map(|region| region.start).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_search_context_is_clipped_to_region() {
+        let data = b"header:token=1234;trailer".to_vec();
+        let start = data.as_ptr() as u64;
+        let region = region_over(&data);
+
+        let mut reader = AutoReader::new(std::process::id() as i32);
+        let mut scanner = PatternScanner::new(compile_regex("token=[0-9]+", 0).unwrap());
+        let mut scan = Scan::default();
+        let control = &mut ScanControl::new();
+        search_regions(&mut reader, &[region], &mut scanner, 8, &mut scan, control);
+        let matches = scan.results;
+
+        assert_eq!(matches.len(), 1);
+        let context = matches[0].context.as_ref().unwrap();
+        assert_eq!(context.address, start);
+        assert_eq!(context.bytes, b"header:token=1234;trailer");
+    }
+
+    #[test]
+    fn test_progress_covers_every_region() {
+        struct Recorder(Vec<(u64, u64)>);
+        impl ScanObserver for Recorder {
+            fn progress(&mut self, bytes_scanned: u64, total_bytes: u64, _: &MemoryRegion) {
+                self.0.push((bytes_scanned, total_bytes));
+            }
+        }
+
+        let first = vec![b'a'; reader::READ_CHUNK_SIZE + 100];
+        let second = b"second region".to_vec();
+        let regions = [region_over(&first), region_over(&second)];
+        let total = (first.len() + second.len()) as u64;
+
+        let mut reader = AutoReader::new(std::process::id() as i32);
+        let mut recorder = Recorder(Vec::new());
+        let options = ScanOptions::default();
+        let mut control = ScanControl::new().observer(&mut recorder);
+        extract_strings(&mut reader, &regions, &options, &mut Scan::default(), &mut control);
+
+        assert_eq!(recorder.0.len(), 3);
+        assert!(recorder.0.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert_eq!(recorder.0.last(), Some(&(total, total)));
+    }
+
+    #[test]
+    fn test_cancelled_scan_keeps_partial_results() {
+        let first = b"found before cancelling".to_vec();
+        let second = b"never scanned".to_vec();
+        let regions = [region_over(&first), region_over(&second)];
+
+        // Cancel from the progress callback, as another thread would mid-scan
+        struct CancelAfterFirst<'a>(&'a AtomicBool);
+        impl ScanObserver for CancelAfterFirst<'_> {
+            fn progress(&mut self, _: u64, _: u64, _: &MemoryRegion) {
+                self.0.store(true, Ordering::Relaxed);
+            }
+        }
+        let cancel = AtomicBool::new(false);
+        let mut observer = CancelAfterFirst(&cancel);
+        let mut control = ScanControl::new().observer(&mut observer).cancel_flag(&cancel);
+
+        let mut reader = AutoReader::new(std::process::id() as i32);
+        let mut scan = Scan::default();
+        let options = ScanOptions::default();
+        let stopped = extract_strings(&mut reader, &regions, &options, &mut scan, &mut control);
+
+        assert_eq!(stopped, Some(StopReason::Cancelled));
+        assert_eq!(scan.regions.len(), 1);
+        assert_eq!(scan.results.len(), 1);
+        assert_eq!(scan.results[0].value, "found before cancelling");
+
+        let mut control = ScanControl::new().deadline(Some(Instant::now()));
+        let mut scan = Scan::default();
+        let stopped = extract_strings(&mut reader, &regions, &options, &mut scan, &mut control);
+        assert_eq!(stopped, Some(StopReason::Timeout));
+        assert!(scan.results.is_empty());
+    }
+}
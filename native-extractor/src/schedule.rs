@@ -0,0 +1,354 @@
+//! String scans rerun on a schedule, reporting only what changed
+//!
+//! Monitoring an app for hours wants the strings that appeared and went
+//! between two looks, not every string at every look. Each [`Schedule`]
+//! has a thread of its own that reruns a scan once per interval and hands
+//! the strings new and gone since the run before to a callback, skipping
+//! runs that changed nothing. The first run, made as the schedule starts,
+//! is the baseline and isn't reported.
+
+use crate::diff::SnapshotDiff;
+use crate::error::ExtractError;
+use crate::scan::{self, ExtractedString};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Shortest interval between two runs
+pub const MIN_INTERVAL_SECS: u64 = 1;
+
+/// The `schedule` object of the options `scheduleScan` takes
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ScheduleOptions {
+    /// Time from the start of one run to the next, in seconds
+    pub interval_secs: u64,
+    /// Runs after the baseline before the schedule ends by itself; unset
+    /// runs until cancelled
+    pub max_runs: Option<u64>,
+}
+
+impl Default for ScheduleOptions {
+    fn default() -> Self {
+        ScheduleOptions {
+            interval_secs: 60,
+            max_runs: None,
+        }
+    }
+}
+
+impl ScheduleOptions {
+    /// Parse the `schedule` object of the options JSON
+    pub fn from_json(json: &str) -> Result<Self, ExtractError> {
+        let options: ScheduleOptions = scan::parse_object(json, "schedule")?;
+        if options.interval_secs < MIN_INTERVAL_SECS {
+            return Err(ExtractError::InvalidArgument(format!(
+                "Invalid schedule options: intervalSecs must be at least {}",
+                MIN_INTERVAL_SECS
+            )));
+        }
+        Ok(options)
+    }
+}
+
+/// The strings one run found changed since the run before
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Delta {
+    /// Runs since the baseline, this one included
+    pub run: u64,
+    /// Time since the run before
+    pub elapsed_ms: u64,
+    /// Strings the run before didn't hold, the first place each was found
+    pub new_strings: Vec<ExtractedString>,
+    /// Strings the run before held that are gone, sorted
+    pub gone_strings: Vec<String>,
+}
+
+/// Reruns the scan, comparing it with the run before
+pub type Run = Box<dyn FnMut() -> Result<SnapshotDiff, ExtractError> + Send>;
+
+/// Opens the process and makes the baseline run, on the schedule's thread
+pub type Setup = Box<dyn FnOnce() -> Result<Run, ExtractError> + Send>;
+
+/// Receives each delta, on the schedule's thread; an error ends the
+/// schedule
+pub type Notify = Box<dyn FnMut(&Delta) -> Result<(), String> + Send>;
+
+/// How a schedule has gone so far
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduleStatus {
+    /// Runs made after the baseline
+    pub runs: u64,
+    /// Deltas passed to the callback
+    pub deltas: u64,
+    /// Runs that failed
+    pub failures: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    /// Whether the schedule stopped by itself, because the process exited,
+    /// the callback failed or `maxRuns` were made
+    pub ended: bool,
+}
+
+/// A registered schedule, as listed
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduleInfo {
+    pub handle: i64,
+    pub pid: i32,
+    pub interval_secs: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_runs: Option<u64>,
+    #[serde(flatten)]
+    pub status: ScheduleStatus,
+}
+
+/// A scan being rerun, until dropped
+pub struct Schedule {
+    info: ScheduleInfo,
+    status: Arc<Mutex<ScheduleStatus>>,
+    /// Wakes the runner thread to stop it
+    stop: Option<mpsc::Sender<()>>,
+    runner: Option<JoinHandle<()>>,
+}
+
+impl Schedule {
+    /// Start the thread that runs `setup` and then what it returns once per
+    /// interval, calling `notify` with each delta.
+    ///
+    /// Fails with the error of `setup`, so a missing process or bad options
+    /// are reported rather than retried forever. Everything runs on the
+    /// runner thread, which a ptrace reader needs.
+    pub fn start(
+        pid: i32,
+        options: &ScheduleOptions,
+        setup: Setup,
+        mut notify: Notify,
+    ) -> Result<Self, ExtractError> {
+        let status = Arc::new(Mutex::new(ScheduleStatus::default()));
+        let (stop, wake) = mpsc::channel::<()>();
+        let (started, first) = mpsc::sync_channel::<Result<(), ExtractError>>(1);
+        let interval = Duration::from_secs(options.interval_secs);
+        let max_runs = options.max_runs.unwrap_or(u64::MAX);
+        let runner = {
+            let status = Arc::clone(&status);
+            thread::spawn(move || {
+                let mut run = match setup() {
+                    Ok(run) => run,
+                    Err(e) => {
+                        let _ = started.send(Err(e));
+                        return;
+                    }
+                };
+                let _ = started.send(Ok(()));
+
+                let mut next = Instant::now() + interval;
+                for number in 1..=max_runs {
+                    match wake.recv_timeout(next.saturating_duration_since(Instant::now())) {
+                        Err(RecvTimeoutError::Timeout) => {}
+                        _ => return,
+                    }
+                    let mut round = ScheduleStatus {
+                        runs: 1,
+                        ..ScheduleStatus::default()
+                    };
+                    let mut failed = None;
+                    match run() {
+                        Ok(diff)
+                            if diff.new_strings.is_empty() && diff.gone_strings.is_empty() => {}
+                        Ok(diff) => {
+                            let delta = Delta {
+                                run: number,
+                                elapsed_ms: diff.elapsed_ms,
+                                new_strings: diff.new_strings,
+                                gone_strings: diff.gone_strings,
+                            };
+                            match notify(&delta) {
+                                Ok(()) => round.deltas += 1,
+                                Err(e) => failed = Some(e),
+                            }
+                        }
+                        Err(ExtractError::NoSuchProcess(e)) => failed = Some(e),
+                        Err(e) => {
+                            round.failures += 1;
+                            round.last_error = Some(e.to_string());
+                        }
+                    }
+
+                    let mut status = status.lock().unwrap_or_else(|e| e.into_inner());
+                    status.runs += round.runs;
+                    status.deltas += round.deltas;
+                    status.failures += round.failures;
+                    if round.last_error.is_some() {
+                        status.last_error = round.last_error;
+                    }
+                    if let Some(e) = failed {
+                        status.last_error = Some(e);
+                        status.ended = true;
+                        return;
+                    }
+                    drop(status);
+                    // After a slow run, carry on from now rather than catching up
+                    next = (next + interval).max(Instant::now());
+                }
+                status.lock().unwrap_or_else(|e| e.into_inner()).ended = true;
+            })
+        };
+        match first.recv() {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => return Err(e),
+            Err(_) => return Err(ExtractError::Io("Schedule thread stopped".into())),
+        }
+        Ok(Schedule {
+            info: ScheduleInfo {
+                handle: 0,
+                pid,
+                interval_secs: options.interval_secs,
+                max_runs: options.max_runs,
+                status: ScheduleStatus::default(),
+            },
+            status,
+            stop: Some(stop),
+            runner: Some(runner),
+        })
+    }
+
+    /// The schedule as it stands now
+    pub fn info(&self) -> ScheduleInfo {
+        ScheduleInfo {
+            status: self.status.lock().unwrap_or_else(|e| e.into_inner()).clone(),
+            ..self.info.clone()
+        }
+    }
+}
+
+impl Drop for Schedule {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(runner) = self.runner.take() {
+            // Released from its own callback, the thread stops by itself
+            // once the callback returns
+            if runner.thread().id() != thread::current().id() {
+                let _ = runner.join();
+            }
+        }
+    }
+}
+
+type ScheduleTable = Mutex<HashMap<i64, Schedule>>;
+
+/// Handles are never reused, so a stale handle can't cancel a newer
+/// schedule
+static NEXT_HANDLE: AtomicI64 = AtomicI64::new(1);
+
+fn schedules() -> &'static ScheduleTable {
+    static SCHEDULES: OnceLock<ScheduleTable> = OnceLock::new();
+    SCHEDULES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Store a schedule, returning its handle. Handles are never 0.
+pub fn register(mut schedule: Schedule) -> i64 {
+    let handle = NEXT_HANDLE.fetch_add(1, Ordering::Relaxed);
+    schedule.info.handle = handle;
+    schedules().lock().unwrap_or_else(|e| e.into_inner()).insert(handle, schedule);
+    handle
+}
+
+/// Stop a schedule, waiting for any run under way to finish. Returns false
+/// for an unknown handle.
+pub fn release(handle: i64) -> bool {
+    let schedule = schedules().lock().unwrap_or_else(|e| e.into_inner()).remove(&handle);
+    // Dropped outside the lock, as it waits for the runner thread
+    schedule.is_some()
+}
+
+/// Every registered schedule, including those that ended, in the order
+/// they were made
+pub fn list() -> Vec<ScheduleInfo> {
+    let schedules = schedules().lock().unwrap_or_else(|e| e.into_inner());
+    let mut list: Vec<ScheduleInfo> = schedules.values().map(Schedule::info).collect();
+    list.sort_unstable_by_key(|info| info.handle);
+    list
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scan::Location;
+    use crate::strings::StringEncoding;
+
+    fn diff(new: &[&str], gone: &[&str]) -> SnapshotDiff {
+        SnapshotDiff {
+            elapsed_ms: 1000,
+            new_regions: Vec::new(),
+            removed_regions: Vec::new(),
+            changed_regions: Vec::new(),
+            new_strings: new
+                .iter()
+                .map(|value| ExtractedString {
+                    location: Location::new(&"[heap]".into(), 0x1000, 0x1000),
+                    encoding: StringEncoding::Ascii,
+                    value: value.to_string(),
+                    count: None,
+                    addresses: Vec::new(),
+                })
+                .collect(),
+            gone_strings: gone.iter().map(|value| value.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_reports_deltas() {
+        let options = ScheduleOptions {
+            interval_secs: 0,
+            max_runs: Some(3),
+        };
+        let mut runs = vec![
+            Ok(diff(&["token=abc"], &[])),
+            Ok(diff(&[], &[])),
+            Ok(diff(&[], &["token=abc"])),
+        ]
+        .into_iter();
+        let setup: Setup = Box::new(move || Ok(Box::new(move || runs.next().unwrap()) as Run));
+        let (send, deltas) = mpsc::channel();
+        let notify: Notify =
+            Box::new(move |delta| send.send(delta.clone()).map_err(|e| e.to_string()));
+        let handle = register(Schedule::start(1, &options, setup, notify).unwrap());
+
+        let delta = deltas.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!((delta.run, delta.new_strings[0].value.as_str()), (1, "token=abc"));
+        // The run that changed nothing isn't reported
+        let delta = deltas.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!((delta.run, delta.gone_strings), (3, vec!["token=abc".to_string()]));
+
+        let info = || list().into_iter().find(|info| info.handle == handle).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !info().status.ended && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(1));
+        }
+        let status = info().status;
+        assert_eq!((status.runs, status.deltas, status.ended), (3, 2, true));
+        assert!(release(handle));
+        assert!(!release(handle));
+
+        let setup: Setup = Box::new(|| Err(ExtractError::NoSuchProcess("gone".into())));
+        let notify: Notify = Box::new(|_| Ok(()));
+        let started = Schedule::start(1, &options, setup, notify);
+        assert!(matches!(started, Err(ExtractError::NoSuchProcess(_))));
+    }
+
+    #[test]
+    fn test_schedule_options_from_json() {
+        let json = r#"{"minLength": 8, "schedule": {"intervalSecs": 300, "maxRuns": 12}}"#;
+        let options = ScheduleOptions::from_json(json).unwrap();
+        assert_eq!((options.interval_secs, options.max_runs), (300, Some(12)));
+        assert_eq!(ScheduleOptions::from_json("").unwrap().interval_secs, 60);
+        assert!(ScheduleOptions::from_json(r#"{"schedule": {"intervalSecs": 0}}"#).is_err());
+    }
+}
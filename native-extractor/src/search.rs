@@ -0,0 +1,266 @@
+//! Pattern search over process memory
+//!
+//! Patterns are matched against raw bytes as they are read. To catch matches
+//! that straddle a chunk boundary, the last [`DEFAULT_OVERLAP`] bytes of each
+//! chunk are kept and searched again together with the next one; that is
+//! also the longest match guaranteed to be reported in full.
+
+use aho_corasick::{AhoCorasick, AhoCorasickBuilder, MatchKind};
+use regex::bytes::{Regex, RegexBuilder};
+
+/// Bytes of look-behind kept between chunks
+pub const DEFAULT_OVERLAP: usize = 4096;
+
+/// `java.util.regex.Pattern` flag values honored by [`compile_regex`]
+pub const FLAG_CASE_INSENSITIVE: i32 = 0x02;
+pub const FLAG_COMMENTS: i32 = 0x04;
+pub const FLAG_MULTILINE: i32 = 0x08;
+pub const FLAG_DOTALL: i32 = 0x20;
+
+/// Compile a byte regex, taking flags with the same values as
+/// `java.util.regex.Pattern` so Kotlin callers can pass those constants.
+/// Unsupported flags are ignored.
+pub fn compile_regex(pattern: &str, flags: i32) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(flags & FLAG_CASE_INSENSITIVE != 0)
+        .ignore_whitespace(flags & FLAG_COMMENTS != 0)
+        .multi_line(flags & FLAG_MULTILINE != 0)
+        .dot_matches_new_line(flags & FLAG_DOTALL != 0)
+        .build()
+}
+
+/// Build a single-pass matcher for many literal keywords.
+///
+/// Every occurrence of every keyword is reported, including overlapping
+/// ones, so `pass` and `password` both hit on the same bytes.
+pub fn build_keyword_matcher<K: AsRef<[u8]>>(
+    keywords: &[K],
+    case_insensitive: bool,
+) -> Result<AhoCorasick, aho_corasick::BuildError> {
+    AhoCorasickBuilder::new()
+        .match_kind(MatchKind::Standard)
+        .ascii_case_insensitive(case_insensitive)
+        .build(keywords)
+}
+
+/// The byte regex for a hex pattern such as `4d 5a ?? 00`: pairs of hex
+/// digits for bytes and `??` for any byte, with whitespace ignored
+pub fn hex_pattern_regex(pattern: &str) -> Result<String, String> {
+    let digits: Vec<char> = pattern.chars().filter(|c| !c.is_whitespace()).collect();
+    if digits.is_empty() || !digits.len().is_multiple_of(2) {
+        return Err(format!("Hex pattern needs whole bytes: {}", pattern));
+    }
+    let mut regex = String::from("(?s-u)");
+    for pair in digits.chunks(2) {
+        match pair {
+            ['?', '?'] => regex.push('.'),
+            [high, low] if high.is_ascii_hexdigit() && low.is_ascii_hexdigit() => {
+                regex.push_str(&format!("\\x{}{}", high, low));
+            }
+            _ => return Err(format!("Invalid byte in hex pattern: {}{}", pair[0], pair[1])),
+        }
+    }
+    Ok(regex)
+}
+
+/// A pattern match found in memory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hit {
+    /// Virtual address of the first matched byte
+    pub address: u64,
+    /// Index of the pattern that matched, for matchers with several patterns
+    pub pattern: usize,
+    pub bytes: Vec<u8>,
+}
+
+/// Something that can find pattern occurrences in a haystack
+pub trait Matcher {
+    /// Call `emit(start, end, pattern)` for each match
+    fn find_all(&self, haystack: &[u8], emit: &mut dyn FnMut(usize, usize, usize));
+
+    /// Whether matches may overlap each other. Non-overlapping matchers get
+    /// matches that start inside an already reported one suppressed.
+    fn overlapping(&self) -> bool {
+        false
+    }
+
+    /// Whether that only holds between matches of the same pattern, as for
+    /// a set of independent regexes
+    fn disjoint_per_pattern(&self) -> bool {
+        false
+    }
+}
+
+impl Matcher for Regex {
+    fn find_all(&self, haystack: &[u8], emit: &mut dyn FnMut(usize, usize, usize)) {
+        for found in self.find_iter(haystack) {
+            emit(found.start(), found.end(), 0);
+        }
+    }
+}
+
+/// Several regexes searched independently, each its own pattern: matches of
+/// different regexes may overlap, as a token inside a longer secret does
+impl Matcher for Vec<Regex> {
+    fn find_all(&self, haystack: &[u8], emit: &mut dyn FnMut(usize, usize, usize)) {
+        for (pattern, regex) in self.iter().enumerate() {
+            for found in regex.find_iter(haystack) {
+                emit(found.start(), found.end(), pattern);
+            }
+        }
+    }
+
+    fn disjoint_per_pattern(&self) -> bool {
+        true
+    }
+}
+
+impl Matcher for AhoCorasick {
+    fn find_all(&self, haystack: &[u8], emit: &mut dyn FnMut(usize, usize, usize)) {
+        if self.overlapping() {
+            for found in self.find_overlapping_iter(haystack) {
+                emit(found.start(), found.end(), found.pattern().as_usize());
+            }
+        } else {
+            for found in self.find_iter(haystack) {
+                emit(found.start(), found.end(), found.pattern().as_usize());
+            }
+        }
+    }
+
+    fn overlapping(&self) -> bool {
+        self.match_kind() == MatchKind::Standard
+    }
+}
+
+/// Incremental search over a stream of chunks for one [`Matcher`]
+pub struct PatternScanner<M> {
+    matcher: M,
+    overlap: usize,
+    buffer: Vec<u8>,
+    base: u64,
+    /// End address of the last reported hit, or of each pattern's, so a
+    /// non-overlapping matcher doesn't report a match starting inside one it
+    /// already reported
+    reported_until: Vec<u64>,
+}
+
+impl<M: Matcher> PatternScanner<M> {
+    pub fn new(matcher: M) -> Self {
+        Self::with_overlap(matcher, DEFAULT_OVERLAP)
+    }
+
+    pub fn with_overlap(matcher: M, overlap: usize) -> Self {
+        PatternScanner {
+            matcher,
+            overlap,
+            buffer: Vec::new(),
+            base: 0,
+            reported_until: Vec::new(),
+        }
+    }
+
+    /// Search a chunk read from `address`, appending hits to `out`
+    pub fn feed(&mut self, address: u64, chunk: &[u8], out: &mut Vec<Hit>) {
+        if !self.buffer.is_empty() && self.base + self.buffer.len() as u64 != address {
+            self.finish(out);
+        }
+        if self.buffer.is_empty() {
+            self.base = address;
+        }
+        self.buffer.extend_from_slice(chunk);
+        self.scan(false, out);
+    }
+
+    /// Flush hits in the retained overlap at the end of a region
+    pub fn finish(&mut self, out: &mut Vec<Hit>) {
+        self.scan(true, out);
+        self.buffer.clear();
+    }
+
+    fn scan(&mut self, is_final: bool, out: &mut Vec<Hit>) {
+        // Only matches starting before `cut` have seen enough look-ahead;
+        // the rest are found again on the next pass
+        let cut = if is_final {
+            self.buffer.len()
+        } else {
+            self.buffer.len().saturating_sub(self.overlap)
+        };
+        if cut == 0 {
+            return;
+        }
+
+        let base = self.base;
+        let buffer = &self.buffer;
+        let overlapping = self.matcher.overlapping();
+        let per_pattern = self.matcher.disjoint_per_pattern();
+        let reported_until = &mut self.reported_until;
+        self.matcher.find_all(buffer, &mut |start, end, pattern| {
+            let address = base + start as u64;
+            let slot = if per_pattern { pattern } else { 0 };
+            if reported_until.len() <= slot {
+                reported_until.resize(slot + 1, 0);
+            }
+            let reported_until = &mut reported_until[slot];
+            if start < cut && end > start && (overlapping || address >= *reported_until) {
+                out.push(Hit {
+                    address,
+                    pattern,
+                    bytes: buffer[start..end].to_vec(),
+                });
+                *reported_until = base + end as u64;
+            }
+        });
+
+        self.buffer.drain(..cut);
+        self.base += cut as u64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search_chunks<M: Matcher>(matcher: M, overlap: usize, chunks: &[&[u8]]) -> Vec<Hit> {
+        let mut scanner = PatternScanner::with_overlap(matcher, overlap);
+        let mut hits = Vec::new();
+        let mut address = 0x1000;
+        for chunk in chunks {
+            scanner.feed(address, chunk, &mut hits);
+            address += chunk.len() as u64;
+        }
+        scanner.finish(&mut hits);
+        hits
+    }
+
+    #[test]
+    fn test_match_across_chunk_boundary_is_reported_once() {
+        let regex = compile_regex(r"token=[0-9a-f]+", 0).unwrap();
+        let hits = search_chunks(regex, 16, &[b"xxxxtoken=de", b"adbeef;yyyy", b"token=01;"]);
+
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].address, 0x1004);
+        assert_eq!(hits[0].bytes, b"token=deadbeef");
+        assert_eq!(hits[1].bytes, b"token=01");
+    }
+
+    #[test]
+    fn test_java_pattern_flags() {
+        let regex = compile_regex("secret", FLAG_CASE_INSENSITIVE).unwrap();
+        let hits = search_chunks(regex, 16, &[b"..SeCrEt.."]);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].address, 0x1002);
+
+        assert!(compile_regex("(unclosed", 0).is_err());
+    }
+
+    #[test]
+    fn test_keywords_report_overlapping_hits_across_chunks() {
+        let matcher = build_keyword_matcher(&["pass", "password", "alice"], true).unwrap();
+        let hits = search_chunks(matcher, 8, &[b"..PassW", b"ord..alice..pass"]);
+
+        let found: Vec<(u64, usize)> = hits.iter().map(|hit| (hit.address, hit.pattern)).collect();
+        assert_eq!(found, [(0x1002, 0), (0x1002, 1), (0x100c, 2), (0x1013, 0)]);
+        assert_eq!(hits[1].bytes, b"PassWord");
+    }
+}
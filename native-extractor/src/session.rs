@@ -0,0 +1,339 @@
+//! Long-lived handles on a target process
+//!
+//! A [`Session`] keeps the memory reader and the parsed maps of one process
+//! open between JNI calls, so repeated scans and reads don't reopen /proc
+//! files or re-parse maps each time. Sessions are kept in a process-wide
+//! table and handed to Java as opaque `long` handles.
+
+use crate::diff::ProcessSnapshot;
+use crate::dump::LoadedDump;
+use crate::error::ExtractError;
+use crate::filter::RegionFilter;
+use crate::maps::{self, MemoryRegion};
+use crate::patch::Patches;
+use crate::pointers::PointerMap;
+use crate::reader::{self, BufferPool, MemoryReader, ReaderStrategy};
+use crate::report::{OutputFormat, ProcessInfo};
+use crate::scan::ScanControl;
+use crate::value::ValueSearch;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// An open process, or a dump of one: its reader and cached region list
+pub struct Session {
+    pid: i32,
+    reader: Box<dyn MemoryReader>,
+    regions: Vec<MemoryRegion>,
+    /// Set for a dump, from its manifest if it had one; a live process's
+    /// identity is read from /proc when needed
+    saved: Option<Option<ProcessInfo>>,
+    /// Set by [`cancel`] to stop the scan running on this session
+    cancel: Arc<AtomicBool>,
+    /// Read buffers shared by the scans run on this session
+    buffers: Arc<BufferPool>,
+    /// What the last value scan found, narrowed by any refinements since,
+    /// or the snapshot taken to refine
+    value_search: Option<ValueSearch>,
+    /// The snapshot the next diff compares with
+    snapshot: Option<ProcessSnapshot>,
+    /// The pointers found by the last pointer map built
+    pointer_map: Option<PointerMap>,
+    /// The bytes writes to the process replaced, to restore
+    patches: Patches,
+    /// How calls on the session render results their options don't give a
+    /// format for
+    output_format: OutputFormat,
+}
+
+/// What a scan starting on a session runs with
+pub struct ScanStart {
+    /// Set when the scan is cancelled
+    pub cancel: Arc<AtomicBool>,
+    pub buffers: Arc<BufferPool>,
+}
+
+impl ScanStart {
+    /// Controls for the scan, stopping on cancel and reading into the
+    /// session's buffers
+    pub fn control(&self) -> ScanControl<'_> {
+        ScanControl::new().cancel_flag(&self.cancel).buffers(&self.buffers)
+    }
+}
+
+impl Session {
+    /// Parse the process's maps and open a reader for it.
+    ///
+    /// Note that ptrace only works from the thread that attached, so with
+    /// the ptrace strategy (or auto falling back to it) the session should
+    /// be used from the thread that opened it.
+    pub fn open(pid: i32, strategy: ReaderStrategy) -> Result<Self, ExtractError> {
+        let regions = read_regions(pid)?;
+        let reader = reader::open_reader(pid, strategy)
+            .map_err(|e| ExtractError::from_io("Failed to open process memory", &e))?;
+
+        Ok(Session {
+            pid,
+            reader,
+            regions,
+            saved: None,
+            cancel: Arc::new(AtomicBool::new(false)),
+            buffers: Arc::new(BufferPool::new()),
+            value_search: None,
+            snapshot: None,
+            pointer_map: None,
+            patches: Patches::default(),
+            output_format: OutputFormat::Text,
+        })
+    }
+
+    /// A session on a dump instead of a live process, for offline analysis
+    pub fn from_dump(dump: LoadedDump) -> Self {
+        Session {
+            pid: dump.pid,
+            reader: dump.reader,
+            regions: dump.regions,
+            saved: Some(dump.process),
+            cancel: Arc::new(AtomicBool::new(false)),
+            buffers: Arc::new(BufferPool::new()),
+            value_search: None,
+            snapshot: None,
+            pointer_map: None,
+            patches: Patches::default(),
+            output_format: OutputFormat::Text,
+        }
+    }
+
+    /// PID of the process, or of the process a dump was taken of (0 if
+    /// the dump doesn't say)
+    pub fn pid(&self) -> i32 {
+        self.pid
+    }
+
+    /// How calls on the session render results by default
+    pub fn output_format(&self) -> OutputFormat {
+        self.output_format
+    }
+
+    pub fn set_output_format(&mut self, format: OutputFormat) {
+        self.output_format = format;
+    }
+
+    /// Whether this session reads a live process rather than a dump
+    pub fn is_live(&self) -> bool {
+        self.saved.is_none()
+    }
+
+    /// Identity of a dumped process, if the dump recorded it
+    pub fn saved_process(&self) -> Option<&ProcessInfo> {
+        self.saved.as_ref().and_then(Option::as_ref)
+    }
+
+    /// Re-read the maps, picking up mappings created or removed since the
+    /// session was opened. A dump's regions never change.
+    pub fn refresh_regions(&mut self) -> Result<(), ExtractError> {
+        if self.is_live() {
+            self.regions = read_regions(self.pid)?;
+        }
+        Ok(())
+    }
+
+    /// Re-read the regions from smaps, so that each carries its memory usage.
+    /// A dump's regions keep whatever usage was saved with them.
+    pub fn refresh_usage(&mut self) -> Result<(), ExtractError> {
+        if self.is_live() {
+            self.regions = maps::read_smaps(self.pid)
+                .map_err(|e| ExtractError::from_io("Failed to read smaps", &e))?;
+        }
+        Ok(())
+    }
+
+    /// Every mapping, readable or not, as of the last refresh
+    pub fn all_regions(&self) -> &[MemoryRegion] {
+        &self.regions
+    }
+
+    /// The readable regions accepted by `filter`
+    pub fn regions(&self, filter: &RegionFilter) -> Vec<MemoryRegion> {
+        self.regions
+            .iter()
+            .filter(|region| region.perms.read && filter.matches(region))
+            .cloned()
+            .collect()
+    }
+
+    /// [`Session::regions`], first reading the regions' usage if `filter`
+    /// selects by residency and it hasn't been read yet
+    pub fn select(&mut self, filter: &RegionFilter) -> Result<Vec<MemoryRegion>, ExtractError> {
+        let unknown = self.regions.iter().any(|region| region.usage.is_none());
+        if filter.min_resident > 0 && unknown {
+            self.refresh_usage()?;
+        }
+        Ok(self.regions(filter))
+    }
+
+    pub fn reader(&mut self) -> &mut dyn MemoryReader {
+        self.reader.as_mut()
+    }
+
+    /// Name of the reader backend in use
+    pub fn reader_name(&self) -> &'static str {
+        self.reader.name()
+    }
+
+    /// Take what the last value scan or snapshot left to refine
+    pub fn take_value_search(&mut self) -> Option<ValueSearch> {
+        self.value_search.take()
+    }
+
+    /// Keep `search` to refine, in place of any before it
+    pub fn set_value_search(&mut self, search: ValueSearch) {
+        self.value_search = Some(search);
+    }
+
+    /// Take the snapshot the last diff, or the first snapshot, left
+    pub fn take_snapshot(&mut self) -> Option<ProcessSnapshot> {
+        self.snapshot.take()
+    }
+
+    /// Keep `snapshot` for the next diff to compare with
+    pub fn set_snapshot(&mut self, snapshot: ProcessSnapshot) {
+        self.snapshot = Some(snapshot);
+    }
+
+    /// The last pointer map built on this session
+    pub fn pointer_map(&self) -> Option<&PointerMap> {
+        self.pointer_map.as_ref()
+    }
+
+    pub fn set_pointer_map(&mut self, map: PointerMap) {
+        self.pointer_map = Some(map);
+    }
+
+    /// The writes made through this session that can still be undone
+    pub fn patches(&mut self) -> &mut Patches {
+        &mut self.patches
+    }
+
+    /// Start a scan, whose [`ScanStart::control`] stops it when the session
+    /// is cancelled. Any cancel requested before this call is discarded, so
+    /// cancelling an idle session doesn't stop the next scan.
+    pub fn start_scan(&self) -> ScanStart {
+        self.cancel.store(false, Ordering::Relaxed);
+        ScanStart {
+            cancel: Arc::clone(&self.cancel),
+            buffers: Arc::clone(&self.buffers),
+        }
+    }
+}
+
+fn read_regions(pid: i32) -> Result<Vec<MemoryRegion>, ExtractError> {
+    maps::read_maps(pid).map_err(|e| ExtractError::from_io("Failed to read maps", &e))
+}
+
+/// A registered session, with its cancel flag reachable without taking the
+/// lock a running scan holds
+struct Entry {
+    session: Arc<Mutex<Session>>,
+    cancel: Arc<AtomicBool>,
+}
+
+type SessionTable = Mutex<HashMap<i64, Entry>>;
+
+/// Handles are never reused, so a stale handle can't reach a newer session
+static NEXT_HANDLE: AtomicI64 = AtomicI64::new(1);
+
+fn sessions() -> &'static SessionTable {
+    static SESSIONS: OnceLock<SessionTable> = OnceLock::new();
+    SESSIONS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Store a session, returning its handle. Handles are never 0.
+pub fn register(session: Session) -> i64 {
+    let handle = NEXT_HANDLE.fetch_add(1, Ordering::Relaxed);
+    let entry = Entry {
+        cancel: Arc::clone(&session.cancel),
+        session: Arc::new(Mutex::new(session)),
+    };
+    sessions()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(handle, entry);
+    handle
+}
+
+/// Look up an open session
+pub fn get(handle: i64) -> Option<Arc<Mutex<Session>>> {
+    sessions()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&handle)
+        .map(|entry| Arc::clone(&entry.session))
+}
+
+/// Ask the scan running on a session to stop; it returns the results found
+/// so far. Returns false for an unknown handle.
+pub fn cancel(handle: i64) -> bool {
+    let sessions = sessions().lock().unwrap_or_else(|e| e.into_inner());
+    match sessions.get(&handle) {
+        Some(entry) => {
+            entry.cancel.store(true, Ordering::Relaxed);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Remove a session; it is closed once any call still using it returns.
+/// Returns false for an unknown handle.
+pub fn close(handle: i64) -> bool {
+    sessions()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&handle)
+        .is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_lifecycle() {
+        let mut session =
+            Session::open(std::process::id() as i32, ReaderStrategy::Procfs).unwrap();
+        assert!(session.regions(&RegionFilter::default()).iter().all(|r| r.perms.read));
+        let resident = RegionFilter {
+            min_resident: 4096,
+            ..RegionFilter::default()
+        };
+        let selected = session.select(&resident).unwrap();
+        assert!(!selected.is_empty());
+        assert!(selected.iter().all(|r| r.usage.is_some_and(|usage| usage.rss >= 4096)));
+
+        let handle = register(session);
+        assert_ne!(handle, 0);
+
+        let data = b"session read".to_vec();
+        {
+            let session = get(handle).unwrap();
+            let mut session = session.lock().unwrap();
+            session.refresh_regions().unwrap();
+
+            let mut buf = vec![0u8; data.len()];
+            reader::read_into(session.reader(), data.as_ptr() as u64, &mut buf).unwrap();
+            assert_eq!(buf, data);
+        }
+
+        assert!(cancel(handle));
+        let flag = get(handle).unwrap().lock().unwrap().start_scan().cancel;
+        assert!(!flag.load(Ordering::Relaxed));
+        assert!(cancel(handle));
+        assert!(flag.load(Ordering::Relaxed));
+
+        assert!(close(handle));
+        assert!(get(handle).is_none());
+        assert!(!close(handle));
+        assert!(!cancel(handle));
+    }
+}
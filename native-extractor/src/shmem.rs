@@ -0,0 +1,284 @@
+//! Enumeration and dumps of memfd and ashmem objects
+//!
+//! Packers increasingly decrypt code into a memfd and map it from there, so
+//! it never touches the file system. Such objects show up in two places:
+//! mappings of them in the maps, as `/memfd:<name> (deleted)` or
+//! `/dev/ashmem/<name> (deleted)`, and descriptors still open on them. Both
+//! are gathered per object, told apart by inode. An object with an open
+//! descriptor is dumped in full through `/proc/[pid]/fd`, including the
+//! parts that aren't mapped; one whose descriptor was closed after mapping
+//! it only through its mappings.
+
+use crate::dump::{self, DumpedFile};
+use crate::maps::MemoryRegion;
+use crate::reader::MemoryReader;
+use crate::scan::{RegionResult, ScanControl, ScanSink, StopReason};
+use crate::sha256::Sha256;
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::time::Instant;
+
+const MEMFD_PREFIX: &str = "/memfd:";
+const ASHMEM_PREFIX: &str = "/dev/ashmem";
+const DELETED: &str = " (deleted)";
+
+/// The kind of a shared memory object
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SharedMemoryKind {
+    Memfd,
+    Ashmem,
+}
+
+/// A memfd or ashmem object of a process
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SharedMemory {
+    /// Name given at creation, e.g. `jit-cache`
+    pub name: String,
+    pub kind: SharedMemoryKind,
+    /// Path as the kernel shows it
+    pub path: String,
+    pub inode: u64,
+    /// Descriptors open on the object
+    pub fds: Vec<i32>,
+    /// Size of the object, known when a descriptor is open on it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    pub mappings: Vec<MemoryRegion>,
+}
+
+/// The kind and name of a shared memory object at `path`, if it is one
+pub fn parse_path(path: &str) -> Option<(SharedMemoryKind, String)> {
+    let path = path.strip_suffix(DELETED).unwrap_or(path);
+    if let Some(name) = path.strip_prefix(MEMFD_PREFIX) {
+        return Some((SharedMemoryKind::Memfd, name.to_string()));
+    }
+    let name = path.strip_prefix(ASHMEM_PREFIX)?;
+    Some((SharedMemoryKind::Ashmem, name.trim_start_matches('/').to_string()))
+}
+
+/// List the memfd and ashmem objects `pid` maps, among its `regions`, or
+/// holds a descriptor on, in order of first mapping then descriptor.
+///
+/// Descriptors closed while they are listed are skipped.
+pub fn list_shared_memory(pid: i32, regions: &[MemoryRegion]) -> io::Result<Vec<SharedMemory>> {
+    let mut objects: Vec<SharedMemory> = Vec::new();
+    let mut add = |path: &str, inode: u64| -> Option<usize> {
+        let (kind, name) = parse_path(path)?;
+        let index = objects
+            .iter()
+            .position(|object| object.inode == inode && object.path == path)
+            .unwrap_or_else(|| {
+                objects.push(SharedMemory {
+                    name,
+                    kind,
+                    path: path.to_string(),
+                    inode,
+                    fds: Vec::new(),
+                    size: None,
+                    mappings: Vec::new(),
+                });
+                objects.len() - 1
+            });
+        Some(index)
+    };
+
+    let mut mapped = Vec::new();
+    for region in regions {
+        if let Some(index) = region.path.as_deref().and_then(|path| add(path, region.inode)) {
+            mapped.push((index, region.clone()));
+        }
+    }
+    let mut fds = Vec::new();
+    for entry in fs::read_dir(format!("/proc/{}/fd", pid))? {
+        let entry = entry?;
+        let Some(fd) = entry.file_name().to_str().and_then(|name| name.parse::<i32>().ok()) else {
+            continue;
+        };
+        let (Ok(target), Ok(metadata)) = (fs::read_link(entry.path()), fs::metadata(entry.path()))
+        else {
+            continue;
+        };
+        if let Some(index) = add(&target.to_string_lossy(), metadata.ino()) {
+            fds.push((index, fd, metadata.len()));
+        }
+    }
+
+    for (index, region) in mapped {
+        objects[index].mappings.push(region);
+    }
+    fds.sort_unstable();
+    for (index, fd, size) in fds {
+        objects[index].fds.push(fd);
+        objects[index].size = Some(size);
+    }
+    Ok(objects)
+}
+
+/// File an object read through descriptor `fd` is dumped to
+pub fn object_file_name(object: &SharedMemory, fd: i32) -> String {
+    let name: String = object
+        .name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || "-_.".contains(c) { c } else { '_' })
+        .collect();
+    format!("{}-fd{}.bin", name, fd)
+}
+
+/// Write `object` to `dir`: the whole object through its first open
+/// descriptor if it has one, else each of its mappings as [`dump`] would.
+///
+/// Failing to write to `dir` is an error; an object that can't be read is
+/// recorded in `sink` as an unreadable region.
+pub fn dump_shared_memory(
+    pid: i32,
+    object: &SharedMemory,
+    reader: &mut dyn MemoryReader,
+    dir: &Path,
+    sink: &mut dyn ScanSink<DumpedFile>,
+    control: &mut ScanControl,
+) -> io::Result<Option<StopReason>> {
+    let Some(&fd) = object.fds.first() else {
+        return dump::dump_regions(reader, &object.mappings, dir, sink, control);
+    };
+    if let Some(reason) = control.check() {
+        return Ok(Some(reason));
+    }
+    let started = Instant::now();
+
+    let region = MemoryRegion {
+        start: 0,
+        end: object.size.unwrap_or_default(),
+        perms: object.mappings.first().map(|region| region.perms).unwrap_or_default(),
+        offset: 0,
+        dev: String::new(),
+        inode: object.inode,
+        path: Some(object.path.clone()),
+        usage: None,
+    };
+    let mut source = match File::open(format!("/proc/{}/fd/{}", pid, fd)) {
+        Ok(source) => source,
+        Err(e) => {
+            sink.region(RegionResult {
+                region,
+                bytes_read: 0,
+                error: Some(e.to_string()),
+                skipped: Vec::new(),
+                elapsed_us: 0,
+                interrupted: false,
+                pid: None,
+            });
+            return Ok(None);
+        }
+    };
+
+    let name = object_file_name(object, fd);
+    let mut out = BufWriter::new(File::create(dir.join(&name))?);
+    let mut hash = Sha256::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    let mut size = 0u64;
+    let mut error = None;
+    loop {
+        let read = match source.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => {
+                error = Some(e.to_string());
+                break;
+            }
+        };
+        out.write_all(&buffer[..read])?;
+        hash.update(&buffer[..read]);
+        size += read as u64;
+    }
+    out.flush()?;
+
+    sink.result(DumpedFile {
+        start: 0,
+        file: name,
+        size,
+        sha256: hash.finish_hex(),
+    });
+    sink.region(RegionResult {
+        region,
+        bytes_read: size,
+        error: error.filter(|_| size == 0),
+        skipped: Vec::new(),
+        elapsed_us: started.elapsed().as_micros() as u64,
+        interrupted: false,
+        pid: None,
+    });
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps;
+    use crate::reader::ProcfsReader;
+    use crate::scan::Scan;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+    #[test]
+    fn test_parse_path() {
+        assert_eq!(
+            parse_path("/memfd:jit-cache (deleted)"),
+            Some((SharedMemoryKind::Memfd, "jit-cache".to_string()))
+        );
+        assert_eq!(
+            parse_path("/dev/ashmem/dalvik-data-code-cache (deleted)"),
+            Some((SharedMemoryKind::Ashmem, "dalvik-data-code-cache".to_string()))
+        );
+        assert_eq!(parse_path("/system/lib64/libc.so"), None);
+    }
+
+    #[test]
+    fn test_lists_and_dumps_a_memfd() {
+        let pid = std::process::id() as i32;
+        let name = format!("shmem-test-{}", pid);
+        let cname = std::ffi::CString::new(name.clone()).unwrap();
+        // SAFETY: a new descriptor, owned from here on
+        let fd = unsafe { OwnedFd::from_raw_fd(libc::memfd_create(cname.as_ptr(), 0)) };
+        let mut file = File::from(fd);
+        let data = vec![0x42u8; 3 * 4096];
+        file.write_all(&data).unwrap();
+        // SAFETY: a shared mapping of the memfd, unmapped below
+        let mapped = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                4096,
+                libc::PROT_READ,
+                libc::MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        assert_ne!(mapped, libc::MAP_FAILED);
+
+        let regions = maps::read_maps(pid).unwrap();
+        let objects = list_shared_memory(pid, &regions).unwrap();
+        // SAFETY: the mapping made above, no longer used
+        unsafe { libc::munmap(mapped, 4096) };
+        let object = objects.into_iter().find(|object| object.name == name).unwrap();
+        assert_eq!(object.kind, SharedMemoryKind::Memfd);
+        assert_eq!(object.fds, [file.as_raw_fd()]);
+        assert_eq!(object.size, Some(data.len() as u64));
+        assert_eq!(object.mappings.len(), 1);
+        assert_eq!(object.mappings[0].start, mapped as u64);
+
+        // The whole object is dumped, not just the mapped page
+        let dir = std::env::temp_dir().join(format!("shmem-test-{}", pid));
+        fs::create_dir_all(&dir).unwrap();
+        let mut reader = ProcfsReader::open(pid).unwrap();
+        let mut scan = Scan::default();
+        let mut control = ScanControl::new();
+        dump_shared_memory(pid, &object, &mut reader, &dir, &mut scan, &mut control).unwrap();
+        assert_eq!(fs::read(dir.join(&scan.results[0].file)).unwrap(), data);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
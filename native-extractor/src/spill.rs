@@ -0,0 +1,197 @@
+//! Results kept off the heap once a scan has found too many
+//!
+//! Scanning a multi-gigabyte process can find millions of strings, and
+//! holding all of them, then the report serialized from them, can get the
+//! calling app killed for running out of memory. A [`SpillSink`] keeps
+//! results in memory up to a budget and appends the rest to an NDJSON file
+//! of `string` lines in the [`crate::stream`] format. The report names the
+//! file; the caller reads the rest of the results from it and deletes it.
+
+use crate::scan::{ExtractedString, Located, RegionResult, Scan, ScanSink};
+use crate::stream::{NdjsonWriter, Record};
+use serde::Serialize;
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// Heap memory a result holds
+pub trait Footprint {
+    fn footprint(&self) -> usize;
+}
+
+impl Footprint for ExtractedString {
+    fn footprint(&self) -> usize {
+        // The region name is shared by every result in the region
+        mem::size_of::<Self>() + self.value.capacity() + self.addresses.capacity() * 8
+    }
+}
+
+/// Where the results past the memory budget went
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Spill {
+    /// The NDJSON file holding them
+    pub path: String,
+    /// How many there are
+    pub results: usize,
+}
+
+enum SpillFile {
+    Unopened,
+    Open {
+        path: PathBuf,
+        writer: Box<NdjsonWriter<BufWriter<File>>>,
+    },
+    Failed(io::Error),
+}
+
+/// Collects results into a [`Scan`] until they take up `budget` bytes,
+/// then writes the rest to a file in `dir`
+pub struct SpillSink<'a, T> {
+    scan: &'a mut Scan<T>,
+    budget: Option<usize>,
+    used: usize,
+    dir: PathBuf,
+    file: SpillFile,
+    spilled: usize,
+    dropped: usize,
+    pid: Option<i32>,
+}
+
+impl<'a, T: Footprint + Located + Record> SpillSink<'a, T> {
+    /// A sink collecting into `scan`, without limit if `budget` is `None`
+    pub fn new(scan: &'a mut Scan<T>, budget: Option<usize>, dir: &Path) -> Self {
+        SpillSink {
+            scan,
+            budget,
+            used: 0,
+            dir: dir.to_path_buf(),
+            file: SpillFile::Unopened,
+            spilled: 0,
+            dropped: 0,
+            pid: None,
+        }
+    }
+
+    /// Mark the regions and results that follow as coming from `pid`
+    pub fn set_pid(&mut self, pid: i32) {
+        self.pid = Some(pid);
+    }
+
+    /// Where the results past the budget went, if any were. A failure to
+    /// write the file is returned instead, saying how many were lost.
+    pub fn finish(self) -> io::Result<Option<Spill>> {
+        match self.file {
+            SpillFile::Unopened => Ok(None),
+            SpillFile::Open { path, writer } => {
+                writer.close()?;
+                Ok(Some(Spill {
+                    path: path.to_string_lossy().into_owned(),
+                    results: self.spilled,
+                }))
+            }
+            SpillFile::Failed(e) => Err(io::Error::new(
+                e.kind(),
+                format!("{}; {} results past the memory limit were dropped", e, self.dropped),
+            )),
+        }
+    }
+
+    fn spill(&mut self, item: T) {
+        if let SpillFile::Unopened = self.file {
+            self.file = match create_spill_file(&self.dir) {
+                Ok((path, file)) => SpillFile::Open {
+                    path,
+                    writer: Box::new(NdjsonWriter::new(BufWriter::new(file))),
+                },
+                Err(e) => SpillFile::Failed(e),
+            };
+        }
+        match &mut self.file {
+            SpillFile::Open { writer, .. } => {
+                writer.write(&item);
+                self.spilled += 1;
+            }
+            _ => self.dropped += 1,
+        }
+    }
+}
+
+impl<T: Footprint + Located + Record> ScanSink<T> for SpillSink<'_, T> {
+    fn region(&mut self, mut result: RegionResult) {
+        result.pid = self.pid.or(result.pid);
+        self.scan.region(result);
+    }
+
+    fn result(&mut self, mut item: T) {
+        if let Some(pid) = self.pid {
+            item.location_mut().pid = Some(pid);
+        }
+        let size = item.footprint();
+        if self.budget.is_some_and(|budget| self.used + size > budget) {
+            // Once over, stay over, so the file holds everything after the
+            // results in memory
+            self.budget = Some(0);
+            self.spill(item);
+        } else {
+            self.used += size;
+            self.scan.results.push(item);
+        }
+    }
+}
+
+/// Create a file for results in `dir` under a name no other scan uses
+fn create_spill_file(dir: &Path) -> io::Result<(PathBuf, File)> {
+    static NEXT: AtomicU32 = AtomicU32::new(0);
+    loop {
+        let serial = NEXT.fetch_add(1, Ordering::Relaxed);
+        let path = dir.join(format!("results-{}-{}.ndjson", process::id(), serial));
+        match File::options().write(true).create_new(true).open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scan::Location;
+    use crate::strings::StringEncoding;
+    use std::fs;
+
+    fn string(value: &str) -> ExtractedString {
+        ExtractedString {
+            location: Location::new(&"[heap]".into(), 0x1000, 0x1010),
+            encoding: StringEncoding::Ascii,
+            value: value.to_string(),
+            count: None,
+            addresses: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_spills_results_past_the_budget() {
+        let budget = 2 * string("0123456789").footprint();
+        let mut scan = Scan::default();
+        let mut sink = SpillSink::new(&mut scan, Some(budget), &std::env::temp_dir());
+        sink.set_pid(7);
+        for value in ["0123456789", "abcdefghij", "short", "ABCDEFGHIJ"] {
+            sink.result(string(value));
+        }
+        let spill = sink.finish().unwrap().unwrap();
+
+        let kept: Vec<&str> = scan.results.iter().map(|s| s.value.as_str()).collect();
+        assert_eq!(kept, ["0123456789", "abcdefghij"]);
+        assert_eq!(spill.results, 2);
+        let lines = fs::read_to_string(&spill.path).unwrap();
+        fs::remove_file(&spill.path).unwrap();
+        assert_eq!(lines.lines().count(), 2);
+        assert!(lines.starts_with(r#"{"type":"string","address":4112,"#));
+        assert!(lines.contains(r#""pid":7"#) && lines.contains("short"));
+    }
+}
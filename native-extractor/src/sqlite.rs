@@ -0,0 +1,732 @@
+//! SQLite databases rebuilt from memory
+//!
+//! Apps keep message history, caches and tokens in SQLite, and the
+//! databases they have open, in-memory and deleted ones included, are in
+//! their memory in one of two forms:
+//!
+//! - whole images, where a database is mapped from its file or held by the
+//!   `memdb` VFS: a 100-byte header starting `SQLite format 3\0`, then each
+//!   page in turn
+//! - the page cache, where every page read is kept in an allocation of its
+//!   own, followed by the `PgHdr1` SQLite finds it by. That starts with a
+//!   pointer to the page and one just past itself, then the page number,
+//!   so cached pages are found wherever they lie and put back in order,
+//!   grouped by the `PCache1` that holds them.
+//!
+//! Each database is written to a file, pages not found left zeroed and
+//! the header switched to rollback journaling, so it opens without its
+//! `-wal` file. The schema is read back from the pages found, with the
+//! rows each table still has.
+
+use crate::dump::DumpedFile;
+use crate::maps::MemoryRegion;
+use crate::reader::{self, MemoryReader};
+use crate::scan::{self, RegionVisitor, ScanControl, ScanSink, StopReason};
+use crate::sha256::Sha256;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::mem::size_of;
+use std::path::Path;
+
+/// Size of the header at the start of every database
+pub const HEADER_SIZE: usize = 100;
+
+const MAGIC: &[u8; 16] = b"SQLite format 3\0";
+const ALIGN: usize = 8;
+
+/// Native pointers in SQLite, which has the ABI of this library
+const WORD: usize = size_of::<usize>();
+
+/// The start of a `PgHdr1` that is read: `page.pBuf`, `page.pExtra`, then
+/// `iKey` and two flags, `pNext` and `pCache`
+const PGHDR1_READ: usize = 4 * WORD + 8;
+
+/// Largest database rebuilt
+const MAX_DATABASE_SIZE: u64 = 256 * 1024 * 1024;
+
+/// The fields of a database header that [`parse_header`] checks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DbHeader {
+    pub page_size: usize,
+    /// Bytes at the end of each page left out of the b-tree
+    pub reserved: usize,
+    /// Pages in the database, if the header gives a size that is current
+    pub page_count: Option<u32>,
+    /// `1` for UTF-8, `2` for UTF-16LE, `3` for UTF-16BE
+    pub encoding: u32,
+}
+
+/// Where a database was found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DatabaseSource {
+    /// All its pages in order, from its header on
+    Image,
+    /// Pages from the page cache of a connection
+    PageCache,
+}
+
+/// A table, index, view or trigger in a database's schema
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SqliteObject {
+    /// `table`, `index`, `view` or `trigger`
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub name: String,
+    /// The table an index or trigger is on
+    pub table: String,
+    pub root_page: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sql: Option<String>,
+    /// Rows found in the pages of a table
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rows: Option<usize>,
+}
+
+/// A database found in memory and written to disk
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CarvedDatabase {
+    /// `start` is the address of page 1
+    #[serde(flatten)]
+    pub file: DumpedFile,
+    pub source: DatabaseSource,
+    pub page_size: usize,
+    /// Pages in the file, and how many of them weren't found
+    pub pages: u32,
+    pub missing_pages: u32,
+    pub schema: Vec<SqliteObject>,
+}
+
+fn be16(data: &[u8], offset: usize) -> Option<usize> {
+    Some(u16::from_be_bytes(data.get(offset..offset + 2)?.try_into().unwrap()).into())
+}
+
+fn be32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(offset..offset + 4)?.try_into().unwrap()))
+}
+
+/// Parse the database header at the start of `bytes`, if it is one: the
+/// magic, a page size that is a power of two from 512 to 65536, file format
+/// versions 1 or 2, and the payload fractions every database has
+pub fn parse_header(bytes: &[u8]) -> Option<DbHeader> {
+    let header = bytes.get(..HEADER_SIZE)?;
+    if &header[..16] != MAGIC {
+        return None;
+    }
+    let page_size = match be16(header, 16)? {
+        1 => 65536,
+        size => size,
+    };
+    let reserved = usize::from(header[20]);
+    let encoding = be32(header, 56)?;
+    let valid = page_size.is_power_of_two()
+        && page_size >= 512
+        && page_size - reserved >= 480
+        && (1..=2).contains(&header[18])
+        && (1..=2).contains(&header[19])
+        && header[21..24] == [64, 32, 32]
+        && encoding <= 3;
+    // The size in the header is only kept up to date by SQLite 3.7.0 and
+    // later, which says so by storing the change counter again
+    let pages = be32(header, 28)?;
+    let page_count = (pages > 0 && be32(header, 92) == be32(header, 24)).then_some(pages);
+    valid.then_some(DbHeader {
+        page_size,
+        reserved,
+        page_count,
+        encoding: encoding.max(1),
+    })
+}
+
+/// A SQLite varint at the start of `data`, and its length
+fn varint(data: &[u8]) -> Option<(u64, usize)> {
+    let mut value = 0u64;
+    for (i, &byte) in data.iter().enumerate().take(9) {
+        if i == 8 {
+            return Some((value << 8 | u64::from(byte), 9));
+        }
+        value = value << 7 | u64::from(byte & 0x7f);
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+/// A column of a record
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(String),
+    Blob(Vec<u8>),
+}
+
+/// The pages of a database found in memory, by page number
+pub struct Pages {
+    header: DbHeader,
+    pages: BTreeMap<u32, Vec<u8>>,
+}
+
+impl Pages {
+    /// The pages of the database whose header is `header`, page 1 holding
+    /// it
+    pub fn new(header: DbHeader, pages: BTreeMap<u32, Vec<u8>>) -> Self {
+        Pages { header, pages }
+    }
+
+    /// Pages in the database: as many as the header says, or as far as the
+    /// last page found
+    pub fn count(&self) -> u32 {
+        let last = self.pages.keys().next_back().copied().unwrap_or(0);
+        self.header.page_count.unwrap_or(0).max(last)
+    }
+
+    fn page(&self, number: u32) -> Option<&[u8]> {
+        self.pages.get(&number).map(Vec::as_slice)
+    }
+
+    fn usable(&self) -> usize {
+        self.header.page_size - self.header.reserved
+    }
+
+    /// Call `visit` with the page and offset of each cell of the table
+    /// b-tree at `root` that can be found, in rowid order
+    fn visit_table(&self, root: u32, visit: &mut dyn FnMut(&[u8], usize)) {
+        let mut stack = vec![root];
+        let mut seen = HashSet::new();
+        while let Some(number) = stack.pop() {
+            if !seen.insert(number) {
+                continue;
+            }
+            let Some(page) = self.page(number) else {
+                continue;
+            };
+            let header = if number == 1 { HEADER_SIZE } else { 0 };
+            let (pointers, interior) = match page[header] {
+                13 => (header + 8, false),
+                5 => (header + 12, true),
+                _ => continue,
+            };
+            let count = be16(page, header + 3).unwrap_or(0);
+            let cells: Vec<usize> = (0..count)
+                .filter_map(|i| be16(page, pointers + 2 * i))
+                .filter(|&at| at >= pointers + 2 * count && at < self.usable())
+                .collect();
+            if interior {
+                // Pushed right to left, so the leftmost child is read first
+                stack.extend(be32(page, header + 8));
+                stack.extend(cells.iter().rev().filter_map(|&at| be32(page, at)));
+            } else {
+                for at in cells {
+                    visit(page, at);
+                }
+            }
+        }
+    }
+
+    /// The payload of the table leaf cell at `at` in `page`, following it
+    /// into overflow pages, or `None` if one of those wasn't found
+    fn payload(&self, page: &[u8], at: usize) -> Option<Vec<u8>> {
+        let (size, n) = varint(page.get(at..)?)?;
+        let (_rowid, m) = varint(page.get(at + n..)?)?;
+        let start = at + n + m;
+        let size = usize::try_from(size).ok().filter(|&size| size as u64 <= MAX_DATABASE_SIZE)?;
+        let usable = self.usable();
+        let max_local = usable - 35;
+        let local = if size <= max_local {
+            size
+        } else {
+            let min_local = (usable - 12) * 32 / 255 - 23;
+            let local = min_local + (size - min_local) % (usable - 4);
+            if local <= max_local { local } else { min_local }
+        };
+        let mut payload = page.get(start..start + local)?.to_vec();
+        if local < size {
+            let mut next = be32(page, start + local)?;
+            let mut seen = HashSet::new();
+            while payload.len() < size {
+                if !seen.insert(next) {
+                    return None;
+                }
+                let overflow = self.page(next)?;
+                let take = (size - payload.len()).min(usable - 4);
+                payload.extend_from_slice(overflow.get(4..4 + take)?);
+                next = be32(overflow, 0)?;
+            }
+        }
+        Some(payload)
+    }
+
+    fn text(&self, bytes: &[u8]) -> String {
+        let units = |from: fn([u8; 2]) -> u16| -> Vec<u16> {
+            bytes.chunks_exact(2).map(|pair| from([pair[0], pair[1]])).collect()
+        };
+        match self.header.encoding {
+            2 => String::from_utf16_lossy(&units(u16::from_le_bytes)),
+            3 => String::from_utf16_lossy(&units(u16::from_be_bytes)),
+            _ => String::from_utf8_lossy(bytes).into_owned(),
+        }
+    }
+
+    /// The columns of a record
+    fn record(&self, payload: &[u8]) -> Option<Vec<Value>> {
+        let (header_size, mut at) = varint(payload)?;
+        let header_size = usize::try_from(header_size).ok()?;
+        let mut body = header_size;
+        let mut values = Vec::new();
+        while at < header_size {
+            let (serial, n) = varint(payload.get(at..header_size)?)?;
+            at += n;
+            let integer = |len: usize| -> Option<i64> {
+                let bytes = payload.get(body..body + len)?;
+                let value = bytes.iter().fold(0i64, |n, &b| n << 8 | i64::from(b));
+                // Sign-extended from `len` bytes
+                let shift = 64 - 8 * len as u32;
+                Some(value << shift >> shift)
+            };
+            let (value, len) = match serial {
+                0 => (Value::Null, 0),
+                1..=4 => (Value::Integer(integer(serial as usize)?), serial as usize),
+                5 => (Value::Integer(integer(6)?), 6),
+                6 => (Value::Integer(integer(8)?), 8),
+                7 => (Value::Real(f64::from_bits(integer(8)? as u64)), 8),
+                8 => (Value::Integer(0), 0),
+                9 => (Value::Integer(1), 0),
+                10 | 11 => return None,
+                _ => {
+                    let len = usize::try_from((serial - 12) / 2).ok()?;
+                    let bytes = payload.get(body..body + len)?;
+                    let value = if serial % 2 == 1 {
+                        Value::Text(self.text(bytes))
+                    } else {
+                        Value::Blob(bytes.to_vec())
+                    };
+                    (value, len)
+                }
+            };
+            body += len;
+            values.push(value);
+        }
+        Some(values)
+    }
+
+    /// The objects in `sqlite_schema` that can be read, with the rows of
+    /// each table counted
+    pub fn schema(&self) -> Vec<SqliteObject> {
+        let mut objects = Vec::new();
+        self.visit_table(1, &mut |page, at| {
+            let Some(values) = self.payload(page, at).and_then(|p| self.record(&p)) else {
+                return;
+            };
+            let text = |i: usize| match values.get(i) {
+                Some(Value::Text(text)) => Some(text.clone()),
+                _ => None,
+            };
+            let root_page = match values.get(3) {
+                Some(&Value::Integer(root)) => u32::try_from(root).unwrap_or(0),
+                _ => 0,
+            };
+            if let (Some(kind), Some(name)) = (text(0), text(1)) {
+                objects.push(SqliteObject {
+                    kind,
+                    name,
+                    table: text(2).unwrap_or_default(),
+                    root_page,
+                    sql: text(4),
+                    rows: None,
+                });
+            }
+        });
+        for object in &mut objects {
+            if object.kind == "table" && object.root_page > 0 {
+                let mut rows = 0;
+                self.visit_table(object.root_page, &mut |_, _| rows += 1);
+                object.rows = Some(rows);
+            }
+        }
+        objects
+    }
+
+    /// Write the database to `path`, pages not found zeroed, returning its
+    /// size and SHA-256
+    pub fn write(&self, path: &Path) -> io::Result<(u64, String)> {
+        let count = self.count();
+        let mut data = vec![0u8; count as usize * self.header.page_size];
+        for (&number, page) in &self.pages {
+            let at = (number as usize - 1) * self.header.page_size;
+            data[at..at + page.len()].copy_from_slice(page);
+        }
+        if data.len() >= HEADER_SIZE {
+            // Rollback journaling, and a current size of `count` pages
+            data[18] = 1;
+            data[19] = 1;
+            data[28..32].copy_from_slice(&count.to_be_bytes());
+            data.copy_within(24..28, 92);
+        }
+        fs::write(path, &data)?;
+        let mut hash = Sha256::new();
+        hash.update(&data);
+        Ok((data.len() as u64, hash.finish_hex()))
+    }
+}
+
+/// File a database whose page 1 is at `address` is written to
+pub fn database_file_name(address: u64) -> String {
+    format!("{:08x}.db", address)
+}
+
+/// A pointer with any tag in its top byte, which Android puts on heap
+/// pointers, cleared
+fn untag(pointer: u64) -> u64 {
+    if WORD == 8 {
+        pointer & 0x00ff_ffff_ffff_ffff
+    } else {
+        pointer
+    }
+}
+
+fn word(data: &[u8], offset: usize) -> u64 {
+    let bytes = &data[offset..offset + WORD];
+    untag(if WORD == 8 {
+        u64::from_le_bytes(bytes.try_into().unwrap())
+    } else {
+        u32::from_le_bytes(bytes.try_into().unwrap()).into()
+    })
+}
+
+/// Search `regions` for databases and write each to its own file in `dir`,
+/// delivering a [`CarvedDatabase`] for each.
+///
+/// Pages are gathered from every region before any database is put
+/// together, since cached ones are scattered over the heap. Failing to
+/// write to `dir` is an error; files written before the failure are left
+/// in place.
+pub fn carve_databases(
+    reader: &mut dyn MemoryReader,
+    regions: &[MemoryRegion],
+    dir: &Path,
+    sink: &mut dyn ScanSink<CarvedDatabase>,
+    control: &mut ScanControl,
+) -> io::Result<Option<StopReason>> {
+    let mut visitor = SqliteVisitor::default();
+    let stopped = scan::walk_regions(reader, regions, &mut visitor, sink, control);
+
+    // Page 1 of a cache is found by its magic too, but what follows it
+    // isn't the rest of the database
+    let cached: HashSet<u64> =
+        visitor.cached.values().filter_map(|pages| pages.get(&1).copied()).collect();
+    for &address in visitor.headers.iter().filter(|address| !cached.contains(address)) {
+        let Some(region) = regions.iter().find(|region| region.contains(address)) else {
+            continue;
+        };
+        if let Some(database) = read_image(reader, region, address) {
+            write(database, address, DatabaseSource::Image, dir, sink)?;
+        }
+    }
+    let mut caches: Vec<_> = visitor.cached.into_iter().collect();
+    caches.sort_by_key(|(_, pages)| pages.get(&1).copied());
+    for ((_, page_size), addresses) in caches {
+        let Some(&address) = addresses.get(&1) else {
+            continue;
+        };
+        if let Some(database) = read_cache(reader, page_size, &addresses) {
+            write(database, address, DatabaseSource::PageCache, dir, sink)?;
+        }
+    }
+    Ok(stopped)
+}
+
+fn read_page(reader: &mut dyn MemoryReader, address: u64, page_size: usize) -> Option<Vec<u8>> {
+    let mut page = vec![0u8; page_size];
+    (reader::read_into(reader, address, &mut page).ok()? == page_size).then_some(page)
+}
+
+/// The header of page 1 at `address`, if it holds together and the
+/// b-tree under it is a table
+fn read_first_page(
+    reader: &mut dyn MemoryReader,
+    address: u64,
+    page_size: Option<usize>,
+) -> Option<(DbHeader, Vec<u8>)> {
+    let mut header = [0u8; HEADER_SIZE];
+    if reader::read_into(reader, address, &mut header).unwrap_or(0) < HEADER_SIZE {
+        return None;
+    }
+    let header = parse_header(&header)?;
+    if page_size.is_some_and(|size| size != header.page_size) {
+        return None;
+    }
+    let page = read_page(reader, address, header.page_size)?;
+    matches!(page[HEADER_SIZE], 5 | 13).then_some((header, page))
+}
+
+/// The pages of the image at `address`, as many as the header says or as
+/// fit in `region`
+fn read_image(reader: &mut dyn MemoryReader, region: &MemoryRegion, address: u64) -> Option<Pages> {
+    let (header, first) = read_first_page(reader, address, None)?;
+    let page_size = header.page_size as u64;
+    let fit = (region.end - address) / page_size;
+    let count = header.page_count.map_or(fit, |count| u64::from(count).min(fit));
+    let count = count.min(MAX_DATABASE_SIZE / page_size) as u32;
+    let mut pages = BTreeMap::from([(1, first)]);
+    for number in 2..=count {
+        let at = address + u64::from(number - 1) * page_size;
+        if let Some(page) = read_page(reader, at, header.page_size) {
+            pages.insert(number, page);
+        }
+    }
+    Some(Pages::new(header, pages))
+}
+
+/// The pages of a cache, read from `addresses`
+fn read_cache(
+    reader: &mut dyn MemoryReader,
+    page_size: usize,
+    addresses: &BTreeMap<u32, u64>,
+) -> Option<Pages> {
+    let (header, first) = read_first_page(reader, *addresses.get(&1)?, Some(page_size))?;
+    let max = (MAX_DATABASE_SIZE / page_size as u64) as u32;
+    let mut pages = BTreeMap::from([(1, first)]);
+    for (&number, &address) in addresses.range(2..=max) {
+        if let Some(page) = read_page(reader, address, page_size) {
+            pages.insert(number, page);
+        }
+    }
+    Some(Pages::new(header, pages))
+}
+
+fn write(
+    pages: Pages,
+    address: u64,
+    source: DatabaseSource,
+    dir: &Path,
+    sink: &mut dyn ScanSink<CarvedDatabase>,
+) -> io::Result<()> {
+    let name = database_file_name(address);
+    let (size, sha256) = pages.write(&dir.join(&name))?;
+    sink.result(CarvedDatabase {
+        file: DumpedFile {
+            start: address,
+            file: name,
+            size,
+            sha256,
+        },
+        source,
+        page_size: pages.header.page_size,
+        pages: pages.count(),
+        missing_pages: pages.count() - pages.pages.len() as u32,
+        schema: pages.schema(),
+    });
+    Ok(())
+}
+
+#[derive(Default)]
+struct SqliteVisitor {
+    /// Addresses of the database headers found
+    headers: Vec<u64>,
+    /// Addresses of cached pages by cache and page size, then page number
+    cached: HashMap<(u64, usize), BTreeMap<u32, u64>>,
+    /// The end of the last chunk, where a header may start
+    carry: Vec<u8>,
+    carry_address: u64,
+}
+
+impl SqliteVisitor {
+    /// Look for headers and `PgHdr1`s at the aligned offsets of `data`
+    /// before `end`
+    fn search(&mut self, address: u64, data: &[u8], end: usize) {
+        let mut at = 0;
+        while at < end && at + PGHDR1_READ <= data.len() {
+            let here = address + at as u64;
+            if &data[at..at + MAGIC.len()] == MAGIC {
+                self.headers.push(here);
+            }
+            let (page, extra) = (word(data, at), word(data, at + WORD));
+            let page_size = here.wrapping_sub(page);
+            let extra_offset = extra.wrapping_sub(here);
+            if page < here
+                && page_size.is_power_of_two()
+                && (512..=65536).contains(&page_size)
+                && (PGHDR1_READ as u64..=128).contains(&extra_offset)
+                && extra_offset.is_multiple_of(ALIGN as u64)
+            {
+                let key = u32::from_le_bytes(data[at + 2 * WORD..][..4].try_into().unwrap());
+                let cache = word(data, at + 3 * WORD + 8);
+                if key > 0 && cache != 0 {
+                    let pages = self.cached.entry((cache, page_size as usize)).or_default();
+                    pages.entry(key).or_insert(page);
+                }
+            }
+            at += ALIGN;
+        }
+    }
+}
+
+impl RegionVisitor for SqliteVisitor {
+    type Output = CarvedDatabase;
+
+    fn visit_chunk(&mut self, _region: &MemoryRegion, address: u64, chunk: &[u8]) {
+        // Chunks start page-aligned; what spans two is searched joined up
+        if !self.carry.is_empty() && self.carry_address + self.carry.len() as u64 == address {
+            let mut joined = std::mem::take(&mut self.carry);
+            let end = joined.len();
+            joined.extend_from_slice(&chunk[..chunk.len().min(PGHDR1_READ)]);
+            self.search(self.carry_address, &joined, end);
+        }
+        self.search(address, chunk, chunk.len());
+        let keep = chunk.len().min(PGHDR1_READ - ALIGN);
+        self.carry = chunk[chunk.len() - keep..].to_vec();
+        self.carry_address = address + (chunk.len() - keep) as u64;
+    }
+
+    fn end_region(
+        &mut self,
+        _region: &MemoryRegion,
+        _reader: &mut dyn MemoryReader,
+        _sink: &mut dyn ScanSink<CarvedDatabase>,
+    ) {
+        // Databases are put together once every region has been read
+        self.carry.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps::parse_maps_line;
+    use crate::reader::ProcfsReader;
+    use crate::scan::Scan;
+
+    const PAGE_SIZE: usize = 512;
+
+    /// A record of `values`, each an integer or text
+    fn record(values: &[Result<i64, &str>]) -> Vec<u8> {
+        let (mut header, mut body) = (Vec::new(), Vec::new());
+        for value in values {
+            match value {
+                Ok(n) => {
+                    header.push(1);
+                    body.push(*n as u8);
+                }
+                Err(text) => {
+                    header.push((13 + 2 * text.len()) as u8);
+                    body.extend_from_slice(text.as_bytes());
+                }
+            }
+        }
+        let mut record = vec![header.len() as u8 + 1];
+        record.extend(header);
+        record.extend(body);
+        record
+    }
+
+    /// A table leaf page holding `records`, rowids from 1, with room for a
+    /// database header before it if `first`
+    fn leaf(records: &[Vec<u8>], first: bool) -> Vec<u8> {
+        let mut page = vec![0u8; PAGE_SIZE];
+        let header = if first { HEADER_SIZE } else { 0 };
+        page[header] = 13;
+        page[header + 3..header + 5].copy_from_slice(&(records.len() as u16).to_be_bytes());
+        let mut end = PAGE_SIZE;
+        for (i, record) in records.iter().enumerate() {
+            let mut cell = vec![record.len() as u8, i as u8 + 1];
+            cell.extend_from_slice(record);
+            end -= cell.len();
+            page[end..end + cell.len()].copy_from_slice(&cell);
+            let pointer = header + 8 + 2 * i;
+            page[pointer..pointer + 2].copy_from_slice(&(end as u16).to_be_bytes());
+        }
+        if first {
+            page[..16].copy_from_slice(MAGIC);
+            page[16..18].copy_from_slice(&(PAGE_SIZE as u16).to_be_bytes());
+            page[18..24].copy_from_slice(&[2, 2, 0, 64, 32, 32]);
+            page[24..28].copy_from_slice(&7u32.to_be_bytes());
+            page[28..32].copy_from_slice(&2u32.to_be_bytes());
+            page[56..60].copy_from_slice(&1u32.to_be_bytes());
+            page[92..96].copy_from_slice(&7u32.to_be_bytes());
+        }
+        page
+    }
+
+    fn database() -> (Vec<u8>, Vec<u8>) {
+        let sql = "CREATE TABLE messages(body TEXT)";
+        let schema = record(&[Err("table"), Err("messages"), Err("messages"), Ok(2), Err(sql)]);
+        let rows = [record(&[Err("see you at 8")]), record(&[Err("ok")])];
+        (leaf(&[schema], true), leaf(&rows, false))
+    }
+
+    #[test]
+    fn test_schema_and_rows() {
+        let (first, second) = database();
+        let header = parse_header(&first).unwrap();
+        assert_eq!((header.page_size, header.page_count), (PAGE_SIZE, Some(2)));
+        let pages = Pages::new(header, BTreeMap::from([(1, first), (2, second.clone())]));
+        let schema = pages.schema();
+        assert_eq!(schema.len(), 1);
+        assert_eq!((schema[0].name.as_str(), schema[0].root_page), ("messages", 2));
+        assert_eq!(schema[0].sql.as_deref(), Some("CREATE TABLE messages(body TEXT)"));
+        assert_eq!(schema[0].rows, Some(2));
+
+        let mut bodies = Vec::new();
+        pages.visit_table(2, &mut |page, at| {
+            bodies.push(pages.record(&pages.payload(page, at).unwrap()).unwrap());
+        });
+        assert_eq!(bodies[0], [Value::Text("see you at 8".to_string())]);
+        assert_eq!(varint(&[0x81, 0x00]), Some((128, 2)));
+    }
+
+    #[test]
+    fn test_carves_cached_pages() {
+        let (first, second) = database();
+        // Two cached pages, each followed by its PgHdr1; page 2 comes first
+        let slot = PAGE_SIZE + 128;
+        let mut data = vec![0u8; 2 * slot + ALIGN];
+        let start = (data.as_ptr() as u64).next_multiple_of(ALIGN as u64);
+        let skew = (start - data.as_ptr() as u64) as usize;
+        let bytes = &mut data[skew..skew + 2 * slot];
+        for (i, (number, page)) in [(2u32, &second), (1, &first)].into_iter().enumerate() {
+            let at = i * slot;
+            bytes[at..at + PAGE_SIZE].copy_from_slice(page);
+            let pghdr = at + PAGE_SIZE;
+            let here = start + pghdr as u64;
+            let mut put = |offset: usize, value: u64| {
+                bytes[pghdr + offset..pghdr + offset + WORD]
+                    .copy_from_slice(&value.to_le_bytes()[..WORD]);
+            };
+            put(0, here - PAGE_SIZE as u64);
+            put(WORD, here + 56);
+            put(3 * WORD + 8, 0x7000_1000);
+            bytes[pghdr + 2 * WORD..pghdr + 2 * WORD + 4].copy_from_slice(&number.to_le_bytes());
+        }
+        let end = start + 2 * slot as u64;
+        let region =
+            parse_maps_line(&format!("{:x}-{:x} rw-p 00000000 00:00 0 [anon:test]", start, end))
+                .unwrap();
+
+        let pid = std::process::id() as i32;
+        let dir = std::env::temp_dir().join(format!("sqlite-test-{}", pid));
+        fs::create_dir_all(&dir).unwrap();
+        let mut reader = ProcfsReader::open(pid).unwrap();
+        let mut scan = Scan::default();
+        let stopped =
+            carve_databases(&mut reader, &[region], &dir, &mut scan, &mut ScanControl::new());
+
+        assert_eq!(stopped.unwrap(), None);
+        assert_eq!(scan.results.len(), 1);
+        let database = &scan.results[0];
+        assert_eq!(database.source, DatabaseSource::PageCache);
+        assert_eq!(database.file.start, start + slot as u64);
+        assert_eq!((database.pages, database.missing_pages), (2, 0));
+        assert_eq!(database.schema[0].rows, Some(2));
+        let written = fs::read(dir.join(&database.file.file)).unwrap();
+        assert_eq!(&written[PAGE_SIZE..], &second[..]);
+        assert_eq!(written[18..20], [1, 1]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,230 @@
+//! Newline-delimited JSON streaming of scan results
+//!
+//! Large heaps can hold millions of strings, too many to collect into one
+//! report. In streaming mode each result is written as its own JSON line as
+//! soon as it is found. Every line is an object whose `type` field says what
+//! it holds:
+//!
+//! - `process`: the target's [`ProcessInfo`], first
+//! - `string` / `match`: one result
+//! - `region`: a [`RegionResult`], after that region's results
+//! - `error`: a failure, with a `message`, and a `code` if it stopped the
+//!   scan (see [`ExtractError::code`])
+//! - `stats`: the final [`Stats`], always last
+
+use crate::error::ExtractError;
+use crate::report::{ProcessInfo, Stats};
+use crate::scan::{ExtractedString, Match, RegionResult, ScanSink};
+use serde::Serialize;
+use std::io::{self, Write};
+use std::time::Instant;
+
+/// Something that can be written as a stream line
+pub trait Record: Serialize {
+    /// Value of the line's `type` field
+    const KIND: &'static str;
+}
+
+impl Record for ProcessInfo {
+    const KIND: &'static str = "process";
+}
+
+impl Record for RegionResult {
+    const KIND: &'static str = "region";
+}
+
+impl Record for ExtractedString {
+    const KIND: &'static str = "string";
+}
+
+impl Record for Match {
+    const KIND: &'static str = "match";
+}
+
+impl Record for Stats {
+    const KIND: &'static str = "stats";
+}
+
+#[derive(Serialize)]
+struct ErrorRecord<'a> {
+    message: &'a str,
+    /// [`ExtractError::code`] of a failure that stopped the scan
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<i32>,
+}
+
+impl Record for ErrorRecord<'_> {
+    const KIND: &'static str = "error";
+}
+
+#[derive(Serialize)]
+struct Tagged<'a, T> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    #[serde(flatten)]
+    record: &'a T,
+}
+
+/// Writes records to `out`, one JSON object per line.
+///
+/// Each line is handed to `out` in a single `write_all`, so a writer that
+/// forwards whole writes never splits a line. After the first write error
+/// nothing more is written; the error is returned by [`finish`](Self::finish).
+pub struct NdjsonWriter<W: Write> {
+    out: W,
+    line: Vec<u8>,
+    stats: Stats,
+    started: Instant,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> NdjsonWriter<W> {
+    pub fn new(out: W) -> Self {
+        NdjsonWriter {
+            out,
+            line: Vec::new(),
+            stats: Stats::default(),
+            started: Instant::now(),
+            error: None,
+        }
+    }
+
+    /// Whether a write has failed, so further scanning is wasted
+    pub fn failed(&self) -> bool {
+        self.error.is_some()
+    }
+
+    pub fn stats_mut(&mut self) -> &mut Stats {
+        &mut self.stats
+    }
+
+    pub fn write<T: Record>(&mut self, record: &T) {
+        if self.error.is_some() {
+            return;
+        }
+
+        self.line.clear();
+        let tagged = Tagged {
+            kind: T::KIND,
+            record,
+        };
+        let written = serde_json::to_writer(&mut self.line, &tagged)
+            .map_err(io::Error::from)
+            .and_then(|()| {
+                self.line.push(b'\n');
+                self.out.write_all(&self.line)
+            });
+        if let Err(e) = written {
+            self.error = Some(e);
+        }
+    }
+
+    /// A failure that only left part of the output empty
+    pub fn error(&mut self, message: &str) {
+        self.write(&ErrorRecord {
+            message,
+            code: None,
+        });
+    }
+
+    /// A failure that stopped the scan, with its code
+    pub fn fatal(&mut self, error: &ExtractError) {
+        self.write(&ErrorRecord {
+            message: error.message(),
+            code: Some(error.code()),
+        });
+    }
+
+    /// Flush what has been written so far
+    pub fn flush(&mut self) {
+        if self.error.is_none() {
+            self.error = self.out.flush().err();
+        }
+    }
+
+    /// Write the closing `stats` line and flush
+    pub fn finish(mut self) -> io::Result<W> {
+        self.stats.set_elapsed(self.started.elapsed());
+        let stats = self.stats.clone();
+        self.write(&stats);
+        self.close()
+    }
+
+    /// Flush without writing a `stats` line, returning the first write
+    /// error if there was one
+    pub fn close(mut self) -> io::Result<W> {
+        match self.error {
+            Some(e) => Err(e),
+            None => {
+                self.out.flush()?;
+                Ok(self.out)
+            }
+        }
+    }
+}
+
+impl<W: Write, T: Record> ScanSink<T> for NdjsonWriter<W> {
+    fn region(&mut self, result: RegionResult) {
+        self.stats.add_region(&result);
+        self.write(&result);
+    }
+
+    fn result(&mut self, item: T) {
+        self.stats.results += 1;
+        self.write(&item);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps::parse_maps_line;
+    use crate::scan::Location;
+    use crate::strings::StringEncoding;
+
+    #[test]
+    fn test_ndjson_lines_are_tagged() {
+        let region = parse_maps_line("1000-2000 rw-p 00000000 00:00 0 [heap]").unwrap();
+        let mut writer = NdjsonWriter::new(Vec::new());
+
+        writer.result(ExtractedString {
+            location: Location::new(&"[heap]".into(), 0x1000, 0x1010),
+            encoding: StringEncoding::Ascii,
+            value: "hello".into(),
+            count: None,
+            addresses: Vec::new(),
+        });
+        ScanSink::<ExtractedString>::region(
+            &mut writer,
+            RegionResult {
+                region,
+                bytes_read: 0x1000,
+                error: None,
+                skipped: Vec::new(),
+                elapsed_us: 0,
+                interrupted: false,
+                pid: None,
+            },
+        );
+        writer.error("something failed");
+        writer.fatal(&ExtractError::NoSuchProcess("gone".into()));
+
+        let out = writer.finish().unwrap();
+        let lines: Vec<serde_json::Value> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[0]["type"], "string");
+        assert_eq!(lines[0]["offset"], 0x10);
+        assert_eq!(lines[1]["type"], "region");
+        assert_eq!(lines[2]["message"], "something failed");
+        assert!(lines[2].get("code").is_none());
+        assert_eq!(lines[3]["code"], 1);
+        assert_eq!(lines[4]["type"], "stats");
+        assert_eq!(lines[4]["results"], 1);
+        assert_eq!(lines[4]["bytesRead"], 0x1000);
+    }
+}
@@ -0,0 +1,778 @@
+//! String extraction from raw memory
+//!
+//! Memory arrives in chunks, so a string can start in one chunk and end in
+//! the next. [`StringExtractor`] keeps the unterminated tail of each chunk
+//! and carries on scanning where it left off once the following one
+//! arrives, which means callers can feed chunks of any size and still get
+//! whole strings back, each byte scanned once.
+
+use encoding_rs::{DecoderResult, Encoding, EUC_KR, GBK, SHIFT_JIS, UTF_8};
+use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
+
+/// Text encodings the extractor can recognize
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StringEncoding {
+    /// Printable 7-bit ASCII, one byte per character
+    Ascii,
+    /// UTF-16 little endian, the in-memory form of Java strings that
+    /// contain non-Latin-1 characters
+    Utf16le,
+    Utf8,
+    ShiftJis,
+    Gbk,
+    EucKr,
+}
+
+impl StringEncoding {
+    /// The encoding_rs decoder for multibyte encodings
+    fn multibyte(self) -> Option<&'static Encoding> {
+        match self {
+            StringEncoding::Ascii | StringEncoding::Utf16le => None,
+            StringEncoding::Utf8 => Some(UTF_8),
+            StringEncoding::ShiftJis => Some(SHIFT_JIS),
+            StringEncoding::Gbk => Some(GBK),
+            StringEncoding::EucKr => Some(EUC_KR),
+        }
+    }
+
+    /// Bytes `text` takes up in this encoding
+    pub fn byte_length(self, text: &str) -> usize {
+        match self {
+            StringEncoding::Ascii => text.len(),
+            StringEncoding::Utf16le => text.encode_utf16().count() * 2,
+            _ => self.multibyte().map_or(text.len(), |encoding| encoding.encode(text).0.len()),
+        }
+    }
+}
+
+/// A string found in memory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FoundString {
+    /// Virtual address of the first byte of the string
+    pub address: u64,
+    pub encoding: StringEncoding,
+    pub value: String,
+}
+
+/// Printable ASCII, space through tilde
+pub fn is_printable_ascii(byte: u8) -> bool {
+    (32..=126).contains(&byte)
+}
+
+/// Whether a decoded UTF-16 character is plausible text.
+///
+/// Controls, private use characters, noncharacters and the replacement
+/// character all end a run; random memory decodes to them constantly.
+fn is_printable_char(c: char) -> bool {
+    if c.is_ascii() {
+        return is_printable_ascii(c as u8);
+    }
+    !c.is_control()
+        && !matches!(c, '\u{E000}'..='\u{F8FF}' | '\u{FFFD}')
+        && (c as u32 & 0xFFFE) != 0xFFFE
+}
+
+/// Scan a byte buffer for printable ASCII strings
+pub fn extract_printable_strings(data: &[u8], min_length: usize) -> Vec<String> {
+    let mut strings = Vec::new();
+    scan_ascii(data, 0, min_length, true, &mut |_, value| strings.push(value));
+    strings
+}
+
+/// The bytes a run of text is made of
+#[derive(Debug, Clone, Copy)]
+enum RunClass {
+    /// Printable ASCII
+    Ascii,
+    /// Printable ASCII and bytes with the high bit set, see
+    /// [`is_multibyte_candidate`]
+    Multibyte,
+}
+
+const LOW_BITS: u64 = 0x0101_0101_0101_0101;
+const HIGH_BITS: u64 = 0x8080_8080_8080_8080;
+
+impl RunClass {
+    fn contains(self, byte: u8) -> bool {
+        match self {
+            RunClass::Ascii => is_printable_ascii(byte),
+            RunClass::Multibyte => is_multibyte_candidate(byte),
+        }
+    }
+
+    /// The high bit of each byte of `word` that isn't in the class, all
+    /// eight bytes tested at once.
+    ///
+    /// Only the low seven bits of each byte go into the additions, so no
+    /// carry crosses into the next byte.
+    fn outside(self, word: u64) -> u64 {
+        let high = word & HIGH_BITS;
+        let low = word & !HIGH_BITS;
+        // Bytes whose low seven bits are below 0x20, and those equal to 0x7F
+        let control = !(low + 0x60 * LOW_BITS) & HIGH_BITS;
+        let delete = (low + LOW_BITS) & HIGH_BITS;
+        match self {
+            RunClass::Ascii => high | control | delete,
+            RunClass::Multibyte => !high & (control | delete) & HIGH_BITS,
+        }
+    }
+
+    /// Offset of the first byte at or after `from` that is in the class if
+    /// `inside`, or outside it if not; `data.len()` if there's none.
+    ///
+    /// Memory is mostly long stretches of either, so it is tested eight bytes
+    /// at a time.
+    fn find(self, data: &[u8], from: usize, inside: bool) -> usize {
+        let mut words = data[from..].chunks_exact(8);
+        let mut offset = from;
+        for word in words.by_ref() {
+            let outside = self.outside(u64::from_le_bytes(word.try_into().unwrap()));
+            let found = if inside { !outside & HIGH_BITS } else { outside };
+            if found != 0 {
+                return offset + found.trailing_zeros() as usize / 8;
+            }
+            offset += 8;
+        }
+        let tail = words.remainder();
+        match tail.iter().position(|&byte| self.contains(byte) == inside) {
+            Some(at) => offset + at,
+            None => data.len(),
+        }
+    }
+}
+
+/// Find maximal runs of bytes in `class`, calling `on_run(offset, run)` for
+/// each.
+///
+/// Returns how much of `data` was consumed and how much of the rest is
+/// known to be in the run left open. Unless `is_final` is set, a run
+/// touching the end of the buffer is not reported; the consumed offset is
+/// its start, so the caller can carry it over to when more data arrives and
+/// pass the second count back as `resume`. A nonzero `resume` says `data`
+/// starts with an open run that long, which isn't scanned again: each byte
+/// of a long run is looked at once, however many chunks it spans.
+fn scan_runs(
+    data: &[u8],
+    resume: usize,
+    class: RunClass,
+    is_final: bool,
+    on_run: &mut dyn FnMut(usize, &[u8]),
+) -> (usize, usize) {
+    let mut run_start = (resume > 0).then_some(0);
+    let mut at = resume;
+
+    while at < data.len() {
+        at = class.find(data, at, run_start.is_none());
+        if at == data.len() {
+            break;
+        }
+        match run_start.take() {
+            Some(start) => on_run(start, &data[start..at]),
+            None => run_start = Some(at),
+        }
+    }
+
+    match run_start {
+        Some(start) if !is_final => (start, data.len() - start),
+        Some(start) => {
+            on_run(start, &data[start..]);
+            (data.len(), 0)
+        }
+        None => (data.len(), 0),
+    }
+}
+
+/// Scan `data` for ASCII runs of at least `min_length` bytes, calling
+/// `emit(offset, value)` for each. Carry semantics are those of [`scan_runs`].
+fn scan_ascii(
+    data: &[u8],
+    resume: usize,
+    min_length: usize,
+    is_final: bool,
+    emit: &mut dyn FnMut(usize, String),
+) -> (usize, usize) {
+    scan_runs(data, resume, RunClass::Ascii, is_final, &mut |offset, run| {
+        if run.len() >= min_length {
+            emit(offset, String::from_utf8_lossy(run).into_owned());
+        }
+    })
+}
+
+/// Bytes that may appear inside text in UTF-8 and the legacy CJK encodings:
+/// printable ASCII plus every byte with the high bit set
+fn is_multibyte_candidate(byte: u8) -> bool {
+    is_printable_ascii(byte) || byte >= 0x80
+}
+
+/// Scan `data` for text in a multibyte encoding.
+///
+/// Candidate runs are decoded with encoding_rs. A malformed sequence splits
+/// the run, the text before it being emitted with its exact byte offset,
+/// and the same decoder carries on after it into the same buffer, which
+/// only grows as long as the longest piece of text. Pieces that are pure
+/// ASCII are dropped, since the ASCII scanner reports those; only text
+/// that actually needed the encoding is emitted. Carry semantics are those
+/// of [`scan_runs`].
+fn scan_multibyte(
+    data: &[u8],
+    resume: usize,
+    encoding: &'static Encoding,
+    min_length: usize,
+    is_final: bool,
+    emit: &mut dyn FnMut(usize, String),
+) -> (usize, usize) {
+    let mut text = String::new();
+    scan_runs(
+        data,
+        resume,
+        RunClass::Multibyte,
+        is_final,
+        &mut |offset, run| {
+            if run.len() < min_length || run.is_ascii() {
+                return;
+            }
+
+            let mut decoder = encoding.new_decoder_without_bom_handling();
+            text.clear();
+            // Start of the text in `text`, and how far decoding has got
+            let (mut start, mut at) = (0, 0);
+            loop {
+                let (result, read) =
+                    decoder.decode_to_string_without_replacement(&run[at..], &mut text, true);
+                at += read;
+                match result {
+                    DecoderResult::OutputFull => text.reserve(text.len().max(64)),
+                    DecoderResult::Malformed(_, after) => {
+                        emit_printable_pieces(encoding, offset + start, &text, min_length, emit);
+                        text.clear();
+                        start = at - after as usize;
+                    }
+                    DecoderResult::InputEmpty => {
+                        emit_printable_pieces(encoding, offset + start, &text, min_length, emit);
+                        break;
+                    }
+                }
+            }
+        },
+    )
+}
+
+/// Split decoded text at characters that aren't plausible text and emit the
+/// pieces that are long enough and contain non-ASCII characters
+fn emit_printable_pieces(
+    encoding: &'static Encoding,
+    base: usize,
+    text: &str,
+    min_length: usize,
+    emit: &mut dyn FnMut(usize, String),
+) {
+    let mut rest = text;
+    let mut consumed_bytes = 0usize;
+
+    while !rest.is_empty() {
+        let end = rest.find(|c| !is_printable_char(c)).unwrap_or(rest.len());
+        let piece = &rest[..end];
+        if !piece.is_ascii() && piece.chars().count() >= min_length {
+            emit(base + consumed_bytes, piece.to_string());
+        }
+
+        let skipped = rest[end..].chars().next().map_or(0, char::len_utf8);
+        let advanced = &rest[..end + skipped];
+        // Valid input round-trips through these encodings, so re-encoding
+        // gives the original byte length of the text before the next piece
+        consumed_bytes += if encoding == UTF_8 {
+            advanced.len()
+        } else {
+            encoding.encode(advanced).0.len()
+        };
+        rest = &rest[end + skipped..];
+    }
+}
+
+/// Scan `data` for UTF-16LE runs of at least `min_length` characters.
+///
+/// Only even offsets are considered, matching the alignment of Java char
+/// data. A high surrogate followed by a low surrogate decodes to one
+/// supplementary character; an unpaired surrogate ends the run. Carry and
+/// `resume` semantics are those of [`scan_runs`], `resume` being even.
+fn scan_utf16le(
+    data: &[u8],
+    resume: usize,
+    min_length: usize,
+    is_final: bool,
+    emit: &mut dyn FnMut(usize, String),
+) -> (usize, usize) {
+    let unit_at = |i: usize| u16::from_le_bytes([data[i], data[i + 1]]);
+    // A run is decoded once it ends, having been checked unit by unit
+    let mut emit_run = |start: usize, end: usize| {
+        let units = data[start..end].chunks_exact(2);
+        let units = units.map(|pair| u16::from_le_bytes([pair[0], pair[1]]));
+        let run: String = char::decode_utf16(units).map(|c| c.unwrap_or('\u{FFFD}')).collect();
+        if run.chars().count() >= min_length {
+            emit(start, run);
+        }
+    };
+
+    let mut run_start = (resume > 0).then_some(0);
+    let mut i = resume;
+
+    while i + 1 < data.len() {
+        let unit = unit_at(i);
+        let (decoded, width) = match unit {
+            0xD800..=0xDBFF if i + 3 >= data.len() => {
+                if !is_final {
+                    // The low surrogate is in the next chunk
+                    break;
+                }
+                (None, 2)
+            }
+            0xD800..=0xDBFF => match unit_at(i + 2) {
+                low @ 0xDC00..=0xDFFF => {
+                    let code = 0x10000 + (((unit as u32) - 0xD800) << 10) + (low as u32 - 0xDC00);
+                    (char::from_u32(code), 4)
+                }
+                _ => (None, 2),
+            },
+            0xDC00..=0xDFFF => (None, 2),
+            _ => (char::from_u32(unit as u32), 2),
+        };
+
+        match decoded.filter(|&c| is_printable_char(c)) {
+            Some(_) => {
+                run_start.get_or_insert(i);
+            }
+            None => {
+                if let Some(start) = run_start.take() {
+                    emit_run(start, i);
+                }
+            }
+        }
+        i += width;
+    }
+
+    if !is_final {
+        return match run_start {
+            Some(start) => (start, i - start),
+            None => (i, 0),
+        };
+    }
+    if let Some(start) = run_start {
+        emit_run(start, i.min(data.len()));
+    }
+    (data.len(), 0)
+}
+
+/// Unscanned bytes carried over from the previous chunk for one encoding
+struct Pending {
+    encoding: StringEncoding,
+    carry: Vec<u8>,
+    carry_address: u64,
+    /// Bytes at the start of `carry` already scanned, all in an open run
+    resume: usize,
+}
+
+impl Pending {
+    fn scan(
+        &self,
+        data: &[u8],
+        min_length: usize,
+        is_final: bool,
+        base: u64,
+        out: &mut Vec<FoundString>,
+    ) -> (usize, usize) {
+        let encoding = self.encoding;
+        let mut emit = |offset: usize, value: String| {
+            out.push(FoundString {
+                address: base + offset as u64,
+                encoding,
+                value,
+            })
+        };
+        let resume = self.resume;
+        match encoding.multibyte() {
+            Some(decoder) => scan_multibyte(data, resume, decoder, min_length, is_final, &mut emit),
+            None if encoding == StringEncoding::Utf16le => {
+                scan_utf16le(data, resume, min_length, is_final, &mut emit)
+            }
+            None => scan_ascii(data, resume, min_length, is_final, &mut emit),
+        }
+    }
+
+    fn feed(&mut self, address: u64, chunk: &[u8], min_length: usize, out: &mut Vec<FoundString>) {
+        if !self.carry.is_empty() && self.carry_address + self.carry.len() as u64 != address {
+            // Not contiguous with the carried bytes, so those can't grow any further
+            self.finish(min_length, out);
+        }
+
+        if self.carry.is_empty() {
+            // Common case: scan the chunk in place and keep only its tail
+            let (consumed, resume) = self.scan(chunk, min_length, false, address, out);
+            self.carry.extend_from_slice(&chunk[consumed..]);
+            self.carry_address = address + consumed as u64;
+            self.resume = resume;
+        } else {
+            // Only the bytes after the part of the open run already scanned
+            // are looked at
+            self.carry.extend_from_slice(chunk);
+            let carry = std::mem::take(&mut self.carry);
+            let (consumed, resume) = self.scan(&carry, min_length, false, self.carry_address, out);
+            self.carry = carry;
+            self.carry.drain(..consumed);
+            self.carry_address += consumed as u64;
+            self.resume = resume;
+        }
+    }
+
+    fn finish(&mut self, min_length: usize, out: &mut Vec<FoundString>) {
+        let carry = std::mem::take(&mut self.carry);
+        self.scan(&carry, min_length, true, self.carry_address, out);
+        self.resume = 0;
+    }
+}
+
+/// Incremental string extractor fed one chunk of memory at a time
+pub struct StringExtractor {
+    min_length: usize,
+    pending: Vec<Pending>,
+}
+
+impl StringExtractor {
+    pub fn new(encodings: &[StringEncoding], min_length: usize) -> Self {
+        let mut pending: Vec<Pending> = Vec::new();
+        for &encoding in encodings {
+            if pending.iter().all(|p| p.encoding != encoding) {
+                pending.push(Pending {
+                    encoding,
+                    carry: Vec::new(),
+                    carry_address: 0,
+                    resume: 0,
+                });
+            }
+        }
+        StringExtractor {
+            min_length: min_length.max(1),
+            pending,
+        }
+    }
+
+    /// Scan a chunk read from `address`, appending completed strings to `out`
+    pub fn feed(&mut self, address: u64, chunk: &[u8], out: &mut Vec<FoundString>) {
+        for pending in &mut self.pending {
+            pending.feed(address, chunk, self.min_length, out);
+        }
+    }
+
+    /// Flush strings still open at the end of a region
+    pub fn finish(&mut self, out: &mut Vec<FoundString>) {
+        for pending in &mut self.pending {
+            pending.finish(self.min_length, out);
+        }
+    }
+}
+
+/// A kind of character a [`StringFilter`] can require
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CharClass {
+    Digit,
+    Letter,
+    Upper,
+    Lower,
+    Punctuation,
+    Whitespace,
+}
+
+impl CharClass {
+    pub fn contains(self, c: char) -> bool {
+        match self {
+            CharClass::Digit => c.is_numeric(),
+            CharClass::Letter => c.is_alphabetic(),
+            CharClass::Upper => c.is_uppercase(),
+            CharClass::Lower => c.is_lowercase(),
+            CharClass::Punctuation => c.is_ascii_punctuation(),
+            CharClass::Whitespace => c.is_whitespace(),
+        }
+    }
+}
+
+/// Tests a found string must pass beyond its minimum length, so garbage
+/// such as padding runs and byte arrays that happen to be printable is
+/// dropped before it leaves the scan
+#[derive(Debug, Clone, Default)]
+pub struct StringFilter {
+    /// Longest string kept, in characters
+    pub max_length: Option<usize>,
+    /// Classes a string must have at least one character of, each
+    pub must_contain: Vec<CharClass>,
+    /// The only characters a string may hold, if restricted
+    pub alphabet: Option<Vec<RangeInclusive<char>>>,
+    /// Fewest different characters a string must hold
+    pub min_distinct_chars: Option<usize>,
+}
+
+impl StringFilter {
+    /// Parse an alphabet such as `0-9a-fA-F`: each character stands for
+    /// itself, and two joined by `-` for the range between them. A `-` at
+    /// either end is literal.
+    pub fn parse_alphabet(alphabet: &str) -> Result<Vec<RangeInclusive<char>>, String> {
+        let chars: Vec<char> = alphabet.chars().collect();
+        let mut ranges = Vec::new();
+        let mut i = 0;
+        while i < chars.len() {
+            if i + 2 < chars.len() && chars[i + 1] == '-' {
+                let (low, high) = (chars[i], chars[i + 2]);
+                if low > high {
+                    return Err(format!("alphabet range {}-{} is reversed", low, high));
+                }
+                ranges.push(low..=high);
+                i += 3;
+            } else {
+                ranges.push(chars[i]..=chars[i]);
+                i += 1;
+            }
+        }
+        if ranges.is_empty() {
+            return Err("alphabet is empty".into());
+        }
+        Ok(ranges)
+    }
+
+    /// Whether `value` passes every test
+    pub fn accepts(&self, value: &str) -> bool {
+        if self.max_length.is_some_and(|max| value.chars().nth(max).is_some()) {
+            return false;
+        }
+        if let Some(alphabet) = &self.alphabet {
+            if !value.chars().all(|c| alphabet.iter().any(|range| range.contains(&c))) {
+                return false;
+            }
+        }
+        let has_class = |class: CharClass| value.chars().any(|c| class.contains(c));
+        if !self.must_contain.iter().all(|&class| has_class(class)) {
+            return false;
+        }
+        match self.min_distinct_chars {
+            Some(min) if min > 1 => {
+                let mut seen: Vec<char> = Vec::with_capacity(min);
+                for c in value.chars() {
+                    if !seen.contains(&c) {
+                        seen.push(c);
+                        if seen.len() >= min {
+                            return true;
+                        }
+                    }
+                }
+                false
+            }
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16(text: &str) -> Vec<u8> {
+        text.encode_utf16()
+            .flat_map(|unit| unit.to_le_bytes())
+            .collect()
+    }
+
+    fn extract_all(
+        encodings: &[StringEncoding],
+        chunks: &[&[u8]],
+        min_length: usize,
+    ) -> Vec<FoundString> {
+        let mut extractor = StringExtractor::new(encodings, min_length);
+        let mut out = Vec::new();
+        let mut address = 0x1000;
+        for chunk in chunks {
+            extractor.feed(address, chunk, &mut out);
+            address += chunk.len() as u64;
+        }
+        extractor.finish(&mut out);
+        out
+    }
+
+    #[test]
+    fn test_extract_printable_strings() {
+        let data = b"Hello\x00World\x00Test123\x00";
+        let strings = extract_printable_strings(data, 3);
+        assert!(strings.contains(&"Hello".to_string()));
+        assert!(strings.contains(&"World".to_string()));
+        assert!(strings.contains(&"Test123".to_string()));
+    }
+
+    #[test]
+    fn test_word_classes_agree_with_bytes() {
+        for class in [RunClass::Ascii, RunClass::Multibyte] {
+            for byte in 0..=255u8 {
+                for lane in 0..8 {
+                    let mut word = [b'a'; 8];
+                    word[lane] = byte;
+                    let outside = class.outside(u64::from_le_bytes(word));
+                    let expected = if class.contains(byte) { 0 } else { 0x80 << (lane * 8) };
+                    assert_eq!(outside, expected, "{:?} {:#x} in lane {}", class, byte, lane);
+                }
+            }
+        }
+
+        let data = b"\x00\x01abc\x7fdefghijklmnop\x80\xffqrs";
+        assert_eq!(RunClass::Ascii.find(data, 0, true), 2);
+        assert_eq!(RunClass::Ascii.find(data, 2, false), 5);
+        assert_eq!(RunClass::Ascii.find(data, 6, false), 19);
+        assert_eq!(RunClass::Multibyte.find(data, 6, false), data.len());
+    }
+
+    #[test]
+    fn test_string_filter() {
+        let filter = StringFilter {
+            max_length: Some(8),
+            must_contain: vec![CharClass::Digit],
+            alphabet: Some(StringFilter::parse_alphabet("0-9a-f-").unwrap()),
+            min_distinct_chars: Some(3),
+        };
+        assert!(filter.accepts("dead-b33"));
+        assert!(!filter.accepts("deadbeef"), "no digit");
+        assert!(!filter.accepts("0123456789"), "too long");
+        assert!(!filter.accepts("00000000"), "padding");
+        assert!(!filter.accepts("0xdead"), "outside the alphabet");
+        assert!(StringFilter::parse_alphabet("z-a").is_err());
+        assert!(StringFilter::default().accepts("anything"));
+    }
+
+    #[test]
+    fn test_ascii_string_split_across_chunks() {
+        let found = extract_all(
+            &[StringEncoding::Ascii],
+            &[b"\x00\x00pass", b"word\x00ok"],
+            4,
+        );
+
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].value, "password");
+        assert_eq!(found[0].address, 0x1002);
+    }
+
+    #[test]
+    fn test_run_spanning_many_chunks() {
+        let text = "a long run of text, róughly 世界 long enough to span many chunks 😀";
+        for (encoding, bytes) in [
+            (StringEncoding::Ascii, b"sk-0123456789abcdefghijklmnopqrstuvwxyz".to_vec()),
+            (StringEncoding::Utf16le, utf16(text)),
+            (StringEncoding::Utf8, text.as_bytes().to_vec()),
+        ] {
+            let mut data = vec![0u8, 0];
+            data.extend(&bytes);
+            data.extend([0, 0, b'e', b'n', b'd', b'!']);
+            // Odd sizes, so UTF-16 units and UTF-8 sequences are split too
+            for size in [1, 3, 5, 7] {
+                let chunks: Vec<&[u8]> = data.chunks(size).collect();
+                let found = extract_all(&[encoding], &chunks, 4);
+                let expected = String::from_utf8(bytes.clone()).unwrap_or(text.to_string());
+                assert_eq!(found[0].value, expected, "{:?} in chunks of {}", encoding, size);
+                assert_eq!(found[0].address, 0x1002);
+            }
+        }
+    }
+
+    #[test]
+    fn test_utf16le_with_surrogate_pair_split_across_chunks() {
+        let mut data = vec![0u8, 0];
+        data.extend(utf16("héllo 世界 😀!"));
+        data.extend([0, 0]);
+        // Split in the middle of the emoji's surrogate pair
+        let emoji = data.len() - 8;
+        let (first, second) = data.split_at(emoji + 2);
+
+        let found = extract_all(&[StringEncoding::Utf16le], &[first, second], 4);
+
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].value, "héllo 世界 😀!");
+        assert_eq!(found[0].address, 0x1002);
+        assert_eq!(found[0].encoding, StringEncoding::Utf16le);
+    }
+
+    #[test]
+    fn test_utf16le_unpaired_surrogate_ends_run() {
+        let mut data = utf16("abcd");
+        data.extend(0xD800u16.to_le_bytes());
+        data.extend(utf16("efgh"));
+
+        let found = extract_all(&[StringEncoding::Utf16le], &[&data], 4);
+        let values: Vec<_> = found.iter().map(|f| f.value.as_str()).collect();
+        assert_eq!(values, ["abcd", "efgh"]);
+    }
+
+    #[test]
+    fn test_multibyte_encodings() {
+        for (encoding, codec) in [
+            (StringEncoding::Utf8, UTF_8),
+            (StringEncoding::ShiftJis, SHIFT_JIS),
+            (StringEncoding::Gbk, GBK),
+            (StringEncoding::EucKr, EUC_KR),
+        ] {
+            let text = match encoding {
+                StringEncoding::EucKr => "비밀번호 변경",
+                StringEncoding::Gbk => "用户名和密码",
+                _ => "パスワードを入力",
+            };
+            let mut data = b"\x00\x01".to_vec();
+            data.extend_from_slice(&codec.encode(text).0);
+            data.extend_from_slice(b"\x00only ascii\x00");
+
+            let found = extract_all(&[encoding], &[&data[..5], &data[5..]], 4);
+
+            assert_eq!(found.len(), 1, "{:?}", encoding);
+            assert_eq!(found[0].value, text);
+            assert_eq!(found[0].address, 0x1002);
+        }
+    }
+
+    #[test]
+    fn test_multibyte_malformed_sequence_splits_run() {
+        let mut data = "日本語の文字列".as_bytes().to_vec();
+        data.push(0xFF);
+        let second = data.len();
+        data.extend_from_slice("もう一つの文字列".as_bytes());
+
+        let found = extract_all(&[StringEncoding::Utf8], &[&data], 4);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].value, "日本語の文字列");
+        assert_eq!(found[1].value, "もう一つの文字列");
+        assert_eq!(found[1].address, 0x1000 + second as u64);
+    }
+
+    #[test]
+    fn test_multibyte_text_after_malformed_bytes() {
+        let mut data = vec![0xff; 4096];
+        data.extend("日本語のテキスト".as_bytes());
+        data.push(0xff);
+
+        let found = extract_all(&[StringEncoding::Utf8], &[&data], 4);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].value, "日本語のテキスト");
+        assert_eq!(found[0].address, 0x1000 + 4096);
+    }
+
+    #[test]
+    fn test_both_encodings() {
+        let mut data = b"ascii text\x00\x00".to_vec();
+        data.extend(utf16("wide text"));
+
+        let found = extract_all(
+            &[StringEncoding::Ascii, StringEncoding::Utf16le],
+            &[&data],
+            6,
+        );
+        assert!(found
+            .iter()
+            .any(|f| f.encoding == StringEncoding::Ascii && f.value == "ascii text"));
+        assert!(found
+            .iter()
+            .any(|f| f.encoding == StringEncoding::Utf16le && f.value == "wide text"));
+    }
+}
@@ -0,0 +1,199 @@
+//! Names for addresses within loaded libraries
+//!
+//! A library's `.dynsym`, and its `.symtab` where it wasn't stripped, are
+//! read from the file the maps name; `.symtab` is never loaded, so memory
+//! alone can't name a library's internal functions. Addresses are named as
+//! the function or object they lie in, e.g. `libfoo.so!decrypt_config+0x24`.
+//! Tables are read the first time an address in the library is named.
+//!
+//! Libraries loaded straight from an APK, whose maps name the APK, and
+//! files no longer on disk, as for a dump pulled from another device, go
+//! unnamed. C++ names are left mangled.
+
+use crate::elf::{self, Symbol, PT_LOAD, SHT_DYNSYM, SHT_SYMTAB};
+use crate::maps::MemoryRegion;
+use crate::pointers::{self, Module};
+use crate::scan::Located;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+
+/// Largest symbol or string table read
+const MAX_TABLE_SIZE: u64 = 64 << 20;
+
+/// Symbols before an address searched for one it lies in, as symbols can
+/// overlap
+const MAX_OVERLAP: usize = 16;
+
+const EM_ARM: u16 = 40;
+
+/// The functions and objects a library defines, by link-time address
+pub struct SymbolTable {
+    /// Link-time address of the start of the file, which is mapped at the
+    /// module's base
+    link_base: u64,
+    /// Sorted by address
+    symbols: Vec<Symbol>,
+}
+
+impl SymbolTable {
+    /// The symbols `symbols` has of functions and objects defined there
+    pub fn new(link_base: u64, mut symbols: Vec<Symbol>) -> Self {
+        let kinds = [elf::STT_FUNC, elf::STT_OBJECT, elf::STT_GNU_IFUNC];
+        symbols.retain(|symbol| {
+            symbol.defined && symbol.value != 0 && !symbol.name.is_empty()
+                && kinds.contains(&symbol.kind)
+        });
+        // Stable, so `.dynsym` keeps its names where `.symtab` repeats them
+        symbols.sort_by_key(|symbol| symbol.value);
+        symbols.dedup_by(|a, b| a.value == b.value && a.size == b.size);
+        SymbolTable { link_base, symbols }
+    }
+
+    /// The symbols of the ELF file at `path`; `None` if it isn't ELF
+    pub fn read(path: &Path) -> io::Result<Option<Self>> {
+        let file = File::open(path)?;
+        let mut header = [0u8; 64];
+        let read = file.read_at(&mut header, 0)?;
+        let Some(header) = elf::parse_header(&header[..read]) else {
+            return Ok(None);
+        };
+
+        let table = read_bytes(&file, header.phoff, elf::program_headers_size(&header) as u64)?;
+        let link_base = elf::parse_program_headers(&header, &table)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|segment| segment.kind == PT_LOAD)
+            .min_by_key(|segment| segment.vaddr)
+            .map_or(0, |segment| segment.vaddr.saturating_sub(segment.offset));
+
+        let table = read_bytes(&file, header.shoff, elf::section_headers_size(&header) as u64)?;
+        let sections = elf::parse_section_headers(&header, &table).unwrap_or_default();
+        let mut symbols = Vec::new();
+        for kind in [SHT_DYNSYM, SHT_SYMTAB] {
+            for section in sections.iter().filter(|section| section.kind == kind) {
+                let Some(names) = sections.get(section.link as usize) else {
+                    continue;
+                };
+                if section.size > MAX_TABLE_SIZE || names.size > MAX_TABLE_SIZE {
+                    continue;
+                }
+                let table = read_bytes(&file, section.offset, section.size)?;
+                let names = read_bytes(&file, names.offset, names.size)?;
+                symbols.extend(elf::parse_symbols(header.class64, &table, &names));
+            }
+        }
+        // Thumb functions have the low bit of their address set
+        if header.machine == EM_ARM {
+            for symbol in symbols.iter_mut().filter(|symbol| symbol.kind == elf::STT_FUNC) {
+                symbol.value &= !1;
+            }
+        }
+        Ok(Some(SymbolTable::new(link_base, symbols)))
+    }
+
+    /// Where the symbol `name` is in the module, mapped at `base`
+    pub fn address(&self, name: &str, base: u64) -> Option<u64> {
+        let symbol = self.symbols.iter().find(|symbol| symbol.name == name)?;
+        Some(symbol.value.wrapping_sub(self.link_base).wrapping_add(base))
+    }
+
+    /// The symbol the link-time address `address` lies in
+    pub fn lookup(&self, address: u64) -> Option<&Symbol> {
+        let index = self.symbols.partition_point(|symbol| symbol.value <= address);
+        self.symbols[..index]
+            .iter()
+            .rev()
+            .take(MAX_OVERLAP)
+            .find(|symbol| address - symbol.value < symbol.size.max(1))
+    }
+}
+
+fn read_bytes(file: &File, offset: u64, size: u64) -> io::Result<Vec<u8>> {
+    let mut bytes = vec![0u8; size as usize];
+    file.read_exact_at(&mut bytes, offset)?;
+    Ok(bytes)
+}
+
+/// Names addresses of one process by the symbols of its libraries
+pub struct Symbolizer {
+    modules: Vec<Module>,
+    /// Tables read so far, by index into `modules`; `None` for those that
+    /// couldn't be
+    tables: HashMap<usize, Option<SymbolTable>>,
+}
+
+impl Symbolizer {
+    pub fn new(regions: &[MemoryRegion]) -> Self {
+        Symbolizer {
+            modules: pointers::modules(regions),
+            tables: HashMap::new(),
+        }
+    }
+
+    /// `libfoo.so!name+0x24` for the symbol `address` lies in
+    pub fn symbol(&mut self, address: u64) -> Option<String> {
+        self.name(address, address)
+    }
+
+    /// The symbol of a return address, found from the call before it, as
+    /// a call that never returns can be the last instruction of a function
+    pub fn caller_symbol(&mut self, return_address: u64) -> Option<String> {
+        self.name(return_address.checked_sub(1)?, return_address)
+    }
+
+    /// Name `address` by the symbol `lookup` lies in
+    fn name(&mut self, lookup: u64, address: u64) -> Option<String> {
+        let module = pointers::module_at(&self.modules, lookup)?;
+        let index = self.modules.iter().position(|other| std::ptr::eq(other, module))?;
+        let table = self
+            .tables
+            .entry(index)
+            .or_insert_with(|| SymbolTable::read(Path::new(&module.path)).ok().flatten())
+            .as_ref()?;
+        let to_link = |address: u64| {
+            address.wrapping_sub(module.base).wrapping_add(table.link_base)
+        };
+        let symbol = table.lookup(to_link(lookup))?;
+        match to_link(address).wrapping_sub(symbol.value) {
+            0 => Some(format!("{}!{}", module.name(), symbol.name)),
+            offset => Some(format!("{}!{}+0x{:x}", module.name(), symbol.name, offset)),
+        }
+    }
+
+    /// Name where each of `results` lies, where it is in a library
+    pub fn annotate<T: Located>(&mut self, results: &mut [T]) {
+        for result in results {
+            let location = result.location_mut();
+            location.symbol = self.symbol(location.address);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps;
+
+    static MARKER: [u8; 64] = [0x5a; 64];
+
+    #[test]
+    fn test_names_own_symbols() {
+        let regions = maps::read_maps(std::process::id() as i32).unwrap();
+        let mut symbolizer = Symbolizer::new(&regions);
+
+        let function = test_names_own_symbols as *const () as u64;
+        let name = symbolizer.symbol(function + 4).unwrap();
+        assert!(name.contains("!") && name.contains("test_names_own_symbols"), "{}", name);
+        assert!(name.ends_with("+0x4"), "{}", name);
+        let name = symbolizer.caller_symbol(function + 1).unwrap();
+        assert!(name.contains("test_names_own_symbols") && name.ends_with("+0x1"), "{}", name);
+
+        let marker = std::hint::black_box(&MARKER).as_ptr() as u64;
+        let name = symbolizer.symbol(marker + 8).unwrap();
+        assert!(name.contains("MARKER") && name.ends_with("+0x8"), "{}", name);
+        assert_eq!(symbolizer.symbol(8), None);
+    }
+}
@@ -0,0 +1,135 @@
+//! Locating the stack of every thread of a process
+//!
+//! Transient data such as text being typed often lives only on a worker
+//! thread's stack. Only some stacks are named in the maps (`[stack]` for
+//! the main thread, `[anon:stack_and_tls:<tid>]` for bionic pthreads), so
+//! the rest are found through the stack pointer each thread last stopped
+//! with, from /proc/[pid]/task/[tid]/syscall.
+
+use crate::maps::MemoryRegion;
+use serde::Serialize;
+use std::fs;
+use std::io;
+
+/// A thread and the region holding its stack
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThreadStack {
+    pub tid: i32,
+    /// Thread name, from /proc/[pid]/task/[tid]/comm
+    pub name: String,
+    /// Stack pointer when the thread last entered the kernel, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stack_pointer: Option<u64>,
+    /// Bounds of the stack region; missing if it couldn't be located
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stack_start: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stack_end: Option<u64>,
+}
+
+/// Thread IDs of a process, in ascending order
+pub fn list_threads(pid: i32) -> io::Result<Vec<i32>> {
+    let mut tids = Vec::new();
+    for entry in fs::read_dir(format!("/proc/{}/task", pid))? {
+        let entry = entry?;
+        if let Some(tid) = entry.file_name().to_str().and_then(|name| name.parse().ok()) {
+            tids.push(tid);
+        }
+    }
+    tids.sort_unstable();
+    Ok(tids)
+}
+
+/// Locate the stack of every thread of `pid` among its `regions`.
+///
+/// Threads that exit while the task list is walked are skipped.
+pub fn find_thread_stacks(pid: i32, regions: &[MemoryRegion]) -> io::Result<Vec<ThreadStack>> {
+    let mut stacks = Vec::new();
+    for tid in list_threads(pid)? {
+        let task = format!("/proc/{}/task/{}", pid, tid);
+        let Ok(name) = fs::read_to_string(format!("{}/comm", task)) else {
+            continue;
+        };
+        let stack_pointer = fs::read_to_string(format!("{}/syscall", task))
+            .ok()
+            .and_then(|syscall| parse_stack_pointer(&syscall));
+
+        let region = stack_region(pid, tid, stack_pointer, regions);
+        stacks.push(ThreadStack {
+            tid,
+            name: name.trim_end().to_string(),
+            stack_pointer,
+            stack_start: region.map(|region| region.start),
+            stack_end: region.map(|region| region.end),
+        });
+    }
+    Ok(stacks)
+}
+
+/// The region a thread's stack lives in: the one named after it, or else
+/// the one holding its stack pointer
+fn stack_region(
+    pid: i32,
+    tid: i32,
+    stack_pointer: Option<u64>,
+    regions: &[MemoryRegion],
+) -> Option<&MemoryRegion> {
+    let names = [format!("[anon:stack_and_tls:{}]", tid), format!("[stack:{}]", tid)];
+    let named = regions.iter().find(|region| {
+        let path = region.path.as_deref();
+        path.is_some_and(|path| names.iter().any(|name| name == path))
+            || (tid == pid && path == Some("[stack]"))
+    });
+    named.or_else(|| {
+        let stack_pointer = stack_pointer?;
+        regions.iter().find(|region| region.contains(stack_pointer))
+    })
+}
+
+/// Stack pointer from a task's syscall file: `<nr> <args...> <sp> <pc>` in
+/// a syscall, `-1 <sp> <pc>` when blocked elsewhere, `running` on a CPU
+fn parse_stack_pointer(syscall: &str) -> Option<u64> {
+    let fields: Vec<&str> = syscall.split_whitespace().collect();
+    if fields.len() < 3 {
+        return None;
+    }
+    let sp = fields[fields.len() - 2].strip_prefix("0x")?;
+    u64::from_str_radix(sp, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_finds_stack_of_blocked_thread() {
+        assert_eq!(parse_stack_pointer("running"), None);
+        assert_eq!(parse_stack_pointer("-1 0x7ffc1000 0x7f002000"), Some(0x7ffc1000));
+        let in_syscall = "202 0x1 0x2 0x3 0x4 0x5 0x6 0x7ffc2000 0x7f003000";
+        assert_eq!(parse_stack_pointer(in_syscall), Some(0x7ffc2000));
+
+        let (to_test, from_thread) = mpsc::channel();
+        let (to_thread, from_test) = mpsc::channel::<()>();
+        let worker = std::thread::spawn(move || {
+            let local = [0u8; 64];
+            // SAFETY: gettid has no preconditions
+            let tid = unsafe { libc::gettid() };
+            to_test.send((tid, local.as_ptr() as u64)).unwrap();
+            // Block in the kernel so the syscall file has a stack pointer
+            from_test.recv().unwrap();
+        });
+
+        let (tid, local) = from_thread.recv().unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        let pid = std::process::id() as i32;
+        let stacks = find_thread_stacks(pid, &maps::read_maps(pid).unwrap()).unwrap();
+        to_thread.send(()).unwrap();
+        worker.join().unwrap();
+
+        let stack = stacks.iter().find(|stack| stack.tid == tid).unwrap();
+        assert!(stack.stack_start.unwrap() <= local && local < stack.stack_end.unwrap());
+    }
+}
@@ -0,0 +1,264 @@
+//! Pacing of scans that must not disturb the device
+//!
+//! A monitoring scan left running in the background on a device under test
+//! shouldn't itself cause jank or thermal throttling. A [`Throttle`] paces a
+//! region walk between chunks: it can cap the share of a core each scanning
+//! thread uses, pause after every so many mebibytes read, and hold the scan
+//! while the device's thermal zones are hotter than a limit.
+
+use crate::filter;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const THERMAL_DIR: &str = "/sys/class/thermal";
+
+/// Work done between two CPU-limit pauses
+const SLICE: Duration = Duration::from_millis(50);
+
+/// How often thermal zones are read, and how long a hot device is left to
+/// cool before they are read again
+const THERMAL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Longest single sleep, so a cancel or deadline isn't kept waiting
+const MAX_NAP: Duration = Duration::from_millis(100);
+
+/// Throttling options, the `throttle` object of the scan options. Nothing
+/// is throttled by default.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ThrottleOptions {
+    /// Most of one core each scanning thread may keep busy, in percent
+    pub max_cpu_percent: Option<u32>,
+    /// Pause for `sleepMs` after every this many MiB read
+    pub sleep_every_mb: Option<u64>,
+    pub sleep_ms: u64,
+    /// Hold the scan while any thermal zone is at or above this many °C
+    pub max_temperature: Option<f64>,
+    /// Types of the thermal zones to watch, as wildcard patterns; all of
+    /// them if empty
+    pub thermal_zones: Vec<String>,
+}
+
+/// One of the device's thermal sensors
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThermalZone {
+    /// What the zone measures, such as `cpu-1-0-usr` or `battery`
+    pub kind: String,
+    /// In °C
+    pub temperature: f64,
+}
+
+/// Read every thermal zone under `/sys/class/thermal`. Zones whose
+/// temperature can't be read, as some report only while powered, are left
+/// out.
+pub fn read_thermal_zones() -> io::Result<Vec<ThermalZone>> {
+    read_thermal_zones_in(Path::new(THERMAL_DIR))
+}
+
+fn read_thermal_zones_in(dir: &Path) -> io::Result<Vec<ThermalZone>> {
+    let mut zones = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_zone = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with("thermal_zone"));
+        if !is_zone {
+            continue;
+        }
+        let temperature = fs::read_to_string(path.join("temp"))
+            .ok()
+            .and_then(|temp| temp.trim().parse::<i64>().ok());
+        let Some(millidegrees) = temperature else {
+            continue;
+        };
+        let kind = fs::read_to_string(path.join("type")).unwrap_or_default();
+        zones.push(ThermalZone {
+            kind: kind.trim().to_string(),
+            temperature: millidegrees as f64 / 1000.0,
+        });
+    }
+    zones.sort_by(|a, b| a.kind.cmp(&b.kind));
+    Ok(zones)
+}
+
+/// Shared pacing state of one scan, used by every thread it runs on
+#[derive(Debug)]
+pub struct Throttle {
+    options: ThrottleOptions,
+    paused_us: AtomicU64,
+    thermal: Mutex<Thermal>,
+}
+
+#[derive(Debug, Default)]
+struct Thermal {
+    /// When the zones were last read, and whether they were too hot
+    checked: Option<(Instant, bool)>,
+    /// Hottest watched zone seen during the scan
+    hottest: Option<f64>,
+}
+
+/// Pacing state of one scanning thread
+#[derive(Debug, Default)]
+pub struct Pacer {
+    /// Start of the work done since the last CPU-limit pause
+    busy_since: Option<Instant>,
+    /// Bytes read since the last `sleepEveryMb` pause
+    bytes: u64,
+}
+
+impl Throttle {
+    /// A throttle for `options`, or `None` if they don't throttle anything
+    pub fn new(options: &ThrottleOptions) -> Option<Self> {
+        let cpu = options.max_cpu_percent.is_some_and(|percent| percent < 100);
+        let sleep = options.sleep_every_mb.is_some() && options.sleep_ms > 0;
+        if !cpu && !sleep && options.max_temperature.is_none() {
+            return None;
+        }
+        Some(Throttle {
+            options: options.clone(),
+            paused_us: AtomicU64::new(0),
+            thermal: Mutex::new(Thermal::default()),
+        })
+    }
+
+    /// Pace a thread that has just handled a chunk of `len` bytes, sleeping
+    /// as the options require. Sleeps end early once `stop` returns true.
+    pub fn pace(&self, pacer: &mut Pacer, len: usize, stop: &dyn Fn() -> bool) {
+        let mut paused = false;
+        if let Some(percent) = self.options.max_cpu_percent.filter(|&percent| percent < 100) {
+            let busy = pacer.busy_since.get_or_insert_with(Instant::now).elapsed();
+            if busy >= SLICE {
+                let percent = percent.max(1);
+                self.sleep(busy * (100 - percent) / percent, stop);
+                paused = true;
+            }
+        }
+
+        if let Some(every) = self.options.sleep_every_mb.filter(|_| self.options.sleep_ms > 0) {
+            pacer.bytes += len as u64;
+            if pacer.bytes >= every.max(1) << 20 {
+                pacer.bytes = 0;
+                self.sleep(Duration::from_millis(self.options.sleep_ms), stop);
+                paused = true;
+            }
+        }
+
+        if let Some(limit) = self.options.max_temperature {
+            let mut fresh = false;
+            while self.too_hot(limit, fresh) && !stop() {
+                self.sleep(THERMAL_INTERVAL, stop);
+                fresh = true;
+                paused = true;
+            }
+        }
+
+        // Time spent paused doesn't count as work
+        if paused && pacer.busy_since.is_some() {
+            pacer.busy_since = Some(Instant::now());
+        }
+    }
+
+    /// Whether a watched zone is at `limit` or above, reading the zones
+    /// again if `fresh` or the last reading is old
+    fn too_hot(&self, limit: f64, fresh: bool) -> bool {
+        let mut thermal = self.thermal.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((checked, hot)) = thermal.checked {
+            if !fresh && checked.elapsed() < THERMAL_INTERVAL {
+                return hot;
+            }
+        }
+        let hottest = read_thermal_zones()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|zone| {
+                let patterns = &self.options.thermal_zones;
+                patterns.is_empty()
+                    || patterns.iter().any(|pattern| filter::wildcard_match(pattern, &zone.kind))
+            })
+            .map(|zone| zone.temperature)
+            .fold(None, |hottest: Option<f64>, temperature| {
+                Some(hottest.map_or(temperature, |hottest| hottest.max(temperature)))
+            });
+        if let Some(temperature) = hottest {
+            thermal.hottest = Some(thermal.hottest.map_or(temperature, |t| t.max(temperature)));
+        }
+        let hot = hottest.is_some_and(|temperature| temperature >= limit);
+        thermal.checked = Some((Instant::now(), hot));
+        hot
+    }
+
+    fn sleep(&self, duration: Duration, stop: &dyn Fn() -> bool) {
+        let started = Instant::now();
+        while !stop() {
+            let left = duration.saturating_sub(started.elapsed());
+            if left.is_zero() {
+                break;
+            }
+            thread::sleep(left.min(MAX_NAP));
+        }
+        let slept = started.elapsed().as_micros() as u64;
+        self.paused_us.fetch_add(slept, Ordering::Relaxed);
+    }
+
+    /// Total time the scan's threads spent paused
+    pub fn paused(&self) -> Duration {
+        Duration::from_micros(self.paused_us.load(Ordering::Relaxed))
+    }
+
+    /// Hottest watched thermal zone seen, in °C, if the zones were read
+    pub fn hottest(&self) -> Option<f64> {
+        self.thermal.lock().unwrap_or_else(|e| e.into_inner()).hottest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_thermal_zones() {
+        let dir = std::env::temp_dir().join(format!("thermal-test-{}", std::process::id()));
+        let zones = [("thermal_zone0", "cpu-0", "45500"), ("thermal_zone1", "battery", "")];
+        for (zone, kind, temp) in zones {
+            fs::create_dir_all(dir.join(zone)).unwrap();
+            fs::write(dir.join(zone).join("type"), format!("{}\n", kind)).unwrap();
+            fs::write(dir.join(zone).join("temp"), temp).unwrap();
+        }
+        fs::create_dir_all(dir.join("cooling_device0")).unwrap();
+
+        let zones = read_thermal_zones_in(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            zones,
+            [ThermalZone {
+                kind: "cpu-0".into(),
+                temperature: 45.5
+            }]
+        );
+    }
+
+    #[test]
+    fn test_pauses_after_every_mebibyte() {
+        assert!(Throttle::new(&ThrottleOptions::default()).is_none());
+        let options = ThrottleOptions {
+            sleep_every_mb: Some(1),
+            sleep_ms: 20,
+            ..ThrottleOptions::default()
+        };
+        let throttle = Throttle::new(&options).unwrap();
+        let mut pacer = Pacer::default();
+        for _ in 0..32 {
+            throttle.pace(&mut pacer, 64 * 1024, &|| false);
+        }
+        assert!(throttle.paused() >= Duration::from_millis(40));
+        assert_eq!(throttle.hottest(), None);
+    }
+}
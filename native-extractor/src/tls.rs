@@ -0,0 +1,525 @@
+//! TLS secrets, written out as a key log Wireshark decrypts traffic with
+//!
+//! BoringSSL, which Conscrypt, Cronet and most native code on Android use,
+//! keeps what it needs to decrypt a connection in two structures, both
+//! found by their layout rather than by symbols:
+//!
+//! - `SSL_SESSION`: a TLS 1.2 master secret, `secret_length` 48 and the
+//!   secret, then the session ID and the session ID context, each a length
+//!   byte and an array zero-padded past it, with the protocol version a
+//!   few bytes before
+//! - `SSL3_STATE`: two record sequence numbers, the server and client
+//!   randoms, then, within a kilobyte, a pointer to the established
+//!   session and for TLS 1.3 the current traffic and exporter secrets,
+//!   three secrets of the same hash length zero-padded to 48 bytes
+//!
+//! A connection gives a `CLIENT_RANDOM` line for the session it points to,
+//! or its TLS 1.3 secrets; a session with an ID also gives an
+//! `RSA Session-ID:` line, which Wireshark matches on resumption. The
+//! target is taken to be the client, as apps are, so the secret it writes
+//! with is the client's.
+
+use crate::maps::MemoryRegion;
+use crate::reader::MemoryReader;
+use crate::scan::{Location, RegionVisitor, ScanSink};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Bytes of a TLS 1.2 master secret, and of the arrays secrets are kept in
+const SECRET_LENGTH: usize = 48;
+
+/// Bytes of the randoms and the session ID and its context
+const RANDOM_LENGTH: usize = 32;
+
+/// Bytes before a session's `secret_length` the version may be found in
+const VERSION_BEFORE: usize = 8;
+
+/// Bytes of a session from `VERSION_BEFORE` before its `secret_length`
+/// to the end of its session ID context
+const SESSION_LENGTH: usize = VERSION_BEFORE + 3 + SECRET_LENGTH + 2 * RANDOM_LENGTH;
+
+/// Bytes of the sequence numbers and randoms that start a connection
+const CONNECTION_HEADER: usize = 16 + 2 * RANDOM_LENGTH;
+
+/// Bytes after a connection's randoms searched for its session and secrets
+const CONNECTION_WINDOW: usize = 1024;
+
+/// Bytes a session's struct may start before its `secret_length`
+const SESSION_START_BEFORE: u64 = 64;
+
+/// The `tls` object of the scan options
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct TlsOptions {
+    /// Key log file on the device the secrets are appended to, as
+    /// `SSLKEYLOGFILE` is; secrets are only reported if not set
+    pub keylog_file: Option<String>,
+}
+
+/// What a secret is, by the label of its key log line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum KeylogLabel {
+    #[serde(rename = "CLIENT_RANDOM")]
+    ClientRandom,
+    #[serde(rename = "SESSION_ID")]
+    SessionId,
+    #[serde(rename = "CLIENT_TRAFFIC_SECRET_0")]
+    ClientTrafficSecret,
+    #[serde(rename = "SERVER_TRAFFIC_SECRET_0")]
+    ServerTrafficSecret,
+    #[serde(rename = "EXPORTER_SECRET")]
+    ExporterSecret,
+}
+
+/// A distinct secret
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TlsSecret {
+    /// Where the secret was first seen
+    #[serde(flatten)]
+    pub location: Location,
+    pub label: KeylogLabel,
+    /// The client random of its connection, as lowercase hex
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_random: Option<String>,
+    /// The ID of its session, as lowercase hex
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    /// How many times it was seen
+    pub count: usize,
+    #[serde(skip)]
+    pub secret: Vec<u8>,
+}
+
+impl KeylogLabel {
+    /// As in the report, and in key log lines bar `SESSION_ID`
+    pub fn name(self) -> &'static str {
+        match self {
+            KeylogLabel::ClientRandom => "CLIENT_RANDOM",
+            KeylogLabel::SessionId => "SESSION_ID",
+            KeylogLabel::ClientTrafficSecret => "CLIENT_TRAFFIC_SECRET_0",
+            KeylogLabel::ServerTrafficSecret => "SERVER_TRAFFIC_SECRET_0",
+            KeylogLabel::ExporterSecret => "EXPORTER_SECRET",
+        }
+    }
+}
+
+impl TlsSecret {
+    /// Its line of an NSS key log, or for a session ID the line of
+    /// Wireshark's own form
+    pub fn keylog_line(&self) -> String {
+        let secret = hex(&self.secret);
+        match self.label {
+            KeylogLabel::SessionId => format!(
+                "RSA Session-ID:{} Master-Key:{}",
+                self.session_id.as_deref().unwrap_or_default(),
+                secret
+            ),
+            label => format!(
+                "{} {} {}",
+                label.name(),
+                self.client_random.as_deref().unwrap_or_default(),
+                secret
+            ),
+        }
+    }
+}
+
+/// An `SSL_SESSION`
+#[derive(Debug, Clone)]
+pub struct TlsSession {
+    /// Where its master secret is
+    pub location: Location,
+    pub master_secret: Vec<u8>,
+    pub session_id: Vec<u8>,
+}
+
+/// An `SSL3_STATE`
+#[derive(Debug, Clone)]
+pub struct TlsConnection {
+    /// Where its client random is
+    pub location: Location,
+    pub client_random: Vec<u8>,
+    /// The words after its randoms that could point to its session, with
+    /// any pointer tag cleared
+    pub pointers: Vec<u64>,
+    /// Its TLS 1.3 write, read and exporter secrets, and where each is
+    pub secrets: Option<[(u64, Vec<u8>); 3]>,
+}
+
+/// What [`TlsVisitor`] finds, put together by [`resolve`]
+#[derive(Debug, Clone)]
+pub enum TlsItem {
+    Session(TlsSession),
+    Connection(TlsConnection),
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Whether `bytes` have the spread of random bytes, at least three in
+/// four of them distinct
+fn random_looking(bytes: &[u8]) -> bool {
+    let mut seen = [false; 256];
+    let distinct = bytes.iter().filter(|&&b| !std::mem::replace(&mut seen[b as usize], true));
+    distinct.count() >= bytes.len() - bytes.len() / 4
+}
+
+/// The first `length` bytes of `array` if they are random and the rest
+/// zero
+fn padded(array: &[u8], length: usize) -> Option<&[u8]> {
+    let (value, padding) = array.split_at_checked(length)?;
+    (padding.iter().all(|&b| b == 0) && (value.is_empty() || random_looking(value)))
+        .then_some(value)
+}
+
+/// The master secret and session ID of the session whose `secret_length`
+/// is `VERSION_BEFORE` bytes into `data`
+fn session_at(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    let data = data.get(..SESSION_LENGTH)?;
+    let (before, rest) = data.split_at(VERSION_BEFORE);
+    // TLS 1.0 to 1.2, the versions with a master secret
+    let version = before
+        .chunks_exact(2)
+        .any(|v| matches!(u16::from_le_bytes([v[0], v[1]]), 0x0301..=0x0303));
+    if !version || rest[0] as usize != SECRET_LENGTH {
+        return None;
+    }
+    let secret = padded(&rest[1..1 + SECRET_LENGTH], SECRET_LENGTH)?;
+    let rest = &rest[1 + SECRET_LENGTH..];
+    let session_id = padded(&rest[1..1 + RANDOM_LENGTH], rest[0] as usize)?;
+    // The context is whatever the app sets, so only its padding is known
+    let context = &rest[1 + RANDOM_LENGTH..];
+    let padding = context.get(1 + context[0] as usize..1 + RANDOM_LENGTH)?;
+    padding.iter().all(|&b| b == 0).then_some((secret, session_id))
+}
+
+/// The TLS 1.3 secrets at the start of `data`, with their offsets, laid
+/// out each followed by its length or all three followed by their lengths
+fn secrets_at(data: &[u8]) -> Option<[(usize, &[u8]); 3]> {
+    // The stride of the secrets, and the offset and stride of the lengths
+    let layouts = [
+        (SECRET_LENGTH + 1, SECRET_LENGTH, SECRET_LENGTH + 1),
+        (SECRET_LENGTH, 3 * SECRET_LENGTH, 1),
+    ];
+    layouts.into_iter().find_map(|(stride, lengths, length_stride)| {
+        let length = *data.get(lengths)? as usize;
+        if length != 32 && length != SECRET_LENGTH {
+            return None;
+        }
+        let secret = |i: usize| {
+            let array = data.get(i * stride..i * stride + SECRET_LENGTH)?;
+            if *data.get(lengths + i * length_stride)? as usize != length {
+                return None;
+            }
+            Some((i * stride, padded(array, length)?))
+        };
+        Some([secret(0)?, secret(1)?, secret(2)?])
+    })
+}
+
+/// Finds the sessions and connections in each region it is fed
+#[derive(Default)]
+pub struct TlsVisitor {
+    /// Address of the first byte buffered
+    base: u64,
+    /// The bytes read and not yet searched, with those a structure
+    /// starting before the end of the last chunk may need
+    buffer: Vec<u8>,
+    /// What was found in the region being read, with the address of each
+    found: Vec<(u64, TlsItem)>,
+}
+
+impl TlsVisitor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Search the bytes buffered, all of them at the end of a run of
+    /// memory, else those the longest structure fits after
+    fn search(&mut self, end: bool) {
+        let lookahead = CONNECTION_HEADER + CONNECTION_WINDOW;
+        let len = self.buffer.len();
+        let searchable = if end { len } else { len.saturating_sub(lookahead) };
+        let placeholder = Location::new(&Arc::from(""), 0, 0);
+        for offset in 0..searchable {
+            let data = &self.buffer[offset..];
+            let address = self.base + offset as u64;
+            if data.get(VERSION_BEFORE) == Some(&(SECRET_LENGTH as u8)) {
+                if let Some((secret, session_id)) = session_at(data) {
+                    self.found.push((
+                        address + VERSION_BEFORE as u64 + 1,
+                        TlsItem::Session(TlsSession {
+                            location: placeholder.clone(),
+                            master_secret: secret.to_vec(),
+                            session_id: session_id.to_vec(),
+                        }),
+                    ));
+                }
+            }
+            if !address.is_multiple_of(8) || data.len() < CONNECTION_HEADER {
+                continue;
+            }
+            // Sequence numbers below 2^32, big-endian
+            if data[..4] != [0; 4] || data[8..12] != [0; 4] {
+                continue;
+            }
+            // No zero word either, so the sequence numbers aren't taken for
+            // the start of the randoms
+            let randoms = &data[16..CONNECTION_HEADER];
+            if !randoms.chunks(RANDOM_LENGTH).all(random_looking)
+                || randoms.chunks(4).any(|word| word == [0; 4])
+            {
+                continue;
+            }
+            let window = &data[CONNECTION_HEADER..data.len().min(lookahead)];
+            // Aligned user space addresses, bar the tag of a tagged pointer
+            let pointers: Vec<u64> = window
+                .chunks_exact(8)
+                .map(|word| u64::from_le_bytes(word.try_into().unwrap()) & ((1 << 56) - 1))
+                .filter(|&p| p.is_multiple_of(8) && (0x1000..1 << 48).contains(&p))
+                .collect();
+            let secrets = (0..window.len()).find_map(|i| secrets_at(&window[i..]).map(|s| (i, s)));
+            if pointers.is_empty() && secrets.is_none() {
+                continue;
+            }
+            let secrets_address = address + CONNECTION_HEADER as u64;
+            self.found.push((
+                address + 16 + RANDOM_LENGTH as u64,
+                TlsItem::Connection(TlsConnection {
+                    location: placeholder.clone(),
+                    client_random: randoms[RANDOM_LENGTH..].to_vec(),
+                    pointers,
+                    secrets: secrets.map(|(at, secrets)| {
+                        secrets.map(|(offset, secret)| {
+                            (secrets_address + (at + offset) as u64, secret.to_vec())
+                        })
+                    }),
+                }),
+            ));
+        }
+        self.buffer.drain(..searchable);
+        self.base += searchable as u64;
+    }
+}
+
+impl RegionVisitor for TlsVisitor {
+    type Output = TlsItem;
+
+    fn visit_chunk(&mut self, _region: &MemoryRegion, address: u64, chunk: &[u8]) {
+        if address != self.base + self.buffer.len() as u64 {
+            self.search(true);
+            self.base = address;
+        }
+        self.buffer.extend_from_slice(chunk);
+        self.search(false);
+    }
+
+    fn end_region(
+        &mut self,
+        region: &MemoryRegion,
+        _reader: &mut dyn MemoryReader,
+        sink: &mut dyn ScanSink<TlsItem>,
+    ) {
+        self.search(true);
+
+        let name: Arc<str> = region.name().into();
+        for (address, mut item) in std::mem::take(&mut self.found) {
+            let location = Location::new(&name, region.start, address);
+            match &mut item {
+                TlsItem::Session(session) => session.location = location,
+                TlsItem::Connection(connection) => connection.location = location,
+            }
+            sink.result(item);
+        }
+    }
+}
+
+/// The secrets of the sessions and connections found in one process:
+/// each connection's TLS 1.3 secrets, or the master secret of the session
+/// it points to, then a line for each session with an ID. Secrets seen
+/// more than once are reported once.
+pub fn resolve(items: Vec<TlsItem>) -> Vec<TlsSecret> {
+    let mut sessions: BTreeMap<u64, TlsSession> = BTreeMap::new();
+    let mut connections = Vec::new();
+    for item in items {
+        match item {
+            TlsItem::Session(session) => {
+                // By the address of its `secret_length`
+                sessions.insert(session.location.address - 1, session);
+            }
+            TlsItem::Connection(connection) => connections.push(connection),
+        }
+    }
+
+    let mut secrets: Vec<TlsSecret> = Vec::new();
+    let mut seen: HashMap<(KeylogLabel, String), usize> = HashMap::new();
+    let mut add = |secret: TlsSecret| {
+        let id = secret.client_random.as_ref().or(secret.session_id.as_ref());
+        let id = (secret.label, id.cloned().unwrap_or_default());
+        match seen.get(&id) {
+            Some(&index) => secrets[index].count += 1,
+            None => {
+                seen.insert(id, secrets.len());
+                secrets.push(secret);
+            }
+        }
+    };
+    for connection in connections {
+        let client_random = Some(hex(&connection.client_random));
+        if let Some(tls13) = connection.secrets {
+            let labels = [
+                KeylogLabel::ClientTrafficSecret,
+                KeylogLabel::ServerTrafficSecret,
+                KeylogLabel::ExporterSecret,
+            ];
+            for (label, (address, secret)) in labels.into_iter().zip(tls13) {
+                let offset = connection.location.offset + address - connection.location.address;
+                add(TlsSecret {
+                    location: Location {
+                        address,
+                        offset,
+                        ..connection.location.clone()
+                    },
+                    label,
+                    client_random: client_random.clone(),
+                    session_id: None,
+                    count: 1,
+                    secret,
+                });
+            }
+            continue;
+        }
+        let session = connection.pointers.iter().find_map(|&pointer| {
+            sessions.range(pointer..=pointer + SESSION_START_BEFORE).next().map(|(_, s)| s)
+        });
+        if let Some(session) = session {
+            add(TlsSecret {
+                location: session.location.clone(),
+                label: KeylogLabel::ClientRandom,
+                client_random,
+                session_id: None,
+                count: 1,
+                secret: session.master_secret.clone(),
+            });
+        }
+    }
+    for session in sessions.into_values().filter(|session| !session.session_id.is_empty()) {
+        add(TlsSecret {
+            location: session.location,
+            label: KeylogLabel::SessionId,
+            client_random: None,
+            session_id: Some(hex(&session.session_id)),
+            count: 1,
+            secret: session.master_secret,
+        });
+    }
+    secrets
+}
+
+/// Append the key log lines of `secrets` to `path`, created readable by
+/// the owner only if missing
+pub fn write_keylog(secrets: &[TlsSecret], path: &Path) -> io::Result<()> {
+    let mut file = OpenOptions::new().append(true).create(true).mode(0o600).open(path)?;
+    let mut lines = String::new();
+    for secret in secrets {
+        lines.push_str(&secret.keylog_line());
+        lines.push('\n');
+    }
+    file.write_all(lines.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps::parse_maps_line;
+    use crate::reader::ProcfsReader;
+    use crate::scan::{self, Scan, ScanControl};
+
+    /// Bytes that look random, from `seed`
+    fn random(seed: u64, length: usize) -> Vec<u8> {
+        let mut state = seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1;
+        (0..length)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                (state >> 24) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_resolves_keylog_lines() {
+        let mut data = vec![0u8; 0x2000];
+        let start = data.as_ptr() as u64;
+
+        // A TLS 1.2 session at 0x100: references, version, group and
+        // signature algorithm, then the secret, session ID and context
+        let session = 0x100;
+        data[session + 4..session + 10].copy_from_slice(b"\x03\x03\x1d\x00\x04\x08");
+        data[session + 10] = SECRET_LENGTH as u8;
+        data[session + 11..session + 59].copy_from_slice(&random(1, SECRET_LENGTH));
+        data[session + 59] = RANDOM_LENGTH as u8;
+        data[session + 60..session + 92].copy_from_slice(&random(2, RANDOM_LENGTH));
+
+        // A connection pointing to it, with a tagged pointer
+        let tls12 = 0x800;
+        data[tls12 + 7] = 5;
+        data[tls12 + 16..tls12 + 80].copy_from_slice(&random(3, 2 * RANDOM_LENGTH));
+        let pointer = (start + session as u64) | 0xb4 << 56;
+        data[tls12 + 0x1a0..tls12 + 0x1a8].copy_from_slice(&pointer.to_le_bytes());
+
+        // A TLS 1.3 connection holding SHA-256 secrets, each followed by
+        // its length
+        let tls13 = 0x1000;
+        data[tls13 + 16..tls13 + 80].copy_from_slice(&random(4, 2 * RANDOM_LENGTH));
+        for i in 0..3 {
+            let at = tls13 + 0x120 + i * (SECRET_LENGTH + 1);
+            data[at..at + 32].copy_from_slice(&random(5 + i as u64, 32));
+            data[at + SECRET_LENGTH] = 32;
+        }
+
+        let end = start + data.len() as u64;
+        let region = parse_maps_line(&format!("{:x}-{:x} rw-p 00000000 00:00 0", start, end));
+        let pid = std::process::id() as i32;
+        let mut reader = ProcfsReader::open(pid).unwrap();
+        let mut scan = Scan::default();
+        let regions = [region.unwrap()];
+        let mut visitor = TlsVisitor::new();
+        let mut control = ScanControl::new().chunk_size(Some(0x1000));
+        scan::walk_regions(&mut reader, &regions, &mut visitor, &mut scan, &mut control);
+        let secrets = resolve(scan.results);
+
+        let labels: Vec<KeylogLabel> = secrets.iter().map(|secret| secret.label).collect();
+        assert_eq!(
+            labels,
+            [
+                KeylogLabel::ClientRandom,
+                KeylogLabel::ClientTrafficSecret,
+                KeylogLabel::ServerTrafficSecret,
+                KeylogLabel::ExporterSecret,
+                KeylogLabel::SessionId,
+            ]
+        );
+        assert_eq!(secrets[0].location.address, start + session as u64 + 11);
+        let client_random = hex(&random(3, 64)[32..]);
+        let master_secret = hex(&random(1, SECRET_LENGTH));
+        assert_eq!(
+            secrets[0].keylog_line(),
+            format!("CLIENT_RANDOM {} {}", client_random, master_secret)
+        );
+        assert_eq!(secrets[2].location.address, start + tls13 as u64 + 0x120 + 49);
+        assert_eq!(secrets[2].secret, random(6, 32));
+        assert_eq!(
+            secrets[4].keylog_line(),
+            format!("RSA Session-ID:{} Master-Key:{}", hex(&random(2, 32)), master_secret)
+        );
+    }
+}
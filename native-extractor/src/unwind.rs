@@ -0,0 +1,874 @@
+//! Backtraces of a stopped target's threads
+//!
+//! Each thread is unwound from the registers it was stopped with. Where the
+//! module holding the pc has an `.eh_frame_hdr`, found through the
+//! `PT_GNU_EH_FRAME` program header of its mapped image, the function's call
+//! frame information (CFI) says where the caller's registers and return
+//! address were saved, and they are read back as a debugger would. Where
+//! there is none, or it can't be followed, the chain of frame records is
+//! walked instead: each holds the caller's frame pointer and return address.
+//! Code built with neither, and arm32 code carrying only ARM exception
+//! tables (`.ARM.exidx`), can cut a backtrace short or skip frames, so the
+//! frames found by frame pointer are marked as such.
+//!
+//! Frames are annotated with the module the pc is in and its offset from
+//! the module's base, as tombstones show them.
+
+use crate::elf;
+use crate::pointers::{self, Module};
+use crate::reader::{self, MemoryReader};
+use crate::registers::{Arch, ThreadState};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Most frames listed for a thread
+pub const MAX_FRAMES: usize = 64;
+
+/// Longest CIE or FDE read
+const MAX_ENTRY_LENGTH: u64 = 64 << 10;
+
+/// Pointer encodings (`DW_EH_PE_*`)
+const DW_EH_PE_OMIT: u8 = 0xff;
+const DW_EH_PE_DATAREL_SDATA4: u8 = 0x3b;
+
+/// How a frame was found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Method {
+    /// The registers the thread was stopped with
+    Context,
+    /// Call frame information
+    Cfi,
+    /// A frame record
+    FramePointer,
+}
+
+/// One frame of a backtrace
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Frame {
+    pub pc: u64,
+    pub sp: u64,
+    /// Path of the module the pc is in, and the pc's offset from its base
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub module: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u64>,
+    /// The symbol the pc is in, as `libfoo.so!name+0x24`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<String>,
+    pub method: Method,
+}
+
+/// The frames of one thread, innermost first
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Backtrace {
+    pub tid: i32,
+    pub name: String,
+    pub frames: Vec<Frame>,
+    /// Why unwinding stopped before the outermost frame
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stopped: Option<String>,
+}
+
+/// How an ABI's registers are numbered and its frames laid out
+struct Abi {
+    word: usize,
+    /// Register names by DWARF register number
+    dwarf: &'static [&'static str],
+    sp: usize,
+    fp: usize,
+    /// Offsets from the frame pointer of the caller's frame pointer, the
+    /// return address and the caller's stack pointer
+    record: (i64, i64, i64),
+    /// Bits of the pc that are the address, without a pointer
+    /// authentication code or the Thumb bit
+    pc_mask: u64,
+}
+
+const ARM64: Abi = Abi {
+    word: 8,
+    dwarf: &[
+        "x0", "x1", "x2", "x3", "x4", "x5", "x6", "x7", "x8", "x9", "x10", "x11", "x12", "x13",
+        "x14", "x15", "x16", "x17", "x18", "x19", "x20", "x21", "x22", "x23", "x24", "x25", "x26",
+        "x27", "x28", "x29", "x30", "sp",
+    ],
+    sp: 31,
+    fp: 29,
+    record: (0, 8, 16),
+    pc_mask: (1 << 48) - 1,
+};
+
+/// Thumb code, which Android's arm32 libraries are, keeps its frame pointer
+/// in r7
+const ARM: Abi = Abi {
+    word: 4,
+    dwarf: &[
+        "r0", "r1", "r2", "r3", "r4", "r5", "r6", "r7", "r8", "r9", "r10", "r11", "r12", "sp", "lr",
+        "pc",
+    ],
+    sp: 13,
+    fp: 7,
+    record: (0, 4, 8),
+    pc_mask: 0xffff_fffe,
+};
+
+const X86_64: Abi = Abi {
+    word: 8,
+    dwarf: &[
+        "rax", "rdx", "rcx", "rbx", "rsi", "rdi", "rbp", "rsp", "r8", "r9", "r10", "r11", "r12",
+        "r13", "r14", "r15", "rip",
+    ],
+    sp: 7,
+    fp: 6,
+    record: (0, 8, 16),
+    pc_mask: u64::MAX,
+};
+
+const X86: Abi = Abi {
+    word: 4,
+    dwarf: &["eax", "ecx", "edx", "ebx", "esp", "ebp", "esi", "edi", "eip"],
+    sp: 4,
+    fp: 5,
+    record: (0, 4, 8),
+    pc_mask: 0xffff_ffff,
+};
+
+const RISCV64: Abi = Abi {
+    word: 8,
+    dwarf: &[
+        "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0", "a1", "a2", "a3", "a4",
+        "a5", "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t3", "t4",
+        "t5", "t6",
+    ],
+    sp: 2,
+    fp: 8,
+    record: (-16, -8, 0),
+    pc_mask: u64::MAX,
+};
+
+fn abi(arch: Arch) -> &'static Abi {
+    match arch {
+        Arch::Arm64 => &ARM64,
+        Arch::Arm => &ARM,
+        Arch::X86_64 => &X86_64,
+        Arch::X86 => &X86,
+        Arch::Riscv64 => &RISCV64,
+    }
+}
+
+/// Registers by DWARF number, as far as they are known
+type Registers = Vec<Option<u64>>;
+
+/// The outcome of unwinding one frame
+enum Step {
+    /// The caller's pc and registers
+    Caller(u64, Registers),
+    /// Nothing called this frame
+    Outermost,
+}
+
+/// A module's `.eh_frame_hdr` search table
+#[derive(Debug, Clone, Copy)]
+struct SearchTable {
+    /// Address of the header, which the table's entries are relative to
+    header: u64,
+    /// Address and number of its `(initial location, FDE)` entries
+    table: u64,
+    count: u64,
+    word: usize,
+}
+
+/// A CIE: what the FDEs sharing it have in common
+#[derive(Debug, Clone, Default)]
+struct Cie {
+    code_align: u64,
+    data_align: i64,
+    /// The CFI column of the return address
+    ra: u64,
+    fde_encoding: u8,
+    /// Bytes in an address of the module
+    word: usize,
+    /// Whether FDEs hold augmentation data, to be skipped
+    augmented: bool,
+    instructions: Vec<u8>,
+    address: u64,
+}
+
+/// An FDE: the CFI of one function
+#[derive(Debug, Clone, Default)]
+struct Fde {
+    cie: Cie,
+    start: u64,
+    end: u64,
+    instructions: Vec<u8>,
+    address: u64,
+}
+
+/// Where a register was saved, for one row of the CFI table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Rule {
+    Undefined,
+    SameValue,
+    /// Saved at this offset from the CFA
+    Offset(i64),
+    /// The CFA plus this offset
+    ValOffset(i64),
+    /// In another register
+    Register(u64),
+    /// Given by a DWARF expression, which isn't evaluated
+    Expression,
+}
+
+/// The CFI table's row for one pc
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Row {
+    /// The CFA as a register plus an offset; `None` for an expression
+    cfa: Option<(u64, i64)>,
+    rules: HashMap<u64, Rule>,
+}
+
+/// Little-endian DWARF fields read from bytes that were at `base`
+struct Cursor<'a> {
+    bytes: &'a [u8],
+    at: usize,
+    base: u64,
+    word: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(bytes: &'a [u8], base: u64, word: usize) -> Self {
+        Cursor {
+            bytes,
+            at: 0,
+            base,
+            word,
+        }
+    }
+
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.bytes.get(self.at..self.at.checked_add(len)?)?;
+        self.at += len;
+        Some(bytes)
+    }
+
+    fn unsigned(&mut self, len: usize) -> Option<u64> {
+        let fold = |value: u64, &byte: &u8| value << 8 | u64::from(byte);
+        Some(self.take(len)?.iter().rev().fold(0, fold))
+    }
+
+    fn signed(&mut self, len: usize) -> Option<i64> {
+        let shift = 64 - 8 * len as u32;
+        Some(((self.unsigned(len)? << shift) as i64) >> shift)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|bytes| bytes[0])
+    }
+
+    fn uleb(&mut self) -> Option<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    fn sleb(&mut self) -> Option<i64> {
+        let mut value = 0i64;
+        let mut shift = 0;
+        loop {
+            let byte = self.u8()?;
+            value |= i64::from(byte & 0x7f).checked_shl(shift)?;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                if shift < 64 && byte & 0x40 != 0 {
+                    value |= -1 << shift;
+                }
+                return Some(value);
+            }
+        }
+    }
+
+    fn block(&mut self) -> Option<&'a [u8]> {
+        let len = usize::try_from(self.uleb()?).ok()?;
+        self.take(len)
+    }
+
+    /// A pointer in `encoding`; `datarel` is what data-relative pointers
+    /// are relative to
+    fn encoded(&mut self, encoding: u8, datarel: u64) -> Option<u64> {
+        if encoding == DW_EH_PE_OMIT || encoding & 0x80 != 0 {
+            return None;
+        }
+        let field = self.base.wrapping_add(self.at as u64);
+        let value = match encoding & 0x0f {
+            0x00 => self.unsigned(self.word)?,
+            0x01 => self.uleb()?,
+            0x02 => self.unsigned(2)?,
+            0x03 => self.unsigned(4)?,
+            0x04 | 0x0c => self.unsigned(8)?,
+            0x09 => self.sleb()? as u64,
+            0x0a => self.signed(2)? as u64,
+            0x0b => self.signed(4)? as u64,
+            _ => return None,
+        };
+        let value = match encoding & 0x70 {
+            0x00 => value,
+            0x10 => field.wrapping_add(value),
+            0x30 => datarel.wrapping_add(value),
+            _ => return None,
+        };
+        Some(mask(value, self.word))
+    }
+}
+
+/// `value` cut to a word of `word` bytes
+fn mask(value: u64, word: usize) -> u64 {
+    if word == 4 {
+        value & 0xffff_ffff
+    } else {
+        value
+    }
+}
+
+/// Run the CFA instructions of `program`, which was at `address`, from
+/// `loc` up to `target`, updating `row`; `initial` is the row the CIE's
+/// instructions left, which restore instructions go back to
+fn execute(
+    program: &[u8],
+    address: u64,
+    cie: &Cie,
+    target: u64,
+    loc: &mut u64,
+    row: &mut Row,
+    initial: &Row,
+) -> Option<()> {
+    let mut cursor = Cursor::new(program, address, cie.word);
+    let mut remembered = Vec::new();
+    while cursor.at < program.len() {
+        let op = cursor.u8()?;
+        let low = u64::from(op & 0x3f);
+        let advance = match op & 0xc0 {
+            0x40 => Some(low),
+            0x80 => {
+                let offset = cursor.uleb()? as i64 * cie.data_align;
+                row.rules.insert(low, Rule::Offset(offset));
+                None
+            }
+            0xc0 => {
+                restore(row, initial, low);
+                None
+            }
+            _ => match op {
+                0x00 | 0x2d => None,
+                0x01 => {
+                    let to = cursor.encoded(cie.fde_encoding, 0)?;
+                    if to > target {
+                        return Some(());
+                    }
+                    *loc = to;
+                    None
+                }
+                0x02 => Some(cursor.unsigned(1)?),
+                0x03 => Some(cursor.unsigned(2)?),
+                0x04 => Some(cursor.unsigned(4)?),
+                0x05 => {
+                    let reg = cursor.uleb()?;
+                    let offset = cursor.uleb()? as i64 * cie.data_align;
+                    row.rules.insert(reg, Rule::Offset(offset));
+                    None
+                }
+                0x06 => {
+                    restore(row, initial, cursor.uleb()?);
+                    None
+                }
+                0x07 => {
+                    row.rules.insert(cursor.uleb()?, Rule::Undefined);
+                    None
+                }
+                0x08 => {
+                    row.rules.insert(cursor.uleb()?, Rule::SameValue);
+                    None
+                }
+                0x09 => {
+                    let reg = cursor.uleb()?;
+                    row.rules.insert(reg, Rule::Register(cursor.uleb()?));
+                    None
+                }
+                0x0a => {
+                    remembered.push(row.clone());
+                    None
+                }
+                0x0b => {
+                    *row = remembered.pop()?;
+                    None
+                }
+                0x0c => {
+                    row.cfa = Some((cursor.uleb()?, cursor.uleb()? as i64));
+                    None
+                }
+                0x0d => {
+                    let reg = cursor.uleb()?;
+                    row.cfa = Some((reg, row.cfa.map_or(0, |(_, offset)| offset)));
+                    None
+                }
+                0x0e => {
+                    let offset = cursor.uleb()? as i64;
+                    row.cfa = Some((row.cfa?.0, offset));
+                    None
+                }
+                0x0f => {
+                    cursor.block()?;
+                    row.cfa = None;
+                    None
+                }
+                0x10 | 0x16 => {
+                    let reg = cursor.uleb()?;
+                    cursor.block()?;
+                    row.rules.insert(reg, Rule::Expression);
+                    None
+                }
+                0x11 => {
+                    let reg = cursor.uleb()?;
+                    let offset = cursor.sleb()? * cie.data_align;
+                    row.rules.insert(reg, Rule::Offset(offset));
+                    None
+                }
+                0x12 => {
+                    let reg = cursor.uleb()?;
+                    row.cfa = Some((reg, cursor.sleb()? * cie.data_align));
+                    None
+                }
+                0x13 => {
+                    let offset = cursor.sleb()? * cie.data_align;
+                    row.cfa = Some((row.cfa?.0, offset));
+                    None
+                }
+                0x14 | 0x15 => {
+                    let reg = cursor.uleb()?;
+                    let factor = if op == 0x14 {
+                        cursor.uleb()? as i64
+                    } else {
+                        cursor.sleb()?
+                    };
+                    row.rules.insert(reg, Rule::ValOffset(factor * cie.data_align));
+                    None
+                }
+                0x2e => {
+                    cursor.uleb()?;
+                    None
+                }
+                0x2f => {
+                    let reg = cursor.uleb()?;
+                    let offset = -(cursor.uleb()? as i64) * cie.data_align;
+                    row.rules.insert(reg, Rule::Offset(offset));
+                    None
+                }
+                _ => return None,
+            },
+        };
+        if let Some(delta) = advance {
+            *loc = loc.wrapping_add(delta.wrapping_mul(cie.code_align));
+            if *loc > target {
+                return Some(());
+            }
+        }
+    }
+    Some(())
+}
+
+/// Give `reg` the rule the CIE's instructions left it with
+fn restore(row: &mut Row, initial: &Row, reg: u64) {
+    match initial.rules.get(&reg) {
+        Some(&rule) => row.rules.insert(reg, rule),
+        None => row.rules.remove(&reg),
+    };
+}
+
+/// Unwinds threads of one process
+pub struct Unwinder<'a> {
+    reader: &'a mut dyn MemoryReader,
+    modules: &'a [Module],
+    /// Each module's search table, by base address, once looked for
+    tables: HashMap<u64, Option<SearchTable>>,
+}
+
+impl<'a> Unwinder<'a> {
+    pub fn new(reader: &'a mut dyn MemoryReader, modules: &'a [Module]) -> Self {
+        Unwinder {
+            reader,
+            modules,
+            tables: HashMap::new(),
+        }
+    }
+
+    /// The backtrace of a thread stopped with the registers of `thread`
+    pub fn backtrace(&mut self, thread: &ThreadState) -> Backtrace {
+        let abi = abi(thread.arch);
+        let mut regs: Registers = abi
+            .dwarf
+            .iter()
+            .map(|name| thread.registers.iter().find(|r| r.name == *name).map(|r| r.value))
+            .collect();
+        let mut pc = thread.pc & abi.pc_mask;
+        let mut method = Method::Context;
+        let mut frames = Vec::new();
+        let mut stopped = None;
+        loop {
+            let sp = regs[abi.sp].unwrap_or_default();
+            frames.push(self.frame(pc, sp, method));
+            if frames.len() == MAX_FRAMES {
+                stopped = Some(format!("Stopped after {} frames", MAX_FRAMES));
+                break;
+            }
+            // A return address follows the call, which may be the last
+            // instruction of the function
+            let lookup = if frames.len() == 1 { pc } else { pc.wrapping_sub(1) };
+            let step = match self.cfi_step(abi, lookup, &regs) {
+                Some(step) => Some((step, Method::Cfi)),
+                None => {
+                    let step = self.frame_pointer_step(abi, &regs);
+                    step.map(|step| (step, Method::FramePointer))
+                }
+            };
+            let (caller_pc, caller_regs) = match step {
+                Some((Step::Caller(caller_pc, caller_regs), step_method)) => {
+                    method = step_method;
+                    (caller_pc & abi.pc_mask, caller_regs)
+                }
+                Some((Step::Outermost, _)) => break,
+                None => {
+                    stopped = Some(format!("No unwind information for 0x{:x}", pc));
+                    break;
+                }
+            };
+            if caller_pc == 0 {
+                break;
+            }
+            // The stack grows down, so a caller's frame is above its callee's
+            let caller_sp = caller_regs[abi.sp].unwrap_or_default();
+            if caller_sp < sp || (caller_sp == sp && caller_pc == pc) {
+                stopped = Some(format!("Stack pointer went backwards at 0x{:x}", pc));
+                break;
+            }
+            pc = caller_pc;
+            regs = caller_regs;
+        }
+        Backtrace {
+            tid: thread.tid,
+            name: thread.name.clone(),
+            frames,
+            stopped,
+        }
+    }
+
+    fn frame(&self, pc: u64, sp: u64, method: Method) -> Frame {
+        let module = pointers::module_at(self.modules, pc);
+        Frame {
+            pc,
+            sp,
+            module: module.map(|module| module.path.clone()),
+            offset: module.map(|module| pc - module.base),
+            symbol: None,
+            method,
+        }
+    }
+
+    fn read_word(&mut self, address: u64, word: usize) -> Option<u64> {
+        let bytes = self.read(address, word)?;
+        Cursor::new(&bytes, address, word).unsigned(word)
+    }
+
+    fn read(&mut self, address: u64, len: usize) -> Option<Vec<u8>> {
+        let mut bytes = vec![0u8; len];
+        let read = reader::read_into(self.reader, address, &mut bytes).ok()?;
+        (read == len).then_some(bytes)
+    }
+
+    /// Unwind a frame with the CFI for `pc`, if there is any that can be
+    /// followed
+    fn cfi_step(&mut self, abi: &Abi, pc: u64, regs: &Registers) -> Option<Step> {
+        let fde = self.find_fde(pc)?;
+        let row = fde_row(&fde, pc)?;
+        let (cfa_reg, cfa_offset) = row.cfa?;
+        let base = (*regs.get(usize::try_from(cfa_reg).ok()?)?)?;
+        let cfa = mask(base.wrapping_add(cfa_offset as u64), abi.word);
+
+        let mut caller = regs.clone();
+        for (&reg, &rule) in &row.rules {
+            let Some(slot) = usize::try_from(reg).ok().filter(|&reg| reg < caller.len()) else {
+                continue;
+            };
+            caller[slot] = match rule {
+                Rule::Undefined | Rule::Expression => None,
+                Rule::SameValue => regs[slot],
+                Rule::Offset(offset) => self.read_word(cfa.wrapping_add(offset as u64), abi.word),
+                Rule::ValOffset(offset) => Some(mask(cfa.wrapping_add(offset as u64), abi.word)),
+                Rule::Register(from) => regs.get(from as usize).copied().flatten(),
+            };
+        }
+        if row.rules.get(&fde.cie.ra) == Some(&Rule::Undefined) {
+            return Some(Step::Outermost);
+        }
+        caller[abi.sp] = Some(cfa);
+        let return_address = (*caller.get(usize::try_from(fde.cie.ra).ok()?)?)?;
+        Some(Step::Caller(return_address, caller))
+    }
+
+    /// Unwind a frame through the frame record the frame pointer points to
+    fn frame_pointer_step(&mut self, abi: &Abi, regs: &Registers) -> Option<Step> {
+        let fp = regs[abi.fp]?;
+        if fp == 0 {
+            return Some(Step::Outermost);
+        }
+        let sp = regs[abi.sp]?;
+        if fp < sp || !fp.is_multiple_of(abi.word as u64) {
+            return None;
+        }
+        let at = |offset: i64| mask(fp.wrapping_add(offset as u64), abi.word);
+        let (prev, ret, caller_sp) = abi.record;
+        let caller_fp = self.read_word(at(prev), abi.word)?;
+        let return_address = self.read_word(at(ret), abi.word)?;
+        let mut caller = vec![None; regs.len()];
+        caller[abi.fp] = Some(caller_fp);
+        caller[abi.sp] = Some(at(caller_sp));
+        Some(Step::Caller(return_address, caller))
+    }
+
+    /// The search table of the module `pc` is in, found once per module
+    fn search_table(&mut self, pc: u64) -> Option<SearchTable> {
+        let module = pointers::module_at(self.modules, pc)?;
+        let base = module.base;
+        if let Some(&table) = self.tables.get(&base) {
+            return table;
+        }
+        let table = self.load_search_table(base);
+        self.tables.insert(base, table);
+        table
+    }
+
+    fn load_search_table(&mut self, base: u64) -> Option<SearchTable> {
+        let page_size = reader::page_size();
+        let image = self.read(base, page_size)?;
+        let header = elf::parse_header(&image)?;
+        let table = image
+            .get(usize::try_from(header.phoff).ok()?..)?
+            .get(..elf::program_headers_size(&header))?;
+        let headers = elf::parse_program_headers(&header, table)?;
+        let first = headers.iter().filter(|h| h.kind == elf::PT_LOAD).map(|h| h.vaddr).min()?;
+        let bias = base.wrapping_sub(first & !(page_size as u64 - 1));
+        let eh_frame = headers.iter().find(|h| h.kind == elf::PT_GNU_EH_FRAME)?;
+
+        let word = if header.class64 { 8 } else { 4 };
+        let address = mask(bias.wrapping_add(eh_frame.vaddr), word);
+        let bytes = self.read(address, 4 + 2 * 8)?;
+        let mut cursor = Cursor::new(&bytes, address, word);
+        let version = cursor.u8()?;
+        let (frame_encoding, count_encoding, table_encoding) =
+            (cursor.u8()?, cursor.u8()?, cursor.u8()?);
+        if version != 1 || table_encoding != DW_EH_PE_DATAREL_SDATA4 {
+            return None;
+        }
+        cursor.encoded(frame_encoding, address)?;
+        let count = cursor.encoded(count_encoding, address)?;
+        Some(SearchTable {
+            header: address,
+            table: address + cursor.at as u64,
+            count,
+            word,
+        })
+    }
+
+    /// The FDE covering `pc`, by binary search of its module's table
+    fn find_fde(&mut self, pc: u64) -> Option<Fde> {
+        let table = self.search_table(pc)?;
+        let mut entry = |index: u64| {
+            let bytes = self.read(table.table.wrapping_add(8 * index), 8)?;
+            let mut cursor = Cursor::new(&bytes, 0, table.word);
+            let start = cursor.encoded(DW_EH_PE_DATAREL_SDATA4, table.header)?;
+            let fde = cursor.encoded(DW_EH_PE_DATAREL_SDATA4, table.header)?;
+            Some((start, fde))
+        };
+        let (mut low, mut high) = (0, table.count);
+        while low < high {
+            let middle = low + (high - low) / 2;
+            if entry(middle)?.0 <= pc {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+        let (_, address) = entry(low.checked_sub(1)?)?;
+        let fde = self.parse_fde(address, table.word)?;
+        (fde.start..fde.end).contains(&pc).then_some(fde)
+    }
+
+    /// The contents of the CIE or FDE at `address` after its length, and
+    /// their address
+    fn read_entry(&mut self, address: u64, word: usize) -> Option<(Vec<u8>, u64)> {
+        let mut length = self.read_word(address, 4)?;
+        let mut start = address + 4;
+        if length == 0xffff_ffff {
+            length = self.read_word(start, 8)?;
+            start += 8;
+        }
+        if length == 0 || length > MAX_ENTRY_LENGTH {
+            return None;
+        }
+        let bytes = self.read(start, length as usize)?;
+        Some((bytes, mask(start, word)))
+    }
+
+    fn parse_fde(&mut self, address: u64, word: usize) -> Option<Fde> {
+        let (bytes, start) = self.read_entry(address, word)?;
+        let mut cursor = Cursor::new(&bytes, start, word);
+        let cie_pointer = cursor.unsigned(4)?;
+        if cie_pointer == 0 {
+            return None;
+        }
+        let cie = self.parse_cie(start.wrapping_sub(cie_pointer), word)?;
+        let begin = cursor.encoded(cie.fde_encoding, 0)?;
+        let range = cursor.encoded(cie.fde_encoding & 0x0f, 0)?;
+        if cie.augmented {
+            cursor.block()?;
+        }
+        Some(Fde {
+            start: begin,
+            end: begin.wrapping_add(range),
+            instructions: bytes[cursor.at..].to_vec(),
+            address: start + cursor.at as u64,
+            cie,
+        })
+    }
+
+    fn parse_cie(&mut self, address: u64, word: usize) -> Option<Cie> {
+        let (bytes, start) = self.read_entry(address, word)?;
+        let mut cursor = Cursor::new(&bytes, start, word);
+        if cursor.unsigned(4)? != 0 {
+            return None;
+        }
+        let version = cursor.u8()?;
+        let augmentation = cursor.take(bytes[cursor.at..].iter().position(|&b| b == 0)?)?;
+        cursor.u8()?;
+        if version == 4 {
+            cursor.take(2)?;
+        }
+        let mut cie = Cie {
+            code_align: cursor.uleb()?,
+            data_align: cursor.sleb()?,
+            ra: if version == 1 { u64::from(cursor.u8()?) } else { cursor.uleb()? },
+            word,
+            augmented: augmentation.first() == Some(&b'z'),
+            ..Cie::default()
+        };
+        if cie.augmented {
+            let data = cursor.block()?;
+            let mut data = Cursor::new(data, start + (cursor.at - data.len()) as u64, word);
+            for &kind in &augmentation[1..] {
+                match kind {
+                    b'R' => cie.fde_encoding = data.u8()?,
+                    b'L' => {
+                        data.u8()?;
+                    }
+                    b'P' => {
+                        let encoding = data.u8()?;
+                        // Only its size matters here, so an indirect
+                        // pointer is read as a direct one
+                        data.encoded(encoding & 0x7f, 0)?;
+                    }
+                    b'S' | b'B' | b'G' => {}
+                    _ => break,
+                }
+            }
+        } else if !augmentation.is_empty() {
+            return None;
+        }
+        cie.instructions = bytes[cursor.at..].to_vec();
+        cie.address = start + cursor.at as u64;
+        Some(cie)
+    }
+}
+
+/// The row of `fde`'s CFI table for `pc`
+fn fde_row(fde: &Fde, pc: u64) -> Option<Row> {
+    let cie = &fde.cie;
+    let mut row = Row::default();
+    let mut loc = fde.start;
+    execute(&cie.instructions, cie.address, cie, u64::MAX, &mut loc, &mut row, &Row::default())?;
+    let initial = row.clone();
+    loc = fde.start;
+    execute(&fde.instructions, fde.address, cie, pc, &mut loc, &mut row, &initial)?;
+    Some(row)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps;
+    use crate::reader::ProcfsReader;
+
+    fn arm64_thread(regs: &[(usize, u64)]) -> ThreadState {
+        let mut regset = [0u64; 34];
+        for &(index, value) in regs {
+            regset[index] = value;
+        }
+        let bytes: Vec<u8> = regset.iter().flat_map(|value| value.to_le_bytes()).collect();
+        ThreadState::decode(1, "main".into(), &bytes).unwrap()
+    }
+
+    #[test]
+    fn test_follows_frame_records() {
+        let mut stack = vec![0u64; 8];
+        let base = stack.as_ptr() as u64;
+        let at = |index: usize| base + 8 * index as u64;
+        let (first, second) = (at(2), at(5));
+        stack[2] = second;
+        stack[3] = 0x1234;
+        stack[6] = 0x5678;
+        // x29, sp and pc
+        let thread = arm64_thread(&[(29, first), (31, at(0)), (32, 0x10)]);
+
+        let mut reader = ProcfsReader::open(std::process::id() as i32).unwrap();
+        let backtrace = Unwinder::new(&mut reader, &[]).backtrace(&thread);
+        let frames: Vec<_> = backtrace.frames.iter().map(|f| (f.pc, f.method)).collect();
+        assert_eq!(
+            frames,
+            [
+                (0x10, Method::Context),
+                (0x1234, Method::FramePointer),
+                (0x5678, Method::FramePointer)
+            ]
+        );
+        assert_eq!(backtrace.frames[1].sp, at(4));
+        assert_eq!(backtrace.stopped, None);
+        drop(stack);
+    }
+
+    #[test]
+    fn test_finds_own_cfi() {
+        let pid = std::process::id() as i32;
+        let modules = pointers::modules(&maps::read_maps(pid).unwrap());
+        let mut reader = ProcfsReader::open(pid).unwrap();
+        let mut unwinder = Unwinder::new(&mut reader, &modules);
+
+        let pc = test_finds_own_cfi as fn() as usize as u64;
+        let fde = unwinder.find_fde(pc).unwrap();
+        assert!(fde.start <= pc && pc < fde.end);
+        // At entry the CFA is the stack pointer before the call
+        let row = fde_row(&fde, fde.start).unwrap();
+        #[cfg(target_arch = "x86_64")]
+        {
+            assert_eq!(row.cfa, Some((7, 8)));
+            assert_eq!(row.rules.get(&16), Some(&Rule::Offset(-8)));
+        }
+        #[cfg(target_arch = "aarch64")]
+        assert_eq!(row.cfa, Some((31, 0)));
+        assert!(row.cfa.is_some());
+    }
+}
@@ -0,0 +1,330 @@
+//! URLs in memory, and the hosts they point at
+//!
+//! Strings are extracted as usual and URLs matched inside them by the
+//! generic syntax of RFC 3986: a scheme, `://`, an authority of optional
+//! user info, a host and an optional port, then a path, query and
+//! fragment. The host must be a DNS name with a dot in it, `localhost`, an
+//! IPv4 address or a bracketed IPv6 one; only `file` URLs may leave it
+//! empty. Punctuation ending a sentence or closing a bracket the URL didn't
+//! open isn't part of it.
+//!
+//! Each distinct URL is reported once, with how often it was seen, and the
+//! hosts are summed up over their URLs, so the endpoints an app talks to
+//! can be read off at a glance.
+
+use crate::maps::MemoryRegion;
+use crate::reader::MemoryReader;
+use crate::scan::{Location, RegionVisitor, ScanSink};
+use crate::strings::{FoundString, StringEncoding, StringExtractor};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::sync::{Arc, OnceLock};
+
+/// Shortest string searched for URLs, `a://b.c` and then some
+const MIN_STRING_LENGTH: usize = 8;
+
+/// Longest URL reported; longer ones are cut here
+pub const MAX_URL_LENGTH: usize = 8192;
+
+/// Scheme, user info, host, port and the rest
+fn url_regex() -> &'static Regex {
+    static URL: OnceLock<Regex> = OnceLock::new();
+    URL.get_or_init(|| {
+        Regex::new(concat!(
+            r"(?i-u)([a-z][a-z0-9+.-]{0,31})://",
+            r"(?:[a-z0-9._~!$&'()*+,;=:%-]*@)?",
+            r"(\[[0-9a-f:.]+\]|[a-z0-9._~%-]*)",
+            r"(?::([0-9]{1,5}))?",
+            r"(?:[/?#][a-z0-9._~!$&'()*+,;=:@%/?#\[\]-]*)?",
+        ))
+        .expect("URL pattern is valid")
+    })
+}
+
+/// The `urls` object of the scan options
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct UrlOptions {
+    /// The only schemes reported, as in `["https", "wss"]`; any if empty
+    pub schemes: Vec<String>,
+}
+
+/// A distinct URL
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FoundUrl {
+    /// Where it was first seen
+    #[serde(flatten)]
+    pub location: Location,
+    pub encoding: StringEncoding,
+    pub url: String,
+    /// Lowercased, as are hosts
+    pub scheme: String,
+    pub host: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    /// How many times it was seen
+    pub count: usize,
+}
+
+/// A host and the URLs pointing at it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UrlHost {
+    pub host: String,
+    /// Distinct URLs with this host
+    pub urls: usize,
+    /// Times those URLs were seen
+    pub count: usize,
+    pub schemes: Vec<String>,
+    pub ports: Vec<u16>,
+}
+
+/// Whether `host` is a name or address a URL can point at
+fn valid_host(host: &str) -> bool {
+    if let Some(inner) = host.strip_prefix('[').and_then(|h| h.strip_suffix(']')) {
+        return inner.parse::<Ipv6Addr>().is_ok();
+    }
+    if host.bytes().all(|b| b.is_ascii_digit() || b == b'.') {
+        return host.parse::<Ipv4Addr>().is_ok();
+    }
+    if host.eq_ignore_ascii_case("localhost") {
+        return true;
+    }
+    let labels: Vec<&str> = host.split('.').collect();
+    let top = labels[labels.len() - 1];
+    host.len() <= 253
+        && labels.len() > 1
+        && labels.iter().all(|label| {
+            (1..=63).contains(&label.len())
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+        })
+        && top.bytes().any(|b| b.is_ascii_alphabetic())
+        && !top.contains('_')
+}
+
+/// How much of a matched URL to keep: trailing punctuation, and closing
+/// brackets with no opening one in the URL, are dropped
+fn trimmed(url: &str) -> &str {
+    let mut url = url;
+    loop {
+        let Some(last) = url.chars().last() else {
+            return url;
+        };
+        let unbalanced = |open: char| url.matches(open).count() < url.matches(last).count();
+        let drop = match last {
+            '.' | ',' | ';' | ':' | '!' | '?' | '\'' => true,
+            ')' => unbalanced('('),
+            ']' => unbalanced('['),
+            _ => false,
+        };
+        if !drop {
+            return url;
+        }
+        url = &url[..url.len() - 1];
+    }
+}
+
+/// A URL found in a string
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParsedUrl<'a> {
+    /// Byte offset in the string
+    pub offset: usize,
+    pub url: &'a str,
+    pub scheme: String,
+    pub host: String,
+    pub port: Option<u16>,
+}
+
+/// The URLs in `text`, schemes and hosts lowercased
+pub fn find_urls(text: &str) -> Vec<ParsedUrl<'_>> {
+    let mut found = Vec::new();
+    for whole in url_regex().find_iter(text) {
+        // Matched again once trimmed, which may reach back into the host
+        let url = trimmed(whole.as_str());
+        let Some(captures) = url_regex().captures(url) else {
+            continue;
+        };
+        let scheme = captures[1].to_ascii_lowercase();
+        let host = captures[2].to_ascii_lowercase();
+        let port = match captures.get(3) {
+            Some(port) => match port.as_str().parse::<u16>() {
+                Ok(port) => Some(port),
+                Err(_) => continue,
+            },
+            None => None,
+        };
+        let valid = if host.is_empty() { scheme == "file" } else { valid_host(&host) };
+        if valid {
+            found.push(ParsedUrl {
+                offset: whole.start(),
+                url: &url[..url.floor_char_boundary(MAX_URL_LENGTH)],
+                scheme,
+                host,
+                port,
+            });
+        }
+    }
+    found
+}
+
+/// Finds URLs in the strings of each region it is fed
+pub struct UrlVisitor {
+    extractor: StringExtractor,
+    schemes: Vec<String>,
+    strings: Vec<FoundString>,
+}
+
+impl UrlVisitor {
+    pub fn new(options: &UrlOptions, encodings: &[StringEncoding]) -> Self {
+        UrlVisitor {
+            extractor: StringExtractor::new(encodings, MIN_STRING_LENGTH),
+            schemes: options.schemes.iter().map(|s| s.to_ascii_lowercase()).collect(),
+            strings: Vec::new(),
+        }
+    }
+}
+
+impl RegionVisitor for UrlVisitor {
+    type Output = FoundUrl;
+
+    fn visit_chunk(&mut self, _region: &MemoryRegion, address: u64, chunk: &[u8]) {
+        self.extractor.feed(address, chunk, &mut self.strings);
+    }
+
+    fn end_region(
+        &mut self,
+        region: &MemoryRegion,
+        _reader: &mut dyn MemoryReader,
+        sink: &mut dyn ScanSink<FoundUrl>,
+    ) {
+        self.extractor.finish(&mut self.strings);
+
+        let name: Arc<str> = region.name().into();
+        for string in self.strings.drain(..) {
+            for found in find_urls(&string.value) {
+                if !self.schemes.is_empty() && !self.schemes.contains(&found.scheme) {
+                    continue;
+                }
+                let skipped = string.encoding.byte_length(&string.value[..found.offset]);
+                sink.result(FoundUrl {
+                    location: Location::new(&name, region.start, string.address + skipped as u64),
+                    encoding: string.encoding,
+                    url: found.url.to_string(),
+                    scheme: found.scheme,
+                    host: found.host,
+                    port: found.port,
+                    count: 1,
+                });
+            }
+        }
+    }
+}
+
+/// Collapse URLs seen more than once, from the same process, into the
+/// first occurrence, and sum up their hosts, those seen most first
+pub fn aggregate(urls: Vec<FoundUrl>) -> (Vec<FoundUrl>, Vec<UrlHost>) {
+    let mut unique: Vec<FoundUrl> = Vec::new();
+    let mut seen: HashMap<(Option<i32>, String), usize> = HashMap::new();
+    for url in urls {
+        let key = (url.location.pid, url.url.clone());
+        match seen.get(&key) {
+            Some(&index) => unique[index].count += url.count,
+            None => {
+                seen.insert(key, unique.len());
+                unique.push(url);
+            }
+        }
+    }
+
+    let mut hosts: Vec<UrlHost> = Vec::new();
+    let mut by_host: HashMap<&str, usize> = HashMap::new();
+    for url in &unique {
+        let index = *by_host.entry(&url.host).or_insert_with(|| {
+            hosts.push(UrlHost {
+                host: url.host.clone(),
+                urls: 0,
+                count: 0,
+                schemes: Vec::new(),
+                ports: Vec::new(),
+            });
+            hosts.len() - 1
+        });
+        let host = &mut hosts[index];
+        host.urls += 1;
+        host.count += url.count;
+        if !host.schemes.contains(&url.scheme) {
+            host.schemes.push(url.scheme.clone());
+        }
+        if let Some(port) = url.port.filter(|port| !host.ports.contains(port)) {
+            host.ports.push(port);
+        }
+    }
+    for host in &mut hosts {
+        host.schemes.sort();
+        host.ports.sort_unstable();
+    }
+    hosts.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.host.cmp(&b.host)));
+    (unique, hosts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_urls() {
+        let urls = |text| -> Vec<String> {
+            find_urls(text).into_iter().map(|found| found.url.to_string()).collect()
+        };
+        assert_eq!(
+            urls("see https://api.example.com/v1/items?id=3#top, or (http://10.0.0.2:8080/x)."),
+            ["https://api.example.com/v1/items?id=3#top", "http://10.0.0.2:8080/x"]
+        );
+        assert_eq!(urls("wss://user:pw@[::1]:9000/socket"), ["wss://user:pw@[::1]:9000/socket"]);
+        assert_eq!(urls("file:///data/data/app/db"), ["file:///data/data/app/db"]);
+        assert_eq!(urls("content://com.example.provider/items/7"), [
+            "content://com.example.provider/items/7"
+        ]);
+        // No host, a host with no dot, a bad address, a port too large
+        assert!(urls("https:///x http://intranet/ http://300.1.1.1/ http://a.com:70000/")
+            .is_empty());
+
+        let found = find_urls("url=HTTPS://Example.COM:443/").remove(0);
+        assert_eq!((found.offset, found.scheme.as_str()), (4, "https"));
+        assert_eq!((found.host.as_str(), found.port), ("example.com", Some(443)));
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let url = |url: &str, address| {
+            let found = find_urls(url).remove(0);
+            FoundUrl {
+                location: Location::new(&"[heap]".into(), 0, address),
+                encoding: StringEncoding::Ascii,
+                url: found.url.to_string(),
+                scheme: found.scheme,
+                host: found.host,
+                port: found.port,
+                count: 1,
+            }
+        };
+        let (urls, hosts) = aggregate(vec![
+            url("https://a.example.com/login", 0x10),
+            url("https://cdn.example.net/x.png", 0x20),
+            url("https://a.example.com/login", 0x30),
+            url("wss://a.example.com:8443/live", 0x40),
+        ]);
+        assert_eq!(urls.len(), 3);
+        assert_eq!((urls[0].location.address, urls[0].count), (0x10, 2));
+        assert_eq!(hosts.len(), 2);
+        assert_eq!(hosts[0].host, "a.example.com");
+        assert_eq!((hosts[0].urls, hosts[0].count), (2, 3));
+        assert_eq!(hosts[0].schemes, ["https", "wss"]);
+        assert_eq!(hosts[0].ports, [8443]);
+    }
+}
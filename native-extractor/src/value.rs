@@ -0,0 +1,832 @@
+//! Numeric values, found by their bytes as a memory editor finds them
+//!
+//! A value is searched for as the bytes its type lays it out in: an
+//! integer of 1, 2, 4 or 8 bytes in two's complement, or an IEEE 754 float
+//! or double, in either byte order. Only addresses that are a multiple of
+//! the alignment are tried, the type's width by default as a compiler
+//! lays values out; an alignment of 1 also finds values in packed
+//! structures, including those across the end of a chunk. A float only
+//! matches its exact bits, so `0.1` computed another way, or `-0.0` for
+//! `0.0`, isn't found.
+//!
+//! The addresses found are kept as [`Candidates`], with the value each held,
+//! so a later call can narrow them by what they hold now (`equals` again,
+//! `changed`, `decreased`...) reading only those addresses rather than
+//! searching all of memory again. When the value isn't known at first, a
+//! [`Snapshot`] saves the memory instead, and the first refinement compares
+//! every aligned value with what it held then.
+
+use crate::maps::MemoryRegion;
+use crate::reader::{self, MemoryReader};
+use crate::scan::{Location, RegionVisitor, ScanSink};
+use serde::{Deserialize, Serialize};
+use serde_json::Number;
+use std::fmt;
+
+/// Bytes of memory read at once when refining candidates close together
+const REFINE_WINDOW: u64 = 64 << 10;
+
+/// How a value is laid out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ValueType {
+    Int8,
+    Int16,
+    #[default]
+    Int32,
+    Int64,
+    /// IEEE 754 single precision
+    Float,
+    /// IEEE 754 double precision
+    Double,
+}
+
+impl ValueType {
+    /// Bytes a value of this type takes up
+    pub fn width(self) -> usize {
+        match self {
+            ValueType::Int8 => 1,
+            ValueType::Int16 => 2,
+            ValueType::Int32 | ValueType::Float => 4,
+            ValueType::Int64 | ValueType::Double => 8,
+        }
+    }
+
+    /// Bits of `number` as this type, in the low bytes
+    fn encode(self, number: &Number) -> Result<u64, String> {
+        match self {
+            ValueType::Float => {
+                let value = number.as_f64().unwrap_or_default() as f32;
+                Ok(u64::from(value.to_bits()))
+            }
+            ValueType::Double => Ok(number.as_f64().unwrap_or_default().to_bits()),
+            _ => {
+                let value = number
+                    .as_i64()
+                    .map(i128::from)
+                    .or_else(|| number.as_u64().map(i128::from))
+                    .ok_or_else(|| format!("{} isn't an integer", number))?;
+                // Either signed or unsigned, as the bytes are the same
+                let bits = 8 * self.width() as u32;
+                if !(-(1i128 << (bits - 1))..1i128 << bits).contains(&value) {
+                    return Err(format!("{} doesn't fit in {}", number, self));
+                }
+                Ok(value as u64 & (u64::MAX >> (64 - bits)))
+            }
+        }
+    }
+
+    /// The value the low bytes of `raw` hold as this type
+    fn decode(self, raw: u64) -> Value {
+        match self {
+            ValueType::Float => Value::Float(f64::from(f32::from_bits(raw as u32))),
+            ValueType::Double => Value::Float(f64::from_bits(raw)),
+            _ => {
+                let shift = 64 - 8 * self.width() as u32;
+                Value::Int(((raw << shift) as i64) >> shift)
+            }
+        }
+    }
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ValueType::Int8 => "int8",
+            ValueType::Int16 => "int16",
+            ValueType::Int32 => "int32",
+            ValueType::Int64 => "int64",
+            ValueType::Float => "float",
+            ValueType::Double => "double",
+        })
+    }
+}
+
+/// Byte order of a value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Endian {
+    /// As ARM and x86 lay values out
+    #[default]
+    Little,
+    /// As network protocols and Java serialization do
+    Big,
+}
+
+impl Endian {
+    /// `width` bytes holding the low bytes of `raw`
+    fn to_bytes(self, raw: u64, width: usize) -> Vec<u8> {
+        match self {
+            Endian::Little => raw.to_le_bytes()[..width].to_vec(),
+            Endian::Big => raw.to_be_bytes()[8 - width..].to_vec(),
+        }
+    }
+
+    /// `bytes` read as a number, in the low bytes of the result
+    pub fn from_bytes(self, bytes: &[u8]) -> u64 {
+        let fold = |raw: u64, &byte: &u8| raw << 8 | u64::from(byte);
+        match self {
+            Endian::Little => bytes.iter().rev().fold(0, fold),
+            Endian::Big => bytes.iter().fold(0, fold),
+        }
+    }
+}
+
+/// A value read from memory; integers are shown signed
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize)]
+#[serde(untagged)]
+pub enum Value {
+    Int(i64),
+    Float(f64),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Int(value) => write!(f, "{}", value),
+            Value::Float(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// What a candidate must hold now, against what it held before, to be kept
+/// by a refinement
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Condition {
+    /// The value of `equals`
+    #[default]
+    Equals,
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+}
+
+/// The `value` object of the scan options
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ValueOptions {
+    #[serde(rename = "type")]
+    pub value_type: ValueType,
+    /// The value searched for
+    pub equals: Option<Number>,
+    pub endian: Endian,
+    /// Addresses tried are a multiple of this; the type's width if unset
+    pub alignment: Option<usize>,
+    /// Most matches listed in the report; all of them are counted
+    pub max_results: usize,
+    /// Which candidates a refinement keeps
+    pub condition: Condition,
+}
+
+impl Default for ValueOptions {
+    fn default() -> Self {
+        ValueOptions {
+            value_type: ValueType::default(),
+            equals: None,
+            endian: Endian::default(),
+            alignment: None,
+            max_results: 1000,
+            condition: Condition::default(),
+        }
+    }
+}
+
+impl ValueOptions {
+    pub fn alignment(&self) -> usize {
+        self.alignment.unwrap_or(self.value_type.width())
+    }
+
+    /// Bits of `equals` as a value of `value_type`
+    fn target(&self, value_type: ValueType) -> Result<u64, String> {
+        let number = self.equals.as_ref().ok_or("value equals must be set")?;
+        value_type.encode(number)
+    }
+
+    /// The bytes of the value searched for
+    pub fn needle(&self) -> Result<Vec<u8>, String> {
+        let raw = self.target(self.value_type)?;
+        Ok(self.endian.to_bytes(raw, self.value_type.width()))
+    }
+}
+
+/// The bytes `number` is laid out in as a value of `value_type`
+pub fn value_bytes(
+    value_type: ValueType,
+    endian: Endian,
+    number: &Number,
+) -> Result<Vec<u8>, String> {
+    Ok(endian.to_bytes(value_type.encode(number)?, value_type.width()))
+}
+
+/// A value found
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FoundValue {
+    #[serde(flatten)]
+    pub location: Location,
+    pub value: Value,
+    /// What it held before a refinement
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous: Option<Value>,
+}
+
+/// The result of a value scan
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValueScan {
+    #[serde(rename = "type")]
+    pub value_type: ValueType,
+    pub endian: Endian,
+    pub alignment: usize,
+    /// Every match, including those past `maxResults`
+    pub count: usize,
+    /// The first `maxResults` matches, by address
+    pub matches: Vec<FoundValue>,
+}
+
+/// A refinement's `condition`, ready to test values with
+#[derive(Debug, Clone, Copy)]
+pub struct Refinement {
+    condition: Condition,
+    value_type: ValueType,
+    /// Bits of `equals`, for [`Condition::Equals`]
+    target: Option<u64>,
+}
+
+impl Refinement {
+    /// Fails if the condition is on `equals` and it isn't a `value_type`
+    pub fn new(options: &ValueOptions, value_type: ValueType) -> Result<Self, String> {
+        let target = match options.condition {
+            Condition::Equals => Some(options.target(value_type)?),
+            _ => None,
+        };
+        Ok(Refinement {
+            condition: options.condition,
+            value_type,
+            target,
+        })
+    }
+
+    /// Whether a value that held the bits `before` and holds `now` is kept
+    fn keeps(&self, before: u64, now: u64) -> bool {
+        let decode = |raw| self.value_type.decode(raw);
+        match self.condition {
+            Condition::Equals => Some(now) == self.target,
+            Condition::Changed => now != before,
+            Condition::Unchanged => now == before,
+            Condition::Increased => decode(now) > decode(before),
+            Condition::Decreased => decode(now) < decode(before),
+        }
+    }
+}
+
+/// The addresses a value scan found, kept to be narrowed down
+#[derive(Debug, Clone)]
+pub struct Candidates {
+    pub value_type: ValueType,
+    pub endian: Endian,
+    pub alignment: usize,
+    /// Sorted
+    pub addresses: Vec<u64>,
+    /// The bits each address held when last read
+    pub values: Vec<u64>,
+}
+
+impl Candidates {
+    /// The addresses, sorted, a scan for the value `options` describe found
+    pub fn found(options: &ValueOptions, addresses: Vec<u64>) -> Result<Self, String> {
+        let raw = options.target(options.value_type)?;
+        Ok(Candidates {
+            value_type: options.value_type,
+            endian: options.endian,
+            alignment: options.alignment(),
+            values: vec![raw; addresses.len()],
+            addresses,
+        })
+    }
+
+    /// The candidates, the first `max_results` of them listed with the
+    /// values they held `previous`ly if given
+    pub fn report(
+        &self,
+        regions: &[MemoryRegion],
+        max_results: usize,
+        previous: Option<&[u64]>,
+    ) -> ValueScan {
+        let matches = self
+            .addresses
+            .iter()
+            .zip(&self.values)
+            .take(max_results)
+            .enumerate()
+            .map(|(index, (&address, &raw))| FoundValue {
+                location: locate(regions, address),
+                value: self.value_type.decode(raw),
+                previous: previous.map(|previous| self.value_type.decode(previous[index])),
+            })
+            .collect();
+        ValueScan {
+            value_type: self.value_type,
+            endian: self.endian,
+            alignment: self.alignment,
+            count: self.addresses.len(),
+            matches,
+        }
+    }
+
+    /// Read each candidate again, keeping those whose value meets the
+    /// `condition` of `options`; the values they held before are returned
+    /// alongside. Candidates that can't be read any more are dropped.
+    pub fn refine(
+        &self,
+        reader: &mut dyn MemoryReader,
+        options: &ValueOptions,
+    ) -> Result<(Candidates, Vec<u64>), String> {
+        let test = Refinement::new(options, self.value_type)?;
+        let width = self.value_type.width();
+        let mut refined = Candidates {
+            addresses: Vec::new(),
+            values: Vec::new(),
+            ..*self
+        };
+        let mut previous = Vec::new();
+        let mut window = vec![0u8; REFINE_WINDOW as usize];
+        let mut single = [0u8; 8];
+        let mut index = 0;
+        while index < self.addresses.len() {
+            // The candidates within a window of the first are read at once
+            let start = self.addresses[index];
+            let count = self.addresses[index..]
+                .partition_point(|&address| address + width as u64 <= start + REFINE_WINDOW);
+            let end = self.addresses[index + count - 1] + width as u64;
+            let length = (end - start) as usize;
+            let filled = reader::read_into(reader, start, &mut window[..length]).unwrap_or(0);
+            for at in index..index + count {
+                let (address, before) = (self.addresses[at], self.values[at]);
+                let offset = (address - start) as usize;
+                let bytes = if offset + width <= filled {
+                    &window[offset..offset + width]
+                } else {
+                    // Past memory that couldn't be read, perhaps in another region
+                    match reader::read_into(reader, address, &mut single[..width]) {
+                        Ok(read) if read == width => &single[..width],
+                        _ => continue,
+                    }
+                };
+                let now = self.endian.from_bytes(bytes);
+                if test.keeps(before, now) {
+                    refined.addresses.push(address);
+                    refined.values.push(now);
+                    previous.push(before);
+                }
+            }
+            index += count;
+        }
+        Ok((refined, previous))
+    }
+}
+
+/// Bytes of memory a snapshot can hold at most
+pub const MAX_SNAPSHOT_LENGTH: u64 = 1 << 30;
+
+/// A contiguous run of memory saved by a snapshot
+#[derive(Debug, Clone)]
+pub struct Segment {
+    pub start: u64,
+    pub bytes: Vec<u8>,
+}
+
+impl Segment {
+    fn end(&self) -> u64 {
+        self.start + self.bytes.len() as u64
+    }
+}
+
+/// Regions of memory saved as they were, for a search whose first value
+/// isn't known: the values at every aligned address are compared with
+/// those there later
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub value_type: ValueType,
+    pub endian: Endian,
+    pub alignment: usize,
+    /// The regions saved, to be read again
+    pub regions: Vec<MemoryRegion>,
+    /// What was read of them, sorted
+    pub segments: Vec<Segment>,
+}
+
+impl Snapshot {
+    pub fn new(options: &ValueOptions, regions: Vec<MemoryRegion>, segments: Vec<Segment>) -> Self {
+        let mut segments = segments;
+        segments.sort_unstable_by_key(|segment| segment.start);
+        Snapshot {
+            value_type: options.value_type,
+            endian: options.endian,
+            alignment: options.alignment(),
+            regions,
+            segments,
+        }
+    }
+
+    /// Bytes saved
+    pub fn length(&self) -> u64 {
+        self.segments.iter().map(|segment| segment.bytes.len() as u64).sum()
+    }
+
+    pub fn info(&self) -> SnapshotInfo {
+        SnapshotInfo {
+            value_type: self.value_type,
+            endian: self.endian,
+            alignment: self.alignment,
+            length: self.length(),
+        }
+    }
+
+    /// The candidates of the values a walk with [`DiffVisitor`] kept, and
+    /// the values each held in the snapshot
+    pub fn candidates(&self, mut kept: Vec<(u64, u64, u64)>) -> (Candidates, Vec<u64>) {
+        kept.sort_unstable_by_key(|&(address, _, _)| address);
+        let candidates = Candidates {
+            value_type: self.value_type,
+            endian: self.endian,
+            alignment: self.alignment,
+            addresses: kept.iter().map(|&(address, _, _)| address).collect(),
+            values: kept.iter().map(|&(_, now, _)| now).collect(),
+        };
+        (candidates, kept.iter().map(|&(_, _, before)| before).collect())
+    }
+}
+
+/// What a snapshot saved
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotInfo {
+    #[serde(rename = "type")]
+    pub value_type: ValueType,
+    pub endian: Endian,
+    pub alignment: usize,
+    /// Bytes saved
+    pub length: u64,
+}
+
+/// Where a session's value search stands
+#[derive(Debug, Clone)]
+pub enum ValueSearch {
+    /// A value scan found these, or a refinement left them
+    Candidates(Candidates),
+    /// Memory was saved, to be compared when the search is first refined
+    Snapshot(Snapshot),
+}
+
+/// Saves the memory of each region it is fed as [`Segment`]s
+#[derive(Default)]
+pub struct SnapshotVisitor {
+    segments: Vec<Segment>,
+}
+
+impl RegionVisitor for SnapshotVisitor {
+    type Output = Segment;
+
+    fn visit_chunk(&mut self, _region: &MemoryRegion, address: u64, chunk: &[u8]) {
+        match self.segments.last_mut() {
+            Some(segment) if segment.end() == address => segment.bytes.extend_from_slice(chunk),
+            _ => self.segments.push(Segment {
+                start: address,
+                bytes: chunk.to_vec(),
+            }),
+        }
+    }
+
+    fn end_region(
+        &mut self,
+        _region: &MemoryRegion,
+        _reader: &mut dyn MemoryReader,
+        sink: &mut dyn ScanSink<Segment>,
+    ) {
+        for segment in self.segments.drain(..) {
+            sink.result(segment);
+        }
+    }
+}
+
+/// Compares the memory of each region it is fed with a snapshot of it,
+/// giving the address, the bits now and the bits before of each value kept
+pub struct DiffVisitor<'a> {
+    snapshot: &'a Snapshot,
+    test: Refinement,
+    /// The last bytes of the chunk before, for values across its end
+    tail: Vec<u8>,
+    /// Where the next chunk starts if it carries on from the last one
+    next: Option<u64>,
+    found: Vec<(u64, u64, u64)>,
+}
+
+impl<'a> DiffVisitor<'a> {
+    pub fn new(snapshot: &'a Snapshot, test: Refinement) -> Self {
+        DiffVisitor {
+            snapshot,
+            test,
+            tail: Vec::new(),
+            next: None,
+            found: Vec::new(),
+        }
+    }
+
+    /// Compare the values wholly inside both `data`, starting at
+    /// `address`, and the snapshot
+    fn compare(&mut self, address: u64, data: &[u8]) {
+        let (width, alignment) = (self.snapshot.value_type.width(), self.snapshot.alignment);
+        let end = address + data.len() as u64;
+        let segments = &self.snapshot.segments;
+        let first = segments.partition_point(|segment| segment.end() <= address);
+        for segment in segments[first..].iter().take_while(|segment| segment.start < end) {
+            let (low, high) = (segment.start.max(address), segment.end().min(end));
+            let mut at = low.next_multiple_of(alignment as u64);
+            while at + width as u64 <= high {
+                let (now, before) = ((at - address) as usize, (at - segment.start) as usize);
+                let endian = self.snapshot.endian;
+                let now = endian.from_bytes(&data[now..now + width]);
+                let before = endian.from_bytes(&segment.bytes[before..before + width]);
+                if self.test.keeps(before, now) {
+                    self.found.push((at, now, before));
+                }
+                at += alignment as u64;
+            }
+        }
+    }
+}
+
+impl RegionVisitor for DiffVisitor<'_> {
+    type Output = (u64, u64, u64);
+
+    fn visit_chunk(&mut self, _region: &MemoryRegion, address: u64, chunk: &[u8]) {
+        let keep = self.snapshot.value_type.width() - 1;
+        if self.next == Some(address) && !self.tail.is_empty() {
+            // Too few bytes of the chunk for a value wholly inside it
+            let mut joined = std::mem::take(&mut self.tail);
+            let start = address - joined.len() as u64;
+            joined.extend_from_slice(&chunk[..keep.min(chunk.len())]);
+            self.compare(start, &joined);
+        }
+        self.compare(address, chunk);
+        self.tail.clear();
+        self.tail.extend_from_slice(&chunk[chunk.len() - keep.min(chunk.len())..]);
+        self.next = Some(address + chunk.len() as u64);
+    }
+
+    fn end_region(
+        &mut self,
+        _region: &MemoryRegion,
+        _reader: &mut dyn MemoryReader,
+        sink: &mut dyn ScanSink<(u64, u64, u64)>,
+    ) {
+        self.tail.clear();
+        self.next = None;
+        for found in self.found.drain(..) {
+            sink.result(found);
+        }
+    }
+}
+
+/// Where `address` is among `regions`, sorted by start as the maps list
+/// them
+pub fn locate(regions: &[MemoryRegion], address: u64) -> Location {
+    let index = regions.partition_point(|region| region.start <= address);
+    match index.checked_sub(1).map(|index| &regions[index]) {
+        Some(region) if address < region.end => {
+            Location::new(&region.name().into(), region.start, address)
+        }
+        _ => Location::new(&"".into(), address, address),
+    }
+}
+
+/// Finds the addresses in each region it is fed that hold a value
+pub struct ValueVisitor<'a> {
+    needle: &'a [u8],
+    alignment: u64,
+    /// The last bytes of the chunk before, for values across its end
+    tail: Vec<u8>,
+    /// Where the next chunk starts if it carries on from the last one
+    next: Option<u64>,
+    found: Vec<u64>,
+}
+
+impl<'a> ValueVisitor<'a> {
+    pub fn new(needle: &'a [u8], alignment: usize) -> Self {
+        ValueVisitor {
+            needle,
+            alignment: alignment as u64,
+            tail: Vec::new(),
+            next: None,
+            found: Vec::new(),
+        }
+    }
+
+    /// Record the aligned addresses in `data`, starting at `address`,
+    /// that hold the value
+    fn search(&mut self, address: u64, data: &[u8]) {
+        let width = self.needle.len();
+        let mut offset = (address.next_multiple_of(self.alignment) - address) as usize;
+        while offset + width <= data.len() {
+            if data[offset..offset + width] == *self.needle {
+                self.found.push(address + offset as u64);
+            }
+            offset += self.alignment as usize;
+        }
+    }
+}
+
+impl RegionVisitor for ValueVisitor<'_> {
+    type Output = u64;
+
+    fn visit_chunk(&mut self, _region: &MemoryRegion, address: u64, chunk: &[u8]) {
+        let keep = self.needle.len() - 1;
+        if self.next == Some(address) && !self.tail.is_empty() {
+            // Too few bytes of the chunk for a value wholly inside it
+            let mut joined = std::mem::take(&mut self.tail);
+            let start = address - joined.len() as u64;
+            joined.extend_from_slice(&chunk[..keep.min(chunk.len())]);
+            self.search(start, &joined);
+        }
+        self.search(address, chunk);
+        self.tail.clear();
+        self.tail.extend_from_slice(&chunk[chunk.len() - keep.min(chunk.len())..]);
+        self.next = Some(address + chunk.len() as u64);
+    }
+
+    fn end_region(
+        &mut self,
+        _region: &MemoryRegion,
+        _reader: &mut dyn MemoryReader,
+        sink: &mut dyn ScanSink<u64>,
+    ) {
+        self.tail.clear();
+        self.next = None;
+        for address in std::mem::take(&mut self.found) {
+            sink.result(address);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps::parse_maps_line;
+    use crate::reader::ProcfsReader;
+    use crate::scan::{self, Scan, ScanControl};
+
+    fn options(value_type: ValueType, equals: Number, endian: Endian) -> ValueOptions {
+        ValueOptions {
+            value_type,
+            equals: Some(equals),
+            endian,
+            ..ValueOptions::default()
+        }
+    }
+
+    fn decode(options: &ValueOptions, bytes: &[u8]) -> Value {
+        options.value_type.decode(options.endian.from_bytes(bytes))
+    }
+
+    #[test]
+    fn test_value_bytes() {
+        let int = options(ValueType::Int32, Number::from(-2), Endian::Little);
+        assert_eq!(int.needle().unwrap(), [0xfe, 0xff, 0xff, 0xff]);
+        assert_eq!(decode(&int, &[0xfe, 0xff, 0xff, 0xff]), Value::Int(-2));
+        let big = options(ValueType::Int16, Number::from(0x1234), Endian::Big);
+        assert_eq!(big.needle().unwrap(), [0x12, 0x34]);
+        assert_eq!(decode(&big, &[0x12, 0x34]), Value::Int(0x1234));
+        // Unsigned values past the signed range have the same bytes
+        let byte = options(ValueType::Int8, Number::from(255), Endian::Little);
+        assert_eq!(byte.needle().unwrap(), [0xff]);
+        assert_eq!(decode(&byte, &[0xff]), Value::Int(-1));
+        let wide = options(ValueType::Int64, Number::from(u64::MAX), Endian::Little);
+        assert_eq!(wide.needle().unwrap(), [0xff; 8]);
+        assert!(options(ValueType::Int8, Number::from(256), Endian::Little).needle().is_err());
+        assert!(options(ValueType::Int8, Number::from(-129), Endian::Little).needle().is_err());
+        let fraction = Number::from_f64(1.5).unwrap();
+        assert!(options(ValueType::Int32, fraction.clone(), Endian::Little).needle().is_err());
+
+        let float = options(ValueType::Float, fraction.clone(), Endian::Little);
+        assert_eq!(float.needle().unwrap(), 1.5f32.to_le_bytes());
+        assert_eq!(decode(&float, &1.5f32.to_le_bytes()), Value::Float(1.5));
+        let double = options(ValueType::Double, fraction, Endian::Big);
+        assert_eq!(double.needle().unwrap(), 1.5f64.to_be_bytes());
+        assert!(ValueOptions::default().needle().is_err());
+    }
+
+    #[test]
+    fn test_finds_values() {
+        let mut data = vec![0u8; 0x2000];
+        let bytes = 0x1234_5678u32.to_le_bytes();
+        data[0x100..0x104].copy_from_slice(&bytes);
+        // Unaligned, and across the end of a chunk
+        data[0x202..0x206].copy_from_slice(&bytes);
+        data[0xffe..0x1002].copy_from_slice(&bytes);
+        let start = data.as_ptr() as u64;
+        let end = start + data.len() as u64;
+        let line = format!("{:x}-{:x} rw-p 00000000 00:00 0", start, end);
+        let regions = [parse_maps_line(&line).unwrap()];
+        let options = options(ValueType::Int32, Number::from(0x1234_5678), Endian::Little);
+        let needle = options.needle().unwrap();
+        let mut reader = ProcfsReader::open(std::process::id() as i32).unwrap();
+
+        let mut found = |alignment| {
+            let mut visitor = ValueVisitor::new(&needle, alignment);
+            let mut scan = Scan::default();
+            let mut control = ScanControl::new().chunk_size(Some(0x1000));
+            scan::walk_regions(&mut reader, &regions, &mut visitor, &mut scan, &mut control);
+            scan.results.iter().map(|address| address - start).collect::<Vec<_>>()
+        };
+        assert_eq!(found(4), [0x100]);
+        assert_eq!(found(2), [0x100, 0x202, 0xffe]);
+        assert_eq!(found(1), [0x100, 0x202, 0xffe]);
+
+        let candidates = Candidates::found(&options, vec![start + 0x100]).unwrap();
+        let report = candidates.report(&regions, 1000, None);
+        assert_eq!(report.count, 1);
+        assert_eq!(report.matches[0].location.offset, 0x100);
+        assert_eq!(report.matches[0].value, Value::Int(0x1234_5678));
+        assert_eq!(locate(&regions, end).offset, 0);
+    }
+
+    #[test]
+    fn test_refine_candidates() {
+        let mut data = vec![0i32; 0x8000];
+        // Far enough apart to be read in separate windows
+        let indices = [0x10, 0x20, 0x30, 0x7000];
+        for &index in &indices {
+            data[index] = 100;
+        }
+        let base = data.as_ptr() as u64;
+        let addresses: Vec<u64> = indices.iter().map(|&i| base + 4 * i as u64).collect();
+        let mut options = options(ValueType::Int32, Number::from(100), Endian::Little);
+        let candidates = Candidates::found(&options, addresses.clone()).unwrap();
+        let mut reader = ProcfsReader::open(std::process::id() as i32).unwrap();
+
+        data[0x10] = 90;
+        data[0x20] = 110;
+        data[0x7000] = 90;
+        std::hint::black_box(&data);
+        let refine = |reader: &mut ProcfsReader, options: &ValueOptions| {
+            let (refined, previous) = candidates.refine(reader, options).unwrap();
+            assert!(previous.iter().all(|&before| before == 100));
+            refined.addresses
+        };
+        options.equals = Some(Number::from(90));
+        assert_eq!(refine(&mut reader, &options), [addresses[0], addresses[3]]);
+        options.condition = Condition::Changed;
+        assert_eq!(refine(&mut reader, &options), [addresses[0], addresses[1], addresses[3]]);
+        options.condition = Condition::Unchanged;
+        assert_eq!(refine(&mut reader, &options), [addresses[2]]);
+        options.condition = Condition::Increased;
+        assert_eq!(refine(&mut reader, &options), [addresses[1]]);
+        options.condition = Condition::Decreased;
+        let (refined, previous) = candidates.refine(&mut reader, &options).unwrap();
+        let report = refined.report(&[], 1000, Some(&previous));
+        assert_eq!(report.matches[1].value, Value::Int(90));
+        assert_eq!(report.matches[1].previous, Some(Value::Int(100)));
+    }
+
+    #[test]
+    fn test_compares_snapshot() {
+        let mut data = vec![7u8; 0x2000];
+        let start = data.as_ptr() as u64;
+        let end = start + data.len() as u64;
+        let line = format!("{:x}-{:x} rw-p 00000000 00:00 0", start, end);
+        let regions = vec![parse_maps_line(&line).unwrap()];
+        let mut options = ValueOptions {
+            value_type: ValueType::Int16,
+            alignment: Some(1),
+            condition: Condition::Increased,
+            ..ValueOptions::default()
+        };
+        let mut reader = ProcfsReader::open(std::process::id() as i32).unwrap();
+
+        let mut visitor = SnapshotVisitor::default();
+        let mut scan = Scan::default();
+        let mut control = ScanControl::new().chunk_size(Some(0x1000));
+        scan::walk_regions(&mut reader, &regions, &mut visitor, &mut scan, &mut control);
+        let snapshot = Snapshot::new(&options, regions, scan.results);
+        assert_eq!(snapshot.length(), 0x2000);
+
+        // Across the end of a chunk, at an odd address
+        data[0x1000] = 9;
+        std::hint::black_box(&data);
+        let refinement = Refinement::new(&options, snapshot.value_type).unwrap();
+        let mut visitor = DiffVisitor::new(&snapshot, refinement);
+        let mut scan = Scan::default();
+        let mut control = ScanControl::new().chunk_size(Some(0x1000));
+        scan::walk_regions(&mut reader, &snapshot.regions, &mut visitor, &mut scan, &mut control);
+        let (candidates, previous) = snapshot.candidates(scan.results);
+        // 0x0707 to 0x0907 and 0x0707 to 0x0709
+        assert_eq!(candidates.addresses, [start + 0xfff, start + 0x1000]);
+        assert_eq!(candidates.values, [0x0907, 0x0709]);
+        assert_eq!(previous, [0x0707, 0x0707]);
+
+        options.condition = Condition::Equals;
+        assert!(Refinement::new(&options, ValueType::Int16).is_err());
+    }
+}
@@ -0,0 +1,380 @@
+//! Addresses watched for changes in a live process
+//!
+//! Polling from Java misses values that change and change back between two
+//! JNI calls. Each [`Watch`] has a thread of its own that reads its
+//! addresses once per interval, down to a tenth of a millisecond, and hands
+//! every change it sees to a callback with the old and new values and when
+//! it was seen. Changes that revert within one interval still go unseen:
+//! this samples memory, it doesn't trap writes as a debugger's watchpoint
+//! would.
+
+use crate::error::ExtractError;
+use crate::reader::{AutoReader, MemoryReader};
+use crate::scan;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Shortest interval between two polls
+pub const MIN_INTERVAL_US: u64 = 100;
+
+/// An address and how many bytes of it are watched
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchedAddress {
+    pub address: u64,
+    /// 1 to 8 bytes, read as a little-endian integer
+    #[serde(default = "default_width")]
+    pub width: usize,
+}
+
+fn default_width() -> usize {
+    4
+}
+
+/// The `watch` object of the options `watchAddresses` takes
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct WatchOptions {
+    pub addresses: Vec<WatchedAddress>,
+    /// Time from the start of one poll to the next, in microseconds
+    pub interval_us: u64,
+}
+
+impl Default for WatchOptions {
+    fn default() -> Self {
+        WatchOptions {
+            addresses: Vec::new(),
+            interval_us: 1000,
+        }
+    }
+}
+
+impl WatchOptions {
+    /// Parse the `watch` object of the options JSON
+    pub fn from_json(json: &str) -> Result<Self, ExtractError> {
+        let options: WatchOptions = scan::parse_object(json, "watch")?;
+        if options.interval_us < MIN_INTERVAL_US {
+            return Err(ExtractError::InvalidArgument(format!(
+                "Invalid watch options: intervalUs must be at least {}",
+                MIN_INTERVAL_US
+            )));
+        }
+        Ok(options)
+    }
+}
+
+/// A value seen to change between two polls
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Change {
+    pub address: u64,
+    pub old: u64,
+    pub new: u64,
+    /// When the poll that saw it read the value, in nanoseconds since the
+    /// Unix epoch
+    pub time_nanos: i64,
+}
+
+/// Receives each change a watch sees, on the watch's thread; an error
+/// ends the watch
+pub type Notify = Box<dyn FnMut(&Change) -> Result<(), String> + Send>;
+
+/// How a watch has gone so far
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchStatus {
+    /// Rounds of reads made
+    pub polls: u64,
+    /// Changes passed to the callback
+    pub changes: u64,
+    /// Reads that found an address unreadable
+    pub failures: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    /// Whether the watch stopped by itself, because the process exited or
+    /// the callback failed
+    pub ended: bool,
+}
+
+/// A registered watch, as listed
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchInfo {
+    pub handle: i64,
+    pub pid: i32,
+    pub addresses: Vec<WatchedAddress>,
+    pub interval_us: u64,
+    #[serde(flatten)]
+    pub status: WatchStatus,
+}
+
+/// Addresses being polled, until dropped
+pub struct Watch {
+    info: WatchInfo,
+    status: Arc<Mutex<WatchStatus>>,
+    /// Wakes the poller thread to stop it
+    stop: Option<mpsc::Sender<()>>,
+    poller: Option<JoinHandle<()>>,
+}
+
+impl Watch {
+    /// Read each address `options` give, and start the thread reading them
+    /// again every interval and calling `notify` with what changed.
+    ///
+    /// Fails if the first round reads nothing, so a wrong address or a
+    /// missing process is reported rather than polled forever. Addresses
+    /// are always read from the poller thread, which a ptrace reader needs.
+    pub fn start(pid: i32, options: &WatchOptions, mut notify: Notify) -> Result<Self, String> {
+        if options.addresses.is_empty() {
+            return Err("watch addresses must be set".into());
+        }
+        if let Some(bad) = options.addresses.iter().find(|a| !(1..=8).contains(&a.width)) {
+            return Err(format!("watch width must be 1 to 8 bytes, not {}", bad.width));
+        }
+
+        let status = Arc::new(Mutex::new(WatchStatus::default()));
+        let (stop, wake) = mpsc::channel::<()>();
+        let (started, first) = mpsc::sync_channel::<Result<(), String>>(1);
+        let interval = Duration::from_micros(options.interval_us);
+        let poller = {
+            let status = Arc::clone(&status);
+            let addresses = options.addresses.clone();
+            thread::spawn(move || {
+                let mut reader = AutoReader::new(pid);
+                let mut values = vec![None; addresses.len()];
+                let mut baseline = WatchStatus::default();
+                if let Err(e) = poll(&mut reader, &addresses, &mut values, &mut baseline) {
+                    let _ = started.send(Err(e));
+                    return;
+                }
+                if values.iter().all(Option::is_none) {
+                    let _ = started.send(Err(baseline.last_error.unwrap_or_default()));
+                    return;
+                }
+                *status.lock().unwrap_or_else(|e| e.into_inner()) = baseline;
+                let _ = started.send(Ok(()));
+
+                let mut next = Instant::now() + interval;
+                loop {
+                    match wake.recv_timeout(next.saturating_duration_since(Instant::now())) {
+                        Err(RecvTimeoutError::Timeout) => {}
+                        _ => return,
+                    }
+                    let old = values.clone();
+                    let mut round = WatchStatus::default();
+                    let polled = poll(&mut reader, &addresses, &mut values, &mut round);
+                    let time_nanos = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map_or(0, |time| time.as_nanos() as i64);
+                    let mut failed = polled.err();
+                    for (i, (&old, &new)) in old.iter().zip(&values).enumerate() {
+                        let (Some(old), Some(new)) = (old, new) else {
+                            continue;
+                        };
+                        if old == new || failed.is_some() {
+                            continue;
+                        }
+                        let change = Change {
+                            address: addresses[i].address,
+                            old,
+                            new,
+                            time_nanos,
+                        };
+                        match notify(&change) {
+                            Ok(()) => round.changes += 1,
+                            Err(e) => failed = Some(e),
+                        }
+                    }
+
+                    let mut status = status.lock().unwrap_or_else(|e| e.into_inner());
+                    status.polls += round.polls;
+                    status.changes += round.changes;
+                    status.failures += round.failures;
+                    if round.last_error.is_some() {
+                        status.last_error = round.last_error;
+                    }
+                    if let Some(e) = failed {
+                        status.last_error = Some(e);
+                        status.ended = true;
+                        return;
+                    }
+                    drop(status);
+                    // After a stall, carry on from now rather than catching up
+                    next = (next + interval).max(Instant::now());
+                }
+            })
+        };
+        match first.recv() {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => return Err(e),
+            Err(_) => return Err("Watch thread stopped".into()),
+        }
+        Ok(Watch {
+            info: WatchInfo {
+                handle: 0,
+                pid,
+                addresses: options.addresses.clone(),
+                interval_us: options.interval_us,
+                status: WatchStatus::default(),
+            },
+            status,
+            stop: Some(stop),
+            poller: Some(poller),
+        })
+    }
+
+    /// The watch as it stands now
+    pub fn info(&self) -> WatchInfo {
+        WatchInfo {
+            status: self.status.lock().unwrap_or_else(|e| e.into_inner()).clone(),
+            ..self.info.clone()
+        }
+    }
+}
+
+impl Drop for Watch {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(poller) = self.poller.take() {
+            // Released from its own callback, the thread stops by itself
+            // once the callback returns
+            if poller.thread().id() != thread::current().id() {
+                let _ = poller.join();
+            }
+        }
+    }
+}
+
+/// Read every address into `values`, leaving `None` for those unreadable;
+/// an error means the process has exited
+fn poll(
+    reader: &mut dyn MemoryReader,
+    addresses: &[WatchedAddress],
+    values: &mut [Option<u64>],
+    status: &mut WatchStatus,
+) -> Result<(), String> {
+    status.polls += 1;
+    for (watched, value) in addresses.iter().zip(values) {
+        let mut bytes = [0u8; 8];
+        *value = match reader.read_at(watched.address, &mut bytes[..watched.width]) {
+            Ok(read) if read == watched.width => Some(u64::from_le_bytes(bytes)),
+            Ok(_) => {
+                status.failures += 1;
+                status.last_error = Some(format!("Nothing readable at 0x{:x}", watched.address));
+                None
+            }
+            Err(e) if is_gone(&e) => return Err("Process exited".into()),
+            Err(e) => {
+                status.failures += 1;
+                status.last_error = Some(format!("Failed to read 0x{:x}: {}", watched.address, e));
+                None
+            }
+        };
+    }
+    Ok(())
+}
+
+fn is_gone(error: &io::Error) -> bool {
+    matches!(error.raw_os_error(), Some(libc::ESRCH) | Some(libc::ENOENT))
+}
+
+type WatchTable = Mutex<HashMap<i64, Watch>>;
+
+/// Handles are never reused, so a stale handle can't release a newer watch
+static NEXT_HANDLE: AtomicI64 = AtomicI64::new(1);
+
+fn watches() -> &'static WatchTable {
+    static WATCHES: OnceLock<WatchTable> = OnceLock::new();
+    WATCHES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Store a watch, returning its handle. Handles are never 0.
+pub fn register(mut watch: Watch) -> i64 {
+    let handle = NEXT_HANDLE.fetch_add(1, Ordering::Relaxed);
+    watch.info.handle = handle;
+    watches().lock().unwrap_or_else(|e| e.into_inner()).insert(handle, watch);
+    handle
+}
+
+/// Stop a watch. Returns false for an unknown handle.
+pub fn release(handle: i64) -> bool {
+    let watch = watches().lock().unwrap_or_else(|e| e.into_inner()).remove(&handle);
+    // Dropped outside the lock, as it waits for the poller thread
+    watch.is_some()
+}
+
+/// Every registered watch, including those that ended, in the order they
+/// were made
+pub fn list() -> Vec<WatchInfo> {
+    let watches = watches().lock().unwrap_or_else(|e| e.into_inner());
+    let mut list: Vec<WatchInfo> = watches.values().map(Watch::info).collect();
+    list.sort_unstable_by_key(|info| info.handle);
+    list
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reports_changes() {
+        let mut data = vec![0u8; 16];
+        let base = data.as_mut_ptr();
+        let address = base as u64;
+        let options = WatchOptions {
+            addresses: vec![
+                WatchedAddress { address, width: 4 },
+                WatchedAddress { address: address + 8, width: 2 },
+            ],
+            interval_us: MIN_INTERVAL_US,
+        };
+        let (send, changes) = mpsc::channel();
+        let notify: Notify = Box::new(move |change| send.send(*change).map_err(|e| e.to_string()));
+        let handle = register(Watch::start(std::process::id() as i32, &options, notify).unwrap());
+
+        // SAFETY: both values lie within `data`, which outlives the watch
+        unsafe { (base as *mut u32).write_volatile(7) };
+        let change = changes.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!((change.address, change.old, change.new), (address, 0, 7));
+        assert!(change.time_nanos > 0);
+        // Bytes past the width aren't watched
+        // SAFETY: as above
+        unsafe {
+            base.add(10).write_volatile(1);
+            base.add(9).write_volatile(1);
+        }
+        let change = changes.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!((change.address, change.old, change.new), (address + 8, 0, 0x100));
+
+        // Counted once the callback returns
+        let info = || list().into_iter().find(|info| info.handle == handle).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while info().status.changes < 2 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(info().status.changes, 2);
+        assert!(release(handle));
+        assert!(!release(handle));
+
+        let unmapped = WatchOptions {
+            addresses: vec![WatchedAddress { address: 8, width: 4 }],
+            ..options
+        };
+        let notify: Notify = Box::new(|_| Ok(()));
+        assert!(Watch::start(std::process::id() as i32, &unmapped, notify).is_err());
+        drop(data);
+    }
+
+    #[test]
+    fn test_watch_options_from_json() {
+        let options = WatchOptions::from_json(r#"{"watch": {"intervalUs": 500}}"#).unwrap();
+        assert_eq!(options.interval_us, 500);
+        assert_eq!(WatchOptions::from_json("{}").unwrap().interval_us, 1000);
+        assert!(WatchOptions::from_json(r#"{"watch": {"intervalUs": 99}}"#).is_err());
+    }
+}
@@ -0,0 +1,128 @@
+//! Writing into a target's memory
+//!
+//! process_vm_writev is tried first, as the cheapest way in, but it honours
+//! the target's page protections. Whatever it can't write, such as code or
+//! other read-only mappings, is written through /proc/[pid]/mem, which the
+//! kernel lets a caller allowed to ptrace the target write regardless of
+//! protection, as a debugger setting breakpoints does.
+
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::unix::fs::FileExt;
+
+/// Writes into the memory of a live process
+pub struct MemoryWriter {
+    pid: i32,
+    /// /proc/[pid]/mem, opened for the first write process_vm_writev can't
+    /// make
+    mem: Option<File>,
+}
+
+impl MemoryWriter {
+    pub fn new(pid: i32) -> Self {
+        MemoryWriter { pid, mem: None }
+    }
+
+    pub fn pid(&self) -> i32 {
+        self.pid
+    }
+
+    /// Write `bytes` at `address`, returning how many were written.
+    ///
+    /// As with [`crate::reader::MemoryReader::read_at`], a short write means
+    /// the memory after it is unmapped; an error means nothing at `address`
+    /// could be written.
+    pub fn write_at(&mut self, address: u64, bytes: &[u8]) -> io::Result<usize> {
+        let written = vm_writev(self.pid, address, bytes).unwrap_or(0);
+        if written == bytes.len() {
+            return Ok(written);
+        }
+        match self.write_procfs(address + written as u64, &bytes[written..]) {
+            Ok(more) => Ok(written + more),
+            Err(_) if written > 0 => Ok(written),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn write_procfs(&mut self, address: u64, bytes: &[u8]) -> io::Result<usize> {
+        let mem = match &mut self.mem {
+            Some(mem) => mem,
+            None => {
+                let path = format!("/proc/{}/mem", self.pid);
+                self.mem.insert(OpenOptions::new().write(true).open(path)?)
+            }
+        };
+        let mut written = 0;
+        while written < bytes.len() {
+            match mem.write_at(&bytes[written..], address + written as u64) {
+                Ok(0) => break,
+                Ok(n) => written += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(_) if written > 0 => break,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(written)
+    }
+}
+
+/// Write `bytes` at `address` with a single process_vm_writev call
+fn vm_writev(pid: i32, address: u64, bytes: &[u8]) -> io::Result<usize> {
+    if bytes.is_empty() {
+        return Ok(0);
+    }
+    let local = libc::iovec {
+        iov_base: bytes.as_ptr() as *mut libc::c_void,
+        iov_len: bytes.len(),
+    };
+    let remote = libc::iovec {
+        iov_base: address as *mut libc::c_void,
+        iov_len: bytes.len(),
+    };
+    // SAFETY: the local iovec covers `bytes`, which the kernel only reads;
+    // the remote one is checked by the kernel against the target's mappings
+    let written = unsafe { libc::process_vm_writev(pid, &local, 1, &remote, 1, 0) };
+    if written < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(written as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_writes_own_memory() {
+        let data = vec![0u8; 32];
+        let mut writer = MemoryWriter::new(std::process::id() as i32);
+
+        let written = writer.write_at(data.as_ptr() as u64 + 4, b"patched").unwrap();
+        assert_eq!(written, 7);
+        assert_eq!(&std::hint::black_box(&data)[4..11], b"patched");
+        assert!(writer.write_at(8, b"unmapped").is_err());
+    }
+
+    #[test]
+    fn test_writes_read_only_memory() {
+        // SAFETY: a fresh private anonymous mapping, unmapped below
+        let page = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                4096,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        assert_ne!(page, libc::MAP_FAILED);
+        let mut writer = MemoryWriter::new(std::process::id() as i32);
+
+        assert_eq!(writer.write_at(page as u64, b"code").unwrap(), 4);
+        // SAFETY: the page is mapped readable
+        assert_eq!(unsafe { std::slice::from_raw_parts(page as *const u8, 4) }, b"code");
+        unsafe { libc::munmap(page, 4096) };
+    }
+}
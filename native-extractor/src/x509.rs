@@ -0,0 +1,518 @@
+//! Carving of X.509 certificates out of process memory
+//!
+//! Pinned certificates, CA bundles and the chains of live TLS connections
+//! sit in memory as DER, in BoringSSL's buffers and Java byte arrays, or
+//! as PEM text, as read from `cacerts` and assets. DER is searched for by
+//! the start of a certificate, a `SEQUENCE` of two-byte length holding
+//! another, and PEM by its `BEGIN CERTIFICATE` line, at any alignment.
+//!
+//! A candidate counts if it parses as a certificate to its last byte: the
+//! to-be-signed part with its version, serial number, issuer, validity,
+//! subject and key, then the signature algorithm and signature. Each
+//! distinct certificate is written once, as PEM, however many copies of it
+//! there are.
+
+use crate::base64;
+use crate::der::{
+    self, Element, Elements, Encoding, BIT_STRING, BOOLEAN, GENERALIZED_TIME, INTEGER, OCTET_STRING,
+    OID, SEQUENCE, SET, UTC_TIME,
+};
+use crate::dump::DumpedFile;
+use crate::maps::MemoryRegion;
+use crate::reader::MemoryReader;
+use crate::scan::{self, RegionVisitor, ScanControl, ScanSink, StopReason};
+use crate::search::{Hit, PatternScanner};
+use crate::sha256::Sha256;
+use regex::bytes::Regex;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::OnceLock;
+
+const PEM_LABEL: &str = "CERTIFICATE";
+
+/// Longest candidate match, the PEM `BEGIN` line
+const MAX_MATCH_LENGTH: usize = 27;
+
+/// Longest certificate read, in bytes of DER or PEM text
+const MAX_READ_LENGTH: usize = 96 << 10;
+
+const VERSION: u8 = der::context(0);
+const EXTENSIONS: u8 = der::context(3);
+
+/// What [`parse_certificate`] reads out of a certificate
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Certificate {
+    /// `1` to `3`
+    pub version: u8,
+    /// In hex
+    pub serial: String,
+    /// Attributes in the order they are encoded, as in
+    /// `C=US, O=Example, CN=example.com`
+    pub subject: String,
+    pub issuer: String,
+    /// As in `2026-10-14T06:52:51Z`
+    pub not_before: String,
+    pub not_after: String,
+    /// As in `sha256WithRSAEncryption`, or the OID if not a common one
+    pub signature_algorithm: String,
+    /// As in `RSA 2048` or `EC P-256`
+    pub public_key: String,
+    pub self_signed: bool,
+    /// Whether basic constraints make it a CA certificate
+    pub ca: bool,
+    /// SHA-256 of the DER certificate, as lowercase hex
+    pub fingerprint: String,
+    /// SHA-256 of the subject public key info, as the `sha256/...` pins of
+    /// OkHttp and network security configs give it
+    pub pin: String,
+}
+
+/// A certificate found in memory and written to disk as PEM
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CarvedCertificate {
+    #[serde(flatten)]
+    pub file: DumpedFile,
+    pub encoding: Encoding,
+    /// Bytes of DER
+    pub length: usize,
+    #[serde(flatten)]
+    pub certificate: Certificate,
+}
+
+/// The short name of a name attribute
+fn attribute_name(oid: &str) -> Option<&'static str> {
+    Some(match oid {
+        "2.5.4.3" => "CN",
+        "2.5.4.5" => "serialNumber",
+        "2.5.4.6" => "C",
+        "2.5.4.7" => "L",
+        "2.5.4.8" => "ST",
+        "2.5.4.10" => "O",
+        "2.5.4.11" => "OU",
+        "1.2.840.113549.1.9.1" => "emailAddress",
+        "0.9.2342.19200300.100.1.25" => "DC",
+        _ => return None,
+    })
+}
+
+fn signature_algorithm_name(oid: &str) -> Option<&'static str> {
+    Some(match oid {
+        "1.2.840.113549.1.1.4" => "md5WithRSAEncryption",
+        "1.2.840.113549.1.1.5" => "sha1WithRSAEncryption",
+        "1.2.840.113549.1.1.10" => "rsassaPss",
+        "1.2.840.113549.1.1.11" => "sha256WithRSAEncryption",
+        "1.2.840.113549.1.1.12" => "sha384WithRSAEncryption",
+        "1.2.840.113549.1.1.13" => "sha512WithRSAEncryption",
+        "1.2.840.10045.4.1" => "ecdsa-with-SHA1",
+        "1.2.840.10045.4.3.2" => "ecdsa-with-SHA256",
+        "1.2.840.10045.4.3.3" => "ecdsa-with-SHA384",
+        "1.2.840.10045.4.3.4" => "ecdsa-with-SHA512",
+        "1.3.101.112" => "Ed25519",
+        _ => return None,
+    })
+}
+
+/// A distinguished name, its attributes joined in order
+fn name(element: Element) -> Option<String> {
+    let mut attributes = Vec::new();
+    for set in Elements::new(element.contents) {
+        if set.tag != SET {
+            return None;
+        }
+        for attribute in Elements::new(set.contents) {
+            let mut parts = Elements::new(attribute.contents);
+            let kind = der::oid(parts.tagged(OID)?.contents)?;
+            let value = parts.next()?;
+            let text = match value.tag {
+                // BMPString
+                0x1e => {
+                    let units = value.contents.chunks_exact(2);
+                    char::decode_utf16(units.map(|unit| u16::from_be_bytes([unit[0], unit[1]])))
+                        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                        .collect()
+                }
+                _ => String::from_utf8_lossy(value.contents).into_owned(),
+            };
+            let kind = attribute_name(&kind).map_or(kind, str::to_string);
+            attributes.push(format!("{}={}", kind, text));
+        }
+    }
+    Some(attributes.join(", "))
+}
+
+/// A UTCTime or GeneralizedTime, as `YYYY-MM-DDTHH:MM:SSZ`
+fn time(element: Element) -> Option<String> {
+    let digits = element.contents.strip_suffix(b"Z")?;
+    if !digits.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    let digits = std::str::from_utf8(digits).ok()?;
+    let (year, rest) = match (element.tag, digits.len()) {
+        (UTC_TIME, 12) => {
+            // Two-digit years stand for 1950 to 2049
+            let year: u32 = digits[..2].parse().ok()?;
+            (if year < 50 { 2000 + year } else { 1900 + year }, &digits[2..])
+        }
+        (GENERALIZED_TIME, 14) => (digits[..4].parse().ok()?, &digits[4..]),
+        _ => return None,
+    };
+    Some(format!(
+        "{:04}-{}-{}T{}:{}:{}Z",
+        year,
+        &rest[..2],
+        &rest[2..4],
+        &rest[4..6],
+        &rest[6..8],
+        &rest[8..10]
+    ))
+}
+
+/// The algorithm and size of a subject public key
+fn public_key(spki: Element) -> Option<String> {
+    let mut parts = Elements::new(spki.contents);
+    let mut algorithm = Elements::new(parts.tagged(SEQUENCE)?.contents);
+    let key = parts.tagged(BIT_STRING)?.contents;
+    let kind = der::oid(algorithm.tagged(OID)?.contents)?;
+    match kind.as_str() {
+        RSA_ENCRYPTION => {
+            // An RSAPublicKey, its modulus first
+            let (&0, key) = key.split_first()? else {
+                return None;
+            };
+            let mut fields = Elements::new(Elements::new(key).tagged(SEQUENCE)?.contents);
+            rsa_key(fields.tagged(INTEGER)?.contents)
+        }
+        EC_PUBLIC_KEY => Some(ec_key(&der::oid(algorithm.tagged(OID)?.contents)?)),
+        _ => Some(key_algorithm_name(&kind).map_or(kind, str::to_string)),
+    }
+}
+
+pub const RSA_ENCRYPTION: &str = "1.2.840.113549.1.1.1";
+pub const EC_PUBLIC_KEY: &str = "1.2.840.10045.2.1";
+
+/// Key algorithms with no parameters worth showing
+pub fn key_algorithm_name(oid: &str) -> Option<&'static str> {
+    Some(match oid {
+        "1.2.840.10040.4.1" => "DSA",
+        "1.3.101.110" => "X25519",
+        "1.3.101.111" => "X448",
+        "1.3.101.112" => "Ed25519",
+        "1.3.101.113" => "Ed448",
+        _ => return None,
+    })
+}
+
+/// An RSA key, as `RSA 2048`, from its modulus
+pub fn rsa_key(modulus: &[u8]) -> Option<String> {
+    let modulus = &modulus[modulus.iter().position(|&b| b != 0)?..];
+    Some(format!("RSA {}", modulus.len() * 8 - modulus[0].leading_zeros() as usize))
+}
+
+/// An EC key, as `EC P-256`, from the OID of its curve
+pub fn ec_key(curve: &str) -> String {
+    let curve = match curve {
+        "1.2.840.10045.3.1.7" => "P-256",
+        "1.3.132.0.10" => "secp256k1",
+        "1.3.132.0.34" => "P-384",
+        "1.3.132.0.35" => "P-521",
+        _ => curve,
+    };
+    format!("EC {}", curve)
+}
+
+/// Whether the basic constraints among `extensions` make a CA
+fn is_ca(extensions: Element) -> Option<bool> {
+    for extension in Elements::new(Elements::new(extensions.contents).tagged(SEQUENCE)?.contents) {
+        let mut parts = Elements::new(extension.contents);
+        if der::oid(parts.tagged(OID)?.contents)? != "2.5.29.19" {
+            continue;
+        }
+        if parts.peek() == Some(BOOLEAN) {
+            parts.next();
+        }
+        let value = parts.tagged(OCTET_STRING)?.contents;
+        let mut constraints = Elements::new(Elements::new(value).tagged(SEQUENCE)?.contents);
+        return Some(constraints.tagged(BOOLEAN).is_some_and(|ca| ca.contents != [0]));
+    }
+    Some(false)
+}
+
+fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut hash = Sha256::new();
+    hash.update(data);
+    hash.finish()
+}
+
+/// The SHA-256 of `der`, as lowercase hex
+pub fn fingerprint(der: &[u8]) -> String {
+    sha256(der).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// The `sha256/...` pin of a DER subject public key info
+pub fn pin(spki: &[u8]) -> String {
+    format!("sha256/{}", base64::encode(&sha256(spki)))
+}
+
+/// Parse `der`, which must be a certificate and nothing more
+pub fn parse_certificate(der: &[u8]) -> Option<Certificate> {
+    let mut outer = Elements::new(der);
+    let certificate = outer.tagged(SEQUENCE)?;
+    if !outer.rest.is_empty() {
+        return None;
+    }
+    let mut parts = Elements::new(certificate.contents);
+    let tbs = parts.tagged(SEQUENCE)?;
+    let mut algorithm = Elements::new(parts.tagged(SEQUENCE)?.contents);
+    parts.tagged(BIT_STRING)?;
+    if !parts.rest.is_empty() {
+        return None;
+    }
+    let algorithm = der::oid(algorithm.tagged(OID)?.contents)?;
+
+    let mut fields = Elements::new(tbs.contents);
+    let version = match fields.peek() {
+        Some(VERSION) => match Elements::new(fields.next()?.contents).tagged(INTEGER)?.contents {
+            &[version @ 0..=2] => version + 1,
+            _ => return None,
+        },
+        _ => 1,
+    };
+    let serial = fields.tagged(INTEGER)?.contents;
+    if serial.is_empty() || serial.len() > 32 {
+        return None;
+    }
+    let serial = serial.strip_prefix(&[0]).filter(|s| !s.is_empty()).unwrap_or(serial);
+    fields.tagged(SEQUENCE)?;
+    let issuer = fields.tagged(SEQUENCE)?;
+    let mut validity = Elements::new(fields.tagged(SEQUENCE)?.contents);
+    let not_before = time(validity.next()?)?;
+    let not_after = time(validity.next()?)?;
+    let subject = fields.tagged(SEQUENCE)?;
+    let spki = fields.tagged(SEQUENCE)?;
+    let mut ca = false;
+    // Then the unique IDs of version 2, and the extensions of version 3
+    for field in fields.by_ref() {
+        if field.tag == EXTENSIONS {
+            ca = is_ca(field)?;
+        }
+    }
+    if !fields.rest.is_empty() {
+        return None;
+    }
+
+    Some(Certificate {
+        version,
+        serial: serial.iter().map(|b| format!("{:02x}", b)).collect(),
+        subject: name(subject)?,
+        issuer: name(issuer)?,
+        not_before,
+        not_after,
+        signature_algorithm: signature_algorithm_name(&algorithm).map_or(algorithm, str::to_string),
+        public_key: public_key(spki)?,
+        self_signed: subject.whole == issuer.whole,
+        ca,
+        fingerprint: fingerprint(der),
+        pin: pin(spki.whole),
+    })
+}
+
+/// The start of a DER certificate, or a PEM one
+fn candidates() -> &'static Regex {
+    static CANDIDATES: OnceLock<Regex> = OnceLock::new();
+    CANDIDATES.get_or_init(|| {
+        Regex::new(r"(?s-u)\x30\x82..\x30\x82|-----BEGIN CERTIFICATE-----")
+            .expect("certificate pattern is valid")
+    })
+}
+
+/// File a certificate found at `address` is written to
+pub fn certificate_file_name(address: u64) -> String {
+    format!("{:08x}.pem", address)
+}
+
+/// Search `regions` for certificates and write each distinct one to its
+/// own file in `dir`, delivering a [`CarvedCertificate`] for each.
+///
+/// Returns why the walk stopped early, if it did, or the first write
+/// error.
+pub fn carve_certificates(
+    reader: &mut dyn MemoryReader,
+    regions: &[MemoryRegion],
+    dir: &Path,
+    sink: &mut dyn ScanSink<CarvedCertificate>,
+    control: &mut ScanControl,
+) -> io::Result<Option<StopReason>> {
+    let mut visitor = CertificateVisitor {
+        dir,
+        candidates: PatternScanner::with_overlap(candidates().clone(), MAX_MATCH_LENGTH),
+        found: Vec::new(),
+        seen: HashSet::new(),
+        failed: None,
+    };
+    let stopped = scan::walk_regions(reader, regions, &mut visitor, sink, control);
+    match visitor.failed {
+        Some(e) => Err(e),
+        None => Ok(stopped),
+    }
+}
+
+struct CertificateVisitor<'a> {
+    dir: &'a Path,
+    candidates: PatternScanner<Regex>,
+    /// The candidates found in the region being read
+    found: Vec<Hit>,
+    /// Fingerprints of the certificates written
+    seen: HashSet<String>,
+    /// The first write failure; nothing more is written after one
+    failed: Option<io::Error>,
+}
+
+impl CertificateVisitor<'_> {
+    /// Read the certificate at `address`, if there is one and it wasn't
+    /// seen before, and write it out
+    fn carve(
+        &mut self,
+        reader: &mut dyn MemoryReader,
+        address: u64,
+    ) -> io::Result<Option<CarvedCertificate>> {
+        let Some((encoding, der)) = der::read_at(reader, address, MAX_READ_LENGTH) else {
+            return Ok(None);
+        };
+        let Some(certificate) = parse_certificate(&der) else {
+            return Ok(None);
+        };
+        if !self.seen.insert(certificate.fingerprint.clone()) {
+            return Ok(None);
+        }
+
+        let name = certificate_file_name(address);
+        let pem = der::to_pem(PEM_LABEL, &der);
+        fs::write(self.dir.join(&name), &pem)?;
+        let mut hash = Sha256::new();
+        hash.update(pem.as_bytes());
+        Ok(Some(CarvedCertificate {
+            file: DumpedFile {
+                start: address,
+                file: name,
+                size: pem.len() as u64,
+                sha256: hash.finish_hex(),
+            },
+            encoding,
+            length: der.len(),
+            certificate,
+        }))
+    }
+}
+
+impl RegionVisitor for CertificateVisitor<'_> {
+    type Output = CarvedCertificate;
+
+    fn visit_chunk(&mut self, _region: &MemoryRegion, address: u64, chunk: &[u8]) {
+        self.candidates.feed(address, chunk, &mut self.found);
+    }
+
+    fn end_region(
+        &mut self,
+        _region: &MemoryRegion,
+        reader: &mut dyn MemoryReader,
+        sink: &mut dyn ScanSink<CarvedCertificate>,
+    ) {
+        self.candidates.finish(&mut self.found);
+        for hit in std::mem::take(&mut self.found) {
+            if self.failed.is_some() {
+                return;
+            }
+            match self.carve(reader, hit.address) {
+                Ok(Some(certificate)) => sink.result(certificate),
+                Ok(None) => {}
+                Err(e) => self.failed = Some(e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps::parse_maps_line;
+    use crate::reader::ProcfsReader;
+    use crate::scan::Scan;
+
+    /// A self-signed P-256 certificate for `api.example.com`
+    const PEM: &str = "\
+-----BEGIN CERTIFICATE-----
+MIIBzzCCAXSgAwIBAgIUdHK69wkdaDz3Q+NExtwZHk9Bo8owCgYIKoZIzj0EAwIw
+PjELMAkGA1UEBhMCVVMxFTATBgNVBAoMDEV4YW1wbGUgQ29ycDEYMBYGA1UEAwwP
+YXBpLmV4YW1wbGUuY29tMB4XDTI2MTAxNDA2NTI1MVoXDTM2MTAxMTA2NTI1MVow
+PjELMAkGA1UEBhMCVVMxFTATBgNVBAoMDEV4YW1wbGUgQ29ycDEYMBYGA1UEAwwP
+YXBpLmV4YW1wbGUuY29tMFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAEYEsF/nuH
+nVXGRk8PqMy5qnUe+/UKb4LY9J0fLERbSioH7vaj9LJXJPua+SJNsm0lsCMA2YD+
+llnqcR+/k0p0N6NQME4wHQYDVR0OBBYEFLri7UoyKPT0ffvXJkjIe+MdWgTZMB8G
+A1UdIwQYMBaAFLri7UoyKPT0ffvXJkjIe+MdWgTZMAwGA1UdEwEB/wQCMAAwCgYI
+KoZIzj0EAwIDSQAwRgIhAOVaoiCqqJtcXP6scIdGXdNJinspGk+99EFe5mhg17Ac
+AiEAwqhuoVhCS3oDv1OAiHU+8btr7y/FVxGsnbNeDbPO29E=
+-----END CERTIFICATE-----
+";
+
+    #[test]
+    fn test_parse_certificate() {
+        let (label, der) = der::from_pem(PEM.as_bytes()).unwrap();
+        assert_eq!(der::to_pem(label, &der), PEM);
+        let certificate = parse_certificate(&der).unwrap();
+        assert_eq!(
+            certificate,
+            Certificate {
+                version: 3,
+                serial: "7472baf7091d683cf743e344c6dc191e4f41a3ca".to_string(),
+                subject: "C=US, O=Example Corp, CN=api.example.com".to_string(),
+                issuer: "C=US, O=Example Corp, CN=api.example.com".to_string(),
+                not_before: "2026-10-14T06:52:51Z".to_string(),
+                not_after: "2036-10-11T06:52:51Z".to_string(),
+                signature_algorithm: "ecdsa-with-SHA256".to_string(),
+                public_key: "EC P-256".to_string(),
+                self_signed: true,
+                ca: false,
+                fingerprint: "aac2892ef0ec9b3f75f67eb3fff61e8909819c59600cd34a9c84f9c433ead768"
+                    .to_string(),
+                pin: "sha256/kAZ/K98h4XCjK4aYdZR4kvnVYUFqjsRx6xiOFlA7p78=".to_string(),
+            }
+        );
+        assert!(parse_certificate(&der[..der.len() - 1]).is_none());
+        let mut other = der.clone();
+        other[4] = 0x31;
+        assert!(parse_certificate(&other).is_none());
+    }
+
+    #[test]
+    fn test_carves_distinct_certificates() {
+        let (_, der) = der::from_pem(PEM.as_bytes()).unwrap();
+        let mut data = vec![0u8; 0x3000];
+        data[0x123..0x123 + der.len()].copy_from_slice(&der);
+        data[0x1801..0x1801 + PEM.len()].copy_from_slice(PEM.as_bytes());
+        let start = data.as_ptr() as u64;
+        let end = start + data.len() as u64;
+        let region = parse_maps_line(&format!("{:x}-{:x} rw-p 00000000 00:00 0", start, end));
+
+        let pid = std::process::id() as i32;
+        let dir = std::env::temp_dir().join(format!("x509-test-{}", pid));
+        fs::create_dir_all(&dir).unwrap();
+        let mut reader = ProcfsReader::open(pid).unwrap();
+        let mut scan = Scan::default();
+        let regions = [region.unwrap()];
+        let stopped =
+            carve_certificates(&mut reader, &regions, &dir, &mut scan, &mut ScanControl::new());
+
+        assert_eq!(stopped.unwrap(), None);
+        assert_eq!(scan.results.len(), 1);
+        let carved = &scan.results[0];
+        assert_eq!((carved.file.start, carved.encoding), (start + 0x123, Encoding::Der));
+        assert_eq!(carved.length, der.len());
+        assert_eq!(fs::read_to_string(dir.join(&carved.file.file)).unwrap(), PEM);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,437 @@
+//! YARA rules run over process memory, with the `yara` feature
+//!
+//! Existing YARA corpora, for malware families or credential formats, can
+//! be run as they are against a live process or a dump. The library links
+//! against libyara 4.3 or later through the bindings below, so builds with
+//! the feature need a libyara built for the target ABI on the linker path.
+//!
+//! YARA scans one buffer at a time, and conditions such as `filesize` or
+//! `uint16(0)` are relative to it, so each region is scanned as a buffer of
+//! its own. Regions larger than `maxBufferMb` are scanned in windows of
+//! that size, overlapping by [`search::DEFAULT_OVERLAP`] bytes so strings
+//! straddling two windows are still seen. A rule is reported once per
+//! region, at the first window it matched in.
+
+use crate::maps::MemoryRegion;
+use crate::reader::MemoryReader;
+use crate::scan::{Location, RegionVisitor, ScanSink};
+use crate::search;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::ptr::{self, NonNull};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Most threads libyara lets scan with the same rules at once
+pub const MAX_THREADS: usize = 32;
+
+/// The `yara` object of the scan options
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct YaraOptions {
+    /// Rules source, as in a `.yar` file
+    pub rules: Option<String>,
+    /// Path of a `.yar` file on the device, compiled along with `rules`
+    pub rules_file: Option<String>,
+    /// Largest buffer handed to libyara, in MiB
+    pub max_buffer_mb: usize,
+    /// Seconds libyara may spend on one buffer; `0` for no limit
+    pub timeout_secs: i32,
+}
+
+impl Default for YaraOptions {
+    fn default() -> Self {
+        YaraOptions {
+            rules: None,
+            rules_file: None,
+            max_buffer_mb: 16,
+            timeout_secs: 0,
+        }
+    }
+}
+
+/// A rule that matched in a region
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct YaraMatch {
+    /// Start of the window the rule matched in
+    #[serde(flatten)]
+    pub location: Location,
+    /// Bytes in that window
+    pub length: usize,
+    pub rule: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+mod sys {
+    use std::ffi::{c_char, c_int, c_void};
+
+    pub const ERROR_SUCCESS: c_int = 0;
+    pub const CALLBACK_CONTINUE: c_int = 0;
+    pub const CALLBACK_MSG_RULE_MATCHING: c_int = 1;
+    pub const YARA_ERROR_LEVEL_ERROR: c_int = 0;
+
+    #[repr(C)]
+    pub struct YrCompiler {
+        _private: [u8; 0],
+    }
+
+    #[repr(C)]
+    pub struct YrRules {
+        _private: [u8; 0],
+    }
+
+    #[repr(C)]
+    pub struct YrScanContext {
+        _private: [u8; 0],
+    }
+
+    /// `DECLARE_REFERENCE`: a pointer, or an arena reference while the rules
+    /// are being built, both 8 bytes wide on 32-bit ABIs too
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub union Reference {
+        pub ptr: *const c_char,
+        pub arena: [u32; 2],
+    }
+
+    /// The head of `YR_RULE`, as laid out since libyara 4.3
+    #[repr(C)]
+    pub struct YrRule {
+        pub flags: i32,
+        pub num_atoms: i32,
+        pub required_strings: u32,
+        pub unused: u32,
+        pub identifier: Reference,
+        /// Tags, each NUL-terminated, ended by an empty one
+        pub tags: Reference,
+    }
+
+    pub type CompilerCallback = extern "C" fn(
+        error_level: c_int,
+        file_name: *const c_char,
+        line_number: c_int,
+        rule: *const YrRule,
+        message: *const c_char,
+        user_data: *mut c_void,
+    );
+
+    pub type ScanCallback = extern "C" fn(
+        context: *mut YrScanContext,
+        message: c_int,
+        message_data: *mut c_void,
+        user_data: *mut c_void,
+    ) -> c_int;
+
+    #[link(name = "yara")]
+    extern "C" {
+        pub fn yr_initialize() -> c_int;
+        pub fn yr_finalize() -> c_int;
+        pub fn yr_compiler_create(compiler: *mut *mut YrCompiler) -> c_int;
+        pub fn yr_compiler_destroy(compiler: *mut YrCompiler);
+        pub fn yr_compiler_set_callback(
+            compiler: *mut YrCompiler,
+            callback: CompilerCallback,
+            user_data: *mut c_void,
+        );
+        pub fn yr_compiler_add_string(
+            compiler: *mut YrCompiler,
+            rules: *const c_char,
+            namespace: *const c_char,
+        ) -> c_int;
+        pub fn yr_compiler_get_rules(compiler: *mut YrCompiler, rules: *mut *mut YrRules) -> c_int;
+        pub fn yr_rules_destroy(rules: *mut YrRules) -> c_int;
+        pub fn yr_rules_scan_mem(
+            rules: *mut YrRules,
+            buffer: *const u8,
+            buffer_size: usize,
+            flags: c_int,
+            callback: ScanCallback,
+            user_data: *mut c_void,
+            timeout: c_int,
+        ) -> c_int;
+    }
+}
+
+/// Users of libyara, which is initialized while there are any
+static USERS: Mutex<usize> = Mutex::new(0);
+
+fn acquire() -> Result<(), String> {
+    let mut users = USERS.lock().unwrap_or_else(|e| e.into_inner());
+    if *users == 0 {
+        let status = unsafe { sys::yr_initialize() };
+        if status != sys::ERROR_SUCCESS {
+            return Err(format!("Failed to initialize libyara: error {}", status));
+        }
+    }
+    *users += 1;
+    Ok(())
+}
+
+fn release() {
+    let mut users = USERS.lock().unwrap_or_else(|e| e.into_inner());
+    *users -= 1;
+    if *users == 0 {
+        unsafe { sys::yr_finalize() };
+    }
+}
+
+/// Compiled YARA rules
+pub struct Rules {
+    rules: NonNull<sys::YrRules>,
+}
+
+// libyara allows up to MAX_THREADS threads to scan with the same rules
+unsafe impl Send for Rules {}
+unsafe impl Sync for Rules {}
+
+extern "C" fn on_compiler_message(
+    level: c_int,
+    _file_name: *const c_char,
+    line: c_int,
+    _rule: *const sys::YrRule,
+    message: *const c_char,
+    user_data: *mut c_void,
+) {
+    if level != sys::YARA_ERROR_LEVEL_ERROR || message.is_null() {
+        return;
+    }
+    let errors = unsafe { &mut *(user_data as *mut Vec<String>) };
+    let message = unsafe { CStr::from_ptr(message) }.to_string_lossy();
+    errors.push(format!("line {}: {}", line, message));
+}
+
+impl Rules {
+    /// Compile `source`, or say what is wrong with it
+    pub fn compile(source: &str) -> Result<Self, String> {
+        let source = CString::new(source).map_err(|_| "YARA rules contain a NUL byte")?;
+        acquire()?;
+        let mut compiler = ptr::null_mut();
+        if unsafe { sys::yr_compiler_create(&mut compiler) } != sys::ERROR_SUCCESS {
+            release();
+            return Err("Failed to create a YARA compiler".to_string());
+        }
+        let mut errors: Vec<String> = Vec::new();
+        let mut rules = ptr::null_mut();
+        let status = unsafe {
+            sys::yr_compiler_set_callback(
+                compiler,
+                on_compiler_message,
+                &mut errors as *mut Vec<String> as *mut c_void,
+            );
+            if sys::yr_compiler_add_string(compiler, source.as_ptr(), ptr::null()) == 0 {
+                sys::yr_compiler_get_rules(compiler, &mut rules)
+            } else {
+                -1
+            }
+        };
+        unsafe { sys::yr_compiler_destroy(compiler) };
+        match NonNull::new(rules) {
+            Some(rules) if status == sys::ERROR_SUCCESS => Ok(Rules { rules }),
+            _ => {
+                release();
+                Err(match errors.is_empty() {
+                    true => format!("Failed to compile YARA rules: error {}", status),
+                    false => format!("Invalid YARA rules: {}", errors.join("; ")),
+                })
+            }
+        }
+    }
+
+    /// The rules matching `data`, with their tags, or libyara's error code
+    pub fn scan(&self, data: &[u8], timeout_secs: i32) -> Result<Vec<(String, Vec<String>)>, i32> {
+        let mut matched: Vec<(String, Vec<String>)> = Vec::new();
+        let status = unsafe {
+            sys::yr_rules_scan_mem(
+                self.rules.as_ptr(),
+                data.as_ptr(),
+                data.len(),
+                0,
+                on_scan_message,
+                &mut matched as *mut Vec<(String, Vec<String>)> as *mut c_void,
+                timeout_secs,
+            )
+        };
+        match status {
+            sys::ERROR_SUCCESS => Ok(matched),
+            error => Err(error),
+        }
+    }
+}
+
+impl Drop for Rules {
+    fn drop(&mut self) {
+        unsafe { sys::yr_rules_destroy(self.rules.as_ptr()) };
+        release();
+    }
+}
+
+extern "C" fn on_scan_message(
+    _context: *mut sys::YrScanContext,
+    message: c_int,
+    message_data: *mut c_void,
+    user_data: *mut c_void,
+) -> c_int {
+    if message != sys::CALLBACK_MSG_RULE_MATCHING || message_data.is_null() {
+        return sys::CALLBACK_CONTINUE;
+    }
+    let matched = unsafe { &mut *(user_data as *mut Vec<(String, Vec<String>)>) };
+    let rule = unsafe { &*(message_data as *const sys::YrRule) };
+    let identifier = unsafe { CStr::from_ptr(rule.identifier.ptr) }.to_string_lossy();
+    let mut tags = Vec::new();
+    let mut tag = unsafe { rule.tags.ptr };
+    while !tag.is_null() {
+        let name = unsafe { CStr::from_ptr(tag) };
+        if name.is_empty() {
+            break;
+        }
+        tags.push(name.to_string_lossy().into_owned());
+        tag = unsafe { tag.add(name.to_bytes().len() + 1) };
+    }
+    matched.push((identifier.into_owned(), tags));
+    sys::CALLBACK_CONTINUE
+}
+
+/// Scans each region it is fed with a set of [`Rules`]
+pub struct YaraVisitor<'a> {
+    rules: &'a Rules,
+    max_buffer: usize,
+    timeout_secs: i32,
+    /// Windows libyara failed to scan, shared by every visitor of a scan
+    failures: &'a AtomicUsize,
+    buffer: Vec<u8>,
+    /// Address of the first byte in `buffer`
+    start: u64,
+    /// Bytes at the end of `buffer` not scanned yet
+    unscanned: usize,
+    /// Rules already reported for the region
+    reported: HashSet<String>,
+    found: Vec<YaraMatch>,
+}
+
+impl<'a> YaraVisitor<'a> {
+    pub fn new(rules: &'a Rules, options: &YaraOptions, failures: &'a AtomicUsize) -> Self {
+        YaraVisitor {
+            rules,
+            max_buffer: (options.max_buffer_mb.max(1) << 20).max(2 * search::DEFAULT_OVERLAP),
+            timeout_secs: options.timeout_secs,
+            failures,
+            buffer: Vec::new(),
+            start: 0,
+            unscanned: 0,
+            reported: HashSet::new(),
+            found: Vec::new(),
+        }
+    }
+
+    /// Scan the buffer, then keep only its tail if `keep_tail`
+    fn flush(&mut self, keep_tail: bool) {
+        if self.unscanned == 0 {
+            return;
+        }
+        self.unscanned = 0;
+        match self.rules.scan(&self.buffer, self.timeout_secs) {
+            Ok(matched) => {
+                for (rule, tags) in matched {
+                    if self.reported.insert(rule.clone()) {
+                        self.found.push(YaraMatch {
+                            // Set in end_region, once the region is known
+                            location: Location::new(&Arc::from(""), 0, self.start),
+                            length: self.buffer.len(),
+                            rule,
+                            tags,
+                        });
+                    }
+                }
+            }
+            Err(_) => {
+                self.failures.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        let keep = if keep_tail { search::DEFAULT_OVERLAP.min(self.buffer.len()) } else { 0 };
+        let drop = self.buffer.len() - keep;
+        self.buffer.drain(..drop);
+        self.start += drop as u64;
+    }
+}
+
+impl RegionVisitor for YaraVisitor<'_> {
+    type Output = YaraMatch;
+
+    fn visit_chunk(&mut self, region: &MemoryRegion, address: u64, mut chunk: &[u8]) {
+        if address == region.start {
+            self.reported.clear();
+        }
+        // A gap is where pages were skipped: scan what came before apart
+        if address != self.start + self.buffer.len() as u64 {
+            self.flush(false);
+            self.start = address;
+        }
+        while !chunk.is_empty() {
+            let room = self.max_buffer - self.buffer.len();
+            let (take, rest) = chunk.split_at(room.min(chunk.len()));
+            self.buffer.extend_from_slice(take);
+            self.unscanned += take.len();
+            chunk = rest;
+            if self.buffer.len() == self.max_buffer {
+                self.flush(true);
+            }
+        }
+    }
+
+    fn end_region(
+        &mut self,
+        region: &MemoryRegion,
+        _reader: &mut dyn MemoryReader,
+        sink: &mut dyn ScanSink<YaraMatch>,
+    ) {
+        self.flush(false);
+        self.buffer.clear();
+        self.reported.clear();
+        let name: Arc<str> = region.name().into();
+        for mut found in self.found.drain(..) {
+            found.location = Location::new(&name, region.start, found.location.address);
+            sink.result(found);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps::parse_maps_line;
+    use crate::reader::AutoReader;
+    use crate::scan::Scan;
+
+    #[test]
+    fn test_rules_match_across_windows() {
+        let source = r#"
+            rule session_key : credential { strings: $a = "SESSION=" condition: $a }
+            rule never { strings: $a = "no such bytes" condition: $a }
+        "#;
+        let rules = Rules::compile(source).unwrap();
+        assert!(Rules::compile("rule broken {").is_err());
+
+        let region = parse_maps_line("10000-210000 rw-p 00000000 00:00 0 [heap]").unwrap();
+        let failures = AtomicUsize::new(0);
+        let options = YaraOptions {
+            max_buffer_mb: 1,
+            ..YaraOptions::default()
+        };
+        let mut visitor = YaraVisitor::new(&rules, &options, &failures);
+        // Straddling the first two windows, and again in the second
+        let mut data = vec![0u8; 0x200000];
+        data[0xffffc..0x100004].copy_from_slice(b"SESSION=");
+        data[0x180000..0x180008].copy_from_slice(b"SESSION=");
+        visitor.visit_chunk(&region, 0x10000, &data);
+        let mut scan = Scan::default();
+        visitor.end_region(&region, &mut AutoReader::new(0), &mut scan);
+
+        assert_eq!(failures.load(Ordering::Relaxed), 0);
+        assert_eq!(scan.results.len(), 1);
+        assert_eq!(scan.results[0].rule, "session_key");
+        assert_eq!(scan.results[0].tags, ["credential"]);
+    }
+}
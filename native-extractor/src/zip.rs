@@ -0,0 +1,502 @@
+//! Carving of ZIP archives, APKs among them, out of process memory
+//!
+//! Plugin APKs downloaded at runtime, asset bundles and JARs are often
+//! only ever held in memory, or written somewhere the app deletes them
+//! from once loaded. A ZIP archive is read from its end: the end of
+//! central directory record, `PK\x05\x06`, gives the size and offset of
+//! the central directory, which comes right before it, so the start of the
+//! archive is that far back. Each central directory header must then be
+//! in order, and point at a local file header with the same name and
+//! method, for the archive to be carved from its start to the end of its
+//! comment.
+//!
+//! Archives in one file, without ZIP64 records, are found: those over 4
+//! GiB or 65535 entries aren't. An archive stored inside another is carved
+//! too, and an archive seen before is written once.
+
+use crate::crc32::Crc32;
+use crate::dump::DumpedFile;
+use crate::maps::MemoryRegion;
+use crate::reader::{self, MemoryReader};
+use crate::scan::{self, RegionVisitor, ScanControl, ScanSink, StopReason};
+use crate::search::{Hit, PatternScanner};
+use crate::sha256::Sha256;
+use regex::bytes::Regex;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::OnceLock;
+
+const EOCD_SIGNATURE: &[u8; 4] = b"PK\x05\x06";
+const CENTRAL_SIGNATURE: &[u8; 4] = b"PK\x01\x02";
+const LOCAL_SIGNATURE: &[u8; 4] = b"PK\x03\x04";
+
+/// Size of the end of central directory record, before its comment
+const EOCD_SIZE: usize = 22;
+const CENTRAL_HEADER_SIZE: usize = 46;
+const LOCAL_HEADER_SIZE: usize = 30;
+
+/// Largest archive carved
+const MAX_ARCHIVE_SIZE: u64 = 1 << 30;
+
+/// Entries with this flag are encrypted
+const FLAG_ENCRYPTED: u16 = 1;
+
+const METHOD_STORED: u16 = 0;
+
+fn eocd_pattern() -> &'static Regex {
+    static EOCD: OnceLock<Regex> = OnceLock::new();
+    EOCD.get_or_init(|| Regex::new(r"(?-u)PK\x05\x06").expect("EOCD pattern is valid"))
+}
+
+/// Kinds of archive carved, by the entries in them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ArchiveKind {
+    /// An Android package, with an `AndroidManifest.xml`
+    Apk,
+    /// A Java archive, with a `META-INF/MANIFEST.MF`
+    Jar,
+    Zip,
+}
+
+impl ArchiveKind {
+    fn extension(self) -> &'static str {
+        match self {
+            ArchiveKind::Apk => "apk",
+            ArchiveKind::Jar => "jar",
+            ArchiveKind::Zip => "zip",
+        }
+    }
+}
+
+/// A file in a carved archive, as its central directory gives it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZipEntry {
+    pub name: String,
+    /// Compression method: 0 for stored, 8 for deflated
+    pub method: u16,
+    pub compressed_size: u64,
+    pub size: u64,
+    pub encrypted: bool,
+    /// Whether the data of a stored, unencrypted entry matches its CRC-32
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crc_valid: Option<bool>,
+}
+
+/// An archive found in memory and written to disk
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CarvedArchive {
+    #[serde(flatten)]
+    pub file: DumpedFile,
+    pub kind: ArchiveKind,
+    /// Size the archive should have; `size` is less if memory ran out first
+    pub length: u64,
+    pub entries: Vec<ZipEntry>,
+}
+
+fn le16(data: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([data[at], data[at + 1]])
+}
+
+fn le32(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(data[at..at + 4].try_into().unwrap())
+}
+
+/// The fields of an end of central directory record that locate the
+/// archive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EndOfCentralDirectory {
+    pub entries: usize,
+    pub directory_size: u64,
+    pub directory_offset: u64,
+    pub comment_length: usize,
+}
+
+/// Parse the end of central directory record at the start of `data`, if
+/// it is that of an archive in one file without ZIP64 records
+pub fn parse_eocd(data: &[u8]) -> Option<EndOfCentralDirectory> {
+    if data.len() < EOCD_SIZE || !data.starts_with(EOCD_SIGNATURE) {
+        return None;
+    }
+    let (disk, directory_disk) = (le16(data, 4), le16(data, 6));
+    let (disk_entries, entries) = (le16(data, 8), le16(data, 10));
+    let (size, offset) = (le32(data, 12), le32(data, 16));
+    if disk != 0 || directory_disk != 0 || disk_entries != entries || entries == 0 {
+        return None;
+    }
+    // Fields at their maximum defer to ZIP64 records
+    if entries == u16::MAX || size == u32::MAX || offset == u32::MAX {
+        return None;
+    }
+    Some(EndOfCentralDirectory {
+        entries: usize::from(entries),
+        directory_size: u64::from(size),
+        directory_offset: u64::from(offset),
+        comment_length: usize::from(le16(data, 20)),
+    })
+}
+
+/// A central directory header, with what is needed to check its entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CentralEntry {
+    entry: ZipEntry,
+    name: Vec<u8>,
+    crc: u32,
+    local_offset: u64,
+}
+
+/// Parse the `count` central directory headers that make up `directory`,
+/// which starts `offset` bytes into its archive
+fn parse_directory(directory: &[u8], count: usize, offset: u64) -> Option<Vec<CentralEntry>> {
+    let mut entries = Vec::with_capacity(count);
+    let mut pos = 0;
+    while entries.len() < count {
+        let header = directory.get(pos..pos + CENTRAL_HEADER_SIZE)?;
+        if !header.starts_with(CENTRAL_SIGNATURE) {
+            return None;
+        }
+        let flags = le16(header, 8);
+        let method = le16(header, 10);
+        let name_length = usize::from(le16(header, 28));
+        let extra_length = usize::from(le16(header, 30));
+        let comment_length = usize::from(le16(header, 32));
+        let local_offset = u64::from(le32(header, 42));
+        if le16(header, 34) != 0 || local_offset >= offset {
+            return None;
+        }
+        let name_start = pos + CENTRAL_HEADER_SIZE;
+        let name = directory.get(name_start..name_start + name_length)?.to_vec();
+        pos = name_start + name_length + extra_length + comment_length;
+        entries.push(CentralEntry {
+            entry: ZipEntry {
+                name: String::from_utf8_lossy(&name).into_owned(),
+                method,
+                compressed_size: u64::from(le32(header, 20)),
+                size: u64::from(le32(header, 24)),
+                encrypted: flags & FLAG_ENCRYPTED != 0,
+                crc_valid: None,
+            },
+            name,
+            crc: le32(header, 16),
+            local_offset,
+        });
+    }
+    // The headers fill the directory
+    (pos == directory.len()).then_some(entries)
+}
+
+/// The CRC-32 of `length` bytes of memory at `address`, if they can all
+/// be read
+fn memory_crc(reader: &mut dyn MemoryReader, address: u64, length: u64) -> Option<u32> {
+    let mut crc = Crc32::new();
+    let mut buf = vec![0u8; reader::READ_CHUNK_SIZE.min(length as usize)];
+    let mut done = 0;
+    while done < length {
+        let want = (length - done).min(buf.len() as u64) as usize;
+        let read = reader::read_into(reader, address + done, &mut buf[..want]).ok()?;
+        if read < want {
+            return None;
+        }
+        crc.update(&buf[..read]);
+        done += read as u64;
+    }
+    Some(crc.finish())
+}
+
+/// Check the local file header of `entry` in the archive at `start`,
+/// which has its central directory `offset` bytes in, and for a stored
+/// entry the CRC of its data
+fn check_local(
+    reader: &mut dyn MemoryReader,
+    start: u64,
+    offset: u64,
+    entry: &mut CentralEntry,
+) -> bool {
+    let address = start + entry.local_offset;
+    let mut header = vec![0u8; LOCAL_HEADER_SIZE + entry.name.len()];
+    if reader::read_into(reader, address, &mut header).unwrap_or(0) < header.len() {
+        return false;
+    }
+    let name_length = usize::from(le16(&header, 26));
+    if !header.starts_with(LOCAL_SIGNATURE)
+        || le16(&header, 8) != entry.entry.method
+        || name_length != entry.name.len()
+        || header[LOCAL_HEADER_SIZE..] != entry.name
+    {
+        return false;
+    }
+    let data = entry.local_offset
+        + (LOCAL_HEADER_SIZE + name_length + usize::from(le16(&header, 28))) as u64;
+    if data + entry.entry.compressed_size > offset {
+        return false;
+    }
+    if entry.entry.method == METHOD_STORED && !entry.entry.encrypted {
+        let crc = memory_crc(reader, start + data, entry.entry.compressed_size);
+        entry.entry.crc_valid = Some(crc == Some(entry.crc));
+    }
+    true
+}
+
+/// The kind of an archive with `entries`
+fn archive_kind(entries: &[ZipEntry]) -> ArchiveKind {
+    let has = |name: &str| entries.iter().any(|entry| entry.name == name);
+    if has("AndroidManifest.xml") {
+        ArchiveKind::Apk
+    } else if has("META-INF/MANIFEST.MF") {
+        ArchiveKind::Jar
+    } else {
+        ArchiveKind::Zip
+    }
+}
+
+/// File an archive found at `address` is written to
+pub fn archive_file_name(address: u64, kind: ArchiveKind) -> String {
+    format!("{:08x}.{}", address, kind.extension())
+}
+
+/// Search `regions` for ZIP archives and write each distinct one to its
+/// own file in `dir`, delivering a [`CarvedArchive`] for each.
+///
+/// Failing to write to `dir` is an error; files written before the
+/// failure are left in place.
+pub fn carve_archives(
+    reader: &mut dyn MemoryReader,
+    regions: &[MemoryRegion],
+    dir: &Path,
+    sink: &mut dyn ScanSink<CarvedArchive>,
+    control: &mut ScanControl,
+) -> io::Result<Option<StopReason>> {
+    let mut visitor = ZipVisitor {
+        dir,
+        records: PatternScanner::with_overlap(eocd_pattern().clone(), EOCD_SIGNATURE.len()),
+        found: Vec::new(),
+        seen: HashSet::new(),
+        failed: None,
+    };
+    let stopped = scan::walk_regions(reader, regions, &mut visitor, sink, control);
+    match visitor.failed {
+        Some(e) => Err(e),
+        None => Ok(stopped),
+    }
+}
+
+struct ZipVisitor<'a> {
+    dir: &'a Path,
+    records: PatternScanner<Regex>,
+    /// The end of central directory records found in the region being read
+    found: Vec<Hit>,
+    /// SHA-256 of the archives written
+    seen: HashSet<String>,
+    /// The first write failure; nothing more is written after one
+    failed: Option<io::Error>,
+}
+
+impl ZipVisitor<'_> {
+    /// Read the archive ending with the record at `address`, if it holds
+    /// together, and write it out
+    fn carve(
+        &mut self,
+        reader: &mut dyn MemoryReader,
+        address: u64,
+    ) -> io::Result<Option<CarvedArchive>> {
+        let mut record = [0u8; EOCD_SIZE];
+        if reader::read_into(reader, address, &mut record).unwrap_or(0) < EOCD_SIZE {
+            return Ok(None);
+        }
+        let Some(eocd) = parse_eocd(&record) else {
+            return Ok(None);
+        };
+        let Some(start) = address.checked_sub(eocd.directory_size + eocd.directory_offset) else {
+            return Ok(None);
+        };
+        let length = eocd.directory_offset
+            + eocd.directory_size
+            + (EOCD_SIZE + eocd.comment_length) as u64;
+        if length > MAX_ARCHIVE_SIZE {
+            return Ok(None);
+        }
+        let mut directory = vec![0u8; eocd.directory_size as usize];
+        let directory_start = start + eocd.directory_offset;
+        if reader::read_into(reader, directory_start, &mut directory).unwrap_or(0)
+            < directory.len()
+        {
+            return Ok(None);
+        }
+        let Some(mut entries) =
+            parse_directory(&directory, eocd.entries, eocd.directory_offset)
+        else {
+            return Ok(None);
+        };
+        for entry in &mut entries {
+            if !check_local(reader, start, eocd.directory_offset, entry) {
+                return Ok(None);
+            }
+        }
+        let entries: Vec<ZipEntry> = entries.into_iter().map(|entry| entry.entry).collect();
+        let kind = archive_kind(&entries);
+
+        let name = archive_file_name(start, kind);
+        let path = self.dir.join(&name);
+        let mut out = BufWriter::new(File::create(&path)?);
+        let mut hash = Sha256::new();
+        let mut buf = vec![0u8; reader::READ_CHUNK_SIZE];
+        let mut size = 0;
+        while size < length {
+            let want = (length - size).min(buf.len() as u64) as usize;
+            let read = reader::read_into(reader, start + size, &mut buf[..want]).unwrap_or(0);
+            out.write_all(&buf[..read])?;
+            hash.update(&buf[..read]);
+            size += read as u64;
+            if read < want {
+                break;
+            }
+        }
+        out.flush()?;
+        drop(out);
+        let sha256 = hash.finish_hex();
+        if !self.seen.insert(sha256.clone()) {
+            fs::remove_file(&path)?;
+            return Ok(None);
+        }
+        Ok(Some(CarvedArchive {
+            file: DumpedFile {
+                start,
+                file: name,
+                size,
+                sha256,
+            },
+            kind,
+            length,
+            entries,
+        }))
+    }
+}
+
+impl RegionVisitor for ZipVisitor<'_> {
+    type Output = CarvedArchive;
+
+    fn visit_chunk(&mut self, _region: &MemoryRegion, address: u64, chunk: &[u8]) {
+        self.records.feed(address, chunk, &mut self.found);
+    }
+
+    fn end_region(
+        &mut self,
+        _region: &MemoryRegion,
+        reader: &mut dyn MemoryReader,
+        sink: &mut dyn ScanSink<CarvedArchive>,
+    ) {
+        self.records.finish(&mut self.found);
+        for hit in std::mem::take(&mut self.found) {
+            if self.failed.is_some() {
+                return;
+            }
+            match self.carve(reader, hit.address) {
+                Ok(Some(archive)) => sink.result(archive),
+                Ok(None) => {}
+                Err(e) => self.failed = Some(e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crc32::crc32;
+    use crate::maps::parse_maps_line;
+    use crate::reader::ProcfsReader;
+
+    /// An archive of stored `files`
+    fn archive(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let (mut zip, mut directory) = (Vec::new(), Vec::new());
+        for (name, data) in files {
+            let offset = zip.len() as u32;
+            let mut fields = vec![0u8; 4];
+            fields.extend(crc32(data).to_le_bytes());
+            fields.extend((data.len() as u32).to_le_bytes());
+            fields.extend((data.len() as u32).to_le_bytes());
+            fields.extend((name.len() as u16).to_le_bytes());
+            fields.extend([0, 0]);
+
+            zip.extend_from_slice(LOCAL_SIGNATURE);
+            zip.extend([20, 0, 0, 0, 0, 0]);
+            zip.extend_from_slice(&fields);
+            zip.extend_from_slice(name.as_bytes());
+            zip.extend_from_slice(data);
+
+            directory.extend_from_slice(CENTRAL_SIGNATURE);
+            directory.extend([20, 0, 20, 0, 0, 0, 0, 0]);
+            directory.extend_from_slice(&fields);
+            directory.extend([0; 10]);
+            directory.extend(offset.to_le_bytes());
+            directory.extend_from_slice(name.as_bytes());
+        }
+        let offset = zip.len() as u32;
+        zip.extend_from_slice(&directory);
+        zip.extend_from_slice(EOCD_SIGNATURE);
+        zip.extend([0; 4]);
+        zip.extend((files.len() as u16).to_le_bytes());
+        zip.extend((files.len() as u16).to_le_bytes());
+        zip.extend((directory.len() as u32).to_le_bytes());
+        zip.extend(offset.to_le_bytes());
+        zip.extend(2u16.to_le_bytes());
+        zip.extend_from_slice(b"hi");
+        zip
+    }
+
+    #[test]
+    fn test_parse_directory() {
+        let zip = archive(&[("a.txt", b"one"), ("b/c.bin", b"two")]);
+        let eocd = parse_eocd(&zip[zip.len() - EOCD_SIZE - 2..]).unwrap();
+        assert_eq!((eocd.entries, eocd.directory_offset, eocd.comment_length), (2, 78, 2));
+        let start = eocd.directory_offset as usize;
+        let directory = &zip[start..start + eocd.directory_size as usize];
+        let entries = parse_directory(directory, 2, eocd.directory_offset).unwrap();
+        assert_eq!(entries[1].entry.name, "b/c.bin");
+        assert_eq!((entries[1].local_offset, entries[1].crc), (38, crc32(b"two")));
+        assert_eq!(parse_directory(directory, 1, eocd.directory_offset), None);
+        assert_eq!(parse_directory(directory, 2, 30), None);
+    }
+
+    #[test]
+    fn test_carves_archives() {
+        let apk = archive(&[("AndroidManifest.xml", b"\x03\x00\x08\x00"), ("x", b"data")]);
+        let zip = archive(&[("notes.txt", b"hello")]);
+        let mut data = vec![0u8; 0x3000];
+        data[0x100..0x100 + apk.len()].copy_from_slice(&apk);
+        // A copy of it, written once, and a damaged one
+        data[0x1001..0x1001 + apk.len()].copy_from_slice(&apk);
+        data[0x1800..0x1800 + zip.len()].copy_from_slice(&zip);
+        data[0x1800 + LOCAL_HEADER_SIZE + 9] ^= 1;
+        // A record with no directory before it
+        data[0x2800..0x2800 + 22].copy_from_slice(&zip[zip.len() - 24..zip.len() - 2]);
+        let start = data.as_ptr() as u64;
+        let end = start + data.len() as u64;
+        let region = parse_maps_line(&format!("{:x}-{:x} rw-p 00000000 00:00 0", start, end));
+
+        let pid = std::process::id() as i32;
+        let dir = std::env::temp_dir().join(format!("zip-test-{}", pid));
+        fs::create_dir_all(&dir).unwrap();
+        let mut reader = ProcfsReader::open(pid).unwrap();
+        let mut scan = scan::Scan::default();
+        let regions = [region.unwrap()];
+        let mut control = ScanControl::new().chunk_size(Some(0x1000));
+        let stopped = carve_archives(&mut reader, &regions, &dir, &mut scan, &mut control);
+
+        assert_eq!(stopped.unwrap(), None);
+        let archives: Vec<_> =
+            scan.results.iter().map(|archive| (archive.file.start - start, archive.kind)).collect();
+        assert_eq!(archives, [(0x100, ArchiveKind::Apk), (0x1800, ArchiveKind::Zip)]);
+        let entries = &scan.results[0].entries;
+        assert_eq!((entries.len(), entries[1].crc_valid), (2, Some(true)));
+        assert_eq!(scan.results[1].entries[0].crc_valid, Some(false));
+        assert_eq!(fs::read(dir.join(&scan.results[0].file.file)).unwrap(), apk);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,399 @@
+//! DEFLATE decoding, and the zlib and gzip formats wrapping it
+//!
+//! Streams are decoded whole from a slice, up to a limit on the bytes they
+//! decode to, so a stream found in memory can be tried without trusting it:
+//! anything that isn't valid DEFLATE fails to decode, and a zlib or gzip
+//! stream must also end with the checksum of what it decoded to. Preset
+//! dictionaries aren't supported.
+
+use crate::adler32::adler32;
+use crate::crc32::crc32;
+
+/// Ways decoding can fail
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InflateError {
+    /// The input isn't a valid stream
+    Invalid,
+    /// The input ends before the stream does
+    Truncated,
+}
+
+/// What a stream decoded to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Inflated {
+    pub data: Vec<u8>,
+    /// Bytes of input the stream took up, including any header and trailer
+    pub consumed: usize,
+    /// Whether the stream was decoded to its end, rather than stopped at the
+    /// output limit
+    pub complete: bool,
+}
+
+/// A zlib stream of `data` in stored, uncompressed, blocks
+pub fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut zlib = vec![0x78, 0x01];
+    let mut blocks = data.chunks(0xffff).peekable();
+    if blocks.peek().is_none() {
+        zlib.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        zlib.push(u8::from(blocks.peek().is_none()));
+        let length = block.len() as u16;
+        zlib.extend(length.to_le_bytes());
+        zlib.extend((!length).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend(adler32(data).to_be_bytes());
+    zlib
+}
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] =
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// The order code length code lengths are given in
+const CODE_LENGTH_ORDER: [usize; 19] =
+    [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+const MAX_CODE_LENGTH: usize = 15;
+
+/// Reads the input a bit at a time, least significant first
+struct Bits<'a> {
+    data: &'a [u8],
+    pos: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl Bits<'_> {
+    fn bits(&mut self, n: u32) -> Result<u32, InflateError> {
+        while self.count < n {
+            let byte = *self.data.get(self.pos).ok_or(InflateError::Truncated)?;
+            self.pos += 1;
+            self.buffer |= u32::from(byte) << self.count;
+            self.count += 8;
+        }
+        let value = self.buffer & ((1u64 << n) - 1) as u32;
+        self.buffer = self.buffer.checked_shr(n).unwrap_or(0);
+        self.count -= n;
+        Ok(value)
+    }
+
+    /// Drop the bits left of the current byte
+    fn align(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+    }
+}
+
+/// A canonical Huffman code, as the number of codes of each length and the
+/// symbols in code order
+struct Huffman {
+    counts: [u16; MAX_CODE_LENGTH + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    /// The code with symbols of `lengths`, if it is neither oversubscribed
+    /// nor incomplete, bar a code of a single symbol
+    fn new(lengths: &[u8]) -> Result<Self, InflateError> {
+        let mut counts = [0u16; MAX_CODE_LENGTH + 1];
+        for &length in lengths {
+            counts[usize::from(length)] += 1;
+        }
+        counts[0] = 0;
+        let mut left = 1i32;
+        for &count in &counts[1..] {
+            left = left * 2 - i32::from(count);
+            if left < 0 {
+                return Err(InflateError::Invalid);
+            }
+        }
+        let used: u16 = counts.iter().sum();
+        if left > 0 && used > 1 {
+            return Err(InflateError::Invalid);
+        }
+
+        let mut offsets = [0u16; MAX_CODE_LENGTH + 2];
+        for length in 1..=MAX_CODE_LENGTH {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0u16; usize::from(used)];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                let offset = &mut offsets[usize::from(length)];
+                symbols[usize::from(*offset)] = symbol as u16;
+                *offset += 1;
+            }
+        }
+        Ok(Huffman { counts, symbols })
+    }
+
+    fn decode(&self, bits: &mut Bits) -> Result<u16, InflateError> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for &count in &self.counts[1..] {
+            code |= bits.bits(1)? as i32;
+            let count = i32::from(count);
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(InflateError::Invalid)
+    }
+}
+
+/// The fixed codes for literals and lengths, and for distances
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [8u8; 288];
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    // Both codes are complete, holding two symbols never used
+    let literals = Huffman::new(&lengths).expect("fixed literal code is complete");
+    let distances = Huffman::new(&[5; 32]).expect("fixed distance code is complete");
+    (literals, distances)
+}
+
+/// Read the dynamic codes at the start of a block
+fn dynamic_codes(bits: &mut Bits) -> Result<(Huffman, Huffman), InflateError> {
+    let literals = bits.bits(5)? as usize + 257;
+    let distances = bits.bits(5)? as usize + 1;
+    let code_lengths = bits.bits(4)? as usize + 4;
+    if literals > 286 || distances > 30 {
+        return Err(InflateError::Invalid);
+    }
+    let mut lengths = [0u8; 19];
+    for &index in &CODE_LENGTH_ORDER[..code_lengths] {
+        lengths[index] = bits.bits(3)? as u8;
+    }
+    let code = Huffman::new(&lengths)?;
+
+    let mut lengths = vec![0u8; literals + distances];
+    let mut i = 0;
+    while i < lengths.len() {
+        let symbol = code.decode(bits)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => (*lengths[..i].last().ok_or(InflateError::Invalid)?, 3 + bits.bits(2)?),
+            17 => (0, 3 + bits.bits(3)?),
+            _ => (0, 11 + bits.bits(7)?),
+        };
+        let end = i + repeat as usize;
+        if end > lengths.len() {
+            return Err(InflateError::Invalid);
+        }
+        lengths[i..end].fill(value);
+        i = end;
+    }
+    // Without an end of block code no block could end
+    if lengths[256] == 0 {
+        return Err(InflateError::Invalid);
+    }
+    Ok((Huffman::new(&lengths[..literals])?, Huffman::new(&lengths[literals..])?))
+}
+
+/// Decode the raw DEFLATE stream at the start of `input`, stopping once it
+/// has decoded to `limit` bytes or more
+pub fn inflate(input: &[u8], limit: usize) -> Result<Inflated, InflateError> {
+    let mut bits = Bits {
+        data: input,
+        pos: 0,
+        buffer: 0,
+        count: 0,
+    };
+    let mut out = Vec::new();
+    loop {
+        let last = bits.bits(1)? == 1;
+        match bits.bits(2)? {
+            0 => {
+                bits.align();
+                let header = input.get(bits.pos..bits.pos + 4).ok_or(InflateError::Truncated)?;
+                let length = u16::from_le_bytes([header[0], header[1]]);
+                if length != !u16::from_le_bytes([header[2], header[3]]) {
+                    return Err(InflateError::Invalid);
+                }
+                let start = bits.pos + 4;
+                let end = start + usize::from(length);
+                out.extend_from_slice(input.get(start..end).ok_or(InflateError::Truncated)?);
+                bits.pos = end;
+            }
+            kind @ (1 | 2) => {
+                let (literals, distances) =
+                    if kind == 1 { fixed_codes() } else { dynamic_codes(&mut bits)? };
+                loop {
+                    let symbol = usize::from(literals.decode(&mut bits)?);
+                    if symbol < 256 {
+                        out.push(symbol as u8);
+                    } else if symbol == 256 {
+                        break;
+                    } else {
+                        let index = symbol - 257;
+                        let &base = LENGTH_BASE.get(index).ok_or(InflateError::Invalid)?;
+                        let length = usize::from(base)
+                            + bits.bits(u32::from(LENGTH_EXTRA[index]))? as usize;
+                        let index = usize::from(distances.decode(&mut bits)?);
+                        let &base = DISTANCE_BASE.get(index).ok_or(InflateError::Invalid)?;
+                        let distance = usize::from(base)
+                            + bits.bits(u32::from(DISTANCE_EXTRA[index]))? as usize;
+                        if distance > out.len() {
+                            return Err(InflateError::Invalid);
+                        }
+                        let from = out.len() - distance;
+                        for i in 0..length {
+                            out.push(out[from + i]);
+                        }
+                    }
+                    if out.len() >= limit {
+                        break;
+                    }
+                }
+            }
+            _ => return Err(InflateError::Invalid),
+        }
+        if out.len() >= limit {
+            return Ok(Inflated {
+                data: out,
+                consumed: bits.pos,
+                complete: false,
+            });
+        }
+        if last {
+            return Ok(Inflated {
+                data: out,
+                consumed: bits.pos,
+                complete: true,
+            });
+        }
+    }
+}
+
+/// Whether `header` starts a zlib stream without a preset dictionary
+pub fn is_zlib_header(header: &[u8]) -> bool {
+    let &[cmf, flg, ..] = header else {
+        return false;
+    };
+    let check = u16::from(cmf) << 8 | u16::from(flg);
+    cmf & 0x0f == 8 && cmf >> 4 <= 7 && flg & 0x20 == 0 && check.is_multiple_of(31)
+}
+
+/// Decode the zlib stream at the start of `input`, its Adler-32 checked if
+/// it is decoded to its end
+pub fn inflate_zlib(input: &[u8], limit: usize) -> Result<Inflated, InflateError> {
+    if !is_zlib_header(input) {
+        return Err(InflateError::Invalid);
+    }
+    let mut inflated = inflate(&input[2..], limit)?;
+    inflated.consumed += 2;
+    if inflated.complete {
+        let at = inflated.consumed;
+        let trailer = input.get(at..at + 4).ok_or(InflateError::Truncated)?;
+        if u32::from_be_bytes(trailer.try_into().unwrap()) != adler32(&inflated.data) {
+            return Err(InflateError::Invalid);
+        }
+        inflated.consumed += 4;
+    }
+    Ok(inflated)
+}
+
+/// The length of the gzip header at the start of `input`
+fn gzip_header_length(input: &[u8]) -> Result<usize, InflateError> {
+    let header = input.get(..10).ok_or(InflateError::Truncated)?;
+    let flags = header[3];
+    if header[..3] != [0x1f, 0x8b, 8] || flags & 0xe0 != 0 {
+        return Err(InflateError::Invalid);
+    }
+    let mut pos = 10;
+    // FEXTRA: a length, then that many bytes
+    if flags & 4 != 0 {
+        let length = input.get(pos..pos + 2).ok_or(InflateError::Truncated)?;
+        pos += 2 + usize::from(u16::from_le_bytes([length[0], length[1]]));
+    }
+    // FNAME and FCOMMENT: NUL-terminated strings
+    for flag in [8, 16] {
+        if flags & flag != 0 {
+            let rest = input.get(pos..).ok_or(InflateError::Truncated)?;
+            pos += rest.iter().position(|&b| b == 0).ok_or(InflateError::Truncated)? + 1;
+        }
+    }
+    // FHCRC: a CRC-16 of the header
+    if flags & 2 != 0 {
+        pos += 2;
+    }
+    Ok(pos)
+}
+
+/// Decode the gzip member at the start of `input`, its CRC-32 and size
+/// checked if it is decoded to its end
+pub fn inflate_gzip(input: &[u8], limit: usize) -> Result<Inflated, InflateError> {
+    let header = gzip_header_length(input)?;
+    let mut inflated = inflate(input.get(header..).ok_or(InflateError::Truncated)?, limit)?;
+    inflated.consumed += header;
+    if inflated.complete {
+        let at = inflated.consumed;
+        let trailer = input.get(at..at + 8).ok_or(InflateError::Truncated)?;
+        let crc = u32::from_le_bytes(trailer[..4].try_into().unwrap());
+        let size = u32::from_le_bytes(trailer[4..].try_into().unwrap());
+        if crc != crc32(&inflated.data) || size != inflated.data.len() as u32 {
+            return Err(InflateError::Invalid);
+        }
+        inflated.consumed += 8;
+    }
+    Ok(inflated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inflate() {
+        // "hello hello hello" by zlib -6: fixed codes with a back reference
+        let zlib = b"\x78\x9c\xcb\x48\xcd\xc9\xc9\x57\xc8\x40\x90\x00\x3a\x2e\x06\x7d";
+        let inflated = inflate_zlib(zlib, 1 << 20).unwrap();
+        assert_eq!(inflated.data, b"hello hello hello");
+        assert_eq!((inflated.consumed, inflated.complete), (zlib.len(), true));
+        assert_eq!(inflate_zlib(&zlib[..12], 1 << 20), Err(InflateError::Truncated));
+        let mut bad = zlib.to_vec();
+        bad[15] ^= 1;
+        assert_eq!(inflate_zlib(&bad, 1 << 20), Err(InflateError::Invalid));
+        let partial = inflate_zlib(zlib, 8).unwrap();
+        assert_eq!((&partial.data[..8], partial.complete), (&b"hello he"[..], false));
+
+        let stored = zlib_stored(&[7; 70000]);
+        let inflated = inflate_zlib(&stored, 1 << 20).unwrap();
+        assert_eq!((inflated.data.len(), inflated.consumed), (70000, stored.len()));
+
+        // The same by gzip -n, with a file name
+        let mut gzip = b"\x1f\x8b\x08\x08\0\0\0\0\0\x03a.txt\0".to_vec();
+        gzip.extend_from_slice(&zlib[2..12]);
+        gzip.extend(crc32(b"hello hello hello").to_le_bytes());
+        gzip.extend(17u32.to_le_bytes());
+        let inflated = inflate_gzip(&gzip, 1 << 20).unwrap();
+        assert_eq!(inflated.data, b"hello hello hello");
+        assert_eq!(inflated.consumed, gzip.len());
+    }
+
+    #[test]
+    fn test_dynamic_codes() {
+        // A short text by zlib -9, in a block with dynamic codes
+        let zlib = b"\x78\xda\x15\xc9\xc9\x09\x00\x00\x08\x03\xc1\x5a\x73\xa9\xfd\x57\x20\xf9\
+            \x0c\x0b\x2b\xcb\xe0\x5e\xcd\x80\x72\xcd\xc8\x7b\x60\x2b\xd3\xdb\xff\x82\
+            \x09\x11\x2b";
+        let inflated = inflate_zlib(zlib, 1 << 20).unwrap();
+        assert_eq!(inflated.data, b"cdcdabghcdabefabcdefabefcdghababefefghcdcdab");
+        assert_eq!((inflated.consumed, inflated.complete), (zlib.len(), true));
+    }
+}